@@ -94,5 +94,5 @@ echo ""
 echo "Troubleshooting:"
 echo "   - Check extension console: chrome://extensions -> Agent Inbox -> 'background page'"
 echo "   - Check native messaging: Look for 'agent-bridge started' message"
-echo "   - Verify database: ls -la ~/.agent-tasks/tasks.db"
+echo "   - Verify database: agent-inbox storage info"
 echo ""
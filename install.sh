@@ -15,9 +15,9 @@ else
     echo "✓ Installed agent-inbox to /usr/local/bin/ (with sudo)"
 fi
 
-# Create data directory
-mkdir -p ~/.agent-tasks/wrappers
-echo "✓ Created ~/.agent-tasks directory"
+# Create config directory
+mkdir -p "${XDG_CONFIG_HOME:-$HOME/.config}/agent-inbox/wrappers"
+echo "✓ Created ${XDG_CONFIG_HOME:-$HOME/.config}/agent-inbox directory"
 
 echo ""
 echo "Installation complete!"
@@ -28,7 +28,7 @@ echo "Configuration file: $RC_FILE"
 echo ""
 
 # Create wrappers directory
-WRAPPER_DIR="$HOME/.agent-tasks/wrappers"
+WRAPPER_DIR="${XDG_CONFIG_HOME:-$HOME/.config}/agent-inbox/wrappers"
 mkdir -p "$WRAPPER_DIR"
 
 # Copy wrapper scripts
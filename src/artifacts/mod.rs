@@ -0,0 +1,214 @@
+//! Per-run archive of what a task left behind
+//!
+//! With `[artifacts] enabled`, every finished run gets a directory under
+//! `~/.local/share/agent-inbox/artifacts/<task_id>/<finished_at>/` with a stable layout:
+//!
+//! - `status.json`: outcome and timing of the run
+//! - `output.log`: the captured output, plus `output.log.<n>.gz` chunks
+//! - `events.json`: the task's activity log
+//! - `diffstat.txt`: `git diff --stat` in the project directory, when it is
+//!   a git work tree with changes
+//! - `session.cast`: the run's recording, with `[capture] record`
+//!
+//! The completion notification points at the directory. Only the newest
+//! `keep_runs` directories of each task are kept.
+//!
+//! Snapshots of stalled processes go to the task's `stalls/` directory; see
+//! [`snapshot`].
+
+pub mod snapshot;
+
+use crate::config::ArtifactsConfig;
+use crate::db::Database;
+use crate::models::Task;
+use crate::monitor::{cast, output};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Contents of `status.json`
+#[derive(Debug, Serialize)]
+struct RunStatus<'a> {
+    task_id: &'a str,
+    title: &'a str,
+    agent_type: &'a str,
+    status: &'a str,
+    exit_code: Option<i32>,
+    started_at: String,
+    finished_at: String,
+    duration_secs: i64,
+    project_path: Option<&'a str>,
+}
+
+/// Root of the archive: `[artifacts] dir`, or ~/.local/share/agent-inbox/artifacts
+pub fn root(config: &ArtifactsConfig) -> PathBuf {
+    config
+        .dir
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| crate::storage::data_dir().join("artifacts"))
+}
+
+/// Archive the run that `task` just finished. Returns the run's directory,
+/// or None when collection is disabled.
+pub fn collect(config: &ArtifactsConfig, db: &Database, task: &Task) -> Result<Option<PathBuf>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    let finished = task.completed_at.unwrap_or(task.updated_at);
+    let task_dir = root(config).join(&task.task_id);
+    let dir = task_dir.join(finished.format("%Y%m%dT%H%M%SZ").to_string());
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let project = task
+        .context
+        .as_ref()
+        .and_then(|c| c.project_path.as_deref());
+    let status = RunStatus {
+        task_id: &task.task_id,
+        title: &task.title,
+        agent_type: &task.agent_type,
+        status: task.status.as_str(),
+        exit_code: task.exit_code,
+        started_at: task.created_at.to_rfc3339(),
+        finished_at: finished.to_rfc3339(),
+        duration_secs: (finished - task.created_at).num_seconds().max(0),
+        project_path: project,
+    };
+    fs::write(
+        dir.join("status.json"),
+        serde_json::to_string_pretty(&status)?,
+    )?;
+    fs::write(
+        dir.join("events.json"),
+        serde_json::to_string_pretty(&db.task_events(&task.task_id)?)?,
+    )?;
+    let log = output::default_log_path(&task.task_id);
+    copy_output(&log, &dir)?;
+    if cast::cast_path(&log).exists() {
+        fs::copy(cast::cast_path(&log), dir.join(RECORDING))?;
+    }
+    if let Some(stat) = project.and_then(|p| diffstat(Path::new(p))) {
+        fs::write(dir.join("diffstat.txt"), stat)?;
+    }
+
+    prune(&task_dir, config.keep_runs);
+    Ok(Some(dir))
+}
+
+/// Name of a run's recording in its directory
+const RECORDING: &str = "session.cast";
+
+/// The newest archived recording of `task_id`
+pub fn latest_recording(config: &ArtifactsConfig, task_id: &str) -> Option<PathBuf> {
+    let mut runs: Vec<PathBuf> = fs::read_dir(root(config).join(task_id))
+        .ok()?
+        .flatten()
+        .map(|e| e.path().join(RECORDING))
+        .filter(|p| p.exists())
+        .collect();
+    runs.sort();
+    runs.pop()
+}
+
+/// Copy the log and any compressed chunks of it
+fn copy_output(log: &Path, dir: &Path) -> Result<()> {
+    if log.exists() {
+        fs::copy(log, dir.join("output.log"))?;
+    }
+    let (Some(parent), Some(name)) = (log.parent(), log.file_name()) else {
+        return Ok(());
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+    for entry in fs::read_dir(parent).into_iter().flatten().flatten() {
+        let file = entry.file_name().to_string_lossy().into_owned();
+        if let Some(chunk) = file.strip_prefix(&prefix).filter(|c| c.ends_with(".gz")) {
+            fs::copy(entry.path(), dir.join(format!("output.log.{}", chunk)))?;
+        }
+    }
+    Ok(())
+}
+
+/// `git diff --stat` against HEAD, if `dir` is a git work tree with changes
+fn diffstat(dir: &Path) -> Option<String> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["diff", "--stat", "HEAD"])
+        .output()
+        .ok()?;
+    let stat = String::from_utf8_lossy(&out.stdout).into_owned();
+    (out.status.success() && !stat.trim().is_empty()).then_some(stat)
+}
+
+/// Remove all but the newest `keep` run directories
+fn prune(task_dir: &Path, keep: usize) {
+    let mut runs: Vec<PathBuf> = fs::read_dir(task_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|e| e.file_name() != snapshot::STALLS)
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+    // Names are timestamps, so they sort by age
+    runs.sort();
+    let excess = runs.len().saturating_sub(keep.max(1));
+    for run in &runs[..excess] {
+        if let Err(e) = fs::remove_dir_all(run) {
+            eprintln!("Failed to remove old artifacts {}: {}", run.display(), e);
+        }
+    }
+}
+
+/// Line added to completion notifications
+pub fn notification_line(dir: &Path) -> String {
+    format!("Artifacts: {}", dir.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EventKind;
+
+    #[test]
+    fn test_collect_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(dir.path().join("tasks.db")).unwrap();
+        let config = ArtifactsConfig {
+            enabled: true,
+            dir: Some(dir.path().join("artifacts").display().to_string()),
+            keep_runs: 1,
+            ..Default::default()
+        };
+        let mut task = Task::new(
+            "t1".into(),
+            "claude_code".into(),
+            "Fix bug".into(),
+            None,
+            None,
+        );
+        db.insert_task(&task).unwrap();
+        db.log_event("t1", EventKind::Started, "pid 1");
+        task.set_exited(Some(2));
+
+        let run = collect(&config, &db, &task).unwrap().unwrap();
+        let status: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(run.join("status.json")).unwrap()).unwrap();
+        assert_eq!(status["exit_code"], 2);
+        assert_eq!(status["status"], "exited");
+        let events = fs::read_to_string(run.join("events.json")).unwrap();
+        assert!(events.contains("pid 1"));
+
+        // A later run replaces it
+        task.completed_at = Some(task.updated_at + chrono::Duration::seconds(5));
+        let next = collect(&config, &db, &task).unwrap().unwrap();
+        assert_ne!(next, run);
+        assert!(!run.exists());
+
+        let disabled = ArtifactsConfig::default();
+        assert!(collect(&disabled, &db, &task).unwrap().is_none());
+    }
+}
@@ -0,0 +1,164 @@
+//! Diagnostic snapshots of stalled processes
+//!
+//! With `[artifacts] stall_snapshot`, a ProcessStalled notification comes
+//! with a look at where the process is stuck, saved to
+//! `<artifacts>/<task_id>/stalls/<taken_at>.txt`:
+//!
+//! - the kernel function it sleeps in (`/proc/<pid>/wchan`) and its state
+//! - its kernel stack (`/proc/<pid>/stack`; usually readable by root only)
+//! - its open files, sockets and pipes (`/proc/<pid>/fd`)
+//! - the output of `[artifacts] profiler`, e.g. `py-spy dump --pid {pid}`
+//!   or `eu-stack -p {pid}`, when one is set
+//!
+//! Everything is read once; nothing attaches to the process except the
+//! profiler, which is killed after [`PROFILER_TIMEOUT`].
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Directory of a task's snapshots, next to its run directories
+pub const STALLS: &str = "stalls";
+
+/// How long the profiler may take before it is killed
+pub const PROFILER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Open files listed before the rest are counted instead
+const MAX_FDS: usize = 64;
+
+/// Snapshot `pid` of `task_id` into the archive at `root`. Returns the
+/// snapshot's path.
+pub fn capture(
+    root: &Path,
+    task_id: &str,
+    pid: i32,
+    profiler: Option<&str>,
+    at: DateTime<Utc>,
+) -> Result<PathBuf> {
+    let dir = root.join(task_id).join(STALLS);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(format!("{}.txt", at.format("%Y%m%dT%H%M%SZ")));
+    fs::write(&path, report(pid, profiler, at))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// The snapshot's text
+fn report(pid: i32, profiler: Option<&str>, at: DateTime<Utc>) -> String {
+    let proc_dir = PathBuf::from(format!("/proc/{}", pid));
+    let mut out = format!("Stall snapshot of pid {} at {}\n", pid, at.to_rfc3339());
+
+    let state = read(&proc_dir.join("status")).map(|status| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("State:"))
+            .map_or_else(String::new, |state| state.trim().to_string())
+    });
+    section(&mut out, "state", state);
+    section(&mut out, "wchan", read(&proc_dir.join("wchan")));
+    section(&mut out, "kernel stack", read(&proc_dir.join("stack")));
+    section(&mut out, "open files", open_files(&proc_dir.join("fd")));
+    if let Some(profiler) = profiler {
+        let command = profiler.replace("{pid}", &pid.to_string());
+        section(&mut out, &command, run(&command));
+    }
+    out
+}
+
+/// Append `title` and its contents, or why they are missing
+fn section(out: &mut String, title: &str, contents: Result<String, String>) {
+    let _ = writeln!(out, "\n== {} ==", title);
+    match contents {
+        Ok(text) if text.trim().is_empty() => out.push_str("(empty)\n"),
+        Ok(text) => {
+            out.push_str(text.trim_end());
+            out.push('\n');
+        }
+        Err(e) => {
+            let _ = writeln!(out, "(unavailable: {})", e);
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| e.to_string())
+}
+
+/// `fd -> target` for each open file, by fd number
+fn open_files(fd_dir: &Path) -> Result<String, String> {
+    let mut fds: Vec<(u32, String)> = fs::read_dir(fd_dir)
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .filter_map(|entry| {
+            let fd = entry.file_name().to_string_lossy().parse().ok()?;
+            let target = fs::read_link(entry.path()).ok()?;
+            Some((fd, target.display().to_string()))
+        })
+        .collect();
+    fds.sort();
+    let mut out = String::new();
+    for (fd, target) in fds.iter().take(MAX_FDS) {
+        let _ = writeln!(out, "{} -> {}", fd, target);
+    }
+    if fds.len() > MAX_FDS {
+        let _ = writeln!(out, "... and {} more", fds.len() - MAX_FDS);
+    }
+    Ok(out)
+}
+
+/// Run the profiler through the shell, under coreutils `timeout` so a
+/// profiler stuck on the stuck process doesn't stall the monitor too
+fn run(command: &str) -> Result<String, String> {
+    let output = Command::new("timeout")
+        .arg(PROFILER_TIMEOUT.as_secs().to_string())
+        .args(["sh", "-c", command])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| e.to_string())?;
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    match output.status.code() {
+        Some(0) => Ok(text),
+        Some(124) => Err(format!("timed out after {}s", PROFILER_TIMEOUT.as_secs())),
+        _ => Err(format!("{}\n{}", output.status, text.trim_end())),
+    }
+}
+
+/// Line added to stall notifications
+pub fn notification_line(path: &Path) -> String {
+    format!("Snapshot: {}", path.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_capture() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid = std::process::id() as i32;
+        let at = Utc.with_ymd_and_hms(2025, 3, 1, 4, 15, 0).unwrap();
+
+        let path = capture(dir.path(), "t1", pid, Some("echo profiled {pid}"), at).unwrap();
+        assert_eq!(path, dir.path().join("t1/stalls/20250301T041500Z.txt"));
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with(&format!(
+            "Stall snapshot of pid {} at 2025-03-01T04:15:00",
+            pid
+        )));
+        assert!(text.contains("== wchan =="));
+        assert!(text.contains("== open files ==\n"));
+        assert!(text.contains(&format!("== echo profiled {} ==\nprofiled {}\n", pid, pid)));
+
+        let text = report(pid, Some("echo broken; exit 3"), at);
+        assert!(text.contains("(unavailable: exit status: 3\nbroken)"));
+    }
+}
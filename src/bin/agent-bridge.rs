@@ -2,9 +2,10 @@
  * Native messaging host for browser extension
  * Receives task updates from extension and writes to agent-inbox database
  */
-
+use agent_inbox::config::Config;
 use agent_inbox::db::{default_db_path, Database};
-use agent_inbox::models::{Task, TaskContext, TaskStatus};
+use agent_inbox::models::{EventKind, Task, TaskContext, TaskStatus};
+use agent_inbox::stats;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -92,11 +93,13 @@ fn process_message(db: &Database, message: IncomingMessage) -> Result<()> {
             // Check if task already exists (for follow-up messages)
             if let Some(mut existing_task) = db.get_task_by_id(&message.task_id)? {
                 // Task exists - update to running (for follow-ups)
+                stats::record_response(db, &mut existing_task, chrono::Utc::now());
                 existing_task.status = TaskStatus::Running;
                 existing_task.updated_at = chrono::Utc::now();
                 existing_task.completed_at = None; // Clear completion timestamp
 
                 db.update_task(&existing_task)?;
+                db.log_event(&message.task_id, EventKind::Resumed, "Follow-up message");
                 eprintln!("Updated existing task to running: {}", message.task_id);
             } else {
                 // Task doesn't exist - create new one
@@ -123,8 +126,17 @@ fn process_message(db: &Database, message: IncomingMessage) -> Result<()> {
                     session_id: None,
                     extra,
                 });
+                let host = Config::load()
+                    .map(|config| config.notify.host)
+                    .unwrap_or_default();
+                task.set_host(&agent_inbox::host::current(&host));
 
                 db.insert_task(&task)?;
+                db.log_event(
+                    &message.task_id,
+                    EventKind::Started,
+                    "From the browser extension",
+                );
                 eprintln!("Created new task: {}", message.task_id);
             }
         }
@@ -133,6 +145,12 @@ fn process_message(db: &Database, message: IncomingMessage) -> Result<()> {
             if let Some(mut task) = db.get_task_by_id(&message.task_id)? {
                 task.complete();
                 db.update_task(&task)?;
+                db.log_event(
+                    &message.task_id,
+                    EventKind::Completed,
+                    "Finished generating",
+                );
+                fire_hooks(db, &task);
 
                 eprintln!("Completed task: {}", message.task_id);
             } else {
@@ -144,6 +162,8 @@ fn process_message(db: &Database, message: IncomingMessage) -> Result<()> {
             if let Some(mut task) = db.get_task_by_id(&message.task_id)? {
                 task.set_exited(None);
                 db.update_task(&task)?;
+                db.log_event(&message.task_id, EventKind::Exited, "Tab closed");
+                fire_hooks(db, &task);
 
                 eprintln!("Task exited: {}", message.task_id);
             } else {
@@ -158,6 +178,14 @@ fn process_message(db: &Database, message: IncomingMessage) -> Result<()> {
     Ok(())
 }
 
+/// Run the `[[hooks]]` matching a task that just finished
+fn fire_hooks(db: &Database, task: &Task) {
+    match Config::load().and_then(|config| agent_inbox::hooks::Hooks::from_config(&config)) {
+        Ok(hooks) => hooks.fire(db, task),
+        Err(e) => eprintln!("Skipping hooks: {:#}", e),
+    }
+}
+
 fn main() -> Result<()> {
     // Note: stderr output goes to browser console/logs
     // For debugging, check: chrome://extensions -> Agent Inbox -> background page -> console
@@ -165,6 +193,7 @@ fn main() -> Result<()> {
     eprintln!("agent-bridge started");
 
     // Open database
+    agent_inbox::db::ensure_data_dir()?;
     let db_path = default_db_path();
     let db = Database::open(&db_path).context("Failed to open database")?;
 
@@ -1,4 +1,9 @@
-use clap::{Parser, Subcommand};
+use agent_inbox::daemon::restart::RestartPolicy;
+use agent_inbox::digest::heat::{self, GroupBy};
+use agent_inbox::models::select::{Selection, State};
+use agent_inbox::models::{Priority, Task, Unattended, UnattendedAction};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "agent-inbox")]
@@ -6,6 +11,23 @@ use clap::{Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+
+    /// Run a command in this terminal as a task, notify when it ends and
+    /// exit with its code: `agent-inbox -- cargo test`
+    #[arg(last = true)]
+    pub run: Vec<String>,
+
+    /// Print results as text for people, or as versioned JSON for scripts
+    /// (list, show, timeline, status, stats, costs, summary, report --by,
+    /// notifications history, storage info, plugins, doctor)
+    #[arg(long, global = true, value_enum, default_value_t = Output::Text)]
+    pub output: Output,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Output {
+    Text,
+    Json,
 }
 
 #[derive(Subcommand)]
@@ -19,6 +41,10 @@ pub enum Commands {
         /// Filter by status: running, completed, exited
         #[arg(short, long)]
         status: Option<String>,
+
+        /// Also show each task's command line and environment
+        #[arg(short, long)]
+        verbose: bool,
     },
 
     /// Show detailed information about a specific task
@@ -27,10 +53,171 @@ pub enum Commands {
         task_id: String,
     },
 
+    /// Show everything that happened to a task, oldest first
+    Timeline {
+        /// Task ID
+        task_id: String,
+
+        /// Print the events as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print a task's captured output; with --follow, keep printing it and
+    /// hold back its attention notifications until you stop
+    Tail {
+        /// Task ID
+        task_id: String,
+
+        /// Keep printing new output until the task finishes or you press Ctrl-C
+        #[arg(short, long)]
+        follow: bool,
+
+        /// Lines of earlier output to print first
+        #[arg(short = 'n', long, default_value = "20")]
+        lines: usize,
+    },
+
+    /// Play back the recording of a task's last run (`[capture] record`)
+    Replay {
+        /// Task ID
+        task_id: String,
+
+        /// Playback speed; 2 plays twice as fast
+        #[arg(long, default_value = "1")]
+        speed: f64,
+
+        /// Longest pause kept, in seconds
+        #[arg(long, default_value = "2")]
+        idle_limit: f64,
+    },
+
+    /// Attach to the tmux pane or Zellij session a task runs in, holding
+    /// back its attention notifications until you detach
+    Attach {
+        /// Task ID
+        task_id: String,
+    },
+
+    /// Bring the tmux pane or Zellij tab a task runs in to the front,
+    /// without attaching to it
+    Focus {
+        /// Task ID
+        task_id: String,
+    },
+
+    /// Type an answer into the tmux pane a task runs in, or approve or deny
+    /// the tool-approval prompt it waits on
+    Respond {
+        /// Task ID
+        task_id: String,
+
+        /// Text to type, followed by Enter
+        #[arg(required_unless_present_any = ["approve", "deny"], conflicts_with_all = ["approve", "deny"])]
+        text: Option<String>,
+
+        /// Approve the pending tool call, command or edit
+        #[arg(long, conflicts_with = "deny")]
+        approve: bool,
+
+        /// Deny the pending tool call, command or edit
+        #[arg(long)]
+        deny: bool,
+    },
+
+    /// Copy the prompt a task waits on, or its last block of output, to the
+    /// clipboard
+    CopyPrompt {
+        /// Task ID
+        task_id: String,
+    },
+
+    /// Leave a note on a task ("waiting on infra team"), or list its notes
+    Note {
+        /// Task ID
+        task_id: String,
+
+        /// Note text; without it, the task's notes are listed
+        text: Option<String>,
+
+        /// Remove all of the task's notes
+        #[arg(long, conflicts_with = "text")]
+        clear: bool,
+    },
+
+    /// Silence notifications for a reason, task or channel while detection
+    /// carries on; without any of them, list the mutes in effect
+    Mute {
+        /// Attention reason kind, e.g. process_stalled
+        #[arg(long)]
+        reason: Option<String>,
+
+        /// Task ID (`*` wildcards)
+        #[arg(long)]
+        task: Option<String>,
+
+        /// Channel name (default: every channel)
+        #[arg(long)]
+        channel: Option<String>,
+
+        /// Lift the mute after this many minutes
+        #[arg(long)]
+        minutes: Option<u64>,
+    },
+
+    /// Lift a mute added with `mute`
+    Unmute {
+        /// Mute ID, as shown by `mute`
+        #[arg(required_unless_present = "all")]
+        id: Option<i64>,
+
+        /// Lift every mute added with `mute`
+        #[arg(long, conflicts_with = "id")]
+        all: bool,
+    },
+
+    /// Mark a notification a false positive, so the detector that raised it
+    /// gets more tolerant for that task
+    Feedback {
+        /// Notification ID, as shown by `notifications history`
+        id: i64,
+
+        /// The notification was a misfire
+        #[arg(long, required_unless_present = "clear")]
+        false_positive: bool,
+
+        /// Take back the task's false-positive marks for the notification's reason
+        #[arg(long, conflicts_with = "false_positive")]
+        clear: bool,
+    },
+
+    /// Mark a task's attention need as seen; the same need isn't notified
+    /// again until the task shows activity or its reason's cooldown passes
+    Ack {
+        #[command(flatten)]
+        select: Select,
+    },
+
+    /// Stop running tasks (SIGTERM) and cancel queued ones
+    Kill {
+        #[command(flatten)]
+        select: Select,
+    },
+
+    /// Hold back tasks' notifications for a while: `snooze 1h --all`
+    Snooze {
+        /// How long: 30m, 1h, 2h30m, 1d (a bare number counts minutes)
+        #[arg(value_parser = parse_duration)]
+        duration: chrono::Duration,
+
+        #[command(flatten)]
+        select: Select,
+    },
+
     /// Clear/archive a task
     Clear {
-        /// Task ID to clear
-        task_id: String,
+        #[command(flatten)]
+        select: Select,
     },
 
     /// Clear all completed and exited tasks
@@ -43,8 +230,64 @@ pub enum Commands {
         force: bool,
     },
 
-    /// Watch tasks in real-time (refreshes every 2 seconds)
-    Watch,
+    /// Watch tasks in real-time (refreshes every 2 seconds), or follow a
+    /// process started elsewhere with --pidfile or --unit
+    Watch {
+        /// Follow the process whose pid is in this file, starting a new run
+        /// whenever the file points at a new process
+        #[arg(long, conflicts_with = "unit")]
+        pidfile: Option<PathBuf>,
+
+        /// Follow the main process of a systemd unit (looked up over D-Bus)
+        #[arg(long)]
+        unit: Option<String>,
+
+        /// Look the unit up in the user's service manager
+        #[arg(long, requires = "unit")]
+        user: bool,
+
+        /// Task ID (default: derived from the pid file or unit name)
+        #[arg(long)]
+        id: Option<String>,
+
+        /// Task title (default: the pid file or unit)
+        #[arg(long)]
+        title: Option<String>,
+
+        /// Captured output to scan (default: ~/.local/state/agent-inbox/logs/<task_id>.log)
+        #[arg(long)]
+        log: Option<PathBuf>,
+    },
+
+    /// Print a compact one-shot summary for desktop status bars
+    Status {
+        /// Output format: waybar, i3blocks, zellij, plain
+        #[arg(short, long, default_value = "plain")]
+        format: String,
+    },
+
+    /// Wait until a task is done or needs you; the exit code says which
+    /// (0 done, 2 failed, 3 needs attention, 7 timed out)
+    Wait {
+        /// Task ID
+        task_id: String,
+
+        /// Give up after this many seconds
+        #[arg(long)]
+        timeout: Option<u64>,
+    },
+
+    /// Wait in the foreground for a process to exit, notify once through
+    /// the configured channels and exit; needs no daemon and stores nothing
+    Oneshot {
+        /// Process to wait for
+        #[arg(long)]
+        pid: i32,
+
+        /// Notification title (default: "Process <pid> exited")
+        #[arg(long)]
+        message: Option<String>,
+    },
 
     /// Manually trigger cleanup of old completed tasks
     Cleanup {
@@ -53,10 +296,193 @@ pub enum Commands {
         retention_secs: i64,
     },
 
-    /// Report task status (internal command used by wrappers)
+    /// List installed plugins from ~/.config/agent-inbox/plugins
+    Plugins,
+
+    /// Serve one plugin to the daemon over stdin and stdout, in the sandbox
+    #[command(hide = true)]
+    PluginHost {
+        /// The plugin's directory
+        dir: PathBuf,
+    },
+
+    /// Check the config, the daemon and how process exits are detected
+    Doctor,
+
+    /// Hand a command to the daemon, optionally to start after another task succeeds
+    Queue {
+        /// Start only once this task has completed successfully
+        #[arg(long)]
+        after: Option<String>,
+
+        /// Task ID (default: a new random ID)
+        #[arg(long)]
+        id: Option<String>,
+
+        /// Task title (default: the command)
+        #[arg(long)]
+        title: Option<String>,
+
+        /// Agent type shown in the task list
+        #[arg(long, default_value = "queued")]
+        agent_type: String,
+
+        /// Working directory (default: current directory)
+        #[arg(long)]
+        cwd: Option<String>,
+
+        #[command(flatten)]
+        options: TaskOptions,
+
+        /// on-failure[:max]: start the command again when it fails, until
+        /// it fails more than max (default 5) times within `[daemon]
+        /// crash_loop_minutes`
+        #[arg(long, value_parser = RestartPolicy::from_str)]
+        restart: Option<RestartPolicy>,
+
+        /// Count against this group's `[groups.<name>] max_concurrent`;
+        /// the daemon holds the task back while the group is full
+        #[arg(long)]
+        group: Option<String>,
+
+        /// Command to run, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// Queue a new run of a task with the same command, working directory,
+    /// environment and settings
+    Rerun {
+        /// Task to run again
+        task_id: String,
+
+        /// ID of the new task (default: a new random ID)
+        #[arg(long)]
+        id: Option<String>,
+    },
+
+    /// Summarize everything that ran recently: completions, failures, waits
+    Summary {
+        /// Hours to look back (default: `[digest] hours`, 24)
+        #[arg(long)]
+        hours: Option<u64>,
+
+        /// Send the summary as a notification instead of printing it
+        #[arg(long)]
+        notify: bool,
+    },
+
+    /// Show how long tasks wait for you after a notification
+    Stats {
+        /// Only count responses from the last N hours (default: everything recorded)
+        #[arg(long)]
+        hours: Option<u64>,
+    },
+
+    /// Show tokens and money spent by agents, per day and per task
+    Costs {
+        /// Days to cover, counting today
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+    },
+
+    /// Run the background daemon (launches `[[schedule]]` commands from the config)
+    Daemon {
+        /// Serve the read-only observer view on the API, as with
+        /// `[daemon] observe = true`
+        #[arg(long)]
+        observe: bool,
+    },
+
+    /// Register a task, reusing it if the ID already exists (safe to repeat)
+    Register {
+        /// Stable task ID; re-registering starts a new run of the same task
+        /// (default: a new random ID)
+        #[arg(long)]
+        id: Option<String>,
+
+        /// Agent type (claude_code, opencode, etc.)
+        agent_type: String,
+
+        /// Task title/description
+        title: String,
+
+        /// Working directory (default: current directory)
+        #[arg(long)]
+        cwd: Option<String>,
+
+        /// Process ID
+        #[arg(long)]
+        pid: Option<i32>,
+
+        /// Parent process ID
+        #[arg(long)]
+        ppid: Option<i32>,
+
+        #[command(flatten)]
+        options: TaskOptions,
+
+        /// Shell command that brings the task's terminal to the front, for
+        /// `focus` and notification actions (default: found from WezTerm's
+        /// or kitty's environment)
+        #[arg(long)]
+        focus_cmd: Option<String>,
+    },
+
+    /// Record or show desktop presence (for swayidle and similar idle hooks)
+    Presence {
+        #[command(subcommand)]
+        action: PresenceAction,
+    },
+
+    /// Review notifications sent earlier
+    Notifications {
+        #[command(subcommand)]
+        action: NotificationsAction,
+    },
+
+    /// Act as the hub other machines forward notifications to
+    Hub {
+        #[command(subcommand)]
+        action: HubAction,
+    },
+
+    /// Inspect where agent-inbox keeps its files
+    Storage {
+        #[command(subcommand)]
+        action: StorageAction,
+    },
+
+    /// Look into what the monitor is doing
+    Debug {
+        #[command(subcommand)]
+        action: DebugAction,
+    },
+
+    /// Measure what monitoring costs
+    Bench {
+        #[command(subcommand)]
+        action: BenchAction,
+    },
+
+    /// Report task status (internal command used by wrappers), or without
+    /// a subcommand, break attention down by project
+    #[command(args_conflicts_with_subcommands = true)]
     Report {
         #[command(subcommand)]
-        action: ReportAction,
+        action: Option<ReportAction>,
+
+        /// Group by project (working directory name), agent or tag
+        #[arg(long, default_value = "project", value_parser = GroupBy::from_str)]
+        by: GroupBy,
+
+        /// Period to cover, e.g. 24h, 7d or 4w
+        #[arg(long, default_value = "7d", value_parser = heat::parse_period)]
+        last: chrono::Duration,
+
+        /// Output format: table, markdown or json
+        #[arg(long, default_value = "table")]
+        format: String,
     },
 
     /// Monitor a process for completion or attention needs (internal command)
@@ -66,9 +492,142 @@ pub enum Commands {
 
         /// Process ID to monitor
         pid: i32,
+
+        /// Captured output to scan for progress (default: ~/.local/state/agent-inbox/logs/<task_id>.log)
+        #[arg(long)]
+        log: Option<PathBuf>,
+
+        /// tmux pane running the task ($TMUX_PANE), used to type auto-responses
+        #[arg(long)]
+        tmux_pane: Option<String>,
+    },
+
+    /// Restart the daemon if it exits (internal command spawned by the
+    /// daemon with `[daemon] watchdog = true`)
+    Watchdog {
+        /// The daemon's process ID
+        pid: i32,
+    },
+
+    /// Set up channels, presence detection and agent wrappers interactively
+    /// and write ~/.config/agent-inbox/config.toml
+    Init,
+
+    /// Show a macOS notification with reply and terminal actions and act on
+    /// the answer (internal command run by the desktop channel)
+    Alert {
+        task_id: String,
+
+        #[arg(long)]
+        title: String,
+
+        #[arg(long)]
+        body: String,
+
+        /// Play a sound
+        #[arg(long)]
+        critical: bool,
+
+        /// Offer Approve and Deny for a tool-approval prompt
+        #[arg(long)]
+        approval: bool,
     },
 }
 
+#[derive(Subcommand)]
+pub enum NotificationsAction {
+    /// List logged notifications, newest first, or send one again
+    History {
+        /// Only notifications that went to, or failed on, this channel
+        #[arg(long)]
+        channel: Option<String>,
+
+        /// Only notifications about this task
+        #[arg(long)]
+        task: Option<String>,
+
+        /// From this day on (YYYY-MM-DD, local time)
+        #[arg(long)]
+        since: Option<chrono::NaiveDate>,
+
+        /// Up to and including this day (YYYY-MM-DD, local time)
+        #[arg(long)]
+        until: Option<chrono::NaiveDate>,
+
+        /// Only notifications at least one channel delivered
+        #[arg(long, conflicts_with = "failed")]
+        delivered: bool,
+
+        /// Only notifications at least one channel failed to deliver
+        #[arg(long)]
+        failed: bool,
+
+        /// Show at most this many
+        #[arg(short, long, default_value_t = 50)]
+        limit: usize,
+
+        /// Send the notification with this ID again
+        #[arg(long, conflicts_with_all = ["channel", "task", "since", "until", "delivered", "failed"])]
+        resend: Option<i64>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum HubAction {
+    /// Send a notification forwarded over SSH, read as JSON from stdin
+    /// (internal command run by the `hub` channel)
+    Receive,
+}
+
+#[derive(Subcommand)]
+pub enum StorageAction {
+    /// Show the config, data and state directories and the space each task
+    /// takes
+    Info,
+}
+
+#[derive(Subcommand)]
+pub enum DebugAction {
+    /// Show the monitor's decisions for a task: detector verdicts, what
+    /// held a notification back and where it was routed
+    Trace {
+        /// Task ID
+        task_id: String,
+
+        /// Keep printing new decisions until the task finishes or you press Ctrl-C
+        #[arg(short, long)]
+        follow: bool,
+
+        /// Lines of earlier trace to print first
+        #[arg(short = 'n', long, default_value = "40")]
+        lines: usize,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BenchAction {
+    /// Time the /proc probes and each detector against synthetic processes
+    Detectors {
+        /// Synthetic processes to watch
+        #[arg(long, default_value_t = 100)]
+        tasks: usize,
+
+        /// Checks of each process
+        #[arg(long, default_value_t = 20)]
+        ticks: usize,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PresenceAction {
+    /// Mark the user as away (locked or idle)
+    Away,
+    /// Mark the user as back at the desktop
+    Active,
+    /// Show the detected presence and the channels notifications would use
+    Status,
+}
+
 #[derive(Subcommand)]
 pub enum ReportAction {
     /// Report task start
@@ -92,6 +651,15 @@ pub enum ReportAction {
         /// Parent process ID
         #[arg(long)]
         ppid: Option<i32>,
+
+        #[command(flatten)]
+        options: TaskOptions,
+
+        /// Shell command that brings the task's terminal to the front, for
+        /// `focus` and notification actions (default: found from WezTerm's
+        /// or kitty's environment)
+        #[arg(long)]
+        focus_cmd: Option<String>,
     },
 
     /// Report task completion
@@ -102,6 +670,10 @@ pub enum ReportAction {
         /// Exit code
         #[arg(long)]
         exit_code: Option<i32>,
+
+        /// Send a desktop notification summarizing the captured output
+        #[arg(long)]
+        notify: bool,
     },
 
     /// Report task is running (generating)
@@ -119,4 +691,173 @@ pub enum ReportAction {
         #[arg(long)]
         exit_code: Option<i32>,
     },
+
+    /// Report that a task needs the user, e.g. from a script detector
+    Attention {
+        /// Task ID
+        task_id: String,
+
+        /// Reason kind: waiting_approval, rate_limited, quota_exceeded,
+        /// merge_conflict, or any kind under `[reasons]`
+        #[arg(long)]
+        kind: String,
+
+        /// Specifics, filled into the kind's message template
+        #[arg(long)]
+        detail: Option<String>,
+
+        /// Send a notification with the kind's severity
+        #[arg(long)]
+        notify: bool,
+    },
+}
+
+/// The tasks a command acts on: one ID, or every task matching the filters
+#[derive(Args, Debug, Clone)]
+pub struct Select {
+    /// Task ID
+    #[arg(
+        required_unless_present_any = ["all", "tag", "group", "state"],
+        conflicts_with_all = ["all", "tag", "group", "state"]
+    )]
+    pub task_id: Option<String>,
+
+    /// Every task, or every one the other filters match
+    #[arg(long)]
+    pub all: bool,
+
+    /// Tasks with this tag; repeat to require several
+    #[arg(long)]
+    pub tag: Vec<String>,
+
+    /// Tasks queued in this group (`queue --group`)
+    #[arg(long)]
+    pub group: Option<String>,
+
+    /// running, queued, completed, exited, attention, or a reason kind
+    /// (stalled, input, approval, password, merge_conflict, ...)
+    #[arg(long, value_parser = State::from_str)]
+    pub state: Option<State>,
+
+    /// List the tasks that would be affected without touching them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+impl Select {
+    pub fn selection(&self) -> Selection {
+        Selection {
+            tags: self.tag.clone(),
+            group: self.group.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// How a task is watched and notified about, for the commands that add one
+#[derive(Args, Debug, Clone)]
+pub struct TaskOptions {
+    /// low, normal, high or critical; critical notifications get past
+    /// Focus and batching, low ones only show up in digests
+    #[arg(long, value_parser = Priority::from_str)]
+    pub priority: Option<Priority>,
+
+    /// Label for `[[rules]]` to match on (repeatable)
+    #[arg(long = "tag")]
+    pub tags: Vec<String>,
+
+    /// What to do when an attention notification goes unanswered for
+    /// --unattended-after minutes: wait, kill or respond:<answer>
+    #[arg(long, value_parser = UnattendedAction::from_str)]
+    pub on_unattended: Option<UnattendedAction>,
+
+    /// Minutes an attention need may go unanswered before
+    /// --on-unattended acts
+    #[arg(long, default_value_t = 30)]
+    pub unattended_after: u64,
+
+    /// Seconds after the start during which the task isn't flagged as
+    /// stalled or blocked (default: `[monitor] startup_grace_secs`)
+    #[arg(long)]
+    pub startup_grace: Option<u64>,
+}
+
+impl TaskOptions {
+    /// Set what was given on `task`
+    pub fn apply(&self, task: &mut Task) {
+        if let Some(priority) = self.priority {
+            task.set_priority(priority);
+        }
+        if !self.tags.is_empty() {
+            task.set_tags(&self.tags);
+        }
+        if let Some(action) = &self.on_unattended {
+            task.set_unattended(&Unattended {
+                action: action.clone(),
+                after_secs: self.unattended_after * 60,
+            });
+        }
+        if let Some(secs) = self.startup_grace {
+            task.set_startup_grace(secs);
+        }
+    }
+}
+
+fn parse_duration(text: &str) -> Result<chrono::Duration, String> {
+    agent_inbox::models::progress::parse_duration(text)
+        .filter(|&secs| secs > 0)
+        .map(chrono::Duration::seconds)
+        .ok_or_else(|| format!("Invalid duration {:?} (e.g. 30m, 1h, 2h30m, 1d)", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrapped_command() {
+        let cli = Cli::try_parse_from(["agent-inbox", "--", "cargo", "test", "--release"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.run, ["cargo", "test", "--release"]);
+        // The command's own flags aren't taken for ours
+        let cli = Cli::try_parse_from(["agent-inbox", "--", "ls", "--output", "x"]).unwrap();
+        assert_eq!(cli.run, ["ls", "--output", "x"]);
+        assert!(Cli::try_parse_from(["agent-inbox", "cargo", "test"]).is_err());
+    }
+
+    #[test]
+    fn test_task_options() {
+        let options = |args: &[&str]| match Cli::try_parse_from(args).unwrap().command {
+            Some(Commands::Register { options, .. }) | Some(Commands::Queue { options, .. }) => {
+                options
+            }
+            Some(Commands::Report {
+                action: Some(ReportAction::Start { options, .. }),
+                ..
+            }) => options,
+            _ => panic!("not a command that adds a task"),
+        };
+        let flags = ["--priority", "high", "--tag", "a", "--tag", "b"];
+        for command in [
+            &["agent-inbox", "register", "shell", "Build"][..],
+            &["agent-inbox", "queue", "--", "make"],
+            &[
+                "agent-inbox",
+                "report",
+                "start",
+                "t1",
+                "shell",
+                "/src",
+                "Build",
+            ],
+        ] {
+            let mut args = command.to_vec();
+            let at = args.iter().position(|&a| a == "--").unwrap_or(args.len());
+            args.splice(at..at, flags);
+            let options = options(&args);
+            assert_eq!(options.priority, Some(Priority::High));
+            assert_eq!(options.tags, ["a", "b"]);
+            assert_eq!(options.unattended_after, 30);
+        }
+    }
 }
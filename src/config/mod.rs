@@ -0,0 +1,1225 @@
+//! User configuration loaded from ~/.config/agent-inbox/config.toml
+//!
+//! Every section is optional; a missing file yields the defaults.
+
+use crate::federation::auth::Scope;
+use crate::notify::Urgency;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub notify: NotifyConfig,
+    pub summary: SummaryConfig,
+    /// Per-plugin settings, keyed by plugin name: `[plugins.<name>]`
+    pub plugins: HashMap<String, serde_json::Value>,
+    /// Commands the daemon launches on a schedule: `[[schedule]]`
+    pub schedule: Vec<ScheduleConfig>,
+    /// Replies typed automatically for known prompts: `[[auto_respond]]`
+    pub auto_respond: Vec<AutoRespondRule>,
+    /// Extra patterns that block auto-responses, on top of the built-in
+    /// deny-list (rm -rf, force push, payments, ...)
+    pub auto_respond_deny: Vec<String>,
+    pub presence: PresenceConfig,
+    pub digest: DigestConfig,
+    pub capture: CaptureConfig,
+    /// Commands and webhooks fired when tasks finish: `[[hooks]]`
+    pub hooks: Vec<HookConfig>,
+    /// What to do when attention is needed under given conditions: `[[rules]]`
+    pub rules: Vec<RuleConfig>,
+    /// Template filters of your own, WebAssembly modules by filter name:
+    /// `[helpers]`. See [`crate::notify::template`].
+    pub helpers: HashMap<String, PathBuf>,
+    pub artifacts: ArtifactsConfig,
+    /// Attention reason kinds, new or overriding built-ins: `[reasons.<kind>]`
+    pub reasons: HashMap<String, ReasonConfig>,
+    pub monitor: MonitorConfig,
+    pub reminders: RemindersConfig,
+    pub error_burst: ErrorBurstConfig,
+    pub looping: LoopingConfig,
+    pub costs: CostsConfig,
+    pub daemon: DaemonConfig,
+    /// Concurrency limits of task groups: `[groups.<name>]`
+    pub groups: HashMap<String, GroupConfig>,
+    pub environment: EnvironmentConfig,
+    pub file_activity: FileActivityConfig,
+    pub storage: StorageConfig,
+    pub classifier: ClassifierConfig,
+    pub time: TimeConfig,
+    pub sandbox: SandboxConfig,
+}
+
+/// Which channels receive notifications and how to reach them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    /// Channel names, e.g. ["desktop", "slack"]
+    pub channels: Vec<String>,
+    pub slack: Option<SlackConfig>,
+    pub telegram: Option<TelegramConfig>,
+    pub email: Option<EmailConfig>,
+    pub matrix: Option<MatrixConfig>,
+    pub pagerduty: Option<PagerDutyConfig>,
+    pub opsgenie: Option<OpsgenieConfig>,
+    pub twilio: Option<TwilioConfig>,
+    pub gotify: Option<GotifyConfig>,
+    /// Forward to a hub machine instead of notifying from here
+    pub hub: Option<HubConfig>,
+    /// Pick channels by whether the user is at the desktop
+    pub routing: Option<RoutingConfig>,
+    pub redact: RedactConfig,
+    pub batch: BatchConfig,
+    pub delivery: DeliveryConfig,
+    /// Notifications held back from some or all channels: `[[notify.mute]]`
+    pub mute: Vec<MuteConfig>,
+    pub host: HostConfig,
+    /// Language of notification text per channel: `[notify.locale]`
+    pub locale: LocaleConfig,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            channels: vec!["desktop".to_string()],
+            slack: None,
+            telegram: None,
+            email: None,
+            matrix: None,
+            pagerduty: None,
+            opsgenie: None,
+            twilio: None,
+            gotify: None,
+            hub: None,
+            routing: None,
+            redact: RedactConfig::default(),
+            batch: BatchConfig::default(),
+            delivery: DeliveryConfig::default(),
+            mute: Vec::new(),
+            host: HostConfig::default(),
+            locale: LocaleConfig::default(),
+        }
+    }
+}
+
+/// Locale of each channel's notifications, e.g. `slack = "de"`; the
+/// catalogs are `~/.config/agent-inbox/locales/<locale>.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LocaleConfig {
+    /// Locale of channels not listed (default "en", the built-in text)
+    pub default: Option<String>,
+    #[serde(flatten)]
+    pub channels: HashMap<String, String>,
+}
+
+/// How this machine is named in notifications and task records:
+/// `[notify.host]`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HostConfig {
+    /// Short name used for `{host}` instead of the hostname
+    pub nickname: Option<String>,
+    /// Use the machine's tailscale name when there is no nickname
+    pub tailscale: bool,
+    /// Start every notification title with the host, as in "laptop: ..."
+    pub in_title: bool,
+}
+
+/// The hub that owns notification dispatch for this machine:
+/// `[notify.hub]`. Set either `url` or `ssh`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HubConfig {
+    /// The hub daemon's `[daemon] listen` address, e.g. "http://hub.lan:7470"
+    pub url: Option<String>,
+    /// SSH destination ("me@hub") to run `agent-inbox hub receive` on
+    pub ssh: Option<String>,
+    /// agent-inbox binary on the hub, for the SSH transport
+    pub remote_command: Option<String>,
+    /// Name of this machine in forwarded notifications (default: the
+    /// `[notify.host]` name)
+    pub node: Option<String>,
+    /// Local channels used while the hub is unreachable
+    pub fallback: Vec<String>,
+    /// Environment variable holding a `control` token for the hub's API
+    pub token_env: Option<String>,
+    /// PEM CA that signed the hub's certificate (default: public roots)
+    pub ca: Option<String>,
+    /// PEM client certificate and key, for hubs that require mutual TLS
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+}
+
+/// Silence notifications without disabling detection; a notification is
+/// muted when it matches every field set here
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MuteConfig {
+    /// Attention reason kind, e.g. "process_stalled"
+    pub reason: Option<String>,
+    /// Task ID (`*` wildcards)
+    pub task: Option<String>,
+    /// Channel name; unset mutes every channel
+    pub channel: Option<String>,
+}
+
+/// Coalesce notifications that arrive together: `[notify.batch]`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BatchConfig {
+    /// How long the first notification waits for others to join it; 0
+    /// sends every notification on its own
+    pub window_secs: u64,
+}
+
+/// Per-channel delivery limits: `[notify.delivery]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DeliveryConfig {
+    /// How long a notification waits for any one channel
+    pub timeout_secs: u64,
+    /// Failed deliveries in a row that switch a channel off
+    pub failure_threshold: u32,
+    /// How long a switched-off channel is skipped before it is tried again
+    pub cooldown_secs: u64,
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 10,
+            failure_threshold: 3,
+            cooldown_secs: 300,
+        }
+    }
+}
+
+/// Secrets removed from notifications and hook payloads: `[notify.redact]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RedactConfig {
+    /// Use the built-in patterns (API keys, bearer tokens, AWS keys, ...)
+    pub builtin: bool,
+    /// Extra regexes; a `(?P<secret>...)` group limits what is replaced
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactConfig {
+    fn default() -> Self {
+        Self {
+            builtin: true,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// Slack: an incoming webhook, or a bot token and channel for the Web
+/// API, which threads each task's messages
+#[derive(Debug, Clone, Deserialize)]
+pub struct SlackConfig {
+    #[serde(default)]
+    pub webhook_url: String,
+    /// Environment variable holding a bot token (`xoxb-...`) with `chat:write`
+    pub bot_token_env: Option<String>,
+    /// Channel ID the bot posts to, e.g. "C0123456789"
+    pub channel: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelegramConfig {
+    pub chat_id: String,
+    #[serde(default = "default_telegram_token_env")]
+    pub bot_token_env: String,
+    /// Whether the phone makes a sound, by attention reason kind or
+    /// "completed" / "failed": `[notify.telegram.reasons]`. Others are
+    /// silent when low urgency.
+    #[serde(default)]
+    pub reasons: HashMap<String, TelegramSound>,
+}
+
+/// Telegram can't pick a sound per message, only whether to play one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TelegramSound {
+    Silent,
+    Loud,
+}
+
+fn default_telegram_token_env() -> String {
+    "TELEGRAM_BOT_TOKEN".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmailConfig {
+    pub to: String,
+    #[serde(default = "default_email_from")]
+    pub from: String,
+    #[serde(default = "default_sendmail")]
+    pub sendmail: String,
+}
+
+fn default_email_from() -> String {
+    "agent-inbox@localhost".to_string()
+}
+
+fn default_sendmail() -> String {
+    "sendmail".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MatrixConfig {
+    pub homeserver: String,
+    pub room_id: String,
+    #[serde(default = "default_matrix_token_env")]
+    pub access_token_env: String,
+}
+
+fn default_matrix_token_env() -> String {
+    "MATRIX_ACCESS_TOKEN".to_string()
+}
+
+/// PagerDuty Events API v2: `[notify.pagerduty]`
+#[derive(Debug, Clone, Deserialize)]
+pub struct PagerDutyConfig {
+    /// Environment variable holding the integration's routing key
+    #[serde(default = "default_pagerduty_key_env")]
+    pub routing_key_env: String,
+}
+
+fn default_pagerduty_key_env() -> String {
+    "PAGERDUTY_ROUTING_KEY".to_string()
+}
+
+/// Opsgenie Alert API: `[notify.opsgenie]`
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpsgenieConfig {
+    /// Environment variable holding the API integration key
+    #[serde(default = "default_opsgenie_key_env")]
+    pub api_key_env: String,
+    /// "https://api.eu.opsgenie.com" for EU accounts
+    #[serde(default = "default_opsgenie_url")]
+    pub api_url: String,
+}
+
+fn default_opsgenie_key_env() -> String {
+    "OPSGENIE_API_KEY".to_string()
+}
+
+fn default_opsgenie_url() -> String {
+    "https://api.opsgenie.com".to_string()
+}
+
+/// Self-hosted Gotify push server: `[notify.gotify]`
+#[derive(Debug, Clone, Deserialize)]
+pub struct GotifyConfig {
+    /// Server URL, e.g. "https://push.example.com"
+    pub url: String,
+    /// Environment variable holding the application token
+    #[serde(default = "default_gotify_token_env")]
+    pub app_token_env: String,
+    /// Gotify priority (0-10) per urgency
+    #[serde(default)]
+    pub priority: GotifyPriority,
+    /// Gotify priority by attention reason kind or "completed" / "failed",
+    /// over the one for the urgency: `[notify.gotify.reasons]`
+    #[serde(default)]
+    pub reasons: HashMap<String, u8>,
+}
+
+fn default_gotify_token_env() -> String {
+    "GOTIFY_APP_TOKEN".to_string()
+}
+
+/// Gotify priority of each urgency: `[notify.gotify.priority]`. Clients
+/// stay silent below 4 and show a banner from 8 on.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GotifyPriority {
+    pub low: u8,
+    pub normal: u8,
+    pub critical: u8,
+}
+
+impl Default for GotifyPriority {
+    fn default() -> Self {
+        Self {
+            low: 2,
+            normal: 5,
+            critical: 8,
+        }
+    }
+}
+
+/// Twilio SMS or WhatsApp messages: `[notify.twilio]`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TwilioConfig {
+    pub account_sid: String,
+    /// Environment variable holding the account's auth token
+    #[serde(default = "default_twilio_token_env")]
+    pub auth_token_env: String,
+    /// Twilio number to send from, e.g. "+15551234567"
+    pub from: String,
+    /// Phone number to text
+    pub to: String,
+    /// Send through WhatsApp instead of SMS
+    #[serde(default)]
+    pub whatsapp: bool,
+    /// Least urgent notification worth a text
+    #[serde(default = "default_twilio_min_urgency")]
+    pub min_urgency: Urgency,
+    /// Texts allowed in any hour; more are refused
+    #[serde(default = "default_twilio_max_per_hour")]
+    pub max_per_hour: usize,
+    /// Texts allowed in any 24 hours
+    #[serde(default = "default_twilio_max_per_day")]
+    pub max_per_day: usize,
+    /// SMS segments a text may take, each billed as one message
+    #[serde(default = "default_twilio_max_segments")]
+    pub max_segments: usize,
+}
+
+fn default_twilio_token_env() -> String {
+    "TWILIO_AUTH_TOKEN".to_string()
+}
+
+fn default_twilio_min_urgency() -> Urgency {
+    Urgency::Critical
+}
+
+fn default_twilio_max_per_hour() -> usize {
+    3
+}
+
+fn default_twilio_max_per_day() -> usize {
+    10
+}
+
+fn default_twilio_max_segments() -> usize {
+    1
+}
+
+/// Channels used while the user is at the desktop and while away:
+/// `[notify.routing]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RoutingConfig {
+    /// Channels while the session is unlocked and active, e.g. ["desktop"]
+    pub present: Vec<String>,
+    /// Channels while locked or idle, e.g. ["telegram"]
+    pub away: Vec<String>,
+    /// Channels while a Focus / Do Not Disturb mode is on; default: `present`
+    pub focus: Vec<String>,
+    /// Input idle time after which the user counts as away
+    pub away_after_secs: u64,
+    /// "auto", "hook", "logind", "macos", "multiplexer" or "xprintidle"
+    pub provider: String,
+}
+
+impl Default for RoutingConfig {
+    fn default() -> Self {
+        Self {
+            present: vec!["desktop".to_string()],
+            away: Vec::new(),
+            focus: Vec::new(),
+            away_after_secs: 300,
+            provider: "auto".to_string(),
+        }
+    }
+}
+
+/// A command the daemon runs on a cron-like schedule
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleConfig {
+    /// Unique name; the task ID is `schedule-<name>`
+    pub name: String,
+    /// Cron expression ("0 3 * * *") or shortcut ("@daily"), in local time
+    pub cron: String,
+    /// Shell command, run with `sh -c`
+    pub command: String,
+    /// Working directory (default: the daemon's)
+    pub cwd: Option<String>,
+    /// Task title (default: the name)
+    pub title: Option<String>,
+    #[serde(default = "default_schedule_agent_type")]
+    pub agent_type: String,
+    /// Notify when the run finishes
+    #[serde(default = "default_true")]
+    pub notify: bool,
+    /// Labels for `[[rules]]` to match on, like `--tag`
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_schedule_agent_type() -> String {
+    "scheduled".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Reply automatically when a task's output ends with a matching prompt
+#[derive(Debug, Clone, Deserialize)]
+pub struct AutoRespondRule {
+    pub name: String,
+    /// Regex matched against the last line of output
+    pub pattern: String,
+    /// Text typed in reply (Enter is added)
+    pub response: String,
+    /// Task IDs or agent types the rule is allowed for (`*` wildcards);
+    /// a rule with no entries never fires
+    #[serde(default)]
+    pub tasks: Vec<String>,
+}
+
+/// Run a command or POST a webhook when a task reaches a terminal state.
+/// The task record is the JSON payload (on stdin for commands).
+#[derive(Debug, Clone, Deserialize)]
+pub struct HookConfig {
+    pub name: String,
+    /// States that fire the hook: "completed", "exited"
+    #[serde(default = "default_hook_on")]
+    pub on: Vec<String>,
+    /// Task IDs or agent types (`*` wildcards); empty means every task
+    #[serde(default)]
+    pub tasks: Vec<String>,
+    /// Shell command, run with `sh -c`
+    pub command: Option<String>,
+    /// URL the task record is POSTed to
+    pub url: Option<String>,
+}
+
+fn default_hook_on() -> Vec<String> {
+    vec!["completed".to_string(), "exited".to_string()]
+}
+
+/// Actions taken when a task needs attention under all of `when`'s
+/// conditions. Every matching rule applies, in order.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RuleConfig {
+    pub name: String,
+    #[serde(default)]
+    pub when: RuleWhen,
+    #[serde(default)]
+    pub then: RuleThen,
+}
+
+/// Conditions of a `[[rules]]` entry; unset ones always hold
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RuleWhen {
+    /// Attention reason key ("process_stalled", "error_burst", ...; `*`
+    /// wildcards)
+    pub reason: Option<String>,
+    /// A `--tag` the task carries
+    pub tag: Option<String>,
+    /// The task has been running at least this long
+    pub min_runtime_secs: Option<u64>,
+    /// Local time of day, "22:00-07:00"; may wrap past midnight
+    pub hours: Option<String>,
+    /// Regex matched against the last lines of output
+    pub output: Option<String>,
+    /// WebAssembly module whose `matches` export decides, relative to the
+    /// config directory. See [`crate::monitor::rules`].
+    pub script: Option<PathBuf>,
+    /// The reason came up at least this often for the task, this time
+    /// included, within `within_secs`
+    pub repeats: Option<usize>,
+    pub within_secs: u64,
+}
+
+impl Default for RuleWhen {
+    fn default() -> Self {
+        Self {
+            reason: None,
+            tag: None,
+            min_runtime_secs: None,
+            hours: None,
+            output: None,
+            script: None,
+            repeats: None,
+            within_secs: 600,
+        }
+    }
+}
+
+/// Actions of a `[[rules]]` entry
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RuleThen {
+    /// Send the notification to these channels only, past routing and
+    /// batching
+    pub channels: Vec<String>,
+    /// Raise the notification to critical
+    pub escalate: bool,
+    /// Text typed into the task (Enter is added)
+    pub respond: Option<String>,
+    /// Terminate the task's process
+    pub kill: bool,
+    /// Shell command, run with `sh -c` and the task in the environment
+    pub run: Option<String>,
+    /// Send the notification at all; off for rules that only act
+    pub notify: bool,
+}
+
+impl Default for RuleThen {
+    fn default() -> Self {
+        Self {
+            channels: Vec::new(),
+            escalate: false,
+            respond: None,
+            kill: false,
+            run: None,
+            notify: true,
+        }
+    }
+}
+
+/// When to hold back notifications because the user is already looking
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PresenceConfig {
+    /// Skip non-critical notifications while the user is typing in the
+    /// task's terminal
+    pub suppress_when_watching: bool,
+    /// How recent terminal input must be to count as watching
+    pub watching_secs: u64,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            suppress_when_watching: true,
+            watching_secs: 60,
+        }
+    }
+}
+
+/// Periodic summary of everything that ran
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DigestConfig {
+    /// When the daemon sends the digest, e.g. "0 8 * * *"; unset = never
+    pub cron: Option<String>,
+    /// Hours covered by the digest
+    pub hours: u64,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            cron: None,
+            hours: 24,
+        }
+    }
+}
+
+/// Limits on output captured from tasks the daemon starts: `[capture]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    /// Output held in memory while the log catches up; beyond this it is
+    /// dropped rather than blocking the task
+    pub buffer_bytes: usize,
+    /// Log size at which it is compressed to a chunk and restarted
+    pub chunk_bytes: u64,
+    /// Compressed chunks kept per task
+    pub keep_chunks: u64,
+    /// Also record the output with its timing, for `agent-inbox replay`
+    pub record: bool,
+    /// Size at which a recording stops
+    pub record_max_bytes: u64,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            buffer_bytes: 4 * 1024 * 1024,
+            chunk_bytes: 8 * 1024 * 1024,
+            keep_chunks: 5,
+            record: false,
+            record_max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// How one kind of attention reason is presented; unset fields keep the
+/// built-in value (or the defaults for a new kind)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ReasonConfig {
+    /// "low", "normal" or "critical"
+    pub severity: Option<Urgency>,
+    /// Prefixed to notification titles
+    pub icon: Option<String>,
+    /// Message text; `{detail}` is replaced by the reason's specifics, and
+    /// takes filters such as `{detail|truncate:80}`
+    pub template: Option<String>,
+    /// Minutes an acknowledged need of this kind isn't notified again, unless
+    /// the task shows activity in between; 0 never holds one back
+    pub ack_cooldown_minutes: Option<u64>,
+}
+
+/// How the monitor watches tasks: `[monitor]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MonitorConfig {
+    /// Seconds after a task starts during which stalls and blocked reads
+    /// aren't flagged; prompts found in the output are flagged regardless.
+    /// `--startup-grace` overrides it per task
+    pub startup_grace_secs: u64,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            startup_grace_secs: 10,
+        }
+    }
+}
+
+/// Attention when errors pile up in a running task's output: `[error_burst]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ErrorBurstConfig {
+    /// Error lines within the window that count as a burst; 0 disables
+    pub threshold: usize,
+    pub window_secs: u64,
+}
+
+impl Default for ErrorBurstConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 10,
+            window_secs: 120,
+        }
+    }
+}
+
+/// Attention when a running task's output keeps repeating: `[looping]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LoopingConfig {
+    /// Near-identical blocks of output that count as a loop; 0 disables
+    pub repeats: usize,
+    /// Share of lines two blocks must have in common, 0.0 to 1.0
+    pub similarity: f64,
+}
+
+impl Default for LoopingConfig {
+    fn default() -> Self {
+        Self {
+            repeats: 5,
+            similarity: 0.8,
+        }
+    }
+}
+
+/// Repeat notifications while a task keeps waiting: `[reminders]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RemindersConfig {
+    /// Reminders at most per attention need; 0 disables
+    pub max: u32,
+    /// Wait before the first reminder
+    pub first_secs: u64,
+    /// Each wait is this many times the one before
+    pub factor: f64,
+    /// Attention reason keys reminded about
+    pub reasons: Vec<String>,
+    /// Schedules of single channels: `[reminders.channels.<name>]`
+    pub channels: HashMap<String, ReminderChannelConfig>,
+}
+
+impl Default for RemindersConfig {
+    fn default() -> Self {
+        Self {
+            max: 0,
+            first_secs: 300,
+            factor: 3.0,
+            reasons: vec!["waiting_for_input".to_string()],
+            channels: HashMap::new(),
+        }
+    }
+}
+
+/// One channel's reminder schedule; unset fields follow `[reminders]`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ReminderChannelConfig {
+    pub max: Option<u32>,
+    pub first_secs: Option<u64>,
+    pub factor: Option<f64>,
+}
+
+/// What is recorded about a task's process at registration: `[environment]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EnvironmentConfig {
+    /// Environment variables shown with the task; keep secrets out of this list
+    pub vars: Vec<String>,
+}
+
+impl Default for EnvironmentConfig {
+    fn default() -> Self {
+        Self {
+            vars: [
+                "MODEL",
+                "ANTHROPIC_MODEL",
+                "OPENAI_MODEL",
+                "VIRTUAL_ENV",
+                "CONDA_DEFAULT_ENV",
+            ]
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
+        }
+    }
+}
+
+/// Count file changes in a task's working directory as activity:
+/// `[file_activity]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FileActivityConfig {
+    pub enabled: bool,
+    /// Directory names never watched
+    pub ignore: Vec<String>,
+}
+
+impl Default for FileActivityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ignore: [".git", "node_modules", "target", ".venv", "__pycache__"]
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+/// Ask a model whether a task really waits on the user before notifying:
+/// `[classifier]`. See [`crate::monitor::classify`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ClassifierConfig {
+    pub enabled: bool,
+    /// Reason kinds whose detection is double-checked; the others are
+    /// certain enough
+    pub reasons: Vec<String>,
+    /// Lines of output sent along
+    pub lines: usize,
+    /// Model calls allowed per hour, across all monitors; past it the
+    /// heuristics decide alone
+    pub max_per_hour: usize,
+    pub llm: LlmConfig,
+}
+
+impl Default for ClassifierConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reasons: vec![
+                "waiting_for_input".to_string(),
+                "process_stalled".to_string(),
+            ],
+            lines: 30,
+            max_per_hour: 20,
+            llm: LlmConfig::default(),
+        }
+    }
+}
+
+/// How timestamps read in notifications and reports: `[time]`. See
+/// [`crate::datetime`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TimeConfig {
+    /// "local" for the machine's zone, "UTC", or an IANA name such as
+    /// "Europe/Berlin"
+    pub timezone: String,
+    /// Language of month and day names in `format`, e.g. "de_DE"
+    pub locale: String,
+    /// strftime format of absolute times
+    pub format: String,
+    /// Times up to this many hours old read as "12m ago"; 0 shows every
+    /// time in full
+    pub relative_hours: u64,
+}
+
+impl Default for TimeConfig {
+    fn default() -> Self {
+        Self {
+            timezone: "local".to_string(),
+            locale: "en_US".to_string(),
+            format: "%Y-%m-%d %H:%M".to_string(),
+            relative_hours: 24,
+        }
+    }
+}
+
+/// Where the database, output and artifacts are kept: `[storage]`. See
+/// [`crate::storage`] for the defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// Database and artifacts (default: ~/.local/share/agent-inbox)
+    pub data_dir: Option<PathBuf>,
+    /// Captured output, recordings and presence (default:
+    /// ~/.local/state/agent-inbox)
+    pub state_dir: Option<PathBuf>,
+}
+
+/// Confinement of `[[rules]]` `run` commands, plugins and WebAssembly
+/// scripts: `[sandbox]`. See [`crate::sandbox`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SandboxConfig {
+    pub enabled: bool,
+    /// CPU seconds a process may use before it is killed
+    pub cpu_secs: u64,
+    /// Address space a process may map, and memory a script may grow to
+    pub memory_mb: u64,
+    /// Instructions, roughly, a WebAssembly script may run per call; 0 for
+    /// no limit
+    pub fuel: u64,
+    /// Let them reach the network
+    pub network: bool,
+    /// Paths they may read beyond the system directories
+    pub read: Vec<PathBuf>,
+    /// Run nothing where the system can't confine it fully, rather than
+    /// confine it as far as the system allows
+    pub strict: bool,
+    /// Confine `[[hooks]]` commands too. Off by default: hooks are the
+    /// user's own commands, there to write files and reach the network.
+    pub hooks: bool,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cpu_secs: 10,
+            memory_mb: 1024,
+            fuel: 10_000_000,
+            network: false,
+            read: Vec::new(),
+            strict: false,
+            hooks: false,
+        }
+    }
+}
+
+/// A group of queued tasks: `[groups.<name>]`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GroupConfig {
+    /// Tasks of the group the daemon runs at once; 0 for no limit
+    pub max_concurrent: usize,
+}
+
+/// Background service settings: `[daemon]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Serve the HTTP API (forwarded notifications, task listings) on this
+    /// address, e.g. "127.0.0.1:7470"
+    pub listen: Option<String>,
+    /// Bearer tokens accepted by the API: `[[daemon.tokens]]`. Without any,
+    /// the API only binds to loopback (unless mutual TLS is on).
+    pub tokens: Vec<TokenConfig>,
+    /// Serve HTTPS: `[daemon.tls]`
+    pub tls: Option<TlsConfig>,
+    /// Run a watchdog process that restarts the daemon if it crashes
+    pub watchdog: bool,
+    /// Serve the read-only observer view (`/v1/observe/...`) for
+    /// teammates following a run
+    pub observe: bool,
+    /// Serve `org.adlrocha.AgentNotifications` on the D-Bus session bus
+    /// (needs the `dbus` feature)
+    pub dbus: bool,
+    /// Pick up event files dropped in ~/.local/share/agent-inbox/inbox/
+    pub inbox: bool,
+    /// Notifications when the daemon starts and stops: `[daemon.status]`
+    pub status: DaemonStatusConfig,
+    /// Window for `queue --restart`: more failures than allowed within it
+    /// stop the restarts
+    pub crash_loop_minutes: u64,
+    /// What the daemon allows itself: `[daemon.limits]`
+    pub limits: LimitsConfig,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            listen: None,
+            tokens: Vec::new(),
+            tls: None,
+            watchdog: false,
+            observe: false,
+            dbus: false,
+            inbox: false,
+            status: DaemonStatusConfig::default(),
+            crash_loop_minutes: 10,
+            limits: LimitsConfig::default(),
+        }
+    }
+}
+
+/// Resources the daemon keeps itself to; 0 lifts a limit
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Memory for the output buffers of all the daemon's runs together, in
+    /// MB
+    pub max_buffer_mb: u64,
+    /// Size of the artifacts archive, in MB; the oldest runs go beyond it
+    pub max_artifacts_mb: u64,
+    /// CPU the daemon may use, in percent of one core; beyond it, it polls
+    /// less often
+    pub max_cpu_percent: f64,
+    /// Notify the first time each limit is hit
+    pub warn: bool,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_buffer_mb: 64,
+            max_artifacts_mb: 2048,
+            max_cpu_percent: 10.0,
+            warn: true,
+        }
+    }
+}
+
+/// "Monitoring started/stopped" notifications
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DaemonStatusConfig {
+    pub enabled: bool,
+    /// Title sent on start; `{resumed}` is the number of tasks taken over.
+    /// Both titles can also use `{host}`, `{hostname}` and `{user}`.
+    pub started: String,
+    /// Title sent on stop; `{running}` is the number of runs left unwatched
+    pub stopped: String,
+}
+
+impl Default for DaemonStatusConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            started: "Monitoring started (resumed {resumed} tasks)".to_string(),
+            stopped: "Monitoring stopped ({running} tasks still running, you will NOT be notified)"
+                .to_string(),
+        }
+    }
+}
+
+/// One API token
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenConfig {
+    /// Shown in logs
+    pub name: String,
+    /// Environment variable holding the token
+    pub token_env: String,
+    /// "observe" (the observer view only), "read" (task listings) or
+    /// "control" (also forwarding notifications)
+    #[serde(default)]
+    pub scope: Scope,
+}
+
+/// Server certificate and, for mutual TLS, the CA client certificates
+/// must be signed by
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain
+    pub cert: String,
+    /// PEM private key
+    pub key: String,
+    /// Require client certificates signed by this PEM CA
+    pub client_ca: Option<String>,
+}
+
+/// Spend tracking: `[costs]`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CostsConfig {
+    /// Notify when the day's spend across all tasks crosses this
+    pub daily_budget_usd: Option<f64>,
+}
+
+/// Per-run archive of output, outcome and diffstat: `[artifacts]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ArtifactsConfig {
+    pub enabled: bool,
+    /// Archive root (default: ~/.local/share/agent-inbox/artifacts)
+    pub dir: Option<String>,
+    /// Run directories kept per task
+    pub keep_runs: usize,
+    /// Save a diagnostic snapshot of processes that stall
+    pub stall_snapshot: bool,
+    /// Command added to stall snapshots, with `{pid}` replaced, e.g.
+    /// "py-spy dump --pid {pid}"
+    pub profiler: Option<String>,
+}
+
+impl Default for ArtifactsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: None,
+            keep_runs: 20,
+            stall_snapshot: false,
+            profiler: None,
+        }
+    }
+}
+
+/// How completion notifications summarize captured output
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SummaryConfig {
+    /// "heuristic", "llm" or "none"
+    pub summarizer: String,
+    /// Maximum number of lines in the summary
+    pub max_lines: usize,
+    /// Bytes read from the end of the captured output
+    pub tail_bytes: u64,
+    pub llm: LlmConfig,
+    /// Title and body of completion notifications: `[summary.completion]`
+    pub completion: CompletionConfig,
+}
+
+impl Default for SummaryConfig {
+    fn default() -> Self {
+        Self {
+            summarizer: "heuristic".to_string(),
+            max_lines: 3,
+            tail_bytes: 64 * 1024,
+            llm: LlmConfig::default(),
+            completion: CompletionConfig::default(),
+        }
+    }
+}
+
+/// Templates for completion notifications. Placeholders: `{icon}`,
+/// `{agent}`, `{title}`, `{status}` ("Done in 1h42m (waited on you for
+/// 23m), exit 0"), `{outcome}`, `{severity}`, `{duration}`, `{waited}`
+/// (and `{duration_secs}`, `{waited_secs}`), `{exit}`, `{tests}`,
+/// `{summary}`, `{notes}`, and the task's `{host}`, `{hostname}` and
+/// `{user}`. Placeholders take filters such as `{summary|code}`; see
+/// [`template`](crate::notify::template).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CompletionConfig {
+    pub title: String,
+    pub body: String,
+    /// Append the notes left on the task during the run to the body
+    pub notes: bool,
+    /// Icons by outcome: "success", "failure", "warning" (exited fine but
+    /// tests failed), or "finished" when the exit status is unknown. Unset
+    /// outcomes keep the built-in icon.
+    pub icons: HashMap<String, String>,
+}
+
+impl Default for CompletionConfig {
+    fn default() -> Self {
+        Self {
+            title: "{icon} [{agent}] {title}".to_string(),
+            body: "{status}\n{summary}".to_string(),
+            notes: false,
+            icons: HashMap::new(),
+        }
+    }
+}
+
+/// Connection settings for an optional LLM API
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LlmConfig {
+    /// "anthropic" or "openai" (any OpenAI-compatible endpoint, including local servers)
+    pub provider: String,
+    /// Override the provider's default URL
+    pub endpoint: Option<String>,
+    pub model: String,
+    /// Environment variable holding the API key (never stored in the config file)
+    pub api_key_env: String,
+    pub timeout_secs: u64,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            provider: "anthropic".to_string(),
+            endpoint: None,
+            model: "claude-3-5-haiku-latest".to_string(),
+            api_key_env: "ANTHROPIC_API_KEY".to_string(),
+            timeout_secs: 20,
+        }
+    }
+}
+
+impl Config {
+    /// Load the default config file, falling back to defaults when absent
+    pub fn load() -> Result<Self> {
+        Self::load_from(default_config_path())
+    }
+
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config {}", path.display()))
+    }
+}
+
+pub fn default_config_path() -> PathBuf {
+    crate::storage::config_dir().join("config.toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_missing_config_uses_defaults() {
+        let config = Config::load_from("/nonexistent/config.toml").unwrap();
+        assert_eq!(config.summary.summarizer, "heuristic");
+        assert_eq!(config.summary.max_lines, 3);
+        assert_eq!(config.notify.channels, vec!["desktop"]);
+    }
+
+    #[test]
+    fn test_partial_config() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            "[summary]\nsummarizer = \"llm\"\n\n[summary.llm]\nmodel = \"local\"\n\n[plugins.foo]\nlevel = 2\n\n\
+             [[schedule]]\nname = \"nightly\"\ncron = \"@daily\"\ncommand = \"make lint\""
+        )
+        .unwrap();
+
+        let config = Config::load_from(file.path()).unwrap();
+        assert_eq!(config.summary.summarizer, "llm");
+        assert_eq!(config.summary.llm.model, "local");
+        assert_eq!(config.summary.llm.api_key_env, "ANTHROPIC_API_KEY");
+        assert_eq!(config.summary.max_lines, 3);
+        assert_eq!(config.plugins["foo"]["level"], 2);
+        assert_eq!(config.schedule[0].name, "nightly");
+        assert_eq!(config.schedule[0].agent_type, "scheduled");
+        assert!(config.schedule[0].notify);
+    }
+}
@@ -0,0 +1,325 @@
+//! Token and cost usage reported by agents
+//!
+//! Agents print what they spent: Claude Code's exit summary ("Total cost:
+//! $0.55", "Usage: 12.3k input, 1.1k output"), aider's per-message line
+//! ("Tokens: 2.5k sent, 234 received. Cost: $0.01 message, $0.20 session.")
+//! and codex's "Token usage:" line. The monitor picks these out of the
+//! captured output and stores the increments in the task database, so
+//! spend can be totalled per task and per day.
+//!
+//! With `[costs] daily_budget_usd`, the monitor notifies once when the day's
+//! spend across all tasks crosses the budget.
+
+use crate::db::Database;
+use anyhow::Result;
+use chrono::NaiveDate;
+use regex::Regex;
+use std::ops::AddAssign;
+use std::sync::OnceLock;
+
+/// Tokens and dollars spent
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl Usage {
+    pub fn is_zero(&self) -> bool {
+        self.input_tokens == 0 && self.output_tokens == 0 && self.cost_usd == 0.0
+    }
+
+    /// "$0.42 (12.3k in, 1.1k out)"
+    pub fn describe(&self) -> String {
+        format!(
+            "{} ({} in, {} out)",
+            format_cost(self.cost_usd),
+            format_tokens(self.input_tokens),
+            format_tokens(self.output_tokens)
+        )
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cost_usd += other.cost_usd;
+    }
+}
+
+/// One usage line. Cumulative reports give the session's running total;
+/// the others what a single request cost.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+    pub cost_usd: Option<f64>,
+    pub cumulative: bool,
+}
+
+/// Parse a usage line printed by a known agent
+pub fn parse_line(line: &str) -> Option<Report> {
+    static AIDER: OnceLock<Regex> = OnceLock::new();
+    static TOTAL_COST: OnceLock<Regex> = OnceLock::new();
+    static USAGE: OnceLock<Regex> = OnceLock::new();
+    static CODEX: OnceLock<Regex> = OnceLock::new();
+
+    let aider = AIDER.get_or_init(|| {
+        Regex::new(
+            r"Tokens: (?P<sent>[\d.,]+[kKmM]?) sent(?:, [\d.,]+[kKmM]? cache \w+)*, (?P<received>[\d.,]+[kKmM]?) received\.(?: Cost: \$(?P<cost>[\d.]+) message)?",
+        )
+        .unwrap()
+    });
+    if let Some(caps) = aider.captures(line) {
+        return Some(Report {
+            input_tokens: parse_tokens(&caps["sent"]),
+            output_tokens: parse_tokens(&caps["received"]),
+            cost_usd: caps.name("cost").and_then(|c| c.as_str().parse().ok()),
+            cumulative: false,
+        });
+    }
+
+    let total =
+        TOTAL_COST.get_or_init(|| Regex::new(r"^\s*Total cost:\s+\$(?P<cost>[\d.]+)").unwrap());
+    if let Some(caps) = total.captures(line) {
+        return Some(Report {
+            cost_usd: caps["cost"].parse().ok(),
+            cumulative: true,
+            ..Default::default()
+        });
+    }
+
+    let usage = USAGE.get_or_init(|| {
+        Regex::new(
+            r"^\s*Usage:\s+(?P<input>[\d.,]+[kKmM]?) input, (?P<output>[\d.,]+[kKmM]?) output",
+        )
+        .unwrap()
+    });
+    if let Some(caps) = usage.captures(line) {
+        return Some(Report {
+            input_tokens: parse_tokens(&caps["input"]),
+            output_tokens: parse_tokens(&caps["output"]),
+            cumulative: true,
+            ..Default::default()
+        });
+    }
+
+    let codex = CODEX.get_or_init(|| {
+        Regex::new(
+            r"Token usage: total=[\d,]+ input=(?P<input>[\d,]+).*? output=(?P<output>[\d,]+)",
+        )
+        .unwrap()
+    });
+    let caps = codex.captures(line)?;
+    Some(Report {
+        input_tokens: parse_tokens(&caps["input"]),
+        output_tokens: parse_tokens(&caps["output"]),
+        cumulative: true,
+        ..Default::default()
+    })
+}
+
+/// "12,345", "2.5k", "1.2M"
+fn parse_tokens(text: &str) -> Option<u64> {
+    let text = text.replace(',', "");
+    let (number, scale) = match text.chars().last()? {
+        'k' | 'K' => (&text[..text.len() - 1], 1e3),
+        'm' | 'M' => (&text[..text.len() - 1], 1e6),
+        _ => (text.as_str(), 1.0),
+    };
+    let value: f64 = number.parse().ok()?;
+    Some((value * scale).round() as u64)
+}
+
+/// Turns a task's usage lines into increments, whichever way they are
+/// reported
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    /// Running totals last reported by the session
+    session: Usage,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What `line` adds to the task's usage, if it is a usage line
+    pub fn observe(&mut self, line: &str) -> Option<Usage> {
+        let report = parse_line(line)?;
+        if !report.cumulative {
+            return Some(Usage {
+                input_tokens: report.input_tokens.unwrap_or(0),
+                output_tokens: report.output_tokens.unwrap_or(0),
+                cost_usd: report.cost_usd.unwrap_or(0.0),
+            });
+        }
+
+        // A total lower than the last one belongs to a new session
+        let mut added = Usage::default();
+        if let Some(total) = report.input_tokens {
+            added.input_tokens = total
+                .checked_sub(self.session.input_tokens)
+                .unwrap_or(total);
+            self.session.input_tokens = total;
+        }
+        if let Some(total) = report.output_tokens {
+            added.output_tokens = total
+                .checked_sub(self.session.output_tokens)
+                .unwrap_or(total);
+            self.session.output_tokens = total;
+        }
+        if let Some(total) = report.cost_usd {
+            added.cost_usd = if total >= self.session.cost_usd {
+                total - self.session.cost_usd
+            } else {
+                total
+            };
+            self.session.cost_usd = total;
+        }
+        (!added.is_zero()).then_some(added)
+    }
+}
+
+/// The calendar day in the `[time]` zone, which daily totals and budgets
+/// go by
+pub fn today() -> NaiveDate {
+    crate::datetime::times().today()
+}
+
+/// Store `usage` for `task_id` under today's date. Returns the day's total
+/// spend before and after it.
+pub fn record(db: &Database, task_id: &str, usage: &Usage) -> Result<(f64, f64)> {
+    let day = today();
+    let before = db.usage_on(day)?.cost_usd;
+    db.record_usage(task_id, day, usage)?;
+    Ok((before, before + usage.cost_usd))
+}
+
+/// Whether going from `before` to `after` crossed `budget`
+pub fn crossed(budget: f64, before: f64, after: f64) -> bool {
+    before < budget && after >= budget
+}
+
+/// "$0.42", "$12.30"
+pub fn format_cost(usd: f64) -> String {
+    format!("${:.2}", usd)
+}
+
+/// "950", "12.3k", "1.2M"
+pub fn format_tokens(tokens: u64) -> String {
+    match tokens {
+        0..=999 => tokens.to_string(),
+        1_000..=999_999 => format!("{:.1}k", tokens as f64 / 1e3),
+        _ => format!("{:.1}M", tokens as f64 / 1e6),
+    }
+}
+
+/// Lines of the `costs` report: per day, then the costliest tasks
+pub fn report_lines(db: &Database, days: u32, budget: Option<f64>) -> Result<Vec<String>> {
+    let since = today() - chrono::Duration::days(days.saturating_sub(1) as i64);
+    let daily = db.usage_by_day(since)?;
+    if daily.is_empty() {
+        return Ok(vec!["No token or cost usage recorded yet".to_string()]);
+    }
+
+    let mut lines = Vec::new();
+    let mut total = Usage::default();
+    for (day, usage) in &daily {
+        lines.push(format!("{}  {}", day, usage.describe()));
+        total += *usage;
+    }
+    if daily.len() > 1 {
+        lines.push(format!("Total       {}", total.describe()));
+    }
+    if let Some(budget) = budget {
+        let spent = daily
+            .iter()
+            .find(|(day, _)| *day == today())
+            .map_or(0.0, |(_, usage)| usage.cost_usd);
+        lines.push(format!(
+            "Today: {} of the {} budget",
+            format_cost(spent),
+            format_cost(budget)
+        ));
+    }
+
+    let tasks = db.usage_by_task(since)?;
+    if !tasks.is_empty() {
+        lines.push(String::new());
+        lines.push("Costliest tasks:".to_string());
+        for (task_id, usage) in tasks.iter().take(5) {
+            let name = match db.get_task_by_id(task_id)? {
+                Some(task) => format!("[{}] {}", task.agent_type, task.title),
+                None => task_id.clone(),
+            };
+            lines.push(format!("  {}  {}", usage.describe(), name));
+        }
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker_handles_both_report_styles() {
+        let mut tracker = UsageTracker::new();
+        let aider = "Tokens: 2.5k sent, 234 received. Cost: $0.01 message, $0.20 session.";
+        assert_eq!(
+            tracker.observe(aider),
+            Some(Usage {
+                input_tokens: 2500,
+                output_tokens: 234,
+                cost_usd: 0.01
+            })
+        );
+        assert!(tracker.observe("Applied edit to src/main.rs").is_none());
+
+        // Claude Code's exit summary reports session totals
+        assert_eq!(
+            tracker
+                .observe("Total cost:            $0.55")
+                .unwrap()
+                .cost_usd,
+            0.55
+        );
+        let usage = tracker
+            .observe(
+                "Usage:                 12.3k input, 1,100 output, 0 cache read, 0 cache write",
+            )
+            .unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens), (12_300, 1_100));
+        let more = tracker.observe("Total cost:            $0.80").unwrap();
+        assert!((more.cost_usd - 0.25).abs() < 1e-9);
+        // Repeating a total adds nothing
+        assert!(tracker.observe("Total cost:            $0.80").is_none());
+
+        let codex = "Token usage: total=15,000 input=12,000 (+ 8,000 cached) output=3,000";
+        assert_eq!(tracker.observe(codex).unwrap().output_tokens, 1_900);
+    }
+
+    #[test]
+    fn test_daily_totals_and_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(dir.path().join("tasks.db")).unwrap();
+        let spend = |cost_usd| Usage {
+            input_tokens: 1000,
+            output_tokens: 100,
+            cost_usd,
+        };
+
+        assert_eq!(record(&db, "a", &spend(12.0)).unwrap(), (0.0, 12.0));
+        let (before, after) = record(&db, "b", &spend(9.0)).unwrap();
+        assert!(crossed(20.0, before, after));
+        assert!(!crossed(20.0, after, after + 1.0));
+
+        let lines = report_lines(&db, 7, Some(20.0)).unwrap();
+        assert_eq!(lines[0], format!("{}  $21.00 (2.0k in, 200 out)", today()));
+        assert_eq!(lines[1], "Today: $21.00 of the $20.00 budget");
+        assert_eq!(lines[4], "  $12.00 (1.0k in, 100 out)  a");
+    }
+}
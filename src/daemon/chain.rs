@@ -0,0 +1,216 @@
+//! Dependency chains: tasks queued to start after another task succeeds
+//!
+//! `agent-inbox queue --after <task> -- <command>` stores the command in the
+//! new task's metadata and leaves it Queued. The daemon starts it once the
+//! predecessor completes successfully, or marks it skipped if the
+//! predecessor fails. Only the end of a chain notifies, with the whole
+//! pipeline in the title.
+
+use super::restart::RestartPolicy;
+use crate::db::Database;
+use crate::models::{EventKind, Task, TaskStatus};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Command the daemon starts for a queued task
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QueuedRun {
+    /// Shell command, run with `sh -c`
+    pub command: String,
+    pub cwd: Option<String>,
+    /// Task that must complete successfully first
+    pub after: Option<String>,
+    /// Start the command again when it fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RestartPolicy>,
+    /// Group whose concurrency limit the task counts against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Set in the command's environment, e.g. a re-run's recorded
+    /// variables
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+impl QueuedRun {
+    pub fn of(task: &Task) -> Option<Self> {
+        task.metadata
+            .as_ref()?
+            .get("queued")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    fn attach(&self, task: &mut Task) -> Result<()> {
+        task.metadata
+            .get_or_insert_with(Default::default)
+            .insert("queued".to_string(), serde_json::to_value(self)?);
+        Ok(())
+    }
+}
+
+/// Store `task` as Queued with the command the daemon should run
+pub fn queue_task(db: &Database, mut task: Task, run: QueuedRun) -> Result<Task> {
+    if let Some(after) = &run.after {
+        db.get_task_by_id(after)?
+            .with_context(|| format!("Task not found: {}", after))?;
+    }
+
+    task.status = TaskStatus::Queued;
+    task.pid = None;
+    let message = match &run.after {
+        Some(after) => format!("After {}: {}", after, run.command),
+        None => run.command.clone(),
+    };
+    run.attach(&mut task)?;
+    task.id = Some(db.insert_task(&task)?);
+    db.log_event(&task.task_id, EventKind::Queued, message);
+    Ok(task)
+}
+
+/// What to do with a queued task given its predecessor
+#[derive(Debug, PartialEq)]
+pub enum Gate {
+    Start,
+    Wait,
+    /// Predecessor failed or vanished; the reason is shown on the task
+    Skip(String),
+}
+
+pub fn gate(after: Option<&str>, predecessor: Option<&Task>) -> Gate {
+    let Some(after) = after else {
+        return Gate::Start;
+    };
+    let Some(predecessor) = predecessor else {
+        return Gate::Skip(format!("Skipped: {} no longer exists", after));
+    };
+
+    match predecessor.status {
+        TaskStatus::Queued | TaskStatus::Running => Gate::Wait,
+        _ if succeeded(predecessor) => Gate::Start,
+        _ => Gate::Skip(format!("Skipped: {} did not succeed", predecessor.title)),
+    }
+}
+
+fn succeeded(task: &Task) -> bool {
+    match task.status {
+        TaskStatus::Completed => task.exit_code.unwrap_or(0) == 0,
+        TaskStatus::Exited => task.exit_code == Some(0),
+        _ => false,
+    }
+}
+
+/// Tasks before and after `task` in its chain, oldest first. Branches are
+/// followed along their first queued successor.
+pub fn neighbours(db: &Database, task: &Task) -> Result<(Vec<Task>, Vec<Task>)> {
+    let all = db.list_tasks(None)?;
+    let after_of = |t: &Task| QueuedRun::of(t).and_then(|run| run.after);
+
+    let mut upstream = Vec::new();
+    let mut cursor = after_of(task);
+    while let Some(id) = cursor {
+        let Some(prev) = all.iter().find(|t| t.task_id == id) else {
+            break;
+        };
+        // Guard against cycles from hand-edited metadata
+        if upstream.iter().any(|t: &Task| t.task_id == prev.task_id) {
+            break;
+        }
+        cursor = after_of(prev);
+        upstream.insert(0, prev.clone());
+    }
+
+    let mut downstream = Vec::new();
+    let mut current = task.task_id.clone();
+    while let Some(next) = all
+        .iter()
+        .find(|t| after_of(t).as_deref() == Some(current.as_str()))
+    {
+        if downstream.iter().any(|t: &Task| t.task_id == next.task_id) {
+            break;
+        }
+        current = next.task_id.clone();
+        downstream.push(next.clone());
+    }
+
+    Ok((upstream, downstream))
+}
+
+/// "lint → tests ✗ → deploy-agent"
+pub fn describe(upstream: &[Task], task: &Task, downstream: &[Task], failed: bool) -> String {
+    let mut steps: Vec<String> = upstream.iter().map(|t| t.title.clone()).collect();
+    steps.push(if failed {
+        format!("{} ✗", task.title)
+    } else {
+        task.title.clone()
+    });
+    steps.extend(downstream.iter().map(|t| t.title.clone()));
+    steps.join(" → ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn task(id: &str) -> Task {
+        Task::new(
+            id.to_string(),
+            "queued".to_string(),
+            id.to_string(),
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_gate() {
+        assert_eq!(gate(None, None), Gate::Start);
+        assert!(matches!(gate(Some("lint"), None), Gate::Skip(_)));
+
+        let mut lint = task("lint");
+        assert_eq!(gate(Some("lint"), Some(&lint)), Gate::Wait);
+        lint.complete();
+        assert_eq!(gate(Some("lint"), Some(&lint)), Gate::Start);
+        lint.set_exited(Some(0));
+        assert_eq!(gate(Some("lint"), Some(&lint)), Gate::Start);
+        lint.set_exited(Some(2));
+        assert_eq!(
+            gate(Some("lint"), Some(&lint)),
+            Gate::Skip("Skipped: lint did not succeed".to_string())
+        );
+    }
+
+    #[test]
+    fn test_chain_neighbours() {
+        let file = NamedTempFile::new().unwrap();
+        let db = Database::open(file.path()).unwrap();
+        let run = |after: Option<&str>| QueuedRun {
+            command: "true".to_string(),
+            cwd: None,
+            after: after.map(str::to_string),
+            restart: None,
+            group: None,
+            env: Default::default(),
+        };
+
+        let lint = queue_task(&db, task("lint"), run(None)).unwrap();
+        let tests = queue_task(&db, task("tests"), run(Some("lint"))).unwrap();
+        queue_task(&db, task("deploy-agent"), run(Some("tests"))).unwrap();
+        assert!(queue_task(&db, task("orphan"), run(Some("missing"))).is_err());
+
+        let (up, down) = neighbours(&db, &tests).unwrap();
+        assert_eq!(
+            describe(&up, &tests, &down, false),
+            "lint → tests → deploy-agent"
+        );
+        assert_eq!(
+            describe(&up, &tests, &down, true),
+            "lint → tests ✗ → deploy-agent"
+        );
+
+        let (up, down) = neighbours(&db, &lint).unwrap();
+        assert!(up.is_empty());
+        assert_eq!(down.len(), 2);
+    }
+}
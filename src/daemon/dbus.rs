@@ -0,0 +1,243 @@
+//! D-Bus service on the session bus: `[daemon] dbus = true`
+//!
+//! Lets desktop environments, GNOME Shell extensions and scripts (`busctl`,
+//! `gdbus`) work with tasks without the HTTP API. The daemon owns the name
+//! `org.adlrocha.AgentNotifications` and serves this interface, of the
+//! same name, at `/org/adlrocha/AgentNotifications`:
+//!
+//! - `List(b all) → a(ssss)`: ID, title, status and attention reason (empty
+//!   when none) of the running tasks, or of every task
+//! - `Ack(s task_id) → b`: clear the task's attention need, as seen, like
+//!   `agent-inbox ack`; false when it had none
+//! - `Snooze(s task_id, u minutes) → x`: mute the task's notifications for a
+//!   while; returns the mute's ID, for `agent-inbox unmute`
+//! - signals `Attention(s task_id, s title, s reason)` and
+//!   `AttentionCleared(s task_id, s message)`
+//!
+//! The signals follow the task database, so they cover attention needs
+//! raised by every monitor, not only those of tasks the daemon started.
+
+use crate::db::Database;
+use crate::models::{EventKind, TaskStatus};
+use crate::notify::Mute;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use zbus::blocking::Connection;
+use zbus::fdo;
+use zbus::object_server::SignalEmitter;
+
+/// Bus name and interface
+pub const NAME: &str = "org.adlrocha.AgentNotifications";
+
+/// Object path of the service
+pub const PATH: &str = "/org/adlrocha/AgentNotifications";
+
+/// How often new events are checked for signals
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// One task as `List` returns it: ID, title, status, attention reason
+pub type ListedTask = (String, String, String, String);
+
+struct Service {
+    db_path: PathBuf,
+}
+
+impl Service {
+    fn db(&self) -> fdo::Result<Database> {
+        Database::open(&self.db_path).map_err(failed)
+    }
+}
+
+fn failed(e: anyhow::Error) -> fdo::Error {
+    fdo::Error::Failed(format!("{:#}", e))
+}
+
+#[zbus::interface(name = "org.adlrocha.AgentNotifications")]
+impl Service {
+    fn list(&self, all: bool) -> fdo::Result<Vec<ListedTask>> {
+        list(&self.db()?, all).map_err(failed)
+    }
+
+    fn ack(&self, task_id: &str) -> fdo::Result<bool> {
+        ack(&self.db()?, task_id).map_err(failed)
+    }
+
+    fn snooze(&self, task_id: &str, minutes: u32) -> fdo::Result<i64> {
+        snooze(&self.db()?, task_id, minutes).map_err(failed)
+    }
+
+    #[zbus(signal)]
+    async fn attention(
+        emitter: &SignalEmitter<'_>,
+        task_id: &str,
+        title: &str,
+        reason: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn attention_cleared(
+        emitter: &SignalEmitter<'_>,
+        task_id: &str,
+        message: &str,
+    ) -> zbus::Result<()>;
+}
+
+pub fn list(db: &Database, all: bool) -> Result<Vec<ListedTask>> {
+    let tasks = db.list_tasks(if all { None } else { Some(TaskStatus::Running) })?;
+    Ok(tasks
+        .into_iter()
+        .map(|task| {
+            let status = task.status.as_str().to_string();
+            (
+                task.task_id,
+                task.title,
+                status,
+                task.attention_reason.unwrap_or_default(),
+            )
+        })
+        .collect())
+}
+
+/// Clear `task_id`'s attention need; false when it had none
+pub fn ack(db: &Database, task_id: &str) -> Result<bool> {
+    let mut task = db
+        .get_task_by_id(task_id)?
+        .with_context(|| format!("Task not found: {}", task_id))?;
+    if !task.acknowledge(Utc::now()) {
+        return Ok(false);
+    }
+    db.update_task(&task)?;
+    db.log_event(
+        task_id,
+        EventKind::AttentionCleared,
+        "Acknowledged over D-Bus",
+    );
+    Ok(true)
+}
+
+/// Mute `task_id` for `minutes`; returns the mute's ID
+pub fn snooze(db: &Database, task_id: &str, minutes: u32) -> Result<i64> {
+    anyhow::ensure!(
+        db.get_task_by_id(task_id)?.is_some(),
+        "Task not found: {}",
+        task_id
+    );
+    let mute = Mute::new(None, Some(task_id), None)
+        .until(Utc::now() + chrono::Duration::minutes(minutes as i64));
+    db.insert_mute(&mute)
+}
+
+/// Take the bus name and serve the interface, signalling attention events
+/// from a background thread
+pub fn spawn(db_path: PathBuf) -> Result<()> {
+    let connection = zbus::blocking::connection::Builder::session()?
+        .name(NAME)?
+        .serve_at(
+            PATH,
+            Service {
+                db_path: db_path.clone(),
+            },
+        )?
+        .build()
+        .with_context(|| format!("Failed to register {} on the session bus", NAME))?;
+    thread::spawn(move || {
+        if let Err(e) = signal_events(&connection, &db_path) {
+            eprintln!("D-Bus signals stopped: {:#}", e);
+        }
+    });
+    Ok(())
+}
+
+fn signal_events(connection: &Connection, db_path: &Path) -> Result<()> {
+    let db = Database::open(db_path)?;
+    let service = connection.object_server().interface::<_, Service>(PATH)?;
+    let emitter = service.signal_emitter();
+    let mut last = db.last_event_id()?;
+    loop {
+        // A busy database is tried again on the next poll
+        let events = db.events_after(last).unwrap_or_else(|e| {
+            eprintln!("Failed to read events to signal: {:#}", e);
+            Vec::new()
+        });
+        for (id, event) in events {
+            last = id;
+            let sent = match event.kind {
+                EventKind::Attention => {
+                    let title = match db.get_task_by_id(&event.task_id) {
+                        Ok(task) => task.map(|task| task.title).unwrap_or_default(),
+                        Err(e) => {
+                            eprintln!("Failed to read {}: {:#}", event.task_id, e);
+                            String::new()
+                        }
+                    };
+                    zbus::block_on(Service::attention(
+                        emitter,
+                        &event.task_id,
+                        &title,
+                        &event.message,
+                    ))
+                }
+                EventKind::AttentionCleared | EventKind::Resumed => zbus::block_on(
+                    Service::attention_cleared(emitter, &event.task_id, &event.message),
+                ),
+                _ => Ok(()),
+            };
+            if let Err(e) = sent {
+                eprintln!(
+                    "Failed to signal {} event of {}: {}",
+                    event.kind.as_str(),
+                    event.task_id,
+                    e
+                );
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Task;
+
+    #[test]
+    fn test_list_ack_and_snooze() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(dir.path().join("tasks.db")).unwrap();
+        let mut task = Task::new(
+            "build".to_string(),
+            "shell".to_string(),
+            "Build".to_string(),
+            None,
+            None,
+        );
+        task.attention_reason = Some("Waiting for input".to_string());
+        db.insert_task(&task).unwrap();
+        let last = db.last_event_id().unwrap();
+
+        assert_eq!(
+            list(&db, false).unwrap(),
+            vec![(
+                "build".to_string(),
+                "Build".to_string(),
+                "running".to_string(),
+                "Waiting for input".to_string()
+            )]
+        );
+        assert!(ack(&db, "build").unwrap());
+        assert!(!ack(&db, "build").unwrap());
+        assert!(ack(&db, "missing").is_err());
+        assert_eq!(list(&db, true).unwrap()[0].3, "");
+        let events = db.events_after(last).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].1.kind, EventKind::AttentionCleared);
+
+        let id = snooze(&db, "build", 30).unwrap();
+        let mutes = db.list_mutes(Utc::now()).unwrap();
+        assert_eq!(mutes[0].id, Some(id));
+        assert!(snooze(&db, "missing", 30).is_err());
+    }
+}
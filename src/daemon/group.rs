@@ -0,0 +1,150 @@
+//! Concurrency limits for groups of queued tasks
+//!
+//! `agent-inbox queue --group <name>` puts a task in a group. With
+//! `[groups.<name>] max_concurrent = N`, the daemon runs at most N of the
+//! group's tasks at a time. The rest stay queued, oldest first, until a slot
+//! frees up; each of them notifies when it finally starts. Groups without a
+//! limit hold nothing back.
+
+use super::chain::QueuedRun;
+use crate::config::GroupConfig;
+use crate::models::{Task, TaskStatus};
+use crate::notify::{Category, Notification, Urgency};
+use std::collections::HashMap;
+
+/// Metadata key set on a task while it waits for a slot
+const WAITING: &str = "group_waiting";
+
+/// Free slots per group, for one pass over the queue
+pub struct Slots {
+    limits: HashMap<String, usize>,
+    running: HashMap<String, usize>,
+}
+
+impl Slots {
+    /// Slots left given `tasks`, of which the running ones take a slot in
+    /// their group
+    pub fn new(config: &HashMap<String, GroupConfig>, tasks: &[Task]) -> Self {
+        let limits = config
+            .iter()
+            .filter(|(_, group)| group.max_concurrent > 0)
+            .map(|(name, group)| (name.clone(), group.max_concurrent))
+            .collect();
+        let mut running = HashMap::new();
+        for task in tasks.iter().filter(|t| t.status == TaskStatus::Running) {
+            if let Some(group) = QueuedRun::of(task).and_then(|run| run.group) {
+                *running.entry(group).or_insert(0) += 1;
+            }
+        }
+        Self { limits, running }
+    }
+
+    /// Take a slot in `group`. False when the group is full; tasks without
+    /// a group, or in one without a limit, always get one.
+    pub fn take(&mut self, group: Option<&str>) -> bool {
+        let Some(group) = group else {
+            return true;
+        };
+        let running = self.running.entry(group.to_string()).or_insert(0);
+        match self.limits.get(group) {
+            Some(&limit) if *running >= limit => false,
+            _ => {
+                *running += 1;
+                true
+            }
+        }
+    }
+
+    /// "3 of 4 running"
+    pub fn describe(&self, group: &str) -> String {
+        let running = self.running.get(group).copied().unwrap_or(0);
+        match self.limits.get(group) {
+            Some(limit) => format!("{} of {} running", running, limit),
+            None => format!("{} running", running),
+        }
+    }
+}
+
+/// Whether `task` was held back for a slot
+pub fn waiting(task: &Task) -> bool {
+    task.metadata
+        .as_ref()
+        .is_some_and(|m| m.contains_key(WAITING))
+}
+
+pub fn set_waiting(task: &mut Task, waiting: bool) {
+    let metadata = task.metadata.get_or_insert_with(Default::default);
+    if waiting {
+        metadata.insert(WAITING.to_string(), true.into());
+    } else {
+        metadata.remove(WAITING);
+    }
+}
+
+/// Tells the user that `task`, held back in `group`, has started
+pub fn started(task: &Task, group: &str, slots: &Slots) -> Notification {
+    Notification {
+        title: format!("[{}] {}", task.agent_type, task.title),
+        body: format!(
+            "Started: a slot freed up in group {} ({})",
+            group,
+            slots.describe(group)
+        ),
+        urgency: Urgency::Low,
+        category: Category::Info,
+        task_id: Some(task.task_id.clone()),
+        reason: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, group: Option<&str>, status: TaskStatus) -> Task {
+        let mut task = Task::new(id.into(), "queued".into(), id.into(), None, None);
+        task.status = status;
+        let run = QueuedRun {
+            command: "true".into(),
+            cwd: None,
+            after: None,
+            restart: None,
+            group: group.map(str::to_string),
+            env: Default::default(),
+        };
+        task.metadata
+            .get_or_insert_with(Default::default)
+            .insert("queued".into(), serde_json::to_value(run).unwrap());
+        task
+    }
+
+    #[test]
+    fn test_slots() {
+        let config = HashMap::from([
+            ("batch".to_string(), GroupConfig { max_concurrent: 2 }),
+            ("open".to_string(), GroupConfig { max_concurrent: 0 }),
+        ]);
+        let tasks = [
+            task("a", Some("batch"), TaskStatus::Running),
+            task("b", Some("batch"), TaskStatus::Exited),
+            task("c", None, TaskStatus::Running),
+        ];
+        let mut slots = Slots::new(&config, &tasks);
+        assert_eq!(slots.describe("batch"), "1 of 2 running");
+        assert!(slots.take(Some("batch")));
+        assert!(!slots.take(Some("batch")));
+        assert_eq!(slots.describe("batch"), "2 of 2 running");
+        assert!(slots.take(None));
+        assert!((0..5).all(|_| slots.take(Some("open"))));
+        assert!(slots.take(Some("unconfigured")));
+
+        let mut queued = task("d", Some("batch"), TaskStatus::Queued);
+        set_waiting(&mut queued, true);
+        assert!(waiting(&queued));
+        set_waiting(&mut queued, false);
+        assert!(!waiting(&queued));
+        assert!(started(&queued, "batch", &slots)
+            .body
+            .contains("group batch (2 of 2 running)"));
+    }
+}
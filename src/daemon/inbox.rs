@@ -0,0 +1,384 @@
+//! A drop directory for clients that can't do more than write a file
+//!
+//! With `[daemon] inbox = true`, the daemon picks up `*.json` files in
+//! `~/.local/share/agent-inbox/inbox/`, one event each, in name order:
+//!
+//! ```json
+//! {"event": "start", "task_id": "backup", "title": "Nightly backup", "pid": 4242}
+//! {"event": "attention", "task_id": "backup", "kind": "waiting_for_input", "detail": "tape"}
+//! {"event": "running", "task_id": "backup"}
+//! {"event": "complete", "task_id": "backup", "exit_code": 0}
+//! {"event": "notify", "title": "Disk almost full", "body": "/var at 95%", "urgency": "critical"}
+//! ```
+//!
+//! A file is removed once handled; one that can't be read or applied, or
+//! is over 64 KiB, is moved to `inbox/failed/`. Files are read at every pass of the daemon,
+//! or at once on `SIGUSR1`, so a shell script on a locked-down box needs
+//! neither sockets nor HTTP: write `event.json.tmp`, rename it to
+//! `event.json` (only complete files are picked up), then optionally
+//! `kill -USR1 $(cat ~/.local/state/agent-inbox/daemon.pid)`.
+
+use crate::db::Database;
+use crate::models::{EventKind, Task, TaskContext, TaskStatus};
+use crate::monitor::detectors::AttentionReason;
+use crate::monitor::reasons::ReasonRegistry;
+use crate::notify::{Category, Notification, Urgency};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+static WAKE: AtomicBool = AtomicBool::new(false);
+
+/// Largest event file read; anyone can write to the inbox, so bigger ones
+/// are rejected unread
+pub const MAX_EVENT_BYTES: u64 = 64 * 1024;
+
+/// Where event files are dropped
+pub fn dir() -> PathBuf {
+    crate::storage::data_dir().join("inbox")
+}
+
+/// One event file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DropEvent {
+    /// Register a task, or start a new run of it; with a pid, the daemon
+    /// monitors the process
+    Start {
+        task_id: String,
+        #[serde(default = "default_agent")]
+        agent: String,
+        title: Option<String>,
+        cwd: Option<String>,
+        pid: Option<i32>,
+    },
+    /// Flag the task and notify, as `report attention --notify` does
+    Attention {
+        task_id: String,
+        #[serde(default = "default_kind")]
+        kind: String,
+        detail: Option<String>,
+    },
+    /// The user answered; the task is working again
+    Running { task_id: String },
+    /// The task finished; a non-zero exit code means it failed
+    Complete {
+        task_id: String,
+        exit_code: Option<i32>,
+    },
+    /// A notification about no task in particular
+    Notify {
+        title: String,
+        #[serde(default)]
+        body: String,
+        urgency: Option<Urgency>,
+    },
+}
+
+fn default_agent() -> String {
+    "script".to_string()
+}
+
+fn default_kind() -> String {
+    "waiting_for_input".to_string()
+}
+
+/// What the daemon still has to do once an event is recorded
+#[derive(Debug)]
+pub enum Effect {
+    Nothing,
+    Notify(Notification),
+    /// Monitor the task's process
+    Watch {
+        task_id: String,
+        pid: i32,
+    },
+    /// Fire hooks and send the completion notification
+    Finished {
+        task: Box<Task>,
+        exit_code: Option<i32>,
+    },
+}
+
+/// Record `event` in the database
+pub fn record(
+    db: &Database,
+    reasons: &ReasonRegistry,
+    event: DropEvent,
+    now: DateTime<Utc>,
+) -> Result<Effect> {
+    let existing = |task_id: &str| -> Result<Task> {
+        db.get_task_by_id(task_id)?
+            .with_context(|| format!("Task not found: {}", task_id))
+    };
+    match event {
+        DropEvent::Start {
+            task_id,
+            agent,
+            title,
+            cwd,
+            pid,
+        } => {
+            let title = title.unwrap_or_else(|| task_id.clone());
+            let mut task = Task::new(task_id.clone(), agent, title, pid, None);
+            task.context = Some(TaskContext {
+                url: None,
+                project_path: cwd,
+                session_id: None,
+                extra: Default::default(),
+            });
+            db.register_task(&task)?;
+            let message = match pid {
+                Some(pid) => format!("From the inbox, pid {}", pid),
+                None => "From the inbox".to_string(),
+            };
+            db.log_event(&task_id, EventKind::Started, message);
+            Ok(match pid {
+                Some(pid) => Effect::Watch { task_id, pid },
+                None => Effect::Nothing,
+            })
+        }
+        DropEvent::Attention {
+            task_id,
+            kind,
+            detail,
+        } => {
+            let mut task = existing(&task_id)?;
+            let reason = AttentionReason::kind(&kind, detail.as_deref());
+            let message = reasons.describe(&reason);
+            let cooling = task.acknowledged().is_some_and(|ack| {
+                ack.covers(reason.key(), reasons.ack_cooldown(reason.key()), now)
+            });
+            task.set_attention(reason.key(), message.clone());
+            task.updated_at = now;
+            db.update_task(&task)?;
+            db.log_event(&task_id, EventKind::Attention, message.clone());
+            if cooling {
+                db.log_event(
+                    &task_id,
+                    EventKind::Suppressed,
+                    format!("Acknowledged recently: {}", message),
+                );
+                return Ok(Effect::Nothing);
+            }
+            let title = format!("[{}] {}", task.agent_type, task.title);
+            Ok(Effect::Notify(Notification {
+                title: match reasons.icon(&reason) {
+                    "" => title,
+                    icon => format!("{} {}", icon, title),
+                },
+                body: message,
+                urgency: reasons.severity(&reason),
+                category: Category::Attention,
+                task_id: Some(task_id),
+                reason: Some(reason.key().to_string()),
+            }))
+        }
+        DropEvent::Running { task_id } => {
+            let mut task = existing(&task_id)?;
+            let resumed = task.status != TaskStatus::Running || task.attention_reason.is_some();
+            task.attention_reason = None;
+            task.clear_acknowledged();
+            task.set_running();
+            db.update_task(&task)?;
+            if resumed {
+                db.log_event(&task_id, EventKind::Resumed, "Generating again");
+            }
+            Ok(Effect::Nothing)
+        }
+        DropEvent::Complete { task_id, exit_code } => {
+            let mut task = existing(&task_id)?;
+            match exit_code {
+                Some(code) if code != 0 => task.set_exited(Some(code)),
+                _ => task.complete(),
+            }
+            db.update_task(&task)?;
+            let (kind, message) = match exit_code {
+                Some(code) if code != 0 => (EventKind::Exited, format!("Exit code {}", code)),
+                Some(code) => (EventKind::Completed, format!("Exit code {}", code)),
+                None => (EventKind::Completed, "Finished".to_string()),
+            };
+            db.log_event(&task_id, kind, message);
+            Ok(Effect::Finished {
+                task: Box::new(task),
+                exit_code,
+            })
+        }
+        DropEvent::Notify {
+            title,
+            body,
+            urgency,
+        } => Ok(Effect::Notify(Notification {
+            title,
+            body,
+            urgency: urgency.unwrap_or(Urgency::Normal),
+            category: Category::Info,
+            task_id: None,
+            reason: None,
+        })),
+    }
+}
+
+/// Event files waiting in `dir`, in name order
+pub fn pending(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    files
+}
+
+pub fn read(path: &Path) -> Result<DropEvent> {
+    let mut text = String::new();
+    fs::File::open(path)
+        .and_then(|file| file.take(MAX_EVENT_BYTES + 1).read_to_string(&mut text))
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    anyhow::ensure!(
+        text.len() as u64 <= MAX_EVENT_BYTES,
+        "{} is over {} bytes",
+        path.display(),
+        MAX_EVENT_BYTES
+    );
+    serde_json::from_str(&text).with_context(|| format!("Invalid event in {}", path.display()))
+}
+
+/// Move a file that couldn't be handled out of the way
+pub fn reject(path: &Path) {
+    let Some((dir, name)) = path.parent().zip(path.file_name()) else {
+        return;
+    };
+    let failed = dir.join("failed");
+    let moved = fs::create_dir_all(&failed).and_then(|_| fs::rename(path, failed.join(name)));
+    if moved.is_err() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Turn SIGUSR1 into a request to read the inbox now
+#[cfg(unix)]
+pub fn catch_wake_signal() {
+    extern "C" fn request_wake(_: libc::c_int) {
+        WAKE.store(true, Ordering::SeqCst);
+    }
+    let handler = request_wake as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only stores to an atomic
+    unsafe {
+        libc::signal(libc::SIGUSR1, handler);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn catch_wake_signal() {}
+
+/// Sleep for `interval`, or until SIGUSR1 or a stop request
+pub fn nap(interval: Duration) {
+    let deadline = Instant::now() + interval;
+    while !WAKE.swap(false, Ordering::SeqCst) && !super::status::stop_requested() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return;
+        }
+        std::thread::sleep(left.min(Duration::from_millis(100)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_drop_events() {
+        let file = NamedTempFile::new().unwrap();
+        let db = Database::open(file.path()).unwrap();
+        let reasons = ReasonRegistry::default();
+        let now = Utc::now();
+        let event = |json: &str| serde_json::from_str::<DropEvent>(json).unwrap();
+
+        let start = event(
+            r#"{"event": "start", "task_id": "backup", "title": "Nightly backup", "pid": 42}"#,
+        );
+        assert!(matches!(
+            record(&db, &reasons, start, now).unwrap(),
+            Effect::Watch { pid: 42, .. }
+        ));
+        let task = db.get_task_by_id("backup").unwrap().unwrap();
+        assert_eq!(
+            (task.agent_type.as_str(), task.title.as_str()),
+            ("script", "Nightly backup")
+        );
+
+        let attention = event(
+            r#"{"event": "attention", "task_id": "backup", "kind": "merge_conflict", "detail": "db.sql"}"#,
+        );
+        let Effect::Notify(notification) = record(&db, &reasons, attention, now).unwrap() else {
+            panic!("expected a notification");
+        };
+        assert_eq!(notification.body, "Merge conflict in db.sql");
+        assert_eq!(notification.reason.as_deref(), Some("merge_conflict"));
+
+        let running = event(r#"{"event": "running", "task_id": "backup"}"#);
+        record(&db, &reasons, running, now).unwrap();
+        assert_eq!(
+            db.get_task_by_id("backup")
+                .unwrap()
+                .unwrap()
+                .attention_reason,
+            None
+        );
+
+        let complete = event(r#"{"event": "complete", "task_id": "backup", "exit_code": 3}"#);
+        let Effect::Finished { task, exit_code } = record(&db, &reasons, complete, now).unwrap()
+        else {
+            panic!("expected the run to finish");
+        };
+        assert_eq!((task.status, exit_code), (TaskStatus::Exited, Some(3)));
+
+        let notify =
+            event(r#"{"event": "notify", "title": "Disk almost full", "urgency": "critical"}"#);
+        assert!(matches!(
+            record(&db, &reasons, notify, now).unwrap(),
+            Effect::Notify(Notification {
+                urgency: Urgency::Critical,
+                ..
+            })
+        ));
+        let unknown = event(r#"{"event": "running", "task_id": "nope"}"#);
+        assert!(record(&db, &reasons, unknown, now).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("2-b.json"), r#"{"event": "runing"}"#).unwrap();
+        fs::write(
+            dir.path().join("1-a.json"),
+            r#"{"event": "running", "task_id": "backup"}"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("3-c.json.tmp"), "{").unwrap();
+        let files = pending(dir.path());
+        assert_eq!(files.len(), 2);
+        assert!(files[0].ends_with("1-a.json"));
+        assert!(read(&files[1]).is_err());
+        reject(&files[1]);
+        assert!(dir.path().join("failed/2-b.json").exists());
+        assert_eq!(pending(dir.path()).len(), 1);
+
+        // Oversized files aren't read in full
+        let big = dir.path().join("4-d.json");
+        let padding = " ".repeat(MAX_EVENT_BYTES as usize);
+        fs::write(
+            &big,
+            format!(r#"{{"event": "running", "task_id": "backup"}}{}"#, padding),
+        )
+        .unwrap();
+        assert!(format!("{:#}", read(&big).unwrap_err()).contains("is over"));
+    }
+}
@@ -0,0 +1,332 @@
+//! Resources the daemon keeps itself to: `[daemon.limits]`
+//!
+//! A notifier should never be the heaviest process on the box. The daemon
+//! holds itself to three budgets and degrades rather than going over them:
+//!
+//! - memory for output buffers (`max_buffer_mb`): a run's capture buffer
+//!   (`[capture] buffer_bytes`) is cut to what the buffers of the daemon's
+//!   live runs leave of the budget, though never below 64 KiB, and runs
+//!   started while squeezed aren't recorded. Once the budget is spent each
+//!   further run goes over it by that floor.
+//! - disk for artifacts (`max_artifacts_mb`): the oldest run directories,
+//!   whatever their task, are removed until the archive fits
+//! - CPU (`max_cpu_percent` of one core, monitors and capture threads
+//!   included): over budget, the daemon's poll interval and that of the
+//!   monitors it starts from then on doubles, up to 8 times; under half the
+//!   budget, it halves again
+//!
+//! The first time each limit is hit, a warning notification says so.
+
+use crate::artifacts::snapshot::STALLS;
+use crate::config::{CaptureConfig, LimitsConfig};
+use crate::notify::{Category, Notification, Urgency};
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const MB: u64 = 1024 * 1024;
+
+/// Smallest capture buffer a run gets, however many there are
+const MIN_BUFFER: usize = 64 * 1024;
+
+/// Most the poll interval is stretched
+const MAX_SLOWDOWN: u32 = 8;
+
+/// How often the artifacts archive is measured
+const ARTIFACTS_EVERY: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    Buffers,
+    Artifacts,
+    Cpu,
+}
+
+impl Limit {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Limit::Buffers => "output buffer",
+            Limit::Artifacts => "artifacts disk",
+            Limit::Cpu => "CPU",
+        }
+    }
+}
+
+pub struct Limits {
+    config: LimitsConfig,
+    /// Factor the poll intervals are stretched by
+    slowdown: u32,
+    /// CPU time used and when, at the last measurement
+    measured: Option<(Duration, Instant)>,
+    artifacts_checked: Option<Instant>,
+    warned: HashSet<Limit>,
+    /// Bytes of capture buffer handed to runs still going
+    buffers: Arc<AtomicUsize>,
+}
+
+/// A run's capture buffer, counted against the budget until dropped
+pub struct Lease {
+    buffers: Arc<AtomicUsize>,
+    bytes: usize,
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.buffers.fetch_sub(self.bytes, Ordering::SeqCst);
+    }
+}
+
+impl Limits {
+    pub fn new(config: LimitsConfig) -> Self {
+        Self {
+            config,
+            slowdown: 1,
+            measured: None,
+            artifacts_checked: None,
+            warned: HashSet::new(),
+            buffers: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// `base` stretched for the CPU budget
+    pub fn interval(&self, base: Duration) -> Duration {
+        base * self.slowdown
+    }
+
+    pub fn slowed(&self) -> bool {
+        self.slowdown > 1
+    }
+
+    /// Capture settings for the next run, its buffer within what the runs
+    /// holding a [`Lease`] leave of the budget. The run holds the returned
+    /// lease until its buffer is gone.
+    pub fn capture(&self, base: &CaptureConfig) -> (CaptureConfig, Lease) {
+        let mut config = base.clone();
+        let held = self.buffers_held();
+        let left = (self.config.max_buffer_mb * MB).saturating_sub(held as u64) as usize;
+        if self.config.max_buffer_mb > 0 && left < base.buffer_bytes {
+            config.buffer_bytes = left.max(MIN_BUFFER);
+            config.record = false;
+        }
+        self.buffers
+            .fetch_add(config.buffer_bytes, Ordering::SeqCst);
+        let lease = Lease {
+            buffers: self.buffers.clone(),
+            bytes: config.buffer_bytes,
+        };
+        (config, lease)
+    }
+
+    /// Bytes of capture buffer held by the daemon's runs
+    pub fn buffers_held(&self) -> usize {
+        self.buffers.load(Ordering::SeqCst)
+    }
+
+    /// Measure the CPU used since the last call, `cpu` being the total so
+    /// far, and stretch or relax the poll intervals. Returns the usage, in
+    /// percent of one core, when it was over budget.
+    pub fn pace(&mut self, cpu: Duration, now: Instant) -> Option<f64> {
+        let (last_cpu, last) = self.measured.replace((cpu, now))?;
+        let wall = now.duration_since(last).as_secs_f64();
+        if self.config.max_cpu_percent <= 0.0 || wall <= 0.0 {
+            return None;
+        }
+        let percent = cpu.saturating_sub(last_cpu).as_secs_f64() / wall * 100.0;
+        if percent > self.config.max_cpu_percent {
+            self.slowdown = (self.slowdown * 2).min(MAX_SLOWDOWN);
+            Some(percent)
+        } else {
+            if percent < self.config.max_cpu_percent / 2.0 {
+                self.slowdown = (self.slowdown / 2).max(1);
+            }
+            None
+        }
+    }
+
+    /// Bring the archive under `root` within its budget, at most every ten
+    /// minutes. Returns the runs removed, if any were.
+    pub fn trim_artifacts(&mut self, root: &Path, now: Instant) -> Option<usize> {
+        if self.config.max_artifacts_mb == 0
+            || self
+                .artifacts_checked
+                .is_some_and(|at| now.duration_since(at) < ARTIFACTS_EVERY)
+        {
+            return None;
+        }
+        self.artifacts_checked = Some(now);
+        match trim(root, self.config.max_artifacts_mb * MB) {
+            Ok(0) => None,
+            Ok(removed) => Some(removed),
+            Err(e) => {
+                eprintln!("Failed to trim artifacts in {}: {:#}", root.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Warning that `limit` was hit, the first time only
+    pub fn warning(&mut self, limit: Limit, detail: String) -> Option<Notification> {
+        if !self.config.warn || !self.warned.insert(limit) {
+            return None;
+        }
+        Some(Notification {
+            title: format!("agent-inbox hit its {} limit", limit.as_str()),
+            body: detail,
+            urgency: Urgency::Normal,
+            category: Category::Info,
+            task_id: None,
+            reason: None,
+        })
+    }
+}
+
+/// CPU time this process used so far, all threads together
+#[cfg(unix)]
+pub fn cpu_time() -> Duration {
+    // SAFETY: all-zero is a valid rusage
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    // SAFETY: `usage` outlives the call
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return Duration::ZERO;
+    }
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    time(usage.ru_utime) + time(usage.ru_stime)
+}
+
+#[cfg(not(unix))]
+pub fn cpu_time() -> Duration {
+    Duration::ZERO
+}
+
+/// Remove the oldest run directories under `root` until it takes at most
+/// `max_bytes`. Stall snapshots are counted but kept. Returns the runs
+/// removed.
+fn trim(root: &Path, max_bytes: u64) -> Result<usize> {
+    let mut size = dir_size(root);
+    if size <= max_bytes {
+        return Ok(0);
+    }
+    let mut runs: Vec<PathBuf> = Vec::new();
+    for task in fs::read_dir(root)?.flatten().filter(|e| e.path().is_dir()) {
+        runs.extend(
+            fs::read_dir(task.path())?
+                .flatten()
+                .filter(|e| e.file_name() != STALLS)
+                .map(|e| e.path())
+                .filter(|p| p.is_dir()),
+        );
+    }
+    // Names are timestamps, so they sort by age across tasks
+    runs.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    let mut removed = 0;
+    for run in runs {
+        if size <= max_bytes {
+            break;
+        }
+        let freed = dir_size(&run);
+        fs::remove_dir_all(&run)?;
+        size = size.saturating_sub(freed);
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+fn dir_size(path: &Path) -> u64 {
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| match e.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&e.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits() {
+        let mut limits = Limits::new(LimitsConfig {
+            max_buffer_mb: 8,
+            max_artifacts_mb: 1,
+            max_cpu_percent: 10.0,
+            warn: true,
+        });
+
+        let base = CaptureConfig {
+            record: true,
+            ..Default::default()
+        };
+        let base = CaptureConfig {
+            buffer_bytes: 3 * MB as usize,
+            ..base
+        };
+        let (first, first_lease) = limits.capture(&base);
+        assert_eq!(
+            (first.buffer_bytes, first.record),
+            (base.buffer_bytes, true)
+        );
+        let (second, _second_lease) = limits.capture(&base);
+        assert_eq!(second.buffer_bytes, base.buffer_bytes);
+        // What the first two hold leaves 2 MB
+        let (squeezed, squeezed_lease) = limits.capture(&base);
+        assert_eq!(
+            (squeezed.buffer_bytes, squeezed.record),
+            (2 * MB as usize, false)
+        );
+        assert_eq!(limits.buffers_held(), 8 * MB as usize);
+        let (spent, _spent_lease) = limits.capture(&base);
+        assert_eq!(spent.buffer_bytes, MIN_BUFFER);
+        // Finished runs give their buffers back
+        drop(first_lease);
+        drop(squeezed_lease);
+        assert_eq!(limits.capture(&base).0.buffer_bytes, base.buffer_bytes);
+        assert_eq!(limits.buffers_held(), 3 * MB as usize + MIN_BUFFER);
+
+        let start = Instant::now();
+        let second = |n: u64| start + Duration::from_secs(n);
+        let base = Duration::from_secs(5);
+        assert_eq!(limits.pace(Duration::ZERO, second(0)), None);
+        assert!(limits
+            .pace(Duration::from_millis(1500), second(5))
+            .is_some_and(|p| (p - 30.0).abs() < 0.1));
+        assert_eq!(limits.interval(base), Duration::from_secs(10));
+        // Between half the budget and the budget the interval holds
+        assert_eq!(limits.pace(Duration::from_millis(2200), second(15)), None);
+        assert_eq!(limits.interval(base), Duration::from_secs(10));
+        assert_eq!(limits.pace(Duration::from_millis(2300), second(25)), None);
+        assert!(!limits.slowed());
+
+        let dir = tempfile::tempdir().unwrap();
+        let write = |run: &str, bytes: usize| {
+            let path = dir.path().join(run);
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join("output.log"), vec![b'x'; bytes]).unwrap();
+        };
+        write("a/20240101T000000Z", 600 * 1024);
+        write("b/20240102T000000Z", 600 * 1024);
+        write("a/20240103T000000Z", 100 * 1024);
+        write("a/stalls", 100 * 1024);
+        assert_eq!(limits.trim_artifacts(dir.path(), start), Some(1));
+        assert!(!dir.path().join("a/20240101T000000Z").exists());
+        assert!(
+            dir.path().join("b/20240102T000000Z").exists() && dir.path().join("a/stalls").exists()
+        );
+        // Measured again only after a while
+        write("c/20240104T000000Z", 600 * 1024);
+        assert_eq!(limits.trim_artifacts(dir.path(), second(60)), None);
+        assert_eq!(limits.trim_artifacts(dir.path(), second(601)), Some(1));
+
+        assert!(limits.warning(Limit::Cpu, "30%".to_string()).is_some());
+        assert!(limits.warning(Limit::Cpu, "40%".to_string()).is_none());
+        assert!(cpu_time() > Duration::ZERO);
+    }
+}
@@ -0,0 +1,1076 @@
+//! Long-running background service
+//!
+//! The daemon launches the commands configured under `[[schedule]]` in
+//! `~/.config/agent-inbox/config.toml` and watches each run like any other task:
+//! output is captured to the task's log, the monitor raises stall/attention
+//! notifications, and a completion notification summarizes the outcome.
+//!
+//! It also starts tasks queued with `agent-inbox queue`, in dependency order
+//! (see [`chain`]) and within the concurrency limits of their groups (see
+//! [`group`]), restarts those queued with `--restart` when they fail
+//! (see [`restart`]), and sends the [`digest`](crate::digest) when
+//! `[digest] cron` comes due.
+//!
+//! With `[daemon] listen`, it is also a [`federation`](crate::federation)
+//! hub: notifications forwarded by other machines are sent through this
+//! machine's channels.
+//!
+//! With `[daemon] dbus = true`, it serves a [`dbus`] interface on the
+//! session bus for desktop integrations.
+//!
+//! With `[daemon] inbox = true`, it reads events that scripts drop as files
+//! in its [`inbox`], at every pass or at once on `SIGUSR1`.
+//!
+//! With `[daemon] watchdog = true`, a [`watchdog`] process restarts the
+//! daemon if it crashes.
+//!
+//! It keeps its own memory, disk and CPU use within `[daemon.limits]`,
+//! capturing less and polling less often rather than going over (see
+//! [`limits`]).
+//!
+//! It announces when it starts and stops (see [`status`]). On start it takes
+//! stock of the tasks nothing watches any more (see [`reconcile`]): live
+//! ones are monitored again, dead ones are marked exited with a catch-up
+//! notification, and doubtful ones are flagged.
+//!
+//! Each scheduled command is registered under the stable ID
+//! `schedule-<name>`, so every run shows up as a new run of the same task and
+//! earlier outcomes are kept in its history.
+
+pub mod chain;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+pub mod group;
+pub mod inbox;
+pub mod limits;
+pub mod reconcile;
+pub mod rerun;
+pub mod restart;
+pub mod schedule;
+pub mod status;
+pub mod watchdog;
+
+use crate::artifacts;
+use crate::config::{CaptureConfig, Config, ScheduleConfig, SummaryConfig};
+use crate::db::Database;
+use crate::digest::Digest;
+use crate::federation::{self, Hub};
+use crate::hooks::Hooks;
+use crate::models::{EventKind, Task, TaskContext, TaskStatus};
+use crate::monitor::capture::{self, OutputCapture};
+use crate::monitor::fifo::{self, Fifo};
+use crate::monitor::probe::{ProcProbe, ProcessProbe};
+use crate::monitor::reasons::ReasonRegistry;
+use crate::monitor::redact::SecretFilter;
+use crate::monitor::respond::{AutoResponder, PipeInput};
+use crate::monitor::{output, MonitorBuilder};
+use crate::notify::{self, Category, Notification, Notifier, NotifierBuilder, Urgency};
+use crate::plugins;
+use crate::summary;
+use anyhow::{Context, Result};
+use chain::{Gate, QueuedRun};
+use chrono::{Local, NaiveDateTime, Timelike, Utc};
+use inbox::Effect;
+use limits::{Lease, Limit, Limits};
+use reconcile::Verdict;
+use restart::Restart;
+use schedule::Schedule;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Where the running daemon records its pid
+fn pid_path() -> PathBuf {
+    crate::storage::state_dir().join("daemon.pid")
+}
+
+/// The pid of the daemon running for this user, if one is
+pub fn running() -> Option<i32> {
+    let pid = fs::read_to_string(pid_path()).ok()?.trim().parse().ok()?;
+    ProcProbe.is_alive(pid).then_some(pid)
+}
+
+pub struct Daemon {
+    config: Config,
+    db_path: PathBuf,
+    poll_interval: Duration,
+    jobs: Vec<(ScheduleConfig, Schedule)>,
+    digest: Option<Schedule>,
+    /// Set by `due_jobs` when the digest schedule matched the current minute
+    digest_due: bool,
+    last_minute: Option<NaiveDateTime>,
+    responder: AutoResponder,
+    hooks: Hooks,
+    limits: Limits,
+    /// Built on first use, loading the plugins once
+    notifier: OnceLock<Notifier>,
+}
+
+impl Daemon {
+    /// Validate the configured schedules; fails on bad cron expressions or
+    /// duplicate names
+    pub fn new(config: Config, db_path: PathBuf) -> Result<Self> {
+        let mut names = HashSet::new();
+        let mut jobs = Vec::new();
+        for job in &config.schedule {
+            if !names.insert(job.name.as_str()) {
+                anyhow::bail!("Duplicate schedule name: {}", job.name);
+            }
+            let schedule = Schedule::parse(&job.cron)
+                .with_context(|| format!("Invalid schedule for '{}'", job.name))?;
+            jobs.push((job.clone(), schedule));
+        }
+        let digest = config
+            .digest
+            .cron
+            .as_deref()
+            .map(Schedule::parse)
+            .transpose()
+            .context("Invalid [digest] cron")?;
+
+        Ok(Self {
+            hooks: Hooks::from_config(&config)?,
+            responder: AutoResponder::new(&config.auto_respond)?.deny(&config.auto_respond_deny)?,
+            limits: Limits::new(config.daemon.limits.clone()),
+            config,
+            db_path,
+            poll_interval: Duration::from_secs(5),
+            jobs,
+            digest,
+            digest_due: false,
+            last_minute: None,
+            notifier: OnceLock::new(),
+        })
+    }
+
+    pub fn job_names(&self) -> Vec<&str> {
+        self.jobs.iter().map(|(job, _)| job.name.as_str()).collect()
+    }
+
+    /// Run until SIGINT or SIGTERM, launching jobs as they come due and
+    /// starting queued tasks once their predecessor succeeds
+    pub fn run(&mut self) -> Result<()> {
+        // Closing the pipe, however the daemon exits, wakes the watchdog
+        let watchdog = self
+            .config
+            .daemon
+            .watchdog
+            .then(watchdog::spawn)
+            .transpose()?;
+        let me = std::process::id().to_string();
+        if let Err(e) = fs::write(pid_path(), &me) {
+            eprintln!("Failed to record the daemon's pid: {}", e);
+        }
+        status::catch_stop_signals();
+        if let Some(addr) = &self.config.daemon.listen {
+            let hub = Hub::from_config(&self.config.daemon, addr, self.db_path.clone())?
+                .secrets(SecretFilter::from_config(&self.config.notify.redact)?);
+            let notifier = federation::hub_notifier(&self.config.notify)?;
+            eprintln!("Serving the API on {}", hub.local_addr()?);
+            hub.spawn(move |envelope| federation::receive(&notifier, &envelope));
+        } else if self.config.daemon.observe {
+            eprintln!("The observer view needs [daemon] listen; not serving it");
+        }
+        if self.config.daemon.dbus {
+            #[cfg(feature = "dbus")]
+            match dbus::spawn(self.db_path.clone()) {
+                Ok(()) => eprintln!("Serving {} on the session bus", dbus::NAME),
+                Err(e) => eprintln!("Not serving D-Bus: {:#}", e),
+            }
+            #[cfg(not(feature = "dbus"))]
+            eprintln!("[daemon] dbus needs the `dbus` feature; not serving it");
+        }
+        if self.config.daemon.inbox {
+            inbox::catch_wake_signal();
+            match fs::create_dir_all(inbox::dir()) {
+                Ok(()) => eprintln!("Reading events dropped in {}", inbox::dir().display()),
+                Err(e) => eprintln!("Failed to create {}: {}", inbox::dir().display(), e),
+            }
+        }
+        let resumed = self.resume().unwrap_or_else(|e| {
+            eprintln!("Failed to take over earlier runs: {:#}", e);
+            Vec::new()
+        });
+        if self.config.daemon.status.enabled {
+            let host = crate::host::current(&self.config.notify.host);
+            self.announce(&status::started(
+                &self.config.daemon.status,
+                &host,
+                &resumed,
+            ));
+        }
+
+        while !status::stop_requested() {
+            for job in self.due_jobs(Local::now().naive_local()) {
+                if let Err(e) = self.launch(&job) {
+                    eprintln!("Failed to launch scheduled job '{}': {:#}", job.name, e);
+                }
+            }
+            if let Err(e) = self.start_ready() {
+                eprintln!("Failed to start queued tasks: {:#}", e);
+            }
+            if std::mem::take(&mut self.digest_due) {
+                if let Err(e) = self.send_digest() {
+                    eprintln!("Failed to send digest: {:#}", e);
+                }
+            }
+            if self.config.daemon.inbox {
+                if let Err(e) = self.read_inbox() {
+                    eprintln!("Failed to read the inbox: {:#}", e);
+                }
+            }
+            self.check_limits();
+            inbox::nap(self.limits.interval(self.poll_interval));
+        }
+
+        eprintln!("Stopping");
+        if self.config.daemon.status.enabled {
+            let running = self.unwatched().unwrap_or_else(|e| {
+                eprintln!("Failed to list running tasks: {:#}", e);
+                Vec::new()
+            });
+            let host = crate::host::current(&self.config.notify.host);
+            self.announce(&status::stopped(
+                &self.config.daemon.status,
+                &host,
+                &running,
+            ));
+        }
+        // A newer daemon may have taken over the file
+        if fs::read_to_string(pid_path()).is_ok_and(|pid| pid == me) {
+            let _ = fs::remove_file(pid_path());
+        }
+        // Anything written tells the watchdog this was a stop, not a crash
+        if let Some(mut pipe) = watchdog {
+            let _ = pipe.write_all(b"stop");
+        }
+        Ok(())
+    }
+
+    /// Take over the tasks nothing watches any more (see [`reconcile`]):
+    /// running ones still alive are monitored again (their completion can't
+    /// be waited for, only noticed), those that died unwatched are marked
+    /// exited and caught up on, and completed ones whose process ended are
+    /// marked exited. Returns the tasks taken over and the queued
+    /// tasks still to start.
+    fn resume(&self) -> Result<Vec<Task>> {
+        let db = Database::open(&self.db_path)?;
+        let me = std::process::id() as i32;
+        let mut resumed = Vec::new();
+        let mut tasks = db.list_tasks(Some(TaskStatus::Running))?;
+        tasks.extend(db.list_tasks(Some(TaskStatus::Completed))?);
+        for mut task in tasks {
+            match reconcile::assess(&task, &ProcProbe, me) {
+                None | Some(Verdict::Watched) => {}
+                Some(Verdict::Resume(pid)) => {
+                    if QueuedRun::of(&task).is_some() || task.task_id.starts_with("schedule-") {
+                        task.ppid = Some(me);
+                        db.update_task(&task)?;
+                    }
+                    let monitor =
+                        MonitorBuilder::from_config(Database::open(&self.db_path)?, &self.config)?
+                            .build();
+                    let task_id = task.task_id.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = monitor.monitor_task(task_id, pid, None) {
+                            eprintln!("Monitor failed: {:#}", e);
+                        }
+                    });
+                    resumed.push(task);
+                }
+                Some(Verdict::Gone) => {
+                    let notification = reconcile::catch_up(&task);
+                    task.set_exited(None);
+                    db.update_task(&task)?;
+                    db.log_event(
+                        &task.task_id,
+                        EventKind::Exited,
+                        "Finished while unmonitored",
+                    );
+                    self.hooks.fire(&db, &task);
+                    self.announce(&notification);
+                }
+                Some(Verdict::Ended) => {
+                    task.set_exited(None);
+                    db.update_task(&task)?;
+                    db.log_event(
+                        &task.task_id,
+                        EventKind::Exited,
+                        "Process ended after completing",
+                    );
+                }
+                Some(Verdict::Unclear(reason)) => {
+                    if task.unclear() != Some(reason.as_str()) {
+                        eprintln!("Can't tell what became of {}: {}", task.task_id, reason);
+                        task.set_unclear(&reason);
+                        db.update_task(&task)?;
+                    }
+                }
+            }
+        }
+        resumed.extend(db.list_tasks(Some(TaskStatus::Queued))?);
+        Ok(resumed)
+    }
+
+    /// Runs this daemon launched or watches that are still going, which
+    /// nothing watches once it exits
+    fn unwatched(&self) -> Result<Vec<Task>> {
+        let me = std::process::id() as i32;
+        Ok(Database::open(&self.db_path)?
+            .list_tasks(Some(TaskStatus::Running))?
+            .into_iter()
+            .filter(|task| task.ppid == Some(me) || task.monitor_pid == Some(me))
+            .filter(|task| task.pid.is_some_and(|pid| ProcProbe.is_alive(pid)))
+            .collect())
+    }
+
+    /// The configured channels, plugins included
+    fn notifier(&self) -> Result<&Notifier> {
+        if let Some(notifier) = self.notifier.get() {
+            return Ok(notifier);
+        }
+        let loaded = plugins::load_all(
+            plugins::default_plugin_dir(),
+            &self.config.plugins,
+            &self.config.sandbox,
+        )?;
+        let mut notifier = NotifierBuilder::from_config(&self.config.notify)?;
+        for channel in loaded.channels {
+            notifier = notifier.channel(channel);
+        }
+        Ok(self.notifier.get_or_init(|| notifier.build()))
+    }
+
+    fn announce(&self, notification: &Notification) {
+        match self.notifier() {
+            Ok(notifier) => {
+                notifier.notify(notification);
+            }
+            Err(e) => eprintln!("Failed to send \"{}\": {:#}", notification.title, e),
+        }
+    }
+
+    /// Measure the daemon against `[daemon.limits]`, slowing down or
+    /// trimming artifacts where it is over
+    fn check_limits(&mut self) {
+        let now = Instant::now();
+        let mut warnings = Vec::new();
+        if let Some(percent) = self.limits.pace(limits::cpu_time(), now) {
+            let detail = format!(
+                "Using {:.0}% of a core (limit {}%); polling every {}s",
+                percent,
+                self.config.daemon.limits.max_cpu_percent,
+                self.limits.interval(self.poll_interval).as_secs()
+            );
+            warnings.extend(self.limits.warning(Limit::Cpu, detail));
+        }
+        if self.config.artifacts.enabled {
+            let root = artifacts::root(&self.config.artifacts);
+            if let Some(removed) = self.limits.trim_artifacts(&root, now) {
+                let detail = format!(
+                    "Removed the {} oldest run(s) from {} to stay within {} MB",
+                    removed,
+                    root.display(),
+                    self.config.daemon.limits.max_artifacts_mb
+                );
+                warnings.extend(self.limits.warning(Limit::Artifacts, detail));
+            }
+        }
+        for warning in warnings {
+            eprintln!("{}: {}", warning.title, warning.body);
+            self.announce(&warning);
+        }
+    }
+
+    /// Capture settings for the next run, with its buffer cut down to fit
+    /// next to those of the daemon's other runs, and the lease the run
+    /// holds on it
+    fn capture_config(&mut self) -> (CaptureConfig, Lease) {
+        let (config, lease) = self.limits.capture(&self.config.capture);
+        if config.buffer_bytes < self.config.capture.buffer_bytes {
+            let detail = format!(
+                "Runs' output buffers take {} KB of {} MB; new runs buffer {} KB of output and aren't recorded",
+                self.limits.buffers_held() / 1024,
+                self.config.daemon.limits.max_buffer_mb,
+                config.buffer_bytes / 1024
+            );
+            if let Some(warning) = self.limits.warning(Limit::Buffers, detail) {
+                eprintln!("{}: {}", warning.title, warning.body);
+                self.announce(&warning);
+            }
+        }
+        (config, lease)
+    }
+
+    /// Handle the event files dropped in the inbox, oldest name first
+    fn read_inbox(&self) -> Result<()> {
+        let db = Database::open(&self.db_path)?;
+        let reasons = ReasonRegistry::from_config(&self.config.reasons);
+        for path in inbox::pending(&inbox::dir()) {
+            match inbox::read(&path)
+                .and_then(|event| inbox::record(&db, &reasons, event, Utc::now()))
+            {
+                Ok(effect) => {
+                    let _ = fs::remove_file(&path);
+                    if let Err(e) = self.apply(&db, effect) {
+                        eprintln!("Failed to act on {}: {:#}", path.display(), e);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Rejected {}: {:#}", path.display(), e);
+                    inbox::reject(&path);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Carry out what an inbox event left to do
+    fn apply(&self, db: &Database, effect: Effect) -> Result<()> {
+        let notification = match effect {
+            Effect::Nothing => None,
+            Effect::Notify(notification) => Some(notification),
+            Effect::Watch { task_id, pid } => {
+                let monitor =
+                    MonitorBuilder::from_config(Database::open(&self.db_path)?, &self.config)?
+                        .build();
+                thread::spawn(move || {
+                    if let Err(e) = monitor.monitor_task(task_id, pid, None) {
+                        eprintln!("Monitor failed: {:#}", e);
+                    }
+                });
+                None
+            }
+            Effect::Finished { task, exit_code } => {
+                self.hooks.fire(db, &task);
+                let archived = artifacts::collect(&self.config.artifacts, db, &task)
+                    .unwrap_or_else(|e| {
+                        eprintln!("Failed to collect artifacts of {}: {:#}", task.task_id, e);
+                        None
+                    });
+                completion_notification(
+                    db,
+                    &task,
+                    exit_code,
+                    &self.config.summary,
+                    &SecretFilter::from_config(&self.config.notify.redact)?,
+                )?
+                .map(|mut n| {
+                    if let Some(dir) = &archived {
+                        n.body = format!("{}\n{}", n.body, artifacts::notification_line(dir));
+                    }
+                    n
+                })
+            }
+        };
+        if let Some(notification) = notification {
+            let delivered = self.notifier()?.notify(&notification);
+            if let Some(task_id) = &notification.task_id {
+                let (kind, message) = notify::delivery_event(&notification, &delivered);
+                db.log_event(task_id, kind, message);
+            }
+        }
+        Ok(())
+    }
+
+    /// Jobs scheduled for the minute containing `now`. Each minute is only
+    /// considered once; minutes missed while suspended are not caught up.
+    pub fn due_jobs(&mut self, now: NaiveDateTime) -> Vec<ScheduleConfig> {
+        let minute = now
+            .with_second(0)
+            .and_then(|t| t.with_nanosecond(0))
+            .unwrap_or(now);
+        if self.last_minute == Some(minute) {
+            return Vec::new();
+        }
+        self.last_minute = Some(minute);
+        self.digest_due = self.digest.as_ref().is_some_and(|s| s.matches(&minute));
+
+        self.jobs
+            .iter()
+            .filter(|(_, schedule)| schedule.matches(&minute))
+            .map(|(job, _)| job.clone())
+            .collect()
+    }
+
+    /// Notify a summary of the last `[digest] hours`
+    fn send_digest(&self) -> Result<()> {
+        let db = Database::open(&self.db_path)?;
+        let until = Utc::now();
+        let since = until - chrono::Duration::hours(self.config.digest.hours as i64);
+        let digest = Digest::build(&db, since, until)?;
+        self.notifier()?.notify(&digest.notification());
+        Ok(())
+    }
+
+    /// Start a run of `job` in the background and watch it until it exits
+    fn launch(&mut self, job: &ScheduleConfig) -> Result<()> {
+        let task_id = format!("schedule-{}", job.name);
+        let db = Database::open(&self.db_path)?;
+
+        if let Some(task) = db.get_task_by_id(&task_id)? {
+            if task.status == TaskStatus::Running
+                && task.pid.is_some_and(|pid| ProcProbe.is_alive(pid))
+            {
+                eprintln!("Skipping '{}': previous run is still going", job.name);
+                return Ok(());
+            }
+        }
+
+        let cwd = match &job.cwd {
+            Some(cwd) => cwd.clone(),
+            None => std::env::current_dir()?.to_string_lossy().into_owned(),
+        };
+        let title = job.title.clone().unwrap_or_else(|| job.name.clone());
+        let mut task = Task::new(
+            task_id,
+            job.agent_type.clone(),
+            title,
+            None,
+            Some(std::process::id() as i32),
+        );
+        task.context = Some(TaskContext {
+            url: None,
+            project_path: Some(cwd),
+            session_id: None,
+            extra: HashMap::from([("schedule".to_string(), job.cron.clone().into())]),
+        });
+        task.set_host(&crate::host::current(&self.config.notify.host));
+        if !job.tags.is_empty() {
+            task.set_tags(&job.tags);
+        }
+
+        let (capture_config, lease) = self.capture_config();
+        let (child, capture, fifo) = spawn_command(
+            &task.task_id,
+            &job.command,
+            job.cwd.as_deref(),
+            &BTreeMap::new(),
+            self.responder.applies_to(&task),
+            &capture_config,
+        )?;
+        task.pid = Some(child.id() as i32);
+        db.register_task(&task)?;
+        db.log_event(
+            &task.task_id,
+            EventKind::Started,
+            format!("Scheduled run ({}), pid {}", job.cron, child.id()),
+        );
+
+        self.watch(child, task.task_id, capture, lease, fifo, job.notify)
+    }
+
+    /// Start queued tasks whose predecessor succeeded and skip those whose
+    /// predecessor failed. Tasks of a full group wait, oldest first.
+    fn start_ready(&mut self) -> Result<()> {
+        let db = Database::open(&self.db_path)?;
+        let mut slots = group::Slots::new(
+            &self.config.groups,
+            &db.list_tasks(Some(TaskStatus::Running))?,
+        );
+        let mut queued = db.list_tasks(Some(TaskStatus::Queued))?;
+        queued.sort_by_key(|task| task.created_at);
+        for mut task in queued {
+            let Some(run) = QueuedRun::of(&task) else {
+                continue;
+            };
+            let predecessor = match &run.after {
+                Some(after) => db.get_task_by_id(after)?,
+                None => None,
+            };
+
+            match chain::gate(run.after.as_deref(), predecessor.as_ref()) {
+                Gate::Wait => {}
+                Gate::Skip(reason) => {
+                    task.set_exited(None);
+                    db.log_event(&task.task_id, EventKind::Exited, reason.clone());
+                    task.attention_reason = Some(reason);
+                    db.update_task(&task)?;
+                    self.hooks.fire(&db, &task);
+                }
+                Gate::Start if !slots.take(run.group.as_deref()) => {
+                    if !group::waiting(&task) {
+                        group::set_waiting(&mut task, true);
+                        db.update_task(&task)?;
+                        let name = run.group.as_deref().unwrap_or_default();
+                        db.log_event(
+                            &task.task_id,
+                            EventKind::Queued,
+                            format!(
+                                "Waiting for a slot in group {} ({})",
+                                name,
+                                slots.describe(name)
+                            ),
+                        );
+                    }
+                }
+                Gate::Start => {
+                    let (capture_config, lease) = self.capture_config();
+                    let (child, capture, fifo) = match spawn_command(
+                        &task.task_id,
+                        &run.command,
+                        run.cwd.as_deref(),
+                        &run.env,
+                        self.responder.applies_to(&task),
+                        &capture_config,
+                    ) {
+                        Ok(started) => started,
+                        Err(e) => {
+                            let reason = format!("Failed to start: {:#}", e);
+                            task.set_exited(None);
+                            db.log_event(&task.task_id, EventKind::Exited, reason.clone());
+                            task.attention_reason = Some(reason);
+                            db.update_task(&task)?;
+                            self.hooks.fire(&db, &task);
+                            continue;
+                        }
+                    };
+                    let now = Utc::now();
+                    task.set_running();
+                    task.created_at = now;
+                    task.pid = Some(child.id() as i32);
+                    task.ppid = Some(std::process::id() as i32);
+                    let waited = group::waiting(&task);
+                    group::set_waiting(&mut task, false);
+                    db.update_task(&task)?;
+                    db.log_event(
+                        &task.task_id,
+                        EventKind::Started,
+                        format!("pid {}", child.id()),
+                    );
+                    if let Some(name) = run.group.as_deref().filter(|_| waited) {
+                        self.announce(&group::started(&task, name, &slots));
+                    }
+
+                    self.watch(child, task.task_id, capture, lease, fifo, true)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Monitor a started run, record its outcome and send the completion
+    /// notification, all in the background
+    fn watch(
+        &self,
+        mut child: Child,
+        task_id: String,
+        capture: OutputCapture,
+        lease: Lease,
+        fifo: Option<Fifo>,
+        notify: bool,
+    ) -> Result<()> {
+        let pid = child.id() as i32;
+        let log_path = capture.log_path().to_path_buf();
+        // The run's outcome is recorded below, and fires the hooks there
+        let db = Database::open(&self.db_path)?;
+        let mut builder = MonitorBuilder::from_config(db, &self.config)?
+            .capture(capture.clone())
+            .hooks(Hooks::default());
+        if self.limits.slowed() {
+            // Monitors poll as often as the daemon by default
+            builder = builder.poll_interval(self.limits.interval(self.poll_interval));
+        }
+        if let Some(stdin) = child.stdin.take() {
+            builder = builder.input(Box::new(PipeInput::new(stdin)));
+        }
+        if let Some(reader) = fifo.as_ref().and_then(|fifo| fifo.reader().ok()) {
+            builder = builder.fifo(reader);
+        }
+        let monitor = builder.build();
+        let db_path = self.db_path.clone();
+        let summary_config = self.config.summary.clone();
+        let secrets = SecretFilter::from_config(&self.config.notify.redact)?;
+        let hooks = self.hooks.clone();
+        let artifacts_config = self.config.artifacts.clone();
+        let crash_loop_window =
+            chrono::Duration::minutes(self.config.daemon.crash_loop_minutes as i64);
+
+        thread::spawn(move || {
+            let monitor_thread = {
+                let task_id = task_id.clone();
+                thread::spawn(move || {
+                    if let Err(e) = monitor.monitor_task(task_id, pid, Some(log_path)) {
+                        eprintln!("Monitor failed: {:#}", e);
+                    }
+                    monitor
+                })
+            };
+
+            let exit_code = child.wait().ok().and_then(|status| status.code());
+            drop(fifo);
+            // The summary needs the last lines of output in the log
+            capture.drain(Duration::from_secs(2));
+            drop(lease);
+            let finished = Database::open(&db_path).and_then(|db| {
+                let mut task = db
+                    .get_task_by_id(&task_id)?
+                    .context("Task disappeared while running")?;
+                let restart = match (exit_code, QueuedRun::of(&task).and_then(|run| run.restart)) {
+                    (Some(0), _) | (_, None) => None,
+                    (code, Some(policy)) => Some(restart::on_failure(
+                        &mut task,
+                        policy,
+                        code,
+                        Utc::now(),
+                        crash_loop_window,
+                    )),
+                };
+                match exit_code {
+                    Some(0) => task.complete(),
+                    code => task.set_exited(code),
+                }
+                capture::record_stats(&mut task, capture.stats());
+                if let Some(Restart::Again(failures)) = restart {
+                    // Started again by `start_ready` on the daemon's next pass
+                    task.status = TaskStatus::Queued;
+                    task.pid = None;
+                    db.update_task(&task)?;
+                    let code = exit_code
+                        .map_or("a signal".to_string(), |code| format!("exit code {}", code));
+                    db.log_event(
+                        &task_id,
+                        EventKind::Queued,
+                        format!(
+                            "Failed with {}, restarting ({} in the last {} min)",
+                            code,
+                            failures,
+                            crash_loop_window.num_minutes()
+                        ),
+                    );
+                    return Ok((db, None));
+                }
+                task.set_test_results(
+                    summary::task_test_results(&summary_config, &secrets, &task_id).as_ref(),
+                );
+                db.update_task(&task)?;
+                let (kind, message) = match exit_code {
+                    Some(0) => (EventKind::Completed, "Exit code 0".to_string()),
+                    Some(code) => (EventKind::Exited, format!("Exit code {}", code)),
+                    None => (EventKind::Exited, "Killed by a signal".to_string()),
+                };
+                db.log_event(&task_id, kind, message);
+                hooks.fire(&db, &task);
+                let archived =
+                    artifacts::collect(&artifacts_config, &db, &task).unwrap_or_else(|e| {
+                        eprintln!("Failed to collect artifacts of {}: {:#}", task_id, e);
+                        None
+                    });
+                let notification = match restart {
+                    Some(Restart::CrashLoop(failures)) => {
+                        let output = output::read_tail(output::default_log_path(&task_id), 4096);
+                        Some(restart::crash_loop_notification(
+                            &task,
+                            &failures,
+                            crash_loop_window,
+                            output.as_deref(),
+                        ))
+                    }
+                    _ => completion_notification(&db, &task, exit_code, &summary_config, &secrets)?,
+                };
+                let notification = notification.map(|mut n| {
+                    if let Some(dir) = &archived {
+                        n.body = format!("{}\n{}", n.body, artifacts::notification_line(dir));
+                    }
+                    n
+                });
+                Ok((db, notification))
+            });
+
+            let Ok(monitor) = monitor_thread.join() else {
+                return;
+            };
+            match finished {
+                Ok((db, Some(notification))) if notify => {
+                    let delivered = monitor.notifier().notify(&notification);
+                    let (kind, message) = notify::delivery_event(&notification, &delivered);
+                    db.log_event(&task_id, kind, message);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Failed to record run of {}: {:#}", task_id, e),
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// Spawn `command` with its output captured to the task's log. Stdin is a
+/// pipe when auto-respond rules may need to answer prompts, else /dev/null.
+/// The command finds its FIFO in `$AGENT_NOTIFY_FIFO`, unless one couldn't
+/// be made.
+fn spawn_command(
+    task_id: &str,
+    command: &str,
+    cwd: Option<&str>,
+    env: &BTreeMap<String, String>,
+    pipe_stdin: bool,
+    config: &CaptureConfig,
+) -> Result<(Child, OutputCapture, Option<Fifo>)> {
+    // Each run starts with a fresh log so summaries describe this run only
+    let log_path = output::default_log_path(task_id);
+    if let Some(dir) = log_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let fifo = match Fifo::create(&fifo::dir(), task_id) {
+        Ok(fifo) => Some(fifo),
+        Err(e) => {
+            eprintln!("Failed to make a FIFO for {}: {}", task_id, e);
+            None
+        }
+    };
+
+    let mut process = Command::new("sh");
+    process
+        .arg("-c")
+        .arg(command)
+        .envs(env)
+        .stdin(if pipe_stdin {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(cwd) = cwd {
+        process.current_dir(cwd);
+    }
+    if let Some(fifo) = &fifo {
+        process.env(fifo::ENV, fifo.path());
+    }
+    let mut child = process
+        .spawn()
+        .with_context(|| format!("Failed to run '{}'", command))?;
+    let capture = OutputCapture::start(&mut child, &log_path, config)?;
+    Ok((child, capture, fifo))
+}
+
+/// Completion notification for a finished run, or None while its chain
+/// continues (only the end of a pipeline notifies)
+fn completion_notification(
+    db: &Database,
+    task: &Task,
+    exit_code: Option<i32>,
+    summary_config: &SummaryConfig,
+    secrets: &SecretFilter,
+) -> Result<Option<Notification>> {
+    let failed = exit_code != Some(0);
+    let (upstream, downstream) = chain::neighbours(db, task)?;
+    let continues = downstream.iter().any(|t| t.status == TaskStatus::Queued);
+    if !failed && continues {
+        return Ok(None);
+    }
+
+    let (title, summary) =
+        summary::completion_message(summary_config, secrets, db, task, exit_code);
+    let (urgency, category) = if failed {
+        (Urgency::Critical, Category::Failed)
+    } else {
+        (Urgency::Normal, Category::Completed)
+    };
+    let notification = if upstream.is_empty() && downstream.is_empty() {
+        Notification {
+            title,
+            body: summary,
+            urgency,
+            category,
+            task_id: Some(task.task_id.clone()),
+            reason: None,
+        }
+    } else if failed {
+        Notification {
+            title: format!("Pipeline failed at {}", task.title),
+            body: format!(
+                "{}\n{}",
+                chain::describe(&upstream, task, &downstream, true),
+                summary
+            ),
+            urgency,
+            category,
+            task_id: Some(task.task_id.clone()),
+            reason: None,
+        }
+    } else {
+        Notification {
+            title: format!(
+                "Pipeline finished: {}",
+                chain::describe(&upstream, task, &downstream, false)
+            ),
+            body: summary,
+            urgency,
+            category,
+            task_id: Some(task.task_id.clone()),
+            reason: None,
+        }
+    };
+    Ok(Some(notification))
+}
+
+/// Join command-line arguments into a single `sh -c` string
+pub fn shell_join(args: &[String]) -> String {
+    if let [single] = args {
+        // A lone argument is already a shell command: `queue -- 'make && make test'`
+        return single.clone();
+    }
+    args.iter()
+        .map(|arg| {
+            let plain = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+            if plain {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DigestConfig;
+    use chrono::NaiveDate;
+
+    fn job(name: &str, cron: &str) -> ScheduleConfig {
+        ScheduleConfig {
+            name: name.to_string(),
+            cron: cron.to_string(),
+            command: "true".to_string(),
+            cwd: None,
+            title: None,
+            agent_type: "scheduled".to_string(),
+            notify: false,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_due_jobs_fire_once_per_minute() {
+        let config = Config {
+            schedule: vec![job("nightly", "0 3 * * *"), job("often", "*/5 * * * *")],
+            digest: DigestConfig {
+                cron: Some("0 8 * * *".to_string()),
+                hours: 24,
+            },
+            ..Config::default()
+        };
+        let mut daemon = Daemon::new(config, PathBuf::from("/nonexistent/tasks.db")).unwrap();
+        let at = |h, m, s| {
+            NaiveDate::from_ymd_opt(2024, 3, 4)
+                .unwrap()
+                .and_hms_opt(h, m, s)
+                .unwrap()
+        };
+
+        let names =
+            |jobs: Vec<ScheduleConfig>| jobs.into_iter().map(|j| j.name).collect::<Vec<_>>();
+        assert_eq!(
+            names(daemon.due_jobs(at(3, 0, 1))),
+            vec!["nightly", "often"]
+        );
+        assert!(daemon.due_jobs(at(3, 0, 40)).is_empty());
+        assert!(daemon.due_jobs(at(3, 1, 0)).is_empty());
+        assert_eq!(names(daemon.due_jobs(at(3, 5, 2))), vec!["often"]);
+        assert!(!daemon.digest_due);
+
+        daemon.due_jobs(at(8, 0, 3));
+        assert!(daemon.digest_due);
+    }
+
+    #[test]
+    fn test_only_the_end_of_a_chain_notifies() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let db = Database::open(file.path()).unwrap();
+        let summary = SummaryConfig {
+            summarizer: "none".to_string(),
+            ..SummaryConfig::default()
+        };
+        let queue = |id: &str, after: Option<&str>| {
+            let task = Task::new(
+                id.to_string(),
+                "queued".to_string(),
+                id.to_string(),
+                None,
+                None,
+            );
+            let run = QueuedRun {
+                command: "true".to_string(),
+                cwd: None,
+                after: after.map(str::to_string),
+                restart: None,
+                group: None,
+                env: Default::default(),
+            };
+            chain::queue_task(&db, task, run).unwrap()
+        };
+        let finish = |mut task: Task, code: i32| {
+            if code == 0 {
+                task.complete();
+            } else {
+                task.set_exited(Some(code));
+            }
+            db.update_task(&task).unwrap();
+            completion_notification(&db, &task, Some(code), &summary, &SecretFilter::default())
+                .unwrap()
+        };
+
+        let lint = queue("lint", None);
+        let tests = queue("tests", Some("lint"));
+        let deploy = queue("deploy-agent", Some("tests"));
+
+        assert!(finish(lint, 0).is_none());
+        assert!(finish(tests, 0).is_none());
+        let done = finish(deploy.clone(), 0).unwrap();
+        assert_eq!(done.title, "Pipeline finished: lint → tests → deploy-agent");
+
+        let failed = finish(deploy, 1).unwrap();
+        assert_eq!(failed.title, "Pipeline failed at deploy-agent");
+        assert!(failed.body.starts_with("lint → tests → deploy-agent ✗"));
+        assert_eq!(failed.urgency, Urgency::Critical);
+
+        let standalone = queue("solo", None);
+        let solo = finish(standalone, 0).unwrap();
+        assert_eq!(solo.title, "✅ [queued] solo");
+    }
+
+    #[test]
+    fn test_shell_join() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            shell_join(&args(&["make lint && make test"])),
+            "make lint && make test"
+        );
+        assert_eq!(
+            shell_join(&args(&["cargo", "test", "--all"])),
+            "cargo test --all"
+        );
+        assert_eq!(
+            shell_join(&args(&["claude", "-p", "don't stop"])),
+            "claude -p 'don'\\''t stop'"
+        );
+    }
+
+    #[test]
+    fn test_invalid_schedules_rejected() {
+        let config = Config {
+            schedule: vec![job("a", "@daily"), job("a", "@hourly")],
+            ..Config::default()
+        };
+        assert!(Daemon::new(config, PathBuf::new()).is_err());
+
+        let config = Config {
+            schedule: vec![job("bad", "every night")],
+            ..Config::default()
+        };
+        assert!(Daemon::new(config, PathBuf::new()).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! Taking stock of the task store when the daemon starts
+//!
+//! Reboots, crashed monitors and closed terminals leave tasks marked running
+//! or completed whose processes ended long ago, with nobody told. On start
+//! the daemon checks each task that has a pid against the process table:
+//!
+//! - the process is gone: the task finished while unmonitored. It is marked
+//!   exited, with the exit code unknown, and a catch-up notification goes out.
+//! - the process is alive and nothing watches it: the daemon monitors it.
+//! - the process is alive but runs another program than the one captured at
+//!   registration: the pid may have been reused, or the agent re-executed
+//!   itself. Nothing is inferred; the task is flagged in `list` until
+//!   `report running` or `report exited` settles it.
+//!
+//! A completed task has already been notified about and waits only for its
+//! process to end: once it has, the task is marked exited, quietly; while
+//! it lives, it is left alone.
+//!
+//! A task is watched while the monitor that recorded itself on the task is
+//! alive, or, for tasks no monitor has recorded itself on, while the process
+//! that registered it is.
+
+use crate::models::{Task, TaskStatus};
+use crate::monitor::probe::ProcessProbe;
+use crate::notify::{Category, Notification, Urgency};
+
+/// What to do about a task at startup
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    /// Another process watches it
+    Watched,
+    /// Its process is alive and unwatched: monitor it
+    Resume(i32),
+    /// Its process is gone
+    Gone,
+    /// Its process ended after the task completed, which the user was told
+    /// about: mark it exited, nothing more
+    Ended,
+    /// Its pid may not be the task any more, for the reason given
+    Unclear(String),
+}
+
+/// The verdict on `task`, for a daemon with pid `me`; None for tasks there
+/// is nothing to check about
+pub fn assess(task: &Task, probe: &dyn ProcessProbe, me: i32) -> Option<Verdict> {
+    if !matches!(task.status, TaskStatus::Running | TaskStatus::Completed) {
+        return None;
+    }
+    let pid = task.pid?;
+    let watcher = task.monitor_pid.or(task.ppid);
+    if watcher.is_some_and(|watcher| watcher != me && probe.is_alive(watcher)) {
+        return Some(Verdict::Watched);
+    }
+    if task.status == TaskStatus::Completed {
+        return (!probe.is_alive(pid)).then_some(Verdict::Ended);
+    }
+    if !probe.is_alive(pid) {
+        return Some(Verdict::Gone);
+    }
+    let registered = task.process().and_then(|process| process.command_line);
+    if let (Some(registered), Some(now)) = (registered, probe.command_line(pid)) {
+        if program(&registered) != program(&now) {
+            return Some(Verdict::Unclear(format!(
+                "pid {} now runs {}",
+                pid,
+                program(&now)
+            )));
+        }
+    }
+    Some(Verdict::Resume(pid))
+}
+
+/// The program a command line runs, without its directory
+fn program(command_line: &str) -> &str {
+    let first = command_line.split_whitespace().next().unwrap_or_default();
+    first.rsplit('/').next().unwrap_or(first)
+}
+
+/// Tells the user that `task`, last heard from at its `updated_at`, ended
+/// while nothing watched it
+pub fn catch_up(task: &Task) -> Notification {
+    Notification {
+        title: format!("[{}] {}", task.agent_type, task.title),
+        body: format!(
+            "Finished while unmonitored, some time after {}; exit code unknown",
+            crate::datetime::times().absolute(task.updated_at)
+        ),
+        urgency: Urgency::Normal,
+        category: Category::Completed,
+        task_id: Some(task.task_id.clone()),
+        reason: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProcessSnapshot;
+    use std::collections::HashMap;
+
+    /// Live processes by pid, with their command lines
+    struct Processes(HashMap<i32, &'static str>);
+
+    impl ProcessProbe for Processes {
+        fn is_alive(&self, pid: i32) -> bool {
+            self.0.contains_key(&pid)
+        }
+
+        fn cpu_time(&self, _pid: i32) -> Option<u64> {
+            None
+        }
+
+        fn command_line(&self, pid: i32) -> Option<String> {
+            self.0.get(&pid).map(|line| line.to_string())
+        }
+    }
+
+    fn task(pid: Option<i32>, ppid: Option<i32>, monitor_pid: Option<i32>) -> Task {
+        let mut task = Task::new(
+            "t1".into(),
+            "claude_code".into(),
+            "Refactor".into(),
+            pid,
+            ppid,
+        );
+        task.monitor_pid = monitor_pid;
+        task.set_process(&ProcessSnapshot {
+            command_line: Some("/usr/bin/claude --resume".into()),
+            ..Default::default()
+        });
+        task
+    }
+
+    #[test]
+    fn test_assess() {
+        let me = 1;
+        let probe = Processes(HashMap::from([
+            (10, "claude --resume"),
+            (11, "vim notes.md"),
+            (20, "bash"),
+        ]));
+
+        assert_eq!(
+            assess(&task(Some(10), Some(20), None), &probe, me),
+            Some(Verdict::Watched)
+        );
+        assert_eq!(
+            assess(&task(Some(10), Some(20), Some(30)), &probe, me),
+            Some(Verdict::Resume(10))
+        );
+        assert_eq!(
+            assess(&task(Some(10), Some(me), None), &probe, me),
+            Some(Verdict::Resume(10))
+        );
+        assert_eq!(
+            assess(&task(Some(12), None, Some(30)), &probe, me),
+            Some(Verdict::Gone)
+        );
+        assert_eq!(
+            assess(&task(Some(11), None, None), &probe, me),
+            Some(Verdict::Unclear("pid 11 now runs vim".into()))
+        );
+        assert_eq!(assess(&task(None, None, None), &probe, me), None);
+
+        // Completed tasks are only marked exited once their process ends
+        let mut completed = task(Some(12), None, None);
+        completed.complete();
+        assert_eq!(assess(&completed, &probe, me), Some(Verdict::Ended));
+        completed.pid = Some(10);
+        assert_eq!(assess(&completed, &probe, me), None);
+        completed.ppid = Some(20);
+        assert_eq!(assess(&completed, &probe, me), Some(Verdict::Watched));
+
+        let mut exited = task(Some(12), None, None);
+        exited.set_exited(Some(0));
+        assert_eq!(assess(&exited, &probe, me), None);
+    }
+}
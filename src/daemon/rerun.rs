@@ -0,0 +1,207 @@
+//! Starting a task again: `agent-inbox rerun <task>`
+//!
+//! After fixing whatever an agent got stuck on, the same run should be one
+//! command away. The re-run is a new task queued to the daemon with what
+//! the earlier one was started with:
+//!
+//! - the command and working directory: those it was queued with, its
+//!   `[[schedule]]` entry, or else the command line recorded when it
+//!   registered (refused if secrets were redacted from it)
+//! - the recorded `[environment] vars`, set again in its environment
+//! - its agent type, title, priority, tags, unattended action, startup
+//!   grace, group and restart policy, so the same `[[rules]]` and routing
+//!   apply
+//!
+//! The new task records the run it repeats (shown by `show`), and both
+//! activity logs mention the other.
+
+use super::chain::{self, QueuedRun};
+use crate::config::Config;
+use crate::db::Database;
+use crate::models::{EventKind, Task, TaskContext};
+use anyhow::{bail, Result};
+
+/// Metadata key of the task a re-run repeats
+const RERUN_OF: &str = "rerun_of";
+
+/// Metadata carried over to the re-run
+const CARRIED: [&str; 5] = [
+    "priority",
+    "tags",
+    "unattended",
+    "startup_grace_secs",
+    "process",
+];
+
+/// The task `task` is a re-run of, if any
+pub fn rerun_of(task: &Task) -> Option<&str> {
+    task.metadata.as_ref()?.get(RERUN_OF)?.as_str()
+}
+
+/// What the daemon should run to repeat `task`
+pub fn command(task: &Task, config: &Config) -> Result<QueuedRun> {
+    let env = task.process().map(|p| p.env).unwrap_or_default();
+    if let Some(run) = QueuedRun::of(task) {
+        // The predecessor already ran; the re-run starts right away
+        return Ok(QueuedRun { after: None, ..run });
+    }
+    if let Some(name) = task.task_id.strip_prefix("schedule-") {
+        if let Some(job) = config.schedule.iter().find(|job| job.name == name) {
+            return Ok(QueuedRun {
+                command: job.command.clone(),
+                cwd: job.cwd.clone(),
+                after: None,
+                restart: None,
+                group: None,
+                env,
+            });
+        }
+    }
+    let Some(command) = task.process().and_then(|p| p.command_line) else {
+        bail!("Don't know what {} was started with: it wasn't queued and no command line was recorded", task.task_id);
+    };
+    if command.contains("[REDACTED]") {
+        bail!(
+            "The recorded command line of {} had secrets redacted; queue it again yourself",
+            task.task_id
+        );
+    }
+    Ok(QueuedRun {
+        command,
+        cwd: task.context.as_ref().and_then(|c| c.project_path.clone()),
+        after: None,
+        restart: None,
+        group: None,
+        env,
+    })
+}
+
+/// Queue a re-run of `task` as `task_id`
+pub fn rerun(db: &Database, config: &Config, task: &Task, task_id: String) -> Result<Task> {
+    let run = command(task, config)?;
+    let mut rerun = Task::new(
+        task_id,
+        task.agent_type.clone(),
+        task.title.clone(),
+        None,
+        None,
+    );
+    let metadata = rerun.metadata.get_or_insert_with(Default::default);
+    for key in CARRIED {
+        if let Some(value) = task.metadata.as_ref().and_then(|m| m.get(key)) {
+            metadata.insert(key.to_string(), value.clone());
+        }
+    }
+    metadata.insert(RERUN_OF.to_string(), task.task_id.clone().into());
+    rerun.set_host(&crate::host::current(&config.notify.host));
+    rerun.context = Some(TaskContext {
+        url: task.context.as_ref().and_then(|c| c.url.clone()),
+        project_path: run.cwd.clone(),
+        session_id: None,
+        extra: task
+            .context
+            .as_ref()
+            .map(|c| c.extra.clone())
+            .unwrap_or_default(),
+    });
+
+    let rerun = chain::queue_task(db, rerun, run)?;
+    db.log_event(
+        &rerun.task_id,
+        EventKind::Queued,
+        format!("Re-run of {}", task.task_id),
+    );
+    db.log_event(
+        &task.task_id,
+        EventKind::Queued,
+        format!("Re-run as {}", rerun.task_id),
+    );
+    Ok(rerun)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScheduleConfig;
+    use crate::models::{Priority, ProcessSnapshot, TaskStatus};
+    use std::collections::BTreeMap;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_rerun() {
+        let file = NamedTempFile::new().unwrap();
+        let db = Database::open(file.path()).unwrap();
+        let mut config = Config::default();
+
+        let mut task = Task::new(
+            "fix".into(),
+            "claude_code".into(),
+            "Fix the build".into(),
+            Some(1),
+            None,
+        );
+        task.set_priority(Priority::High);
+        task.set_tags(&["ci".to_string()]);
+        task.set_process(&ProcessSnapshot {
+            command_line: Some("claude -p 'fix the build'".to_string()),
+            env: BTreeMap::from([("MODEL".to_string(), "opus".to_string())]),
+        });
+        task.context = Some(TaskContext {
+            url: None,
+            project_path: Some("/src/api".to_string()),
+            session_id: Some("s1".to_string()),
+            extra: Default::default(),
+        });
+        task.set_exited(Some(1));
+        db.register_task(&task).unwrap();
+
+        let rerun = rerun(&db, &config, &task, "fix-2".into()).unwrap();
+        assert_eq!(rerun.status, TaskStatus::Queued);
+        assert_eq!(
+            (rerun.title.as_str(), rerun.priority(), rerun.tags()),
+            ("Fix the build", Priority::High, vec!["ci".to_string()])
+        );
+        assert_eq!(rerun_of(&rerun), Some("fix"));
+        assert_eq!(rerun.context.as_ref().unwrap().session_id, None);
+        let run = QueuedRun::of(&rerun).unwrap();
+        assert_eq!(run.command, "claude -p 'fix the build'");
+        assert_eq!(run.cwd.as_deref(), Some("/src/api"));
+        assert_eq!(run.env["MODEL"], "opus");
+        let events = db.task_events("fix").unwrap();
+        assert_eq!(events.last().unwrap().message, "Re-run as fix-2");
+
+        // A re-run of a re-run repeats the queued command, without waiting
+        let mut queued = rerun.clone();
+        let mut run = QueuedRun::of(&queued).unwrap();
+        run.after = Some("lint".to_string());
+        queued
+            .metadata
+            .as_mut()
+            .unwrap()
+            .insert("queued".into(), serde_json::to_value(&run).unwrap());
+        assert_eq!(command(&queued, &config).unwrap().after, None);
+
+        let job: ScheduleConfig =
+            toml::from_str("name = 'nightly'\ncron = '@daily'\ncommand = 'make nightly'").unwrap();
+        config.schedule.push(job);
+        let scheduled = Task::new(
+            "schedule-nightly".into(),
+            "scheduled".into(),
+            "nightly".into(),
+            None,
+            None,
+        );
+        assert_eq!(
+            command(&scheduled, &config).unwrap().command,
+            "make nightly"
+        );
+
+        task.set_process(&ProcessSnapshot {
+            command_line: Some("deploy --token [REDACTED]".to_string()),
+            env: BTreeMap::new(),
+        });
+        assert!(command(&task, &config).is_err());
+        let bare = Task::new("bare".into(), "opencode".into(), "bare".into(), None, None);
+        assert!(command(&bare, &config).is_err());
+    }
+}
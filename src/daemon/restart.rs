@@ -0,0 +1,204 @@
+//! Restarting queued tasks that fail, with crash-loop detection
+//!
+//! `agent-inbox queue --restart on-failure[:max]` stores a [`RestartPolicy`]
+//! with the queued command. When a run fails, the task goes back to Queued
+//! instead of exiting, and the daemon starts it again on its next pass.
+//! Failures are remembered in the task's metadata. Once more than `max`
+//! fall within `[daemon] crash_loop_minutes`, the task is left exited and a
+//! critical notification quotes the last exit codes and output.
+
+use crate::models::Task;
+use crate::notify::{Category, Notification, Urgency};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Restarts allowed within the window when `on-failure` gives no count
+pub const DEFAULT_MAX_RESTARTS: u32 = 5;
+
+/// Output lines quoted in the crash-loop notification
+const TAIL_LINES: usize = 10;
+
+/// `on-failure[:max]`: start the command again when it fails, at most
+/// `max` times within the crash-loop window
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct RestartPolicy {
+    pub max: u32,
+}
+
+impl RestartPolicy {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid restart policy: {} (on-failure or on-failure:<max>)",
+                s
+            )
+        };
+        let max = match s.split_once(':') {
+            None if s == "on-failure" => DEFAULT_MAX_RESTARTS,
+            Some(("on-failure", max)) => max.parse().map_err(|_| invalid())?,
+            _ => return Err(invalid()),
+        };
+        Ok(Self { max })
+    }
+}
+
+/// A failed run, kept in the task's metadata
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Failure {
+    pub at: DateTime<Utc>,
+    pub exit_code: Option<i32>,
+}
+
+/// What to do after a failed run
+#[derive(Debug, PartialEq)]
+pub enum Restart {
+    /// Start it again; this many failures are within the window
+    Again(usize),
+    /// Too many failures within the window, oldest first
+    CrashLoop(Vec<Failure>),
+}
+
+/// Failures within the crash-loop window, oldest first
+pub fn failures(task: &Task) -> Vec<Failure> {
+    task.metadata
+        .as_ref()
+        .and_then(|m| m.get("failures"))
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+/// Record a failed run of `task` and decide whether to start it again
+pub fn on_failure(
+    task: &mut Task,
+    policy: RestartPolicy,
+    exit_code: Option<i32>,
+    now: DateTime<Utc>,
+    window: Duration,
+) -> Restart {
+    let mut recent: Vec<Failure> = failures(task)
+        .into_iter()
+        .filter(|failure| now - failure.at < window)
+        .collect();
+    recent.push(Failure { at: now, exit_code });
+    if let Ok(value) = serde_json::to_value(&recent) {
+        task.metadata
+            .get_or_insert_with(Default::default)
+            .insert("failures".to_string(), value);
+    }
+
+    if recent.len() > policy.max as usize {
+        Restart::CrashLoop(recent)
+    } else {
+        Restart::Again(recent.len())
+    }
+}
+
+/// Critical notification sent when restarting stops
+pub fn crash_loop_notification(
+    task: &Task,
+    failures: &[Failure],
+    window: Duration,
+    output: Option<&str>,
+) -> Notification {
+    let codes: Vec<String> = failures
+        .iter()
+        .map(|failure| match failure.exit_code {
+            Some(code) => code.to_string(),
+            None => "signal".to_string(),
+        })
+        .collect();
+    let mut body = format!(
+        "Failed {} times in {} min, not restarting. Exit codes: {}",
+        failures.len(),
+        window.num_minutes(),
+        codes.join(", ")
+    );
+    if let Some(output) = output {
+        let lines: Vec<&str> = output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        let tail = &lines[lines.len().saturating_sub(TAIL_LINES)..];
+        if !tail.is_empty() {
+            body = format!("{}\n{}", body, tail.join("\n"));
+        }
+    }
+
+    Notification {
+        title: format!("🔁 Crash loop: {}", task.title),
+        body,
+        urgency: Urgency::Critical,
+        category: Category::Failed,
+        task_id: Some(task.task_id.clone()),
+        reason: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(
+            RestartPolicy::from_str("on-failure"),
+            Ok(RestartPolicy {
+                max: DEFAULT_MAX_RESTARTS
+            })
+        );
+        assert_eq!(
+            RestartPolicy::from_str("on-failure:2"),
+            Ok(RestartPolicy { max: 2 })
+        );
+        assert!(RestartPolicy::from_str("always").is_err());
+        assert!(RestartPolicy::from_str("on-failure:x").is_err());
+    }
+
+    #[test]
+    fn test_crash_loop_after_max_failures_in_window() {
+        let mut task = Task::new(
+            "flaky".to_string(),
+            "queued".to_string(),
+            "flaky".to_string(),
+            None,
+            None,
+        );
+        let policy = RestartPolicy { max: 2 };
+        let window = Duration::minutes(10);
+        let start = Utc::now();
+        let at = |minutes| start + Duration::minutes(minutes);
+
+        assert_eq!(
+            on_failure(&mut task, policy, Some(1), at(0), window),
+            Restart::Again(1)
+        );
+        assert_eq!(
+            on_failure(&mut task, policy, Some(1), at(5), window),
+            Restart::Again(2)
+        );
+        // The first failure has left the window
+        assert_eq!(
+            on_failure(&mut task, policy, None, at(10), window),
+            Restart::Again(2)
+        );
+        let Restart::CrashLoop(failures) = on_failure(&mut task, policy, Some(2), at(12), window)
+        else {
+            panic!("expected a crash loop");
+        };
+        assert_eq!(failures.len(), 3);
+
+        let notification = crash_loop_notification(
+            &task,
+            &failures,
+            window,
+            Some("building\npanicked at 'boom'\n\n"),
+        );
+        assert_eq!(notification.title, "🔁 Crash loop: flaky");
+        assert_eq!(
+            notification.body,
+            "Failed 3 times in 10 min, not restarting. Exit codes: 1, signal, 2\nbuilding\npanicked at 'boom'"
+        );
+        assert_eq!(notification.urgency, Urgency::Critical);
+    }
+}
@@ -0,0 +1,161 @@
+//! Cron-style schedule expressions
+//!
+//! Supports the classic five fields (minute, hour, day of month, month, day
+//! of week) with `*`, lists, ranges and steps, plus the usual `@daily`-style
+//! shortcuts. As in cron, when both day fields are restricted a time matches
+//! if either of them does; a field starting with `*`, like `*/2`, doesn't
+//! count as restricted.
+
+use anyhow::{Context, Result};
+use chrono::{Datelike, NaiveDateTime, Timelike};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl Schedule {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let expr = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            anyhow::bail!(
+                "Schedule '{}' must have 5 fields (minute hour day month weekday)",
+                expr
+            );
+        }
+
+        let mut days_of_week = parse_field(fields[4], 0, 7).context("Invalid day of week")?;
+        // Both 0 and 7 mean Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week |= 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59).context("Invalid minute")?,
+            hours: parse_field(fields[1], 0, 23).context("Invalid hour")?,
+            days_of_month: parse_field(fields[2], 1, 31).context("Invalid day of month")?,
+            months: parse_field(fields[3], 1, 12).context("Invalid month")?,
+            days_of_week,
+            dom_restricted: !fields[2].starts_with('*'),
+            dow_restricted: !fields[4].starts_with('*'),
+        })
+    }
+
+    /// Whether the schedule fires during the minute containing `time`
+    pub fn matches(&self, time: &NaiveDateTime) -> bool {
+        let bit = |mask: u64, value: u32| mask & (1 << value) != 0;
+
+        let dom = bit(self.days_of_month, time.day());
+        let dow = bit(self.days_of_week, time.weekday().num_days_from_sunday());
+        let day = match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            _ => dom && dow,
+        };
+
+        day && bit(self.minutes, time.minute())
+            && bit(self.hours, time.hour())
+            && bit(self.months, time.month())
+    }
+}
+
+/// Parse one field into a bitmask of allowed values
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().context("Invalid step")?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            anyhow::bail!("Step must be positive in '{}'", part);
+        }
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (a.parse()?, b.parse()?),
+                // "5/15" means every 15 starting at 5
+                None if part.contains('/') => (range.parse()?, max),
+                None => {
+                    let value = range.parse()?;
+                    (value, value)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            anyhow::bail!("'{}' is outside {}-{}", part, min, max);
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_fields_and_steps() {
+        let schedule = Schedule::parse("*/15 9-17 * * 1-5").unwrap();
+        // 2024-03-04 is a Monday
+        assert!(schedule.matches(&at(2024, 3, 4, 9, 0)));
+        assert!(schedule.matches(&at(2024, 3, 4, 17, 45)));
+        assert!(!schedule.matches(&at(2024, 3, 4, 9, 10)));
+        assert!(!schedule.matches(&at(2024, 3, 4, 18, 0)));
+        assert!(!schedule.matches(&at(2024, 3, 3, 9, 0))); // Sunday
+    }
+
+    #[test]
+    fn test_shortcuts_and_day_union() {
+        let nightly = Schedule::parse("@daily").unwrap();
+        assert!(nightly.matches(&at(2024, 3, 4, 0, 0)));
+        assert!(!nightly.matches(&at(2024, 3, 4, 0, 1)));
+
+        // 1st of the month OR any Sunday
+        let schedule = Schedule::parse("30 2 1 * 7").unwrap();
+        assert!(schedule.matches(&at(2024, 3, 1, 2, 30)));
+        assert!(schedule.matches(&at(2024, 3, 3, 2, 30)));
+        assert!(!schedule.matches(&at(2024, 3, 4, 2, 30)));
+
+        // Odd days AND Mondays: a starred step leaves the day unrestricted
+        let schedule = Schedule::parse("0 9 */2 * 1").unwrap();
+        assert!(!schedule.matches(&at(2024, 3, 4, 9, 0))); // even Monday
+        assert!(!schedule.matches(&at(2024, 3, 5, 9, 0))); // odd Tuesday
+        assert!(schedule.matches(&at(2024, 3, 11, 9, 0))); // odd Monday
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        assert!(Schedule::parse("* * * *").is_err());
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+        assert!(Schedule::parse("5-1 * * * *").is_err());
+        assert!(Schedule::parse("@sometimes").is_err());
+    }
+}
@@ -0,0 +1,111 @@
+//! "Monitoring started" and "monitoring stopped" notifications:
+//! `[daemon.status]`
+//!
+//! A daemon that isn't running watches nothing, and nothing says so. The
+//! daemon therefore announces itself when it starts, with the tasks it took
+//! over, and when it is stopped, with the runs it leaves unwatched. A stop
+//! is SIGINT or SIGTERM; a crash or SIGKILL can't be announced, which is
+//! what the [`watchdog`](super::watchdog) is for.
+
+use crate::config::DaemonStatusConfig;
+use crate::models::{HostInfo, Task};
+use crate::notify::{Category, Notification, Urgency};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// Turn SIGINT and SIGTERM into a request to stop, checked with
+/// [`stop_requested`]
+#[cfg(unix)]
+pub fn catch_stop_signals() {
+    extern "C" fn request_stop(_: libc::c_int) {
+        STOP.store(true, Ordering::SeqCst);
+    }
+    let handler = request_stop as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only stores to an atomic
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn catch_stop_signals() {}
+
+pub fn stop_requested() -> bool {
+    STOP.load(Ordering::SeqCst)
+}
+
+/// Sent once the daemon is up; `resumed` are the tasks it took over
+pub fn started(config: &DaemonStatusConfig, host: &HostInfo, resumed: &[Task]) -> Notification {
+    Notification {
+        title: host
+            .fill(&config.started)
+            .replace("{resumed}", &resumed.len().to_string()),
+        body: list(resumed),
+        urgency: Urgency::Low,
+        category: Category::Info,
+        task_id: None,
+        reason: None,
+    }
+}
+
+/// Sent when the daemon is stopped; `running` are its runs nobody watches
+/// from now on
+pub fn stopped(config: &DaemonStatusConfig, host: &HostInfo, running: &[Task]) -> Notification {
+    Notification {
+        title: host
+            .fill(&config.stopped)
+            .replace("{running}", &running.len().to_string()),
+        body: list(running),
+        // Never held back in a batch or by quiet routing while runs go unwatched
+        urgency: if running.is_empty() {
+            Urgency::Normal
+        } else {
+            Urgency::Critical
+        },
+        category: Category::Info,
+        task_id: None,
+        reason: None,
+    }
+}
+
+fn list(tasks: &[Task]) -> String {
+    tasks
+        .iter()
+        .map(|task| format!("• {}", task.title))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let config = DaemonStatusConfig::default();
+        let host = HostInfo::default();
+        let task = |title: &str| {
+            Task::new(
+                title.to_string(),
+                "shell".to_string(),
+                title.to_string(),
+                None,
+                None,
+            )
+        };
+
+        let notification = started(&config, &host, &[task("nightly"), task("deploy")]);
+        assert_eq!(notification.title, "Monitoring started (resumed 2 tasks)");
+        assert_eq!(notification.body, "• nightly\n• deploy");
+
+        let notification = stopped(&config, &host, &[task("nightly")]);
+        assert_eq!(
+            notification.title,
+            "Monitoring stopped (1 tasks still running, you will NOT be notified)"
+        );
+        assert_eq!(notification.urgency, Urgency::Critical);
+        assert_eq!(stopped(&config, &host, &[]).urgency, Urgency::Normal);
+    }
+}
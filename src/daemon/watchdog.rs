@@ -0,0 +1,160 @@
+//! Restarts the daemon when it dies: `[daemon] watchdog = true`
+//!
+//! The daemon spawns `agent-inbox watchdog`, holding the write end of its
+//! stdin. When the daemon exits for any reason the pipe closes, and the
+//! watchdog starts a new daemon (which spawns its own watchdog) and sends a
+//! "restarted after crash" notification. It uses only the configured
+//! built-in channels, or desktop notifications when the config itself is
+//! broken, since plugins may be what brought the daemon down.
+//!
+//! A daemon that keeps dying within a minute of starting is restarted with
+//! growing delays, and given up on after [`MAX_QUICK_RESTARTS`].
+//!
+//! Stopping the daemon's process group (Ctrl-C, `systemctl stop`) stops the
+//! watchdog along with it. A daemon stopped on its own with SIGTERM says so
+//! on the pipe before exiting; only SIGKILL and crashes lead to a restart.
+
+use crate::config::Config;
+use crate::models::progress::format_duration;
+use crate::notify::{Category, Notification, NotifierBuilder, Urgency};
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::process::{ChildStdin, Command, Stdio};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Crashes within [`QUICK_CRASH`] of starting, in a row, before giving up
+pub const MAX_QUICK_RESTARTS: u32 = 5;
+const QUICK_CRASH: Duration = Duration::from_secs(60);
+/// Carries the quick-crash count from one watchdog to the next
+const RESTARTS_ENV: &str = "AGENT_INBOX_WATCHDOG_RESTARTS";
+
+/// Last panic of the daemon's main thread, for the notification
+fn panic_path() -> std::path::PathBuf {
+    crate::storage::state_dir().join("daemon.panic")
+}
+
+/// Start a watchdog for this process. The returned pipe must stay open for
+/// as long as the daemon runs.
+pub fn spawn() -> Result<ChildStdin> {
+    let exe = std::env::current_exe().context("Failed to locate the agent-inbox binary")?;
+    let mut child = Command::new(exe)
+        .args(["watchdog", &std::process::id().to_string()])
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Failed to start the watchdog")?;
+
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = std::fs::write(panic_path(), info.to_string());
+        hook(info);
+    }));
+    Ok(child.stdin.take().expect("watchdog stdin is piped"))
+}
+
+/// Quick crashes so far, the one that just happened included, and how long
+/// to wait before restarting; None to give up
+fn next_restart(previous: u32, uptime: Duration) -> Option<(u32, Duration)> {
+    let restarts = if uptime < QUICK_CRASH {
+        previous + 1
+    } else {
+        1
+    };
+    if restarts > MAX_QUICK_RESTARTS {
+        return None;
+    }
+    Some((
+        restarts,
+        Duration::from_secs(1 << (restarts - 1)).min(QUICK_CRASH),
+    ))
+}
+
+/// `agent-inbox watchdog <pid>`: wait for the daemon on stdin to go away,
+/// then restart it
+pub fn run(daemon_pid: i32) -> Result<()> {
+    let started = Instant::now();
+    let started_at = SystemTime::now();
+    // Read returns once the daemon is gone; it writes only when stopped
+    let mut said = Vec::new();
+    let _ = std::io::stdin().read_to_end(&mut said);
+    if !said.is_empty() {
+        eprintln!("Daemon {} stopped", daemon_pid);
+        return Ok(());
+    }
+    let uptime = started.elapsed();
+
+    let previous = std::env::var(RESTARTS_ENV)
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0);
+    let panic = std::fs::metadata(panic_path())
+        .and_then(|m| m.modified())
+        .is_ok_and(|modified| modified >= started_at)
+        .then(|| std::fs::read_to_string(panic_path()).ok())
+        .flatten();
+    let mut body = format!(
+        "It had been running for {}.",
+        format_duration(uptime.as_secs() as i64)
+    );
+    if let Some(panic) = panic {
+        body = format!("{}\n{}", body, panic.lines().next().unwrap_or_default());
+    }
+
+    let Some((restarts, delay)) = next_restart(previous, uptime) else {
+        eprintln!("Daemon {} keeps crashing, not restarting it", daemon_pid);
+        let body = format!(
+            "{}\nGave up after {} restarts; start it again once fixed.",
+            body, MAX_QUICK_RESTARTS
+        );
+        notify("agent-inbox daemon keeps crashing", &body);
+        return Ok(());
+    };
+    eprintln!(
+        "Daemon {} exited, restarting in {}s",
+        daemon_pid,
+        delay.as_secs()
+    );
+    std::thread::sleep(delay);
+    let exe = std::env::current_exe().context("Failed to locate the agent-inbox binary")?;
+    Command::new(exe)
+        .arg("daemon")
+        .env(RESTARTS_ENV, restarts.to_string())
+        .stdin(Stdio::null())
+        .spawn()
+        .context("Failed to restart the daemon")?;
+    notify("agent-inbox daemon restarted after crash", &body);
+    Ok(())
+}
+
+/// Send through the built-in channels only
+fn notify(title: &str, body: &str) {
+    let notifier = Config::load()
+        .and_then(|config| NotifierBuilder::from_config(&config.notify))
+        .map(NotifierBuilder::build)
+        .unwrap_or_default();
+    notifier.notify(&Notification {
+        title: title.to_string(),
+        body: body.to_string(),
+        urgency: Urgency::Critical,
+        category: Category::Failed,
+        task_id: None,
+        reason: None,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_restart() {
+        let quick = Duration::from_secs(5);
+        assert_eq!(next_restart(0, quick), Some((1, Duration::from_secs(1))));
+        assert_eq!(next_restart(3, quick), Some((4, Duration::from_secs(8))));
+        assert_eq!(next_restart(MAX_QUICK_RESTARTS, quick), None);
+        // A crash after a good run starts counting again
+        assert_eq!(
+            next_restart(MAX_QUICK_RESTARTS, Duration::from_secs(3600)),
+            Some((1, Duration::from_secs(1)))
+        );
+    }
+}
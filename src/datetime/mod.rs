@@ -0,0 +1,204 @@
+//! How times read in notifications and reports: `[time]`
+//!
+//! Times are stored in UTC and shown in `timezone`. It defaults to the
+//! machine's own, which on a server is often UTC while the phone reading
+//! the notification is not; an IANA name ("America/New_York") shows them
+//! in yours wherever the agent runs. Absolute times follow `format`
+//! (strftime), with month and day names in `locale`. Times up to
+//! `relative_hours` old read as "12m ago" and older ones in full, so a
+//! "2d ago" never hides which morning it was.
+//!
+//! Day boundaries (`--since today`, daily costs) follow the same zone. The
+//! settings are read once per process.
+
+use crate::config::{Config, TimeConfig};
+use crate::models::progress::format_duration;
+use anyhow::{bail, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Duration, FixedOffset, Local, Locale, NaiveDate, TimeZone, Utc};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Zone {
+    Local,
+    Named(chrono_tz::Tz),
+}
+
+/// Formats times the way `[time]` asks
+#[derive(Debug, Clone)]
+pub struct Times {
+    zone: Zone,
+    locale: Locale,
+    format: String,
+    relative: Duration,
+}
+
+impl Default for Times {
+    fn default() -> Self {
+        Self::new(&TimeConfig::default()).expect("default [time] settings are valid")
+    }
+}
+
+impl Times {
+    pub fn new(config: &TimeConfig) -> Result<Self> {
+        let zone = match config.timezone.trim() {
+            "" | "local" => Zone::Local,
+            name => match name.parse() {
+                Ok(tz) => Zone::Named(tz),
+                Err(_) => bail!(
+                    "Unknown timezone {:?} in [time] (use an IANA name like \"Europe/Berlin\")",
+                    name
+                ),
+            },
+        };
+        let Ok(locale) = Locale::try_from(config.locale.replace('-', "_").as_str()) else {
+            bail!("Unknown locale {:?} in [time]", config.locale);
+        };
+        if StrftimeItems::new(&config.format).any(|item| item == Item::Error) {
+            bail!("Invalid time format {:?} in [time]", config.format);
+        }
+        Ok(Self {
+            zone,
+            locale,
+            format: config.format.clone(),
+            relative: Duration::hours(config.relative_hours as i64),
+        })
+    }
+
+    /// `at` in full
+    pub fn absolute(&self, at: DateTime<Utc>) -> String {
+        match self.zone {
+            Zone::Local => at
+                .with_timezone(&Local)
+                .format_localized(&self.format, self.locale)
+                .to_string(),
+            Zone::Named(tz) => at
+                .with_timezone(&tz)
+                .format_localized(&self.format, self.locale)
+                .to_string(),
+        }
+    }
+
+    /// "12m ago" while `at` is recent, else `at` in full
+    pub fn relative(&self, at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+        let age = now - at;
+        if age < Duration::zero() || age >= self.relative {
+            return self.absolute(at);
+        }
+        format!("{} ago", format_duration(age.num_seconds()))
+    }
+
+    /// `at` on the zone's clock
+    pub fn local(&self, at: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self.zone {
+            Zone::Local => at.with_timezone(&Local).fixed_offset(),
+            Zone::Named(tz) => at.with_timezone(&tz).fixed_offset(),
+        }
+    }
+
+    /// The calendar day `at` falls on in the zone
+    pub fn date(&self, at: DateTime<Utc>) -> NaiveDate {
+        self.local(at).date_naive()
+    }
+
+    pub fn today(&self) -> NaiveDate {
+        self.date(Utc::now())
+    }
+
+    /// When `day` starts in the zone
+    pub fn midnight(&self, day: NaiveDate) -> DateTime<Utc> {
+        let midnight = day.and_hms_opt(0, 0, 0).unwrap_or_default();
+        let start = match self.zone {
+            Zone::Local => Local
+                .from_local_datetime(&midnight)
+                .earliest()
+                .map(|dt| dt.to_utc()),
+            Zone::Named(tz) => tz
+                .from_local_datetime(&midnight)
+                .earliest()
+                .map(|dt| dt.to_utc()),
+        };
+        start.unwrap_or_else(|| midnight.and_utc())
+    }
+
+    /// "UTC", "Europe/Berlin", or the machine's offset ("+02:00")
+    pub fn zone_name(&self) -> String {
+        match self.zone {
+            Zone::Local => Local::now().format("%:z").to_string(),
+            Zone::Named(tz) => tz.name().to_string(),
+        }
+    }
+}
+
+/// This process's settings, from `[time]`; an invalid section is reported
+/// and the defaults used
+pub fn times() -> &'static Times {
+    static TIMES: OnceLock<Times> = OnceLock::new();
+    TIMES.get_or_init(|| {
+        let config = Config::load().map(|config| config.time).unwrap_or_default();
+        Times::new(&config).unwrap_or_else(|e| {
+            eprintln!("{:#}", e);
+            Times::default()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_times() {
+        let config = TimeConfig {
+            timezone: "America/New_York".to_string(),
+            locale: "de-DE".to_string(),
+            format: "%A %d. %B %H:%M %Z".to_string(),
+            relative_hours: 2,
+        };
+        let times = Times::new(&config).unwrap();
+        let at = Utc.with_ymd_and_hms(2026, 10, 16, 2, 30, 0).unwrap();
+
+        // 02:30 UTC is still the evening before in New York
+        assert_eq!(times.absolute(at), "Donnerstag 15. Oktober 22:30 EDT");
+        assert_eq!(
+            times.date(at),
+            NaiveDate::from_ymd_opt(2026, 10, 15).unwrap()
+        );
+        assert_eq!(times.relative(at, at + Duration::minutes(12)), "12m ago");
+        assert_eq!(
+            times.relative(at, at + Duration::hours(3)),
+            "Donnerstag 15. Oktober 22:30 EDT"
+        );
+        let day = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        assert_eq!(
+            times.midnight(day),
+            Utc.with_ymd_and_hms(2026, 10, 16, 4, 0, 0).unwrap()
+        );
+        assert_eq!(times.zone_name(), "America/New_York");
+
+        let utc = Times::new(&TimeConfig {
+            timezone: "UTC".to_string(),
+            relative_hours: 0,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            utc.relative(at, at + Duration::minutes(1)),
+            "2026-10-16 02:30"
+        );
+
+        let invalid = |config: TimeConfig| Times::new(&config).is_err();
+        assert!(invalid(TimeConfig {
+            timezone: "Mars/Olympus".to_string(),
+            ..Default::default()
+        }));
+        assert!(invalid(TimeConfig {
+            locale: "xx_YY".to_string(),
+            ..Default::default()
+        }));
+        assert!(invalid(TimeConfig {
+            format: "%Q".to_string(),
+            ..Default::default()
+        }));
+    }
+}
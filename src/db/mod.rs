@@ -1,12 +1,16 @@
 use anyhow::{Context, Result};
-use chrono::{TimeZone, Utc};
-use rusqlite::{params, Connection, OptionalExtension};
-use std::collections::HashMap;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 
-use crate::models::{Task, TaskContext, TaskStatus};
+use crate::costs::Usage;
+use crate::models::{EventKind, Task, TaskContext, TaskEvent, TaskStatus};
+use crate::notify::history::{Delivery, HistoryFilter};
+use crate::notify::mute::Mute;
+use crate::notify::Notification;
 
-const SCHEMA_VERSION: i32 = 1;
+const SCHEMA_VERSION: i32 = 11;
 
 pub struct Database {
     conn: Connection,
@@ -50,8 +54,7 @@ impl Database {
                 )?;
             }
             Some(v) if v < SCHEMA_VERSION => {
-                // Future: handle migrations
-                anyhow::bail!("Database schema migration not yet implemented");
+                self.migrate(v)?;
             }
             Some(_) => {
                 // Up to date
@@ -87,6 +90,220 @@ impl Database {
             CREATE INDEX idx_completed_at ON tasks(completed_at);
             ",
         )?;
+        self.create_events_table()?;
+        self.create_notification_queue()?;
+        self.create_usage_table()?;
+        self.create_mutes_table()?;
+        self.create_deliveries_table()?;
+        self.create_threads_table()?;
+        self.create_viewers_table()?;
+        self.create_feedback_table()?;
+        self.create_sequences_table()?;
+        self.add_delivery_receipts()?;
+
+        Ok(())
+    }
+
+    /// Upgrade an older database in place, one version at a time
+    fn migrate(&self, from: i32) -> Result<()> {
+        let tx = Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)?;
+        if from < 2 {
+            self.create_events_table()?;
+        }
+        if from < 3 {
+            self.create_notification_queue()?;
+        }
+        if from < 4 {
+            self.create_usage_table()?;
+        }
+        if from < 5 {
+            self.create_mutes_table()?;
+        }
+        if from < 6 {
+            self.create_deliveries_table()?;
+        }
+        if from < 7 {
+            self.create_threads_table()?;
+        }
+        if from < 8 {
+            self.create_viewers_table()?;
+        }
+        if from < 9 {
+            self.create_feedback_table()?;
+        }
+        if from < 10 {
+            self.create_sequences_table()?;
+        }
+        if from < 11 {
+            self.add_delivery_receipts()?;
+        }
+        self.conn.execute(
+            "UPDATE schema_version SET version = ?1",
+            params![SCHEMA_VERSION],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Version 2: per-task activity log
+    fn create_events_table(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id TEXT NOT NULL,
+                at INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                message TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_events_task_id ON events(task_id);
+            ",
+        )?;
+
+        Ok(())
+    }
+
+    /// Version 3: notifications held back to be sent as one batch
+    fn create_notification_queue(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS notification_queue (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                queued_at INTEGER NOT NULL,
+                notification TEXT NOT NULL
+            );
+            ",
+        )?;
+
+        Ok(())
+    }
+
+    /// Version 4: tokens and cost reported by agents. Rows outlive their
+    /// tasks so daily totals stay accurate after cleanup.
+    fn create_usage_table(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS usage (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id TEXT NOT NULL,
+                day TEXT NOT NULL,
+                input_tokens INTEGER NOT NULL,
+                output_tokens INTEGER NOT NULL,
+                cost_usd REAL NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_usage_day ON usage(day);
+            CREATE INDEX IF NOT EXISTS idx_usage_task_id ON usage(task_id);
+            ",
+        )?;
+
+        Ok(())
+    }
+
+    /// Version 5: notification mutes added from the CLI
+    fn create_mutes_table(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS mutes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                reason TEXT,
+                task TEXT,
+                channel TEXT,
+                until INTEGER
+            );
+            ",
+        )?;
+
+        Ok(())
+    }
+
+    /// Version 6: notification delivery audit log
+    fn create_deliveries_table(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS deliveries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                at INTEGER NOT NULL,
+                task_id TEXT,
+                notification TEXT NOT NULL,
+                delivered TEXT NOT NULL,
+                failed TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_deliveries_at ON deliveries(at);
+            ",
+        )?;
+
+        Ok(())
+    }
+
+    /// Version 7: the chat thread each task's messages go to, per channel
+    fn create_threads_table(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS threads (
+                task_id TEXT NOT NULL,
+                channel TEXT NOT NULL,
+                thread_id TEXT NOT NULL,
+                PRIMARY KEY (task_id, channel)
+            );
+            ",
+        )?;
+
+        Ok(())
+    }
+
+    /// Version 8: processes following a task with `attach` or `tail -f`
+    fn create_viewers_table(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS viewers (
+                task_id TEXT NOT NULL,
+                pid INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                since INTEGER NOT NULL,
+                PRIMARY KEY (task_id, pid)
+            );
+            ",
+        )?;
+
+        Ok(())
+    }
+
+    /// Version 9: notifications the user marked as false positives
+    fn create_feedback_table(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS feedback (
+                delivery_id INTEGER PRIMARY KEY,
+                at INTEGER NOT NULL,
+                task_id TEXT NOT NULL,
+                reason TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_feedback_task ON feedback(task_id);
+            ",
+        )?;
+
+        Ok(())
+    }
+
+    /// Version 10: how far each channel got through each task's
+    /// notification sequence; the row with an empty channel holds the last
+    /// number taken
+    fn create_sequences_table(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sequences (
+                task_id TEXT NOT NULL,
+                channel TEXT NOT NULL,
+                seq INTEGER NOT NULL,
+                PRIMARY KEY (task_id, channel)
+            );
+            ",
+        )?;
+
+        Ok(())
+    }
+
+    /// Version 11: what channels that confirm deliveries returned, as a
+    /// JSON object of channel to message ID
+    fn add_delivery_receipts(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "ALTER TABLE deliveries ADD COLUMN receipts TEXT NOT NULL DEFAULT '{}';",
+        )?;
 
         Ok(())
     }
@@ -148,7 +365,8 @@ impl Database {
             "UPDATE tasks SET
                 agent_type = ?1, title = ?2, status = ?3, updated_at = ?4,
                 completed_at = ?5, pid = ?6, ppid = ?7, monitor_pid = ?8,
-                attention_reason = ?9, exit_code = ?10, context = ?11, metadata = ?12
+                attention_reason = ?9, exit_code = ?10, context = ?11, metadata = ?12,
+                created_at = ?14
             WHERE task_id = ?13",
             params![
                 task.agent_type,
@@ -164,12 +382,54 @@ impl Database {
                 context_json,
                 metadata_json,
                 task.task_id,
+                task.created_at.timestamp(),
             ],
         )?;
 
         Ok(())
     }
 
+    /// Record the process watching a task, without touching the rest of it
+    pub fn set_monitor_pid(&self, task_id: &str, monitor_pid: i32) -> Result<()> {
+        self.conn.execute(
+            "UPDATE tasks SET monitor_pid = ?1 WHERE task_id = ?2",
+            params![monitor_pid, task_id],
+        )?;
+        Ok(())
+    }
+
+    /// Register a task under a stable ID, creating it or starting a new run
+    /// of the existing one (see [`Task::restart`]).
+    ///
+    /// Safe to call repeatedly from wrapper scripts: concurrent callers are
+    /// serialized by a write transaction and never create duplicates. Returns
+    /// the stored task.
+    pub fn register_task(&self, task: &Task) -> Result<Task> {
+        // Take the write lock before reading so two registrations can't race
+        let tx = Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)?;
+
+        let stored = match self.get_task_by_id(&task.task_id)? {
+            Some(mut existing) => {
+                existing.restart(task.pid, task.ppid);
+                existing.agent_type = task.agent_type.clone();
+                existing.title = task.title.clone();
+                if task.context.is_some() {
+                    existing.context = task.context.clone();
+                }
+                self.update_task(&existing)?;
+                existing
+            }
+            None => {
+                let mut created = task.clone();
+                created.id = Some(self.insert_task(task)?);
+                created
+            }
+        };
+
+        tx.commit()?;
+        Ok(stored)
+    }
+
     pub fn get_task_by_id(&self, task_id: &str) -> Result<Option<Task>> {
         let mut stmt = self.conn.prepare(
             "SELECT id, task_id, agent_type, title, status, created_at, updated_at,
@@ -214,6 +474,14 @@ impl Database {
         let affected = self
             .conn
             .execute("DELETE FROM tasks WHERE task_id = ?1", params![task_id])?;
+        self.conn
+            .execute("DELETE FROM events WHERE task_id = ?1", params![task_id])?;
+        self.conn
+            .execute("DELETE FROM threads WHERE task_id = ?1", params![task_id])?;
+        self.conn
+            .execute("DELETE FROM viewers WHERE task_id = ?1", params![task_id])?;
+        self.conn
+            .execute("DELETE FROM sequences WHERE task_id = ?1", params![task_id])?;
 
         Ok(affected > 0)
     }
@@ -221,14 +489,466 @@ impl Database {
     pub fn cleanup_old_completed(&self, older_than_secs: i64) -> Result<usize> {
         let cutoff = Utc::now().timestamp() - older_than_secs;
 
+        // Not by what is left in `tasks`: a hub keeps threads and sequences
+        // of tasks that live on other machines
+        for table in ["threads", "sequences"] {
+            self.conn.execute(
+                &format!(
+                    "DELETE FROM {} WHERE task_id IN
+                     (SELECT task_id FROM tasks WHERE status = 'completed' AND completed_at < ?1)",
+                    table
+                ),
+                params![cutoff],
+            )?;
+        }
         let affected = self.conn.execute(
             "DELETE FROM tasks WHERE status = 'completed' AND completed_at < ?1",
             params![cutoff],
         )?;
+        self.conn.execute(
+            "DELETE FROM events WHERE task_id NOT IN (SELECT task_id FROM tasks)",
+            [],
+        )?;
 
         Ok(affected)
     }
 
+    /// Append to a task's activity log
+    pub fn record_event(&self, event: &TaskEvent) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO events (task_id, at, kind, message) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                event.task_id,
+                event.at.timestamp(),
+                event.kind.as_str(),
+                event.message,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Record an event happening now. Failures are only logged: the activity
+    /// log must never get in the way of tracking the task itself.
+    pub fn log_event(&self, task_id: &str, kind: EventKind, message: impl Into<String>) {
+        if let Err(e) = self.record_event(&TaskEvent::new(task_id, kind, message)) {
+            eprintln!(
+                "Failed to record {} event for {}: {}",
+                kind.as_str(),
+                task_id,
+                e
+            );
+        }
+    }
+
+    /// A task's activity log, oldest first
+    pub fn task_events(&self, task_id: &str) -> Result<Vec<TaskEvent>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT task_id, at, kind, message FROM events WHERE task_id = ?1 ORDER BY id",
+        )?;
+
+        let events = stmt
+            .query_map(params![task_id], row_to_event)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(events)
+    }
+
+    /// Events of every task logged after the one with ID `after` (0 for
+    /// all), oldest first, each with its ID
+    pub fn events_after(&self, after: i64) -> Result<Vec<(i64, TaskEvent)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT task_id, at, kind, message, id FROM events WHERE id > ?1 ORDER BY id",
+        )?;
+
+        let events = stmt
+            .query_map(params![after], |row| Ok((row.get(4)?, row_to_event(row)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(events)
+    }
+
+    /// ID of the latest event, 0 when there is none
+    pub fn last_event_id(&self) -> Result<i64> {
+        Ok(self
+            .conn
+            .query_row("SELECT COALESCE(MAX(id), 0) FROM events", [], |row| {
+                row.get(0)
+            })?)
+    }
+
+    /// Events of every task from `since` through `until`, oldest first
+    pub fn events_between(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<TaskEvent>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT task_id, at, kind, message FROM events
+             WHERE at >= ?1 AND at <= ?2 ORDER BY at, id",
+        )?;
+
+        let events = stmt
+            .query_map(params![since.timestamp(), until.timestamp()], row_to_event)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(events)
+    }
+
+    /// Hold a notification for the next batch; returns its queue ID
+    pub fn queue_notification(
+        &self,
+        notification: &Notification,
+        at: DateTime<Utc>,
+    ) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO notification_queue (queued_at, notification) VALUES (?1, ?2)",
+            params![at.timestamp(), serde_json::to_string(notification)?],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// ID and queue time of the oldest held notification
+    pub fn first_queued_notification(&self) -> Result<Option<(i64, DateTime<Utc>)>> {
+        let row: Option<(i64, i64)> = self
+            .conn
+            .query_row(
+                "SELECT id, queued_at FROM notification_queue ORDER BY id LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        Ok(row.map(|(id, at)| (id, Utc.timestamp_opt(at, 0).unwrap())))
+    }
+
+    /// Remove and return every held notification, oldest first
+    pub fn take_queued_notifications(&self) -> Result<Vec<Notification>> {
+        let tx = Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)?;
+        let queued = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT notification FROM notification_queue ORDER BY id")?;
+            let rows = stmt
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows.iter()
+                .filter_map(|json| serde_json::from_str(json).ok())
+                .collect()
+        };
+        self.conn.execute("DELETE FROM notification_queue", [])?;
+        tx.commit()?;
+        Ok(queued)
+    }
+
+    /// Store a mute; returns its ID
+    pub fn insert_mute(&self, mute: &Mute) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO mutes (reason, task, channel, until) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                mute.reason,
+                mute.task,
+                mute.channel,
+                mute.until.map(|dt| dt.timestamp())
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Stored mutes still in effect at `now`, oldest first. Expired ones
+    /// are dropped on the way.
+    pub fn list_mutes(&self, now: DateTime<Utc>) -> Result<Vec<Mute>> {
+        self.conn.execute(
+            "DELETE FROM mutes WHERE until IS NOT NULL AND until <= ?1",
+            params![now.timestamp()],
+        )?;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, reason, task, channel, until FROM mutes ORDER BY id")?;
+        let mutes = stmt
+            .query_map([], |row| {
+                let until: Option<i64> = row.get(4)?;
+                Ok(Mute {
+                    id: Some(row.get(0)?),
+                    reason: row.get(1)?,
+                    task: row.get(2)?,
+                    channel: row.get(3)?,
+                    until: until.map(|ts| Utc.timestamp_opt(ts, 0).unwrap()),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(mutes)
+    }
+
+    /// Remove one stored mute, or all of them; returns how many went
+    pub fn delete_mutes(&self, id: Option<i64>) -> Result<usize> {
+        let deleted = match id {
+            Some(id) => self
+                .conn
+                .execute("DELETE FROM mutes WHERE id = ?1", params![id])?,
+            None => self.conn.execute("DELETE FROM mutes", [])?,
+        };
+        Ok(deleted)
+    }
+
+    /// Log a notification and the channels that took it; returns its ID
+    pub fn record_delivery(
+        &self,
+        at: DateTime<Utc>,
+        notification: &Notification,
+        delivered: &[String],
+        failed: &[String],
+        receipts: &BTreeMap<String, String>,
+    ) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO deliveries (at, task_id, notification, delivered, failed, receipts)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                at.timestamp(),
+                notification.task_id,
+                serde_json::to_string(notification)?,
+                delivered.join(","),
+                failed.join(","),
+                serde_json::to_string(receipts)?,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Drop deliveries logged before `before`
+    pub fn prune_deliveries(&self, before: DateTime<Utc>) -> Result<usize> {
+        let deleted = self.conn.execute(
+            "DELETE FROM deliveries WHERE at < ?1",
+            params![before.timestamp()],
+        )?;
+        Ok(deleted)
+    }
+
+    /// Logged deliveries matching `filter`, newest first
+    pub fn deliveries(&self, filter: &HistoryFilter) -> Result<Vec<Delivery>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, at, notification, delivered, failed, receipts FROM deliveries
+             WHERE at >= ?1 AND at < ?2 ORDER BY id DESC",
+        )?;
+        let since = filter.since.map_or(i64::MIN, |dt| dt.timestamp());
+        let until = filter.until.map_or(i64::MAX, |dt| dt.timestamp());
+        let deliveries = stmt
+            .query_map(params![since, until], row_to_delivery)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(deliveries
+            .into_iter()
+            .filter(|d| filter.matches(d))
+            .collect())
+    }
+
+    pub fn delivery(&self, id: i64) -> Result<Option<Delivery>> {
+        let delivery = self
+            .conn
+            .query_row(
+                "SELECT id, at, notification, delivered, failed, receipts FROM deliveries WHERE id = ?1",
+                params![id],
+                row_to_delivery,
+            )
+            .optional()?;
+        Ok(delivery)
+    }
+
+    /// The thread `channel` keeps `task_id`'s messages in
+    pub fn thread(&self, task_id: &str, channel: &str) -> Result<Option<String>> {
+        let thread = self
+            .conn
+            .query_row(
+                "SELECT thread_id FROM threads WHERE task_id = ?1 AND channel = ?2",
+                params![task_id, channel],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(thread)
+    }
+
+    pub fn set_thread(&self, task_id: &str, channel: &str, thread_id: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO threads (task_id, channel, thread_id) VALUES (?1, ?2, ?3)",
+            params![task_id, channel, thread_id],
+        )?;
+        Ok(())
+    }
+
+    /// Take the next number of `task_id`'s notification sequence, from 1
+    pub fn next_sequence(&self, task_id: &str) -> Result<i64> {
+        let seq = self.conn.query_row(
+            "INSERT INTO sequences (task_id, channel, seq) VALUES (?1, '', 1)
+             ON CONFLICT (task_id, channel) DO UPDATE SET seq = seq + 1
+             RETURNING seq",
+            params![task_id],
+            |row| row.get(0),
+        )?;
+        Ok(seq)
+    }
+
+    /// Last number of `task_id`'s sequence `channel` is done with; 0 when none
+    pub fn finished_sequence(&self, task_id: &str, channel: &str) -> Result<i64> {
+        let seq = self
+            .conn
+            .query_row(
+                "SELECT seq FROM sequences WHERE task_id = ?1 AND channel = ?2",
+                params![task_id, channel],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(seq.unwrap_or(0))
+    }
+
+    /// `channel` is done with number `seq` of `task_id`'s sequence
+    pub fn finish_sequence(&self, task_id: &str, channel: &str, seq: i64) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO sequences (task_id, channel, seq) VALUES (?1, ?2, ?3)
+             ON CONFLICT (task_id, channel) DO UPDATE SET seq = MAX(seq, excluded.seq)",
+            params![task_id, channel, seq],
+        )?;
+        Ok(())
+    }
+
+    /// Record that the notification logged as `delivery_id`, about `reason`
+    /// on `task_id`, was a false positive. Marking it again is a no-op.
+    pub fn record_false_positive(
+        &self,
+        delivery_id: i64,
+        task_id: &str,
+        reason: &str,
+        at: DateTime<Utc>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO feedback (delivery_id, at, task_id, reason) VALUES (?1, ?2, ?3, ?4)",
+            params![delivery_id, at.timestamp(), task_id, reason],
+        )?;
+        Ok(())
+    }
+
+    /// Take back the false-positive marks of `task_id` for `reason`
+    pub fn clear_false_positives(&self, task_id: &str, reason: &str) -> Result<usize> {
+        let deleted = self.conn.execute(
+            "DELETE FROM feedback WHERE task_id = ?1 AND reason = ?2",
+            params![task_id, reason],
+        )?;
+        Ok(deleted)
+    }
+
+    /// False positives marked on `task_id` since `since`, per reason
+    pub fn false_positives(
+        &self,
+        task_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<HashMap<String, u32>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT reason, COUNT(*) FROM feedback WHERE task_id = ?1 AND at >= ?2 GROUP BY reason",
+        )?;
+        let counts = stmt
+            .query_map(params![task_id, since.timestamp()], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        Ok(counts)
+    }
+
+    /// Record that process `pid` follows `task_id`; `kind` is "attach" or
+    /// "tail"
+    pub fn add_viewer(&self, task_id: &str, pid: i32, kind: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO viewers (task_id, pid, kind, since) VALUES (?1, ?2, ?3, ?4)",
+            params![task_id, pid, kind, Utc::now().timestamp()],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_viewer(&self, task_id: &str, pid: i32) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM viewers WHERE task_id = ?1 AND pid = ?2",
+            params![task_id, pid],
+        )?;
+        Ok(())
+    }
+
+    /// (pid, kind) of the processes recorded as following `task_id`,
+    /// including ones that died without removing themselves
+    pub fn viewers(&self, task_id: &str) -> Result<Vec<(i32, String)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT pid, kind FROM viewers WHERE task_id = ?1 ORDER BY since")?;
+        let viewers = stmt
+            .query_map(params![task_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(viewers)
+    }
+
+    /// Add to a task's usage on `day`
+    pub fn record_usage(&self, task_id: &str, day: NaiveDate, usage: &Usage) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO usage (task_id, day, input_tokens, output_tokens, cost_usd)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                task_id,
+                day.to_string(),
+                usage.input_tokens as i64,
+                usage.output_tokens as i64,
+                usage.cost_usd,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Usage of every task on `day`
+    pub fn usage_on(&self, day: NaiveDate) -> Result<Usage> {
+        let usage = self.conn.query_row(
+            &format!("SELECT {} FROM usage WHERE day = ?1", USAGE_SUMS),
+            params![day.to_string()],
+            row_to_usage,
+        )?;
+        Ok(usage)
+    }
+
+    /// Usage of one task across all its runs
+    pub fn task_usage(&self, task_id: &str) -> Result<Usage> {
+        let usage = self.conn.query_row(
+            &format!("SELECT {} FROM usage WHERE task_id = ?1", USAGE_SUMS),
+            params![task_id],
+            row_to_usage,
+        )?;
+        Ok(usage)
+    }
+
+    /// Daily totals from `since` on, oldest first
+    pub fn usage_by_day(&self, since: NaiveDate) -> Result<Vec<(NaiveDate, Usage)>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT day, {} FROM usage WHERE day >= ?1 GROUP BY day ORDER BY day",
+            USAGE_SUMS
+        ))?;
+        let rows = stmt
+            .query_map(params![since.to_string()], |row| {
+                Ok((row.get::<_, String>(0)?, usage_from(row, 1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(day, usage)| Some((day.parse().ok()?, usage)))
+            .collect())
+    }
+
+    /// Per-task totals from `since` on, costliest first
+    pub fn usage_by_task(&self, since: NaiveDate) -> Result<Vec<(String, Usage)>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT task_id, {} FROM usage WHERE day >= ?1
+             GROUP BY task_id ORDER BY SUM(cost_usd) DESC, SUM(input_tokens) DESC",
+            USAGE_SUMS
+        ))?;
+        let rows = stmt
+            .query_map(params![since.to_string()], |row| {
+                Ok((row.get(0)?, usage_from(row, 1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
     fn row_to_task(&self, row: &rusqlite::Row) -> rusqlite::Result<Task> {
         let created_ts: i64 = row.get(5)?;
         let updated_ts: i64 = row.get(6)?;
@@ -269,23 +989,74 @@ impl Database {
     }
 }
 
+/// Column list summing up usage rows
+const USAGE_SUMS: &str =
+    "COALESCE(SUM(input_tokens), 0), COALESCE(SUM(output_tokens), 0), COALESCE(SUM(cost_usd), 0.0)";
+
+fn row_to_usage(row: &rusqlite::Row) -> rusqlite::Result<Usage> {
+    usage_from(row, 0)
+}
+
+/// The three [`USAGE_SUMS`] columns starting at `first`
+fn usage_from(row: &rusqlite::Row, first: usize) -> rusqlite::Result<Usage> {
+    Ok(Usage {
+        input_tokens: row.get::<_, i64>(first)? as u64,
+        output_tokens: row.get::<_, i64>(first + 1)? as u64,
+        cost_usd: row.get(first + 2)?,
+    })
+}
+
+fn row_to_delivery(row: &rusqlite::Row) -> rusqlite::Result<Delivery> {
+    let at: i64 = row.get(1)?;
+    let notification: String = row.get(2)?;
+    let names = |list: String| -> Vec<String> {
+        list.split(',')
+            .filter(|n| !n.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    Ok(Delivery {
+        id: row.get(0)?,
+        at: Utc.timestamp_opt(at, 0).unwrap(),
+        notification: serde_json::from_str(&notification).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        delivered: names(row.get(3)?),
+        failed: names(row.get(4)?),
+        receipts: serde_json::from_str(&row.get::<_, String>(5)?).unwrap_or_default(),
+    })
+}
+
+fn row_to_event(row: &rusqlite::Row) -> rusqlite::Result<TaskEvent> {
+    let at: i64 = row.get(1)?;
+    let kind: String = row.get(2)?;
+    Ok(TaskEvent {
+        task_id: row.get(0)?,
+        at: Utc.timestamp_opt(at, 0).unwrap(),
+        kind: EventKind::from_str(&kind).map_err(|e| {
+            rusqlite::Error::ToSqlConversionFailure(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                e,
+            )))
+        })?,
+        message: row.get(3)?,
+    })
+}
+
+/// Where the database lives: ~/.local/share/agent-inbox
+pub fn data_dir() -> PathBuf {
+    crate::storage::data_dir()
+}
+
 pub fn default_db_path() -> PathBuf {
-    let home = std::env::var("HOME").expect("HOME environment variable not set");
-    PathBuf::from(home)
-        .join(".agent-tasks")
-        .join("tasks.db")
+    data_dir().join("tasks.db")
 }
 
 pub fn ensure_data_dir() -> Result<PathBuf> {
-    let home = std::env::var("HOME").context("HOME environment variable not set")?;
-    let data_dir = PathBuf::from(home).join(".agent-tasks");
+    std::env::var("HOME").context("HOME environment variable not set")?;
+    crate::storage::prepare()?;
 
-    if !data_dir.exists() {
-        std::fs::create_dir_all(&data_dir)
-            .context("Failed to create data directory")?;
-    }
-
-    Ok(data_dir)
+    Ok(data_dir())
 }
 
 #[cfg(test)]
@@ -385,6 +1156,43 @@ mod tests {
         assert_eq!(completed_tasks[0].task_id, "test-2");
     }
 
+    #[test]
+    fn test_register_task_is_idempotent() {
+        let (db, _temp) = create_test_db();
+
+        let task = Task::new(
+            "nightly".to_string(),
+            "cron".to_string(),
+            "Nightly run".to_string(),
+            Some(10),
+            None,
+        );
+        db.register_task(&task).unwrap();
+        db.register_task(&task).unwrap();
+        assert_eq!(db.list_tasks(None).unwrap().len(), 1);
+
+        let mut stored = db.get_task_by_id("nightly").unwrap().unwrap();
+        stored.set_exited(Some(1));
+        db.update_task(&stored).unwrap();
+
+        let rerun = Task::new(
+            "nightly".to_string(),
+            "cron".to_string(),
+            "Nightly run".to_string(),
+            Some(20),
+            None,
+        );
+        let stored = db.register_task(&rerun).unwrap();
+        assert_eq!(stored.status, TaskStatus::Running);
+        assert_eq!(stored.pid, Some(20));
+
+        let retrieved = db.get_task_by_id("nightly").unwrap().unwrap();
+        assert_eq!(retrieved.pid, Some(20));
+        assert_eq!(retrieved.run_history().len(), 1);
+        assert_eq!(retrieved.run_history()[0].exit_code, Some(1));
+        assert_eq!(db.list_tasks(None).unwrap().len(), 1);
+    }
+
     #[test]
     fn test_delete_task() {
         let (db, _temp) = create_test_db();
@@ -430,4 +1238,72 @@ mod tests {
         let deleted = db.cleanup_old_completed(-1).unwrap();
         assert_eq!(deleted, 1);
     }
+
+    #[test]
+    fn test_events_round_trip() {
+        let (db, _temp) = create_test_db();
+        db.log_event("t1", EventKind::Started, "pid 42");
+        db.log_event("t1", EventKind::Notified, "via desktop");
+        db.log_event("t2", EventKind::Started, "");
+
+        let events = db.task_events("t1").unwrap();
+        let kinds: Vec<EventKind> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![EventKind::Started, EventKind::Notified]);
+        assert_eq!(events[0].message, "pid 42");
+
+        db.delete_task("t1").unwrap();
+        assert!(db.task_events("t1").unwrap().is_empty());
+        assert_eq!(db.task_events("t2").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_false_positives_per_reason() {
+        let (db, _temp) = create_test_db();
+        let now = Utc::now();
+        db.record_false_positive(1, "t1", "process_stalled", now)
+            .unwrap();
+        // Marking the same notification twice counts once
+        db.record_false_positive(1, "t1", "process_stalled", now)
+            .unwrap();
+        db.record_false_positive(2, "t1", "process_stalled", now)
+            .unwrap();
+        db.record_false_positive(3, "t1", "looping", now - chrono::Duration::days(40))
+            .unwrap();
+        db.record_false_positive(4, "t2", "looping", now).unwrap();
+
+        let counts = db
+            .false_positives("t1", now - chrono::Duration::days(30))
+            .unwrap();
+        assert_eq!(counts, HashMap::from([("process_stalled".to_string(), 2)]));
+        assert_eq!(
+            db.clear_false_positives("t1", "process_stalled").unwrap(),
+            2
+        );
+        assert!(db
+            .false_positives("t1", now - chrono::Duration::days(30))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_migrates_version_1_database() {
+        let temp = NamedTempFile::new().unwrap();
+        {
+            let conn = Connection::open(temp.path()).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE schema_version (version INTEGER PRIMARY KEY);
+                 INSERT INTO schema_version (version) VALUES (1);",
+            )
+            .unwrap();
+        }
+
+        let db = Database::open(temp.path()).unwrap();
+        db.log_event("t1", EventKind::Started, "");
+        assert_eq!(db.task_events("t1").unwrap().len(), 1);
+        let version: i32 = db
+            .conn
+            .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+    }
 }
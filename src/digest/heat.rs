@@ -0,0 +1,328 @@
+//! Where attention goes, per project
+//!
+//! `agent-inbox report --by project --last 7d` breaks the activity log of a
+//! period down by project (the name of the task's working directory), agent
+//! or tag: runs, attention events, time spent flagged for attention, and
+//! completions and failures. Projects whose agents keep stopping for you
+//! are the ones whose prompts, permissions or auto-respond rules need
+//! tuning.
+
+use crate::db::Database;
+use crate::models::progress::format_duration;
+use crate::models::{EventKind, Task, TaskEvent};
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// What tasks are grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    /// The last component of the working directory
+    Project,
+    Agent,
+    /// Each of the task's tags; a task with several counts for each
+    Tag,
+}
+
+impl GroupBy {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "project" => Ok(GroupBy::Project),
+            "agent" => Ok(GroupBy::Agent),
+            "tag" => Ok(GroupBy::Tag),
+            _ => Err(format!("Invalid grouping: {} (project, agent or tag)", s)),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            GroupBy::Project => "project",
+            GroupBy::Agent => "agent",
+            GroupBy::Tag => "tag",
+        }
+    }
+
+    /// The groups `task` belongs to
+    fn keys(&self, task: Option<&Task>) -> Vec<String> {
+        let keys = match (self, task) {
+            (_, None) => Vec::new(),
+            (GroupBy::Project, Some(task)) => task
+                .context
+                .as_ref()
+                .and_then(|c| c.project_path.as_deref())
+                .and_then(|path| Path::new(path).file_name())
+                .map(|name| vec![name.to_string_lossy().into_owned()])
+                .unwrap_or_default(),
+            (GroupBy::Agent, Some(task)) => vec![task.agent_type.clone()],
+            (GroupBy::Tag, Some(task)) => task.tags(),
+        };
+        if keys.is_empty() {
+            vec![NONE.to_string()]
+        } else {
+            keys
+        }
+    }
+}
+
+/// Group of tasks without a project or tag, or no longer in the database
+const NONE: &str = "(none)";
+
+/// A period like "24h", "7d" or "4w"
+pub fn parse_period(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid period: {} (e.g. 24h, 7d or 4w)", s);
+    let unit = s.chars().last().ok_or_else(invalid)?;
+    let count: i64 = s[..s.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    match unit {
+        'h' => Ok(Duration::hours(count)),
+        'd' => Ok(Duration::days(count)),
+        'w' => Ok(Duration::weeks(count)),
+        _ => Err(invalid()),
+    }
+}
+
+/// One group's share of the period
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeatRow {
+    pub group: String,
+    /// Tasks with any activity
+    pub tasks: usize,
+    /// Times a task was flagged for attention
+    pub attention: usize,
+    /// Time tasks spent flagged
+    pub waited: Duration,
+    pub completed: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Heat {
+    pub by: GroupBy,
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    /// Most attention first
+    pub rows: Vec<HeatRow>,
+}
+
+impl Heat {
+    /// Break the events from `since` through `until` down `by` group
+    pub fn build(
+        db: &Database,
+        by: GroupBy,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Self> {
+        let events = db.events_between(since, until)?;
+        let mut keys: HashMap<String, Vec<String>> = HashMap::new();
+        for event in &events {
+            if !keys.contains_key(&event.task_id) {
+                let task = db.get_task_by_id(&event.task_id)?;
+                keys.insert(event.task_id.clone(), by.keys(task.as_ref()));
+            }
+        }
+        Ok(Self::from_events(&events, &keys, by, since, until))
+    }
+
+    fn from_events(
+        events: &[TaskEvent],
+        keys: &HashMap<String, Vec<String>>,
+        by: GroupBy,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Self {
+        let mut rows: BTreeMap<&str, HeatRow> = BTreeMap::new();
+        let mut update = |task_id: &str, apply: &dyn Fn(&mut HeatRow)| {
+            for key in &keys[task_id] {
+                apply(rows.entry(key).or_insert_with(|| HeatRow {
+                    group: key.clone(),
+                    ..Default::default()
+                }));
+            }
+        };
+        // When each task was flagged, as in the digest
+        let mut flagged: HashMap<&str, DateTime<Utc>> = HashMap::new();
+        let mut seen: Vec<&str> = Vec::new();
+
+        for event in events {
+            let task_id = event.task_id.as_str();
+            if !seen.contains(&task_id) {
+                seen.push(task_id);
+                update(task_id, &|row| row.tasks += 1);
+            }
+            let ends_attention = matches!(
+                event.kind,
+                EventKind::AttentionCleared
+                    | EventKind::Resumed
+                    | EventKind::Started
+                    | EventKind::Completed
+                    | EventKind::Exited
+            );
+            if ends_attention {
+                if let Some(at) = flagged.remove(task_id) {
+                    update(task_id, &|row| row.waited += event.at - at);
+                }
+            }
+            match event.kind {
+                EventKind::Attention => {
+                    flagged.entry(task_id).or_insert(event.at);
+                    update(task_id, &|row| row.attention += 1);
+                }
+                EventKind::Completed => update(task_id, &|row| row.completed += 1),
+                EventKind::Exited if super::exit_code(&event.message).is_some() => {
+                    update(task_id, &|row| row.failed += 1)
+                }
+                _ => {}
+            }
+        }
+        // Still flagged at the end of the period
+        for (task_id, at) in flagged {
+            update(task_id, &|row| row.waited += until - at);
+        }
+
+        let mut rows: Vec<HeatRow> = rows.into_values().collect();
+        rows.sort_by_key(|row| {
+            (
+                std::cmp::Reverse(row.attention),
+                std::cmp::Reverse(row.waited),
+            )
+        });
+        Self {
+            by,
+            since,
+            until,
+            rows,
+        }
+    }
+
+    fn cells(row: &HeatRow) -> [String; 6] {
+        [
+            row.group.clone(),
+            row.tasks.to_string(),
+            row.attention.to_string(),
+            format_duration(row.waited.num_seconds()),
+            row.completed.to_string(),
+            row.failed.to_string(),
+        ]
+    }
+
+    fn header(&self) -> [String; 6] {
+        [
+            self.by.as_str(),
+            "tasks",
+            "attention",
+            "waited",
+            "completed",
+            "failed",
+        ]
+        .map(|h| h.to_uppercase())
+    }
+
+    /// Aligned columns for the terminal
+    pub fn lines(&self) -> Vec<String> {
+        if self.rows.is_empty() {
+            return vec!["Nothing ran in this period".to_string()];
+        }
+        let rows: Vec<[String; 6]> = std::iter::once(self.header())
+            .chain(self.rows.iter().map(Self::cells))
+            .collect();
+        let widths: Vec<usize> = (0..6)
+            .map(|i| {
+                rows.iter()
+                    .map(|row| row[i].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        rows.iter()
+            .map(|row| {
+                let cells: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .enumerate()
+                    .map(|(i, (cell, width))| match i {
+                        0 => format!("{:<width$}", cell, width = width),
+                        _ => format!("{:>width$}", cell, width = width),
+                    })
+                    .collect();
+                cells.join("  ").trim_end().to_string()
+            })
+            .collect()
+    }
+
+    /// A Markdown table, for pasting into an issue or a retro
+    pub fn markdown(&self) -> String {
+        let header = self.header().map(|h| h.to_lowercase());
+        let mut lines = vec![
+            format!("| {} |", header.join(" | ")),
+            "|:---|---:|---:|---:|---:|---:|".to_string(),
+        ];
+        for row in &self.rows {
+            let cells = Self::cells(row).map(|cell| cell.replace('|', "\\|"));
+            lines.push(format!("| {} |", cells.join(" | ")));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heat_by_project() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let event = |task: &str, minute: i64, kind: EventKind, message: &str| {
+            TaskEvent::new(task, kind, message).at(start + Duration::minutes(minute))
+        };
+        let events = [
+            event("a", 0, EventKind::Started, ""),
+            event("a", 10, EventKind::Attention, "Waiting for input"),
+            event("a", 25, EventKind::Resumed, ""),
+            event("a", 30, EventKind::Attention, "Waiting for approval"),
+            event("b", 31, EventKind::Started, ""),
+            event("a", 40, EventKind::Completed, "Exit code 0"),
+            event("b", 50, EventKind::Exited, "Exit code 2"),
+            event("c", 55, EventKind::Attention, "Process stalled"),
+        ];
+        let keys = HashMap::from([
+            ("a".to_string(), vec!["api".to_string()]),
+            ("b".to_string(), vec!["api".to_string()]),
+            ("c".to_string(), vec![NONE.to_string()]),
+        ]);
+        let heat = Heat::from_events(
+            &events,
+            &keys,
+            GroupBy::Project,
+            start,
+            start + Duration::minutes(60),
+        );
+
+        let api = HeatRow {
+            group: "api".to_string(),
+            tasks: 2,
+            attention: 2,
+            waited: Duration::minutes(25),
+            completed: 1,
+            failed: 1,
+        };
+        assert_eq!(heat.rows[0], api);
+        assert_eq!(
+            (heat.rows[1].attention, heat.rows[1].waited),
+            (1, Duration::minutes(5))
+        );
+        assert_eq!(
+            heat.lines()[1],
+            "api          2          2     25m          1       1"
+        );
+        assert!(heat
+            .markdown()
+            .contains("\n| api | 2 | 2 | 25m | 1 | 1 |\n"));
+
+        assert_eq!(parse_period("7d"), Ok(Duration::days(7)));
+        assert_eq!(parse_period("12h"), Ok(Duration::hours(12)));
+        assert!(parse_period("7").is_err() && parse_period("d").is_err());
+    }
+}
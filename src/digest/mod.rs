@@ -0,0 +1,305 @@
+//! Digest of everything that ran over a period
+//!
+//! Built from the activity log (see [`crate::models::TaskEvent`]): which
+//! tasks completed or failed, how long tasks spent flagged for attention,
+//! and the longest waits for the user. `agent-inbox summary` prints it on
+//! demand, and the daemon can send it on a schedule (`[digest] cron`) so an
+//! overnight run ends in one morning message.
+
+pub mod heat;
+
+use crate::db::Database;
+use crate::models::progress::format_duration;
+use crate::models::{EventKind, TaskEvent};
+use crate::notify::{Category, Notification, Urgency};
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+
+/// Waits listed in the digest
+const LONGEST_WAITS: usize = 3;
+
+/// A task mentioned in the digest
+#[derive(Debug, Clone, PartialEq)]
+pub struct DigestEntry {
+    pub task_id: String,
+    pub title: String,
+    pub exit_code: Option<i32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Digest {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    /// Number of runs started
+    pub started: usize,
+    pub completed: Vec<DigestEntry>,
+    pub failed: Vec<DigestEntry>,
+    /// Time tasks spent flagged for attention
+    pub attention: Duration,
+    /// Longest stretches a task waited on the user, longest first
+    pub longest_waits: Vec<(DigestEntry, Duration)>,
+}
+
+impl Digest {
+    /// Summarize the events from `since` through `until`
+    pub fn build(db: &Database, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Self> {
+        let events = db.events_between(since, until)?;
+        let mut titles: HashMap<String, DigestEntry> = HashMap::new();
+        for event in &events {
+            if !titles.contains_key(&event.task_id) {
+                let task = db.get_task_by_id(&event.task_id)?;
+                titles.insert(
+                    event.task_id.clone(),
+                    DigestEntry {
+                        task_id: event.task_id.clone(),
+                        title: task
+                            .map(|t| t.title)
+                            .unwrap_or_else(|| event.task_id.clone()),
+                        exit_code: None,
+                    },
+                );
+            }
+        }
+        Ok(Self::from_events(&events, &titles, since, until))
+    }
+
+    fn from_events(
+        events: &[TaskEvent],
+        titles: &HashMap<String, DigestEntry>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Self {
+        let entry = |event: &TaskEvent| titles[&event.task_id].clone();
+        let mut digest = Digest {
+            since,
+            until,
+            started: 0,
+            completed: Vec::new(),
+            failed: Vec::new(),
+            attention: Duration::zero(),
+            longest_waits: Vec::new(),
+        };
+        // Per task: when it was flagged, and when it started waiting for input
+        let mut flagged: HashMap<&str, DateTime<Utc>> = HashMap::new();
+        let mut waiting: HashMap<&str, DateTime<Utc>> = HashMap::new();
+        let mut waits = Vec::new();
+
+        for event in events {
+            let task_id = event.task_id.as_str();
+            let ends_attention = matches!(
+                event.kind,
+                EventKind::AttentionCleared
+                    | EventKind::Resumed
+                    | EventKind::Started
+                    | EventKind::Completed
+                    | EventKind::Exited
+            );
+            if ends_attention {
+                if let Some(at) = flagged.remove(task_id) {
+                    digest.attention += event.at - at;
+                    waits.push((entry(event), event.at - at));
+                }
+            }
+
+            match event.kind {
+                EventKind::Started => digest.started += 1,
+                EventKind::Attention => {
+                    flagged.entry(task_id).or_insert(event.at);
+                }
+                EventKind::Completed => {
+                    digest.completed.push(entry(event));
+                    waiting.insert(task_id, event.at);
+                }
+                EventKind::Resumed => {
+                    // Only answered waits count: a finished job never resumes
+                    if let Some(at) = waiting.remove(task_id) {
+                        waits.push((entry(event), event.at - at));
+                    }
+                }
+                EventKind::Exited => {
+                    if let Some(code) = exit_code(&event.message) {
+                        digest.failed.push(DigestEntry {
+                            exit_code: Some(code),
+                            ..entry(event)
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Still flagged at the end of the period
+        for (task_id, at) in flagged {
+            digest.attention += until - at;
+            waits.push((titles[task_id].clone(), until - at));
+        }
+
+        waits.retain(|(_, wait)| *wait > Duration::zero());
+        waits.sort_by_key(|(_, wait)| std::cmp::Reverse(*wait));
+        waits.truncate(LONGEST_WAITS);
+        digest.longest_waits = waits;
+        digest
+    }
+
+    /// Nothing started, finished or needed attention
+    pub fn is_quiet(&self) -> bool {
+        self.started == 0
+            && self.completed.is_empty()
+            && self.failed.is_empty()
+            && self.longest_waits.is_empty()
+    }
+
+    pub fn title(&self) -> String {
+        let hours = (self.until - self.since).num_hours();
+        if self.is_quiet() {
+            return format!("All quiet: nothing ran in the last {}h", hours);
+        }
+        format!(
+            "Last {}h: {} completed, {} failed",
+            hours,
+            self.completed.len(),
+            self.failed.len()
+        )
+    }
+
+    pub fn body(&self) -> String {
+        let mut lines = Vec::new();
+        if self.started > 0 {
+            lines.push(format!("{} run(s) started", self.started));
+        }
+        if !self.completed.is_empty() {
+            lines.push(format!("✓ Completed: {}", join_titles(&self.completed)));
+        }
+        if !self.failed.is_empty() {
+            let failed: Vec<String> = self
+                .failed
+                .iter()
+                .map(|e| match e.exit_code {
+                    Some(code) => format!("{} (exit {})", e.title, code),
+                    None => e.title.clone(),
+                })
+                .collect();
+            lines.push(format!("✗ Failed: {}", failed.join(", ")));
+        }
+        if self.attention > Duration::zero() {
+            lines.push(format!(
+                "Attention: {} total",
+                format_duration(self.attention.num_seconds())
+            ));
+        }
+        if !self.longest_waits.is_empty() {
+            let waits: Vec<String> = self
+                .longest_waits
+                .iter()
+                .map(|(e, d)| format!("{} {}", e.title, format_duration(d.num_seconds())))
+                .collect();
+            lines.push(format!("Longest waits: {}", waits.join(", ")));
+        }
+        lines.join("\n")
+    }
+
+    pub fn notification(&self) -> Notification {
+        Notification {
+            title: self.title(),
+            body: self.body(),
+            urgency: if self.failed.is_empty() {
+                Urgency::Low
+            } else {
+                Urgency::Normal
+            },
+            category: Category::Info,
+            task_id: None,
+            reason: None,
+        }
+    }
+}
+
+/// Non-zero exit code recorded in an Exited event ("Exit code 2"). Exits
+/// without a code (closed tabs, skipped chain steps) are not failures.
+fn exit_code(message: &str) -> Option<i32> {
+    let code: i32 = message.strip_prefix("Exit code ")?.parse().ok()?;
+    (code != 0).then_some(code)
+}
+
+/// Titles with repeats collapsed: "lint ×3, tests"
+fn join_titles(entries: &[DigestEntry]) -> String {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for entry in entries {
+        match counts.iter_mut().find(|(title, _)| *title == entry.title) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((&entry.title, 1)),
+        }
+    }
+    counts
+        .iter()
+        .map(|(title, count)| match count {
+            1 => title.to_string(),
+            n => format!("{} ×{}", title, n),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(task_id: &str, minute: i64, kind: EventKind, message: &str) -> TaskEvent {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        TaskEvent::new(task_id, kind, message).at(start + Duration::minutes(minute))
+    }
+
+    fn titles(ids: &[&str]) -> HashMap<String, DigestEntry> {
+        ids.iter()
+            .map(|id| {
+                (
+                    id.to_string(),
+                    DigestEntry {
+                        task_id: id.to_string(),
+                        title: id.to_string(),
+                        exit_code: None,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_digest_from_events() {
+        let events = vec![
+            event("lint", 0, EventKind::Started, ""),
+            event("lint", 5, EventKind::Completed, "Exit code 0"),
+            event("agent", 0, EventKind::Started, "pid 1"),
+            event("agent", 10, EventKind::Attention, "Process stalled"),
+            event("agent", 40, EventKind::AttentionCleared, "Activity resumed"),
+            event("agent", 50, EventKind::Completed, "Finished generating"),
+            event("agent", 70, EventKind::Resumed, "Generating again"),
+            event("deploy", 60, EventKind::Started, ""),
+            event("deploy", 61, EventKind::Exited, "Exit code 2"),
+            event("deploy", 62, EventKind::Attention, "Destructive prompt"),
+        ];
+        let since = events[0].at;
+        let until = since + Duration::hours(24);
+        let digest =
+            Digest::from_events(&events, &titles(&["lint", "agent", "deploy"]), since, until);
+
+        assert_eq!(digest.started, 3);
+        assert_eq!(digest.completed.len(), 2);
+        assert_eq!(digest.failed[0].exit_code, Some(2));
+        assert_eq!(digest.longest_waits[0].0.title, "deploy");
+        assert_eq!(digest.longest_waits[1].1, Duration::minutes(30));
+        assert_eq!(digest.title(), "Last 24h: 2 completed, 1 failed");
+        assert!(digest.body().contains("✗ Failed: deploy (exit 2)"));
+        assert_eq!(digest.notification().urgency, Urgency::Normal);
+    }
+
+    #[test]
+    fn test_quiet_digest() {
+        let since = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let digest = Digest::from_events(&[], &HashMap::new(), since, since + Duration::hours(12));
+        assert!(digest.is_quiet());
+        assert_eq!(digest.title(), "All quiet: nothing ran in the last 12h");
+        assert_eq!(digest.notification().urgency, Urgency::Low);
+    }
+}
@@ -0,0 +1,692 @@
+//! `--output json`: what the reporting commands print for scripts
+//!
+//! Each command prints a single object,
+//!
+//! ```json
+//! {"version": 1, "command": "list", "data": [...]}
+//! ```
+//!
+//! with `data` in the shapes below. They are written out here rather than
+//! derived from the internal types, so reworking those doesn't break
+//! anyone's scripts. Within a version, fields are only ever added; removing
+//! or changing one bumps [`VERSION`]. Times are RFC 3339 in UTC, durations
+//! whole seconds, sizes bytes.
+
+use super::most_urgent_task;
+use agent_inbox::costs::Usage;
+use agent_inbox::digest::heat::Heat;
+use agent_inbox::digest::{Digest, DigestEntry};
+use agent_inbox::exit;
+use agent_inbox::models::{Task, TaskEvent, TaskStatus};
+use agent_inbox::monitor::bench::{Report, Timing};
+use agent_inbox::monitor::liveness::Detection;
+use agent_inbox::notify::history::Delivery;
+use agent_inbox::notify::{Category, Urgency};
+use agent_inbox::plugins::PluginManifest;
+use agent_inbox::stats::{Latency, Stats};
+use agent_inbox::storage::{Dirs, TaskStorage};
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Version of the shapes in this module
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Envelope<'a, T: Serialize> {
+    version: u32,
+    command: &'a str,
+    data: T,
+}
+
+/// `data` as the output of `command`
+fn render<T: Serialize>(command: &str, data: T) -> Result<String> {
+    let envelope = Envelope {
+        version: VERSION,
+        command,
+        data,
+    };
+    Ok(serde_json::to_string_pretty(&envelope)?)
+}
+
+fn print<T: Serialize>(command: &str, data: T) -> Result<()> {
+    println!("{}", render(command, data)?);
+    Ok(())
+}
+
+/// What a failed command writes to stderr: `{"version": 1, "error":
+/// {"code": 5, "kind": "not_found", "message": "Task not found: t1"}}`,
+/// with the code it exits with (see [`agent_inbox::exit`])
+pub fn error(error: &anyhow::Error) -> String {
+    #[derive(Serialize)]
+    struct Error<'a> {
+        code: i32,
+        kind: &'a str,
+        message: String,
+    }
+    #[derive(Serialize)]
+    struct Payload<'a> {
+        version: u32,
+        error: Error<'a>,
+    }
+    let code = exit::code_of(error);
+    let payload = Payload {
+        version: VERSION,
+        error: Error {
+            code: code.value(),
+            kind: code.as_str(),
+            message: format!("{:#}", error),
+        },
+    };
+    serde_json::to_string(&payload).unwrap_or_default()
+}
+
+#[derive(Serialize)]
+struct TaskJson<'a> {
+    task_id: &'a str,
+    agent: &'a str,
+    title: &'a str,
+    status: &'a str,
+    priority: String,
+    pid: Option<i32>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    completed_at: Option<DateTime<Utc>>,
+    exit_code: Option<i32>,
+    attention_reason: Option<&'a str>,
+    /// "3/10 tests", as `list` shows it
+    progress: Option<String>,
+    tags: Vec<String>,
+    last_input: Option<DateTime<Utc>>,
+    /// Set when the daemon couldn't tell at startup whether the pid is
+    /// still the task
+    unclear: Option<&'a str>,
+    /// The task this one repeats, for `rerun`
+    rerun_of: Option<&'a str>,
+}
+
+impl<'a> TaskJson<'a> {
+    fn new(task: &'a Task) -> Self {
+        Self {
+            task_id: &task.task_id,
+            agent: &task.agent_type,
+            title: &task.title,
+            status: task.status.as_str(),
+            priority: task.priority().as_str().to_string(),
+            pid: task.pid,
+            created_at: task.created_at,
+            updated_at: task.updated_at,
+            completed_at: task.completed_at,
+            exit_code: task.exit_code,
+            attention_reason: task.attention_reason.as_deref(),
+            progress: task.progress().map(|p| p.describe()),
+            tags: task.tags(),
+            last_input: task.last_input(),
+            unclear: task.unclear(),
+            rerun_of: agent_inbox::daemon::rerun::rerun_of(task),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct UsageJson {
+    input_tokens: u64,
+    output_tokens: u64,
+    cost_usd: f64,
+}
+
+impl From<&Usage> for UsageJson {
+    fn from(usage: &Usage) -> Self {
+        Self {
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            cost_usd: usage.cost_usd,
+        }
+    }
+}
+
+pub fn task_list(command: &str, tasks: &[Task]) -> Result<()> {
+    print(command, tasks.iter().map(TaskJson::new).collect::<Vec<_>>())
+}
+
+pub fn task_detail(task: &Task, usage: &Usage) -> Result<()> {
+    #[derive(Serialize)]
+    struct Run {
+        started_at: DateTime<Utc>,
+        ended_at: Option<DateTime<Utc>>,
+        status: String,
+        exit_code: Option<i32>,
+        pid: Option<i32>,
+    }
+    #[derive(Serialize)]
+    struct Note {
+        at: DateTime<Utc>,
+        text: String,
+    }
+    #[derive(Serialize)]
+    struct Detail<'a> {
+        #[serde(flatten)]
+        task: TaskJson<'a>,
+        command_line: Option<String>,
+        environment: BTreeMap<String, String>,
+        focus_command: Option<&'a str>,
+        notes: Vec<Note>,
+        /// Earlier runs, oldest first
+        runs: Vec<Run>,
+        usage: UsageJson,
+    }
+    let process = task.process().unwrap_or_default();
+    print(
+        "show",
+        Detail {
+            task: TaskJson::new(task),
+            command_line: process.command_line,
+            environment: process.env,
+            focus_command: task.focus_command(),
+            notes: task
+                .notes()
+                .into_iter()
+                .map(|n| Note {
+                    at: n.at,
+                    text: n.text,
+                })
+                .collect(),
+            runs: task
+                .run_history()
+                .into_iter()
+                .map(|run| Run {
+                    started_at: run.started_at,
+                    ended_at: run.ended_at,
+                    status: run.status.as_str().to_string(),
+                    exit_code: run.exit_code,
+                    pid: run.pid,
+                })
+                .collect(),
+            usage: usage.into(),
+        },
+    )
+}
+
+pub fn timeline(task: &Task, events: &[TaskEvent]) -> Result<()> {
+    #[derive(Serialize)]
+    struct Event<'a> {
+        at: DateTime<Utc>,
+        kind: &'a str,
+        message: &'a str,
+    }
+    #[derive(Serialize)]
+    struct Timeline<'a> {
+        task: TaskJson<'a>,
+        events: Vec<Event<'a>>,
+    }
+    print(
+        "timeline",
+        Timeline {
+            task: TaskJson::new(task),
+            events: events
+                .iter()
+                .map(|e| Event {
+                    at: e.at,
+                    kind: e.kind.as_str(),
+                    message: &e.message,
+                })
+                .collect(),
+        },
+    )
+}
+
+pub fn status(tasks: &[Task]) -> Result<()> {
+    #[derive(Serialize)]
+    struct Status<'a> {
+        running: usize,
+        completed: usize,
+        exited: usize,
+        /// The task most in need of the user
+        most_urgent: Option<TaskJson<'a>>,
+    }
+    let count = |status: TaskStatus| tasks.iter().filter(|t| t.status == status).count();
+    print(
+        "status",
+        Status {
+            running: count(TaskStatus::Running),
+            completed: count(TaskStatus::Completed),
+            exited: count(TaskStatus::Exited),
+            most_urgent: most_urgent_task(tasks).map(TaskJson::new),
+        },
+    )
+}
+
+#[derive(Serialize)]
+struct LatencyJson {
+    responses: usize,
+    median_secs: i64,
+    p90_secs: i64,
+    longest_secs: i64,
+}
+
+impl From<&Latency> for LatencyJson {
+    fn from(latency: &Latency) -> Self {
+        Self {
+            responses: latency.count,
+            median_secs: latency.median,
+            p90_secs: latency.p90,
+            longest_secs: latency.longest,
+        }
+    }
+}
+
+pub fn stats(stats: &Stats) -> Result<()> {
+    #[derive(Serialize)]
+    struct Slowest<'a> {
+        title: &'a str,
+        secs: i64,
+    }
+    #[derive(Serialize)]
+    struct StatsJson<'a> {
+        /// Null until an answered notification is recorded
+        overall: Option<LatencyJson>,
+        by_agent: BTreeMap<&'a str, LatencyJson>,
+        slowest: Option<Slowest<'a>>,
+    }
+    print(
+        "stats",
+        StatsJson {
+            overall: stats.overall.as_ref().map(Into::into),
+            by_agent: stats
+                .by_agent
+                .iter()
+                .map(|(agent, l)| (agent.as_str(), l.into()))
+                .collect(),
+            slowest: stats
+                .slowest
+                .as_ref()
+                .map(|(title, secs)| Slowest { title, secs: *secs }),
+        },
+    )
+}
+
+pub fn heat(heat: &Heat) -> Result<()> {
+    #[derive(Serialize)]
+    struct Row<'a> {
+        group: &'a str,
+        tasks: usize,
+        attention: usize,
+        waited_secs: i64,
+        completed: usize,
+        failed: usize,
+    }
+    #[derive(Serialize)]
+    struct HeatJson<'a> {
+        by: &'a str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        /// Most attention first
+        groups: Vec<Row<'a>>,
+    }
+    print(
+        "report",
+        HeatJson {
+            by: heat.by.as_str(),
+            since: heat.since,
+            until: heat.until,
+            groups: heat
+                .rows
+                .iter()
+                .map(|row| Row {
+                    group: &row.group,
+                    tasks: row.tasks,
+                    attention: row.attention,
+                    waited_secs: row.waited.num_seconds(),
+                    completed: row.completed,
+                    failed: row.failed,
+                })
+                .collect(),
+        },
+    )
+}
+
+pub fn costs(
+    daily: &[(NaiveDate, Usage)],
+    tasks: &[(String, Usage)],
+    budget: Option<f64>,
+) -> Result<()> {
+    #[derive(Serialize)]
+    struct Day {
+        day: NaiveDate,
+        #[serde(flatten)]
+        usage: UsageJson,
+    }
+    #[derive(Serialize)]
+    struct TaskCost<'a> {
+        task_id: &'a str,
+        #[serde(flatten)]
+        usage: UsageJson,
+    }
+    #[derive(Serialize)]
+    struct Costs<'a> {
+        days: Vec<Day>,
+        /// Costliest first
+        tasks: Vec<TaskCost<'a>>,
+        daily_budget_usd: Option<f64>,
+    }
+    print(
+        "costs",
+        Costs {
+            days: daily
+                .iter()
+                .map(|(day, usage)| Day {
+                    day: *day,
+                    usage: usage.into(),
+                })
+                .collect(),
+            tasks: tasks
+                .iter()
+                .map(|(task_id, usage)| TaskCost {
+                    task_id,
+                    usage: usage.into(),
+                })
+                .collect(),
+            daily_budget_usd: budget,
+        },
+    )
+}
+
+pub fn digest(digest: &Digest) -> Result<()> {
+    #[derive(Serialize)]
+    struct Entry<'a> {
+        task_id: &'a str,
+        title: &'a str,
+        exit_code: Option<i32>,
+    }
+    #[derive(Serialize)]
+    struct Wait<'a> {
+        #[serde(flatten)]
+        task: Entry<'a>,
+        secs: i64,
+    }
+    #[derive(Serialize)]
+    struct Summary<'a> {
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        started: usize,
+        completed: Vec<Entry<'a>>,
+        failed: Vec<Entry<'a>>,
+        attention_secs: i64,
+        longest_waits: Vec<Wait<'a>>,
+    }
+    fn entry(e: &DigestEntry) -> Entry<'_> {
+        Entry {
+            task_id: &e.task_id,
+            title: &e.title,
+            exit_code: e.exit_code,
+        }
+    }
+    print(
+        "summary",
+        Summary {
+            since: digest.since,
+            until: digest.until,
+            started: digest.started,
+            completed: digest.completed.iter().map(entry).collect(),
+            failed: digest.failed.iter().map(entry).collect(),
+            attention_secs: digest.attention.num_seconds(),
+            longest_waits: digest
+                .longest_waits
+                .iter()
+                .map(|(e, waited)| Wait {
+                    task: entry(e),
+                    secs: waited.num_seconds(),
+                })
+                .collect(),
+        },
+    )
+}
+
+pub fn notification_history(deliveries: &[Delivery]) -> Result<()> {
+    #[derive(Serialize)]
+    struct DeliveryJson<'a> {
+        id: i64,
+        at: DateTime<Utc>,
+        title: &'a str,
+        body: &'a str,
+        urgency: Urgency,
+        category: Category,
+        task_id: Option<&'a str>,
+        delivered: &'a [String],
+        failed: &'a [String],
+        /// Message IDs from the channels that confirm deliveries
+        receipts: &'a BTreeMap<String, String>,
+    }
+    let deliveries: Vec<_> = deliveries
+        .iter()
+        .map(|d| DeliveryJson {
+            id: d.id,
+            at: d.at,
+            title: &d.notification.title,
+            body: &d.notification.body,
+            urgency: d.notification.urgency,
+            category: d.notification.category,
+            task_id: d.notification.task_id.as_deref(),
+            delivered: &d.delivered,
+            failed: &d.failed,
+            receipts: &d.receipts,
+        })
+        .collect();
+    print("notifications history", deliveries)
+}
+
+pub fn storage(dirs: &Dirs, db_size: u64, usage: &[(String, TaskStorage)]) -> Result<()> {
+    #[derive(Serialize)]
+    struct TaskUsage<'a> {
+        task_id: &'a str,
+        output: u64,
+        recording: u64,
+        artifacts: u64,
+        total: u64,
+    }
+    #[derive(Serialize)]
+    struct Storage<'a> {
+        config_dir: &'a Path,
+        data_dir: &'a Path,
+        state_dir: &'a Path,
+        database: u64,
+        /// Largest first
+        tasks: Vec<TaskUsage<'a>>,
+    }
+    print(
+        "storage info",
+        Storage {
+            config_dir: &dirs.config,
+            data_dir: &dirs.data,
+            state_dir: &dirs.state,
+            database: db_size,
+            tasks: usage
+                .iter()
+                .map(|(task_id, s)| TaskUsage {
+                    task_id,
+                    output: s.output,
+                    recording: s.recording,
+                    artifacts: s.artifacts,
+                    total: s.total(),
+                })
+                .collect(),
+        },
+    )
+}
+
+/// `bench detectors`: times in microseconds
+pub fn bench(report: &Report) -> Result<()> {
+    #[derive(Serialize)]
+    struct Stage<'a> {
+        name: &'a str,
+        mean_us: f64,
+        p50_us: f64,
+        p95_us: f64,
+        max_us: f64,
+    }
+    #[derive(Serialize)]
+    struct Bench<'a> {
+        tasks: usize,
+        ticks: usize,
+        probe: Stage<'a>,
+        /// In the order the monitor asks them
+        detectors: Vec<Stage<'a>>,
+        check: Stage<'a>,
+        tick: Stage<'a>,
+        busy_us: f64,
+        /// Null where CPU time can't be read
+        cpu_us: Option<f64>,
+    }
+    fn us(d: std::time::Duration) -> f64 {
+        d.as_secs_f64() * 1e6
+    }
+    fn stage(timing: &Timing) -> Stage<'_> {
+        Stage {
+            name: &timing.name,
+            mean_us: us(timing.mean()),
+            p50_us: us(timing.percentile(0.5)),
+            p95_us: us(timing.percentile(0.95)),
+            max_us: us(timing.max()),
+        }
+    }
+    print(
+        "bench detectors",
+        Bench {
+            tasks: report.tasks,
+            ticks: report.ticks,
+            probe: stage(&report.probe),
+            detectors: report.detectors.iter().map(stage).collect(),
+            check: stage(&report.check),
+            tick: stage(&report.tick),
+            busy_us: us(report.busy),
+            cpu_us: report.cpu.map(us),
+        },
+    )
+}
+
+/// `doctor`: each check, then how process exits are detected
+pub fn doctor(checks: &[(&str, bool, String)], liveness: &Detection) -> Result<()> {
+    #[derive(Serialize)]
+    struct Check<'a> {
+        name: &'a str,
+        ok: bool,
+        detail: &'a str,
+    }
+    #[derive(Serialize)]
+    struct Unavailable<'a> {
+        strategy: &'a str,
+        reason: &'a str,
+    }
+    #[derive(Serialize)]
+    struct Liveness<'a> {
+        strategy: &'a str,
+        unavailable: Vec<Unavailable<'a>>,
+    }
+    #[derive(Serialize)]
+    struct Doctor<'a> {
+        checks: Vec<Check<'a>>,
+        liveness: Liveness<'a>,
+    }
+    print(
+        "doctor",
+        Doctor {
+            checks: checks
+                .iter()
+                .map(|(name, ok, detail)| Check {
+                    name,
+                    ok: *ok,
+                    detail,
+                })
+                .collect(),
+            liveness: Liveness {
+                strategy: liveness.strategy.as_str(),
+                unavailable: liveness
+                    .unavailable
+                    .iter()
+                    .map(|(strategy, reason)| Unavailable {
+                        strategy: strategy.as_str(),
+                        reason,
+                    })
+                    .collect(),
+            },
+        },
+    )
+}
+
+pub fn plugins(manifests: &[PluginManifest]) -> Result<()> {
+    #[derive(Serialize)]
+    struct Plugin<'a> {
+        name: &'a str,
+        version: &'a str,
+        description: &'a str,
+        capabilities: &'a [String],
+    }
+    let plugins: Vec<_> = manifests
+        .iter()
+        .map(|m| Plugin {
+            name: &m.name,
+            version: &m.version,
+            description: &m.description,
+            capabilities: &m.capabilities,
+        })
+        .collect();
+    print("plugins", plugins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_shape() {
+        let mut task = Task::new(
+            "t1".into(),
+            "claude_code".into(),
+            "Fix login".into(),
+            Some(42),
+            None,
+        );
+        task.set_tags(&["auth".to_string()]);
+        task.complete();
+        let tasks = [task];
+        let json: serde_json::Value = serde_json::from_str(
+            &render("list", tasks.iter().map(TaskJson::new).collect::<Vec<_>>()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["command"], "list");
+        let task = &json["data"][0];
+        let keys: Vec<&str> = task
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        // The v1 contract: adding keys is fine, losing one is not
+        for key in [
+            "task_id",
+            "agent",
+            "title",
+            "status",
+            "priority",
+            "pid",
+            "created_at",
+            "updated_at",
+            "completed_at",
+            "exit_code",
+            "attention_reason",
+            "progress",
+            "tags",
+            "last_input",
+            "unclear",
+        ] {
+            assert!(keys.contains(&key), "missing {}", key);
+        }
+        assert_eq!(task["status"], "completed");
+        assert_eq!(task["pid"], 42);
+        assert_eq!(task["tags"][0], "auth");
+        assert!(task["completed_at"].as_str().unwrap().ends_with('Z'));
+    }
+}
@@ -1,4 +1,12 @@
-use crate::models::{Task, TaskStatus};
+pub mod json;
+
+use agent_inbox::costs::Usage;
+use agent_inbox::datetime::{times, Times};
+use agent_inbox::models::progress::format_duration;
+use agent_inbox::models::{EventKind, Priority, Task, TaskEvent, TaskStatus};
+use agent_inbox::monitor::bench::{Report as Bench, Timing};
+use agent_inbox::notify::history::Delivery;
+use agent_inbox::storage::{Dirs, TaskStorage};
 use chrono::Utc;
 
 // ANSI color codes
@@ -25,21 +33,31 @@ const ICON_RUNNING: &str = "▶️ ";
 const ICON_COMPLETED: &str = "✓";
 const ICON_FAILED: &str = "✗";
 const ICON_ARROW: &str = "→";
+const ICON_NOTE: &str = "✎";
+const ICON_KEYBOARD: &str = "⌨";
 
-pub fn display_task_list(tasks: &[Task]) {
+/// `verbose` adds each task's command line and environment
+pub fn display_task_list(tasks: &[Task], verbose: bool) {
+    let mut queued = Vec::new();
     let mut running = Vec::new();
     let mut completed = Vec::new();
     let mut exited = Vec::new();
 
     for task in tasks {
         match task.status {
+            TaskStatus::Queued => queued.push(task),
             TaskStatus::Running => running.push(task),
             TaskStatus::Completed => completed.push(task),
             TaskStatus::Exited => exited.push(task),
         }
     }
 
-    if running.is_empty() && completed.is_empty() && exited.is_empty() {
+    // Higher priorities first within each section, keeping the DB order otherwise
+    for section in [&mut queued, &mut running, &mut completed, &mut exited] {
+        section.sort_by_key(|task| std::cmp::Reverse(task.priority()));
+    }
+
+    if queued.is_empty() && running.is_empty() && completed.is_empty() && exited.is_empty() {
         println!("{}{}No active tasks{}", DIM, GRAY, RESET);
         println!("{}Start a conversation in Claude.ai or Gemini to create tasks{}", DIM, RESET);
         return;
@@ -58,6 +76,9 @@ pub fn display_task_list(tasks: &[Task]) {
     if !running.is_empty() {
         summary_parts.push(format!("{}{}{} running{}", BOLD, BRIGHT_BLUE, running.len(), RESET));
     }
+    if !queued.is_empty() {
+        summary_parts.push(format!("{}{} queued{}", YELLOW, queued.len(), RESET));
+    }
     if !completed.is_empty() {
         summary_parts.push(format!("{}{} completed{}", GREEN, completed.len(), RESET));
     }
@@ -75,7 +96,18 @@ pub fn display_task_list(tasks: &[Task]) {
         println!("{}{}{} RUNNING{}", BOLD, BRIGHT_BLUE, ICON_RUNNING, RESET);
         println!("{}{}{}", GRAY, "─".repeat(50), RESET);
         for (idx, task) in running.iter().enumerate() {
-            print_task_summary(idx + 1, task);
+            print_task_summary(idx + 1, task, verbose);
+        }
+        println!();
+    }
+
+    // Queued section (waiting for a predecessor to finish)
+    if !queued.is_empty() {
+        println!("{}{} {} QUEUED{}", BOLD, YELLOW, ICON_ARROW, RESET);
+        println!("{}{}{}", GRAY, "─".repeat(50), RESET);
+        let start_idx = running.len();
+        for (idx, task) in queued.iter().enumerate() {
+            print_task_summary(start_idx + idx + 1, task, verbose);
         }
         println!();
     }
@@ -84,9 +116,9 @@ pub fn display_task_list(tasks: &[Task]) {
     if !completed.is_empty() {
         println!("{}{} {} COMPLETED{}", BOLD, GREEN, ICON_COMPLETED, RESET);
         println!("{}{}{}", GRAY, "─".repeat(50), RESET);
-        let start_idx = running.len();
+        let start_idx = running.len() + queued.len();
         for (idx, task) in completed.iter().enumerate() {
-            print_task_summary(start_idx + idx + 1, task);
+            print_task_summary(start_idx + idx + 1, task, verbose);
         }
         println!();
     }
@@ -95,9 +127,9 @@ pub fn display_task_list(tasks: &[Task]) {
     if !exited.is_empty() {
         println!("{}{} {} EXITED{}", BOLD, GRAY, ICON_FAILED, RESET);
         println!("{}{}{}", GRAY, "─".repeat(50), RESET);
-        let start_idx = running.len() + completed.len();
+        let start_idx = running.len() + queued.len() + completed.len();
         for (idx, task) in exited.iter().enumerate() {
-            print_task_summary(start_idx + idx + 1, task);
+            print_task_summary(start_idx + idx + 1, task, verbose);
         }
         println!();
     }
@@ -108,7 +140,7 @@ pub fn display_task_list(tasks: &[Task]) {
     println!();
 }
 
-fn print_task_summary(idx: usize, task: &Task) {
+fn print_task_summary(idx: usize, task: &Task, verbose: bool) {
     // Agent badge with color
     let agent_label = if let Some(pid) = task.pid {
         format!("{}:{}", task.agent_type, pid)
@@ -124,10 +156,11 @@ fn print_task_summary(idx: usize, task: &Task) {
         _ => (WHITE, agent_label.clone()),
     };
 
-    let elapsed = format_elapsed(task.updated_at.timestamp());
+    let elapsed = format_elapsed(task.updated_at);
 
     // Status indicator
     let status_indicator = match task.status {
+        TaskStatus::Queued => format!("{}{}", YELLOW, "○"),
         TaskStatus::Running => format!("{}{}", BRIGHT_BLUE, "●"),
         TaskStatus::Completed => format!("{}{}", GREEN, "●"),
         TaskStatus::Exited => format!("{}{}", GRAY, "●"),
@@ -137,7 +170,16 @@ fn print_task_summary(idx: usize, task: &Task) {
     print!("  {}{}{:2}.{} ", GRAY, BOLD, idx, RESET);
     print!("{}{} ", status_indicator, RESET);
     print!("{}{}[{}]{} ", BOLD, agent_color, badge, RESET);
+    match task.priority() {
+        Priority::Critical => print!("{}{}‼ critical{} ", BOLD, RED, RESET),
+        Priority::High => print!("{}! high{} ", YELLOW, RESET),
+        Priority::Low => print!("{}low{} ", DIM, RESET),
+        Priority::Normal => {}
+    }
     print!("{}\"{}\"{} ", WHITE, truncate(&task.title, 60), RESET);
+    if let Some(progress) = task.progress() {
+        print!("{}{}{} ", YELLOW, progress.describe(), RESET);
+    }
     println!("{}{}{}", DIM, elapsed, RESET);
 
     // Additional info for exited tasks
@@ -146,9 +188,53 @@ fn print_task_summary(idx: usize, task: &Task) {
             println!("      {}{} Exit code: {}{}", GRAY, ICON_ARROW, code, RESET);
         }
     }
+
+    // Left for the user by the daemon's check at startup
+    if let Some(reason) = task.unclear() {
+        println!(
+            "      {}? {}; settle with `report running` or `report exited`{}",
+            YELLOW, reason, RESET
+        );
+    }
+
+    // Recent typing means the user is in a conversation with the agent
+    if let Some(at) = task
+        .last_input()
+        .filter(|_| task.status == TaskStatus::Running)
+    {
+        println!(
+            "      {}{} you typed {}{}",
+            GRAY,
+            ICON_KEYBOARD,
+            format_elapsed(at),
+            RESET
+        );
+    }
+
+    if let Some(process) = task.process().filter(|_| verbose) {
+        if let Some(command_line) = &process.command_line {
+            println!("      {}$ {}{}", GRAY, truncate(command_line, 100), RESET);
+        }
+        if !process.env.is_empty() {
+            println!("      {}{}{}", GRAY, process.env_line(), RESET);
+        }
+    }
+
+    if let Some(note) = task.notes().last() {
+        println!(
+            "      {}{} {}{} {}{}{}",
+            MAGENTA,
+            ICON_NOTE,
+            truncate(&note.text, 70),
+            RESET,
+            DIM,
+            format_elapsed(note.at),
+            RESET
+        );
+    }
 }
 
-pub fn display_task_detail(task: &Task) {
+pub fn display_task_detail(task: &Task, usage: &Usage) {
     println!();
     println!("{}{}╭─────────────────────────────────────────────╮{}", BOLD, CYAN, RESET);
     println!("{}{}│  {}Task Details{}                            │{}", BOLD, CYAN, WHITE, CYAN, RESET);
@@ -157,6 +243,7 @@ pub fn display_task_detail(task: &Task) {
 
     // Status badge
     let (status_color, status_text) = match task.status {
+        TaskStatus::Queued => (YELLOW, "QUEUED"),
         TaskStatus::Running => (BRIGHT_BLUE, "RUNNING"),
         TaskStatus::Completed => (GREEN, "COMPLETED"),
         TaskStatus::Exited => (GRAY, "EXITED"),
@@ -165,9 +252,36 @@ pub fn display_task_detail(task: &Task) {
     println!("{}{}Status:{} {}{}{}{}", BOLD, GRAY, RESET, BOLD, status_color, status_text, RESET);
     println!();
 
-    println!("{}{}ID:{} {}{}{}", BOLD, GRAY, RESET, CYAN, task.task_id, RESET);
-    println!("{}{}Agent:{} {}{}{}", BOLD, GRAY, RESET, MAGENTA, task.agent_type, RESET);
-    println!("{}{}Title:{} {}{}{}", BOLD, GRAY, RESET, WHITE, task.title, RESET);
+    println!(
+        "{}{}ID:{} {}{}{}",
+        BOLD, GRAY, RESET, CYAN, task.task_id, RESET
+    );
+    println!(
+        "{}{}Agent:{} {}{}{}",
+        BOLD, GRAY, RESET, MAGENTA, task.agent_type, RESET
+    );
+    println!(
+        "{}{}Title:{} {}{}{}",
+        BOLD, GRAY, RESET, WHITE, task.title, RESET
+    );
+    println!(
+        "{}{}Priority:{} {}",
+        BOLD,
+        GRAY,
+        RESET,
+        task.priority().as_str()
+    );
+    if let Some(host) = task.host() {
+        let nickname = host
+            .nickname
+            .as_ref()
+            .map(|n| format!(" ({})", n))
+            .unwrap_or_default();
+        println!(
+            "{}{}Host:{} {}@{}{}",
+            BOLD, GRAY, RESET, host.user, host.hostname, nickname
+        );
+    }
     println!();
 
     println!("{}{}Timestamps:{}", BOLD, GRAY, RESET);
@@ -176,6 +290,15 @@ pub fn display_task_detail(task: &Task) {
     if let Some(completed) = task.completed_at {
         println!("  {}Completed: {}{}{}", GRAY, GREEN, format_datetime(&completed), RESET);
     }
+    if let Some(input) = task.last_input() {
+        println!(
+            "  {}Input:    {}{}{}",
+            GRAY,
+            RESET,
+            format_datetime(&input),
+            RESET
+        );
+    }
     println!();
 
     if task.pid.is_some() || task.ppid.is_some() {
@@ -192,6 +315,105 @@ pub fn display_task_detail(task: &Task) {
         println!();
     }
 
+    if let Some(earlier) = agent_inbox::daemon::rerun::rerun_of(task) {
+        println!("{}{}Re-run Of:{} {}", BOLD, GRAY, RESET, earlier);
+        println!();
+    }
+
+    if let Some(command) = task.focus_command() {
+        println!("{}{}Focus With:{} {}", BOLD, GRAY, RESET, command);
+        println!();
+    }
+
+    if let Some(process) = task.process() {
+        println!("{}{}Started With:{}", BOLD, GRAY, RESET);
+        if let Some(command_line) = &process.command_line {
+            println!("  {}Command: {}{}{}", GRAY, RESET, command_line, RESET);
+        }
+        for (name, value) in &process.env {
+            println!("  {}{}={}{}{}", GRAY, name, RESET, value, RESET);
+        }
+        println!();
+    }
+
+    if let Some(progress) = task.progress() {
+        println!(
+            "{}{}Progress:{} {}{}{} {}({}){}",
+            BOLD,
+            GRAY,
+            RESET,
+            YELLOW,
+            progress.describe(),
+            RESET,
+            GRAY,
+            progress.label,
+            RESET
+        );
+        println!();
+    }
+
+    if let Some(tests) = task.test_results() {
+        let color = if tests.has_failures() { RED } else { GREEN };
+        println!(
+            "{}{}Tests:{} {}{}{} {}({}){}",
+            BOLD,
+            GRAY,
+            RESET,
+            color,
+            tests.describe(),
+            RESET,
+            GRAY,
+            tests.framework,
+            RESET
+        );
+        println!();
+    }
+
+    if let Some(stats) = agent_inbox::monitor::capture::stats(task) {
+        print!(
+            "{}{}Output:{} {} captured",
+            BOLD,
+            GRAY,
+            RESET,
+            format_bytes(stats.bytes_read)
+        );
+        if stats.bytes_dropped > 0 {
+            print!(
+                ", {}{} dropped{}",
+                YELLOW,
+                format_bytes(stats.bytes_dropped),
+                RESET
+            );
+        }
+        if stats.chunks_spilled > 0 {
+            print!(", {} compressed chunk(s)", stats.chunks_spilled);
+        }
+        println!();
+        println!();
+    }
+
+    let waits: Vec<i64> = agent_inbox::stats::response_times(task)
+        .iter()
+        .map(|r| r.secs)
+        .collect();
+    if let Some(latency) = agent_inbox::stats::Latency::of(waits) {
+        println!(
+            "{}{}Response time:{} median {}, longest {} ({} answered)",
+            BOLD,
+            GRAY,
+            RESET,
+            format_duration(latency.median),
+            format_duration(latency.longest),
+            latency.count
+        );
+        println!();
+    }
+
+    if !usage.is_zero() {
+        println!("{}{}Cost:{} {}", BOLD, GRAY, RESET, usage.describe());
+        println!();
+    }
+
     if let Some(reason) = &task.attention_reason {
         println!("{}{} Attention Reason:{} {}{}{}", BOLD, YELLOW, RESET, YELLOW, reason, RESET);
         println!();
@@ -202,6 +424,64 @@ pub fn display_task_detail(task: &Task) {
         println!();
     }
 
+    let notes = task.notes();
+    if !notes.is_empty() {
+        println!("{}{}Notes:{}", BOLD, GRAY, RESET);
+        for note in &notes {
+            println!(
+                "  {}{}{}  {}{}{}",
+                GRAY,
+                format_datetime(&note.at),
+                RESET,
+                MAGENTA,
+                note.text,
+                RESET
+            );
+        }
+        println!();
+    }
+
+    let responses = agent_inbox::monitor::respond::responses(task);
+    if !responses.is_empty() {
+        println!("{}{}Auto-responses:{}", BOLD, GRAY, RESET);
+        for response in responses.iter().rev().take(5) {
+            println!(
+                "  {}{}{}  {:?} → {}{:?}{} ({})",
+                GRAY,
+                format_datetime(&response.at),
+                RESET,
+                response.prompt,
+                CYAN,
+                response.response,
+                RESET,
+                response.rule
+            );
+        }
+        println!();
+    }
+
+    let runs = task.run_history();
+    if !runs.is_empty() {
+        println!("{}{}Previous Runs:{}", BOLD, GRAY, RESET);
+        for run in runs.iter().rev().take(5) {
+            let outcome = match run.exit_code {
+                Some(code) => format!("{} (exit {})", run.status.as_str(), code),
+                None => run.status.as_str().to_string(),
+            };
+            println!(
+                "  {}{}{}  {}",
+                GRAY,
+                format_datetime(&run.started_at),
+                RESET,
+                outcome
+            );
+        }
+        if runs.len() > 5 {
+            println!("  {}... {} earlier{}", GRAY, runs.len() - 5, RESET);
+        }
+        println!();
+    }
+
     if let Some(context) = &task.context {
         println!("{}{}Context:{}", BOLD, GRAY, RESET);
         if let Some(url) = &context.url {
@@ -223,22 +503,363 @@ pub fn display_task_detail(task: &Task) {
     }
 }
 
-fn format_datetime(dt: &chrono::DateTime<Utc>) -> String {
-    dt.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+/// Output formats supported by `agent-inbox status`
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatusFormat {
+    Waybar,
+    I3blocks,
+    /// zjstatus `#[fg=...]` markup, for a Zellij status bar
+    Zellij,
+    Plain,
 }
 
-fn format_elapsed(timestamp: i64) -> String {
-    let now = Utc::now().timestamp();
-    let elapsed = now - timestamp;
+impl StatusFormat {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "waybar" => Ok(StatusFormat::Waybar),
+            "i3blocks" => Ok(StatusFormat::I3blocks),
+            "zellij" => Ok(StatusFormat::Zellij),
+            "plain" => Ok(StatusFormat::Plain),
+            _ => Err(format!(
+                "Invalid status format: {} (expected waybar, i3blocks, zellij or plain)",
+                s
+            )),
+        }
+    }
+}
 
-    if elapsed < 60 {
-        format!("({}s ago)", elapsed)
-    } else if elapsed < 3600 {
-        format!("({}m ago)", elapsed / 60)
-    } else if elapsed < 86400 {
-        format!("({}h ago)", elapsed / 3600)
+/// Pick the task that most deserves the user's eyes: the one that has been
+/// waiting for input the longest, otherwise the most recently active runner.
+/// Chronological activity log of a task, offsets relative to the first event
+pub fn display_timeline(task: &Task, events: &[TaskEvent]) {
+    println!();
+    println!(
+        "{}{}Timeline:{} {}[{}]{} {}{}{} {}({}){}",
+        BOLD,
+        CYAN,
+        RESET,
+        MAGENTA,
+        task.agent_type,
+        RESET,
+        WHITE,
+        task.title,
+        RESET,
+        GRAY,
+        task.task_id,
+        RESET
+    );
+    if let Some(process) = task.process() {
+        if let Some(command_line) = &process.command_line {
+            println!("  {}$ {}{}", GRAY, command_line, RESET);
+        }
+        if !process.env.is_empty() {
+            println!("  {}{}{}", GRAY, process.env_line(), RESET);
+        }
+    }
+    println!();
+
+    let Some(first) = events.first() else {
+        println!("  {}No events recorded{}", DIM, RESET);
+        println!();
+        return;
+    };
+
+    let times = times();
+    let mut day = None;
+    for event in events {
+        let date = times.date(event.at);
+        if day != Some(date) {
+            println!(
+                "  {}{} {}{}",
+                BOLD,
+                date.format("%Y-%m-%d"),
+                times.zone_name(),
+                RESET
+            );
+            day = Some(date);
+        }
+        let color = match event.kind {
+            EventKind::Queued | EventKind::Progress | EventKind::RateLimited => YELLOW,
+            EventKind::Started | EventKind::Resumed => BRIGHT_BLUE,
+            EventKind::Attention => RED,
+            EventKind::AttentionCleared | EventKind::Completed => GREEN,
+            EventKind::AutoResponded
+            | EventKind::Replied
+            | EventKind::Notified
+            | EventKind::Hook
+            | EventKind::Rule => CYAN,
+            EventKind::Unattended | EventKind::Killed => RED,
+            EventKind::Suppressed | EventKind::Exited => GRAY,
+            EventKind::Note => MAGENTA,
+        };
+        println!(
+            "  {}{} {:>7}{}  {}{:<17}{} {}",
+            GRAY,
+            times.local(event.at).format("%H:%M:%S"),
+            format!("+{}", format_duration((event.at - first.at).num_seconds())),
+            RESET,
+            color,
+            event.kind.as_str(),
+            RESET,
+            event.message
+        );
+    }
+    println!();
+}
+
+fn most_urgent_task(tasks: &[Task]) -> Option<&Task> {
+    tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Completed)
+        .min_by_key(|t| t.updated_at)
+        .or_else(|| {
+            tasks
+                .iter()
+                .filter(|t| t.status == TaskStatus::Running)
+                .max_by_key(|t| t.updated_at)
+        })
+}
+
+/// Render a compact, colour-free summary for status bars (waybar, polybar, i3blocks)
+/// Logged notifications, newest first, with where each went
+pub fn display_notification_history(deliveries: &[Delivery]) {
+    if deliveries.is_empty() {
+        println!("{}No notifications logged{}", DIM, RESET);
+        return;
+    }
+
+    for delivery in deliveries {
+        let notification = &delivery.notification;
+        let channels: Vec<String> = delivery
+            .delivered
+            .iter()
+            .map(|name| match delivery.receipts.get(name) {
+                Some(receipt) => {
+                    format!("{}{} {} #{}{}", GREEN, ICON_COMPLETED, name, receipt, RESET)
+                }
+                None => format!("{}{} {}{}", GREEN, ICON_COMPLETED, name, RESET),
+            })
+            .chain(
+                delivery
+                    .failed
+                    .iter()
+                    .map(|name| format!("{}{} {}{}", RED, ICON_FAILED, name, RESET)),
+            )
+            .collect();
+        println!(
+            "{}{:>5}{}  {}{}{}  {}{}{}  {}",
+            BOLD,
+            delivery.id,
+            RESET,
+            GRAY,
+            times().absolute(delivery.at),
+            RESET,
+            WHITE,
+            notification.title,
+            RESET,
+            if channels.is_empty() {
+                format!("{}no channel{}", GRAY, RESET)
+            } else {
+                channels.join(" ")
+            }
+        );
+        if let Some(line) = notification.body.lines().find(|l| !l.trim().is_empty()) {
+            println!("       {}{}{}", DIM, line, RESET);
+        }
+    }
+}
+
+/// Where files are kept, then the space each task takes, largest first
+pub fn display_storage(dirs: &Dirs, db_size: u64, usage: &[(String, TaskStorage)]) {
+    println!("{}Config:{} {}", BOLD, RESET, dirs.config.display());
+    println!("{}Data:{}   {}", BOLD, RESET, dirs.data.display());
+    println!("{}State:{}  {}", BOLD, RESET, dirs.state.display());
+    println!("{}Database:{} {}", BOLD, RESET, format_bytes(db_size));
+    if usage.is_empty() {
+        println!("{}No captured output or artifacts{}", DIM, RESET);
+        return;
+    }
+
+    println!();
+    println!(
+        "{}{:<36} {:>10} {:>10} {:>10} {:>10}{}",
+        GRAY, "TASK", "OUTPUT", "RECORDING", "ARTIFACTS", "TOTAL", RESET
+    );
+    for (task_id, storage) in usage {
+        println!(
+            "{:<36} {:>10} {:>10} {:>10} {}{:>10}{}",
+            truncate(task_id, 36),
+            format_bytes(storage.output),
+            format_bytes(storage.recording),
+            format_bytes(storage.artifacts),
+            BOLD,
+            format_bytes(storage.total()),
+            RESET
+        );
+    }
+    let total: u64 = usage.iter().map(|(_, storage)| storage.total()).sum();
+    println!(
+        "{}{:<36} {:>43}{}",
+        GRAY,
+        "",
+        format_bytes(total + db_size),
+        RESET
+    );
+}
+
+pub fn display_bench(report: &Bench) {
+    let micros = |d: std::time::Duration| match d.as_secs_f64() * 1e6 {
+        us if us >= 1000.0 => format!("{:.1}ms", us / 1000.0),
+        us => format!("{:.1}µs", us),
+    };
+    println!(
+        "{}{} tasks × {} ticks = {} checks{}",
+        BOLD,
+        report.tasks,
+        report.ticks,
+        report.check.count(),
+        RESET
+    );
+    println!();
+    println!(
+        "{}{:<24} {:>12} {:>12} {:>12} {:>12}{}",
+        GRAY, "STAGE", "MEAN", "P50", "P95", "MAX", RESET
+    );
+    let row = |timing: &Timing, style: &str| {
+        println!(
+            "{}{:<24} {:>12} {:>12} {:>12} {:>12}{}",
+            style,
+            truncate(&timing.name, 24),
+            micros(timing.mean()),
+            micros(timing.percentile(0.5)),
+            micros(timing.percentile(0.95)),
+            micros(timing.max()),
+            RESET
+        );
+    };
+    row(&report.probe, "");
+    for detector in &report.detectors {
+        row(detector, "");
+    }
+    row(&report.check, BOLD);
+    println!();
+    println!(
+        "{}Tick (every task once):{} mean {:.1}ms, p95 {:.1}ms",
+        BOLD,
+        RESET,
+        report.tick.mean().as_secs_f64() * 1e3,
+        report.tick.percentile(0.95).as_secs_f64() * 1e3
+    );
+    match (report.cpu, report.cpu_share()) {
+        (Some(cpu), Some(share)) => println!(
+            "{}CPU:{} {:.0}ms over {:.0}ms checking ({:.0}%)",
+            BOLD,
+            RESET,
+            cpu.as_secs_f64() * 1e3,
+            report.busy.as_secs_f64() * 1e3,
+            share * 100.0
+        ),
+        _ => println!("{}CPU: not measurable here{}", DIM, RESET),
+    }
+}
+
+pub fn format_status_bar(tasks: &[Task], format: &StatusFormat) -> String {
+    let running = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Running)
+        .count();
+    let completed = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Completed)
+        .count();
+    let exited = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Exited)
+        .count();
+
+    let text = format!("▶ {} ✓ {}", running, completed);
+    let urgent = most_urgent_task(tasks);
+    let urgent_line = urgent.map(|t| {
+        format!(
+            "{} [{}] {} {}",
+            t.status.as_str(),
+            t.agent_type,
+            truncate(&t.title, 40),
+            format_elapsed(t.updated_at)
+        )
+    });
+
+    // CSS class / colour hint: waiting on the user beats busy beats idle
+    let class = if completed > 0 {
+        "attention"
+    } else if running > 0 {
+        "running"
     } else {
-        format!("({}d ago)", elapsed / 86400)
+        "idle"
+    };
+
+    match format {
+        StatusFormat::Plain => match urgent_line {
+            Some(line) => format!("{} | {}", text, line),
+            None => text,
+        },
+        StatusFormat::Waybar => {
+            let tooltip = format!(
+                "{} running, {} completed, {} exited{}",
+                running,
+                completed,
+                exited,
+                urgent_line.map(|l| format!("\n{}", l)).unwrap_or_default()
+            );
+            serde_json::json!({
+                "text": text,
+                "alt": class,
+                "class": class,
+                "tooltip": tooltip,
+            })
+            .to_string()
+        }
+        StatusFormat::Zellij => format!("#[fg={}]{}", status_color(class), text),
+        StatusFormat::I3blocks => {
+            // i3blocks reads full_text, short_text and color on separate lines
+            let color = status_color(class);
+            let full_text = match urgent_line {
+                Some(line) => format!("{} {}", text, line),
+                None => text.clone(),
+            };
+            format!("{}\n{}\n{}", full_text, text, color)
+        }
+    }
+}
+
+fn status_color(class: &str) -> &'static str {
+    match class {
+        "attention" => "#a6e3a1",
+        "running" => "#89b4fa",
+        _ => "#6c7086",
+    }
+}
+
+fn format_datetime(dt: &chrono::DateTime<Utc>) -> String {
+    times().absolute(*dt)
+}
+
+/// "(12m ago)", or the time in full once it is older than `[time]
+/// relative_hours`
+fn format_elapsed(at: chrono::DateTime<Utc>) -> String {
+    elapsed_with(times(), at, Utc::now())
+}
+
+fn elapsed_with(times: &Times, at: chrono::DateTime<Utc>, now: chrono::DateTime<Utc>) -> String {
+    format!("({})", times.relative(at, now))
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 => format!("{:.1} MiB", b as f64 / (1024.0 * 1024.0)),
+        b if b >= 1024 => format!("{:.1} KiB", b as f64 / 1024.0),
+        b => format!("{} B", b),
     }
 }
 
@@ -256,11 +877,61 @@ mod tests {
 
     #[test]
     fn test_format_elapsed() {
-        let now = Utc::now().timestamp();
+        // The defaults, whatever the [time] section of this machine's config
+        let times = Times::default();
+        let now = Utc::now();
+        let ago = |secs: i64| elapsed_with(&times, now - chrono::Duration::seconds(secs), now);
+
+        assert_eq!(ago(30), "(30s ago)");
+        assert_eq!(ago(120), "(2m ago)");
+        assert_eq!(ago(3660), "(1h1m ago)");
+        // Past a day, the time in full
+        assert_eq!(
+            ago(90000),
+            format!(
+                "({})",
+                times.absolute(now - chrono::Duration::seconds(90000))
+            )
+        );
+    }
+
+    fn task(id: &str, status: TaskStatus) -> Task {
+        let mut task = Task::new(
+            id.to_string(),
+            "claude_code".to_string(),
+            id.to_string(),
+            None,
+            None,
+        );
+        task.status = status;
+        task
+    }
 
-        assert_eq!(format_elapsed(now - 30), "(30s ago)");
-        assert_eq!(format_elapsed(now - 120), "(2m ago)");
-        assert_eq!(format_elapsed(now - 3660), "(1h ago)");
-        assert_eq!(format_elapsed(now - 90000), "(1d ago)");
+    #[test]
+    fn test_status_bar_formats() {
+        let tasks = vec![
+            task("busy", TaskStatus::Running),
+            task("waiting", TaskStatus::Completed),
+            task("gone", TaskStatus::Exited),
+        ];
+
+        let plain = format_status_bar(&tasks, &StatusFormat::Plain);
+        assert!(plain.starts_with("▶ 1 ✓ 1 | completed [claude_code] waiting"));
+
+        let waybar: serde_json::Value =
+            serde_json::from_str(&format_status_bar(&tasks, &StatusFormat::Waybar)).unwrap();
+        assert_eq!(waybar["class"], "attention");
+        assert_eq!(waybar["text"], "▶ 1 ✓ 1");
+
+        let i3 = format_status_bar(&tasks, &StatusFormat::I3blocks);
+        assert_eq!(i3.lines().count(), 3);
+
+        assert_eq!(
+            format_status_bar(&tasks, &StatusFormat::Zellij),
+            "#[fg=#a6e3a1]▶ 1 ✓ 1"
+        );
+
+        assert_eq!(format_status_bar(&[], &StatusFormat::Plain), "▶ 0 ✓ 0");
+        assert!(StatusFormat::from_str("polybar").is_err());
     }
 }
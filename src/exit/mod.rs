@@ -0,0 +1,135 @@
+//! Exit codes, for scripts that branch on why a command failed
+//!
+//! | code | kind                 | meaning                                          |
+//! |------|----------------------|--------------------------------------------------|
+//! | 0    | `ok`                 | done                                             |
+//! | 1    | `error`              | anything not listed below                        |
+//! | 2    | `task_failed`        | the task exited with a non-zero code             |
+//! | 3    | `needs_attention`    | the task is waiting for the user                 |
+//! | 4    | `daemon_unreachable` | the command needs a daemon and none is running   |
+//! | 5    | `not_found`          | no task or notification with the given ID        |
+//! | 6    | `undelivered`        | no channel delivered the notification            |
+//! | 7    | `timed_out`          | `wait --timeout` ran out                         |
+//! | 64   | `usage`              | bad arguments (`EX_USAGE`)                       |
+//!
+//! Commands raise a code by failing with a [`Failure`]; every other error
+//! exits 1. `agent-inbox -- <command>` is the exception: it exits with the
+//! command's own code.
+
+use crate::models::{Task, TaskStatus};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    Ok = 0,
+    Error = 1,
+    TaskFailed = 2,
+    NeedsAttention = 3,
+    DaemonUnreachable = 4,
+    NotFound = 5,
+    Undelivered = 6,
+    TimedOut = 7,
+    Usage = 64,
+}
+
+impl Code {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Code::Ok => "ok",
+            Code::Error => "error",
+            Code::TaskFailed => "task_failed",
+            Code::NeedsAttention => "needs_attention",
+            Code::DaemonUnreachable => "daemon_unreachable",
+            Code::NotFound => "not_found",
+            Code::Undelivered => "undelivered",
+            Code::TimedOut => "timed_out",
+            Code::Usage => "usage",
+        }
+    }
+
+    pub fn value(self) -> i32 {
+        self as i32
+    }
+}
+
+/// An error that ends the command with `code`
+#[derive(Debug)]
+pub struct Failure {
+    pub code: Code,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(code: Code, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn task_not_found(task_id: &str) -> Self {
+        Self::new(Code::NotFound, format!("Task not found: {}", task_id))
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// The code `error` ends the command with: that of the first [`Failure`]
+/// in its chain, 1 without one
+pub fn code_of(error: &anyhow::Error) -> Code {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<Failure>())
+        .map_or(Code::Error, |failure| failure.code)
+}
+
+/// Where `task` stands for someone waiting on it: None while it is still
+/// going or queued, [`Code::Ok`] once it is done or its agent finished its
+/// turn. An exit without a known code counts as done.
+pub fn outcome(task: &Task) -> Option<Code> {
+    match task.status {
+        TaskStatus::Exited if task.exit_code.is_some_and(|code| code != 0) => {
+            Some(Code::TaskFailed)
+        }
+        TaskStatus::Exited | TaskStatus::Completed => Some(Code::Ok),
+        TaskStatus::Running if task.attention_reason.is_some() => Some(Code::NeedsAttention),
+        TaskStatus::Running | TaskStatus::Queued => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_codes() {
+        let error =
+            anyhow::Error::new(Failure::task_not_found("t1")).context("Failed to show the task");
+        assert_eq!(code_of(&error), Code::NotFound);
+        assert_eq!(
+            format!("{:#}", error),
+            "Failed to show the task: Task not found: t1"
+        );
+        let error = Err::<(), _>(std::io::Error::other("disk full"))
+            .context("Failed to write")
+            .unwrap_err();
+        assert_eq!(code_of(&error), Code::Error);
+
+        let mut task = Task::new("t1".into(), "claude_code".into(), "Fix".into(), None, None);
+        assert_eq!(outcome(&task), None);
+        task.attention_reason = Some("Waiting for input".into());
+        assert_eq!(outcome(&task), Some(Code::NeedsAttention));
+        task.set_exited(Some(3));
+        assert_eq!(outcome(&task), Some(Code::TaskFailed));
+        task.set_exited(None);
+        assert_eq!(outcome(&task), Some(Code::Ok));
+        assert_eq!(Code::Usage.value(), 64);
+    }
+}
@@ -0,0 +1,145 @@
+//! Bearer tokens for the hub API
+//!
+//! Each token has a scope: `observe` covers only the observer view, `read`
+//! the task listings, `control` also forwarding notifications. Tokens are
+//! configured by the name of the environment variable holding them, never
+//! in the config file itself.
+
+use crate::config::TokenConfig;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// What a token may do; each scope includes the ones before it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Observe,
+    #[default]
+    Read,
+    Control,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Scope::Observe => "observe",
+            Scope::Read => "read",
+            Scope::Control => "control",
+        }
+    }
+}
+
+/// Why a request was turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denied {
+    /// No token, or an unknown one (401)
+    Unauthenticated,
+    /// A valid token without the needed scope (403)
+    Forbidden,
+}
+
+struct Token {
+    name: String,
+    secret: String,
+    scope: Scope,
+}
+
+/// The accepted tokens. With none, every request is let through; the hub
+/// only allows that on loopback or behind mutual TLS.
+#[derive(Default)]
+pub struct Auth {
+    tokens: Vec<Token>,
+}
+
+impl Auth {
+    /// Tokens from `[[daemon.tokens]]`; fails when a variable is unset
+    pub fn from_config(tokens: &[TokenConfig]) -> Result<Self> {
+        tokens.iter().try_fold(Self::default(), |auth, token| {
+            let secret = std::env::var(&token.token_env)
+                .ok()
+                .filter(|s| !s.is_empty())
+                .with_context(|| {
+                    format!("{} (token '{}') is not set", token.token_env, token.name)
+                })?;
+            Ok(auth.token(&token.name, &secret, token.scope))
+        })
+    }
+
+    pub fn token(mut self, name: &str, secret: &str, scope: Scope) -> Self {
+        self.tokens.push(Token {
+            name: name.to_string(),
+            secret: secret.to_string(),
+            scope,
+        });
+        self
+    }
+
+    /// No tokens configured
+    pub fn is_open(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Check an `Authorization` header against the scope a request needs.
+    /// Returns the token's name ("" when the API is open).
+    pub fn check(&self, authorization: Option<&str>, needed: Scope) -> Result<&str, Denied> {
+        if self.is_open() {
+            return Ok("");
+        }
+        let presented = authorization
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .ok_or(Denied::Unauthenticated)?;
+        let token = self
+            .tokens
+            .iter()
+            .find(|t| constant_time_eq(t.secret.as_bytes(), presented.as_bytes()))
+            .ok_or(Denied::Unauthenticated)?;
+        if token.scope < needed {
+            return Err(Denied::Forbidden);
+        }
+        Ok(&token.name)
+    }
+}
+
+/// Compare without leaking where the first difference is
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes() {
+        let auth = Auth::default()
+            .token("phone", "r3ad", Scope::Read)
+            .token("laptop", "c0ntrol", Scope::Control)
+            .token("teammate", "0bserve", Scope::Observe);
+
+        assert_eq!(auth.check(Some("Bearer r3ad"), Scope::Read), Ok("phone"));
+        assert_eq!(
+            auth.check(Some("Bearer r3ad"), Scope::Control),
+            Err(Denied::Forbidden)
+        );
+        assert_eq!(
+            auth.check(Some("Bearer c0ntrol"), Scope::Read),
+            Ok("laptop")
+        );
+        assert_eq!(
+            auth.check(Some("Bearer 0bserve"), Scope::Observe),
+            Ok("teammate")
+        );
+        assert_eq!(
+            auth.check(Some("Bearer 0bserve"), Scope::Read),
+            Err(Denied::Forbidden)
+        );
+        assert_eq!(auth.check(Some("Bearer r3ad"), Scope::Observe), Ok("phone"));
+        assert_eq!(
+            auth.check(Some("Bearer nope"), Scope::Read),
+            Err(Denied::Unauthenticated)
+        );
+        assert_eq!(auth.check(None, Scope::Read), Err(Denied::Unauthenticated));
+        assert_eq!(Auth::default().check(None, Scope::Control), Ok(""));
+    }
+}
@@ -0,0 +1,854 @@
+//! Several machines, one place that notifies
+//!
+//! Edge machines list the `hub` channel in `[notify] channels`: instead of
+//! notifying themselves, they forward every notification to a hub daemon,
+//! which sends it through its own channels with the edge's name in front.
+//! The phone only has to pair with the hub, however many machines run
+//! agents.
+//!
+//! Two transports reach the hub:
+//!
+//! - HTTP: the hub daemon listens on `[daemon] listen` and accepts
+//!   `POST /v1/notify` with an [`Envelope`] as the JSON body.
+//! - SSH: the edge runs `agent-inbox hub receive` on the hub and writes the
+//!   envelope to its stdin, so nothing has to listen at all.
+//!
+//! The same listener serves read-only task listings in the
+//! [`schema`](crate::schema) (`GET /v1/tasks`, `GET /v1/tasks/<id>`), and
+//! with `[daemon] observe = true` the [`observe`] view for teammates.
+//! Beyond loopback it requires [`auth`] tokens, mutual [`tls`], or both.
+//! Edges and hubs of different releases agree on a [`protocol`] version
+//! first.
+
+pub mod auth;
+pub mod observe;
+pub mod protocol;
+#[cfg(feature = "tls")]
+pub mod tls;
+
+use crate::config::{DaemonConfig, NotifyConfig};
+use crate::db::Database;
+use crate::monitor::redact::SecretFilter;
+use crate::notify::{Notification, Notifier, NotifierBuilder};
+use crate::schema::{TaskInfo, TaskList, Timeline};
+use anyhow::{Context, Result};
+use auth::{Auth, Denied, Scope};
+use chrono::{DateTime, Utc};
+use protocol::Versions;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Largest request body the hub accepts
+const MAX_BODY: usize = 1024 * 1024;
+
+/// Largest request line and headers the hub accepts
+const MAX_HEAD: u64 = 16 * 1024;
+
+/// How long a connection may take to send its whole request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Requests read at once; connections beyond these are closed unanswered
+const MAX_CONNECTIONS: usize = 32;
+
+/// Observer streams followed at once, each holding one of the
+/// [`MAX_CONNECTIONS`]; more are refused, so requests always get through
+const MAX_STREAMS: usize = 16;
+
+/// How long a write may wait on a client that doesn't read; a stream to one
+/// that stopped reading ends then
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A notification forwarded from an edge machine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    /// The machine it came from
+    pub node: String,
+    pub notification: Notification,
+    pub sent_at: DateTime<Utc>,
+    /// Versions the sender speaks
+    #[serde(default = "legacy_versions")]
+    pub protocol: Versions,
+}
+
+fn legacy_versions() -> Versions {
+    Versions::LEGACY
+}
+
+impl Envelope {
+    pub fn new(node: &str, notification: &Notification) -> Self {
+        Self {
+            node: node.to_string(),
+            notification: notification.clone(),
+            sent_at: Utc::now(),
+            protocol: Versions::OURS,
+        }
+    }
+
+    /// Read an envelope from `body`. One from an edge this hub shares no
+    /// protocol version with is refused with a message saying which
+    /// machine to upgrade.
+    pub fn parse(body: &[u8]) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_slice(body).context("Invalid envelope")?;
+        let theirs = match value.get("protocol") {
+            Some(protocol) => {
+                serde_json::from_value(protocol.clone()).context("Invalid envelope")?
+            }
+            None => Versions::LEGACY,
+        };
+        if let Err(mismatch) = Versions::OURS.negotiate(theirs) {
+            anyhow::bail!("{}", mismatch.explain("the hub", "the edge"));
+        }
+        serde_json::from_value(value).map_err(|e| {
+            if theirs.max > protocol::VERSION {
+                anyhow::anyhow!(
+                    "Invalid envelope: {} (the edge speaks protocol {}, the hub {}: upgrade agent-inbox on the hub)",
+                    e,
+                    theirs,
+                    Versions::OURS
+                )
+            } else {
+                anyhow::anyhow!("Invalid envelope: {}", e)
+            }
+        })
+    }
+
+    /// The notification as the hub sends it: "laptop: [claude_code] Fix bug"
+    pub fn localized(&self) -> Notification {
+        Notification {
+            title: format!("{}: {}", self.node, self.notification.title),
+            // The task lives on the edge, out of reach of the hub's actions
+            task_id: None,
+            ..self.notification.clone()
+        }
+    }
+}
+
+/// This machine's name: $HOSTNAME, else the kernel's hostname
+pub fn node_name() -> String {
+    crate::host::hostname()
+}
+
+/// The notifier a hub dispatches forwarded notifications with: its own
+/// channels, minus `hub` itself so two machines can't bounce a
+/// notification between them forever
+pub fn hub_notifier(config: &NotifyConfig) -> Result<Notifier> {
+    let mut config = config.clone();
+    config.channels.retain(|name| name != "hub");
+    // Forwarded titles already start with the edge's name
+    config.host.in_title = false;
+    Ok(NotifierBuilder::from_config(&config)?.build())
+}
+
+/// Dispatch a forwarded notification; returns the channels that took it
+pub fn receive(notifier: &Notifier, envelope: &Envelope) -> Vec<String> {
+    notifier.notify(&envelope.localized())
+}
+
+/// One API request
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    /// Versions the client speaks
+    protocol: Versions,
+    body: Vec<u8>,
+}
+
+/// How to answer a request
+enum Reply {
+    Json(u16, serde_json::Value),
+    /// Keep the connection open, streaming this task's events
+    Stream(String),
+}
+
+impl From<(u16, serde_json::Value)> for Reply {
+    fn from((status, body): (u16, serde_json::Value)) -> Self {
+        Reply::Json(status, body)
+    }
+}
+
+/// HTTP endpoint of a hub daemon
+pub struct Hub {
+    listener: TcpListener,
+    auth: Auth,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<rustls::ServerConfig>>,
+    db_path: Option<PathBuf>,
+    observe: bool,
+    secrets: SecretFilter,
+    /// Observer streams open
+    streams: AtomicUsize,
+}
+
+impl Hub {
+    pub fn bind(addr: &str) -> Result<Self> {
+        let listener =
+            TcpListener::bind(addr).with_context(|| format!("Failed to listen on {}", addr))?;
+        Ok(Self {
+            listener,
+            auth: Auth::default(),
+            #[cfg(feature = "tls")]
+            tls: None,
+            db_path: None,
+            observe: false,
+            secrets: SecretFilter::default(),
+            streams: AtomicUsize::new(0),
+        })
+    }
+
+    /// The API as `[daemon]` configures it. Refuses to serve beyond
+    /// loopback without tokens or client certificates.
+    pub fn from_config(config: &DaemonConfig, addr: &str, db_path: PathBuf) -> Result<Self> {
+        let hub = Self::bind(addr)?
+            .auth(Auth::from_config(&config.tokens)?)
+            .tasks(db_path)
+            .observe(config.observe);
+        let mutual_tls = config
+            .tls
+            .as_ref()
+            .is_some_and(|tls| tls.client_ca.is_some());
+        #[cfg(feature = "tls")]
+        let hub = match &config.tls {
+            Some(tls) => hub.tls(tls::server_config(tls)?),
+            None => hub,
+        };
+        #[cfg(not(feature = "tls"))]
+        anyhow::ensure!(
+            config.tls.is_none(),
+            "[daemon.tls] is configured but agent-inbox was built without the `tls` feature"
+        );
+        let local = hub.local_addr()?;
+        if !local.ip().is_loopback() && hub.auth.is_open() && !mutual_tls {
+            anyhow::bail!(
+                "Refusing to serve the API on {} without authentication; add [[daemon.tokens]] or [daemon.tls] client_ca",
+                local
+            );
+        }
+        Ok(hub)
+    }
+
+    /// Require bearer tokens
+    pub fn auth(mut self, auth: Auth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Serve HTTPS
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, config: Arc<rustls::ServerConfig>) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    /// Serve the task listings of this database
+    pub fn tasks(mut self, db_path: PathBuf) -> Self {
+        self.db_path = Some(db_path);
+        self
+    }
+
+    /// Serve the observer view of the task database
+    pub fn observe(mut self, enabled: bool) -> Self {
+        self.observe = enabled;
+        self
+    }
+
+    /// Redact what is served with `secrets` rather than the built-in
+    /// patterns
+    pub fn secrets(mut self, secrets: SecretFilter) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept requests forever, each connection on a thread of its own,
+    /// handing each forwarded notification to `handle`, which returns the
+    /// channels that delivered it
+    pub fn serve<F>(self, handle: F)
+    where
+        F: Fn(Envelope) -> Vec<String> + Send + Sync + 'static,
+    {
+        let hub = Arc::new(self);
+        let handle = Arc::new(handle);
+        let open = Arc::new(AtomicUsize::new(0));
+        for stream in hub.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Hub request failed: {:#}", e);
+                    continue;
+                }
+            };
+            if open.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                open.fetch_sub(1, Ordering::SeqCst);
+                eprintln!(
+                    "Hub busy with {} requests, closing a connection",
+                    MAX_CONNECTIONS
+                );
+                continue;
+            }
+            let (hub, handle, open) = (hub.clone(), handle.clone(), open.clone());
+            thread::spawn(move || {
+                if let Err(e) = hub.handle_connection(stream, &*handle) {
+                    eprintln!("Hub request failed: {:#}", e);
+                }
+                open.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    }
+
+    /// Serve on a background thread
+    pub fn spawn<F>(self, handle: F) -> thread::JoinHandle<()>
+    where
+        F: Fn(Envelope) -> Vec<String> + Send + Sync + 'static,
+    {
+        thread::spawn(move || self.serve(handle))
+    }
+
+    fn handle_connection(
+        &self,
+        stream: TcpStream,
+        handle: &dyn Fn(Envelope) -> Vec<String>,
+    ) -> Result<()> {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let stream = Deadline {
+            stream,
+            until: Instant::now() + READ_TIMEOUT,
+        };
+        #[cfg(feature = "tls")]
+        if let Some(config) = &self.tls {
+            let connection = rustls::ServerConnection::new(config.clone())?;
+            let mut stream = rustls::StreamOwned::new(connection, stream);
+            match self.exchange(&mut stream, handle)? {
+                Some(task_id) => self.stream(stream, task_id),
+                None => {
+                    stream.conn.send_close_notify();
+                    stream.flush()?;
+                }
+            }
+            return Ok(());
+        }
+        let mut stream = stream;
+        if let Some(task_id) = self.exchange(&mut stream, handle)? {
+            self.stream(stream, task_id);
+        }
+        Ok(())
+    }
+
+    /// Read one request from `stream` and write the response. Returns the
+    /// task whose events the connection should go on streaming, if any.
+    fn exchange<S: Read + Write>(
+        &self,
+        stream: &mut S,
+        handle: &dyn Fn(Envelope) -> Vec<String>,
+    ) -> Result<Option<String>> {
+        let reply = match read_request(stream) {
+            Ok(request) => self.route(&request, handle),
+            Err(e) => error(400, &format!("{:#}", e)).into(),
+        };
+        match reply {
+            Reply::Json(status, body) => write_response(stream, status, &body).map(|_| None),
+            Reply::Stream(task_id) => Ok(Some(task_id)),
+        }
+    }
+
+    /// Follow a task's events on the connection's thread, until the
+    /// observer leaves or stops reading; frees the slot [`route`](Self::route)
+    /// took for it
+    fn stream<S: Write>(&self, mut stream: S, task_id: String) {
+        if let Some(db_path) = &self.db_path {
+            if let Err(e) = observe::stream(db_path, &task_id, &self.secrets, &mut stream) {
+                eprintln!("Event stream of {} ended: {:#}", task_id, e);
+            }
+        }
+        self.streams.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn route(&self, request: &Request, handle: &dyn Fn(Envelope) -> Vec<String>) -> Reply {
+        if let Err(mismatch) = Versions::OURS.negotiate(request.protocol) {
+            return error(426, &mismatch.explain("the hub", "the client")).into();
+        }
+        let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        let needed = match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["v1", "observe", ..]) => Scope::Observe,
+            ("GET", _) => Scope::Read,
+            _ => Scope::Control,
+        };
+        let client = match self.auth.check(request.authorization.as_deref(), needed) {
+            Ok(name) => name,
+            Err(Denied::Unauthenticated) => return error(401, "Missing or unknown token").into(),
+            Err(Denied::Forbidden) => {
+                return error(403, &format!("Token lacks the {} scope", needed.as_str())).into()
+            }
+        };
+
+        let reply = match (request.method.as_str(), segments.as_slice()) {
+            ("POST", ["v1", "notify"]) => match Envelope::parse(&request.body) {
+                Ok(envelope) => {
+                    if !client.is_empty() {
+                        eprintln!("Notification from {} (token '{}')", envelope.node, client);
+                    }
+                    (200, serde_json::json!({ "delivered": handle(envelope) }))
+                }
+                Err(e) => error(400, &format!("{:#}", e)),
+            },
+            ("GET", ["v1", "tasks"]) => self.with_db(|db| {
+                let tasks = db.list_tasks(None)?.iter().map(TaskInfo::of).collect();
+                Ok(serde_json::to_value(TaskList::new(tasks))?)
+            }),
+            ("GET", ["v1", "tasks", task_id]) => self.with_db(|db| {
+                Ok(match db.get_task_by_id(task_id)? {
+                    Some(task) => serde_json::to_value(Timeline::new(
+                        TaskInfo::of(&task),
+                        &db.task_events(task_id)?,
+                    ))?,
+                    None => serde_json::Value::Null,
+                })
+            }),
+            (_, ["v1", "observe", ..]) if !self.observe || self.db_path.is_none() => {
+                error(404, "Not found")
+            }
+            ("GET", ["v1", "observe", "tasks"]) => self.with_db(observe::list),
+            ("GET", ["v1", "observe", "tasks", task_id]) => {
+                self.with_db(|db| observe::timeline(db, task_id))
+            }
+            ("GET", ["v1", "observe", "tasks", task_id, "stream"]) => {
+                match self.with_db(|db| observe::timeline(db, task_id)) {
+                    (200, _) if self.streams.fetch_add(1, Ordering::SeqCst) >= MAX_STREAMS => {
+                        self.streams.fetch_sub(1, Ordering::SeqCst);
+                        error(503, "Too many event streams")
+                    }
+                    (200, _) => return Reply::Stream(task_id.to_string()),
+                    reply => reply,
+                }
+            }
+            (_, ["v1", "notify"]) | (_, ["v1", "tasks", ..]) | (_, ["v1", "observe", ..]) => {
+                error(405, "Method not allowed")
+            }
+            _ => error(404, "Not found"),
+        };
+        reply.into()
+    }
+
+    /// Answer from the task database, redacted; null means not found
+    fn with_db(
+        &self,
+        query: impl FnOnce(&Database) -> Result<serde_json::Value>,
+    ) -> (u16, serde_json::Value) {
+        let Some(path) = &self.db_path else {
+            return error(404, "Not found");
+        };
+        match Database::open(path).and_then(|db| query(&db)) {
+            Ok(serde_json::Value::Null) => error(404, "Not found"),
+            Ok(mut value) => {
+                self.secrets.redact_json(&mut value);
+                (200, value)
+            }
+            Err(e) => error(500, &format!("{:#}", e)),
+        }
+    }
+}
+
+fn error(status: u16, message: &str) -> (u16, serde_json::Value) {
+    (status, serde_json::json!({ "error": message }))
+}
+
+/// A connection that has [`READ_TIMEOUT`] from being accepted to send its
+/// request, however slowly it trickles in
+struct Deadline {
+    stream: TcpStream,
+    until: Instant,
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Request took too long",
+            ));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for Deadline {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Read one HTTP/1.1 request
+fn read_request<S: Read>(stream: &mut S) -> Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut head = (&mut reader).take(MAX_HEAD);
+    let mut line = String::new();
+    let mut next_line = |line: &mut String| -> Result<usize> {
+        line.clear();
+        let n = head.read_line(line)?;
+        anyhow::ensure!(head.limit() > 0, "Request header too large");
+        Ok(n)
+    };
+    next_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        anyhow::bail!("Malformed request line");
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut length = 0;
+    let mut authorization = None;
+    let mut protocol = Versions::LEGACY;
+    loop {
+        if next_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().context("Invalid Content-Length")?;
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            } else if name.eq_ignore_ascii_case(protocol::HEADER) {
+                protocol = Versions::from_str(value.trim()).map_err(anyhow::Error::msg)?;
+            }
+        }
+    }
+    if length > MAX_BODY {
+        anyhow::bail!("Request body too large");
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Request {
+        method,
+        path,
+        authorization,
+        protocol,
+        body,
+    })
+}
+
+fn write_response<S: Write>(stream: &mut S, status: u16, body: &serde_json::Value) -> Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        426 => "Upgrade Required",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        protocol::HEADER,
+        Versions::OURS,
+        body
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::{Category, Urgency};
+    use std::sync::mpsc;
+
+    fn post(addr: SocketAddr, path: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: hub\r\nContent-Length: {}\r\n\r\n{}",
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_hub_accepts_forwarded_notifications() {
+        let hub = Hub::bind("127.0.0.1:0").unwrap();
+        let addr = hub.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        hub.spawn(move |envelope| {
+            tx.send(envelope.localized()).unwrap();
+            vec!["desktop".to_string()]
+        });
+
+        let envelope = Envelope::new(
+            "laptop",
+            &Notification {
+                title: "[claude_code] Fix bug".to_string(),
+                body: "Done".to_string(),
+                urgency: Urgency::Normal,
+                category: Category::Completed,
+                task_id: None,
+                reason: None,
+            },
+        );
+        let response = post(
+            addr,
+            "/v1/notify",
+            &serde_json::to_string(&envelope).unwrap(),
+        );
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with(r#"{"delivered":["desktop"]}"#));
+        assert_eq!(rx.recv().unwrap().title, "laptop: [claude_code] Fix bug");
+
+        assert!(post(addr, "/v1/notify", "{}").starts_with("HTTP/1.1 400"));
+        assert!(post(addr, "/v1/other", "").starts_with("HTTP/1.1 404"));
+
+        // Edges name the versions they speak; the hub answers with its own
+        let ours = format!("{}: {}", protocol::HEADER, Versions::OURS);
+        assert!(response.contains(&ours));
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET /v1/tasks HTTP/1.1\r\n{}: 7-9\r\n\r\n",
+            protocol::HEADER
+        )
+        .unwrap();
+        let mut refused = String::new();
+        stream.read_to_string(&mut refused).unwrap();
+        assert!(refused.starts_with("HTTP/1.1 426"));
+        assert!(refused.contains(&ours));
+        assert!(refused.contains("upgrade agent-inbox on the hub"));
+
+        // Envelopes from before versioning still parse
+        let mut legacy = serde_json::to_value(&envelope).unwrap();
+        legacy.as_object_mut().unwrap().remove("protocol");
+        assert_eq!(
+            Envelope::parse(legacy.to_string().as_bytes())
+                .unwrap()
+                .protocol,
+            Versions::LEGACY
+        );
+        let mut future = serde_json::to_value(&envelope).unwrap();
+        future["protocol"] = serde_json::json!("7");
+        let e = Envelope::parse(future.to_string().as_bytes()).unwrap_err();
+        assert!(e.to_string().ends_with("upgrade agent-inbox on the hub"));
+
+        // An open API stays on loopback
+        let open = DaemonConfig::default();
+        assert!(Hub::from_config(&open, "0.0.0.0:0", PathBuf::from("tasks.db")).is_err());
+        assert!(Hub::from_config(&open, "127.0.0.1:0", PathBuf::from("tasks.db")).is_ok());
+    }
+
+    #[test]
+    fn test_hub_connections_are_independent() {
+        let hub = Hub::bind("127.0.0.1:0").unwrap();
+        let addr = hub.local_addr().unwrap();
+        hub.spawn(|_| Vec::new());
+
+        // A client that never finishes its request holds up no one else
+        let mut idle = TcpStream::connect(addr).unwrap();
+        write!(idle, "POST /v1/notify HTTP/1.1\r\n").unwrap();
+        let started = Instant::now();
+        assert!(post(addr, "/v1/other", "").starts_with("HTTP/1.1 404"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // Nor does an endless header fill its memory; all of this one is
+        // read, so the hub closes the connection cleanly
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut request = "GET /v1/tasks HTTP/1.1\r\nX-Filler: ".to_string();
+        request.extend(std::iter::repeat_n('x', MAX_HEAD as usize - request.len()));
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400"));
+        assert!(response.contains("Request header too large"));
+    }
+
+    #[test]
+    fn test_observer_view() {
+        use crate::models::{EventKind, Task};
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("tasks.db");
+        let db = Database::open(&db_path).unwrap();
+        db.insert_task(&Task::new(
+            "t1".into(),
+            "claude_code".into(),
+            "Deploy".into(),
+            Some(4242),
+            None,
+        ))
+        .unwrap();
+        db.insert_task(&Task::new(
+            "t2".into(),
+            "claude_code".into(),
+            "Rotate password=swordfish99".into(),
+            None,
+            None,
+        ))
+        .unwrap();
+        db.log_event("t1", EventKind::Started, "pid 4242");
+        db.log_event("t1", EventKind::Note, "deploy with token=hunter2hunter2");
+        let hub = Hub::bind("127.0.0.1:0")
+            .unwrap()
+            .tasks(db_path)
+            .observe(true)
+            .auth(Auth::default().token("teammate", "watch", Scope::Observe));
+        let addr = hub.local_addr().unwrap();
+        hub.spawn(|_| Vec::new());
+
+        let connect = |method: &str, path: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "{} {} HTTP/1.1\r\nAuthorization: Bearer watch\r\nContent-Length: 0\r\n\r\n",
+                method, path
+            )
+            .unwrap();
+            BufReader::new(stream)
+        };
+        let request = |method: &str, path: &str| {
+            let mut response = String::new();
+            connect(method, path).read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let list = request("GET", "/v1/observe/tasks");
+        assert!(list.starts_with("HTTP/1.1 200"));
+        assert!(list.contains(r#""title":"Deploy""#) && !list.contains("4242"));
+        assert!(list.contains("[REDACTED]") && !list.contains("swordfish99"));
+        let timeline = request("GET", "/v1/observe/tasks/t1");
+        assert!(timeline.contains(r#""type":"started""#));
+        // Events go without their messages, and with them the pid
+        assert!(!timeline.contains("4242") && !timeline.contains("hunter2"));
+        assert!(request("GET", "/v1/observe/tasks/nope/stream").starts_with("HTTP/1.1 404"));
+        // Nothing beyond the observer view
+        assert!(request("GET", "/v1/tasks").starts_with("HTTP/1.1 403"));
+        assert!(request("POST", "/v1/notify").starts_with("HTTP/1.1 403"));
+
+        let mut stream = connect("GET", "/v1/observe/tasks/t1/stream");
+        let mut seen = String::new();
+        let mut next_event = || {
+            let mut line = String::new();
+            while !line.starts_with("event:") {
+                line.clear();
+                stream.read_line(&mut line).unwrap();
+                seen += &line;
+            }
+            line.trim().to_string()
+        };
+        assert_eq!(next_event(), "event: started");
+        assert_eq!(next_event(), "event: note");
+        db.log_event("t1", EventKind::Progress, "Reached 50%");
+        assert_eq!(next_event(), "event: progress");
+        assert!(
+            !seen.contains("4242") && !seen.contains("hunter2"),
+            "{}",
+            seen
+        );
+
+        // Streams are capped, leaving room for requests
+        let streams: Vec<_> = (1..MAX_STREAMS)
+            .map(|_| {
+                let mut stream = connect("GET", "/v1/observe/tasks/t1/stream");
+                let mut status = String::new();
+                stream.read_line(&mut status).unwrap();
+                assert!(status.starts_with("HTTP/1.1 200"), "{}", status);
+                stream
+            })
+            .collect();
+        assert!(request("GET", "/v1/observe/tasks/t1/stream").starts_with("HTTP/1.1 503"));
+        assert!(request("GET", "/v1/observe/tasks").starts_with("HTTP/1.1 200"));
+        drop(streams);
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_mutual_tls_and_tokens() {
+        use crate::config::{HubConfig, TlsConfig};
+        use crate::notify::{Channel, HubChannel};
+
+        let fixture = |name: &str| {
+            format!(
+                "{}/src/federation/testdata/{}",
+                env!("CARGO_MANIFEST_DIR"),
+                name
+            )
+        };
+        let server = tls::server_config(&TlsConfig {
+            cert: fixture("server.pem"),
+            key: fixture("server.key"),
+            client_ca: Some(fixture("ca.pem")),
+        })
+        .unwrap();
+        let hub = Hub::bind("127.0.0.1:0").unwrap().tls(server).auth(
+            Auth::default()
+                .token("viewer", "read-secret", Scope::Read)
+                .token("edge", "control-secret", Scope::Control),
+        );
+        let addr = hub.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        hub.spawn(move |envelope| {
+            tx.send(envelope.localized()).unwrap();
+            vec!["desktop".to_string()]
+        });
+
+        std::env::set_var("AGENT_INBOX_TEST_READ_TOKEN", "read-secret");
+        std::env::set_var("AGENT_INBOX_TEST_CONTROL_TOKEN", "control-secret");
+        let edge = |token_env: &str, client_cert: bool| {
+            HubChannel::new(
+                HubConfig {
+                    url: Some(format!("https://localhost:{}", addr.port())),
+                    node: Some("laptop".to_string()),
+                    token_env: Some(token_env.to_string()),
+                    ca: Some(fixture("ca.pem")),
+                    client_cert: client_cert.then(|| fixture("client.pem")),
+                    client_key: client_cert.then(|| fixture("client.key")),
+                    ..Default::default()
+                },
+                Vec::new(),
+            )
+            .unwrap()
+        };
+        let notification = Notification {
+            title: "[claude_code] Fix bug".to_string(),
+            body: "Done".to_string(),
+            urgency: Urgency::Normal,
+            category: Category::Completed,
+            task_id: None,
+            reason: None,
+        };
+
+        edge("AGENT_INBOX_TEST_CONTROL_TOKEN", true)
+            .send(&notification)
+            .unwrap();
+        assert_eq!(rx.recv().unwrap().title, "laptop: [claude_code] Fix bug");
+        // A read-only token can't forward, and nothing gets in without a
+        // client certificate
+        assert!(edge("AGENT_INBOX_TEST_READ_TOKEN", true)
+            .send(&notification)
+            .is_err());
+        assert!(edge("AGENT_INBOX_TEST_CONTROL_TOKEN", false)
+            .send(&notification)
+            .is_err());
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,92 @@
+//! The read-only observer view: `[daemon] observe = true`
+//!
+//! Lets a teammate follow a shared agent run without access to anything
+//! else. Observer tokens (`scope = "observe"`) reach only these endpoints:
+//!
+//! - `GET /v1/observe/tasks`: the task list
+//! - `GET /v1/observe/tasks/<id>`: one task with its timeline
+//! - `GET /v1/observe/tasks/<id>/stream`: the timeline as server-sent
+//!   events, replaying past events, then following new ones
+//!
+//! Tasks are trimmed to what shows their progress: no pids, working
+//! directories or hosts. Events say what happened and when, but not the
+//! activity log's message, which names pids, prompts and attached
+//! terminals. What text is left is redacted per `[notify.redact]`.
+//! Payloads follow the [`schema`](crate::schema).
+
+use crate::db::Database;
+use crate::models::TaskEvent;
+use crate::monitor::redact::SecretFilter;
+use crate::schema::{Event, TaskInfo, TaskList, Timeline};
+use anyhow::Result;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How often a stream checks for new events
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often an idle stream sends a comment, to notice observers leaving
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+pub fn list(db: &Database) -> Result<serde_json::Value> {
+    let tasks = db
+        .list_tasks(None)?
+        .iter()
+        .map(TaskInfo::observed)
+        .collect();
+    Ok(serde_json::to_value(TaskList::new(tasks))?)
+}
+
+/// The task and its timeline; null when there is no such task
+pub fn timeline(db: &Database, task_id: &str) -> Result<serde_json::Value> {
+    Ok(match db.get_task_by_id(task_id)? {
+        Some(task) => serde_json::to_value(Timeline::observed(
+            TaskInfo::observed(&task),
+            &db.task_events(task_id)?,
+        ))?,
+        None => serde_json::Value::Null,
+    })
+}
+
+/// Write `task_id`'s events to `out` as server-sent events, redacted with
+/// `secrets`, until the observer disconnects or the task is removed
+pub fn stream<W: Write>(
+    db_path: &Path,
+    task_id: &str,
+    secrets: &SecretFilter,
+    out: &mut W,
+) -> Result<()> {
+    write!(
+        out,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+    )?;
+    out.flush()?;
+    let db = Database::open(db_path)?;
+    let mut sent = 0;
+    let mut last_write = Instant::now();
+    loop {
+        if db.get_task_by_id(task_id)?.is_none() {
+            return Ok(());
+        }
+        let events = db.task_events(task_id)?;
+        for event in events.iter().skip(sent) {
+            write_event(out, event, secrets)?;
+            last_write = Instant::now();
+        }
+        sent = sent.max(events.len());
+        if last_write.elapsed() >= KEEPALIVE {
+            write!(out, ": keepalive\n\n")?;
+            last_write = Instant::now();
+        }
+        out.flush()?;
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn write_event<W: Write>(out: &mut W, event: &TaskEvent, secrets: &SecretFilter) -> Result<()> {
+    let mut data = serde_json::to_value(Event::observed(event))?;
+    secrets.redact_json(&mut data);
+    write!(out, "event: {}\ndata: {}\n\n", event.kind.as_str(), data)?;
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! Versions of the hub API
+//!
+//! Machines are upgraded one at a time, so the hub may be older than its
+//! edges or the other way round. Each side states the range of protocol
+//! versions it speaks: edges in an `Agent-Inbox-Protocol: <min>-<max>`
+//! header and in the envelope's `protocol` field (the SSH transport has no
+//! headers), the hub in the same header on every response. When the
+//! ranges don't overlap, the request is refused with 426 and a message
+//! naming the machine to upgrade, instead of failing later on a body one
+//! side can't read.
+//!
+//! Machines from before versioning send neither and speak version 1.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Header carrying the versions a machine speaks
+pub const HEADER: &str = "Agent-Inbox-Protocol";
+
+/// Newest version this build speaks; 2 added the version exchange
+pub const VERSION: u32 = 2;
+
+/// Oldest version this build still understands
+pub const MIN_VERSION: u32 = 1;
+
+/// A range of protocol versions, written `2` or `1-2`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Versions {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl Versions {
+    /// What this build speaks
+    pub const OURS: Versions = Versions {
+        min: MIN_VERSION,
+        max: VERSION,
+    };
+
+    /// What a machine from before versioning speaks
+    pub const LEGACY: Versions = Versions { min: 1, max: 1 };
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid protocol versions: {} (<version> or <min>-<max>)",
+                s
+            )
+        };
+        let parse = |v: &str| v.trim().parse::<u32>().map_err(|_| invalid());
+        let (min, max) = match s.split_once('-') {
+            Some((min, max)) => (parse(min)?, parse(max)?),
+            None => (parse(s)?, parse(s)?),
+        };
+        if min == 0 || min > max {
+            return Err(invalid());
+        }
+        Ok(Self { min, max })
+    }
+
+    /// The newest version both sides speak
+    pub fn negotiate(self, theirs: Versions) -> Result<u32, Mismatch> {
+        let version = self.max.min(theirs.max);
+        if version < self.min.max(theirs.min) {
+            return Err(Mismatch { ours: self, theirs });
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for Versions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.min == self.max {
+            write!(f, "{}", self.max)
+        } else {
+            write!(f, "{}-{}", self.min, self.max)
+        }
+    }
+}
+
+impl TryFrom<String> for Versions {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        Self::from_str(&s)
+    }
+}
+
+impl From<Versions> for String {
+    fn from(versions: Versions) -> String {
+        versions.to_string()
+    }
+}
+
+/// Two machines without a version in common
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mismatch {
+    pub ours: Versions,
+    pub theirs: Versions,
+}
+
+impl Mismatch {
+    /// What went wrong and which machine to upgrade, as seen from `here`
+    /// talking to `there`
+    pub fn explain(&self, here: &str, there: &str) -> String {
+        if self.theirs.max < self.ours.min {
+            format!(
+                "{} speaks protocol {}, {} needs {} or later: upgrade agent-inbox on {}",
+                there, self.theirs, here, self.ours.min, there
+            )
+        } else {
+            format!(
+                "{} needs protocol {} or later, {} speaks {}: upgrade agent-inbox on {}",
+                there, self.theirs.min, here, self.ours, here
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        let versions = |s| Versions::from_str(s).unwrap();
+        assert_eq!(versions("1-2"), Versions { min: 1, max: 2 });
+        assert_eq!(versions("3").to_string(), "3");
+        assert!(Versions::from_str("2-1").is_err());
+        assert!(Versions::from_str("x").is_err());
+
+        assert_eq!(versions("1-2").negotiate(Versions::LEGACY), Ok(1));
+        assert_eq!(versions("1-2").negotiate(versions("2-5")), Ok(2));
+
+        let too_old = versions("2-3").negotiate(Versions::LEGACY).unwrap_err();
+        assert_eq!(
+            too_old.explain("the hub", "the edge"),
+            "the edge speaks protocol 1, the hub needs 2 or later: upgrade agent-inbox on the edge"
+        );
+        let too_new = versions("1-2").negotiate(versions("4-5")).unwrap_err();
+        assert_eq!(
+            too_new.explain("this machine", "the hub"),
+            "the hub needs protocol 4 or later, this machine speaks 1-2: upgrade agent-inbox on this machine"
+        );
+    }
+}
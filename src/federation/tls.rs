@@ -0,0 +1,77 @@
+//! TLS for the hub API, optionally mutual
+
+use crate::config::{HubConfig, TlsConfig};
+use anyhow::{Context, Result};
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+use std::sync::Arc;
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("Failed to read certificates from {}", path))?;
+    anyhow::ensure!(!certs.is_empty(), "No certificates in {}", path);
+    Ok(certs)
+}
+
+fn key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_file(path)
+        .with_context(|| format!("Failed to read private key from {}", path))
+}
+
+fn roots(path: &str) -> Result<Arc<RootCertStore>> {
+    let mut roots = RootCertStore::empty();
+    for cert in certs(path)? {
+        roots
+            .add(cert)
+            .with_context(|| format!("Invalid CA certificate in {}", path))?;
+    }
+    Ok(Arc::new(roots))
+}
+
+/// Server side of `[daemon.tls]`; with `client_ca`, clients must present a
+/// certificate it signed
+pub fn server_config(config: &TlsConfig) -> Result<Arc<ServerConfig>> {
+    let builder =
+        ServerConfig::builder_with_provider(provider()).with_safe_default_protocol_versions()?;
+    let builder = match &config.client_ca {
+        Some(ca) => builder.with_client_cert_verifier(
+            WebPkiClientVerifier::builder_with_provider(roots(ca)?, provider()).build()?,
+        ),
+        None => builder.with_no_client_auth(),
+    };
+    let server = builder
+        .with_single_cert(certs(&config.cert)?, key(&config.key)?)
+        .context("Invalid [daemon.tls] certificate or key")?;
+    Ok(Arc::new(server))
+}
+
+/// Client side for `[notify.hub]`: None when the defaults (public roots, no
+/// client certificate) will do
+pub fn client_config(config: &HubConfig) -> Result<Option<Arc<ClientConfig>>> {
+    if config.ca.is_none() && config.client_cert.is_none() {
+        return Ok(None);
+    }
+    let ca = config
+        .ca
+        .as_deref()
+        .context("[notify.hub] client_cert needs ca, the CA that signed the hub's certificate")?;
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots(ca)?);
+    let client = match (&config.client_cert, &config.client_key) {
+        (Some(cert), Some(key_path)) => builder
+            .with_client_auth_cert(certs(cert)?, key(key_path)?)
+            .context("Invalid [notify.hub] client certificate or key")?,
+        (None, None) => builder.with_no_client_auth(),
+        _ => anyhow::bail!("[notify.hub] needs both client_cert and client_key"),
+    };
+    Ok(Some(Arc::new(client)))
+}
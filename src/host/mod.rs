@@ -0,0 +1,104 @@
+//! Which machine and account this is, for telling notifications from
+//! several machines apart
+//!
+//! Captured into each task when it registers (see [`HostInfo`]) and filled
+//! into the notification templates as `{host}`, `{hostname}` and `{user}`.
+//! `{host}` is the `[notify.host] nickname` when set, else the machine's
+//! tailscale name with `tailscale = true`, else the hostname.
+
+use crate::config::HostConfig;
+use crate::models::HostInfo;
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// This machine and user, named according to `config`
+pub fn current(config: &HostConfig) -> HostInfo {
+    let nickname = config
+        .nickname
+        .clone()
+        .or_else(|| config.tailscale.then(tailscale_name).flatten());
+    HostInfo {
+        hostname: hostname(),
+        user: username(),
+        nickname,
+    }
+}
+
+/// $HOSTNAME, else the kernel's hostname
+pub fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| command_output("hostname", &[]))
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// $USER, else whoever `id` says we are
+pub fn username() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("LOGNAME"))
+        .ok()
+        .or_else(|| command_output("id", &["-un"]))
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// The machine's name in the tailnet, "laptop" for
+/// "laptop.tail1234.ts.net.". Asked once per process; None when tailscale
+/// isn't installed or logged in.
+fn tailscale_name() -> Option<String> {
+    static NAME: OnceLock<Option<String>> = OnceLock::new();
+    NAME.get_or_init(|| {
+        let status = command_output(
+            "tailscale",
+            &["status", "--json", "--self=true", "--peers=false"],
+        )?;
+        dns_label(&serde_json::from_str(&status).ok()?)
+    })
+    .clone()
+}
+
+/// First label of `Self.DNSName` in `tailscale status --json`
+fn dns_label(status: &serde_json::Value) -> Option<String> {
+    let dns_name = status.get("Self")?.get("DNSName")?.as_str()?;
+    let label = dns_name.split('.').next()?;
+    (!label.is_empty()).then(|| label.to_string())
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        let config = HostConfig {
+            nickname: Some("work-laptop".to_string()),
+            ..HostConfig::default()
+        };
+        let host = current(&config);
+        assert_eq!(host.name(), "work-laptop");
+        assert!(!host.hostname.is_empty());
+        assert_eq!(
+            host.fill("{user}@{host}"),
+            format!("{}@work-laptop", host.user)
+        );
+
+        let status = serde_json::json!({"Self": {"DNSName": "laptop.tail1234.ts.net.", "HostName": "Laptop"}});
+        assert_eq!(dns_label(&status).as_deref(), Some("laptop"));
+        assert_eq!(
+            dns_label(&serde_json::json!({"Self": {"DNSName": ""}})),
+            None
+        );
+    }
+}
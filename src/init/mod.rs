@@ -0,0 +1,584 @@
+//! `agent-inbox init`: interactive first-run setup
+//!
+//! Asks which channels to notify through (sending a test message via each),
+//! picks presence detection for the platform, optionally installs the agent
+//! wrappers and the auto-reset systemd unit, and writes a commented
+//! config.toml.
+
+use agent_inbox::config::{
+    self, EmailConfig, MatrixConfig, NotifyConfig, RoutingConfig, SlackConfig, TelegramConfig,
+};
+use agent_inbox::notify::{Category, Notification, NotifierBuilder, Urgency};
+use anyhow::{Context, Result};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Wrappers for the agents `init` knows how to wrap, as shipped in wrappers/
+const WRAPPERS: &[(&str, &str)] = &[
+    ("claude", include_str!("../../wrappers/claude-wrapper")),
+    ("opencode", include_str!("../../wrappers/opencode-wrapper")),
+];
+
+const RESET_UNIT: &str = "agent-inbox-reset.service";
+
+/// Questions on one stream, answers from another
+pub struct Prompt<R, W> {
+    input: R,
+    output: W,
+    ended: bool,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            ended: false,
+        }
+    }
+
+    pub fn say(&mut self, line: &str) -> Result<()> {
+        writeln!(self.output, "{}", line)?;
+        Ok(())
+    }
+
+    /// A line of input; the default when the answer is empty. Fails when
+    /// input ends on a question without a default.
+    pub fn ask(&mut self, question: &str, default: Option<&str>) -> Result<String> {
+        loop {
+            match default {
+                Some(default) if !default.is_empty() => {
+                    write!(self.output, "{} [{}]: ", question, default)?
+                }
+                _ => write!(self.output, "{}: ", question)?,
+            }
+            self.output.flush()?;
+            let mut line = String::new();
+            self.ended = self.input.read_line(&mut line)? == 0;
+            let answer = line.trim();
+            if !answer.is_empty() {
+                return Ok(answer.to_string());
+            }
+            if let Some(default) = default {
+                return Ok(default.to_string());
+            }
+            anyhow::ensure!(!self.ended, "Input ended before setup was complete");
+        }
+    }
+
+    pub fn confirm(&mut self, question: &str, default: bool) -> Result<bool> {
+        let hint = if default { "Y/n" } else { "y/N" };
+        loop {
+            let answer = self.ask(&format!("{} [{}]", question, hint), Some(""))?;
+            match answer.to_lowercase().as_str() {
+                "" => return Ok(default),
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => self.say("Please answer y or n")?,
+            }
+        }
+    }
+}
+
+/// Presence detection suited to this machine
+#[derive(Debug, Clone, Copy)]
+pub struct Platform {
+    pub name: &'static str,
+    /// `[notify.routing] provider`
+    pub provider: &'static str,
+    /// systemd is managing the user session
+    pub systemd: bool,
+}
+
+pub fn detect_platform() -> Platform {
+    let systemd = Path::new("/run/systemd/system").exists();
+    if cfg!(target_os = "macos") {
+        Platform {
+            name: "macOS",
+            provider: "macos",
+            systemd: false,
+        }
+    } else if systemd && Path::new("/run/systemd/seats").exists() {
+        Platform {
+            name: "Linux with systemd-logind",
+            provider: "logind",
+            systemd,
+        }
+    } else {
+        Platform {
+            name: std::env::consts::OS,
+            provider: "auto",
+            systemd,
+        }
+    }
+}
+
+/// Channels compiled into this build, with a description for the menu
+fn available_channels() -> Vec<(&'static str, &'static str)> {
+    [
+        (
+            "desktop",
+            "desktop notifications",
+            cfg!(feature = "desktop"),
+        ),
+        (
+            "terminal",
+            "marks the task's tmux window or terminal title",
+            true,
+        ),
+        ("slack", "Slack incoming webhook", cfg!(feature = "slack")),
+        ("telegram", "Telegram bot", cfg!(feature = "telegram")),
+        ("email", "email through sendmail", cfg!(feature = "email")),
+        ("matrix", "Matrix room", cfg!(feature = "matrix")),
+    ]
+    .into_iter()
+    .filter(|(_, _, built)| *built)
+    .map(|(name, about, _)| (name, about))
+    .collect()
+}
+
+/// Send a test notification through one channel of `config`
+pub fn send_test(config: &NotifyConfig, name: &str) -> Result<()> {
+    let config = NotifyConfig {
+        channels: vec![name.to_string()],
+        routing: None,
+        ..config.clone()
+    };
+    let notifier = NotifierBuilder::from_config(&config)?.build();
+    let notification = Notification {
+        title: "agent-inbox is set up".to_string(),
+        body: format!("Test notification via {}", name),
+        urgency: Urgency::Normal,
+        category: Category::Info,
+        task_id: None,
+        reason: None,
+    };
+    anyhow::ensure!(
+        notifier.notify(&notification).iter().any(|n| n == name),
+        "delivery via {} failed",
+        name
+    );
+    Ok(())
+}
+
+/// Ask for each channel's settings and check it with a live test message.
+/// Returns once at least one channel is configured.
+pub fn ask_channels<R: BufRead, W: Write>(
+    prompt: &mut Prompt<R, W>,
+    test: impl Fn(&NotifyConfig, &str) -> Result<()>,
+) -> Result<NotifyConfig> {
+    let mut notify = NotifyConfig {
+        channels: Vec::new(),
+        ..NotifyConfig::default()
+    };
+    while notify.channels.is_empty() {
+        for (name, about) in available_channels() {
+            if !prompt.confirm(
+                &format!("Notify via {} ({})?", name, about),
+                name == "desktop",
+            )? {
+                continue;
+            }
+            match name {
+                "slack" => {
+                    notify.slack = Some(SlackConfig {
+                        webhook_url: prompt.ask("  Slack webhook URL", None)?,
+                        bot_token_env: None,
+                        channel: None,
+                    })
+                }
+                "telegram" => {
+                    let chat_id = prompt.ask("  Telegram chat ID", None)?;
+                    let bot_token_env = prompt.ask(
+                        "  Environment variable holding the bot token",
+                        Some("TELEGRAM_BOT_TOKEN"),
+                    )?;
+                    notify.telegram = Some(TelegramConfig {
+                        chat_id,
+                        bot_token_env,
+                        reasons: Default::default(),
+                    });
+                }
+                "email" => {
+                    let to = prompt.ask("  Send to address", None)?;
+                    let from = prompt.ask("  From address", Some("agent-inbox@localhost"))?;
+                    let sendmail = prompt.ask("  sendmail command", Some("sendmail"))?;
+                    notify.email = Some(EmailConfig { to, from, sendmail });
+                }
+                "matrix" => {
+                    let homeserver = prompt.ask("  Homeserver URL", None)?;
+                    let room_id = prompt.ask("  Room ID", None)?;
+                    let access_token_env = prompt.ask(
+                        "  Environment variable holding the access token",
+                        Some("MATRIX_ACCESS_TOKEN"),
+                    )?;
+                    notify.matrix = Some(MatrixConfig {
+                        homeserver,
+                        room_id,
+                        access_token_env,
+                    });
+                }
+                _ => {}
+            }
+            // The terminal channel only marks tasks, there is nothing to show yet
+            if name != "terminal" {
+                prompt.say(&format!("  Sending a test notification via {}...", name))?;
+                let works = match test(&notify, name) {
+                    Ok(()) => prompt.confirm("  Did it arrive?", true)?,
+                    Err(e) => {
+                        prompt.say(&format!("  Failed: {:#}", e))?;
+                        false
+                    }
+                };
+                if !works && !prompt.confirm(&format!("  Keep {} anyway?", name), false)? {
+                    continue;
+                }
+            }
+            notify.channels.push(name.to_string());
+        }
+        if notify.channels.is_empty() {
+            anyhow::ensure!(!prompt.ended, "Input ended before a channel was set up");
+            prompt.say("At least one channel is needed.")?;
+        }
+    }
+    Ok(notify)
+}
+
+/// Offer to route by presence when both local and remote channels are set up
+fn ask_routing<R: BufRead, W: Write>(
+    prompt: &mut Prompt<R, W>,
+    notify: &NotifyConfig,
+    platform: Platform,
+) -> Result<Option<RoutingConfig>> {
+    let (present, away): (Vec<String>, Vec<String>) = notify
+        .channels
+        .iter()
+        .cloned()
+        .partition(|name| name == "desktop" || name == "terminal");
+    if present.is_empty() || away.is_empty() {
+        return Ok(None);
+    }
+    let question = format!(
+        "Use {} only while you're away from the desk?",
+        away.join(", ")
+    );
+    if !prompt.confirm(&question, true)? {
+        return Ok(None);
+    }
+    Ok(Some(RoutingConfig {
+        present,
+        away,
+        provider: platform.provider.to_string(),
+        ..RoutingConfig::default()
+    }))
+}
+
+fn quote(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+fn list(values: &[String]) -> String {
+    let quoted: Vec<String> = values.iter().map(|v| quote(v)).collect();
+    format!("[{}]", quoted.join(", "))
+}
+
+/// The config file, with comments explaining each setting
+pub fn render(notify: &NotifyConfig, platform: Platform) -> String {
+    let mut out = String::from(
+        "# agent-inbox configuration, written by `agent-inbox init`.\n\
+         # Every section is optional; the README lists all settings.\n\n",
+    );
+    out += "[notify]\n# Where notifications go: desktop, terminal, slack, telegram, email, matrix, hub\n";
+    out += &format!("channels = {}\n", list(&notify.channels));
+    if let Some(slack) = &notify.slack {
+        out += &format!(
+            "\n[notify.slack]\nwebhook_url = {}\n",
+            quote(&slack.webhook_url)
+        );
+    }
+    if let Some(telegram) = &notify.telegram {
+        out += &format!(
+            "\n[notify.telegram]\nchat_id = {}\n# Environment variable holding the bot token\nbot_token_env = {}\n",
+            quote(&telegram.chat_id),
+            quote(&telegram.bot_token_env)
+        );
+    }
+    if let Some(email) = &notify.email {
+        out += &format!(
+            "\n[notify.email]\nto = {}\nfrom = {}\nsendmail = {}\n",
+            quote(&email.to),
+            quote(&email.from),
+            quote(&email.sendmail)
+        );
+    }
+    if let Some(matrix) = &notify.matrix {
+        out += &format!(
+            "\n[notify.matrix]\nhomeserver = {}\nroom_id = {}\n# Environment variable holding the access token\naccess_token_env = {}\n",
+            quote(&matrix.homeserver),
+            quote(&matrix.room_id),
+            quote(&matrix.access_token_env)
+        );
+    }
+
+    out += &format!("\n# Presence detection for {}\n", platform.name);
+    match &notify.routing {
+        Some(routing) => {
+            out +=
+                "# Channels while you're at the desk, and while locked or idle\n[notify.routing]\n";
+            out += &format!(
+                "present = {}\naway = {}\n",
+                list(&routing.present),
+                list(&routing.away)
+            );
+            out += &format!(
+                "# Input idle time after which you count as away\naway_after_secs = {}\n",
+                routing.away_after_secs
+            );
+            out += &format!(
+                "# \"auto\", \"hook\", \"logind\", \"macos\" or \"xprintidle\"\nprovider = {}\n",
+                quote(&routing.provider)
+            );
+        }
+        None => {
+            out += "# Uncomment to send to other channels while you're away:\n";
+            out += "# [notify.routing]\n# present = [\"desktop\"]\n# away = [\"telegram\"]\n";
+            out += &format!("# provider = {}\n", quote(platform.provider));
+        }
+    }
+
+    let presence = config::PresenceConfig::default();
+    out += "\n[presence]\n# Hold back notifications while you're typing in the task's terminal\n";
+    out += &format!(
+        "suppress_when_watching = {}\nwatching_secs = {}\n",
+        presence.suppress_when_watching, presence.watching_secs
+    );
+
+    let burst = config::ErrorBurstConfig::default();
+    out += "\n# Ask for attention when error lines pile up in a task's output; 0 disables\n[error_burst]\n";
+    out += &format!(
+        "threshold = {}\nwindow_secs = {}\n",
+        burst.threshold, burst.window_secs
+    );
+
+    let looping = config::LoopingConfig::default();
+    out += "\n# Ask for attention when a task's output keeps repeating itself; 0 disables\n[looping]\n";
+    out += &format!(
+        "repeats = {}\nsimilarity = {}\n",
+        looping.repeats, looping.similarity
+    );
+    out
+}
+
+/// The shell's rc file, from $SHELL
+fn rc_file(home: &Path) -> PathBuf {
+    let shell = std::env::var("SHELL").unwrap_or_default();
+    match Path::new(&shell).file_name().and_then(|name| name.to_str()) {
+        Some("zsh") => home.join(".zshrc"),
+        _ => home.join(".bashrc"),
+    }
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+/// Append `alias <agent>=<wrapper>` unless the rc file already aliases it
+fn add_alias(rc: &Path, agent: &str, wrapper: &Path, agent_inbox: &Path) -> Result<bool> {
+    let existing = std::fs::read_to_string(rc).unwrap_or_default();
+    if existing
+        .lines()
+        .any(|line| line.trim_start().starts_with(&format!("alias {}=", agent)))
+    {
+        return Ok(false);
+    }
+    let mut lines = format!("\n# agent-inbox wrapper for {}\n", agent);
+    if !existing.contains("AGENT_INBOX_BIN=") {
+        lines += &format!("export AGENT_INBOX_BIN='{}'\n", agent_inbox.display());
+    }
+    lines += &format!("alias {}='{}'\n", agent, wrapper.display());
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(rc)
+        .with_context(|| format!("Failed to open {}", rc.display()))?;
+    file.write_all(lines.as_bytes())?;
+    Ok(true)
+}
+
+/// Copy the wrappers for installed agents and alias them in the rc file
+fn install_wrappers<R: BufRead, W: Write>(
+    prompt: &mut Prompt<R, W>,
+    home: &Path,
+    agent_inbox: &Path,
+) -> Result<()> {
+    let dir = agent_inbox::storage::config_dir().join("wrappers");
+    std::fs::create_dir_all(&dir)?;
+    let rc = rc_file(home);
+    for (agent, script) in WRAPPERS {
+        if !on_path(agent) {
+            prompt.say(&format!("  {} not found on PATH, skipping", agent))?;
+            continue;
+        }
+        let wrapper = dir.join(format!("{}-wrapper", agent));
+        std::fs::write(&wrapper, script)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&wrapper, std::fs::Permissions::from_mode(0o755))?;
+        }
+        if add_alias(&rc, agent, &wrapper, agent_inbox)? {
+            prompt.say(&format!("  ✓ Aliased {} in {}", agent, rc.display()))?;
+        } else {
+            prompt.say(&format!(
+                "  ⚠ {} already has an alias in {}; point it at {}",
+                agent,
+                rc.display(),
+                wrapper.display()
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// The systemd user unit that clears stale tasks on login, as
+/// scripts/setup-auto-reset.sh installs it
+fn install_reset_unit(home: &Path, agent_inbox: &Path) -> Result<PathBuf> {
+    let dir = home.join(".config/systemd/user");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(RESET_UNIT);
+    let unit = format!(
+        "[Unit]\nDescription=Reset agent-inbox tasks on login\nAfter=default.target\n\n\
+         [Service]\nType=oneshot\nExecStart={} reset --force\nRemainAfterExit=yes\n\n\
+         [Install]\nWantedBy=default.target\n",
+        agent_inbox.display()
+    );
+    std::fs::write(&path, unit).with_context(|| format!("Failed to write {}", path.display()))?;
+    for args in [
+        &["--user", "daemon-reload"][..],
+        &["--user", "enable", RESET_UNIT][..],
+    ] {
+        let status = Command::new("systemctl")
+            .args(args)
+            .status()
+            .context("Failed to run systemctl")?;
+        anyhow::ensure!(
+            status.success(),
+            "systemctl {} exited with {}",
+            args.join(" "),
+            status
+        );
+    }
+    Ok(path)
+}
+
+/// Run the wizard on `prompt`, writing the config to `path`
+pub fn run<R: BufRead, W: Write>(prompt: &mut Prompt<R, W>, path: &Path) -> Result<()> {
+    prompt.say("Setting up agent-inbox.\n")?;
+    if path.exists()
+        && !prompt.confirm(&format!("{} exists. Replace it?", path.display()), false)?
+    {
+        prompt.say("Keeping the existing config.")?;
+        return Ok(());
+    }
+
+    let platform = detect_platform();
+    let mut notify = ask_channels(prompt, send_test)?;
+    notify.routing = ask_routing(prompt, &notify, platform)?;
+
+    let home = PathBuf::from(std::env::var("HOME").context("HOME environment variable not set")?);
+    let agent_inbox = std::env::current_exe().context("Failed to locate the agent-inbox binary")?;
+    if prompt.confirm(
+        "Install wrappers so claude and opencode runs are tracked?",
+        true,
+    )? {
+        install_wrappers(prompt, &home, &agent_inbox)?;
+    }
+    if platform.systemd
+        && prompt.confirm(
+            "Install a systemd user unit that clears stale tasks on login?",
+            false,
+        )?
+    {
+        match install_reset_unit(&home, &agent_inbox) {
+            Ok(unit) => prompt.say(&format!("  ✓ Installed {}", unit.display()))?,
+            Err(e) => prompt.say(&format!("  Failed to install the unit: {:#}", e))?,
+        }
+    }
+
+    let content = render(&notify, platform);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    prompt.say(&format!("\nWrote {}", path.display()))?;
+    Ok(())
+}
+
+/// `agent-inbox init` on the terminal
+pub fn run_interactive() -> Result<()> {
+    let stdin = std::io::stdin();
+    let mut prompt = Prompt::new(stdin.lock(), std::io::stdout());
+    run(&mut prompt, &config::default_config_path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agent_inbox::Config;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_wizard_writes_loadable_config() {
+        // Skip the channels not in this build, then answer for the rest
+        let mut script = String::new();
+        for (name, _) in available_channels() {
+            script += match name {
+                "terminal" | "slack" => "y\n",
+                _ => "n\n",
+            };
+            if name == "slack" {
+                script += "https://hooks.slack.com/services/T0/B0/\"x\"\ny\n";
+            }
+        }
+        script += "y\n";
+        let mut prompt = Prompt::new(Cursor::new(script), Vec::new());
+        let platform = Platform {
+            name: "Linux",
+            provider: "logind",
+            systemd: true,
+        };
+        let tested = std::cell::RefCell::new(Vec::new());
+        let mut notify = ask_channels(&mut prompt, |_, name| {
+            tested.borrow_mut().push(name.to_string());
+            Ok(())
+        })
+        .unwrap();
+        notify.routing = ask_routing(&mut prompt, &notify, platform).unwrap();
+
+        let config: Config = toml::from_str(&render(&notify, platform)).unwrap();
+        if cfg!(feature = "slack") {
+            assert_eq!(*tested.borrow(), vec!["slack"]);
+            assert_eq!(config.notify.channels, vec!["terminal", "slack"]);
+            assert_eq!(
+                config.notify.slack.unwrap().webhook_url,
+                "https://hooks.slack.com/services/T0/B0/\"x\""
+            );
+            let routing = config.notify.routing.unwrap();
+            assert_eq!(routing.away, vec!["slack"]);
+            assert_eq!(routing.provider, "logind");
+        } else {
+            assert_eq!(config.notify.channels, vec!["terminal"]);
+        }
+        assert!(config.presence.suppress_when_watching);
+    }
+
+    #[test]
+    fn test_prompt_ends_without_answer() {
+        let mut prompt = Prompt::new(Cursor::new("\n"), Vec::new());
+        assert!(prompt.ask("Slack webhook URL", None).is_err());
+        let mut prompt = Prompt::new(Cursor::new("maybe\n"), Vec::new());
+        assert!(prompt.confirm("Install?", true).unwrap());
+    }
+}
@@ -1,3 +1,75 @@
-// Library exports for agent-inbox
+//! Library API for agent-inbox
+//!
+//! Everything the `agent-inbox` CLI does is available to other Rust tools:
+//!
+//! - [`db::Database`]: the SQLite task store shared by all frontends
+//! - [`models`]: task records and parsed progress
+//! - [`monitor`]: process monitoring, attention detectors and output parsing
+//! - [`notify`]: notification channels and the [`Notifier`] dispatcher
+//! - [`plugins`]: detectors and channels loaded from shared libraries at runtime
+//! - [`presence`]: whether the user is already looking at a task's terminal
+//! - [`summary`]: completion summaries of captured output
+//! - [`config`]: the `~/.config/agent-inbox/config.toml` schema
+//! - [`daemon`]: the background service that runs scheduled commands
+//! - [`digest`]: period summaries of everything that ran
+//! - [`hooks`]: commands and webhooks fired when tasks finish
+//! - [`sandbox`]: confinement of rule commands and plugins
+//! - [`schema`]: the versioned JSON of hooks, the HTTP API and its streams
+//! - [`artifacts`]: per-run archives of output, outcome and diffstat
+//! - [`stats`]: how long tasks wait on the user after a notification
+//! - [`costs`]: token and cost usage reported by agents, per task and per day
+//! - [`federation`]: forwarding notifications from several machines to one hub
+//! - [`host`]: which machine and user a task runs on
+//! - [`storage`]: where the database, output and artifacts are kept
+//! - [`exit`]: the CLI's exit codes, for scripts
+//! - `testing`: mock channels and scripted processes (`test-util` feature)
+//!
+//! ```no_run
+//! use agent_inbox::{Config, Database, MonitorBuilder, NotifierBuilder, Task};
+//! use std::time::Duration;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let db = Database::open(agent_inbox::db::default_db_path())?;
+//! let task = Task::new("job-1".into(), "my_tool".into(), "Nightly run".into(), Some(4242), None);
+//! db.insert_task(&task)?;
+//!
+//! let monitor = MonitorBuilder::new(db)
+//!     .poll_interval(Duration::from_secs(2))
+//!     .notifier(NotifierBuilder::from_config(&Config::load()?.notify)?.build())
+//!     .build();
+//! monitor.monitor_task("job-1".into(), 4242, None)?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod artifacts;
+pub mod config;
+pub mod costs;
+pub mod daemon;
+pub mod datetime;
 pub mod db;
+pub mod digest;
+pub mod exit;
+pub mod federation;
+pub mod hooks;
+pub mod host;
+#[cfg(feature = "llm")]
+pub mod llm;
 pub mod models;
+pub mod monitor;
+pub mod notify;
+pub mod plugins;
+pub mod presence;
+pub mod sandbox;
+pub mod schema;
+pub mod stats;
+pub mod storage;
+pub mod summary;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+
+pub use config::Config;
+pub use db::Database;
+pub use models::{Progress, Task, TaskContext, TaskStatus};
+pub use monitor::{MonitorBuilder, TaskMonitor};
+pub use notify::{Channel, Notification, Notifier, NotifierBuilder, Urgency};
@@ -0,0 +1,98 @@
+//! Minimal blocking client for optional LLM features
+//!
+//! Supports the Anthropic Messages API and OpenAI-compatible chat
+//! completion endpoints (which covers most local model servers).
+
+use crate::config::LlmConfig;
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+pub struct LlmClient {
+    config: LlmConfig,
+}
+
+impl LlmClient {
+    pub fn new(config: LlmConfig) -> Self {
+        Self { config }
+    }
+
+    /// Send a single-turn prompt and return the model's text reply
+    pub fn complete(&self, prompt: &str, max_tokens: u32) -> Result<String> {
+        let api_key = std::env::var(&self.config.api_key_env).ok();
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(self.config.timeout_secs))
+            .build();
+
+        let response: Value = match self.config.provider.as_str() {
+            "anthropic" => {
+                let url = self
+                    .config
+                    .endpoint
+                    .as_deref()
+                    .unwrap_or("https://api.anthropic.com/v1/messages");
+                let key =
+                    api_key.with_context(|| format!("{} is not set", self.config.api_key_env))?;
+                agent
+                    .post(url)
+                    .set("x-api-key", &key)
+                    .set("anthropic-version", "2023-06-01")
+                    .send_json(json!({
+                        "model": self.config.model,
+                        "max_tokens": max_tokens,
+                        "messages": [{"role": "user", "content": prompt}],
+                    }))
+                    .context("LLM request failed")?
+                    .into_json()?
+            }
+            "openai" => {
+                let url = self
+                    .config
+                    .endpoint
+                    .as_deref()
+                    .unwrap_or("https://api.openai.com/v1/chat/completions");
+                let mut request = agent.post(url);
+                // Local servers usually run without a key
+                if let Some(key) = &api_key {
+                    request = request.set("Authorization", &format!("Bearer {}", key));
+                }
+                request
+                    .send_json(json!({
+                        "model": self.config.model,
+                        "max_tokens": max_tokens,
+                        "messages": [{"role": "user", "content": prompt}],
+                    }))
+                    .context("LLM request failed")?
+                    .into_json()?
+            }
+            other => anyhow::bail!("Unknown LLM provider: {}", other),
+        };
+
+        extract_text(&response).context("Unexpected LLM response shape")
+    }
+}
+
+fn extract_text(response: &Value) -> Option<String> {
+    // Anthropic: {"content": [{"type": "text", "text": "..."}]}
+    // OpenAI:    {"choices": [{"message": {"content": "..."}}]}
+    response["content"][0]["text"]
+        .as_str()
+        .or_else(|| response["choices"][0]["message"]["content"].as_str())
+        .map(|s| s.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_text() {
+        let anthropic = json!({"content": [{"type": "text", "text": " Done. \n"}]});
+        assert_eq!(extract_text(&anthropic).as_deref(), Some("Done."));
+
+        let openai = json!({"choices": [{"message": {"content": "All good"}}]});
+        assert_eq!(extract_text(&openai).as_deref(), Some("All good"));
+
+        assert!(extract_text(&json!({"error": "nope"})).is_none());
+    }
+}
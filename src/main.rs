@@ -1,20 +1,62 @@
 mod cli;
-mod db;
 mod display;
-mod models;
-mod monitor;
+mod init;
 
+use agent_inbox::db::{self, Database};
+use agent_inbox::digest::Digest;
+use agent_inbox::exit::{self, Code, Failure};
+use agent_inbox::models::progress::format_duration;
+use agent_inbox::models::{EventKind, Priority, Task, TaskContext, TaskStatus};
+use agent_inbox::monitor::detectors::AttentionReason;
+use agent_inbox::monitor::reasons::ReasonRegistry;
+use agent_inbox::monitor::redact::SecretFilter;
+use agent_inbox::monitor::source::{PidFile, PidSource, SystemdUnit};
+use agent_inbox::presence::{attached, desktop, zellij, Presence, SystemPresence};
+use agent_inbox::{
+    artifacts, config, costs, daemon, federation, monitor, notify, plugins, stats, storage, summary,
+};
 use anyhow::{Context, Result};
 use clap::Parser;
-use cli::{Cli, Commands, ReportAction};
-use db::Database;
-use models::{Task, TaskContext, TaskStatus};
+use cli::{
+    BenchAction, Cli, Commands, DebugAction, HubAction, NotificationsAction, Output,
+    PresenceAction, ReportAction, StorageAction,
+};
 use std::collections::HashMap;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+fn main() {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            // --help and --version end up here too
+            std::process::exit(if e.use_stderr() {
+                Code::Usage.value()
+            } else {
+                0
+            });
+        }
+    };
+    let json = cli.output == Output::Json;
+    if let Err(e) = run(cli) {
+        if json {
+            eprintln!("{}", display::json::error(&e));
+        } else {
+            eprintln!("Error: {:?}", e);
+        }
+        std::process::exit(exit::code_of(&e).value());
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    // Zero-state: no database, only the config and plugins
+    if let Some(Commands::Oneshot { pid, message }) = cli.command {
+        return oneshot(pid, message);
+    }
+    if let Some(Commands::PluginHost { dir }) = &cli.command {
+        return plugins::host(dir);
+    }
 
     // Ensure data directory exists
     db::ensure_data_dir()?;
@@ -26,13 +68,23 @@ fn main() -> Result<()> {
     // Run cleanup on every invocation
     let _ = db.cleanup_old_completed(3600); // 1 hour default
 
+    let json = cli.output == Output::Json;
     match cli.command {
+        None if !cli.run.is_empty() => std::process::exit(run_wrapped(db, &cli.run)?),
         None => {
             // Default: show running tasks (actively generating)
             let tasks = db.list_tasks(Some(TaskStatus::Running))?;
-            display::display_task_list(&tasks);
+            if json {
+                display::json::task_list("list", &tasks)?;
+            } else {
+                display::display_task_list(&tasks, false);
+            }
         }
-        Some(Commands::List { all, status }) => {
+        Some(Commands::List {
+            all,
+            status,
+            verbose,
+        }) => {
             let tasks = if let Some(status_str) = status {
                 let status = TaskStatus::from_str(&status_str)
                     .map_err(|e| anyhow::anyhow!(e))?;
@@ -44,23 +96,338 @@ fn main() -> Result<()> {
                 db.list_tasks(Some(TaskStatus::Running))?
             };
 
-            display::display_task_list(&tasks);
+            if json {
+                display::json::task_list("list", &tasks)?;
+            } else {
+                display::display_task_list(&tasks, verbose);
+            }
         }
         Some(Commands::Show { task_id }) => {
             let task = db
                 .get_task_by_id(&task_id)?
-                .ok_or_else(|| anyhow::anyhow!("Task not found: {}", task_id))?;
+                .ok_or_else(|| Failure::task_not_found(&task_id))?;
+
+            let usage = db.task_usage(&task_id)?;
+            if json {
+                display::json::task_detail(&task, &usage)?;
+            } else {
+                display::display_task_detail(&task, &usage);
+            }
+        }
+        Some(Commands::Timeline {
+            task_id,
+            json: events_only,
+        }) => {
+            let task = db
+                .get_task_by_id(&task_id)?
+                .ok_or_else(|| Failure::task_not_found(&task_id))?;
+            let events = db.task_events(&task_id)?;
 
-            display::display_task_detail(&task);
+            if json {
+                display::json::timeline(&task, &events)?;
+            } else if events_only {
+                println!("{}", serde_json::to_string_pretty(&events)?);
+            } else {
+                display::display_timeline(&task, &events);
+            }
+        }
+        Some(Commands::Tail {
+            task_id,
+            follow,
+            lines,
+        }) => {
+            let task = db
+                .get_task_by_id(&task_id)?
+                .ok_or_else(|| Failure::task_not_found(&task_id))?;
+            tail_task(&db, &db_path, &task, lines, follow)?;
+        }
+        Some(Commands::Debug {
+            action:
+                DebugAction::Trace {
+                    task_id,
+                    follow,
+                    lines,
+                },
+        }) => {
+            let task = db
+                .get_task_by_id(&task_id)?
+                .ok_or_else(|| Failure::task_not_found(&task_id))?;
+            trace_task(&db, &task, lines, follow)?;
+        }
+        Some(Commands::Bench {
+            action: BenchAction::Detectors { tasks, ticks },
+        }) => {
+            let config = config::Config::load()?;
+            let detectors = || {
+                Ok(
+                    monitor::MonitorBuilder::from_config(Database::open(":memory:")?, &config)?
+                        .into_detectors(),
+                )
+            };
+            if !json {
+                eprintln!("Checking {} synthetic processes {} times...", tasks, ticks);
+            }
+            let report = monitor::bench::run(&monitor::probe::ProcProbe, detectors, tasks, ticks)?;
+            if json {
+                display::json::bench(&report)?;
+            } else {
+                display::display_bench(&report);
+            }
+        }
+        Some(Commands::Replay {
+            task_id,
+            speed,
+            idle_limit,
+        }) => {
+            anyhow::ensure!(
+                speed > 0.0 && idle_limit >= 0.0,
+                "--speed must be positive and --idle-limit not negative"
+            );
+            let config = config::Config::load()?;
+            let live = monitor::cast::cast_path(&monitor::output::default_log_path(&task_id));
+            let path = Some(live)
+                .filter(|path| path.exists())
+                .or_else(|| artifacts::latest_recording(&config.artifacts, &task_id))
+                .ok_or_else(|| {
+                    anyhow::anyhow!("No recording of {} (set [capture] record = true)", task_id)
+                })?;
+            monitor::cast::replay(
+                &path,
+                speed,
+                Duration::from_secs_f64(idle_limit),
+                &mut std::io::stdout(),
+                thread::sleep,
+            )?;
+        }
+        Some(Commands::Attach { task_id }) => {
+            let task = db
+                .get_task_by_id(&task_id)?
+                .ok_or_else(|| Failure::task_not_found(&task_id))?;
+            attach_task(&db_path, &task)?;
         }
-        Some(Commands::Clear { task_id }) => {
-            let deleted = db.delete_task(&task_id)?;
-            if deleted {
-                println!("Task {} cleared", task_id);
+        Some(Commands::Focus { task_id }) => {
+            let task = db
+                .get_task_by_id(&task_id)?
+                .ok_or_else(|| Failure::task_not_found(&task_id))?;
+            agent_inbox::presence::focus(&task)?;
+        }
+        Some(Commands::Respond {
+            task_id,
+            text,
+            approve,
+            deny: _,
+        }) => {
+            let task = db
+                .get_task_by_id(&task_id)?
+                .ok_or_else(|| Failure::task_not_found(&task_id))?;
+            match text {
+                Some(text) => {
+                    notify::actions::reply(&db, &task, &text, "the command line")?;
+                    println!("Sent to {}", task_id);
+                }
+                None => {
+                    let approval =
+                        notify::actions::answer_approval(&db, &task, approve, "the command line")?;
+                    println!(
+                        "{} {}",
+                        if approve { "Approved" } else { "Denied" },
+                        approval.action
+                    );
+                }
+            }
+        }
+        Some(Commands::CopyPrompt { task_id }) => {
+            let task = db
+                .get_task_by_id(&task_id)?
+                .ok_or_else(|| Failure::task_not_found(&task_id))?;
+            let text = notify::clipboard::copy_prompt(&task)?;
+            println!("Copied {} line(s) from {}", text.lines().count(), task_id);
+        }
+        Some(Commands::Note {
+            task_id,
+            text,
+            clear,
+        }) => {
+            let mut task = db
+                .get_task_by_id(&task_id)?
+                .ok_or_else(|| Failure::task_not_found(&task_id))?;
+
+            if clear {
+                task.clear_notes();
+                db.update_task(&task)?;
+                println!("Notes cleared for {}", task_id);
+            } else if let Some(text) = text {
+                task.add_note(&text);
+                db.update_task(&task)?;
+                db.log_event(&task_id, EventKind::Note, text);
+                println!("Note added to {}", task_id);
             } else {
-                println!("Task not found: {}", task_id);
+                for note in task.notes() {
+                    println!(
+                        "{}  {}",
+                        agent_inbox::datetime::times().absolute(note.at),
+                        note.text
+                    );
+                }
             }
         }
+        Some(Commands::Mute {
+            reason,
+            task,
+            channel,
+            minutes,
+        }) => {
+            if reason.is_none() && task.is_none() && channel.is_none() {
+                anyhow::ensure!(
+                    minutes.is_none(),
+                    "--minutes needs --reason, --task or --channel"
+                );
+                let config = config::Config::load()?;
+                for mute in &config.notify.mute {
+                    println!("config  {}", notify::Mute::from_config(mute).describe());
+                }
+                for mute in db.list_mutes(chrono::Utc::now())? {
+                    println!("{:>6}  {}", mute.id.unwrap_or_default(), mute.describe());
+                }
+                return Ok(());
+            }
+            let mut mute =
+                notify::Mute::new(reason.as_deref(), task.as_deref(), channel.as_deref());
+            if let Some(minutes) = minutes {
+                mute = mute.until(chrono::Utc::now() + chrono::Duration::minutes(minutes as i64));
+            }
+            let id = db.insert_mute(&mute)?;
+            println!("Muted {} (id {})", mute.describe(), id);
+        }
+        Some(Commands::Unmute { id, all }) => {
+            let lifted = db.delete_mutes(if all { None } else { id })?;
+            match id {
+                Some(id) if lifted == 0 => println!("No mute with id {}", id),
+                Some(id) => println!("Mute {} lifted", id),
+                None => println!("Lifted {} mutes", lifted),
+            }
+        }
+        Some(Commands::Feedback { id, clear, .. }) => {
+            let delivery = db.delivery(id)?.ok_or_else(|| {
+                Failure::new(Code::NotFound, format!("No notification with id {}", id))
+            })?;
+            let notification = delivery.notification;
+            let (Some(task_id), Some(reason)) = (notification.task_id, notification.reason) else {
+                anyhow::bail!("Notification {} wasn't raised by a detector", id);
+            };
+            if clear {
+                let cleared = db.clear_false_positives(&task_id, &reason)?;
+                println!(
+                    "Cleared {} false positives of {} on {}",
+                    cleared, reason, task_id
+                );
+                return Ok(());
+            }
+            let now = chrono::Utc::now();
+            db.record_false_positive(id, &task_id, &reason, now)?;
+            db.log_event(
+                &task_id,
+                EventKind::Note,
+                format!("Notification {} ({}) marked a false positive", id, reason),
+            );
+            let since = now - chrono::Duration::days(monitor::detectors::FEEDBACK_DAYS);
+            let count = db
+                .false_positives(&task_id, since)?
+                .get(&reason)
+                .copied()
+                .unwrap_or_default();
+            println!(
+                "Marked {} a false positive; {} on {} now needs {:.1}x the evidence",
+                id,
+                reason,
+                task_id,
+                monitor::detectors::tolerance(count)
+            );
+        }
+        Some(Commands::Ack { select }) => {
+            let Some(tasks) = selected(&db, &select, "acknowledge")? else {
+                return Ok(());
+            };
+            let now = chrono::Utc::now();
+            for_each(tasks, |mut task| {
+                if !task.acknowledge(now) {
+                    println!("{} doesn't need attention", task.task_id);
+                    return Ok(());
+                }
+                db.update_task(&task)?;
+                db.log_event(&task.task_id, EventKind::AttentionCleared, "Acknowledged");
+                println!("Acknowledged {}", task.task_id);
+                Ok(())
+            })?;
+        }
+        Some(Commands::Kill { select }) => {
+            use agent_inbox::monitor::probe::{ProcProbe, ProcessProbe};
+            let Some(tasks) = selected(&db, &select, "kill")? else {
+                return Ok(());
+            };
+            let hostname = agent_inbox::host::current(&config::HostConfig::default()).hostname;
+            for_each(tasks, |mut task| {
+                match (task.status.clone(), task.pid) {
+                    (TaskStatus::Queued, _) => {
+                        task.set_exited(None);
+                        db.update_task(&task)?;
+                        db.log_event(
+                            &task.task_id,
+                            EventKind::Killed,
+                            "Cancelled before it started",
+                        );
+                        println!("Cancelled {}", task.task_id);
+                    }
+                    (TaskStatus::Running, Some(pid)) => {
+                        if let Some(host) = task.host().filter(|host| host.hostname != hostname) {
+                            anyhow::bail!(
+                                "{} runs on {}; kill it there",
+                                task.task_id,
+                                host.hostname
+                            );
+                        }
+                        ProcProbe.terminate(pid).with_context(|| {
+                            format!("Failed to stop {} (pid {})", task.task_id, pid)
+                        })?;
+                        db.log_event(
+                            &task.task_id,
+                            EventKind::Killed,
+                            format!("Sent SIGTERM to pid {}", pid),
+                        );
+                        println!("Stopped {} (pid {})", task.task_id, pid);
+                    }
+                    (TaskStatus::Running, None) => {
+                        println!("{} has no process to stop", task.task_id)
+                    }
+                    _ => println!("{} isn't running", task.task_id),
+                }
+                Ok(())
+            })?;
+        }
+        Some(Commands::Snooze { duration, select }) => {
+            let Some(tasks) = selected(&db, &select, "snooze")? else {
+                return Ok(());
+            };
+            let until = chrono::Utc::now() + duration;
+            let at = agent_inbox::datetime::times().absolute(until);
+            for_each(tasks, |task| {
+                let id = db.insert_mute(
+                    &notify::Mute::new(None, Some(&task.task_id), None).until(until),
+                )?;
+                println!("Snoozed {} until {} (mute {})", task.task_id, at, id);
+                Ok(())
+            })?;
+        }
+        Some(Commands::Clear { select }) => {
+            let Some(tasks) = selected(&db, &select, "clear")? else {
+                return Ok(());
+            };
+            for_each(tasks, |task| {
+                db.delete_task(&task.task_id)?;
+                println!("Task {} cleared", task.task_id);
+                Ok(())
+            })?;
+        }
         Some(Commands::ClearAll) => {
             let completed = db.list_tasks(Some(TaskStatus::Completed))?;
             let exited = db.list_tasks(Some(TaskStatus::Exited))?;
@@ -113,24 +480,195 @@ fn main() -> Result<()> {
 
             println!("✓ Cleared all {} tasks", count);
         }
-        Some(Commands::Watch) => {
-            println!("Watching tasks (Ctrl+C to exit)...\n");
+        Some(Commands::Watch {
+            pidfile,
+            unit,
+            user,
+            id,
+            title,
+            log,
+        }) => {
+            let (source, default_id): (Box<dyn PidSource>, String) = if let Some(path) = pidfile {
+                let stem = path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                (Box::new(PidFile::new(path)), format!("pidfile-{}", stem))
+            } else if let Some(unit) = unit {
+                let unit = SystemdUnit::new(&unit, user);
+                let name = unit.unit().trim_end_matches(".service").to_string();
+                (Box::new(unit), format!("unit-{}", name))
+            } else {
+                println!("Watching tasks (Ctrl+C to exit)...\n");
 
-            loop {
-                // Clear screen
-                print!("\x1B[2J\x1B[1;1H");
+                loop {
+                    // Clear screen
+                    print!("\x1B[2J\x1B[1;1H");
 
-                let tasks = db.list_tasks(None)?;
-                display::display_task_list(&tasks);
+                    let tasks = db.list_tasks(None)?;
+                    display::display_task_list(&tasks, false);
 
-                thread::sleep(Duration::from_secs(2));
+                    thread::sleep(Duration::from_secs(2));
+                }
+            };
+
+            // Runs until the task is cleared
+            let task_id = id.unwrap_or(default_id);
+            let title = title.unwrap_or_else(|| source.describe());
+            let task = Task::new(task_id, "service".to_string(), title, None, None);
+            let config = config::Config::load()?;
+            monitor::MonitorBuilder::from_config(db, &config)?
+                .build()
+                .follow(task, source.as_ref(), log)?;
+        }
+        Some(Commands::Status { format }) => {
+            let format =
+                display::StatusFormat::from_str(&format).map_err(|e| anyhow::anyhow!(e))?;
+            let tasks = db.list_tasks(None)?;
+            if json {
+                display::json::status(&tasks)?;
+            } else {
+                println!("{}", display::format_status_bar(&tasks, &format));
             }
         }
+        Some(Commands::Wait { task_id, timeout }) => {
+            let give_up = timeout.map(|secs| Instant::now() + Duration::from_secs(secs));
+            loop {
+                let task = db
+                    .get_task_by_id(&task_id)?
+                    .ok_or_else(|| Failure::task_not_found(&task_id))?;
+                let Some(code) = exit::outcome(&task) else {
+                    if task.status == TaskStatus::Queued && daemon::running().is_none() {
+                        let message = format!(
+                            "Task {} is queued, but no daemon is running to start it",
+                            task_id
+                        );
+                        return Err(Failure::new(Code::DaemonUnreachable, message).into());
+                    }
+                    if give_up.is_some_and(|at| Instant::now() >= at) {
+                        let message = format!("Task {} is still {}", task_id, task.status.as_str());
+                        return Err(Failure::new(Code::TimedOut, message).into());
+                    }
+                    thread::sleep(Duration::from_secs(1));
+                    continue;
+                };
+                let message = match code {
+                    Code::TaskFailed => format!(
+                        "Task {} exited with code {}",
+                        task_id,
+                        task.exit_code.unwrap_or_default()
+                    ),
+                    Code::NeedsAttention => format!(
+                        "Task {} needs attention: {}",
+                        task_id,
+                        task.attention_reason.as_deref().unwrap_or_default()
+                    ),
+                    _ => {
+                        println!("Task {} {}", task_id, task.status.as_str());
+                        return Ok(());
+                    }
+                };
+                return Err(Failure::new(code, message).into());
+            }
+        }
+        Some(Commands::Oneshot { .. } | Commands::PluginHost { .. }) => {
+            unreachable!("run before the database is opened")
+        }
         Some(Commands::Cleanup { retention_secs }) => {
             let deleted = db.cleanup_old_completed(retention_secs)?;
             println!("Cleaned up {} old completed tasks", deleted);
         }
-        Some(Commands::Report { action }) => match action {
+        Some(Commands::Register {
+            id,
+            agent_type,
+            title,
+            cwd,
+            pid,
+            ppid,
+            options,
+            focus_cmd,
+        }) => {
+            let task_id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            let cwd = match cwd {
+                Some(cwd) => cwd,
+                None => std::env::current_dir()?.to_string_lossy().into_owned(),
+            };
+
+            let mut task = Task::new(task_id, agent_type, title, pid, ppid);
+            task.context = Some(TaskContext {
+                url: None,
+                project_path: Some(cwd),
+                session_id: None,
+                extra: HashMap::new(),
+            });
+
+            // Print only the ID so wrappers can capture it
+            let mut task = db.register_task(&task)?;
+            capture_process(&mut task, pid);
+            if let Some(command) = &focus_cmd {
+                task.set_focus_command(command);
+            }
+            options.apply(&mut task);
+            db.update_task(&task)?;
+            db.log_event(&task.task_id, EventKind::Started, started_message(&task));
+            println!("{}", task.task_id);
+        }
+        Some(Commands::Presence { action }) => match action {
+            PresenceAction::Away => desktop::write_hook_state(true)?,
+            PresenceAction::Active => desktop::write_hook_state(false)?,
+            PresenceAction::Status => {
+                let config = config::Config::load()?;
+                let routing = config.notify.routing.clone().unwrap_or_default();
+                let provider = desktop::provider_from_config(&routing)?;
+                match provider.state() {
+                    Some(state) => println!(
+                        "{}: {} (idle {}s{}{})",
+                        provider.name(),
+                        if state.is_away(Duration::from_secs(routing.away_after_secs)) {
+                            "away"
+                        } else {
+                            "present"
+                        },
+                        state.idle.as_secs(),
+                        if state.locked { ", locked" } else { "" },
+                        if state.focus { ", focus on" } else { "" }
+                    ),
+                    None => println!("{}: unknown", provider.name()),
+                }
+
+                let loaded = plugins::load_all(
+                    plugins::default_plugin_dir(),
+                    &config.plugins,
+                    &config.sandbox,
+                )?;
+                let notifier = build_notifier(&config, loaded.channels)?;
+                println!("Channels: {}", notifier.active_channel_names().join(", "));
+            }
+        },
+        Some(Commands::Report {
+            action: None,
+            by,
+            last,
+            format,
+        }) => {
+            let until = chrono::Utc::now();
+            let heat = agent_inbox::digest::heat::Heat::build(&db, by, until - last, until)?;
+            match format.as_str() {
+                _ if json => display::json::heat(&heat)?,
+                "json" => display::json::heat(&heat)?,
+                "markdown" => println!("{}", heat.markdown()),
+                "table" => {
+                    for line in heat.lines() {
+                        println!("{}", line);
+                    }
+                }
+                other => anyhow::bail!("Unknown format: {} (table, markdown or json)", other),
+            }
+        }
+        Some(Commands::Report {
+            action: Some(action),
+            ..
+        }) => match action {
             ReportAction::Start {
                 task_id,
                 agent_type,
@@ -138,8 +676,11 @@ fn main() -> Result<()> {
                 title,
                 pid,
                 ppid,
+                options,
+                focus_cmd,
             } => {
                 let mut task = Task::new(task_id, agent_type, title, pid, ppid);
+                options.apply(&mut task);
 
                 // Add context
                 task.context = Some(TaskContext {
@@ -148,54 +689,895 @@ fn main() -> Result<()> {
                     session_id: None,
                     extra: HashMap::new(),
                 });
+                capture_process(&mut task, pid);
+                if let Some(command) = &focus_cmd {
+                    task.set_focus_command(command);
+                }
 
                 db.insert_task(&task)?;
+                db.log_event(&task.task_id, EventKind::Started, started_message(&task));
                 println!("Task started: {}", task.task_id);
             }
-            ReportAction::Complete { task_id, exit_code } => {
-                let mut task = db
-                    .get_task_by_id(&task_id)?
-                    .ok_or_else(|| anyhow::anyhow!("Task not found: {}", task_id))?;
-
-                // If exit_code is provided and non-zero, mark as exited (failed)
-                // Otherwise mark as completed (finished generating)
-                if let Some(code) = exit_code {
-                    if code != 0 {
-                        task.set_exited(Some(code));
-                    } else {
-                        task.complete();
-                    }
-                } else {
-                    task.complete();
-                }
-                db.update_task(&task)?;
+            ReportAction::Complete {
+                task_id,
+                exit_code,
+                notify,
+            } => {
+                report_complete(&db, &task_id, exit_code, notify)?;
                 println!("Task completed: {}", task_id);
             }
             ReportAction::Running { task_id } => {
                 let mut task = db
                     .get_task_by_id(&task_id)?
-                    .ok_or_else(|| anyhow::anyhow!("Task not found: {}", task_id))?;
+                    .ok_or_else(|| Failure::task_not_found(&task_id))?;
 
+                let resumed = task.status != TaskStatus::Running || task.attention_reason.is_some();
+                task.attention_reason = None;
+                task.clear_acknowledged();
+                let waited = if resumed {
+                    stats::record_response(&db, &mut task, chrono::Utc::now())
+                } else {
+                    None
+                };
                 task.set_running();
                 db.update_task(&task)?;
+                if resumed {
+                    let message = match waited {
+                        Some(secs) => {
+                            format!("Generating again, answered after {}", format_duration(secs))
+                        }
+                        None => "Generating again".to_string(),
+                    };
+                    db.log_event(&task_id, EventKind::Resumed, message);
+                    let config = config::Config::load()?;
+                    let loaded = plugins::load_all(
+                        plugins::default_plugin_dir(),
+                        &config.plugins,
+                        &config.sandbox,
+                    )?;
+                    build_notifier(&config, loaded.channels)?.resolved(&task_id);
+                }
                 println!("Task running: {}", task_id);
             }
             ReportAction::Exited { task_id, exit_code } => {
                 let mut task = db
                     .get_task_by_id(&task_id)?
-                    .ok_or_else(|| anyhow::anyhow!("Task not found: {}", task_id))?;
+                    .ok_or_else(|| Failure::task_not_found(&task_id))?;
 
                 task.set_exited(exit_code);
                 db.update_task(&task)?;
+                db.log_event(
+                    &task_id,
+                    EventKind::Exited,
+                    match exit_code {
+                        Some(code) => format!("Exit code {}", code),
+                        None => "Process terminated".to_string(),
+                    },
+                );
+                fire_hooks(&db, &task);
                 println!("Task exited: {}", task_id);
             }
+            ReportAction::Attention {
+                task_id,
+                kind,
+                detail,
+                notify,
+            } => {
+                let mut task = db
+                    .get_task_by_id(&task_id)?
+                    .ok_or_else(|| Failure::task_not_found(&task_id))?;
+
+                let config = config::Config::load()?;
+                let reasons = ReasonRegistry::from_config(&config.reasons);
+                let reason = AttentionReason::kind(&kind, detail.as_deref());
+                let message = reasons.describe(&reason);
+                let now = chrono::Utc::now();
+                let cooling = task.acknowledged().is_some_and(|ack| {
+                    ack.covers(reason.key(), reasons.ack_cooldown(reason.key()), now)
+                });
+                task.set_attention(reason.key(), message.clone());
+                task.updated_at = now;
+                db.update_task(&task)?;
+                db.log_event(&task_id, EventKind::Attention, message.clone());
+
+                if notify && cooling {
+                    db.log_event(
+                        &task_id,
+                        EventKind::Suppressed,
+                        format!("Acknowledged recently: {}", message),
+                    );
+                } else if notify {
+                    let loaded = plugins::load_all(
+                        plugins::default_plugin_dir(),
+                        &config.plugins,
+                        &config.sandbox,
+                    )?;
+                    let title = format!("[{}] {}", task.agent_type, task.title);
+                    let notification = notify::Notification {
+                        title: match reasons.icon(&reason) {
+                            "" => title,
+                            icon => format!("{} {}", icon, title),
+                        },
+                        body: message,
+                        urgency: reasons.severity(&reason),
+                        category: notify::Category::Attention,
+                        task_id: Some(task_id.clone()),
+                        reason: Some(reason.key().to_string()),
+                    };
+                    let delivered = build_notifier(&config, loaded.channels)?.notify(&notification);
+                    let (kind, message) = notify::delivery_event(&notification, &delivered);
+                    db.log_event(&task_id, kind, message);
+                }
+                println!("Task needs attention: {}", task_id);
+            }
         },
-        Some(Commands::Monitor { task_id, pid }) => {
+        Some(Commands::Monitor {
+            task_id,
+            pid,
+            log,
+            tmux_pane,
+        }) => {
             // Create a monitor and start monitoring
-            let monitor = monitor::TaskMonitor::new(db);
-            monitor.monitor_task(task_id, pid)?;
+            let config = config::Config::load()?;
+            if let Some(pane) = &tmux_pane {
+                if let Some(mut task) = db.get_task_by_id(&task_id)? {
+                    task.set_tmux_pane(pane);
+                    db.update_task(&task)?;
+                }
+            }
+            let mut builder = monitor::MonitorBuilder::from_config(db, &config)?;
+            if let Some(pane) = tmux_pane {
+                builder = builder.input(Box::new(monitor::respond::TmuxInput::new(&pane)));
+            }
+            builder.build().monitor_task(task_id, pid, log)?;
+        }
+        Some(Commands::Watchdog { pid }) => daemon::watchdog::run(pid)?,
+        Some(Commands::Init) => init::run_interactive()?,
+        Some(Commands::Alert {
+            task_id,
+            title,
+            body,
+            critical,
+            approval,
+        }) => {
+            let config = config::Config::load()?;
+            let locales = notify::Locales::from_config(
+                &config.notify.locale,
+                &notify::locale::default_locale_dir(),
+            )?;
+            let catalog = locales.for_channel("desktop").unwrap_or_default();
+            notify::actions::run_alert(&db, &task_id, &title, &body, critical, approval, &catalog)?;
+        }
+        Some(Commands::Queue {
+            after,
+            id,
+            title,
+            agent_type,
+            cwd,
+            options,
+            restart,
+            group,
+            command,
+        }) => {
+            let command = daemon::shell_join(&command);
+            let cwd = match cwd {
+                Some(cwd) => cwd,
+                None => std::env::current_dir()?.to_string_lossy().into_owned(),
+            };
+            let task_id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            let title = title.unwrap_or_else(|| command.clone());
+
+            let mut task = Task::new(task_id, agent_type, title, None, None);
+            options.apply(&mut task);
+            let host = config::Config::load()
+                .map(|config| config.notify.host)
+                .unwrap_or_default();
+            task.set_host(&agent_inbox::host::current(&host));
+            task.context = Some(TaskContext {
+                url: None,
+                project_path: Some(cwd.clone()),
+                session_id: None,
+                extra: HashMap::new(),
+            });
+            let run = daemon::chain::QueuedRun {
+                command,
+                cwd: Some(cwd),
+                after,
+                restart,
+                group,
+                env: Default::default(),
+            };
+            let task = daemon::chain::queue_task(&db, task, run)?;
+            println!("{}", task.task_id);
+            if daemon::running().is_none() {
+                return Err(Failure::new(
+                    Code::DaemonUnreachable,
+                    "Queued, but no daemon is running to start it; start one with `agent-inbox daemon`",
+                )
+                .into());
+            }
+        }
+        Some(Commands::Rerun { task_id, id }) => {
+            let task = db
+                .get_task_by_id(&task_id)?
+                .ok_or_else(|| Failure::task_not_found(&task_id))?;
+            let config = config::Config::load()?;
+            let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            let rerun = daemon::rerun::rerun(&db, &config, &task, id)?;
+            println!("{}", rerun.task_id);
+            if daemon::running().is_none() {
+                return Err(Failure::new(
+                    Code::DaemonUnreachable,
+                    "Queued, but no daemon is running to start it; start one with `agent-inbox daemon`",
+                )
+                .into());
+            }
+        }
+        Some(Commands::Summary { hours, notify }) => {
+            let config = config::Config::load()?;
+            let hours = hours.unwrap_or(config.digest.hours);
+            let until = chrono::Utc::now();
+            let digest = Digest::build(&db, until - chrono::Duration::hours(hours as i64), until)?;
+
+            if notify {
+                let loaded = plugins::load_all(
+                    plugins::default_plugin_dir(),
+                    &config.plugins,
+                    &config.sandbox,
+                )?;
+                build_notifier(&config, loaded.channels)?.notify(&digest.notification());
+            } else if json {
+                display::json::digest(&digest)?;
+            } else {
+                println!("{}", digest.title());
+                let body = digest.body();
+                if !body.is_empty() {
+                    println!("{}", body);
+                }
+            }
+        }
+        Some(Commands::Stats { hours }) => {
+            let since = hours.map(|h| chrono::Utc::now() - chrono::Duration::hours(h as i64));
+            let stats = stats::Stats::from_tasks(&db.list_tasks(None)?, since);
+            if json {
+                display::json::stats(&stats)?;
+            } else {
+                for line in stats.lines() {
+                    println!("{}", line);
+                }
+            }
+        }
+        Some(Commands::Costs { days }) => {
+            let budget = config::Config::load()?.costs.daily_budget_usd;
+            if json {
+                let since = costs::today() - chrono::Duration::days(days.saturating_sub(1) as i64);
+                display::json::costs(&db.usage_by_day(since)?, &db.usage_by_task(since)?, budget)?;
+            } else {
+                for line in costs::report_lines(&db, days, budget)? {
+                    println!("{}", line);
+                }
+            }
+        }
+        Some(Commands::Notifications {
+            action:
+                NotificationsAction::History {
+                    channel,
+                    task,
+                    since,
+                    until,
+                    delivered,
+                    failed,
+                    limit,
+                    resend,
+                },
+        }) => {
+            if let Some(id) = resend {
+                let delivery = db.delivery(id)?.ok_or_else(|| {
+                    Failure::new(Code::NotFound, format!("No notification with id {}", id))
+                })?;
+                let config = config::Config::load()?;
+                let loaded = plugins::load_all(
+                    plugins::default_plugin_dir(),
+                    &config.plugins,
+                    &config.sandbox,
+                )?;
+                let notification = delivery.notification;
+                let delivered = build_notifier(&config, loaded.channels)?.notify(&notification);
+                if let Some(task_id) = &notification.task_id {
+                    let (kind, message) = notify::delivery_event(&notification, &delivered);
+                    db.log_event(task_id, kind, format!("Resent: {}", message));
+                }
+                if delivered.is_empty() {
+                    return Err(Failure::new(
+                        Code::Undelivered,
+                        "No channel delivered the notification",
+                    )
+                    .into());
+                }
+                println!("Resent {} via {}", id, delivered.join(", "));
+                return Ok(());
+            }
+
+            let filter = notify::history::HistoryFilter {
+                channel,
+                task,
+                since: since.map(local_midnight),
+                until: until.and_then(|day| day.succ_opt()).map(local_midnight),
+                outcome: if delivered {
+                    Some(notify::history::Outcome::Delivered)
+                } else if failed {
+                    Some(notify::history::Outcome::Failed)
+                } else {
+                    None
+                },
+            };
+            let mut deliveries = db.deliveries(&filter)?;
+            deliveries.truncate(limit);
+            if json {
+                display::json::notification_history(&deliveries)?;
+            } else {
+                display::display_notification_history(&deliveries);
+            }
+        }
+        Some(Commands::Hub {
+            action: HubAction::Receive,
+        }) => {
+            let mut input = String::new();
+            std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)?;
+            let envelope = federation::Envelope::parse(input.as_bytes())?;
+            let notifier = federation::hub_notifier(&config::Config::load()?.notify)?;
+            let delivered = federation::receive(&notifier, &envelope);
+            if delivered.is_empty() {
+                return Err(Failure::new(
+                    Code::Undelivered,
+                    "No channel delivered the notification",
+                )
+                .into());
+            }
+        }
+        Some(Commands::Storage {
+            action: StorageAction::Info,
+        }) => {
+            let config = config::Config::load()?;
+            let usage = storage::usage_by_task(
+                &storage::state_dir().join("logs"),
+                &artifacts::root(&config.artifacts),
+            );
+            if json {
+                display::json::storage(storage::dirs(), storage::dir_size(&db_path), &usage)?;
+            } else {
+                display::display_storage(storage::dirs(), storage::dir_size(&db_path), &usage);
+            }
+        }
+        Some(Commands::Daemon { observe }) => {
+            let mut config = config::Config::load()?;
+            config.daemon.observe |= observe;
+            let mut daemon = daemon::Daemon::new(config, db_path)?;
+            let jobs = daemon.job_names();
+            if jobs.is_empty() {
+                println!("agent-inbox daemon started (no [[schedule]] entries configured)");
+            } else {
+                println!("agent-inbox daemon started, scheduled: {}", jobs.join(", "));
+            }
+            daemon.run()?;
+        }
+        Some(Commands::Doctor) => {
+            let path = config::default_config_path();
+            // Sections checked beyond parsing
+            let loaded = config::Config::load();
+            let config = match loaded
+                .as_ref()
+                .map_err(|e| anyhow::anyhow!("{:#}", e))
+                .and_then(|config| agent_inbox::datetime::Times::new(&config.time))
+            {
+                Ok(_) if path.exists() => (true, path.display().to_string()),
+                Ok(_) => (
+                    true,
+                    format!("{} not found, using defaults", path.display()),
+                ),
+                Err(e) => (false, format!("{:#}", e)),
+            };
+            let daemon = match daemon::running() {
+                Some(pid) => (true, format!("running, pid {}", pid)),
+                None => (
+                    false,
+                    "not running; queued tasks and schedules won't start".to_string(),
+                ),
+            };
+            let problems = monitor::access::system();
+            let proc = if problems.is_empty() {
+                (true, "processes fully readable".to_string())
+            } else {
+                (false, problems.join("; "))
+            };
+            let sandbox =
+                agent_inbox::sandbox::Sandbox::new(loaded.unwrap_or_default().sandbox).describe();
+            let checks = [
+                ("config", config.0, config.1),
+                ("daemon", daemon.0, daemon.1),
+                ("proc", proc.0, proc.1),
+                ("sandbox", sandbox.0, sandbox.1),
+            ];
+            let liveness = monitor::liveness::detect();
+            if json {
+                return display::json::doctor(&checks, liveness);
+            }
+            for (name, ok, detail) in &checks {
+                println!("{} {:<8} {}", if *ok { "✓" } else { "✗" }, name, detail);
+            }
+            println!("  {:<8} {}", "exits", liveness.strategy.as_str());
+            for (strategy, reason) in &liveness.unavailable {
+                println!("    {} unavailable: {}", strategy.as_str(), reason);
+            }
+        }
+        Some(Commands::Plugins) => {
+            let dir = plugins::default_plugin_dir();
+            let manifests = plugins::discover(&dir)?;
+            if json {
+                return display::json::plugins(&manifests);
+            }
+            if manifests.is_empty() {
+                println!("No plugins installed in {}", dir.display());
+            }
+            for manifest in manifests {
+                println!(
+                    "{} {} [{}] {}",
+                    manifest.name,
+                    manifest.version,
+                    manifest.capabilities.join(", "),
+                    manifest.description
+                );
+                for (key, field) in &manifest.config_schema {
+                    println!("    {} ({}) {}", key, field.field_type, field.description);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Mark a task finished with `exit_code`, fire its hooks, archive its
+/// artifacts and, with `notify`, send the completion notification unless
+/// the user is at its terminal
+fn report_complete(
+    db: &Database,
+    task_id: &str,
+    exit_code: Option<i32>,
+    notify: bool,
+) -> Result<()> {
+    let mut task = db
+        .get_task_by_id(task_id)?
+        .ok_or_else(|| Failure::task_not_found(task_id))?;
+
+    // If exit_code is provided and non-zero, mark as exited (failed)
+    // Otherwise mark as completed (finished generating)
+    if let Some(code) = exit_code {
+        if code != 0 {
+            task.set_exited(Some(code));
+        } else {
+            task.complete();
+        }
+    } else {
+        task.complete();
+    }
+    let config = config::Config::load()?;
+    let secrets = SecretFilter::from_config(&config.notify.redact)?;
+    task.set_test_results(summary::task_test_results(&config.summary, &secrets, task_id).as_ref());
+    db.update_task(&task)?;
+    let (kind, message) = match exit_code {
+        Some(code) if code != 0 => (EventKind::Exited, format!("Exit code {}", code)),
+        Some(code) => (EventKind::Completed, format!("Exit code {}", code)),
+        None => (EventKind::Completed, "Finished generating".to_string()),
+    };
+    db.log_event(task_id, kind, message);
+    fire_hooks(db, &task);
+    let archived = collect_artifacts(db, &task);
+
+    if notify {
+        // The wrapper reports from the task's own terminal
+        let watching = task.priority() != Priority::Critical
+            && SystemPresence::from_config(&config.presence)
+                .is_some_and(|p| p.watching(std::process::id() as i32));
+        if watching {
+            db.log_event(
+                task_id,
+                EventKind::Suppressed,
+                "User at the terminal: completion",
+            );
+        } else {
+            let loaded = plugins::load_all(
+                plugins::default_plugin_dir(),
+                &config.plugins,
+                &config.sandbox,
+            )?;
+            let (title, mut body) =
+                summary::completion_message(&config.summary, &secrets, db, &task, exit_code);
+            if let Some(dir) = &archived {
+                body = format!("{}\n{}", body, artifacts::notification_line(dir));
+            }
+            let notification = notify::Notification {
+                title,
+                body,
+                urgency: notify::Urgency::Normal,
+                category: notify::Category::Completed,
+                task_id: Some(task_id.to_string()),
+                reason: None,
+            };
+            let delivered = build_notifier(&config, loaded.channels)?.notify(&notification);
+            let (kind, message) = notify::delivery_event(&notification, &delivered);
+            db.log_event(task_id, kind, message);
+        }
+    }
+    Ok(())
+}
+
+/// `agent-inbox -- <cmd>`: run the command in this terminal as a task,
+/// monitored like any other, notify when it ends and return its exit code
+fn run_wrapped(db: Database, args: &[String]) -> Result<i32> {
+    let command = daemon::shell_join(args);
+    let task_id = uuid::Uuid::new_v4().to_string();
+    let cwd = std::env::current_dir()?;
+    let mut process = std::process::Command::new(&args[0]);
+    process.args(&args[1..]);
+    // Removed when the command is done
+    let fifo = match monitor::fifo::Fifo::create(&monitor::fifo::dir(), &task_id) {
+        Ok(fifo) => {
+            process.env(monitor::fifo::ENV, fifo.path());
+            Some(fifo)
+        }
+        Err(e) => {
+            eprintln!("Failed to make a FIFO for {}: {}", task_id, e);
+            None
+        }
+    };
+    // Whatever can fail does so before the command starts
+    let monitor_db = Database::open(db::default_db_path())?;
+    let mut monitor = monitor::MonitorBuilder::from_config(monitor_db, &config::Config::load()?)?;
+    if let Some(reader) = fifo.as_ref().and_then(|fifo| fifo.reader().ok()) {
+        monitor = monitor.fifo(reader);
+    }
+    let monitor = monitor.build();
+    let mut child = process
+        .spawn()
+        .with_context(|| format!("Failed to run '{}'", command))?;
+    // Ctrl-C is meant for the command; stay around to report how it ended
+    #[cfg(unix)]
+    // SAFETY: installs the default ignore disposition, no handler code runs
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+        libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+    }
+
+    let pid = child.id() as i32;
+    let mut task = Task::new(
+        task_id,
+        "shell".to_string(),
+        command,
+        Some(pid),
+        Some(std::process::id() as i32),
+    );
+    task.context = Some(TaskContext {
+        url: None,
+        project_path: Some(cwd.to_string_lossy().into_owned()),
+        session_id: None,
+        extra: HashMap::new(),
+    });
+    capture_process(&mut task, Some(pid));
+    // The command runs either way; failing to track it mustn't orphan it or
+    // lose its exit code
+    let tracked = db.insert_task(&task);
+    match &tracked {
+        Ok(_) => {
+            db.log_event(&task.task_id, EventKind::Started, started_message(&task));
+            // Not waited for at the end: it only notices the exit on its next poll
+            let task_id = task.task_id.clone();
+            thread::spawn(move || {
+                if let Err(e) = monitor.monitor_task(task_id, pid, None) {
+                    eprintln!("Monitor failed: {:#}", e);
+                }
+            });
+        }
+        Err(e) => eprintln!(
+            "Failed to record '{}', not tracking it: {:#}",
+            task.title, e
+        ),
+    }
+
+    let status = child.wait()?;
+    drop(fifo);
+    #[cfg(unix)]
+    let code = {
+        use std::os::unix::process::ExitStatusExt;
+        status.code().or(status.signal().map(|signal| 128 + signal))
+    };
+    #[cfg(not(unix))]
+    let code = status.code();
+    let code = code.unwrap_or(1);
+    if tracked.is_ok() {
+        if let Err(e) = report_complete(&db, &task.task_id, Some(code), true) {
+            eprintln!("Failed to report {}: {:#}", task.task_id, e);
         }
     }
+    Ok(code)
+}
 
+/// Activity-log text for a task (re)starting, e.g. "Run 3, pid 1234"
+fn started_message(task: &Task) -> String {
+    let run = task.run_history().len() + 1;
+    match (run, task.pid) {
+        (1, Some(pid)) => format!("pid {}", pid),
+        (1, None) => String::new(),
+        (run, Some(pid)) => format!("Run {}, pid {}", run, pid),
+        (run, None) => format!("Run {}", run),
+    }
+}
+
+/// Print the last `lines` lines of a task's captured output, raw so colors
+/// survive; with `follow`, keep printing until the task finishes
+fn tail_task(
+    db: &Database,
+    db_path: &std::path::Path,
+    task: &Task,
+    lines: usize,
+    follow: bool,
+) -> Result<()> {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    let log_path = monitor::output::default_log_path(&task.task_id);
+    let earlier = monitor::output::read_tail(&log_path, 256 * 1024).unwrap_or_default();
+    let earlier: Vec<&str> = earlier.lines().collect();
+    let mut stdout = std::io::stdout();
+    for line in &earlier[earlier.len().saturating_sub(lines)..] {
+        writeln!(stdout, "{}", line)?;
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    let _attachment = attached::Attachment::start(db_path.to_path_buf(), &task.task_id, "tail")?;
+    let mut offset = std::fs::metadata(&log_path).map(|m| m.len()).unwrap_or(0);
+    loop {
+        let running = db
+            .get_task_by_id(&task.task_id)?
+            .is_some_and(|task| matches!(task.status, TaskStatus::Running | TaskStatus::Queued));
+        if let Ok(mut file) = std::fs::File::open(&log_path) {
+            // Compressed into a chunk and started over
+            if file.metadata()?.len() < offset {
+                offset = 0;
+            }
+            file.seek(SeekFrom::Start(offset))?;
+            let mut new = Vec::new();
+            offset += file.read_to_end(&mut new)? as u64;
+            stdout.write_all(&new)?;
+            stdout.flush()?;
+        }
+        if !running {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+/// Print the monitor's trace for `task`, then with `follow` what it adds
+/// while the task runs
+fn trace_task(db: &Database, task: &Task, lines: usize, follow: bool) -> Result<()> {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    let path = monitor::trace::path(&monitor::trace::dir(), &task.task_id);
+    let earlier = monitor::trace::last_lines(&path, lines);
+    if earlier.is_empty() && !follow {
+        println!(
+            "No trace for {} yet; it is written while `monitor` or the daemon watches the task",
+            task.task_id
+        );
+        return Ok(());
+    }
+    let mut stdout = std::io::stdout();
+    for line in &earlier {
+        writeln!(stdout, "{}", line)?;
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    let mut offset = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    loop {
+        let running = db
+            .get_task_by_id(&task.task_id)?
+            .is_some_and(|task| matches!(task.status, TaskStatus::Running | TaskStatus::Queued));
+        if let Ok(mut file) = std::fs::File::open(&path) {
+            // Rotated and started over
+            if file.metadata()?.len() < offset {
+                offset = 0;
+            }
+            file.seek(SeekFrom::Start(offset))?;
+            let mut new = Vec::new();
+            offset += file.read_to_end(&mut new)? as u64;
+            stdout.write_all(&new)?;
+            stdout.flush()?;
+        }
+        if !running {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+/// Attach this terminal to the task's tmux pane or Zellij session until
+/// the user detaches
+fn attach_task(db_path: &std::path::Path, task: &Task) -> Result<()> {
+    if let Some(pane) = task.zellij_pane().filter(|_| task.tmux_pane().is_none()) {
+        if zellij::inside() {
+            // Zellij doesn't nest; a focused tab already counts as watching
+            return zellij::focus(&pane);
+        }
+        let _attachment =
+            attached::Attachment::start(db_path.to_path_buf(), &task.task_id, "attach")?;
+        let status = zellij::attach(&pane)?;
+        anyhow::ensure!(
+            status.success(),
+            "zellij could not attach to {}",
+            pane.session
+        );
+        return Ok(());
+    }
+    let pane = task.tmux_pane().with_context(|| {
+        format!(
+            "{} has no tmux pane or Zellij session to attach to; follow its output with `agent-inbox tail -f {}`",
+            task.task_id, task.task_id
+        )
+    })?;
+    let tmux = |args: &[&str]| -> Result<std::process::ExitStatus> {
+        std::process::Command::new("tmux")
+            .args(args)
+            .status()
+            .context("Failed to run tmux")
+    };
+    if std::env::var_os("TMUX").is_some() {
+        // A nested client would fight the current one; a focused pane
+        // already counts as watching ([presence] suppress_when_watching)
+        anyhow::ensure!(
+            tmux(&["switch-client", "-t", pane])?.success(),
+            "tmux could not switch to {}",
+            pane
+        );
+        return Ok(());
+    }
+    tmux(&["select-window", "-t", pane, ";", "select-pane", "-t", pane])?;
+    let _attachment = attached::Attachment::start(db_path.to_path_buf(), &task.task_id, "attach")?;
+    let status = tmux(&["attach-session", "-t", pane])?;
+    anyhow::ensure!(status.success(), "tmux could not attach to {}", pane);
+    Ok(())
+}
+
+/// Record the command line and `[environment] vars` of a registering
+/// task's process, the host it runs on, its Zellij pane and how to focus its
+/// WezTerm or kitty pane. A broken config only costs the environment and the
+/// host's nickname.
+fn capture_process(task: &mut Task, pid: Option<i32>) {
+    let (vars, host) = match config::Config::load() {
+        Ok(config) => (config.environment.vars, config.notify.host),
+        Err(e) => {
+            eprintln!("Skipping environment capture: {:#}", e);
+            (Vec::new(), config::HostConfig::default())
+        }
+    };
+    task.set_process(&monitor::environ::capture(pid, &vars));
+    task.set_host(&agent_inbox::host::current(&host));
+    if let Some(pane) = zellij::from_env() {
+        task.set_zellij_pane(&pane);
+    }
+    if let Some(command) =
+        agent_inbox::presence::focus_command_from_env(|var| std::env::var(var).ok())
+    {
+        task.set_focus_command(&command);
+    }
+}
+
+/// The tasks `select` picks, or None once a `--dry-run` has listed them. A
+/// single ID that doesn't exist is an error; filters may match nothing.
+fn selected(db: &Database, select: &cli::Select, verb: &str) -> Result<Option<Vec<Task>>> {
+    let tasks = match &select.task_id {
+        Some(task_id) => vec![db
+            .get_task_by_id(task_id)?
+            .ok_or_else(|| Failure::task_not_found(task_id))?],
+        None => select.selection().select(db.list_tasks(None)?),
+    };
+    if tasks.is_empty() {
+        println!("No tasks match");
+        return Ok(None);
+    }
+    if select.dry_run {
+        println!("Would {} {} task(s):", verb, tasks.len());
+        for task in &tasks {
+            println!(
+                "  {:<20} {:<10} {}",
+                task.task_id,
+                task.status.as_str(),
+                task.title
+            );
+        }
+        return Ok(None);
+    }
+    Ok(Some(tasks))
+}
+
+/// Apply `action` to every task, reporting failures without stopping; the
+/// command fails if any task did
+fn for_each(tasks: Vec<Task>, mut action: impl FnMut(Task) -> Result<()>) -> Result<()> {
+    let total = tasks.len();
+    let mut failed = 0;
+    for task in tasks {
+        let task_id = task.task_id.clone();
+        if let Err(e) = action(task) {
+            eprintln!("{}: {:#}", task_id, e);
+            failed += 1;
+        }
+    }
+    anyhow::ensure!(failed == 0, "{} of {} task(s) failed", failed, total);
     Ok(())
 }
+
+/// Start of `day` in the `[time]` zone
+fn local_midnight(day: chrono::NaiveDate) -> chrono::DateTime<chrono::Utc> {
+    agent_inbox::datetime::times().midnight(day)
+}
+
+/// Run the `[[hooks]]` matching a task that just finished. A broken config
+/// must not fail the report itself.
+fn fire_hooks(db: &Database, task: &Task) {
+    match config::Config::load().and_then(|config| agent_inbox::hooks::Hooks::from_config(&config))
+    {
+        Ok(hooks) => hooks.fire(db, task),
+        Err(e) => eprintln!("Skipping hooks: {:#}", e),
+    }
+}
+
+/// Archive a finished run when `[artifacts]` is enabled. Like hooks, this
+/// never fails the report.
+fn collect_artifacts(db: &Database, task: &Task) -> Option<std::path::PathBuf> {
+    config::Config::load()
+        .and_then(|config| artifacts::collect(&config.artifacts, db, task))
+        .unwrap_or_else(|e| {
+            eprintln!("Skipping artifacts: {:#}", e);
+            None
+        })
+}
+
+/// Configured channels plus any channels contributed by plugins
+/// `agent-inbox oneshot`: wait for `pid` to exit, then notify once
+fn oneshot(pid: i32, message: Option<String>) -> Result<()> {
+    let config = config::Config::load()?;
+    let loaded = plugins::load_all(
+        plugins::default_plugin_dir(),
+        &config.plugins,
+        &config.sandbox,
+    )?;
+    let mut builder = notify::NotifierBuilder::from_config(&config.notify)?.stateless();
+    for channel in loaded.channels {
+        builder = builder.channel(channel);
+    }
+    let watched = monitor::oneshot::Oneshot::watch(&monitor::probe::ProcProbe, pid)?;
+    eprintln!("Waiting for process {} to exit (Ctrl+C to stop)...", pid);
+    let notification = watched.wait(message);
+    let delivered = builder.build().notify(&notification);
+    if delivered.is_empty() {
+        return Err(
+            Failure::new(Code::Undelivered, "No channel delivered the notification").into(),
+        );
+    }
+    println!("{} (via {})", notification.title, delivered.join(", "));
+    Ok(())
+}
+
+fn build_notifier(
+    config: &config::Config,
+    extra: Vec<Box<dyn notify::Channel>>,
+) -> Result<notify::Notifier> {
+    let mut builder = notify::NotifierBuilder::from_config(&config.notify)?;
+    for channel in extra {
+        builder = builder.channel(channel);
+    }
+    Ok(builder.build())
+}
@@ -0,0 +1,115 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What happened to a task, for `agent-inbox timeline`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Queued,
+    Started,
+    /// Output crossed a progress milestone (25%, 50%, ...)
+    Progress,
+    /// A detector or the deny-list flagged the task
+    Attention,
+    /// The flagged condition went away on its own
+    AttentionCleared,
+    /// The agent hit a provider rate limit and is waiting to retry
+    RateLimited,
+    AutoResponded,
+    /// The user answered from a notification's reply action
+    Replied,
+    Notified,
+    /// A notification was held back, e.g. because the user was watching
+    Suppressed,
+    /// The user answered and the agent is generating again
+    Resumed,
+    Completed,
+    Exited,
+    /// A completion hook ran
+    Hook,
+    /// A `[[rules]]` entry acted on an attention need
+    Rule,
+    /// Nobody answered an attention need in time and `--on-unattended` acted
+    Unattended,
+    /// The user left a note with `agent-inbox note`, or the task one over
+    /// its FIFO
+    Note,
+    /// The user stopped the task with `agent-inbox kill`
+    Killed,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            EventKind::Queued => "queued",
+            EventKind::Started => "started",
+            EventKind::Progress => "progress",
+            EventKind::Attention => "attention",
+            EventKind::AttentionCleared => "attention_cleared",
+            EventKind::RateLimited => "rate_limited",
+            EventKind::AutoResponded => "auto_responded",
+            EventKind::Replied => "replied",
+            EventKind::Notified => "notified",
+            EventKind::Suppressed => "suppressed",
+            EventKind::Resumed => "resumed",
+            EventKind::Completed => "completed",
+            EventKind::Exited => "exited",
+            EventKind::Hook => "hook",
+            EventKind::Rule => "rule",
+            EventKind::Unattended => "unattended",
+            EventKind::Note => "note",
+            EventKind::Killed => "killed",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "queued" => Ok(EventKind::Queued),
+            "started" => Ok(EventKind::Started),
+            "progress" => Ok(EventKind::Progress),
+            "attention" => Ok(EventKind::Attention),
+            "attention_cleared" => Ok(EventKind::AttentionCleared),
+            "rate_limited" => Ok(EventKind::RateLimited),
+            "auto_responded" => Ok(EventKind::AutoResponded),
+            "replied" => Ok(EventKind::Replied),
+            "notified" => Ok(EventKind::Notified),
+            "suppressed" => Ok(EventKind::Suppressed),
+            "resumed" => Ok(EventKind::Resumed),
+            "completed" => Ok(EventKind::Completed),
+            "exited" => Ok(EventKind::Exited),
+            "hook" => Ok(EventKind::Hook),
+            "rule" => Ok(EventKind::Rule),
+            "unattended" => Ok(EventKind::Unattended),
+            "note" => Ok(EventKind::Note),
+            "killed" => Ok(EventKind::Killed),
+            _ => Err(format!("Invalid event kind: {}", s)),
+        }
+    }
+}
+
+/// One entry in a task's activity log
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskEvent {
+    pub task_id: String,
+    pub at: DateTime<Utc>,
+    pub kind: EventKind,
+    pub message: String,
+}
+
+impl TaskEvent {
+    pub fn new(task_id: &str, kind: EventKind, message: impl Into<String>) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+            at: Utc::now(),
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Same event at an explicit time (e.g. from a fake clock)
+    pub fn at(mut self, at: DateTime<Utc>) -> Self {
+        self.at = at;
+        self
+    }
+}
@@ -1,3 +1,11 @@
+pub mod event;
+pub mod progress;
+pub mod select;
 pub mod task;
 
-pub use task::{Task, TaskContext, TaskStatus};
+pub use event::{EventKind, TaskEvent};
+pub use progress::Progress;
+pub use task::{
+    Acknowledged, HostInfo, Note, Priority, ProcessSnapshot, RunRecord, Task, TaskContext,
+    TaskStatus, TestResults, Unattended, UnattendedAction, ZellijPane,
+};
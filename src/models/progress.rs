@@ -0,0 +1,121 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Latest progress observed in a task's output, stored in task metadata
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Progress {
+    /// Completion fraction in the range 0.0..=1.0
+    pub fraction: f64,
+    /// The marker the fraction was parsed from (e.g. "3/10", "42%")
+    pub label: String,
+    /// Estimated seconds until completion, when the rate is known
+    pub eta_secs: Option<i64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Progress {
+    pub fn percent(&self) -> u8 {
+        (self.fraction.clamp(0.0, 1.0) * 100.0).round() as u8
+    }
+
+    /// Short human readable form, e.g. "42% ETA 5m"
+    pub fn describe(&self) -> String {
+        match self.eta_secs {
+            Some(eta) => format!("{}% ETA {}", self.percent(), format_duration(eta)),
+            None => format!("{}%", self.percent()),
+        }
+    }
+}
+
+/// Format a duration in seconds compactly: "45s", "12m", "1h42m", "2d3h"
+pub fn format_duration(secs: i64) -> String {
+    let secs = secs.max(0);
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m", secs / 60)
+    } else if secs < 86400 {
+        let minutes = (secs % 3600) / 60;
+        if minutes == 0 {
+            format!("{}h", secs / 3600)
+        } else {
+            format!("{}h{}m", secs / 3600, minutes)
+        }
+    } else {
+        let hours = (secs % 86400) / 3600;
+        if hours == 0 {
+            format!("{}d", secs / 86400)
+        } else {
+            format!("{}d{}h", secs / 86400, hours)
+        }
+    }
+}
+
+/// Parse a duration written like [`format_duration`]'s output: "45s",
+/// "15m", "1h30m", "2d". A bare number counts minutes.
+pub fn parse_duration(text: &str) -> Option<i64> {
+    let text = text.trim();
+    if let Ok(minutes) = text.parse::<i64>() {
+        return (minutes >= 0).then_some(minutes * 60);
+    }
+    let mut secs = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return None,
+        };
+        secs += number.parse::<i64>().ok()? * unit;
+        number.clear();
+    }
+    (number.is_empty() && !text.is_empty()).then_some(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(45), "45s");
+        assert_eq!(format_duration(720), "12m");
+        assert_eq!(format_duration(6120), "1h42m");
+        assert_eq!(format_duration(7200), "2h");
+        assert_eq!(format_duration(183600), "2d3h");
+        assert_eq!(format_duration(-5), "0s");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("1h"), Some(3600));
+        assert_eq!(parse_duration("1h42m"), Some(6120));
+        assert_eq!(parse_duration("2d3h"), Some(183600));
+        assert_eq!(parse_duration("45s"), Some(45));
+        assert_eq!(parse_duration("30"), Some(1800));
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("1h30"), None);
+        assert_eq!(parse_duration("an hour"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn test_progress_describe() {
+        let mut progress = Progress {
+            fraction: 0.8,
+            label: "8/10".to_string(),
+            eta_secs: Some(600),
+            updated_at: Utc::now(),
+        };
+        assert_eq!(progress.describe(), "80% ETA 10m");
+
+        progress.eta_secs = None;
+        assert_eq!(progress.describe(), "80%");
+    }
+}
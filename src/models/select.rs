@@ -0,0 +1,170 @@
+//! Picking the tasks a bulk command acts on
+//!
+//! `kill`, `snooze`, `ack` and `clear` take one task ID, or filters that
+//! may match many: `--tag` (every one given), `--group`, `--state` and
+//! `--all`. `--state` is a status (`running`, `queued`, `completed`,
+//! `exited`), `attention` for any attention need, or a reason kind;
+//! `stalled`, `input`, `approval` and `password` stand for the common ones.
+
+use super::{Task, TaskStatus};
+use crate::daemon::chain::QueuedRun;
+
+/// Short names of the reason kinds most worth picking tasks by
+const REASON_ALIASES: [(&str, &str); 4] = [
+    ("stalled", "process_stalled"),
+    ("input", "waiting_for_input"),
+    ("approval", "waiting_approval"),
+    ("password", "password_prompt"),
+];
+
+/// What `--state` picks
+#[derive(Debug, Clone, PartialEq)]
+pub enum State {
+    Status(TaskStatus),
+    /// Any attention need
+    Attention,
+    /// An attention need of this kind
+    Reason(String),
+}
+
+impl State {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim().to_lowercase().replace('-', "_");
+        if s.is_empty() {
+            return Err("Empty state".to_string());
+        }
+        if let Ok(status) = TaskStatus::from_str(&s) {
+            return Ok(State::Status(status));
+        }
+        if s == "attention" {
+            return Ok(State::Attention);
+        }
+        Ok(match REASON_ALIASES.iter().find(|(alias, _)| *alias == s) {
+            Some((_, key)) => State::Reason(key.to_string()),
+            None => State::Reason(s),
+        })
+    }
+
+    fn matches(&self, task: &Task) -> bool {
+        match self {
+            State::Status(status) => task.status == *status,
+            State::Attention => task.attention_reason.is_some(),
+            State::Reason(key) => {
+                task.attention_reason.is_some() && task.attention_key() == Some(key.as_str())
+            }
+        }
+    }
+}
+
+/// Filters of a bulk command; every one given must match
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Selection {
+    pub tags: Vec<String>,
+    /// Queue group (`queue --group`)
+    pub group: Option<String>,
+    pub state: Option<State>,
+}
+
+impl Selection {
+    pub fn matches(&self, task: &Task) -> bool {
+        let tags = task.tags();
+        self.tags.iter().all(|tag| tags.contains(tag))
+            && self.group.as_ref().is_none_or(|group| {
+                QueuedRun::of(task).and_then(|run| run.group).as_ref() == Some(group)
+            })
+            && self.state.as_ref().is_none_or(|state| state.matches(task))
+    }
+
+    /// The tasks out of `tasks` that match
+    pub fn select(&self, tasks: Vec<Task>) -> Vec<Task> {
+        tasks
+            .into_iter()
+            .filter(|task| self.matches(task))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection() {
+        let task = |id: &str, tags: &[&str], attention: Option<&str>| {
+            let mut task = Task::new(id.into(), "tool".into(), id.into(), None, None);
+            task.set_tags(&tags.iter().map(|t| t.to_string()).collect::<Vec<_>>());
+            if let Some(key) = attention {
+                task.set_attention(key, "Needs you".to_string());
+            }
+            task
+        };
+        let mut queued = task("nightly-1", &[], None);
+        let run = QueuedRun {
+            command: "make".into(),
+            cwd: None,
+            after: None,
+            restart: None,
+            group: Some("nightly".into()),
+            env: Default::default(),
+        };
+        queued
+            .metadata
+            .get_or_insert_with(Default::default)
+            .insert("queued".into(), serde_json::to_value(&run).unwrap());
+        let tasks = vec![
+            task("a", &["experiment"], Some("process_stalled")),
+            task("b", &["experiment", "gpu"], Some("waiting_for_input")),
+            task("c", &["prod"], None),
+            queued,
+        ];
+        let ids = |selection: Selection| -> Vec<String> {
+            selection
+                .select(tasks.clone())
+                .into_iter()
+                .map(|t| t.task_id)
+                .collect()
+        };
+
+        assert_eq!(ids(Selection::default()).len(), 4);
+        assert_eq!(
+            ids(Selection {
+                tags: vec!["experiment".into()],
+                state: Some(State::from_str("stalled").unwrap()),
+                ..Default::default()
+            }),
+            ["a"]
+        );
+        assert_eq!(
+            ids(Selection {
+                tags: vec!["experiment".into(), "gpu".into()],
+                ..Default::default()
+            }),
+            ["b"]
+        );
+        assert_eq!(
+            ids(Selection {
+                state: Some(State::Attention),
+                ..Default::default()
+            }),
+            ["a", "b"]
+        );
+        assert_eq!(
+            ids(Selection {
+                group: Some("nightly".into()),
+                ..Default::default()
+            }),
+            ["nightly-1"]
+        );
+
+        assert_eq!(
+            State::from_str("Running"),
+            Ok(State::Status(TaskStatus::Running))
+        );
+        assert_eq!(
+            State::from_str("merge-conflict"),
+            Ok(State::Reason("merge_conflict".into()))
+        );
+        assert!(State::from_str(" ").is_err());
+    }
+}
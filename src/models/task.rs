@@ -1,13 +1,19 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+use super::progress::Progress;
 
 /// Task status - simplified to 3 states for reliability
 /// - Running: Agent is actively generating output
 /// - Completed: Agent finished generating, waiting for user input
 /// - Exited: Agent/tab closed or process terminated
+///
+/// Tasks handed to the daemon with `queue --after` additionally start out
+/// as Queued until their predecessor completes.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum TaskStatus {
+    Queued,
     Running,
     Completed,
     Exited,
@@ -16,14 +22,17 @@ pub enum TaskStatus {
 impl TaskStatus {
     pub fn as_str(&self) -> &str {
         match self {
+            TaskStatus::Queued => "queued",
             TaskStatus::Running => "running",
             TaskStatus::Completed => "completed",
             TaskStatus::Exited => "exited",
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self, String> {
         match s {
+            "queued" => Ok(TaskStatus::Queued),
             "running" => Ok(TaskStatus::Running),
             "completed" => Ok(TaskStatus::Completed),
             "exited" => Ok(TaskStatus::Exited),
@@ -35,6 +44,95 @@ impl TaskStatus {
     }
 }
 
+/// How much a task matters: critical tasks get through Focus and batching,
+/// low ones are only reported in digests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Critical,
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+            Priority::Critical => "critical",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "low" => Ok(Priority::Low),
+            "normal" => Ok(Priority::Normal),
+            "high" => Ok(Priority::High),
+            "critical" => Ok(Priority::Critical),
+            _ => Err(format!(
+                "Invalid priority: {} (low, normal, high or critical)",
+                s
+            )),
+        }
+    }
+}
+
+/// What the monitor does once an attention need has gone unanswered for
+/// a while
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnattendedAction {
+    /// Keep waiting (the default)
+    Wait,
+    /// Terminate the process
+    Kill,
+    /// Type this answer, as an auto-respond rule would
+    Respond(String),
+}
+
+impl UnattendedAction {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.split_once(':') {
+            None if s == "wait" => Ok(UnattendedAction::Wait),
+            None if s == "kill" => Ok(UnattendedAction::Kill),
+            Some(("respond", answer)) => Ok(UnattendedAction::Respond(answer.to_string())),
+            _ => Err(format!(
+                "Invalid action: {} (wait, kill or respond:<answer>)",
+                s
+            )),
+        }
+    }
+}
+
+/// `--on-unattended`: the action, taken `after_secs` after the attention
+/// notification if nobody answered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Unattended {
+    pub action: UnattendedAction,
+    pub after_secs: u64,
+}
+
+/// An attention need the user acknowledged without answering it yet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Acknowledged {
+    /// Its reason key, e.g. "waiting_for_input"
+    pub reason: String,
+    pub at: DateTime<Utc>,
+}
+
+impl Acknowledged {
+    /// Whether a new `reason` need at `now` is the acknowledged one, still
+    /// within its `cooldown`
+    pub fn covers(&self, reason: &str, cooldown: chrono::Duration, now: DateTime<Utc>) -> bool {
+        self.reason == reason && now - self.at < cooldown
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskContext {
     pub url: Option<String>,
@@ -44,6 +142,119 @@ pub struct TaskContext {
     pub extra: HashMap<String, serde_json::Value>,
 }
 
+/// Outcome of an earlier run of a re-registered task
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RunRecord {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub status: TaskStatus,
+    pub exit_code: Option<i32>,
+    pub pid: Option<i32>,
+}
+
+/// Runs kept in a task's metadata; older ones are dropped
+pub const MAX_RUN_HISTORY: usize = 20;
+
+/// A remark left on a task with `agent-inbox note`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Note {
+    pub at: DateTime<Utc>,
+    pub text: String,
+}
+
+/// Notes kept per task; older ones are dropped
+pub const MAX_NOTES: usize = 20;
+
+/// How the task's process was started, captured at registration
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProcessSnapshot {
+    pub command_line: Option<String>,
+    /// Allow-listed environment variables that were set
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+impl ProcessSnapshot {
+    pub fn is_empty(&self) -> bool {
+        self.command_line.is_none() && self.env.is_empty()
+    }
+
+    /// "MODEL=opus VIRTUAL_ENV=/srv/venv"
+    pub fn env_line(&self) -> String {
+        self.env
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// The machine and account a task runs under, captured at registration
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HostInfo {
+    pub hostname: String,
+    pub user: String,
+    /// `[notify.host] nickname`, or the machine's tailscale name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+}
+
+/// The Zellij pane a task runs in, from `$ZELLIJ_SESSION_NAME` and
+/// `$ZELLIJ_PANE_ID`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ZellijPane {
+    pub session: String,
+    pub pane_id: String,
+    /// The tab that was focused when the task registered, which is the one
+    /// its command was typed in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tab: Option<String>,
+}
+
+impl HostInfo {
+    /// The nickname when there is one, else the hostname
+    pub fn name(&self) -> &str {
+        self.nickname.as_deref().unwrap_or(&self.hostname)
+    }
+
+    /// Fill `{host}`, `{hostname}` and `{user}` into a template
+    pub fn fill(&self, template: &str) -> String {
+        template
+            .replace("{host}", self.name())
+            .replace("{hostname}", &self.hostname)
+            .replace("{user}", &self.user)
+    }
+}
+
+/// Pass/fail counts of the last test run found in a task's output
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TestResults {
+    /// "cargo", "pytest" or "jest"
+    pub framework: String,
+    pub passed: u64,
+    pub failed: u64,
+    #[serde(default)]
+    pub skipped: u64,
+}
+
+impl TestResults {
+    pub fn has_failures(&self) -> bool {
+        self.failed > 0
+    }
+
+    /// "41 passed, 2 failed, 1 skipped"
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!("{} passed", self.passed)];
+        if self.failed > 0 {
+            parts.push(format!("{} failed", self.failed));
+        }
+        if self.skipped > 0 {
+            parts.push(format!("{} skipped", self.skipped));
+        }
+        parts.join(", ")
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pub id: Option<i64>,
@@ -112,6 +323,7 @@ impl Task {
         self.status = TaskStatus::Running;
         self.completed_at = None;
         self.updated_at = Utc::now();
+        self.clear_unclear();
     }
 
     /// Mark task as exited (closed/terminated)
@@ -120,6 +332,357 @@ impl Task {
         self.exit_code = exit_code;
         self.completed_at = Some(Utc::now());
         self.updated_at = Utc::now();
+        self.clear_unclear();
+    }
+
+    /// Start a new run of an existing task (re-registration under a stable ID).
+    ///
+    /// Re-registering the same live process is a no-op. Otherwise the
+    /// previous run is appended to the run history and the task restarts as
+    /// Running with the new pid and a fresh start time. Returns true when a
+    /// new run was started.
+    pub fn restart(&mut self, pid: Option<i32>, ppid: Option<i32>) -> bool {
+        if self.status == TaskStatus::Running && self.pid == pid {
+            return false;
+        }
+
+        let record = RunRecord {
+            started_at: self.created_at,
+            ended_at: self.completed_at,
+            status: self.status.clone(),
+            exit_code: self.exit_code,
+            pid: self.pid,
+        };
+        let mut runs = self.run_history();
+        runs.push(record);
+        let excess = runs.len().saturating_sub(MAX_RUN_HISTORY);
+        runs.drain(..excess);
+
+        let metadata = self.metadata.get_or_insert_with(HashMap::new);
+        metadata.remove("progress");
+        metadata.remove("unclear");
+        metadata.remove("attention_key");
+        metadata.remove("acknowledged");
+        if let Ok(value) = serde_json::to_value(&runs) {
+            metadata.insert("runs".to_string(), value);
+        }
+
+        let now = Utc::now();
+        self.status = TaskStatus::Running;
+        self.created_at = now;
+        self.updated_at = now;
+        self.completed_at = None;
+        self.pid = pid;
+        self.ppid = ppid;
+        self.monitor_pid = None;
+        self.attention_reason = None;
+        self.exit_code = None;
+        true
+    }
+
+    /// Earlier runs of this task, oldest first
+    pub fn run_history(&self) -> Vec<RunRecord> {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.get("runs"))
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Last progress parsed from the task's output, if any
+    pub fn progress(&self) -> Option<Progress> {
+        self.metadata
+            .as_ref()?
+            .get("progress")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    /// Record parsed progress in the task metadata
+    pub fn set_progress(&mut self, progress: &Progress) {
+        if let Ok(value) = serde_json::to_value(progress) {
+            self.metadata
+                .get_or_insert_with(HashMap::new)
+                .insert("progress".to_string(), value);
+        }
+    }
+
+    /// When someone last typed into the task's terminal, as noticed by its
+    /// monitor
+    pub fn last_input(&self) -> Option<DateTime<Utc>> {
+        self.metadata
+            .as_ref()?
+            .get("last_input")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    pub fn set_last_input(&mut self, at: DateTime<Utc>) {
+        if let Ok(value) = serde_json::to_value(at) {
+            self.metadata
+                .get_or_insert_with(HashMap::new)
+                .insert("last_input".to_string(), value);
+        }
+    }
+
+    /// Notes on the task, oldest first
+    pub fn notes(&self) -> Vec<Note> {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.get("notes"))
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn add_note(&mut self, text: &str) {
+        let mut notes = self.notes();
+        notes.push(Note {
+            at: Utc::now(),
+            text: text.to_string(),
+        });
+        let excess = notes.len().saturating_sub(MAX_NOTES);
+        notes.drain(..excess);
+        if let Ok(value) = serde_json::to_value(&notes) {
+            self.metadata
+                .get_or_insert_with(HashMap::new)
+                .insert("notes".to_string(), value);
+        }
+    }
+
+    pub fn clear_notes(&mut self) {
+        if let Some(metadata) = self.metadata.as_mut() {
+            metadata.remove("notes");
+        }
+    }
+
+    /// Command line and environment of the current run's process
+    pub fn process(&self) -> Option<ProcessSnapshot> {
+        self.metadata
+            .as_ref()?
+            .get("process")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    /// Record the process snapshot; an empty one clears the previous run's
+    pub fn set_process(&mut self, snapshot: &ProcessSnapshot) {
+        if snapshot.is_empty() {
+            if let Some(metadata) = self.metadata.as_mut() {
+                metadata.remove("process");
+            }
+        } else if let Ok(value) = serde_json::to_value(snapshot) {
+            self.metadata
+                .get_or_insert_with(HashMap::new)
+                .insert("process".to_string(), value);
+        }
+    }
+
+    /// Normal unless set with `--priority`
+    pub fn priority(&self) -> Priority {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.get("priority"))
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn set_priority(&mut self, priority: Priority) {
+        if let Ok(value) = serde_json::to_value(priority) {
+            self.metadata
+                .get_or_insert_with(HashMap::new)
+                .insert("priority".to_string(), value);
+        }
+    }
+
+    /// Seconds after starting during which the task isn't flagged as
+    /// stalled or blocked, when set for this task
+    pub fn startup_grace(&self) -> Option<u64> {
+        self.metadata.as_ref()?.get("startup_grace_secs")?.as_u64()
+    }
+
+    pub fn set_startup_grace(&mut self, secs: u64) {
+        self.metadata
+            .get_or_insert_with(HashMap::new)
+            .insert("startup_grace_secs".to_string(), serde_json::json!(secs));
+    }
+
+    /// Where the task was registered
+    pub fn host(&self) -> Option<HostInfo> {
+        self.metadata
+            .as_ref()?
+            .get("host")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    pub fn set_host(&mut self, host: &HostInfo) {
+        if let Ok(value) = serde_json::to_value(host) {
+            self.metadata
+                .get_or_insert_with(HashMap::new)
+                .insert("host".to_string(), value);
+        }
+    }
+
+    /// Test counts parsed from the output when the last run finished
+    pub fn test_results(&self) -> Option<TestResults> {
+        self.metadata
+            .as_ref()?
+            .get("test_results")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    /// Record the run's test counts; None clears the previous run's
+    pub fn set_test_results(&mut self, results: Option<&TestResults>) {
+        match results.and_then(|r| serde_json::to_value(r).ok()) {
+            Some(value) => {
+                self.metadata
+                    .get_or_insert_with(HashMap::new)
+                    .insert("test_results".to_string(), value);
+            }
+            None => {
+                if let Some(metadata) = self.metadata.as_mut() {
+                    metadata.remove("test_results");
+                }
+            }
+        }
+    }
+
+    /// tmux pane the task runs in, where replies can be typed
+    pub fn tmux_pane(&self) -> Option<&str> {
+        self.metadata.as_ref()?.get("tmux_pane")?.as_str()
+    }
+
+    pub fn set_tmux_pane(&mut self, pane: &str) {
+        self.metadata
+            .get_or_insert_with(HashMap::new)
+            .insert("tmux_pane".to_string(), serde_json::Value::from(pane));
+    }
+
+    /// Zellij pane running the task, recorded from its environment
+    pub fn zellij_pane(&self) -> Option<ZellijPane> {
+        self.metadata
+            .as_ref()?
+            .get("zellij")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    pub fn set_zellij_pane(&mut self, pane: &ZellijPane) {
+        if let Ok(value) = serde_json::to_value(pane) {
+            self.metadata
+                .get_or_insert_with(HashMap::new)
+                .insert("zellij".to_string(), value);
+        }
+    }
+
+    /// Shell command that brings the task's terminal to the front, run by
+    /// `focus` and "Open Terminal" instead of looking for a tmux pane or
+    /// Zellij tab
+    pub fn focus_command(&self) -> Option<&str> {
+        self.metadata.as_ref()?.get("focus_cmd")?.as_str()
+    }
+
+    pub fn set_focus_command(&mut self, command: &str) {
+        self.metadata
+            .get_or_insert_with(HashMap::new)
+            .insert("focus_cmd".to_string(), serde_json::Value::from(command));
+    }
+
+    /// Labels given with `--tag`, for `[[rules]]` to match on
+    pub fn tags(&self) -> Vec<String> {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.get("tags"))
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn set_tags(&mut self, tags: &[String]) {
+        self.metadata
+            .get_or_insert_with(HashMap::new)
+            .insert("tags".to_string(), serde_json::Value::from(tags));
+    }
+
+    /// What to do when an attention need goes unanswered
+    pub fn unattended(&self) -> Option<Unattended> {
+        self.metadata
+            .as_ref()?
+            .get("unattended")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    pub fn set_unattended(&mut self, unattended: &Unattended) {
+        if let Ok(value) = serde_json::to_value(unattended) {
+            self.metadata
+                .get_or_insert_with(HashMap::new)
+                .insert("unattended".to_string(), value);
+        }
+    }
+
+    /// Reason key of the current attention need, when whoever raised it
+    /// recorded one
+    pub fn attention_key(&self) -> Option<&str> {
+        self.metadata.as_ref()?.get("attention_key")?.as_str()
+    }
+
+    /// Flag the task for attention: `key` is the reason's kind, `message`
+    /// what `attention_reason` shows
+    pub fn set_attention(&mut self, key: &str, message: String) {
+        self.attention_reason = Some(message);
+        self.metadata
+            .get_or_insert_with(HashMap::new)
+            .insert("attention_key".to_string(), serde_json::Value::from(key));
+    }
+
+    /// Clear the attention need as seen, remembering its kind so the same
+    /// need isn't notified again right away. False when there was none.
+    pub fn acknowledge(&mut self, now: DateTime<Utc>) -> bool {
+        if self.attention_reason.take().is_none() {
+            return false;
+        }
+        let metadata = self.metadata.get_or_insert_with(HashMap::new);
+        if let Some(key) = metadata
+            .remove("attention_key")
+            .and_then(|v| v.as_str().map(str::to_string))
+        {
+            let acknowledged = Acknowledged {
+                reason: key,
+                at: now,
+            };
+            if let Ok(value) = serde_json::to_value(&acknowledged) {
+                metadata.insert("acknowledged".to_string(), value);
+            }
+        }
+        true
+    }
+
+    /// The last attention need acknowledged, until the task shows activity
+    pub fn acknowledged(&self) -> Option<Acknowledged> {
+        self.metadata
+            .as_ref()?
+            .get("acknowledged")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    /// Forget the acknowledgement, once the task moved on. True when there
+    /// was one.
+    pub fn clear_acknowledged(&mut self) -> bool {
+        self.metadata
+            .as_mut()
+            .is_some_and(|metadata| metadata.remove("acknowledged").is_some())
+    }
+
+    /// Why the daemon, taking stock at startup, couldn't tell whether the
+    /// task's process is still the task; set until its state is reported
+    pub fn unclear(&self) -> Option<&str> {
+        self.metadata.as_ref()?.get("unclear")?.as_str()
+    }
+
+    pub fn set_unclear(&mut self, reason: &str) {
+        self.metadata
+            .get_or_insert_with(HashMap::new)
+            .insert("unclear".to_string(), serde_json::Value::from(reason));
+    }
+
+    fn clear_unclear(&mut self) {
+        if let Some(metadata) = self.metadata.as_mut() {
+            metadata.remove("unclear");
+        }
     }
 }
 
@@ -206,11 +769,81 @@ mod tests {
         assert!(task.completed_at.is_none());
     }
 
+    #[test]
+    fn test_progress_metadata_roundtrip() {
+        let mut task = Task::new(
+            "test-id".to_string(),
+            "claude_code".to_string(),
+            "Test task".to_string(),
+            None,
+            None,
+        );
+        assert!(task.progress().is_none());
+
+        let progress = Progress {
+            fraction: 0.3,
+            label: "3/10".to_string(),
+            eta_secs: Some(120),
+            updated_at: Utc::now(),
+        };
+        task.set_progress(&progress);
+        assert_eq!(task.progress(), Some(progress));
+    }
+
+    #[test]
+    fn test_restart_archives_previous_run() {
+        let mut task = Task::new(
+            "nightly".to_string(),
+            "cron".to_string(),
+            "Nightly".to_string(),
+            Some(100),
+            None,
+        );
+
+        // Same live process registering again changes nothing
+        assert!(!task.restart(Some(100), None));
+        assert!(task.run_history().is_empty());
+
+        task.set_exited(Some(2));
+        assert!(task.restart(Some(200), Some(1)));
+        assert_eq!(task.status, TaskStatus::Running);
+        assert_eq!(task.pid, Some(200));
+        assert!(task.exit_code.is_none());
+
+        let runs = task.run_history();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].exit_code, Some(2));
+        assert_eq!(runs[0].pid, Some(100));
+
+        for pid in 0..MAX_RUN_HISTORY as i32 {
+            task.complete();
+            task.restart(Some(pid), None);
+        }
+        assert_eq!(task.run_history().len(), MAX_RUN_HISTORY);
+    }
+
+    #[test]
+    fn test_notes() {
+        let mut task = Task::new("t".into(), "claude_code".into(), "Test".into(), None, None);
+        for i in 0..MAX_NOTES + 2 {
+            task.add_note(&format!("note {}", i));
+        }
+        let notes = task.notes();
+        assert_eq!(notes.len(), MAX_NOTES);
+        assert_eq!(
+            notes.last().unwrap().text,
+            format!("note {}", MAX_NOTES + 1)
+        );
+        task.clear_notes();
+        assert!(task.notes().is_empty());
+    }
+
     #[test]
     fn test_status_serialization() {
         assert_eq!(TaskStatus::Running.as_str(), "running");
         assert_eq!(TaskStatus::Completed.as_str(), "completed");
         assert_eq!(TaskStatus::Exited.as_str(), "exited");
+        assert_eq!(TaskStatus::Queued.as_str(), "queued");
     }
 
     #[test]
@@ -0,0 +1,180 @@
+//! What /proc shows of a monitored process
+//!
+//! Inside containers /proc may be missing, mounted with `hidepid` so other
+//! users' processes aren't listed, or refuse to show another process's
+//! open files without ptrace rights. Rather than every probe quietly coming
+//! back empty, the monitor checks at the start of each run what it can read
+//! of the process, says what it can't, and works around it:
+//!
+//! - not listed: whether the process lives is asked of the kernel with
+//!   `kill(pid, 0)` instead
+//! - no `stat`: its CPU time is unknown, so stalls go by output silence,
+//!   counted from the start
+//! - no `fd` links: its terminal is unknown, so `TerminalLost` is off,
+//!   keystrokes don't count as activity and password prompts are only
+//!   recognised in the output
+//!
+//! The gaps are printed when the run starts and written to the task's
+//! trace; `agent-inbox doctor` reports them for the machine as a whole.
+
+#[cfg(not(any(target_os = "freebsd", target_os = "openbsd")))]
+use std::path::Path;
+
+/// What of a process can be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    /// It has a /proc entry
+    pub listed: bool,
+    /// Its state and CPU time can be read
+    pub stat: bool,
+    /// Its open files, and so its terminal, can be read
+    pub fds: bool,
+}
+
+impl Access {
+    pub const FULL: Access = Access {
+        listed: true,
+        stat: true,
+        fds: true,
+    };
+
+    /// What /proc lets this process read of `pid`
+    #[cfg(not(any(target_os = "freebsd", target_os = "openbsd")))]
+    pub fn of(pid: i32) -> Self {
+        Self {
+            listed: Path::new(&format!("/proc/{}", pid)).exists(),
+            stat: std::fs::read_to_string(format!("/proc/{}/stat", pid)).is_ok(),
+            fds: std::fs::read_dir(format!("/proc/{}/fd", pid)).is_ok(),
+        }
+    }
+
+    /// The BSDs ask the kernel with sysctl, which has no such gaps
+    #[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+    pub fn of(_pid: i32) -> Self {
+        Self::FULL
+    }
+
+    /// What can't be read, most serious first
+    pub fn gaps(&self) -> Vec<Gap> {
+        let mut gaps = Vec::new();
+        if !self.listed {
+            gaps.push(Gap::Unlisted);
+        }
+        if !self.stat {
+            gaps.push(Gap::Stat);
+        }
+        if !self.fds {
+            gaps.push(Gap::Fds);
+        }
+        gaps
+    }
+}
+
+/// Something /proc won't show of a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gap {
+    Unlisted,
+    Stat,
+    Fds,
+}
+
+impl Gap {
+    /// What is missing and what the monitor does instead
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Gap::Unlisted => "not listed in /proc; checking that it lives with kill(2)",
+            Gap::Stat => "can't read its CPU time; stalls go by output silence only",
+            Gap::Fds => {
+                "can't read its open files; terminal loss, keystrokes and echo-off password reads go unseen"
+            }
+        }
+    }
+
+    /// Detectors that can't work without it, by [`name`](super::detectors::AttentionDetector::name)
+    pub fn disables(&self) -> &'static [&'static str] {
+        match self {
+            Gap::Fds => &["TerminalLost"],
+            Gap::Unlisted | Gap::Stat => &[],
+        }
+    }
+}
+
+/// How /proc is mounted, if that hides processes: `hidepid=2`,
+/// `subset=pid`. None when it hides nothing or can't be told.
+pub fn restriction() -> Option<String> {
+    let mounts = std::fs::read_to_string("/proc/mounts").ok()?;
+    mount_restriction(&mounts)
+}
+
+fn mount_restriction(mounts: &str) -> Option<String> {
+    let options = mounts.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        (fields.get(1) == Some(&"/proc") && fields.get(2) == Some(&"proc"))
+            .then(|| fields.get(3).copied())?
+    })?;
+    let hiding: Vec<&str> = options
+        .split(',')
+        .filter(|option| match option.split_once('=') {
+            Some(("hidepid", value)) => !matches!(value, "0" | "off"),
+            Some(("subset", _)) => true,
+            _ => false,
+        })
+        .collect();
+    (!hiding.is_empty()).then(|| hiding.join(","))
+}
+
+/// What `doctor` reports: how /proc is mounted, and the gaps in what this
+/// process can read of its parent, standing in for a task. Empty when
+/// nothing is missing.
+pub fn system() -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(options) = restriction() {
+        problems.push(format!(
+            "/proc is mounted with {}: other users' tasks are hidden",
+            options
+        ));
+    }
+    #[cfg(unix)]
+    {
+        // SAFETY: getppid(2) has no memory effects
+        let parent = unsafe { libc::getppid() };
+        problems.extend(
+            Access::of(parent)
+                .gaps()
+                .iter()
+                .map(|gap| gap.describe().to_string()),
+        );
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_gaps() {
+        assert!(Access::FULL.gaps().is_empty());
+        if cfg!(target_os = "linux") {
+            assert!(Access::of(std::process::id() as i32).gaps().is_empty());
+        }
+        let hidden = Access {
+            listed: false,
+            stat: false,
+            fds: false,
+        };
+        assert_eq!(hidden.gaps(), [Gap::Unlisted, Gap::Stat, Gap::Fds]);
+        assert_eq!(Gap::Fds.disables(), ["TerminalLost"]);
+
+        let mounts = "sysfs /sys sysfs rw 0 0\nproc /proc proc rw,nosuid,relatime,hidepid=invisible,subset=pid 0 0\n";
+        assert_eq!(
+            mount_restriction(mounts).as_deref(),
+            Some("hidepid=invisible,subset=pid")
+        );
+        assert_eq!(
+            mount_restriction("proc /proc proc rw,hidepid=0 0 0\n"),
+            None
+        );
+        assert_eq!(mount_restriction("proc /proc proc rw,relatime 0 0\n"), None);
+    }
+}
@@ -0,0 +1,91 @@
+//! Terminal escape handling for captured output
+//!
+//! Agents and build tools color their output and move the cursor around, so
+//! a raw log line can read `\x1b[1;31merror\x1b[0m: ...`. Detectors, progress
+//! parsing, summaries and notification text all work on the text [`strip`]
+//! returns; the log on disk keeps the raw stream for replaying in a terminal.
+
+/// Remove escape sequences and control characters from terminal output.
+///
+/// - CSI sequences (colors, erase, cursor movement) are dropped, except
+///   cursor-forward, which becomes spaces since TUIs use it for padding
+/// - OSC sequences (window titles, hyperlink targets) are dropped; the text
+///   of a hyperlink stays
+/// - a backspace erases the previous character, undoing overstrike
+/// - other control characters are dropped; tabs and line breaks are kept
+///
+/// An escape sequence cut off at the end of `text` is dropped as well.
+pub fn strip(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                Some('[') => csi(&mut chars, &mut out),
+                Some(']' | 'P' | 'X' | '^' | '_') => string_sequence(&mut chars),
+                // Two-character escapes, possibly with intermediates (ESC ( B)
+                Some(' '..='/') => {
+                    while chars.next_if(|c| (' '..='/').contains(c)).is_some() {}
+                    chars.next();
+                }
+                _ => {}
+            },
+            '\u{9b}' => csi(&mut chars, &mut out),
+            '\x08' => {
+                out.pop();
+            }
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Consume a CSI sequence after its introducer
+fn csi(chars: &mut std::iter::Peekable<std::str::Chars>, out: &mut String) {
+    let mut params = String::new();
+    while let Some(c) = chars.next_if(|c| ('0'..='?').contains(c)) {
+        params.push(c);
+    }
+    while chars.next_if(|c| (' '..='/').contains(c)).is_some() {}
+    if chars.next() == Some('C') {
+        let n = params.parse::<usize>().unwrap_or(1).min(80);
+        out.extend(std::iter::repeat_n(' ', n));
+    }
+}
+
+/// Consume an OSC/DCS-style sequence, terminated by BEL or ESC \
+fn string_sequence(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\x07' | '\u{9c}' => return,
+            '\x1b' => {
+                chars.next_if_eq(&'\\');
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip() {
+        assert_eq!(strip("\x1b[1;31merror\x1b[0m: failed"), "error: failed");
+        assert_eq!(strip("\x1b[2K\rBuilding [3/10]"), "\rBuilding [3/10]");
+        assert_eq!(strip("a\x1b[3Cb"), "a   b");
+        assert_eq!(strip("\x1b]0;claude\x07Continue? [y/n]"), "Continue? [y/n]");
+        assert_eq!(
+            strip("see \x1b]8;;https://example.com\x1b\\docs\x1b]8;;\x1b\\"),
+            "see docs"
+        );
+        assert_eq!(strip("\x1b(Bplain\x1b="), "plain");
+        assert_eq!(strip("N\x08NAME\x07\tx"), "NAME\tx");
+        assert_eq!(strip("42%\x1b[3"), "42%");
+    }
+}
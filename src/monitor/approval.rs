@@ -0,0 +1,214 @@
+//! Tool-approval prompts
+//!
+//! Agents ask before they run a command or touch a file. Claude Code draws
+//! a menu ("Do you want to proceed?", "Allow this tool?") with numbered
+//! options, "1. Yes" through "3. No, and tell Claude what to do
+//! differently"; aider asks "Run shell command? (Y)es/(N)o/(D)on't ask
+//! again [Yes]:". [`parse`] recognizes both in the question a task waits
+//! on, and picks out the action being asked about and the keys that
+//! approve and deny it.
+//!
+//! The monitor raises [`WaitingApproval`](super::detectors::AttentionReason)
+//! for them and keeps the pending approval in the task's metadata, where
+//! `agent-inbox respond --approve/--deny` and the Approve and Deny actions
+//! of interactive notifications find the keys to type.
+
+use crate::models::Task;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Metadata key of the approval a task waits on
+const PENDING: &str = "approval";
+
+/// Lines of the action kept for the notification
+const MAX_ACTION_LINES: usize = 4;
+
+/// An approval prompt and how to answer it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Approval {
+    /// What the agent wants to do: the command, the edit, the tool call
+    pub action: String,
+    /// Typed to approve
+    pub approve: String,
+    /// Typed to deny
+    pub deny: String,
+}
+
+impl Approval {
+    /// The approval `task` waits on, if any
+    pub fn pending(task: &Task) -> Option<Self> {
+        let value = task.metadata.as_ref()?.get(PENDING)?;
+        serde_json::from_value(value.clone()).ok()
+    }
+
+    /// Record the approval `task` waits on, or clear it with None
+    pub fn set_pending(task: &mut Task, approval: Option<&Approval>) {
+        let metadata = task.metadata.get_or_insert_with(Default::default);
+        match approval.and_then(|a| serde_json::to_value(a).ok()) {
+            Some(value) => {
+                metadata.insert(PENDING.to_string(), value);
+            }
+            None => {
+                metadata.remove(PENDING);
+            }
+        }
+    }
+}
+
+/// The approval `question` asks for, if it is an approval prompt.
+/// `question` is the trailing block of output, as [`super::prompt::extract`]
+/// cuts it.
+pub fn parse(question: &str) -> Option<Approval> {
+    let lines: Vec<&str> = question
+        .lines()
+        .map(|line| line.trim().trim_matches(BORDERS).trim())
+        .filter(|line| !line.is_empty() && !line.starts_with(CORNERS))
+        .collect();
+    menu(&lines).or_else(|| yes_no(&lines))
+}
+
+const BORDERS: [char; 4] = ['│', '┃', '║', '|'];
+const CORNERS: [char; 8] = ['╭', '┌', '╔', '┏', '╰', '└', '╚', '┗'];
+
+/// A question followed by numbered options, Claude Code style
+fn menu(lines: &[&str]) -> Option<Approval> {
+    static QUESTION: OnceLock<Regex> = OnceLock::new();
+    static OPTION: OnceLock<Regex> = OnceLock::new();
+    let question = QUESTION.get_or_init(|| {
+        Regex::new(r"(?i)^(do you want to (.+)|allow this (tool|command|action).*|would you like to (.+))\?$").unwrap()
+    });
+    let option = OPTION.get_or_init(|| Regex::new(r"^(?:[❯›>]\s*)?(\d)\.\s+(.+)$").unwrap());
+
+    let at = lines.iter().rposition(|line| question.is_match(line))?;
+    let options: Vec<(&str, &str)> = lines[at + 1..]
+        .iter()
+        .map_while(|line| option.captures(line))
+        .map(|caps| (caps.get(1).unwrap().as_str(), caps.get(2).unwrap().as_str()))
+        .collect();
+    // Anything after the options means the menu is gone
+    if options.is_empty() || at + 1 + options.len() != lines.len() {
+        return None;
+    }
+    let key = |answer: &str| {
+        options
+            .iter()
+            .find(|(_, text)| text.starts_with(answer))
+            .map(|(number, _)| number.to_string())
+    };
+    let caps = question.captures(lines[at])?;
+    // "Do you want to make this edit to main.rs?" says it all;
+    // "Do you want to proceed?" refers to what is shown above it
+    let asked = caps
+        .get(2)
+        .or(caps.get(4))
+        .map(|m| m.as_str())
+        .filter(|asked| {
+            !matches!(
+                asked.to_lowercase().as_str(),
+                "proceed" | "continue" | "allow this"
+            )
+        });
+    Some(Approval {
+        action: match asked {
+            Some(asked) => capitalize(asked),
+            None => shown_above(&lines[..at])?,
+        },
+        approve: key("Yes")?,
+        deny: key("No")?,
+    })
+}
+
+/// A question ending in "(Y)es/(N)o", aider style
+fn yes_no(lines: &[&str]) -> Option<Approval> {
+    static QUESTION: OnceLock<Regex> = OnceLock::new();
+    let question = QUESTION.get_or_init(|| {
+        Regex::new(r"^(.+?)\?\s*\(Y\)es/\(N\)o\b.*(\[(Yes|No)\]:|\(Y/n\)|\(y/N\))$").unwrap()
+    });
+    let (last, above) = lines.split_last()?;
+    let caps = question.captures(last)?;
+    let asked = &caps[1];
+    // aider prints the command it wants to run just before asking; the
+    // lines before that may be anything
+    let action = match above.last() {
+        Some(command) if asked.to_lowercase().starts_with("run shell command") => {
+            command.to_string()
+        }
+        _ => asked.to_string(),
+    };
+    Some(Approval {
+        action,
+        approve: "y".to_string(),
+        deny: "n".to_string(),
+    })
+}
+
+/// The last few lines before the question
+fn shown_above(lines: &[&str]) -> Option<String> {
+    let start = lines.len().saturating_sub(MAX_ACTION_LINES);
+    let action = lines[start..].join("\n");
+    (!action.is_empty()).then_some(action)
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_approval_prompts() {
+        let claude = "╭──────────────────────────────────╮\n\
+                      │ Bash command                     │\n\
+                      │                                  │\n\
+                      │   cargo test --workspace         │\n\
+                      │   Run the test suite             │\n\
+                      │                                  │\n\
+                      │ Do you want to proceed?          │\n\
+                      │ ❯ 1. Yes                         │\n\
+                      │   2. Yes, and don't ask again    │\n\
+                      │   3. No, and tell Claude what to do differently (esc) │\n\
+                      ╰──────────────────────────────────╯";
+        assert_eq!(
+            parse(claude),
+            Some(Approval {
+                action: "Bash command\ncargo test --workspace\nRun the test suite".to_string(),
+                approve: "1".to_string(),
+                deny: "3".to_string(),
+            })
+        );
+
+        let edit = "Do you want to make this edit to src/main.rs?\n❯ 1. Yes\n  2. No";
+        assert_eq!(parse(edit).unwrap().action, "Make this edit to src/main.rs");
+
+        let aider = "pytest -x tests/\nRun shell command? (Y)es/(N)o/(D)on't ask again [Yes]:";
+        let approval = parse(aider).unwrap();
+        assert_eq!(approval.action, "pytest -x tests/");
+        assert_eq!(
+            (approval.approve.as_str(), approval.deny.as_str()),
+            ("y", "n")
+        );
+        let add = "Add src/lib.rs to the chat? (Y)es/(N)o/(D)on't ask again [Yes]:";
+        assert_eq!(parse(add).unwrap().action, "Add src/lib.rs to the chat");
+
+        // Answered, or not asking for approval
+        assert_eq!(parse("Run shell command? (Y)es/(N)o [Yes]: y"), None);
+        assert_eq!(
+            parse("Do you want to proceed?\n1. Yes\n2. No\nRunning…"),
+            None
+        );
+        assert_eq!(parse("Continue? [y/N]"), None);
+
+        let mut task = Task::new("t".into(), "claude_code".into(), "t".into(), None, None);
+        Approval::set_pending(&mut task, parse(edit).as_ref());
+        assert_eq!(Approval::pending(&task), parse(edit));
+        Approval::set_pending(&mut task, None);
+        assert_eq!(Approval::pending(&task), None);
+    }
+}
@@ -0,0 +1,269 @@
+//! What watching tasks costs: `agent-inbox bench detectors`
+//!
+//! Starts `tasks` synthetic processes, half of them printing and burning a
+//! little CPU and half asleep, and runs `ticks` checks on each with the
+//! real probe: the process's CPU time, password state, terminal and input
+//! mark (the /proc scans), then every detector the configured monitor
+//! runs, fed a few lines of compiler-ish output. Each task gets its own
+//! detectors, as each monitored task does.
+//!
+//! Every probe, detector and whole check is timed, along with the CPU this
+//! process used while checking, so a slower /proc path shows up as numbers
+//! before a release rather than as a warm laptop after it.
+
+use super::detectors::{AttentionDetector, TaskContext};
+use super::probe::ProcessProbe;
+use crate::models::Task;
+use anyhow::{Context, Result};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Pause between ticks, so busy processes move on
+const TICK_GAP: Duration = Duration::from_millis(50);
+
+/// Output the detectors are fed, a line per check in turn
+const OUTPUT: [&str; 6] = [
+    "   Compiling app v0.1.0 (/src/app)",
+    "test result: ok. 42 passed; 0 failed",
+    "error[E0308]: mismatched types",
+    "Step 3/10: running migrations",
+    "Retrying in 5s...",
+    "warning: unused variable: `x`",
+];
+
+/// Times measured for one stage of a check
+#[derive(Debug, Clone, Default)]
+pub struct Timing {
+    pub name: String,
+    samples: Vec<Duration>,
+}
+
+impl Timing {
+    fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            samples: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, sample: Duration) {
+        self.samples.push(sample);
+    }
+
+    pub fn count(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.samples.len() {
+            0 => Duration::ZERO,
+            n => self.samples.iter().sum::<Duration>() / n as u32,
+        }
+    }
+
+    /// The sample `p` (0.0 to 1.0) of the way up, nearest rank
+    pub fn percentile(&self, p: f64) -> Duration {
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        let rank = ((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len().max(1));
+        sorted.get(rank - 1).copied().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples.iter().max().copied().unwrap_or_default()
+    }
+}
+
+/// What a run measured
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub tasks: usize,
+    pub ticks: usize,
+    /// Reading the process's state through the probe, per check
+    pub probe: Timing,
+    /// Each detector, per check, in the order the monitor asks them
+    pub detectors: Vec<Timing>,
+    /// A whole check of one task: probe and every detector
+    pub check: Timing,
+    /// Checking every task once
+    pub tick: Timing,
+    /// Time spent checking, pauses between ticks left out
+    pub busy: Duration,
+    /// CPU this process used while checking; None where it can't be read
+    pub cpu: Option<Duration>,
+}
+
+impl Report {
+    /// CPU used as a share of the time spent checking
+    pub fn cpu_share(&self) -> Option<f64> {
+        let cpu = self.cpu?;
+        (!self.busy.is_zero()).then(|| cpu.as_secs_f64() / self.busy.as_secs_f64())
+    }
+}
+
+/// The synthetic processes, killed when dropped
+struct Synthetic(Vec<Child>);
+
+impl Synthetic {
+    fn spawn(count: usize) -> Result<Self> {
+        let mut children = Synthetic(Vec::with_capacity(count));
+        for i in 0..count {
+            let script = if i % 2 == 0 {
+                "while :; do echo step; i=0; while [ $i -lt 500 ]; do i=$((i+1)); done; sleep 0.1; done"
+            } else {
+                "sleep 3600"
+            };
+            let child = Command::new("sh")
+                .args(["-c", script])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .context("Failed to start a synthetic process")?;
+            children.0.push(child);
+        }
+        Ok(children)
+    }
+
+    fn pids(&self) -> Vec<i32> {
+        self.0.iter().map(|child| child.id() as i32).collect()
+    }
+}
+
+impl Drop for Synthetic {
+    fn drop(&mut self) {
+        for child in &mut self.0 {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// CPU time this process has used, user and system
+#[cfg(unix)]
+fn cpu_used() -> Option<Duration> {
+    // SAFETY: rusage is plain data, and getrusage only writes to it
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+    let time = |tv: libc::timeval| Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000);
+    Some(time(usage.ru_utime) + time(usage.ru_stime))
+}
+
+#[cfg(not(unix))]
+fn cpu_used() -> Option<Duration> {
+    None
+}
+
+/// Check `tasks` synthetic processes `ticks` times each through `probe`,
+/// with the detectors `detectors` makes for every task
+pub fn run(
+    probe: &dyn ProcessProbe,
+    detectors: impl Fn() -> Result<Vec<Box<dyn AttentionDetector>>>,
+    tasks: usize,
+    ticks: usize,
+) -> Result<Report> {
+    anyhow::ensure!(
+        tasks > 0 && ticks > 0,
+        "--tasks and --ticks must be at least 1"
+    );
+    let processes = Synthetic::spawn(tasks)?;
+    let watched: Vec<(Task, Vec<Box<dyn AttentionDetector>>)> = processes
+        .pids()
+        .into_iter()
+        .map(|pid| {
+            let task = Task::new(
+                format!("bench-{}", pid),
+                "bench".into(),
+                "Synthetic".into(),
+                Some(pid),
+                None,
+            );
+            Ok((task, detectors()?))
+        })
+        .collect::<Result<_>>()?;
+    let mut report = Report {
+        tasks,
+        ticks,
+        probe: Timing::new("probe"),
+        detectors: watched[0].1.iter().map(|d| Timing::new(d.name())).collect(),
+        check: Timing::new("check"),
+        tick: Timing::new("tick"),
+        busy: Duration::ZERO,
+        cpu: None,
+    };
+
+    let mut cpu = Duration::ZERO;
+    for tick in 0..ticks {
+        let cpu_before = cpu_used();
+        let tick_start = Instant::now();
+        for (task, detectors) in &watched {
+            let pid = task.pid.unwrap_or_default();
+            let check_start = Instant::now();
+            let mut context = TaskContext::new(pid, SystemTime::now());
+            context.last_cpu_time = probe.cpu_time(pid);
+            context.echo_off = probe.reading_password(pid);
+            let _ = probe.input_mark(pid);
+            let _ = probe.terminal(pid);
+            let _ = probe.is_alive(pid);
+            context.new_output = vec![OUTPUT[tick % OUTPUT.len()].to_string()];
+            report.probe.add(check_start.elapsed());
+            // Every detector, not just up to the first that fires
+            for (detector, timing) in detectors.iter().zip(&mut report.detectors) {
+                let start = Instant::now();
+                let _ = detector.check(task, &context);
+                timing.add(start.elapsed());
+            }
+            report.check.add(check_start.elapsed());
+        }
+        let elapsed = tick_start.elapsed();
+        report.tick.add(elapsed);
+        report.busy += elapsed;
+        if let (Some(before), Some(after)) = (cpu_before, cpu_used()) {
+            cpu += after.saturating_sub(before);
+            report.cpu = Some(cpu);
+        }
+        std::thread::sleep(TICK_GAP);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::detectors::StallDetector;
+    use crate::monitor::probe::ProcProbe;
+
+    #[test]
+    fn test_bench() {
+        let samples = |millis: &[u64]| {
+            let mut timing = Timing::new("t");
+            for &ms in millis {
+                timing.add(Duration::from_millis(ms));
+            }
+            timing
+        };
+        let timing = samples(&[5, 1, 3, 2, 4]);
+        assert_eq!(timing.mean(), Duration::from_millis(3));
+        assert_eq!(timing.percentile(0.5), Duration::from_millis(3));
+        assert_eq!(timing.percentile(0.95), Duration::from_millis(5));
+        assert_eq!(timing.max(), Duration::from_millis(5));
+        assert_eq!(samples(&[]).percentile(0.95), Duration::ZERO);
+
+        let detectors = || -> Result<Vec<Box<dyn AttentionDetector>>> {
+            Ok(vec![Box::new(StallDetector::new(Duration::from_secs(60)))])
+        };
+        let report = run(&ProcProbe, detectors, 2, 3).unwrap();
+        assert_eq!(report.detectors[0].name, "Stall");
+        assert_eq!(
+            (
+                report.detectors[0].count(),
+                report.check.count(),
+                report.tick.count()
+            ),
+            (6, 6, 3)
+        );
+        assert!(report.tick.max() >= report.check.max());
+    }
+}
@@ -0,0 +1,420 @@
+//! Output capture for processes the daemon starts itself
+//!
+//! The child's stdout and stderr are pipes read by background threads.
+//! Because every byte passes through here, the time of the last output is
+//! exact rather than inferred from the log at poll intervals.
+//!
+//! Capture never slows the child down, however much it prints:
+//!
+//! - reader threads only move bytes into a bounded in-memory buffer; when the
+//!   buffer is full the bytes are dropped and counted instead of blocking
+//! - anything typed after a password prompt is masked on the way in (see
+//!   [`super::redact`]), so it never reaches the disk
+//! - a writer thread appends the buffer to the task's log, marking gaps
+//! - once the log reaches `chunk_bytes` it is compressed into a numbered
+//!   `.gz` chunk next to it and a fresh log is started; only the newest
+//!   `keep_chunks` chunks are kept
+//! - with `record`, the writer also adds each chunk, stamped with the time
+//!   it was read, to a session recording (see [`super::cast`])
+
+use super::cast::{self, Recorder};
+use super::redact::Redactor;
+use crate::config::CaptureConfig;
+use crate::models::Task;
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Counters describing a capture, kept in the task's metadata
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CaptureStats {
+    /// Bytes read from the child
+    pub bytes_read: u64,
+    /// Bytes dropped because the buffer was full
+    pub bytes_dropped: u64,
+    /// Bytes written to the log (and spilled chunks)
+    pub bytes_written: u64,
+    /// Log chunks compressed to disk
+    pub chunks_spilled: u64,
+}
+
+/// State shared between the reader threads, the writer thread and handles
+#[derive(Default)]
+struct Buffer {
+    /// Output with when it was read, since the start
+    chunks: VecDeque<(Duration, Vec<u8>)>,
+    len: usize,
+    /// Bytes dropped since the writer last marked a gap
+    gap: u64,
+    open_streams: usize,
+    writer_done: bool,
+    last_output: Option<SystemTime>,
+    stats: CaptureStats,
+}
+
+/// Handle on a running capture. Clones share the same state.
+#[derive(Clone)]
+pub struct OutputCapture {
+    log_path: PathBuf,
+    started: SystemTime,
+    shared: Arc<(Mutex<Buffer>, Condvar)>,
+}
+
+impl OutputCapture {
+    /// Start copying the child's piped stdout and stderr into `log_path`
+    /// (truncated first)
+    pub fn start(child: &mut Child, log_path: &Path, config: &CaptureConfig) -> Result<Self> {
+        let log = File::create(log_path)
+            .with_context(|| format!("Failed to create {}", log_path.display()))?;
+        let capture = Self {
+            log_path: log_path.to_path_buf(),
+            started: SystemTime::now(),
+            shared: Arc::new((Mutex::new(Buffer::default()), Condvar::new())),
+        };
+
+        let streams: Vec<Box<dyn Read + Send>> = [
+            child
+                .stdout
+                .take()
+                .map(|s| Box::new(s) as Box<dyn Read + Send>),
+            child
+                .stderr
+                .take()
+                .map(|s| Box::new(s) as Box<dyn Read + Send>),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let recorder = if config.record {
+            let title = log_path.file_stem().unwrap_or_default().to_string_lossy();
+            let path = cast::cast_path(log_path);
+            Some(Recorder::create(
+                &path,
+                &title,
+                capture.started,
+                config.record_max_bytes,
+            )?)
+        } else {
+            // Don't leave an earlier run's recording to be replayed as this one
+            let _ = fs::remove_file(cast::cast_path(log_path));
+            None
+        };
+        capture.shared.0.lock().unwrap().open_streams = streams.len();
+        for stream in streams {
+            capture.read(stream, config.buffer_bytes);
+        }
+        capture.write(log, recorder, config.clone());
+        Ok(capture)
+    }
+
+    /// Move bytes from the pipe into the buffer, dropping what doesn't fit
+    fn read(&self, mut stream: Box<dyn Read + Send>, capacity: usize) {
+        let shared = self.shared.clone();
+        let started = self.started;
+        thread::spawn(move || {
+            let (lock, ready) = &*shared;
+            let mut buf = vec![0u8; 64 * 1024];
+            let mut redactor = Redactor::new();
+            loop {
+                let n = match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => n,
+                };
+                let bytes = redactor.feed(&buf[..n]);
+                let now = SystemTime::now();
+                let mut buffer = lock.lock().unwrap();
+                buffer.last_output = Some(now);
+                buffer.stats.bytes_read += n as u64;
+                if buffer.len + n > capacity {
+                    buffer.stats.bytes_dropped += n as u64;
+                    buffer.gap += n as u64;
+                } else {
+                    buffer.len += n;
+                    let at = now.duration_since(started).unwrap_or_default();
+                    buffer.chunks.push_back((at, bytes));
+                }
+                ready.notify_all();
+            }
+            lock.lock().unwrap().open_streams -= 1;
+            ready.notify_all();
+        });
+    }
+
+    /// Drain the buffer into the log until every stream is closed
+    fn write(&self, log: File, mut recorder: Option<Recorder>, config: CaptureConfig) {
+        let shared = self.shared.clone();
+        let mut log = SpillingLog {
+            path: self.log_path.clone(),
+            file: log,
+            len: 0,
+            config,
+            spilled: 0,
+        };
+        thread::spawn(move || {
+            let (lock, ready) = &*shared;
+            loop {
+                let (chunks, gap, done) = {
+                    let mut buffer = lock.lock().unwrap();
+                    while buffer.chunks.is_empty() && buffer.gap == 0 && buffer.open_streams > 0 {
+                        buffer = ready.wait(buffer).unwrap();
+                    }
+                    buffer.len = 0;
+                    let gap = std::mem::take(&mut buffer.gap);
+                    (
+                        std::mem::take(&mut buffer.chunks),
+                        gap,
+                        buffer.open_streams == 0,
+                    )
+                };
+
+                let mut written = 0;
+                let mut spilled = 0;
+                let mut result = Ok(());
+                if gap > 0 {
+                    let marker = format!("\n[agent-inbox: dropped {} bytes of output]\n", gap);
+                    result = log.append(marker.as_bytes(), &mut spilled);
+                    if let (Some(recorder), Some((at, _))) = (recorder.as_mut(), chunks.front()) {
+                        record(recorder, *at, marker.as_bytes());
+                    }
+                }
+                for (at, chunk) in &chunks {
+                    if result.is_err() {
+                        break;
+                    }
+                    result = log.append(chunk, &mut spilled);
+                    written += chunk.len() as u64;
+                    if let Some(recorder) = recorder.as_mut() {
+                        record(recorder, *at, chunk);
+                    }
+                }
+                if let Err(e) = result {
+                    eprintln!("Failed to write captured output: {}", e);
+                }
+                if let Some(recorder) = recorder.as_mut() {
+                    if let Err(e) = recorder.flush() {
+                        eprintln!("Failed to write the recording: {:#}", e);
+                    }
+                }
+
+                let mut buffer = lock.lock().unwrap();
+                buffer.stats.bytes_written += written;
+                buffer.stats.chunks_spilled += spilled;
+                if done && buffer.chunks.is_empty() && buffer.gap == 0 {
+                    buffer.writer_done = true;
+                    ready.notify_all();
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Wait until the pipes are drained into the log, at most `timeout`.
+    /// Background processes that inherited the pipes can keep them open
+    /// after the child exits, hence the limit.
+    pub fn drain(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let (lock, ready) = &*self.shared;
+        let mut buffer = lock.lock().unwrap();
+        while !buffer.writer_done {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            buffer = ready.wait_timeout(buffer, deadline - now).unwrap().0;
+        }
+        true
+    }
+
+    pub fn log_path(&self) -> &Path {
+        &self.log_path
+    }
+
+    /// When the capture started
+    pub fn started(&self) -> SystemTime {
+        self.started
+    }
+
+    /// When the last byte of output arrived, if any has
+    pub fn last_output(&self) -> Option<SystemTime> {
+        self.shared.0.lock().unwrap().last_output
+    }
+
+    pub fn stats(&self) -> CaptureStats {
+        self.shared.0.lock().unwrap().stats
+    }
+}
+
+/// The live log plus its compressed predecessors
+struct SpillingLog {
+    path: PathBuf,
+    file: File,
+    len: u64,
+    config: CaptureConfig,
+    /// Chunks written so far; names the next one
+    spilled: u64,
+}
+
+impl SpillingLog {
+    fn append(&mut self, bytes: &[u8], spilled: &mut u64) -> io::Result<()> {
+        self.file.write_all(bytes)?;
+        self.len += bytes.len() as u64;
+        if self.len >= self.config.chunk_bytes {
+            self.spill()?;
+            *spilled += 1;
+        }
+        Ok(())
+    }
+
+    /// Compress the live log into `<log>.<n>.gz` and start a new one
+    fn spill(&mut self) -> io::Result<()> {
+        self.spilled += 1;
+        let chunk = chunk_path(&self.path, self.spilled);
+        let mut encoder = GzEncoder::new(File::create(&chunk)?, Compression::fast());
+        io::copy(&mut File::open(&self.path)?, &mut encoder)?;
+        encoder.finish()?;
+
+        self.file = File::create(&self.path)?;
+        self.len = 0;
+        if self.spilled > self.config.keep_chunks {
+            let _ = fs::remove_file(chunk_path(
+                &self.path,
+                self.spilled - self.config.keep_chunks,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Add output to the recording; a failure loses the recording, not the log
+fn record(recorder: &mut Recorder, at: Duration, bytes: &[u8]) {
+    if let Err(e) = recorder.output(at, bytes) {
+        eprintln!("Failed to write the recording: {:#}", e);
+    }
+}
+
+/// Record the capture counters in the task metadata
+pub fn record_stats(task: &mut Task, stats: CaptureStats) {
+    if let Ok(value) = serde_json::to_value(stats) {
+        task.metadata
+            .get_or_insert_with(Default::default)
+            .insert("capture".to_string(), value);
+    }
+}
+
+/// Capture counters of the task's last daemon-started run
+pub fn stats(task: &Task) -> Option<CaptureStats> {
+    task.metadata
+        .as_ref()?
+        .get("capture")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+}
+
+/// `<task>.log.<n>.gz`; higher numbers are newer
+pub fn chunk_path(log_path: &Path, n: u64) -> PathBuf {
+    let mut name = log_path.as_os_str().to_owned();
+    name.push(format!(".{}.gz", n));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::process::{Command, Stdio};
+
+    fn run(script: &str, config: &CaptureConfig) -> (OutputCapture, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("task.log");
+        let mut child = Command::new("sh")
+            .args(["-c", script])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let capture = OutputCapture::start(&mut child, &log_path, config).unwrap();
+        child.wait().unwrap();
+        assert!(capture.drain(Duration::from_secs(10)));
+        (capture, dir)
+    }
+
+    #[test]
+    fn test_capture_records_output_and_time() {
+        let (capture, _dir) = run("echo out; echo err >&2", &CaptureConfig::default());
+
+        let log = fs::read_to_string(capture.log_path()).unwrap();
+        assert!(log.contains("out\n") && log.contains("err\n"));
+        assert!(capture.last_output().unwrap() >= capture.started());
+        assert_eq!(capture.stats().bytes_written, 8);
+        assert_eq!(capture.stats().bytes_dropped, 0);
+    }
+
+    #[test]
+    fn test_record_writes_a_cast_next_to_the_log() {
+        let config = CaptureConfig {
+            record: true,
+            ..CaptureConfig::default()
+        };
+        let (capture, _dir) = run("echo one; sleep 0.2; echo two", &config);
+
+        let cast = fs::read_to_string(cast::cast_path(capture.log_path())).unwrap();
+        let events: Vec<(f64, String, String)> = cast
+            .lines()
+            .skip(1)
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].2, "one\r\n");
+        assert!(events[1].0 - events[0].0 >= 0.15);
+    }
+
+    #[test]
+    fn test_full_log_spills_to_compressed_chunks() {
+        let config = CaptureConfig {
+            chunk_bytes: 1000,
+            keep_chunks: 1,
+            ..CaptureConfig::default()
+        };
+        // 2400 bytes: two chunks of at least 1000 spill, the first is pruned
+        let (capture, _dir) = run(
+            "for i in 1 2 3 4; do head -c 600 /dev/zero | tr '\\000' x; sleep 0.05; done",
+            &config,
+        );
+
+        let stats = capture.stats();
+        assert_eq!(stats.bytes_read, 2400);
+        assert_eq!(stats.bytes_written, 2400);
+        assert_eq!(stats.chunks_spilled, 2);
+        assert!(!chunk_path(capture.log_path(), 1).exists());
+        let newest = chunk_path(capture.log_path(), 2);
+        let mut text = String::new();
+        GzDecoder::new(File::open(newest).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert!(text.len() >= 1000 && text.chars().all(|c| c == 'x'));
+    }
+
+    #[test]
+    fn test_overflow_drops_and_counts() {
+        let config = CaptureConfig {
+            buffer_bytes: 0,
+            ..CaptureConfig::default()
+        };
+        let (capture, _dir) = run("head -c 5000 /dev/zero", &config);
+
+        let stats = capture.stats();
+        assert_eq!(stats.bytes_read, 5000);
+        assert_eq!(stats.bytes_dropped, 5000);
+        let log = fs::read_to_string(capture.log_path()).unwrap();
+        assert!(log.contains("[agent-inbox: dropped"));
+    }
+}
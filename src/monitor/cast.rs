@@ -0,0 +1,229 @@
+//! Session recordings in asciicast v2 format
+//!
+//! With `[capture] record = true`, everything a daemon-started task prints
+//! is also written to `<task_id>.cast` next to its log, each chunk stamped
+//! with the time it arrived. `agent-inbox replay` plays it back, and
+//! asciinema can play or upload it too.
+//!
+//! Tasks run on pipes rather than a terminal, so their output has bare
+//! `\n` line endings; these become `\r\n` here, as a terminal's line
+//! discipline would have made them.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Size of the terminal the recording claims; piped output has none
+const WIDTH: u16 = 120;
+const HEIGHT: u16 = 40;
+
+/// `<task_id>.cast`, next to the task's log
+pub fn cast_path(log_path: &Path) -> PathBuf {
+    log_path.with_extension("cast")
+}
+
+#[derive(Serialize)]
+struct Header<'a> {
+    version: u8,
+    width: u16,
+    height: u16,
+    timestamp: u64,
+    title: &'a str,
+}
+
+/// Writes output events to a recording, up to `max_bytes`
+pub struct Recorder {
+    out: BufWriter<File>,
+    written: u64,
+    max_bytes: u64,
+    /// A multi-byte character split across chunks
+    partial: Vec<u8>,
+    /// The last chunk ended with `\r`
+    after_cr: bool,
+    full: bool,
+}
+
+impl Recorder {
+    /// Start a recording at `path` (truncated first)
+    pub fn create(path: &Path, title: &str, started: SystemTime, max_bytes: u64) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut recorder = Self {
+            out: BufWriter::new(file),
+            written: 0,
+            max_bytes,
+            partial: Vec::new(),
+            after_cr: false,
+            full: false,
+        };
+        let header = Header {
+            version: 2,
+            width: WIDTH,
+            height: HEIGHT,
+            timestamp: started
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            title,
+        };
+        recorder.line(&serde_json::to_string(&header)?)?;
+        Ok(recorder)
+    }
+
+    /// Record `bytes` printed `at` after the start
+    pub fn output(&mut self, at: Duration, bytes: &[u8]) -> Result<()> {
+        if self.full {
+            return Ok(());
+        }
+        self.partial.extend_from_slice(bytes);
+        let valid = match std::str::from_utf8(&self.partial) {
+            Ok(text) => text.len(),
+            // Incomplete at the end: keep the rest for the next chunk
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.partial.len(),
+        };
+        let rest = self.partial.split_off(valid);
+        let chunk = std::mem::replace(&mut self.partial, rest);
+        let text = String::from_utf8_lossy(&chunk);
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let mut data = String::with_capacity(text.len());
+        for c in text.chars() {
+            if c == '\n' && !self.after_cr {
+                data.push('\r');
+            }
+            data.push(c);
+            self.after_cr = c == '\r';
+        }
+        let event = serde_json::to_string(&(at.as_secs_f64(), "o", data))?;
+        if self.written + event.len() as u64 > self.max_bytes {
+            self.full = true;
+            let marker = "\r\n[agent-inbox: recording size limit reached]\r\n";
+            return self.line(&serde_json::to_string(&(at.as_secs_f64(), "o", marker))?);
+        }
+        self.line(&event)
+    }
+
+    fn line(&mut self, line: &str) -> Result<()> {
+        writeln!(self.out, "{}", line)?;
+        self.written += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Play the recording at `path` into `out`: `speed` scales the delays,
+/// and no pause is longer than `idle_limit`. `sleep` waits out a delay.
+pub fn replay(
+    path: &Path,
+    speed: f64,
+    idle_limit: Duration,
+    out: &mut impl Write,
+    mut sleep: impl FnMut(Duration),
+) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut lines = BufReader::new(file).lines();
+    let header: serde_json::Value =
+        serde_json::from_str(&lines.next().context("Empty recording")??)?;
+    anyhow::ensure!(
+        header["version"] == 2,
+        "Not an asciicast v2 recording: {}",
+        path.display()
+    );
+
+    let mut last = 0.0;
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (at, kind, data): (f64, String, String) =
+            serde_json::from_str(&line).with_context(|| format!("Bad event: {}", line))?;
+        if kind != "o" {
+            continue;
+        }
+        let delay = Duration::from_secs_f64(((at - last) / speed).max(0.0));
+        last = at;
+        if !delay.is_zero() {
+            out.flush()?;
+            sleep(delay.min(idle_limit));
+        }
+        out.write_all(data.as_bytes())?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("task.cast");
+        let mut recorder = Recorder::create(&path, "nightly", SystemTime::now(), 1 << 20).unwrap();
+        recorder
+            .output(Duration::from_millis(100), b"Compiling\n")
+            .unwrap();
+        // "é" split across two reads
+        recorder.output(Duration::from_secs(5), b"caf\xc3").unwrap();
+        recorder
+            .output(Duration::from_secs(30), b"\xa9\r\ndone\n")
+            .unwrap();
+        recorder.flush().unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].contains("\"version\":2") && lines[0].contains("\"title\":\"nightly\""));
+        assert_eq!(lines[1], r#"[0.1,"o","Compiling\r\n"]"#);
+        assert_eq!(lines[2], r#"[5.0,"o","caf"]"#);
+
+        let mut out = Vec::new();
+        let mut slept = Vec::new();
+        replay(&path, 2.0, Duration::from_secs(3), &mut out, |d| {
+            slept.push(d)
+        })
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Compiling\r\ncafé\r\ndone\r\n"
+        );
+        assert_eq!(
+            slept,
+            vec![
+                Duration::from_millis(50),
+                Duration::from_millis(2450),
+                Duration::from_secs(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recording_stops_at_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("task.cast");
+        let mut recorder = Recorder::create(&path, "noisy", SystemTime::now(), 200).unwrap();
+        for i in 0..20 {
+            recorder
+                .output(Duration::from_secs(i), b"0123456789\n")
+                .unwrap();
+        }
+        recorder.flush().unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.lines().count() < 10);
+        assert!(text
+            .trim_end()
+            .ends_with(r#"recording size limit reached]\r\n"]"#));
+    }
+}
@@ -3,42 +3,234 @@
 //! Currently not used - kept for potential future enhancement.
 //! The monitor uses a simple process-alive check instead.
 
+use super::approval::{self, Approval};
+use super::progress::ProgressTracker;
+use super::reasons::ReasonRegistry;
 use crate::models::Task;
-use std::fs;
+use crate::notify::Urgency;
+use regex::Regex;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::process::Command;
+use std::sync::{Mutex, OnceLock};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone)]
+/// Why a task needs the user. The monitor's own findings are typed; other
+/// producers use a string-keyed kind, presented according to the
+/// [`ReasonRegistry`](super::reasons::ReasonRegistry).
+#[derive(Debug, Clone, PartialEq)]
 pub enum AttentionReason {
     WaitingForInput,
     ProcessStalled,
-    #[allow(dead_code)]
+    /// sudo, ssh or git is waiting for a password or passphrase
+    PasswordPrompt,
+    /// The agent asks before running a tool, command or edit
+    WaitingApproval(Approval),
+    /// A provider rate limit; the agent is backing off and will retry
+    RateLimited {
+        retry_in: Option<Duration>,
+    },
+    /// Errors piling up in the output while the process keeps going
+    ErrorBurst {
+        errors: usize,
+        window: Duration,
+        last: String,
+    },
+    /// The same block of output keeps coming back, e.g. an agent retrying
+    /// one failing step forever
+    Looping {
+        repeats: usize,
+        sample: String,
+    },
+    /// Free-form text, shown as is
     Custom(String),
+    /// A registered kind such as "merge_conflict", with optional specifics
+    Kind {
+        kind: String,
+        detail: Option<String>,
+    },
 }
 
 impl AttentionReason {
-    #[cfg_attr(not(test), allow(dead_code))]
-    pub fn as_str(&self) -> String {
+    pub fn kind(kind: &str, detail: Option<&str>) -> Self {
+        AttentionReason::Kind {
+            kind: kind.to_string(),
+            detail: detail.map(str::to_string),
+        }
+    }
+
+    /// Registry key: "process_stalled", "merge_conflict", ...
+    pub fn key(&self) -> &str {
+        match self {
+            AttentionReason::WaitingForInput => "waiting_for_input",
+            AttentionReason::ProcessStalled => "process_stalled",
+            AttentionReason::PasswordPrompt => "password_prompt",
+            AttentionReason::WaitingApproval(_) => "waiting_approval",
+            AttentionReason::RateLimited { .. } => "rate_limited",
+            AttentionReason::ErrorBurst { .. } => "error_burst",
+            AttentionReason::Looping { .. } => "looping",
+            AttentionReason::Custom(_) => "custom",
+            AttentionReason::Kind { kind, .. } => kind,
+        }
+    }
+
+    pub fn detail(&self) -> Option<String> {
         match self {
-            AttentionReason::WaitingForInput => "Waiting for input".to_string(),
-            AttentionReason::ProcessStalled => "Process stalled (no activity)".to_string(),
-            AttentionReason::Custom(s) => s.clone(),
+            AttentionReason::Kind { detail, .. } => detail.clone(),
+            AttentionReason::WaitingApproval(approval) => Some(approval.action.clone()),
+            AttentionReason::RateLimited {
+                retry_in: Some(wait),
+            } => Some(format!(
+                "retrying in {}",
+                crate::models::progress::format_duration(wait.as_secs() as i64)
+            )),
+            AttentionReason::ErrorBurst {
+                errors,
+                window,
+                last,
+            } => Some(format!(
+                "{} in {}, latest: {}",
+                errors,
+                crate::models::progress::format_duration(window.as_secs() as i64),
+                last
+            )),
+            AttentionReason::Looping { repeats, sample } => {
+                Some(format!("same output {} times: {}", repeats, sample))
+            }
+            _ => None,
         }
     }
+
+    /// Message according to the built-in registry
+    pub fn as_str(&self) -> String {
+        ReasonRegistry::builtin().describe(self)
+    }
+
+    /// Worth interrupting the user for, even while they are at the terminal
+    pub fn is_critical(&self) -> bool {
+        ReasonRegistry::builtin().severity(self) == Urgency::Critical
+    }
 }
 
 pub struct TaskContext {
     pub pid: i32,
     pub last_check: SystemTime,
     pub last_cpu_time: Option<u64>,
+    /// How long the task has been idle: output silence when its output is
+    /// captured, otherwise `cpu_idle`
     pub idle_duration: Duration,
+    /// How long the process tree has burned no CPU
+    pub cpu_idle: Duration,
+    /// When the task last printed anything; None until output is seen
+    /// (or from the start, when the monitor owns the output pipes)
+    pub last_output: Option<SystemTime>,
+    /// Time since `last_output`
+    pub output_idle: Option<Duration>,
+    /// The incomplete last line of output, e.g. a prompt waiting for input
+    pub prompt: Option<String>,
+    /// The trailing block of output holding the question the task may be
+    /// waiting on, as [`prompt::extract`](super::prompt::extract) cuts it
+    pub question: Option<String>,
+    /// The process is blocked reading its terminal with echo off
+    pub echo_off: bool,
+    /// The terminal the process had, once the session behind it closed it
+    /// while the process lived on
+    pub terminal_lost: Option<std::path::PathBuf>,
+    /// Complete lines printed since the previous check
+    pub new_output: Vec<String>,
+    /// The file last changed under the working directory (relative to it)
+    /// and when, with `[file_activity]` on
+    pub last_file: Option<(std::path::PathBuf, SystemTime)>,
+    /// When someone last typed into the task's terminal, as far as the
+    /// monitor has seen; counts as activity, since a user mid-conversation
+    /// hasn't abandoned the task
+    pub last_input: Option<SystemTime>,
+    /// Time since `last_input`
+    pub input_idle: Option<Duration>,
+    /// False positives the user marked on this task in the last
+    /// [`FEEDBACK_DAYS`], per reason key
+    pub false_positives: HashMap<String, u32>,
+    /// How long after the task starts guesses from process state (stalls,
+    /// blocked reads) are held back
+    pub startup_grace: Duration,
+}
+
+/// Startup grace of tasks without their own or a `[monitor]` setting
+pub const STARTUP_GRACE: Duration = Duration::from_secs(10);
+
+/// How far back false positives marked with `agent-inbox feedback` count
+pub const FEEDBACK_DAYS: i64 = 30;
+
+/// How much each false positive relaxes a detector, and the most it can
+const TOLERANCE_PER_FALSE_POSITIVE: f64 = 0.5;
+const MAX_TOLERANCE: f64 = 3.0;
+
+/// Factor by which a detector relaxes its threshold for a task after
+/// `false_positives` misfires: 1 with none, up to [`MAX_TOLERANCE`]
+pub fn tolerance(false_positives: u32) -> f64 {
+    (1.0 + TOLERANCE_PER_FALSE_POSITIVE * false_positives as f64).min(MAX_TOLERANCE)
+}
+
+impl TaskContext {
+    pub fn new(pid: i32, now: SystemTime) -> Self {
+        Self {
+            pid,
+            last_check: now,
+            last_cpu_time: None,
+            idle_duration: Duration::ZERO,
+            cpu_idle: Duration::ZERO,
+            last_output: None,
+            output_idle: None,
+            prompt: None,
+            question: None,
+            echo_off: false,
+            terminal_lost: None,
+            new_output: Vec::new(),
+            last_file: None,
+            last_input: None,
+            input_idle: None,
+            false_positives: HashMap::new(),
+            startup_grace: STARTUP_GRACE,
+        }
+    }
+
+    /// Whether `task` is still within its startup grace at the last check
+    pub fn starting_up(&self, task: &Task) -> bool {
+        let age = self
+            .last_check
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64
+            - task.created_at.timestamp();
+        age <= self.startup_grace.as_secs() as i64
+    }
+
+    /// Factor by which detectors of `reason` (a reason key) should relax
+    /// their thresholds for this task: longer idle times, more errors,
+    /// closer repeats
+    pub fn tolerance(&self, reason: &str) -> f64 {
+        tolerance(
+            self.false_positives
+                .get(reason)
+                .copied()
+                .unwrap_or_default(),
+        )
+    }
 }
 
 pub trait AttentionDetector: Send {
     fn check(&self, task: &Task, context: &TaskContext) -> Option<AttentionReason>;
+
+    /// What `debug trace` calls the detector: its type name, without
+    /// "Detector"
+    fn name(&self) -> String {
+        let name = std::any::type_name_of_val(self);
+        let name = name.rsplit("::").next().unwrap_or(name);
+        name.strip_suffix("Detector").unwrap_or(name).to_string()
+    }
 }
 
 /// Detector that checks if process is waiting on stdin
+#[derive(Default)]
 pub struct ProcessStateDetector;
 
 impl ProcessStateDetector {
@@ -46,11 +238,22 @@ impl ProcessStateDetector {
         Self
     }
 
+    #[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+    fn check_process_state(&self, pid: i32) -> Option<String> {
+        // The kernel says outright when a process sleeps on a terminal read
+        let process = super::kinfo::process(pid)?;
+        if process.state == 'S' && process.wait_channel == "ttyin" {
+            return Some("waiting_input".to_string());
+        }
+        Some(process.state.to_string())
+    }
+
+    #[cfg(not(any(target_os = "freebsd", target_os = "openbsd")))]
     fn check_process_state(&self, pid: i32) -> Option<String> {
         // Check if process is in "sleeping" state and waiting on stdin
         // Read from /proc/<pid>/stat
         let stat_path = format!("/proc/{}/stat", pid);
-        let stat_content = fs::read_to_string(&stat_path).ok()?;
+        let stat_content = std::fs::read_to_string(&stat_path).ok()?;
 
         // Parse the stat file (format: pid (comm) state ...)
         let parts: Vec<&str> = stat_content.split_whitespace().collect();
@@ -64,7 +267,7 @@ impl ProcessStateDetector {
         if state == "S" {
             // Check file descriptors to see if stdin is being read
             let fd_path = format!("/proc/{}/fd/0", pid);
-            if let Ok(link) = fs::read_link(&fd_path) {
+            if let Ok(link) = std::fs::read_link(&fd_path) {
                 let link_str = link.to_string_lossy();
                 // If stdin is connected to terminal and process is sleeping,
                 // it might be waiting for input
@@ -83,15 +286,9 @@ impl AttentionDetector for ProcessStateDetector {
         if let Some(state) = self.check_process_state(context.pid) {
             if state == "waiting_input" {
                 // Additional checks to reduce false positives:
-                // Only flag if task has been running for at least 10 seconds
+                // Only flag once the startup grace is over
                 // AND idle for at least 5 seconds
-                let task_age = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as i64
-                    - task.created_at.timestamp();
-
-                if task_age > 10 && context.idle_duration.as_secs() > 5 {
+                if !context.starting_up(task) && context.idle_duration.as_secs() > 5 {
                     return Some(AttentionReason::WaitingForInput);
                 }
             }
@@ -100,6 +297,341 @@ impl AttentionDetector for ProcessStateDetector {
     }
 }
 
+/// Detector for password and passphrase prompts: either the output ends
+/// with one, or the process is blocked reading its terminal with echo off,
+/// the way getpass() leaves it
+#[derive(Default)]
+pub struct PasswordPromptDetector;
+
+impl PasswordPromptDetector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl AttentionDetector for PasswordPromptDetector {
+    fn check(&self, _task: &Task, context: &TaskContext) -> Option<AttentionReason> {
+        let prompted = context
+            .prompt
+            .as_deref()
+            .is_some_and(super::redact::is_password_prompt);
+        (prompted || context.echo_off).then_some(AttentionReason::PasswordPrompt)
+    }
+}
+
+/// Detector for a process left without its terminal: the ssh connection
+/// dropped or the tmux pane closed, and the process survived the hangup. It
+/// can't be answered any more, and whatever it reads from the dead terminal
+/// fails, so it won't get anywhere by itself.
+#[derive(Default)]
+pub struct TerminalLostDetector;
+
+impl TerminalLostDetector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl AttentionDetector for TerminalLostDetector {
+    fn check(&self, _task: &Task, context: &TaskContext) -> Option<AttentionReason> {
+        let tty = context.terminal_lost.as_ref()?;
+        Some(AttentionReason::kind(
+            "terminal_lost",
+            Some(&tty.display().to_string()),
+        ))
+    }
+}
+
+/// Detector for tool-approval prompts, e.g. Claude Code's "Do you want to
+/// proceed?" menu or aider's "Run shell command? (Y)es/(N)o"
+#[derive(Default)]
+pub struct ApprovalDetector;
+
+impl ApprovalDetector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl AttentionDetector for ApprovalDetector {
+    fn check(&self, _task: &Task, context: &TaskContext) -> Option<AttentionReason> {
+        approval::parse(context.question.as_deref()?).map(AttentionReason::WaitingApproval)
+    }
+}
+
+/// Detector for provider rate limits (HTTP 429, "overloaded", quota
+/// exhaustion) in fresh output. Agents tend to back off silently after
+/// one, which otherwise looks like a stall.
+#[derive(Default)]
+pub struct RateLimitDetector;
+
+impl RateLimitDetector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl AttentionDetector for RateLimitDetector {
+    fn check(&self, _task: &Task, context: &TaskContext) -> Option<AttentionReason> {
+        let lines = &context.new_output;
+        let at = lines.iter().rposition(|l| is_rate_limit(l))?;
+        // Clients often announce the retry on the next line
+        Some(AttentionReason::RateLimited {
+            retry_in: lines[at..].iter().take(3).find_map(|l| retry_delay(l)),
+        })
+    }
+}
+
+fn rate_limit_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"(?ix)
+            rate[\ _-]?limit(ed|_error|_exceeded|\ exceeded|\ reached|\ hit)
+            | (hit|exceeded)\ (a|the|your)\ rate\ limit
+            | too\ many\ requests
+            | (http|status|error|code)\W{0,3}429\b
+            | \b429\W{0,3}(too|error|status)
+            | overloaded_error | \bis\ overloaded\b | \boverloaded\b.*\b(retry|try\ again)
+            | resource_exhausted",
+        )
+        .unwrap()
+    })
+}
+
+/// A line reporting a provider rate limit
+pub fn is_rate_limit(line: &str) -> bool {
+    rate_limit_re().is_match(line)
+}
+
+/// The wait announced in a rate-limit message: "Retry-After: 30",
+/// "retrying in 12 seconds", "try again in 1.5s"
+pub fn retry_delay(line: &str) -> Option<Duration> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(
+            r"(?i)(?:retry[- ]after\W{0,3}(?P<secs>\d+)\b)|(?:(?:retry|retrying|try again|trying again)(?: \w+)? in (?P<n>\d+(?:\.\d+)?) ?(?P<unit>ms|milliseconds?|s\b|secs?|seconds?|m\b|mins?|minutes?|h\b|hours?))",
+        )
+        .unwrap()
+    });
+    let caps = re.captures(line)?;
+    if let Some(secs) = caps.name("secs") {
+        return secs.as_str().parse().ok().map(Duration::from_secs);
+    }
+    let n: f64 = caps.name("n")?.as_str().parse().ok()?;
+    let unit = caps.name("unit")?.as_str().to_ascii_lowercase();
+    let secs = if unit.starts_with("ms") || unit.starts_with("milli") {
+        n / 1000.0
+    } else if unit.starts_with('m') {
+        n * 60.0
+    } else if unit.starts_with('h') {
+        n * 3600.0
+    } else {
+        n
+    };
+    Some(Duration::from_secs_f64(secs))
+}
+
+/// Detector for a burst of error lines (compiler errors, panics,
+/// tracebacks) in the output of a process that keeps running. Agents tend
+/// to spiral after a failure rather than stop.
+pub struct ErrorBurstDetector {
+    threshold: usize,
+    window: Duration,
+    /// When each recent error line was seen, and the latest one
+    seen: Mutex<(VecDeque<SystemTime>, String)>,
+}
+
+impl ErrorBurstDetector {
+    pub fn new(threshold: usize, window: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            window,
+            seen: Mutex::new((VecDeque::new(), String::new())),
+        }
+    }
+}
+
+impl AttentionDetector for ErrorBurstDetector {
+    fn check(&self, _task: &Task, context: &TaskContext) -> Option<AttentionReason> {
+        let now = context.last_check;
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let (times, last) = &mut *seen;
+        for line in context.new_output.iter().filter(|l| is_error_line(l)) {
+            times.push_back(now);
+            *last = line.trim().to_string();
+        }
+        while times
+            .front()
+            .is_some_and(|t| now.duration_since(*t).unwrap_or_default() > self.window)
+        {
+            times.pop_front();
+        }
+        let threshold = (self.threshold as f64 * context.tolerance("error_burst")).ceil() as usize;
+        (times.len() >= threshold).then(|| AttentionReason::ErrorBurst {
+            errors: times.len(),
+            window: self.window,
+            last: last.chars().take(120).collect(),
+        })
+    }
+}
+
+/// Lines per block of output compared by [`LoopingDetector`]
+const LOOP_BLOCK_LINES: usize = 10;
+
+/// Detector for output that keeps repeating itself. Lines are grouped into
+/// blocks, numbers and hashes are blanked out, and each new block is
+/// compared with the recent ones by the overlap of their line sets, so a
+/// loop is found whatever its length or alignment. Progress lines that
+/// move a bar along don't count.
+pub struct LoopingDetector {
+    repeats: usize,
+    similarity: f64,
+    state: Mutex<LoopState>,
+}
+
+#[derive(Default)]
+struct LoopState {
+    progress: ProgressTracker,
+    /// Normalized lines of the block being filled, and its first line
+    block: Vec<String>,
+    sample: Option<String>,
+    /// Recent complete blocks, oldest first
+    recent: VecDeque<HashSet<String>>,
+    /// What the latest complete block found
+    looping: Option<AttentionReason>,
+}
+
+impl LoopingDetector {
+    /// Flag the task once `repeats` recent blocks share at least
+    /// `similarity` (0..=1) of their lines
+    pub fn new(repeats: usize, similarity: f64) -> Self {
+        Self {
+            repeats: repeats.max(2),
+            similarity: similarity.clamp(0.0, 1.0),
+            state: Mutex::new(LoopState::default()),
+        }
+    }
+
+    /// A `tolerance` above 1 asks for blocks closer to identical
+    fn finish_block(&self, state: &mut LoopState, tolerance: f64) {
+        let block: HashSet<String> = state.block.drain(..).collect();
+        let sample = state.sample.take().unwrap_or_default();
+        let similarity = 1.0 - (1.0 - self.similarity) / tolerance;
+        let repeats = state
+            .recent
+            .iter()
+            .filter(|earlier| jaccard(earlier, &block) >= similarity)
+            .count()
+            + 1;
+        state.looping = (repeats >= self.repeats).then(|| AttentionReason::Looping {
+            repeats,
+            sample: sample.chars().take(120).collect(),
+        });
+        state.recent.push_back(block);
+        while state.recent.len() >= self.repeats * 2 {
+            state.recent.pop_front();
+        }
+    }
+}
+
+impl AttentionDetector for LoopingDetector {
+    fn check(&self, _task: &Task, context: &TaskContext) -> Option<AttentionReason> {
+        let now = chrono::DateTime::<chrono::Utc>::from(context.last_check);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        for line in &context.new_output {
+            let normalized = normalize_output_line(line);
+            if normalized.is_empty() || state.progress.observe(line, now) {
+                continue;
+            }
+            state.sample.get_or_insert_with(|| line.trim().to_string());
+            state.block.push(normalized);
+            if state.block.len() == LOOP_BLOCK_LINES {
+                self.finish_block(&mut state, context.tolerance("looping"));
+            }
+        }
+        state.looping.clone()
+    }
+}
+
+/// Lowercased, with numbers and hex hashes blanked and whitespace
+/// collapsed, so "attempt 3 at 12:01" and "attempt 4 at 12:02" compare equal
+fn normalize_output_line(line: &str) -> String {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"[0-9a-f]{7,}|\d+").unwrap());
+    let lower = line.to_lowercase();
+    re.replace_all(&lower, "#")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Shared lines over all distinct lines of the two blocks
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// A line that reports an error: `error:`, `error[E0308]:`, panics,
+/// Python tracebacks and exceptions, `npm ERR!`, `FAILED`
+pub fn is_error_line(line: &str) -> bool {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"(?x)
+            ^\s*(?i:error)(\[\w+\])?:
+            | ^\s*(ERROR|FATAL|FAILED|fatal:|npm\ ERR!)
+            | \bpanicked\ at\b
+            | ^Traceback\ \(most\ recent\ call\ last\)
+            | ^(\w+\.)*\w*(Error|Exception):\ ",
+        )
+        .unwrap()
+    })
+    .is_match(line)
+}
+
+/// The process sleeps while its terminal is in canonical mode with echo
+/// off. Full-screen programs also turn echo off, but use raw mode.
+pub fn reading_with_echo_off(pid: i32) -> bool {
+    if !cfg!(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "openbsd"
+    )) {
+        return false;
+    }
+    if process_state(pid) != Some('S') {
+        return false;
+    }
+
+    let Some(tty) = crate::presence::terminal_of(pid) else {
+        return false;
+    };
+    // GNU stty takes the device with -F, BSD stty with -f
+    let device_flag = if cfg!(target_os = "linux") {
+        "-F"
+    } else {
+        "-f"
+    };
+    Command::new("stty")
+        .arg(device_flag)
+        .arg(&tty)
+        .arg("-a")
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .is_some_and(|o| echo_off_canonical(&String::from_utf8_lossy(&o.stdout)))
+}
+
+/// `stty -a` reports `-echo` and `icanon`
+fn echo_off_canonical(stty: &str) -> bool {
+    let flags: Vec<&str> = stty.split_whitespace().collect();
+    flags.contains(&"-echo") && flags.contains(&"icanon")
+}
+
 /// Detector that checks if process has been inactive for too long
 pub struct StallDetector {
     timeout: Duration,
@@ -109,43 +641,103 @@ impl StallDetector {
     pub fn new(timeout: Duration) -> Self {
         Self { timeout }
     }
+}
 
-    fn get_process_cpu_time(&self, pid: i32) -> Option<u64> {
-        let stat_path = format!("/proc/{}/stat", pid);
-        let stat_content = fs::read_to_string(&stat_path).ok()?;
+/// Scheduler state of a process as /proc/<pid>/stat spells it ('R', 'S', ...)
+#[cfg(not(any(target_os = "freebsd", target_os = "openbsd")))]
+pub fn process_state(pid: i32) -> Option<char> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name may contain spaces; the state follows its ')'
+    let (_, rest) = stat.rsplit_once(')')?;
+    rest.split_whitespace().next()?.chars().next()
+}
 
-        let parts: Vec<&str> = stat_content.split_whitespace().collect();
-        if parts.len() < 15 {
-            return None;
-        }
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub fn process_state(pid: i32) -> Option<char> {
+    super::kinfo::process(pid).map(|p| p.state)
+}
 
-        // Fields 13 and 14 are utime and stime (user and system CPU time)
-        let utime: u64 = parts[13].parse().ok()?;
-        let stime: u64 = parts[14].parse().ok()?;
+/// CPU time (utime + stime, in clock ticks) of a single process
+#[cfg(not(any(target_os = "freebsd", target_os = "openbsd")))]
+pub fn process_cpu_time(pid: i32) -> Option<u64> {
+    let stat_path = format!("/proc/{}/stat", pid);
+    let stat_content = std::fs::read_to_string(&stat_path).ok()?;
 
-        Some(utime + stime)
+    let parts: Vec<&str> = stat_content.split_whitespace().collect();
+    if parts.len() < 15 {
+        return None;
     }
+
+    // Fields 13 and 14 are utime and stime (user and system CPU time)
+    let utime: u64 = parts[13].parse().ok()?;
+    let stime: u64 = parts[14].parse().ok()?;
+
+    Some(utime + stime)
+}
+
+/// CPU time of a single process, in the 100 Hz ticks Linux reports
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub fn process_cpu_time(pid: i32) -> Option<u64> {
+    super::kinfo::process(pid).map(|p| (p.runtime.as_micros() / 10_000) as u64)
+}
+
+/// CPU time of a process plus all of its live descendants.
+///
+/// Wrappers register their own shell PID, which sits idle in `wait` while the
+/// real agent works in a child process, so the whole tree has to be counted.
+pub fn process_tree_cpu_time(pid: i32) -> Option<u64> {
+    let mut total = process_cpu_time(pid)?;
+    let mut pending = vec![pid];
+    let entries = parent_links()?;
+
+    while let Some(parent) = pending.pop() {
+        for (child, _) in entries.iter().filter(|(_, ppid)| *ppid == parent) {
+            total += process_cpu_time(*child).unwrap_or(0);
+            pending.push(*child);
+        }
+    }
+
+    Some(total)
+}
+
+/// (pid, ppid) of every process
+#[cfg(not(any(target_os = "freebsd", target_os = "openbsd")))]
+fn parent_links() -> Option<Vec<(i32, i32)>> {
+    let entries = std::fs::read_dir("/proc")
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().to_str()?.parse::<i32>().ok())
+        .filter_map(|child| {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", child)).ok()?;
+            // ppid is the second field after the parenthesised command name
+            let after_comm = &stat[stat.rfind(')')? + 1..];
+            let ppid = after_comm.split_whitespace().nth(1)?.parse().ok()?;
+            Some((child, ppid))
+        })
+        .collect();
+    Some(entries)
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+fn parent_links() -> Option<Vec<(i32, i32)>> {
+    Some(
+        super::kinfo::processes()
+            .iter()
+            .map(|p| (p.pid, p.ppid))
+            .collect(),
+    )
 }
 
 impl AttentionDetector for StallDetector {
     fn check(&self, task: &Task, context: &TaskContext) -> Option<AttentionReason> {
-        // Check if process CPU usage has changed since last check
-        if let Some(current_cpu) = self.get_process_cpu_time(context.pid) {
-            if let Some(last_cpu) = context.last_cpu_time {
-                // If CPU time hasn't changed AND we've been idle past timeout
-                if current_cpu == last_cpu && context.idle_duration > self.timeout {
-                    // Additional check: ensure task has been running long enough
-                    let task_age = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs() as i64
-                        - task.created_at.timestamp();
-
-                    if task_age > 30 {
-                        return Some(AttentionReason::ProcessStalled);
-                    }
-                }
-            }
+        // Idle time only accumulates while output is silent or the measured
+        // CPU time stays the same, so a measurement plus enough idle time
+        // means stalled
+        let measured = context.output_idle.is_some() || context.last_cpu_time.is_some();
+        let timeout = self.timeout.mul_f64(context.tolerance("process_stalled"));
+        // Additional check: ensure task has been running long enough
+        if measured && context.idle_duration > timeout && !context.starting_up(task) {
+            return Some(AttentionReason::ProcessStalled);
         }
 
         None
@@ -154,6 +746,7 @@ impl AttentionDetector for StallDetector {
 
 /// Detector that uses lsof to check if process is reading from stdin
 #[allow(dead_code)]
+#[derive(Default)]
 pub struct StdinDetector;
 
 impl StdinDetector {
@@ -184,15 +777,9 @@ impl AttentionDetector for StdinDetector {
         // This is a more aggressive check than ProcessStateDetector
         // Only enable if lsof is available and we want detailed stdin tracking
         if self.is_reading_stdin(context.pid) {
-            // Additional heuristic: check if process has been running for more than a few seconds
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as i64;
-            let task_age = now - task.created_at.timestamp();
-
-            // If task is older than 30 seconds and still reading stdin, likely waiting
-            if task_age > 30 {
+            // If the task is past its startup grace and still reading stdin,
+            // it's likely waiting
+            if !context.starting_up(task) {
                 return Some(AttentionReason::WaitingForInput);
             }
         }
@@ -226,6 +813,185 @@ mod tests {
         assert_eq!(AttentionReason::Custom("Test".to_string()).as_str(), "Test");
     }
 
+    #[test]
+    fn test_rate_limit_lines() {
+        let limited = [
+            "Error: 429 Too Many Requests",
+            "API Error: {\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}",
+            "anthropic.RateLimitError: rate_limit_error",
+            "You exceeded your current quota: RESOURCE_EXHAUSTED",
+            "Rate limit reached for gpt-4o. Please try again in 1.5s.",
+        ];
+        for line in limited {
+            assert!(is_rate_limit(line), "{}", line);
+        }
+        assert!(!is_rate_limit("Fixed the rate limiter in src/api.rs"));
+        assert!(!is_rate_limit("Processed 429 files"));
+
+        assert_eq!(
+            retry_delay("Retry-After: 30"),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            retry_delay("retrying in 2 minutes"),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            retry_delay("Please try again in 1.5s."),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            retry_delay("Retrying request in 800ms"),
+            Some(Duration::from_millis(800))
+        );
+        assert_eq!(retry_delay("429 Too Many Requests"), None);
+    }
+
+    #[test]
+    fn test_error_burst() {
+        let task = Task::new("t1".into(), "claude_code".into(), "Fix".into(), None, None);
+        let detector = ErrorBurstDetector::new(3, Duration::from_secs(60));
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let check = |secs: u64, lines: &[&str]| {
+            let mut context = TaskContext::new(1, start + Duration::from_secs(secs));
+            context.new_output = lines.iter().map(|l| l.to_string()).collect();
+            detector.check(&task, &context)
+        };
+
+        assert!(check(
+            0,
+            &[
+                "error[E0308]: mismatched types",
+                "   Compiling foo",
+                "no errors here"
+            ]
+        )
+        .is_none());
+        assert!(check(30, &["thread 'main' panicked at src/main.rs:3:5"]).is_none());
+        // The first error has left the window by now
+        assert!(check(80, &["Traceback (most recent call last):"]).is_none());
+        let reason = check(85, &["ValueError: bad input"]).unwrap();
+        assert_eq!(
+            reason.as_str(),
+            "Errors piling up: 3 in 1m, latest: ValueError: bad input"
+        );
+        assert!(!is_error_line("Fixed 3 errors in the parser"));
+    }
+
+    #[test]
+    fn test_false_positives_relax_detectors() {
+        let mut task = Task::new("t1".into(), "claude_code".into(), "Fix".into(), None, None);
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        task.created_at = chrono::DateTime::from(start);
+        let detector = StallDetector::new(Duration::from_secs(60));
+        let mut context = TaskContext::new(1, start + Duration::from_secs(600));
+        context.output_idle = Some(Duration::from_secs(100));
+        context.idle_duration = Duration::from_secs(100);
+        assert_eq!(
+            detector.check(&task, &context),
+            Some(AttentionReason::ProcessStalled)
+        );
+
+        // Two misfires: twice the idle time before it counts
+        context
+            .false_positives
+            .insert("process_stalled".to_string(), 2);
+        assert_eq!(context.tolerance("process_stalled"), 2.0);
+        assert_eq!(detector.check(&task, &context), None);
+        context.idle_duration = Duration::from_secs(121);
+        assert_eq!(
+            detector.check(&task, &context),
+            Some(AttentionReason::ProcessStalled)
+        );
+
+        assert_eq!(context.tolerance("looping"), 1.0);
+        assert_eq!(tolerance(10), MAX_TOLERANCE);
+    }
+
+    #[test]
+    fn test_startup_grace() {
+        let mut task = Task::new("t1".into(), "claude_code".into(), "Fix".into(), None, None);
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        task.created_at = chrono::DateTime::from(start);
+        let detector = StallDetector::new(Duration::from_secs(3));
+        let mut context = TaskContext::new(1, start + Duration::from_secs(8));
+        context.output_idle = Some(Duration::from_secs(5));
+        context.idle_duration = Duration::from_secs(5);
+        assert!(context.starting_up(&task));
+        assert_eq!(detector.check(&task, &context), None);
+
+        context.startup_grace = Duration::from_secs(5);
+        assert!(!context.starting_up(&task));
+        assert_eq!(
+            detector.check(&task, &context),
+            Some(AttentionReason::ProcessStalled)
+        );
+    }
+
+    #[test]
+    fn test_looping() {
+        let task = Task::new("t1".into(), "claude_code".into(), "Fix".into(), None, None);
+        let detector = LoopingDetector::new(3, 0.8);
+        let check = |lines: Vec<String>| {
+            let mut context = TaskContext::new(1, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+            context.new_output = lines;
+            detector.check(&task, &context)
+        };
+        let attempt = |n: usize| {
+            vec![
+                format!("Attempt {}: running cargo test", n),
+                "error[E0425]: cannot find value `x` in this scope".to_string(),
+                "Let me fix that.".to_string(),
+            ]
+        };
+
+        // Progress moving along is not a loop
+        assert!(check((0..40).map(|n| format!("Downloading {}%", n)).collect()).is_none());
+
+        // A 3-line block repeated out of step with the 10-line comparison blocks
+        assert!(check((1..=7).flat_map(attempt).collect()).is_none());
+        let reason = check((8..=10).flat_map(attempt).collect()).unwrap();
+        assert_eq!(reason.key(), "looping");
+        assert!(
+            reason
+                .as_str()
+                .starts_with("Output looping: same output 3 times: "),
+            "{}",
+            reason.as_str()
+        );
+
+        // Something new breaks the loop once a block completes
+        assert!(check(
+            "abcdefghij"
+                .chars()
+                .map(|c| format!("Editing {}.rs", c))
+                .collect()
+        )
+        .is_none());
+        assert_eq!(
+            normalize_output_line("  Retry  #3 at 12:01 (3fa9c2e1d)"),
+            "retry ## at #:# (#)"
+        );
+    }
+
+    #[test]
+    fn test_echo_off_canonical() {
+        let getpass =
+            "speed 38400 baud; line = 0;\n-parenb cs8\nisig icanon iexten -echo echoe echok";
+        assert!(echo_off_canonical(getpass));
+        let raw = "isig -icanon iexten -echo echoe";
+        assert!(!echo_off_canonical(raw));
+        assert!(!echo_off_canonical("isig icanon iexten echo"));
+    }
+
+    #[test]
+    fn test_process_tree_cpu_time() {
+        let current_pid = std::process::id() as i32;
+        let own = process_cpu_time(current_pid).unwrap();
+        assert!(process_tree_cpu_time(current_pid).unwrap() >= own);
+        assert!(process_tree_cpu_time(999999).is_none());
+    }
+
     #[test]
     fn test_detector_creation() {
         let detectors = create_default_detectors();
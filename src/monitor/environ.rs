@@ -0,0 +1,85 @@
+//! What a task's process was started with: its command line and a few
+//! allow-listed environment variables
+//!
+//! Captured once at registration, so five identical-looking `claude`
+//! processes can be told apart by model, virtualenv or arguments. Only the
+//! variables named in `[environment] vars` are kept, and secrets on the
+//! command line are redacted, since both end up in listings and
+//! notifications.
+
+use super::redact::SecretFilter;
+use crate::models::ProcessSnapshot;
+use std::collections::BTreeMap;
+
+/// Snapshot `pid`'s command line and allow-listed environment. Variables
+/// are read from /proc/<pid>/environ when possible, otherwise from this
+/// process, which wrappers start from the agent's own shell.
+pub fn capture(pid: Option<i32>, vars: &[String]) -> ProcessSnapshot {
+    let environ = pid.and_then(read_environ);
+    let env: BTreeMap<String, String> = vars
+        .iter()
+        .filter_map(|name| {
+            let value = match &environ {
+                Some(environ) => environ.get(name).cloned(),
+                None => std::env::var(name).ok(),
+            };
+            Some((name.clone(), value.filter(|v| !v.is_empty())?))
+        })
+        .collect();
+    ProcessSnapshot {
+        command_line: pid
+            .and_then(read_cmdline)
+            .map(|line| SecretFilter::default().redact(&line)),
+        env,
+    }
+}
+
+/// NUL-separated arguments joined into a shell command, so `rerun` can
+/// start it again
+fn read_cmdline(pid: i32) -> Option<String> {
+    let raw = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let args: Vec<String> = raw
+        .split(|&b| b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    (!args.is_empty()).then(|| crate::daemon::shell_join(&args))
+}
+
+fn read_environ(pid: i32) -> Option<BTreeMap<String, String>> {
+    let raw = std::fs::read(format!("/proc/{}/environ", pid)).ok()?;
+    Some(parse_environ(&raw))
+}
+
+fn parse_environ(raw: &[u8]) -> BTreeMap<String, String> {
+    raw.split(|&b| b == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (name, value) = entry.split_once('=')?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_environ() {
+        let env = parse_environ(b"MODEL=opus\0PATH=/usr/bin\0EMPTY=\0junk\0");
+        assert_eq!(env["MODEL"], "opus");
+        assert_eq!(env["EMPTY"], "");
+        assert!(!env.contains_key("junk"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_capture_own_process() {
+        let vars = ["PATH".to_string(), "NOT_SET_ANYWHERE".to_string()];
+        let snapshot = capture(Some(std::process::id() as i32), &vars);
+        assert!(snapshot.command_line.is_some());
+        assert!(snapshot.env.contains_key("PATH"));
+        assert!(!snapshot.env.contains_key("NOT_SET_ANYWHERE"));
+    }
+}
@@ -0,0 +1,250 @@
+//! Progress reported by the task itself over a named pipe
+//!
+//! Tasks started by `agent-inbox -- <command>` or the daemon find a FIFO at
+//! the path in `$AGENT_NOTIFY_FIFO`, and can write one message per line to it:
+//!
+//! ```text
+//! PROGRESS 40%            progress, as a percentage or "3/10", with a label
+//! NEED_INPUT what branch? the task waits on the user for this
+//! NOTE tests pass         a line for the activity log
+//! ```
+//!
+//! Anything else is ignored, as are lines over 4 KiB. It's the easy way for
+//! a shell script or an agent wrapper to say how far along it is and what it
+//! needs, rather than leave the monitor to guess from its output: a question
+//! raises a waiting-for-input need at once, and progress and notes count as
+//! activity.
+//!
+//! The pipe is held open for reading from the start of the run to its end,
+//! so writing to it never blocks, even before the monitor looks.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// The variable holding the FIFO's path in the task's environment
+pub const ENV: &str = "AGENT_NOTIFY_FIFO";
+
+/// Where FIFOs are made
+pub fn dir() -> PathBuf {
+    crate::storage::state_dir().join("fifo")
+}
+
+/// The longest line read; anything longer is dropped
+pub const MAX_LINE: usize = 4096;
+
+/// `task_id`'s FIFO in `dir`. Task ids may hold `/` or `..`, so anything
+/// but letters, digits, `-` and `_` is written as `%XX`, keeping the name
+/// in `dir` and apart from every other task's.
+pub fn path(dir: &Path, task_id: &str) -> PathBuf {
+    let mut name = String::new();
+    for byte in task_id.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => name.push(byte as char),
+            _ => name.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    dir.join(format!("{}.fifo", name))
+}
+
+/// A message from the task
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// How far along it is, as text the progress tracker reads: "40%",
+    /// "3/10 migrations"
+    Progress(String),
+    /// What it waits on the user for; may be empty
+    NeedInput(String),
+    Note(String),
+}
+
+impl Message {
+    /// The message on `line`, if it is one
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim().to_string();
+        match word.to_ascii_uppercase().as_str() {
+            "PROGRESS" if !rest.is_empty() => Some(Message::Progress(rest)),
+            "NEED_INPUT" => Some(Message::NeedInput(rest)),
+            "NOTE" if !rest.is_empty() => Some(Message::Note(rest)),
+            _ => None,
+        }
+    }
+}
+
+/// A task's FIFO, kept for the length of its run; removed when dropped
+pub struct Fifo {
+    path: PathBuf,
+    file: File,
+}
+
+impl Fifo {
+    /// Make `task_id`'s FIFO in `dir`, replacing one left by an earlier run
+    pub fn create(dir: &Path, task_id: &str) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let path = path(dir, task_id);
+        let _ = std::fs::remove_file(&path);
+        make(&path)?;
+        Ok(Self {
+            file: open(&path)?,
+            path,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A reader for the monitor
+    pub fn reader(&self) -> io::Result<FifoReader> {
+        Ok(FifoReader {
+            file: self.file.try_clone()?,
+        })
+    }
+}
+
+impl Drop for Fifo {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+fn make(path: &Path) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other)?;
+    // SAFETY: path is a valid NUL-terminated string for the duration of the call
+    if unsafe { libc::mkfifo(path.as_ptr(), 0o600) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make(_path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "named pipes need a unix system",
+    ))
+}
+
+/// Open for reading and writing, which doesn't wait for a writer and keeps
+/// writers from waiting for a reader
+fn open(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_NONBLOCK);
+    }
+    options.open(path)
+}
+
+/// Reads the messages a task wrote to its FIFO
+pub struct FifoReader {
+    file: File,
+}
+
+/// The incomplete line read so far
+#[derive(Debug, Default)]
+pub struct Partial {
+    line: Vec<u8>,
+    /// Past [`MAX_LINE`]: the rest of the line is skipped
+    overlong: bool,
+}
+
+impl FifoReader {
+    /// The messages written since the last read; an incomplete line waits
+    /// in `partial` for the rest
+    pub fn read(&self, partial: &mut Partial) -> Vec<Message> {
+        let mut messages = Vec::new();
+        let mut buf = [0u8; 4096];
+        let mut file = &self.file;
+        // Would block once the pipe is empty
+        while let Ok(n @ 1..) = file.read(&mut buf) {
+            for chunk in buf[..n].split_inclusive(|&b| b == b'\n') {
+                let (text, ends) = match chunk.split_last() {
+                    Some((b'\n', text)) => (text, true),
+                    _ => (chunk, false),
+                };
+                if partial.line.len() + text.len() > MAX_LINE {
+                    partial.line.clear();
+                    partial.overlong = true;
+                } else if !partial.overlong {
+                    partial.line.extend_from_slice(text);
+                }
+                if ends {
+                    if !partial.overlong {
+                        messages.extend(Message::parse(&String::from_utf8_lossy(&partial.line)));
+                    }
+                    partial.line.clear();
+                    partial.overlong = false;
+                }
+            }
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_fifo_messages() {
+        assert_eq!(
+            Message::parse("PROGRESS 40%"),
+            Some(Message::Progress("40%".into()))
+        );
+        assert_eq!(
+            Message::parse("  need_input  what branch? "),
+            Some(Message::NeedInput("what branch?".into()))
+        );
+        assert_eq!(
+            Message::parse("NEED_INPUT"),
+            Some(Message::NeedInput(String::new()))
+        );
+        assert_eq!(Message::parse("NOTE"), None);
+        assert_eq!(Message::parse("PROGRESSING nicely"), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let fifo = Fifo::create(dir.path(), "t1").unwrap();
+        let reader = fifo.reader().unwrap();
+        let mut partial = Partial::default();
+        assert!(reader.read(&mut partial).is_empty());
+        // A writer opening the path doesn't wait
+        let mut writer = OpenOptions::new().write(true).open(fifo.path()).unwrap();
+        writer
+            .write_all(b"PROGRESS 3/10 migrations\nhello\nNOTE half")
+            .unwrap();
+        assert_eq!(
+            reader.read(&mut partial),
+            [Message::Progress("3/10 migrations".into())]
+        );
+        writer.write_all(b" way\n").unwrap();
+        assert_eq!(
+            reader.read(&mut partial),
+            [Message::Note("half way".into())]
+        );
+
+        // A line that never ends is dropped, not kept
+        writer.write_all(&[b'x'; 3 * MAX_LINE]).unwrap();
+        assert!(reader.read(&mut partial).is_empty());
+        assert!(partial.line.is_empty());
+        writer.write_all(b"PROGRESS 99%\nNOTE done\n").unwrap();
+        assert_eq!(reader.read(&mut partial), [Message::Note("done".into())]);
+
+        let made = fifo.path().to_path_buf();
+        drop(fifo);
+        assert!(!made.exists());
+
+        // Ids can't reach outside the directory, or another task's FIFO
+        let odd = Fifo::create(dir.path(), "../a/b").unwrap();
+        assert_eq!(odd.path(), dir.path().join("%2E%2E%2Fa%2Fb.fifo"));
+        assert_ne!(path(dir.path(), "a_b"), path(dir.path(), "a/b"));
+    }
+}
@@ -0,0 +1,269 @@
+//! File-system activity under a task's working directory
+//!
+//! Agents that edit code touch files while they work, so a change in the
+//! repository counts as activity just like output does, and a stall
+//! notification can name the file touched last: an agent that stopped
+//! touching the repo 15 minutes ago is probably stuck.
+//!
+//! On Linux the directory tree is watched with inotify, including
+//! directories created later. Elsewhere modification times are compared
+//! between polls, over a bounded number of files.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// Directories watched (or files scanned) at most, so a huge tree can't
+/// exhaust inotify watches or make every poll slow
+const MAX_ENTRIES: usize = 8192;
+
+/// Reports changes under a directory tree
+pub trait FileActivity: Send {
+    /// The most recently changed file since the last call, relative to the
+    /// watched directory
+    fn poll(&mut self) -> Option<PathBuf>;
+}
+
+/// Watch `root`, skipping directories named in `ignore` (".git", "target")
+pub fn watch(root: &Path, ignore: &[String]) -> Result<Box<dyn FileActivity>> {
+    #[cfg(target_os = "linux")]
+    let watcher = Box::new(Inotify::new(root, ignore)?);
+    #[cfg(not(target_os = "linux"))]
+    let watcher = Box::new(MtimeScanner::new(root, ignore));
+    Ok(watcher)
+}
+
+/// Subdirectories of `dir`, recursively and breadth first, leaving out
+/// ignored names and symlinks
+fn directories(dir: &Path, ignore: &[String], limit: usize) -> Vec<PathBuf> {
+    let mut found = vec![dir.to_path_buf()];
+    let mut next = 0;
+    while next < found.len() && found.len() < limit {
+        let Ok(entries) = std::fs::read_dir(&found[next]) else {
+            next += 1;
+            continue;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if is_dir
+                && !ignored(&entry.file_name().to_string_lossy(), ignore)
+                && found.len() < limit
+            {
+                found.push(entry.path());
+            }
+        }
+        next += 1;
+    }
+    found
+}
+
+fn ignored(name: &str, ignore: &[String]) -> bool {
+    ignore.iter().any(|pattern| pattern == name)
+}
+
+/// inotify watches on every directory of the tree
+#[cfg(target_os = "linux")]
+pub struct Inotify {
+    file: std::fs::File,
+    root: PathBuf,
+    ignore: Vec<String>,
+    /// Watched directory of each watch descriptor
+    dirs: std::collections::HashMap<i32, PathBuf>,
+}
+
+#[cfg(target_os = "linux")]
+impl Inotify {
+    const MASK: u32 = libc::IN_MODIFY
+        | libc::IN_CLOSE_WRITE
+        | libc::IN_CREATE
+        | libc::IN_DELETE
+        | libc::IN_MOVED_FROM
+        | libc::IN_MOVED_TO;
+
+    pub fn new(root: &Path, ignore: &[String]) -> Result<Self> {
+        use std::os::fd::FromRawFd;
+
+        anyhow::ensure!(root.is_dir(), "{} is not a directory", root.display());
+        // SAFETY: plain syscall without pointers
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let mut watcher = Self {
+            // SAFETY: `fd` is a fresh descriptor nothing else owns
+            file: unsafe { std::fs::File::from_raw_fd(fd) },
+            root: root.to_path_buf(),
+            ignore: ignore.to_vec(),
+            dirs: std::collections::HashMap::new(),
+        };
+        watcher.add_tree(root);
+        Ok(watcher)
+    }
+
+    fn add_tree(&mut self, dir: &Path) {
+        use std::os::fd::AsRawFd;
+        use std::os::unix::ffi::OsStrExt;
+
+        let room = MAX_ENTRIES.saturating_sub(self.dirs.len());
+        for dir in directories(dir, &self.ignore, room) {
+            let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+                continue;
+            };
+            // SAFETY: `path` is a valid C string for the duration of the call
+            let wd = unsafe {
+                libc::inotify_add_watch(self.file.as_raw_fd(), path.as_ptr(), Self::MASK)
+            };
+            if wd >= 0 {
+                self.dirs.insert(wd, dir);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl FileActivity for Inotify {
+    fn poll(&mut self) -> Option<PathBuf> {
+        use std::io::Read;
+
+        let mut latest = None;
+        let mut buffer = [0u8; 8192];
+        loop {
+            let read = match self.file.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            let mut new_dirs = Vec::new();
+            for (wd, mask, name) in parse_events(&buffer[..read]) {
+                let Some(dir) = self.dirs.get(&wd) else {
+                    continue;
+                };
+                if name.is_empty() || ignored(&name, &self.ignore) {
+                    continue;
+                }
+                let path = dir.join(&name);
+                if mask & libc::IN_ISDIR != 0 {
+                    if mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+                        new_dirs.push(path);
+                    }
+                    continue;
+                }
+                latest = Some(
+                    path.strip_prefix(&self.root)
+                        .map(Path::to_path_buf)
+                        .unwrap_or(path),
+                );
+            }
+            for dir in new_dirs {
+                self.add_tree(&dir);
+            }
+        }
+        latest
+    }
+}
+
+/// (watch descriptor, mask, name) of each `struct inotify_event` in `buffer`
+#[cfg(target_os = "linux")]
+fn parse_events(buffer: &[u8]) -> Vec<(i32, u32, String)> {
+    const HEADER: usize = 16;
+    let field = |at: usize| -> [u8; 4] { buffer[at..at + 4].try_into().unwrap_or_default() };
+    let mut events = Vec::new();
+    let mut offset = 0;
+    while offset + HEADER <= buffer.len() {
+        let wd = i32::from_ne_bytes(field(offset));
+        let mask = u32::from_ne_bytes(field(offset + 4));
+        let len = u32::from_ne_bytes(field(offset + 12)) as usize;
+        let end = (offset + HEADER + len).min(buffer.len());
+        let name = &buffer[offset + HEADER..end];
+        let name = name.split(|&b| b == 0).next().unwrap_or_default();
+        events.push((wd, mask, String::from_utf8_lossy(name).into_owned()));
+        offset = end;
+    }
+    events
+}
+
+/// Compares modification times between polls where there is no inotify
+#[cfg(not(target_os = "linux"))]
+pub struct MtimeScanner {
+    root: PathBuf,
+    ignore: Vec<String>,
+    /// Newest modification time seen so far
+    newest: Option<std::time::SystemTime>,
+}
+
+#[cfg(not(target_os = "linux"))]
+impl MtimeScanner {
+    pub fn new(root: &Path, ignore: &[String]) -> Self {
+        let mut scanner = Self {
+            root: root.to_path_buf(),
+            ignore: ignore.to_vec(),
+            newest: None,
+        };
+        scanner.newest = scanner.scan().map(|(_, at)| at);
+        scanner
+    }
+
+    /// The newest file in the tree and its modification time
+    fn scan(&self) -> Option<(PathBuf, std::time::SystemTime)> {
+        let mut scanned = 0;
+        let mut newest: Option<(PathBuf, std::time::SystemTime)> = None;
+        for dir in directories(&self.root, &self.ignore, MAX_ENTRIES) {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.filter_map(|e| e.ok()) {
+                scanned += 1;
+                if scanned > MAX_ENTRIES {
+                    return newest;
+                }
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                let Ok(modified) = metadata.modified() else {
+                    continue;
+                };
+                if metadata.is_file() && newest.as_ref().is_none_or(|(_, at)| modified > *at) {
+                    newest = Some((entry.path(), modified));
+                }
+            }
+        }
+        newest
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl FileActivity for MtimeScanner {
+    fn poll(&mut self) -> Option<PathBuf> {
+        let (path, modified) = self.scan()?;
+        if self.newest.is_some_and(|newest| modified <= newest) {
+            return None;
+        }
+        self.newest = Some(modified);
+        Some(
+            path.strip_prefix(&self.root)
+                .map(Path::to_path_buf)
+                .unwrap_or(path),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        let mut watcher = watch(dir.path(), &[".git".to_string()]).unwrap();
+        assert_eq!(watcher.poll(), None);
+
+        // Coarse mtimes need the change to land in a later tick
+        #[cfg(not(target_os = "linux"))]
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        assert_eq!(watcher.poll(), None);
+        std::fs::write(dir.path().join("src/lib.rs"), "fn main() {}").unwrap();
+        std::fs::write(dir.path().join(".git/index"), "ignored").unwrap();
+        assert_eq!(watcher.poll(), Some(PathBuf::from("src/lib.rs")));
+        assert_eq!(watcher.poll(), None);
+    }
+}
@@ -0,0 +1,187 @@
+//! Process inspection on FreeBSD and OpenBSD
+//!
+//! There is usually no /proc on the BSDs, so the probe and detectors read
+//! the kernel's process table through `sysctl(kern.proc)` instead. Both
+//! systems return `struct kinfo_proc` records, with different layouts and
+//! MIBs.
+
+use std::ffi::CStr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// What the monitor needs to know about a process
+#[derive(Debug, Clone)]
+pub struct ProcInfo {
+    pub pid: i32,
+    pub ppid: i32,
+    /// Scheduler state as Linux's /proc/<pid>/stat spells it: 'R', 'S',
+    /// 'T', 'Z' or 'I'
+    pub state: char,
+    /// What a sleeping process waits on, e.g. "ttyin" for a terminal read
+    pub wait_channel: String,
+    /// User plus system CPU time
+    pub runtime: Duration,
+    /// Controlling terminal
+    pub tty: Option<PathBuf>,
+}
+
+/// One process, or None when it doesn't exist
+pub fn process(pid: i32) -> Option<ProcInfo> {
+    query(Some(pid)).into_iter().next()
+}
+
+/// Every process on the system (not threads)
+pub fn processes() -> Vec<ProcInfo> {
+    query(None)
+}
+
+#[cfg(target_os = "freebsd")]
+fn query(pid: Option<i32>) -> Vec<ProcInfo> {
+    let mib = match pid {
+        Some(pid) => vec![libc::CTL_KERN, libc::KERN_PROC, libc::KERN_PROC_PID, pid],
+        None => vec![libc::CTL_KERN, libc::KERN_PROC, libc::KERN_PROC_PROC],
+    };
+    read_table::<libc::kinfo_proc>(&mib, None)
+        .into_iter()
+        .map(|p| ProcInfo {
+            pid: p.ki_pid,
+            ppid: p.ki_ppid,
+            state: state_letter(p.ki_stat as i32),
+            wait_channel: c_string(&p.ki_wmesg),
+            runtime: Duration::from_micros(p.ki_runtime),
+            tty: (p.ki_tdev != libc::dev_t::MAX)
+                .then(|| device_path(p.ki_tdev))
+                .flatten(),
+        })
+        .collect()
+}
+
+#[cfg(target_os = "openbsd")]
+fn query(pid: Option<i32>) -> Vec<ProcInfo> {
+    let size = std::mem::size_of::<libc::kinfo_proc>() as libc::c_int;
+    // The last two elements are the record size and how many to return,
+    // filled in by read_table
+    let mib = match pid {
+        Some(pid) => vec![
+            libc::CTL_KERN,
+            libc::KERN_PROC,
+            libc::KERN_PROC_PID,
+            pid,
+            size,
+            0,
+        ],
+        None => vec![
+            libc::CTL_KERN,
+            libc::KERN_PROC,
+            libc::KERN_PROC_ALL,
+            0,
+            size,
+            0,
+        ],
+    };
+    read_table::<libc::kinfo_proc>(&mib, Some(5))
+        .into_iter()
+        .map(|p| ProcInfo {
+            pid: p.p_pid,
+            ppid: p.p_ppid,
+            state: state_letter(p.p_stat as i32),
+            wait_channel: c_string(&p.p_wmesg),
+            runtime: Duration::from_secs(p.p_uutime_sec as u64 + p.p_ustime_sec as u64)
+                + Duration::from_micros(p.p_uutime_usec as u64 + p.p_ustime_usec as u64),
+            tty: (p.p_tdev != u32::MAX)
+                .then(|| device_path(p.p_tdev as libc::dev_t))
+                .flatten(),
+        })
+        .collect()
+}
+
+/// Run a `kern.proc` query: ask for the size, then read with some headroom
+/// for processes started in between. `count_index` is where OpenBSD wants
+/// the number of records.
+fn read_table<T>(mib: &[libc::c_int], count_index: Option<usize>) -> Vec<T> {
+    let mut mib = mib.to_vec();
+    let record = std::mem::size_of::<T>();
+    for _ in 0..3 {
+        let mut len: libc::size_t = 0;
+        // SAFETY: a null buffer only asks for the size
+        let rc = unsafe {
+            libc::sysctl(
+                mib.as_ptr(),
+                mib.len() as libc::c_uint,
+                std::ptr::null_mut(),
+                &mut len,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        if rc != 0 {
+            return Vec::new();
+        }
+        let capacity = len / record + 8;
+        if let Some(i) = count_index {
+            mib[i] = capacity as libc::c_int;
+        }
+        let mut records: Vec<T> = Vec::with_capacity(capacity);
+        let mut len = capacity * record;
+        // SAFETY: the buffer holds `len` bytes, and the kernel reports how
+        // many it filled with whole records
+        let rc = unsafe {
+            libc::sysctl(
+                mib.as_ptr(),
+                mib.len() as libc::c_uint,
+                records.as_mut_ptr().cast(),
+                &mut len,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        if rc == 0 {
+            // SAFETY: see above
+            unsafe { records.set_len(len / record) };
+            return records;
+        }
+        // ENOMEM: the table grew past the headroom, try again
+        if std::io::Error::last_os_error().raw_os_error() != Some(libc::ENOMEM) {
+            return Vec::new();
+        }
+    }
+    Vec::new()
+}
+
+/// SIDL 1, SRUN 2, SSLEEP 3, SSTOP 4, SZOMB 5 on both systems; 6 is SWAIT
+/// (FreeBSD) or SDEAD (OpenBSD), 7 SLOCK or SONPROC
+fn state_letter(stat: i32) -> char {
+    match stat {
+        2 => 'R',
+        3 => 'S',
+        4 => 'T',
+        5 => 'Z',
+        6 if cfg!(target_os = "openbsd") => 'Z',
+        7 if cfg!(target_os = "openbsd") => 'R',
+        6 | 7 => 'D',
+        _ => 'I',
+    }
+}
+
+fn c_string(chars: &[libc::c_char]) -> String {
+    let bytes: Vec<u8> = chars
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// /dev path of a character device, e.g. /dev/pts/3
+fn device_path(dev: libc::dev_t) -> Option<PathBuf> {
+    // SAFETY: devname returns a pointer to a static buffer, or null
+    let name = unsafe { libc::devname(dev, libc::S_IFCHR) };
+    if name.is_null() {
+        return None;
+    }
+    // SAFETY: non-null and NUL-terminated
+    let name = unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned();
+    (!name.starts_with('#') && !name.is_empty()).then(|| PathBuf::from("/dev").join(name))
+}
@@ -0,0 +1,363 @@
+//! Noticing that a monitored process exited
+//!
+//! Between checks the monitor waits on the process instead of sleeping, so
+//! an exit is noticed at once rather than at the next poll. How it waits is
+//! picked once per process, best first:
+//!
+//! - `pidfd`: a pidfd from `pidfd_open(2)` (Linux 5.3+) becomes readable
+//!   when the process exits
+//! - `proc-connector`: the kernel's netlink process connector announces
+//!   every exit on the system; subscribing needs `CAP_NET_ADMIN`
+//! - `polling`: checks the process table, first after 100ms, then at
+//!   doubling intervals up to the poll interval, so short-lived processes
+//!   are caught early and long-running ones cost no more than before
+//!
+//! `agent-inbox doctor` reports the strategy in use and why the better ones
+//! are unavailable.
+
+use super::probe::{ProcProbe, ProcessProbe};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    Pidfd,
+    ProcConnector,
+    Polling,
+}
+
+impl Strategy {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Strategy::Pidfd => "pidfd",
+            Strategy::ProcConnector => "proc-connector",
+            Strategy::Polling => "polling",
+        }
+    }
+}
+
+/// The strategy this machine supports, and why the better ones are out
+#[derive(Debug, Clone)]
+pub struct Detection {
+    pub strategy: Strategy,
+    pub unavailable: Vec<(Strategy, String)>,
+}
+
+/// Probe the strategies once, best first
+pub fn detect() -> &'static Detection {
+    static DETECTION: OnceLock<Detection> = OnceLock::new();
+    DETECTION.get_or_init(|| {
+        let mut unavailable = Vec::new();
+        for strategy in [Strategy::Pidfd, Strategy::ProcConnector] {
+            match open(strategy, std::process::id() as i32) {
+                Ok(_) => {
+                    return Detection {
+                        strategy,
+                        unavailable,
+                    }
+                }
+                Err(e) => unavailable.push((strategy, e.to_string())),
+            }
+        }
+        Detection {
+            strategy: Strategy::Polling,
+            unavailable,
+        }
+    })
+}
+
+enum Watch {
+    #[cfg(target_os = "linux")]
+    Fd(std::os::fd::OwnedFd),
+    Polling,
+}
+
+/// Waits on one process
+pub struct ExitWatch {
+    pid: i32,
+    strategy: Strategy,
+    watch: Watch,
+    /// The watch already saw the exit; a pidfd stays readable after it
+    exited: bool,
+}
+
+impl ExitWatch {
+    /// Watch `pid` with the detected strategy, or by polling if that fails
+    /// for this process
+    pub fn new(pid: i32) -> Self {
+        let strategy = detect().strategy;
+        let (strategy, watch) = match open(strategy, pid) {
+            Ok(watch) => (strategy, watch),
+            Err(_) => (Strategy::Polling, Watch::Polling),
+        };
+        Self {
+            pid,
+            strategy,
+            watch,
+            exited: false,
+        }
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
+    /// Wait until the process exits or `timeout` passes. True when it
+    /// exited.
+    pub fn wait(&mut self, timeout: Duration) -> bool {
+        if self.exited {
+            std::thread::sleep(timeout);
+            return true;
+        }
+        self.exited = match &self.watch {
+            #[cfg(target_os = "linux")]
+            Watch::Fd(fd) if self.strategy == Strategy::Pidfd => linux::wait_readable(fd, timeout),
+            #[cfg(target_os = "linux")]
+            Watch::Fd(fd) => linux::wait_exit_event(fd, self.pid, timeout),
+            Watch::Polling => poll(self.pid, timeout),
+        };
+        self.exited
+    }
+}
+
+/// Check the process table at doubling intervals until `timeout`
+fn poll(pid: i32, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut interval = Duration::from_millis(100);
+    loop {
+        if !ProcProbe.is_alive(pid) {
+            return true;
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return false;
+        }
+        std::thread::sleep(interval.min(left));
+        interval *= 2;
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open(strategy: Strategy, pid: i32) -> std::io::Result<Watch> {
+    match strategy {
+        Strategy::Pidfd => linux::pidfd_open(pid).map(Watch::Fd),
+        Strategy::ProcConnector => linux::proc_connector().map(Watch::Fd),
+        Strategy::Polling => Ok(Watch::Polling),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open(strategy: Strategy, _pid: i32) -> std::io::Result<Watch> {
+    match strategy {
+        Strategy::Polling => Ok(Watch::Polling),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Linux only",
+        )),
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::io::{Error, Result};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::time::{Duration, Instant};
+
+    const CN_IDX_PROC: u32 = 1;
+    const CN_VAL_PROC: u32 = 1;
+    const PROC_CN_MCAST_LISTEN: u32 = 1;
+    const PROC_EVENT_EXIT: u32 = 0x8000_0000;
+    /// `struct nlmsghdr`
+    const NLMSG_HEADER: usize = 16;
+    /// `struct cn_msg`, without its data
+    const CN_HEADER: usize = 20;
+
+    pub fn pidfd_open(pid: i32) -> Result<OwnedFd> {
+        // SAFETY: plain syscall without pointers
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        // SAFETY: `fd` is a fresh descriptor nothing else owns
+        Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+    }
+
+    /// A netlink socket subscribed to process events
+    pub fn proc_connector() -> Result<OwnedFd> {
+        // SAFETY: plain syscall without pointers
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_CONNECTOR,
+            )
+        };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        // SAFETY: `fd` is a fresh descriptor nothing else owns
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SAFETY: all-zero is a valid sockaddr_nl
+        let mut address: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        address.nl_family = libc::AF_NETLINK as u16;
+        address.nl_groups = CN_IDX_PROC;
+        // SAFETY: `address` outlives the call and its size is passed along
+        let bound = unsafe {
+            libc::bind(
+                socket.as_raw_fd(),
+                &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as u32,
+            )
+        };
+        if bound < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let mut message = Vec::with_capacity(NLMSG_HEADER + CN_HEADER + 4);
+        message.extend(((NLMSG_HEADER + CN_HEADER + 4) as u32).to_ne_bytes());
+        message.extend((libc::NLMSG_DONE as u16).to_ne_bytes());
+        message.extend(0u16.to_ne_bytes()); // flags
+        message.extend(0u32.to_ne_bytes()); // seq
+        message.extend(0u32.to_ne_bytes()); // port
+        message.extend(CN_IDX_PROC.to_ne_bytes());
+        message.extend(CN_VAL_PROC.to_ne_bytes());
+        message.extend(0u32.to_ne_bytes()); // seq
+        message.extend(0u32.to_ne_bytes()); // ack
+        message.extend(4u16.to_ne_bytes()); // data length
+        message.extend(0u16.to_ne_bytes()); // flags
+        message.extend(PROC_CN_MCAST_LISTEN.to_ne_bytes());
+        // SAFETY: `message` outlives the call and its length is passed along
+        let sent = unsafe {
+            libc::send(
+                socket.as_raw_fd(),
+                message.as_ptr().cast(),
+                message.len(),
+                0,
+            )
+        };
+        if sent < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(socket)
+    }
+
+    /// Wait up to `timeout` for `fd` to become readable
+    pub fn wait_readable(fd: &OwnedFd, timeout: Duration) -> bool {
+        let mut pollfd = libc::pollfd {
+            fd: fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let millis = timeout.as_millis().min(i32::MAX as u128) as i32;
+        // SAFETY: `pollfd` is a single valid entry for the duration of the call
+        let ready = unsafe { libc::poll(&mut pollfd, 1, millis) };
+        ready > 0
+    }
+
+    /// Read process events until `pid` exits or `timeout` passes
+    pub fn wait_exit_event(socket: &OwnedFd, pid: i32, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut buffer = [0u8; 4096];
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() || !wait_readable(socket, left) {
+                return false;
+            }
+            // SAFETY: `buffer` outlives the call and its length is passed along
+            let read = unsafe {
+                libc::recv(
+                    socket.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                    0,
+                )
+            };
+            if read <= 0 {
+                return false;
+            }
+            if exits(&buffer[..read as usize]).any(|exited| exited == pid) {
+                return true;
+            }
+        }
+    }
+
+    /// Pids of the processes whose exit a datagram announces
+    pub fn exits(datagram: &[u8]) -> impl Iterator<Item = i32> + '_ {
+        let u32_at = |bytes: &[u8], at: usize| -> Option<u32> {
+            Some(u32::from_ne_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+        };
+        let mut offset = 0;
+        std::iter::from_fn(move || loop {
+            let length = u32_at(datagram, offset)? as usize;
+            if length < NLMSG_HEADER {
+                return None;
+            }
+            let message = datagram.get(offset..offset + length)?;
+            // Messages are 4-byte aligned
+            offset += (length + 3) & !3;
+            // `struct proc_event`: what, cpu, timestamp, then the exit's
+            // pid and tgid
+            let event = NLMSG_HEADER + CN_HEADER;
+            if u32_at(message, event) != Some(PROC_EVENT_EXIT) {
+                continue;
+            }
+            let (Some(pid), Some(tgid)) =
+                (u32_at(message, event + 16), u32_at(message, event + 20))
+            else {
+                continue;
+            };
+            // Threads exit too; only the thread group leader ends the process
+            if pid == tgid {
+                return Some(pid as i32);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_exit_watch() {
+        let detection = detect();
+        assert!(detection
+            .unavailable
+            .iter()
+            .all(|(strategy, _)| *strategy != detection.strategy));
+
+        let mut child = Command::new("sleep").arg("0.2").spawn().unwrap();
+        let mut watch = ExitWatch::new(child.id() as i32);
+        let started = Instant::now();
+        // Polling (unlike a pidfd) sees a zombie as alive, so reap it
+        let reaper = std::thread::spawn(move || child.wait());
+        assert!(watch.wait(Duration::from_secs(10)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        reaper.join().unwrap().unwrap();
+        assert!(watch.wait(Duration::ZERO));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_exit_events() {
+        let event = |what: u32, pid: u32, tgid: u32| {
+            let mut message = Vec::new();
+            message.extend(60u32.to_ne_bytes());
+            message.extend([0; 32]);
+            message.extend(what.to_ne_bytes());
+            message.extend([0; 12]);
+            message.extend(pid.to_ne_bytes());
+            message.extend(tgid.to_ne_bytes());
+            message
+        };
+        let datagram = [
+            event(0x8000_0000, 42, 42),
+            event(0x8000_0000, 43, 42),
+            event(1, 44, 44),
+        ]
+        .concat();
+        assert_eq!(linux::exits(&datagram).collect::<Vec<_>>(), vec![42]);
+    }
+}
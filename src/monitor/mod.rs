@@ -1,12 +1,65 @@
-// Detectors module kept for potential future use
-#[allow(dead_code)]
+pub mod access;
+pub mod ansi;
+pub mod approval;
+pub mod bench;
+pub mod capture;
+pub mod cast;
+pub mod classify;
 pub mod detectors;
+pub mod environ;
+pub mod fifo;
+pub mod files;
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub mod kinfo;
+pub mod liveness;
+pub mod oneshot;
+pub mod output;
+pub mod probe;
+pub mod progress;
+pub mod prompt;
+pub mod reasons;
+pub mod redact;
+pub mod remind;
+pub mod respond;
+pub mod rules;
+pub mod source;
+pub mod trace;
 
+use crate::artifacts::{self, snapshot};
+use crate::config::Config;
+use crate::costs::{self, Usage, UsageTracker};
 use crate::db::Database;
-use crate::models::TaskStatus;
+use crate::hooks::Hooks;
+use crate::models::progress::format_duration;
+use crate::models::{EventKind, Priority, Task, TaskEvent, TaskStatus, UnattendedAction};
+use crate::notify::{self, Category, Notification, Notifier, NotifierBuilder, Urgency};
+use crate::plugins;
+use crate::presence::{attached, Presence, SystemPresence};
+use crate::stats;
 use anyhow::Result;
-use std::thread;
+use approval::Approval;
+use capture::OutputCapture;
+use chrono::{DateTime, Utc};
+use classify::{Gate, Verdict};
+use detectors::{
+    ApprovalDetector, AttentionDetector, AttentionReason, ErrorBurstDetector, LoopingDetector,
+    PasswordPromptDetector, RateLimitDetector, StallDetector, TerminalLostDetector,
+};
+use fifo::FifoReader;
+use liveness::ExitWatch;
+use output::OutputTail;
+use probe::{Clock, ProcProbe, ProcessProbe, SystemClock};
+use progress::ProgressTracker;
+use reasons::ReasonRegistry;
+use redact::SecretFilter;
+use remind::Reminders;
+use respond::{AutoResponder, AutoResponse, Decision, InputSink};
+use rules::{Occurrence, Rules};
+use source::PidSource;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
 use std::time::Duration;
+use trace::Trace;
 
 /// Simple process monitor for CLI tools
 ///
@@ -17,35 +70,200 @@ use std::time::Duration;
 /// Note: We don't try to detect "Completed" (waiting for input) for CLI tools
 /// because it's unreliable. The wrapper script handles reporting completion
 /// with exit codes.
+///
+/// While the process runs, the monitor tails the task's captured output (if
+/// any) for progress markers and raises a notification when the process
+/// stalls or a plugin detector reports an attention need.
 pub struct TaskMonitor {
     db: Database,
     poll_interval: Duration,
+    /// Startup grace of tasks that don't set their own
+    startup_grace: Duration,
+    notifier: Notifier,
+    detectors: Vec<Box<dyn AttentionDetector>>,
+    probe: Box<dyn ProcessProbe>,
+    clock: Box<dyn Clock>,
+    responder: AutoResponder,
+    input: Option<Box<dyn InputSink>>,
+    presence: Option<Box<dyn Presence>>,
+    capture: Option<OutputCapture>,
+    hooks: Hooks,
+    reasons: ReasonRegistry,
+    rules: Rules,
+    reminders: Reminders,
+    daily_budget: Option<f64>,
+    /// Directory names skipped when watching the working directory; None
+    /// leaves file activity out
+    file_activity: Option<Vec<String>>,
+    /// Archive root and profiler command for snapshots of stalled processes
+    stall_snapshot: Option<(PathBuf, Option<String>)>,
+    /// Model double-checking uncertain detections
+    classifier: Option<Gate>,
+    /// Where each task's decisions are traced, if anywhere
+    trace: Option<PathBuf>,
+    /// The task's FIFO, when it was started with one
+    fifo: Option<FifoReader>,
+    /// Wait on the process between checks rather than sleep; only with the
+    /// real probe and clock
+    liveness: bool,
 }
 
 impl TaskMonitor {
+    /// Monitor with default settings: 5s polling, 10 minute stall timeout,
+    /// desktop notifications
     pub fn new(db: Database) -> Self {
-        Self {
-            db,
-            poll_interval: Duration::from_secs(5),
-        }
+        MonitorBuilder::new(db).build()
+    }
+
+    pub fn builder(db: Database) -> MonitorBuilder {
+        MonitorBuilder::new(db)
+    }
+
+    /// Where attention notifications go
+    pub fn notifier(&self) -> &Notifier {
+        &self.notifier
     }
 
     /// Monitor a process and update task status when it exits
-    pub fn monitor_task(&self, task_id: String, pid: i32) -> Result<()> {
+    pub fn monitor_task(&self, task_id: String, pid: i32, log: Option<PathBuf>) -> Result<()> {
+        self.monitor_run(task_id, pid, log, None)
+    }
+
+    /// Follow whatever process `source` points at as runs of `task`: each
+    /// time it resolves to a new live process, a new run starts. Returns once
+    /// the task is cleared.
+    pub fn follow(&self, task: Task, source: &dyn PidSource, log: Option<PathBuf>) -> Result<()> {
+        let task_id = task.task_id.clone();
+        let mut last_pid = None;
+        let mut registered = false;
+        let mut waiting = false;
+
         loop {
-            // Check if process is still alive
-            if !is_process_alive(pid) {
+            if registered && self.db.get_task_by_id(&task_id)?.is_none() {
+                return Ok(());
+            }
+            // A pid file left behind by a dead process is not a new run
+            let pid = source
+                .resolve()
+                .filter(|&pid| Some(pid) != last_pid && self.probe.is_alive(pid));
+            let Some(pid) = pid else {
+                if !waiting {
+                    eprintln!("Waiting for a process from {}", source.describe());
+                    waiting = true;
+                }
+                self.clock.sleep(self.poll_interval);
+                continue;
+            };
+            waiting = false;
+            last_pid = Some(pid);
+
+            let mut run = task.clone();
+            run.pid = Some(pid);
+            run.created_at = self.clock.now().into();
+            let stored = self.db.register_task(&run)?;
+            registered = true;
+            let message = match stored.run_history().len() + 1 {
+                1 => format!("pid {} from {}", pid, source.describe()),
+                n => format!("Run {}, pid {} from {}", n, pid, source.describe()),
+            };
+            self.log_event(&task_id, EventKind::Started, message);
+
+            self.monitor_run(task_id.clone(), pid, log.clone(), Some(source))?;
+        }
+    }
+
+    fn monitor_run(
+        &self,
+        task_id: String,
+        pid: i32,
+        log: Option<PathBuf>,
+        source: Option<&dyn PidSource>,
+    ) -> Result<()> {
+        let mut tail = OutputTail::new(log.unwrap_or_else(|| output::default_log_path(&task_id)));
+        let mut tracker = ProgressTracker::new();
+        let mut context = detectors::TaskContext::new(pid, self.clock.now());
+        if let Some(capture) = &self.capture {
+            // Silence counts from the start when we own the pipes
+            context.last_output = Some(capture.last_output().unwrap_or(capture.started()));
+        }
+        let mut files = self.watch_files(&task_id);
+        let mut last_partial = String::new();
+        let mut milestone = 0;
+        let mut recent: VecDeque<String> = VecDeque::with_capacity(RECENT_LINES);
+        // The current attention reason is a rate limit
+        let mut rate_limited = false;
+        let mut usage = UsageTracker::new();
+        // Attention held back while the user was attached, sent on detach,
+        // with the channels rules picked for it
+        let mut held: Option<(Notification, Vec<String>)> = None;
+        // When the current attention need was raised, until someone answers
+        // or `--on-unattended` acts on it
+        let mut unanswered: Option<(DateTime<Utc>, AttentionReason)> = None;
+        // The attention need being reminded about, since when, and how many
+        // reminders each channel has had
+        let mut reminding: Option<(DateTime<Utc>, AttentionReason, HashMap<String, u32>)> = None;
+        // The terminal's input mark at the previous check
+        let mut input_mark = None;
+        // The process's terminal, while it has one
+        let mut terminal: Option<PathBuf> = None;
+        // Reason the classifier ruled out, until the task shows activity
+        let mut dismissed: Option<String> = None;
+        // An incomplete line from the FIFO, and the question last asked
+        // over it, until it is raised
+        let mut fifo_partial = fifo::Partial::default();
+        let mut asked: Option<String> = None;
+        // Lets a daemon starting later tell that the task is watched
+        self.db
+            .set_monitor_pid(&task_id, std::process::id() as i32)?;
+        let mut exit_watch = self.liveness.then(|| ExitWatch::new(pid));
+        let mut trace = self
+            .trace
+            .as_ref()
+            .map(|dir| Trace::new(trace::path(dir, &task_id)));
+        // What /proc won't show of the process, and the detectors that
+        // can't work without it
+        let mut disabled: Vec<&str> = Vec::new();
+        let access = self.probe.access(pid);
+        for gap in access.gaps() {
+            eprintln!("{} (pid {}): {}", task_id, pid, gap.describe());
+            traced(&mut trace, self.clock.now().into(), "access", || {
+                gap.describe().to_string()
+            });
+            disabled.extend(gap.disables());
+        }
+        if !access.stat {
+            // No CPU time to go by, so silence counts from the start
+            context.last_output.get_or_insert(self.clock.now());
+        }
+
+        loop {
+            // Check if process is still alive, and still the one the source
+            // points at
+            let alive = self.probe.is_alive(pid);
+            let replaced = source.and_then(|s| s.resolve()).filter(|&p| p != pid);
+            if !alive || replaced.is_some() {
+                // Exit summaries carry the session's cost
+                self.track_usage(&task_id, &mut usage, &tail.read_new_lines());
                 // Process died, mark as exited
                 if let Some(mut task) = self.db.get_task_by_id(&task_id)? {
-                    // Monitor doesn't know exit code, wrapper will update with correct code
-                    task.set_exited(None);
-                    self.db.update_task(&task)?;
+                    // Monitor doesn't know exit code, wrapper will update with correct code.
+                    // Don't overwrite an outcome the wrapper already reported.
+                    if task.status == TaskStatus::Running {
+                        task.set_exited(None);
+                        self.db.update_task(&task)?;
+                        let message = match replaced {
+                            Some(new_pid) => format!("Replaced by pid {}", new_pid),
+                            None => "Process exited".to_string(),
+                        };
+                        self.log_event(&task_id, EventKind::Exited, message);
+                        self.hooks.fire(&self.db, &task);
+                    }
                 }
                 break;
             }
 
             // Get current task state
-            let task = match self.db.get_task_by_id(&task_id)? {
+            let mut task = match self.db.get_task_by_id(&task_id)? {
                 Some(t) => t,
                 None => {
                     // Task was deleted, stop monitoring
@@ -55,33 +273,2152 @@ impl TaskMonitor {
 
             // Stop monitoring if task is already completed or exited
             if task.status == TaskStatus::Completed || task.status == TaskStatus::Exited {
+                self.track_usage(&task_id, &mut usage, &tail.read_new_lines());
                 break;
             }
 
-            // Sleep before next check
-            thread::sleep(self.poll_interval);
+            let now: DateTime<Utc> = self.clock.now().into();
+            if let Some((notification, channels)) = held.take_if(|_| !self.attached(&task_id)) {
+                let routed = self.notify_via(&task, pid, &notification, &channels);
+                traced(&mut trace, now, "route", || {
+                    format!("User detached: {}", routed)
+                });
+            }
+
+            let mut changed = false;
+            let mut lines = tail.read_new_lines();
+            lines.extend(self.probe.take_output(pid).iter().map(|l| ansi::strip(l)));
+            let printed = !lines.is_empty() || tail.partial() != last_partial;
+            self.track_usage(&task_id, &mut usage, &lines);
+            for line in &lines {
+                tracker.observe(line, now);
+                if recent.len() == RECENT_LINES {
+                    recent.pop_front();
+                }
+                recent.push_back(line.clone());
+            }
+            // Progress and notes from the task count as activity; a
+            // question doesn't
+            let mut told = false;
+            let messages = self
+                .fifo
+                .as_ref()
+                .map(|fifo| fifo.read(&mut fifo_partial))
+                .unwrap_or_default();
+            for message in messages {
+                match message {
+                    fifo::Message::Progress(text) => {
+                        tracker.observe(&text, now);
+                        told = true;
+                    }
+                    fifo::Message::Note(text) => {
+                        self.log_event(&task_id, EventKind::Note, text);
+                        told = true;
+                    }
+                    fifo::Message::NeedInput(question) => asked = Some(question),
+                }
+            }
+            if let Some(progress) = tracker.current() {
+                if task.progress().as_ref() != Some(progress) {
+                    task.set_progress(progress);
+                    changed = true;
+                }
+                let reached = progress.percent() / 25 * 25;
+                if reached > milestone {
+                    milestone = reached;
+                    self.log_event(
+                        &task_id,
+                        EventKind::Progress,
+                        format!("Reached {}%", reached),
+                    );
+                }
+            }
+
+            // The last thing printed, if it is new: a prompt usually has no
+            // trailing newline, so prefer the incomplete line
+            let partial = tail.partial().to_string();
+            let prompt = if partial.is_empty() {
+                lines.last().cloned()
+            } else if partial != last_partial {
+                Some(partial.clone())
+            } else {
+                None
+            };
+            last_partial = partial;
+            // Output, a file change or a keystroke: the task moved on
+            let mut active = printed || told;
+            if active {
+                context.last_output = Some(self.clock.now());
+                self.update_idle_from_output(&mut context);
+            }
+            if let Some(path) = files.as_mut().and_then(|files| files.poll()) {
+                context.last_file = Some((path, self.clock.now()));
+                self.update_idle_from_output(&mut context);
+                active = true;
+            }
+            let mark = self.probe.input_mark(pid);
+            if input_mark.is_some() && mark != input_mark {
+                context.last_input = Some(self.clock.now());
+                self.update_idle_from_output(&mut context);
+                task.set_last_input(now);
+                active = true;
+                changed = true;
+            }
+            input_mark = mark;
+            // No process gets its terminal back, so a loss stays detected
+            match self.probe.terminal(pid) {
+                Some(tty) => terminal = Some(tty),
+                None => context.terminal_lost = context.terminal_lost.take().or(terminal.take()),
+            }
+
+            if let Some(prompt) = prompt {
+                let mut output: Vec<&str> = recent.iter().map(String::as_str).collect();
+                if !last_partial.is_empty() {
+                    output.push(&last_partial);
+                }
+                if self.auto_respond(&mut task, pid, &prompt, &output, now) {
+                    traced(&mut trace, now, "respond", || {
+                        format!("Answered {:?} by itself", prompt)
+                    });
+                    self.db.update_task(&task)?;
+                    self.pause(&mut exit_watch);
+                    self.update_idle(&mut context);
+                    continue;
+                }
+            }
+
+            context.prompt = (!last_partial.is_empty()).then(|| last_partial.clone());
+            let mut output: Vec<&str> = recent.iter().map(String::as_str).collect();
+            if !last_partial.is_empty() {
+                output.push(&last_partial);
+            }
+            context.question =
+                Some(prompt::extract(&output, PROMPT_LINES)).filter(|q| !q.is_empty());
+            context.echo_off = self.probe.reading_password(pid);
+            context.startup_grace = task
+                .startup_grace()
+                .map(Duration::from_secs)
+                .unwrap_or(self.startup_grace);
+            context.new_output = lines;
+            // Misfires marked with `feedback` relax the detectors for this task
+            let since = now - chrono::Duration::days(detectors::FEEDBACK_DAYS);
+            context.false_positives =
+                self.db
+                    .false_positives(&task_id, since)
+                    .unwrap_or_else(|e| {
+                        eprintln!("Failed to read feedback for {}: {:#}", task_id, e);
+                        Default::default()
+                    });
+            traced(&mut trace, now, "check", || {
+                describe_context(&task, &context)
+            });
+            // A question the task asked needs no detecting; otherwise the
+            // first detector to find something wins
+            let mut verdicts = Vec::new();
+            let mut reason = asked.take().map(|question| {
+                AttentionReason::kind(
+                    "waiting_for_input",
+                    Some(question.as_str()).filter(|q| !q.is_empty()),
+                )
+            });
+            let declared = reason.is_some();
+            if declared {
+                verdicts.push("FIFO waiting_for_input".to_string());
+            }
+            let detectors = self
+                .detectors
+                .iter()
+                .filter(|d| disabled.is_empty() || !disabled.contains(&d.name().as_str()));
+            for detector in detectors.take_while(|_| !declared) {
+                reason = detector.check(&task, &context);
+                verdicts.push(format!(
+                    "{} {}",
+                    detector.name(),
+                    reason.as_ref().map_or("-", |r| r.key())
+                ));
+                if reason.is_some() {
+                    break;
+                }
+            }
+            traced(&mut trace, now, "detect", || verdicts.join(", "));
+            if context.idle_duration.is_zero() {
+                dismissed = None;
+            }
+            if active {
+                changed |= task.clear_acknowledged();
+            }
+            // The need the user acknowledged, still there within its cooldown;
+            // another reason is news
+            if let (Some(found), Some(acknowledged), None) =
+                (&reason, task.acknowledged(), &task.attention_reason)
+            {
+                let cooldown = self.reasons.ack_cooldown(found.key());
+                if acknowledged.covers(found.key(), cooldown, now) {
+                    traced(&mut trace, now, "hold", || {
+                        format!(
+                            "{} acknowledged; cooldown {}m",
+                            found.key(),
+                            cooldown.num_minutes()
+                        )
+                    });
+                    reason = None;
+                }
+            }
+            // A new attention need the classifier may rule out, unless the
+            // task said so itself
+            if let (Some(found), Some(gate), None, false) =
+                (&reason, &self.classifier, &task.attention_reason, declared)
+            {
+                let key = found.key().to_string();
+                if dismissed.as_ref() == Some(&key) {
+                    traced(&mut trace, now, "hold", || {
+                        format!("{} ruled out by the classifier until activity", key)
+                    });
+                    reason = None;
+                } else {
+                    let verdict = gate.check(&key, &output, now);
+                    if let Some(verdict) = verdict {
+                        traced(&mut trace, now, "classify", || {
+                            format!("{}: {:?}", key, verdict)
+                        });
+                    }
+                    if verdict == Some(Verdict::NotWaiting) {
+                        self.log_event(
+                            &task_id,
+                            EventKind::Suppressed,
+                            format!(
+                                "Classifier: not waiting on the user ({})",
+                                key.replace('_', " ")
+                            ),
+                        );
+                        dismissed = Some(key);
+                        reason = None;
+                    }
+                }
+            }
+            let backing_off =
+                |reason: &AttentionReason| matches!(reason, AttentionReason::RateLimited { .. });
+            match reason {
+                // A rate limit doesn't hide anything found while backing off
+                Some(reason)
+                    if task.attention_reason.is_none()
+                        || (rate_limited && !backing_off(&reason)) =>
+                {
+                    let mut message = attention_message(&self.reasons, &reason, &task, now);
+                    let stalled = reason == AttentionReason::ProcessStalled;
+                    if let Some((path, at)) = context.last_file.as_ref().filter(|_| stalled) {
+                        let ago = self.clock.now().duration_since(*at).unwrap_or_default();
+                        message.push_str(&format!(
+                            "; last touched {} {} ago",
+                            path.display(),
+                            format_duration(ago.as_secs() as i64)
+                        ));
+                    }
+                    task.set_attention(reason.key(), message.clone());
+                    if let AttentionReason::WaitingApproval(approval) = &reason {
+                        Approval::set_pending(&mut task, Some(approval));
+                    }
+                    changed = true;
+                    rate_limited = backing_off(&reason);
+                    // A rate limit ends by itself; nobody needs to answer it
+                    unanswered = (!rate_limited).then(|| (now, reason.clone()));
+                    reminding = self
+                        .reminders
+                        .covers(reason.key())
+                        .then(|| (now, reason.clone(), HashMap::new()));
+                    let kind = if rate_limited {
+                        EventKind::RateLimited
+                    } else {
+                        EventKind::Attention
+                    };
+                    self.log_event(&task_id, kind, message.clone());
+                    traced(&mut trace, now, "raise", || {
+                        format!(
+                            "{} at {} urgency",
+                            reason.key(),
+                            self.reasons.severity(&reason).as_str()
+                        )
+                    });
+                    let title = format!("[{}] {}", task.agent_type, task.title);
+                    let mut notification = Notification {
+                        title: match self.reasons.icon(&reason) {
+                            "" => title,
+                            icon => format!("{} {}", icon, title),
+                        },
+                        body: message,
+                        urgency: self.reasons.severity(&reason),
+                        category: Category::Attention,
+                        task_id: None,
+                        reason: Some(reason.key().to_string()),
+                    };
+                    if let Some((root, profiler)) = self.stall_snapshot.as_ref().filter(|_| stalled)
+                    {
+                        match snapshot::capture(root, &task_id, pid, profiler.as_deref(), now) {
+                            Ok(path) => {
+                                notification.body = format!(
+                                    "{}\n{}",
+                                    notification.body,
+                                    snapshot::notification_line(&path)
+                                )
+                            }
+                            Err(e) => {
+                                eprintln!("Failed to snapshot stalled task {}: {:#}", task_id, e)
+                            }
+                        }
+                    }
+                    let channels =
+                        self.apply_rules(&task, pid, reason.key(), &output, &mut notification);
+                    match channels {
+                        None => {
+                            traced(&mut trace, now, "route", || {
+                                "Kept quiet by a rule".to_string()
+                            });
+                            held = None;
+                            reminding = None;
+                        }
+                        // The prompt is on the screen of whoever is attached
+                        Some(channels) if self.attached(&task_id) => {
+                            traced(&mut trace, now, "route", || {
+                                "Held until the attached user detaches".to_string()
+                            });
+                            self.log_event(
+                                &task_id,
+                                EventKind::Suppressed,
+                                format!("User attached: {}", notification.body),
+                            );
+                            held = Some((notification, channels));
+                        }
+                        Some(channels) => {
+                            held = None;
+                            let routed = self.notify_via(&task, pid, &notification, &channels);
+                            traced(&mut trace, now, "route", || match channels.as_slice() {
+                                [] => routed,
+                                picked => {
+                                    format!("{} (rules picked {})", routed, picked.join(", "))
+                                }
+                            });
+                        }
+                    }
+                }
+                None if task.attention_reason.is_some() && context.idle_duration.is_zero() => {
+                    // Activity resumed
+                    task.attention_reason = None;
+                    Approval::set_pending(&mut task, None);
+                    held = None;
+                    unanswered = None;
+                    reminding = None;
+                    rate_limited = false;
+                    changed = true;
+                    let message = match stats::record_response(&self.db, &mut task, now) {
+                        Some(secs) => format!("Activity resumed after {}", format_duration(secs)),
+                        None => "Activity resumed".to_string(),
+                    };
+                    traced(&mut trace, now, "clear", || message.clone());
+                    self.log_event(&task_id, EventKind::AttentionCleared, message);
+                    self.notifier.resolved(&task_id);
+                }
+                // Raised once until the task shows activity
+                Some(reason) => traced(&mut trace, now, "hold", || {
+                    format!("{} already raised", reason.key())
+                }),
+                None => {}
+            }
+
+            if let (Some((since, reason)), Some(unattended)) = (&unanswered, task.unattended()) {
+                let waited = (now - *since).num_seconds();
+                if waited >= unattended.after_secs as i64 && !self.attached(&task_id) {
+                    self.act_unattended(&task, pid, &unattended.action, reason, waited, &output);
+                    unanswered = None;
+                }
+            }
+
+            // Acknowledged or answered: nothing to remind about
+            if task.attention_reason.is_none() {
+                reminding = None;
+            }
+            // Low-priority tasks only make the digest
+            let remind = task.priority() != Priority::Low && !self.attached(&task_id);
+            if let Some((since, reason, sent)) = reminding.as_mut().filter(|_| remind) {
+                let waited = (now - *since).to_std().unwrap_or_default();
+                let due = self
+                    .reminders
+                    .due(&self.notifier.active_channel_names(), sent, waited);
+                if !due.is_empty() {
+                    let notification = reminder(&self.reasons, &task, reason, waited);
+                    let routed = self.notify_via(&task, pid, &notification, &due);
+                    traced(&mut trace, now, "remind", || routed);
+                }
+            }
+
+            if changed {
+                self.db.update_task(&task)?;
+            }
+
+            // Wait for the next check, or the process's exit
+            self.pause(&mut exit_watch);
+            self.update_idle(&mut context);
         }
 
         Ok(())
     }
 }
 
-fn is_process_alive(pid: i32) -> bool {
-    // Check if /proc/<pid> exists
-    std::path::Path::new(&format!("/proc/{}", pid)).exists()
+/// Builder for [`TaskMonitor`]
+pub struct MonitorBuilder {
+    db: Database,
+    poll_interval: Duration,
+    stall_timeout: Duration,
+    startup_grace: Duration,
+    error_burst: Option<(usize, Duration)>,
+    looping: Option<(usize, f64)>,
+    notifier: Option<Notifier>,
+    detectors: Vec<Box<dyn AttentionDetector>>,
+    probe: Option<Box<dyn ProcessProbe>>,
+    clock: Option<Box<dyn Clock>>,
+    responder: AutoResponder,
+    input: Option<Box<dyn InputSink>>,
+    presence: Option<Box<dyn Presence>>,
+    capture: Option<OutputCapture>,
+    hooks: Hooks,
+    reasons: ReasonRegistry,
+    rules: Rules,
+    reminders: Reminders,
+    daily_budget: Option<f64>,
+    file_activity: Option<Vec<String>>,
+    stall_snapshot: Option<(PathBuf, Option<String>)>,
+    classifier: Option<Gate>,
+    trace: Option<PathBuf>,
+    fifo: Option<FifoReader>,
+}
+
+impl MonitorBuilder {
+    pub fn new(db: Database) -> Self {
+        Self {
+            db,
+            poll_interval: Duration::from_secs(5),
+            stall_timeout: Duration::from_secs(600), // 10 minutes
+            startup_grace: detectors::STARTUP_GRACE,
+            error_burst: Some((10, Duration::from_secs(120))),
+            looping: Some((5, 0.8)),
+            notifier: None,
+            detectors: Vec::new(),
+            probe: None,
+            clock: None,
+            responder: AutoResponder::default(),
+            input: None,
+            presence: None,
+            capture: None,
+            hooks: Hooks::default(),
+            reasons: ReasonRegistry::default(),
+            rules: Rules::default(),
+            reminders: Reminders::default(),
+            daily_budget: None,
+            file_activity: None,
+            stall_snapshot: None,
+            classifier: None,
+            trace: None,
+            fifo: None,
+        }
+    }
+
+    /// Monitor set up like the CLI's: channels from `[notify]` plus the
+    /// detectors and channels of installed plugins, auto-respond rules and
+    /// presence detection
+    pub fn from_config(db: Database, config: &Config) -> Result<Self> {
+        let loaded = plugins::load_all(
+            plugins::default_plugin_dir(),
+            &config.plugins,
+            &config.sandbox,
+        )?;
+
+        let mut notifier = NotifierBuilder::from_config(&config.notify)?;
+        for channel in loaded.channels {
+            notifier = notifier.channel(channel);
+        }
+        let mut builder = Self::new(db)
+            .notifier(notifier.build())
+            .auto_respond(
+                AutoResponder::new(&config.auto_respond)?.deny(&config.auto_respond_deny)?,
+            )
+            .hooks(Hooks::from_config(config)?)
+            .reasons(ReasonRegistry::from_config(&config.reasons))
+            .rules(Rules::new(&config.rules, &config.sandbox)?)
+            .reminders(Reminders::from_config(&config.reminders))
+            .error_burst(
+                config.error_burst.threshold,
+                Duration::from_secs(config.error_burst.window_secs),
+            )
+            .looping(config.looping.repeats, config.looping.similarity)
+            .startup_grace(Duration::from_secs(config.monitor.startup_grace_secs))
+            .trace(trace::dir());
+        for detector in loaded.detectors {
+            builder = builder.detector(detector);
+        }
+        if let Some(presence) = SystemPresence::from_config(&config.presence) {
+            builder = builder.presence(Box::new(presence));
+        }
+        if let Some(budget) = config.costs.daily_budget_usd {
+            builder = builder.daily_budget(budget);
+        }
+        if config.file_activity.enabled {
+            builder = builder.file_activity(config.file_activity.ignore.clone());
+        }
+        if config.artifacts.stall_snapshot {
+            builder = builder.stall_snapshot(
+                artifacts::root(&config.artifacts),
+                config.artifacts.profiler.clone(),
+            );
+        }
+        if let Some(gate) = Gate::from_config(&config.classifier) {
+            builder =
+                builder.classifier(gate.secrets(SecretFilter::from_config(&config.notify.redact)?));
+        }
+        Ok(builder)
+    }
+
+    /// How often the process and its output are checked
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// How long the process tree may burn no CPU before it counts as stalled
+    pub fn stall_timeout(mut self, timeout: Duration) -> Self {
+        self.stall_timeout = timeout;
+        self
+    }
+
+    /// How long after a task starts stalls and blocked reads aren't flagged
+    /// (10 seconds by default), unless the task sets its own
+    pub fn startup_grace(mut self, grace: Duration) -> Self {
+        self.startup_grace = grace;
+        self
+    }
+
+    /// Flag the task when `threshold` error lines appear within `window`
+    /// (10 in two minutes by default); 0 turns it off
+    pub fn error_burst(mut self, threshold: usize, window: Duration) -> Self {
+        self.error_burst = (threshold > 0).then_some((threshold, window));
+        self
+    }
+
+    /// Flag the task when `repeats` recent blocks of output share at least
+    /// `similarity` of their lines (5 blocks at 0.8 by default); 0 turns it off
+    pub fn looping(mut self, repeats: usize, similarity: f64) -> Self {
+        self.looping = (repeats > 0).then_some((repeats, similarity));
+        self
+    }
+
+    /// Notify when the day's spend reported by all tasks crosses `usd`
+    pub fn daily_budget(mut self, usd: f64) -> Self {
+        self.daily_budget = Some(usd);
+        self
+    }
+
+    /// Count changes under the task's working directory as activity,
+    /// skipping directories with these names
+    pub fn file_activity(mut self, ignore: Vec<String>) -> Self {
+        self.file_activity = Some(ignore);
+        self
+    }
+
+    /// Save a diagnostic snapshot under `root` when the process stalls,
+    /// with the output of `profiler` if given; see [`artifacts::snapshot`]
+    pub fn stall_snapshot(mut self, root: PathBuf, profiler: Option<String>) -> Self {
+        self.stall_snapshot = Some((root, profiler));
+        self
+    }
+
+    /// Ask `gate`'s model before notifying about an uncertain detection;
+    /// see [`classify`]
+    pub fn classifier(mut self, gate: Gate) -> Self {
+        self.classifier = Some(gate);
+        self
+    }
+
+    /// Write each task's decisions to `<dir>/<task>.log` for `debug trace`;
+    /// see [`trace`]
+    pub fn trace(mut self, dir: PathBuf) -> Self {
+        self.trace = Some(dir);
+        self
+    }
+
+    /// Read the messages the task writes to its FIFO; see [`fifo`]
+    pub fn fifo(mut self, reader: FifoReader) -> Self {
+        self.fifo = Some(reader);
+        self
+    }
+
+    /// Notifier for attention events (defaults to desktop notifications)
+    pub fn notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Run an additional detector after the built-in password-prompt,
+    /// rate-limit, stall and error-burst detectors
+    pub fn detector(mut self, detector: Box<dyn AttentionDetector>) -> Self {
+        self.detectors.push(detector);
+        self
+    }
+
+    /// Rules for replying to known prompts; they only fire when an
+    /// [`input`](Self::input) is set
+    pub fn auto_respond(mut self, responder: AutoResponder) -> Self {
+        self.responder = responder;
+        self
+    }
+
+    /// Where auto-responses are typed (the task's stdin or terminal)
+    pub fn input(mut self, input: Box<dyn InputSink>) -> Self {
+        self.input = Some(input);
+        self
+    }
+
+    /// Hold back non-critical notifications while the user is watching the
+    /// task's terminal
+    pub fn presence(mut self, presence: Box<dyn Presence>) -> Self {
+        self.presence = Some(presence);
+        self
+    }
+
+    /// The task's output is captured through our own pipes: measure idle
+    /// time from the exact time of the last output byte
+    pub fn capture(mut self, capture: OutputCapture) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// Completion hooks fired when the monitor finds the process gone
+    pub fn hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Replace the /proc probe, e.g. with a scripted fake in tests
+    pub fn probe(mut self, probe: Box<dyn ProcessProbe>) -> Self {
+        self.probe = Some(probe);
+        self
+    }
+
+    /// Severity, icon and message for each kind of attention reason
+    pub fn reasons(mut self, reasons: ReasonRegistry) -> Self {
+        self.reasons = reasons;
+        self
+    }
+
+    /// Actions for attention needs under compound conditions: `[[rules]]`
+    pub fn rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Send attention needs again while they go unanswered; see [`remind`]
+    pub fn reminders(mut self, reminders: Reminders) -> Self {
+        self.reminders = reminders;
+        self
+    }
+
+    /// Replace the wall clock, e.g. with a fake clock in tests
+    pub fn clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// The detectors the monitor would run, in order: the built-in ones,
+    /// then any added with [`detector`](Self::detector)
+    pub fn into_detectors(mut self) -> Vec<Box<dyn AttentionDetector>> {
+        self.take_detectors()
+    }
+
+    fn take_detectors(&mut self) -> Vec<Box<dyn AttentionDetector>> {
+        let mut detectors: Vec<Box<dyn AttentionDetector>> = vec![
+            Box::new(TerminalLostDetector::new()),
+            Box::new(PasswordPromptDetector::new()),
+            Box::new(ApprovalDetector::new()),
+            Box::new(RateLimitDetector::new()),
+            Box::new(StallDetector::new(self.stall_timeout)),
+        ];
+        if let Some((threshold, window)) = self.error_burst {
+            detectors.push(Box::new(ErrorBurstDetector::new(threshold, window)));
+        }
+        if let Some((repeats, similarity)) = self.looping {
+            detectors.push(Box::new(LoopingDetector::new(repeats, similarity)));
+        }
+        detectors.append(&mut self.detectors);
+        detectors
+    }
+
+    pub fn build(mut self) -> TaskMonitor {
+        let detectors = self.take_detectors();
+        let liveness = self.probe.is_none() && self.clock.is_none();
+
+        TaskMonitor {
+            db: self.db,
+            poll_interval: self.poll_interval,
+            startup_grace: self.startup_grace,
+            notifier: self.notifier.unwrap_or_default(),
+            detectors,
+            probe: self.probe.unwrap_or_else(|| Box::new(ProcProbe)),
+            clock: self.clock.unwrap_or_else(|| Box::new(SystemClock)),
+            responder: self.responder,
+            input: self.input,
+            presence: self.presence,
+            capture: self.capture,
+            hooks: self.hooks,
+            reasons: self.reasons,
+            rules: self.rules,
+            reminders: self.reminders,
+            daily_budget: self.daily_budget,
+            file_activity: self.file_activity,
+            stall_snapshot: self.stall_snapshot,
+            classifier: self.classifier,
+            trace: self.trace,
+            fifo: self.fifo,
+            liveness,
+        }
+    }
+}
+
+impl TaskMonitor {
+    /// Sleep until the next check; with an exit watch, return early when
+    /// the process exits
+    fn pause(&self, exit_watch: &mut Option<ExitWatch>) {
+        match exit_watch {
+            Some(watch) => {
+                watch.wait(self.poll_interval);
+            }
+            None => self.clock.sleep(self.poll_interval),
+        }
+    }
+
+    /// Reply to `prompt` if an auto-respond rule allows it, or escalate if
+    /// the deny-list blocks the reply. `output` is the recent output, the
+    /// prompt last. Returns true when either happened.
+    fn auto_respond(
+        &self,
+        task: &mut Task,
+        pid: i32,
+        prompt: &str,
+        output: &[&str],
+        now: DateTime<Utc>,
+    ) -> bool {
+        let Some(input) = &self.input else {
+            return false;
+        };
+        let excerpt = output[output.len().saturating_sub(EXCERPT_LINES)..].join("\n");
+        let reply = match self.responder.decide(task, prompt, &excerpt) {
+            None => return false,
+            Some(Decision::Reply(reply)) => reply,
+            Some(Decision::Blocked { rule, matched }) => {
+                eprintln!(
+                    "Blocked auto-respond '{}': output matches {:?}",
+                    rule, matched
+                );
+                let reason = format!("Destructive prompt needs review (matched \"{}\")", matched);
+                self.log_event(&task.task_id, EventKind::Attention, reason.clone());
+                task.attention_reason = Some(reason);
+                self.notify(
+                    task,
+                    pid,
+                    &Notification {
+                        title: format!("[{}] {}", task.agent_type, task.title),
+                        body: format!(
+                            "Auto-response blocked: output matches \"{}\"\n\n{}",
+                            matched,
+                            prompt::extract(output, PROMPT_LINES)
+                        ),
+                        urgency: Urgency::Critical,
+                        category: Category::Attention,
+                        task_id: None,
+                        reason: None,
+                    },
+                );
+                return true;
+            }
+        };
+
+        if let Err(e) = input.send_line(&reply.response) {
+            eprintln!("Auto-respond '{}' failed: {:#}", reply.rule, e);
+            return false;
+        }
+        eprintln!(
+            "Auto-responded {:?} to {:?} (rule '{}')",
+            reply.response, prompt, reply.rule
+        );
+
+        self.log_event(
+            &task.task_id,
+            EventKind::AutoResponded,
+            format!(
+                "\"{}\" to: {} (rule '{}')",
+                reply.response,
+                prompt.trim(),
+                reply.rule
+            ),
+        );
+        respond::record(
+            task,
+            AutoResponse {
+                at: now,
+                rule: reply.rule.clone(),
+                prompt: prompt.trim().to_string(),
+                response: reply.response.clone(),
+            },
+        );
+        self.notify(
+            task,
+            pid,
+            &Notification {
+                title: format!("[{}] {}", task.agent_type, task.title),
+                body: format!(
+                    "Auto-responded \"{}\" to: {}",
+                    reply.response,
+                    prompt.trim()
+                ),
+                urgency: Urgency::Low,
+                category: Category::Info,
+                task_id: None,
+                reason: None,
+            },
+        );
+        true
+    }
+
+    /// Whether someone follows the task with `attach` or `tail -f`
+    fn attached(&self, task_id: &str) -> bool {
+        attached::is_attached(&self.db, task_id).unwrap_or_else(|e| {
+            eprintln!("Failed to check who follows {}: {:#}", task_id, e);
+            false
+        })
+    }
+
+    /// Carry out the `[[rules]]` matching this attention need, escalating
+    /// `notification` if one says so. Returns the channels rules send it to
+    /// (empty: as routed), or None when a rule keeps it quiet.
+    fn apply_rules(
+        &self,
+        task: &Task,
+        pid: i32,
+        reason: &str,
+        output: &[&str],
+        notification: &mut Notification,
+    ) -> Option<Vec<String>> {
+        let text = output.join("\n");
+        let occurrence = Occurrence {
+            task,
+            reason,
+            output: &text,
+            now: self.clock.now(),
+        };
+        let mut channels = Vec::new();
+        let mut quiet = false;
+        for (name, then) in self.rules.matching(&occurrence) {
+            let mut done = Vec::new();
+            if then.escalate {
+                notification.urgency = Urgency::Critical;
+                done.push("escalated".to_string());
+            }
+            if let Some(text) = &then.respond {
+                done.push(match self.type_reply(pid, reason, text, output) {
+                    Typed::Done(done) => done,
+                    Typed::Password => "not answering a password prompt".to_string(),
+                    Typed::Denied(matched) => {
+                        notification.urgency = Urgency::Critical;
+                        notification.body = format!(
+                            "{}\nNot answered: output matches \"{}\"",
+                            notification.body, matched
+                        );
+                        format!("escalated, not answering: output matches \"{}\"", matched)
+                    }
+                });
+            }
+            if then.kill {
+                done.push(match self.probe.terminate(pid) {
+                    Ok(()) => "killed the process".to_string(),
+                    Err(e) => format!("failed to kill: {:#}", e),
+                });
+            }
+            if let Some(command) = &then.run {
+                done.push(match self.hooks.run_command(command, task) {
+                    Ok(()) => format!("ran `{}`", command),
+                    Err(e) => format!("failed to run `{}`: {:#}", command, e),
+                });
+            }
+            if !then.channels.is_empty() {
+                done.push(format!("sent to {}", then.channels.join(", ")));
+                channels.extend(then.channels.iter().cloned());
+            }
+            if !then.notify {
+                quiet = true;
+                done.push("no notification".to_string());
+            }
+            eprintln!("Rule '{}' for {}: {}", name, task.task_id, done.join(", "));
+            self.log_event(
+                &task.task_id,
+                EventKind::Rule,
+                format!("'{}': {}", name, done.join(", ")),
+            );
+        }
+        (!quiet).then_some(channels)
+    }
+
+    /// Carry out the task's `--on-unattended` action after an attention
+    /// need went `waited` seconds without an answer, and report what was
+    /// done
+    fn act_unattended(
+        &self,
+        task: &Task,
+        pid: i32,
+        action: &UnattendedAction,
+        reason: &AttentionReason,
+        waited: i64,
+        output: &[&str],
+    ) {
+        let mut urgency = Urgency::Normal;
+        let done = match action {
+            UnattendedAction::Wait => return,
+            UnattendedAction::Kill => match self.probe.terminate(pid) {
+                Ok(()) => "killed the process".to_string(),
+                Err(e) => format!("failed to kill: {:#}", e),
+            },
+            UnattendedAction::Respond(answer) => {
+                match self.type_reply(pid, reason.key(), answer, output) {
+                    Typed::Done(done) => done,
+                    // A default answer is never a password
+                    Typed::Password => "not answering a password prompt, still waiting".to_string(),
+                    Typed::Denied(matched) => {
+                        urgency = Urgency::Critical;
+                        format!(
+                            "not answering, output matches \"{}\"; needs review",
+                            matched
+                        )
+                    }
+                }
+            }
+        };
+        let message = format!("No answer for {}: {}", format_duration(waited), done);
+        eprintln!("{}: {}", task.task_id, message);
+        self.log_event(&task.task_id, EventKind::Unattended, message.clone());
+        self.notify(
+            task,
+            pid,
+            &Notification {
+                title: format!("[{}] {}", task.agent_type, task.title),
+                body: match &task.attention_reason {
+                    Some(attention) => format!("{}\n{}", message, attention),
+                    None => message,
+                },
+                urgency,
+                category: if urgency == Urgency::Critical {
+                    Category::Attention
+                } else {
+                    Category::Info
+                },
+                task_id: None,
+                reason: None,
+            },
+        );
+    }
+
+    /// Type `text` into the task for a rule or `--on-unattended`. Like
+    /// auto-responses, never at a password prompt, nor when the prompt or
+    /// the output before it match the deny-list. `output` ends with the
+    /// prompt.
+    fn type_reply(&self, pid: i32, reason: &str, text: &str, output: &[&str]) -> Typed {
+        let prompt = output.last().copied().unwrap_or_default();
+        if reason == AttentionReason::PasswordPrompt.key()
+            || redact::is_password_prompt(prompt)
+            || self.probe.reading_password(pid)
+        {
+            return Typed::Password;
+        }
+        let excerpt = output[output.len().saturating_sub(EXCERPT_LINES)..].join("\n");
+        if let Some(matched) = self.responder.denied(prompt, &excerpt) {
+            return Typed::Denied(matched);
+        }
+        let sent = match &self.input {
+            Some(input) => input.send_line(text),
+            None => Err(anyhow::anyhow!("no input for this task")),
+        };
+        Typed::Done(match sent {
+            Ok(()) => format!("replied \"{}\"", text),
+            Err(e) => format!("failed to reply: {:#}", e),
+        })
+    }
+
+    /// Send a notification unless the user is already at the task's
+    /// terminal; critical notifications always go out
+    fn notify(&self, task: &Task, pid: i32, notification: &Notification) {
+        self.notify_via(task, pid, notification, &[]);
+    }
+
+    /// Like [`notify`](Self::notify), to `channels` only unless empty.
+    /// Returns what became of it, for the trace.
+    fn notify_via(
+        &self,
+        task: &Task,
+        pid: i32,
+        notification: &Notification,
+        channels: &[String],
+    ) -> String {
+        let urgent =
+            notification.urgency == Urgency::Critical || task.priority() == Priority::Critical;
+        let watching = !urgent && self.presence.as_ref().is_some_and(|p| p.watching(pid));
+        if watching {
+            eprintln!(
+                "User is at the terminal, not notifying: {}",
+                notification.body
+            );
+            let message = format!(
+                "User at the terminal: {}",
+                notification.body.lines().next().unwrap_or_default()
+            );
+            self.log_event(&task.task_id, EventKind::Suppressed, message.clone());
+            return message;
+        }
+        let notification = Notification {
+            task_id: Some(task.task_id.clone()),
+            ..notification.clone()
+        };
+        let delivered = if channels.is_empty() {
+            self.notifier.notify(&notification)
+        } else {
+            self.notifier.notify_via(&notification, channels)
+        };
+        let (kind, message) = notify::delivery_event(&notification, &delivered);
+        self.log_event(&task.task_id, kind, message.clone());
+        message
+    }
+
+    /// Store what the usage lines among `lines` add to the task's spend,
+    /// and notify once when the day's spend crosses the budget
+    fn track_usage(&self, task_id: &str, tracker: &mut UsageTracker, lines: &[String]) {
+        let mut added = Usage::default();
+        for usage in lines.iter().filter_map(|line| tracker.observe(line)) {
+            added += usage;
+        }
+        if added.is_zero() {
+            return;
+        }
+        let (before, after) = match costs::record(&self.db, task_id, &added) {
+            Ok(totals) => totals,
+            Err(e) => {
+                eprintln!("Failed to record usage for {}: {}", task_id, e);
+                return;
+            }
+        };
+        let Some(budget) = self
+            .daily_budget
+            .filter(|&b| costs::crossed(b, before, after))
+        else {
+            return;
+        };
+        let notification = Notification {
+            title: "Agent spend".to_string(),
+            body: format!(
+                "Today's agent spend crossed {} ({} so far)",
+                costs::format_cost(budget),
+                costs::format_cost(after)
+            ),
+            urgency: Urgency::Normal,
+            category: Category::Info,
+            task_id: None,
+            reason: None,
+        };
+        let delivered = self.notifier.notify(&notification);
+        let (kind, message) = notify::delivery_event(&notification, &delivered);
+        self.log_event(task_id, kind, message);
+    }
+
+    /// Add to the task's activity log, timestamped by the monitor's clock
+    fn log_event(&self, task_id: &str, kind: EventKind, message: impl Into<String>) {
+        let event = TaskEvent::new(task_id, kind, message).at(self.clock.now().into());
+        if let Err(e) = self.db.record_event(&event) {
+            eprintln!(
+                "Failed to record {} event for {}: {}",
+                kind.as_str(),
+                task_id,
+                e
+            );
+        }
+    }
+
+    /// Track how long the process tree has been burning no CPU and how
+    /// long its output has been silent
+    fn update_idle(&self, context: &mut detectors::TaskContext) {
+        let now = self.clock.now();
+        let elapsed = now.duration_since(context.last_check).unwrap_or_default();
+        let cpu = self.probe.cpu_time(context.pid);
+
+        if cpu.is_some() && cpu == context.last_cpu_time {
+            context.cpu_idle += elapsed;
+        } else {
+            context.cpu_idle = Duration::ZERO;
+        }
+        context.last_cpu_time = cpu;
+        context.last_check = now;
+        self.update_idle_from_output(context);
+    }
+
+    /// Output silence is what users care about, so it wins over CPU idle
+    /// once output is being captured. A recent file change or keystroke
+    /// still counts as activity.
+    fn update_idle_from_output(&self, context: &mut detectors::TaskContext) {
+        if let Some(captured) = self.capture.as_ref().and_then(|c| c.last_output()) {
+            // Exact, and possibly newer than what the log tail has shown
+            context.last_output = context.last_output.max(Some(captured));
+        }
+        context.output_idle = context
+            .last_output
+            .map(|at| self.clock.now().duration_since(at).unwrap_or_default());
+        context.idle_duration = context.output_idle.unwrap_or(context.cpu_idle);
+        if let Some((_, at)) = &context.last_file {
+            let file_idle = self.clock.now().duration_since(*at).unwrap_or_default();
+            context.idle_duration = context.idle_duration.min(file_idle);
+        }
+        context.input_idle = context
+            .last_input
+            .map(|at| self.clock.now().duration_since(at).unwrap_or_default());
+        if let Some(input_idle) = context.input_idle {
+            context.idle_duration = context.idle_duration.min(input_idle);
+        }
+    }
+
+    /// Start watching the task's working directory, with `[file_activity]`
+    /// on; a directory that can't be watched just leaves files out
+    fn watch_files(&self, task_id: &str) -> Option<Box<dyn files::FileActivity>> {
+        let ignore = self.file_activity.as_ref()?;
+        let task = self.db.get_task_by_id(task_id).ok()??;
+        let dir = task.context?.project_path?;
+        match files::watch(std::path::Path::new(&dir), ignore) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                eprintln!("Not watching {} for file activity: {:#}", dir, e);
+                None
+            }
+        }
+    }
+}
+
+/// Lines of output kept for rules and for finding the question a prompt
+/// asks
+const RECENT_LINES: usize = 40;
+
+/// Lines before a prompt checked against the auto-respond deny-list
+const EXCERPT_LINES: usize = 6;
+
+/// What came of a reply typed for a rule or `--on-unattended`
+enum Typed {
+    /// Sent, or failed to: which
+    Done(String),
+    /// Not sent: the task waits for a password
+    Password,
+    /// Not sent: the output matches the deny-list, with this text
+    Denied(String),
+}
+
+/// Most lines of a prompt quoted when an auto-response is blocked
+const PROMPT_LINES: usize = 15;
+
+/// Attention text enriched with progress, e.g.
+/// "Process stalled (no activity) at 80% after 40m (ETA was 10m)"
+fn attention_message(
+    reasons: &ReasonRegistry,
+    reason: &AttentionReason,
+    task: &Task,
+    now: DateTime<Utc>,
+) -> String {
+    let text = reasons.describe(reason);
+    let Some(progress) = task.progress() else {
+        return text;
+    };
+
+    let age = (now - task.created_at).num_seconds();
+    let mut message = format!(
+        "{} at {}% after {}",
+        text,
+        progress.percent(),
+        format_duration(age)
+    );
+    if let Some(eta) = progress.eta_secs {
+        message.push_str(&format!(" (ETA was {})", format_duration(eta)));
+    }
+    message
+}
+
+/// Add a line to the task's trace, if it is traced; `message` is only
+/// built then
+fn traced(
+    trace: &mut Option<Trace>,
+    now: DateTime<Utc>,
+    stage: &str,
+    message: impl FnOnce() -> String,
+) {
+    if let Some(trace) = trace {
+        trace.record(now, stage, &message());
+    }
+}
+
+/// Attention need `reason` of `task` again, `waited` after it was raised
+fn reminder(
+    reasons: &ReasonRegistry,
+    task: &Task,
+    reason: &AttentionReason,
+    waited: Duration,
+) -> Notification {
+    let title = format!("[{}] {}", task.agent_type, task.title);
+    Notification {
+        title: match reasons.icon(reason) {
+            "" => title,
+            icon => format!("{} {}", icon, title),
+        },
+        body: format!(
+            "Still waiting after {}: {}",
+            format_duration(waited.as_secs() as i64),
+            task.attention_reason
+                .clone()
+                .unwrap_or_else(|| reason.as_str())
+        ),
+        urgency: reasons.severity(reason),
+        category: Category::Attention,
+        task_id: None,
+        reason: Some(reason.key().to_string()),
+    }
+}
+
+/// What the detectors go by at this check
+fn describe_context(task: &Task, context: &detectors::TaskContext) -> String {
+    let secs = |d: Duration| format_duration(d.as_secs() as i64);
+    let mut parts = vec![format!(
+        "idle {} (output {}, cpu {})",
+        secs(context.idle_duration),
+        context.output_idle.map_or("unseen".to_string(), secs),
+        secs(context.cpu_idle)
+    )];
+    parts.push(if context.starting_up(task) {
+        format!("within the {} startup grace", secs(context.startup_grace))
+    } else {
+        "startup grace over".to_string()
+    });
+    let mut relaxed: Vec<String> = context
+        .false_positives
+        .keys()
+        .map(|key| format!("{} {:.1}x", key, context.tolerance(key)))
+        .collect();
+    relaxed.sort();
+    if !relaxed.is_empty() {
+        parts.push(format!("relaxed by feedback: {}", relaxed.join(", ")));
+    }
+    if context.echo_off {
+        parts.push("reading with echo off".to_string());
+    }
+    parts.join("; ")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::AutoRespondRule;
+    use crate::models::Progress;
+    use crate::monitor::access::Access;
+    use crate::testing::{FakeClock, FakePresence, FakeProcess, MockChannel, MockInput};
+    use std::sync::Mutex;
+    use tempfile::NamedTempFile;
+
+    /// Run a scripted process through the monitor; returns the sent
+    /// notifications and the final task row
+    fn simulate(process: FakeProcess) -> (Vec<Notification>, Task) {
+        simulate_with(process, |builder| builder)
+    }
+
+    fn simulate_with(
+        process: FakeProcess,
+        configure: impl FnOnce(MonitorBuilder) -> MonitorBuilder,
+    ) -> (Vec<Notification>, Task) {
+        let (sent, task, _) = simulate_events(process, configure);
+        (sent, task)
+    }
+
+    /// Like [`simulate_with`], also returning the task's activity log
+    fn simulate_events(
+        process: FakeProcess,
+        configure: impl FnOnce(MonitorBuilder) -> MonitorBuilder,
+    ) -> (Vec<Notification>, Task, Vec<TaskEvent>) {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Database::open(db_file.path()).unwrap();
+        let clock = FakeClock::default();
+        let mut task = Task::new(
+            "sim".to_string(),
+            "claude_code".to_string(),
+            "Simulated".to_string(),
+            Some(1),
+            None,
+        );
+        task.created_at = clock.now_utc();
+        db.insert_task(&task).unwrap();
+
+        let channel = MockChannel::new();
+        let builder = MonitorBuilder::new(db)
+            .stall_timeout(Duration::from_secs(60))
+            .probe(Box::new(process))
+            .clock(Box::new(clock))
+            .notifier(
+                Notifier::builder()
+                    .channel(Box::new(channel.clone()))
+                    .build(),
+            );
+        configure(builder)
+            .build()
+            .monitor_task("sim".to_string(), 1, Some("/nonexistent/sim.log".into()))
+            .unwrap();
+
+        let db = Database::open(db_file.path()).unwrap();
+        let task = db.get_task_by_id("sim").unwrap().unwrap();
+        (channel.sent(), task, db.task_events("sim").unwrap())
+    }
+
+    #[test]
+    fn test_password_prompt_is_critical() {
+        let db_file = NamedTempFile::new().unwrap();
+        let log = NamedTempFile::new().unwrap();
+        std::fs::write(log.path(), "Installing\n[sudo] password for bob: ").unwrap();
+        let db = Database::open(db_file.path()).unwrap();
+        let task = Task::new("sim".into(), "tool".into(), "Install".into(), Some(1), None);
+        db.insert_task(&task).unwrap();
+
+        let channel = MockChannel::new();
+        MonitorBuilder::new(db)
+            .probe(Box::new(FakeProcess::new().busy(3)))
+            .clock(Box::new(FakeClock::default()))
+            .notifier(
+                Notifier::builder()
+                    .channel(Box::new(channel.clone()))
+                    .build(),
+            )
+            .build()
+            .monitor_task("sim".into(), 1, Some(log.path().to_path_buf()))
+            .unwrap();
+
+        let sent = channel.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].body, "Waiting for a password");
+        assert_eq!(sent[0].urgency, Urgency::Critical);
+    }
+
+    #[test]
+    fn test_approval_prompt_is_its_own_reason() {
+        let db_file = NamedTempFile::new().unwrap();
+        let log = NamedTempFile::new().unwrap();
+        std::fs::write(
+            log.path(),
+            "Thinking\n\nrm -rf target\nRun shell command? (Y)es/(N)o/(D)on't ask again [Yes]: ",
+        )
+        .unwrap();
+        let db = Database::open(db_file.path()).unwrap();
+        let task = Task::new(
+            "sim".into(),
+            "aider".into(),
+            "Clean up".into(),
+            Some(1),
+            None,
+        );
+        db.insert_task(&task).unwrap();
+
+        let channel = MockChannel::new();
+        MonitorBuilder::new(db)
+            .probe(Box::new(FakeProcess::new().busy(3)))
+            .clock(Box::new(FakeClock::default()))
+            .notifier(
+                Notifier::builder()
+                    .channel(Box::new(channel.clone()))
+                    .build(),
+            )
+            .build()
+            .monitor_task("sim".into(), 1, Some(log.path().to_path_buf()))
+            .unwrap();
+
+        let sent = channel.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].body, "Waiting for approval: rm -rf target");
+        assert_eq!(sent[0].reason.as_deref(), Some("waiting_approval"));
+        let task = Database::open(db_file.path())
+            .unwrap()
+            .get_task_by_id("sim")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Approval::pending(&task).map(|a| a.approve),
+            Some("y".to_string())
+        );
+    }
+
+    #[test]
+    fn test_attached_holds_attention() {
+        let db_file = NamedTempFile::new().unwrap();
+        let log = NamedTempFile::new().unwrap();
+        std::fs::write(log.path(), "Installing\n[sudo] password for bob: ").unwrap();
+        let db = Database::open(db_file.path()).unwrap();
+        let task = Task::new("sim".into(), "tool".into(), "Install".into(), Some(1), None);
+        db.insert_task(&task).unwrap();
+        // This test process plays the terminal attached to the task
+        let _attachment =
+            attached::Attachment::start(db_file.path().to_path_buf(), "sim", "attach").unwrap();
+
+        let channel = MockChannel::new();
+        MonitorBuilder::new(db)
+            .probe(Box::new(FakeProcess::new().busy(3)))
+            .clock(Box::new(FakeClock::default()))
+            .notifier(
+                Notifier::builder()
+                    .channel(Box::new(channel.clone()))
+                    .build(),
+            )
+            .build()
+            .monitor_task("sim".into(), 1, Some(log.path().to_path_buf()))
+            .unwrap();
+
+        assert!(channel.sent().is_empty());
+        let events = Database::open(db_file.path())
+            .unwrap()
+            .task_events("sim")
+            .unwrap();
+        assert!(events.iter().any(|e| e.kind == EventKind::Suppressed
+            && e.message == "User attached: Waiting for a password"));
+    }
+
+    #[test]
+    fn test_rate_limit_is_low_severity() {
+        let process = FakeProcess::new()
+            .busy(2)
+            .print(&["API Error: 429 Too Many Requests", "Retrying in 30 seconds"])
+            .idle(3)
+            .print(&["Resuming"])
+            .busy(2);
+        let (sent, _, events) = simulate_events(process, |builder| builder);
+
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].title, "⏱ [claude_code] Simulated");
+        assert_eq!(sent[0].body, "Rate limited: retrying in 30s");
+        assert_eq!(sent[0].urgency, Urgency::Low);
+        let kinds: Vec<EventKind> = events.iter().map(|e| e.kind).collect();
+        assert!(kinds.contains(&EventKind::RateLimited));
+        assert!(!kinds.contains(&EventKind::Attention));
+        assert!(kinds.contains(&EventKind::AttentionCleared));
+    }
+
+    #[test]
+    fn test_error_burst_while_running() {
+        let errors = ["error[E0425]: cannot find value `x` in this scope"; 4];
+        let process = FakeProcess::new()
+            .busy(2)
+            .print(&errors)
+            .print(&["   Compiling agent", "error: could not compile `agent`"])
+            .busy(3);
+        let (sent, task) = simulate_with(process, |b| b.error_burst(5, Duration::from_secs(60)));
+
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].title, "🔥 [claude_code] Simulated");
+        assert_eq!(
+            sent[0].body,
+            "Errors piling up: 5 in 1m, latest: error: could not compile `agent`"
+        );
+        assert_eq!(task.status, TaskStatus::Exited);
+    }
+
+    #[test]
+    fn test_budget_alert_once_per_day() {
+        let process = FakeProcess::new()
+            .print(&["Tokens: 40k sent, 2.1k received. Cost: $6.00 message, $6.00 session."])
+            .print(&["Tokens: 45k sent, 1.9k received. Cost: $7.00 message, $13.00 session."])
+            .print(&["Tokens: 50k sent, 1.7k received. Cost: $8.00 message, $21.00 session."])
+            .busy(2);
+        let (sent, _) = simulate_with(process, |b| b.daily_budget(10.0));
+
+        assert_eq!(sent.len(), 1);
+        assert_eq!(
+            sent[0].body,
+            "Today's agent spend crossed $10.00 ($13.00 so far)"
+        );
+    }
+
+    #[test]
+    fn test_stall_notifies_once_per_episode() {
+        // 5s polls: two minutes idle, resume, two more idle minutes
+        let process = FakeProcess::new().busy(10).idle(24).busy(2).idle(24);
+        let (sent, task) = simulate(process);
+
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].title, "[claude_code] Simulated");
+        assert_eq!(sent[0].body, "Process stalled (no activity)");
+        assert_eq!(task.status, TaskStatus::Exited);
+    }
+
+    #[test]
+    fn test_classifier_rules_out_a_stall() {
+        struct Busy;
+        impl classify::Classifier for Busy {
+            fn classify(&self, _output: &str) -> Result<Verdict> {
+                Ok(Verdict::NotWaiting)
+            }
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let config = crate::config::ClassifierConfig::default();
+        let gate = Gate::new(Box::new(Busy), &config, dir.path().join("classifier.calls"));
+        let process = FakeProcess::new()
+            .print(&["Linking target/release/app"])
+            .idle(24);
+        let (sent, _, events) = simulate_events(process, |builder| builder.classifier(gate));
+
+        assert!(sent.is_empty());
+        let suppressed: Vec<_> = events
+            .iter()
+            .filter(|e| e.kind == EventKind::Suppressed)
+            .collect();
+        assert_eq!(suppressed.len(), 1);
+        assert_eq!(
+            suppressed[0].message,
+            "Classifier: not waiting on the user (process stalled)"
+        );
+    }
+
+    #[test]
+    fn test_lost_terminal_is_flagged() {
+        let (sent, task) = simulate(FakeProcess::new().busy(3).hang_up().busy(3));
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].reason.as_deref(), Some("terminal_lost"));
+        assert_eq!(sent[0].urgency, Urgency::Critical);
+        assert!(
+            sent[0].body.contains("/dev/pts/0 closed"),
+            "{}",
+            sent[0].body
+        );
+        assert_eq!(task.attention_key(), Some("terminal_lost"));
+    }
+
+    #[test]
+    fn test_unanswered_need_is_reminded() {
+        let config = crate::config::RemindersConfig {
+            max: 2,
+            first_secs: 60,
+            factor: 2.0,
+            reasons: vec!["process_stalled".into()],
+            ..Default::default()
+        };
+        // Stalled after a minute, then waiting seven more: reminded after
+        // one and three minutes, and no more
+        let process = FakeProcess::new().idle(96);
+        let (sent, _) = simulate_with(process, |builder| {
+            builder.reminders(Reminders::from_config(&config))
+        });
+        assert_eq!(sent.len(), 3);
+        assert_eq!(
+            sent[1].body,
+            "Still waiting after 1m: Process stalled (no activity)"
+        );
+        assert_eq!(
+            sent[2].body,
+            "Still waiting after 3m: Process stalled (no activity)"
+        );
+        assert_eq!(sent[2].reason.as_deref(), Some("process_stalled"));
+
+        // Activity ends the wait before any reminder is due
+        let process = FakeProcess::new().idle(14).print(&["Resumed"]).busy(20);
+        let (sent, _) = simulate_with(process, |builder| {
+            builder.reminders(Reminders::from_config(&config))
+        });
+        assert_eq!(sent.len(), 1);
+    }
+
+    #[test]
+    fn test_fifo_messages_from_the_task() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let fifo = fifo::Fifo::create(dir.path(), "sim").unwrap();
+        let mut writer = std::fs::OpenOptions::new()
+            .write(true)
+            .open(fifo.path())
+            .unwrap();
+        writer
+            .write_all(b"PROGRESS 40%\nNOTE schema migrated\nNEED_INPUT Which branch?\n")
+            .unwrap();
+        // Busy all along: only the task's word raises the need
+        let process = FakeProcess::new().busy(6);
+        let (sent, task, events) =
+            simulate_events(process, |builder| builder.fifo(fifo.reader().unwrap()));
+        assert_eq!(sent.len(), 1);
+        assert_eq!(
+            sent[0].body,
+            "Waiting for input: Which branch? at 40% after 0s"
+        );
+        assert_eq!(sent[0].reason.as_deref(), Some("waiting_for_input"));
+        assert_eq!(task.progress().map(|p| p.percent()), Some(40));
+        assert!(events
+            .iter()
+            .any(|e| e.kind == EventKind::Note && e.message == "schema migrated"));
+    }
+
+    #[test]
+    fn test_restricted_proc_falls_back_to_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let no_stat = Access {
+            stat: false,
+            ..Access::FULL
+        };
+        // Busy, but /proc won't say so: silence alone makes the stall
+        let process = FakeProcess::new().busy(24).restricted(no_stat);
+        let (sent, _) = simulate_with(process, |builder| builder.trace(dir.path().to_path_buf()));
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].reason.as_deref(), Some("process_stalled"));
+        let lines = trace::last_lines(&trace::path(dir.path(), "sim"), 1000);
+        assert!(
+            lines[0]
+                .ends_with("access   can't read its CPU time; stalls go by output silence only"),
+            "{}",
+            lines[0]
+        );
+
+        // Without its open files a hang-up goes unseen
+        let no_fds = Access {
+            fds: false,
+            ..Access::FULL
+        };
+        let (sent, task) = simulate(
+            FakeProcess::new()
+                .busy(3)
+                .hang_up()
+                .busy(3)
+                .restricted(no_fds),
+        );
+        assert!(sent.is_empty());
+        assert_eq!(task.status, TaskStatus::Exited);
+    }
+
+    #[test]
+    fn test_trace_explains_decisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let (sent, _) = simulate_with(FakeProcess::new().idle(24), |builder| {
+            builder.trace(dir.path().to_path_buf())
+        });
+        assert_eq!(sent.len(), 1);
+        let lines = trace::last_lines(&trace::path(dir.path(), "sim"), 1000);
+        let stage = |stage: &str| -> Vec<&String> {
+            lines
+                .iter()
+                .filter(|l| l[21..].starts_with(stage))
+                .collect()
+        };
+
+        assert!(
+            stage("check")[0].contains("within the 10s startup grace"),
+            "{:?}",
+            lines
+        );
+        assert!(stage("detect")[0].ends_with("TerminalLost -, PasswordPrompt -, Approval -, RateLimit -, Stall -, ErrorBurst -, Looping -"));
+        let fired = stage("detect")
+            .into_iter()
+            .find(|l| l.contains("process_stalled"))
+            .unwrap();
+        assert!(fired.ends_with("Stall process_stalled"), "{}", fired);
+        assert!(stage("raise")[0].ends_with("process_stalled at normal urgency"));
+        assert_eq!(stage("route").len(), 1);
+        assert!(stage("route")[0].contains("(via "), "{}", stage("route")[0]);
+        // Not notified again while it stands
+        assert!(stage("hold")
+            .iter()
+            .all(|l| l.ends_with("process_stalled already raised")));
+        assert!(!stage("hold").is_empty());
+    }
+
+    #[test]
+    fn test_acknowledged_need_stays_quiet() {
+        let run = |process: FakeProcess, acknowledged: &str| {
+            let db_file = NamedTempFile::new().unwrap();
+            let db = Database::open(db_file.path()).unwrap();
+            let clock = FakeClock::default();
+            let mut task = Task::new("sim".into(), "tool".into(), "Build".into(), Some(1), None);
+            task.set_attention(acknowledged, "Seen".to_string());
+            task.acknowledge(clock.now_utc());
+            db.insert_task(&task).unwrap();
+            let channel = MockChannel::new();
+            MonitorBuilder::new(db)
+                .stall_timeout(Duration::from_secs(60))
+                .probe(Box::new(process))
+                .clock(Box::new(clock))
+                .notifier(
+                    Notifier::builder()
+                        .channel(Box::new(channel.clone()))
+                        .build(),
+                )
+                .build()
+                .monitor_task("sim".into(), 1, Some("/nonexistent/sim.log".into()))
+                .unwrap();
+            channel.sent()
+        };
+
+        assert!(run(FakeProcess::new().idle(24), "process_stalled").is_empty());
+        // Another reason is news, and so is the same one after activity
+        assert_eq!(
+            run(FakeProcess::new().idle(24), "waiting_for_input").len(),
+            1
+        );
+        assert_eq!(
+            run(
+                FakeProcess::new().idle(2).print(&["Linking"]).idle(24),
+                "process_stalled"
+            )
+            .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_timeline_records_why_notifications_went_out() {
+        let process = FakeProcess::new()
+            .print(&["Step 1/4"])
+            .busy(10)
+            .print(&["Step 2/4"])
+            .idle(24)
+            .print(&["retrying"])
+            .busy(1)
+            .idle(24);
+        let (_, _, events) = simulate_events(process, |builder| builder);
+
+        let kinds: Vec<EventKind> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                EventKind::Progress,
+                EventKind::Progress,
+                EventKind::Attention,
+                EventKind::Notified,
+                EventKind::AttentionCleared,
+                EventKind::Attention,
+                EventKind::Notified,
+                EventKind::Exited,
+            ]
+        );
+        assert_eq!(events[1].message, "Reached 50%");
+        assert!(events[3]
+            .message
+            .starts_with("Process stalled (no activity) at 50%"));
+        assert!(events[3].message.ends_with("(via mock)"));
+        assert!(events.windows(2).all(|w| w[0].at <= w[1].at));
+    }
+
+    #[test]
+    fn test_output_silence_counts_as_idle() {
+        // Burns CPU the whole time, but prints nothing after the first line
+        let (sent, _) = simulate(FakeProcess::new().print(&["starting"]).busy(30));
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].body.starts_with("Process stalled"));
+
+        // Without any output the CPU decides, and it never went idle
+        let (sent, _) = simulate(FakeProcess::new().busy(30));
+        assert!(sent.is_empty());
+    }
+
+    #[test]
+    fn test_typing_counts_as_activity() {
+        // 50s silences between keystrokes: never a minute without activity
+        let process = FakeProcess::new()
+            .busy(2)
+            .idle(10)
+            .typing()
+            .idle(10)
+            .typing()
+            .idle(10);
+        let (sent, task) = simulate(process);
+        assert!(sent.is_empty());
+        assert!(task.last_input().is_some());
+
+        // The same silence without anyone typing is a stall
+        let (sent, task) = simulate(FakeProcess::new().busy(2).idle(32));
+        assert_eq!(sent.len(), 1);
+        assert!(task.last_input().is_none());
+    }
+
+    #[test]
+    fn test_no_notifications_while_user_watches() {
+        let presence = FakePresence::new(true);
+        let process = FakeProcess::new().busy(10).idle(24).busy(2).idle(24);
+        let (sent, task) = simulate_with(process, |builder| {
+            builder.presence(Box::new(presence.clone()))
+        });
+
+        assert!(sent.is_empty());
+        // The task is still flagged, only the notification is skipped
+        assert_eq!(task.status, TaskStatus::Exited);
+    }
+
+    #[test]
+    fn test_auto_respond_replaces_attention() {
+        let responder = AutoResponder::new(&[AutoRespondRule {
+            name: "proceed".to_string(),
+            pattern: r"Proceed\? \[y/N\]".to_string(),
+            response: "y".to_string(),
+            tasks: vec!["sim".to_string()],
+        }])
+        .unwrap();
+        let input = MockInput::new();
+
+        let process = FakeProcess::new()
+            .print(&["Step 1/2", "Proceed? [y/N]"])
+            .busy(3)
+            .print(&["Delete the repo? [y/N]"])
+            .busy(2);
+        let (sent, task) = simulate_with(process, |builder| {
+            builder
+                .auto_respond(responder)
+                .input(Box::new(input.clone()))
+        });
+
+        assert_eq!(input.lines(), vec!["y"]);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].urgency, Urgency::Low);
+        assert_eq!(sent[0].body, "Auto-responded \"y\" to: Proceed? [y/N]");
+        assert_eq!(respond::responses(&task)[0].rule, "proceed");
+    }
+
+    #[test]
+    fn test_destructive_prompt_escalates() {
+        let responder = AutoResponder::new(&[AutoRespondRule {
+            name: "yes".to_string(),
+            pattern: r"\[y/N\]".to_string(),
+            response: "y".to_string(),
+            tasks: vec!["*".to_string()],
+        }])
+        .unwrap();
+        let input = MockInput::new();
+
+        let process = FakeProcess::new()
+            .print(&[
+                "Cleaning up",
+                "About to run rm -rf ./dist",
+                "Continue? [y/N]",
+            ])
+            .busy(2);
+        let (sent, task) = simulate_with(process, |builder| {
+            builder
+                .auto_respond(responder)
+                .input(Box::new(input.clone()))
+        });
+
+        assert!(input.lines().is_empty());
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].urgency, Urgency::Critical);
+        assert!(sent[0]
+            .body
+            .contains("About to run rm -rf ./dist\nContinue? [y/N]"));
+        assert!(respond::responses(&task).is_empty());
+    }
+
+    #[test]
+    fn test_rule_reply_is_guarded() {
+        use crate::config::{RuleConfig, RuleThen, RuleWhen, SandboxConfig};
+        let rule = |output: &str, respond: &str| {
+            Rules::new(
+                &[RuleConfig {
+                    name: "answer".to_string(),
+                    when: RuleWhen {
+                        output: Some(output.to_string()),
+                        ..RuleWhen::default()
+                    },
+                    then: RuleThen {
+                        respond: Some(respond.to_string()),
+                        ..RuleThen::default()
+                    },
+                }],
+                &SandboxConfig::default(),
+            )
+            .unwrap()
+        };
+
+        // The deny-list holds for rules too, and escalates
+        let input = MockInput::new();
+        let process = FakeProcess::new()
+            .print(&["Delete production DB? [y/N]"])
+            .idle(20);
+        let (sent, _, events) = simulate_events(process, |builder| {
+            builder
+                .rules(rule("production", "y"))
+                .input(Box::new(input.clone()))
+        });
+        assert!(input.lines().is_empty());
+        assert_eq!(sent[0].urgency, Urgency::Critical);
+        assert!(sent[0]
+            .body
+            .ends_with("Not answered: output matches \"Delete\""));
+        assert!(events.iter().any(|e| e.kind == EventKind::Rule
+            && e.message == "'answer': escalated, not answering: output matches \"Delete\""));
+
+        // Nor is a password typed, whatever raised the need
+        let input = MockInput::new();
+        let process = FakeProcess::new()
+            .print(&["[sudo] password for bob:"])
+            .idle(20);
+        let (_, _, events) = simulate_events(process, |builder| {
+            builder
+                .rules(rule("sudo", "hunter2"))
+                .input(Box::new(input.clone()))
+        });
+        assert!(input.lines().is_empty());
+        assert!(events.iter().any(|e| e.kind == EventKind::Rule
+            && e.message == "'answer': not answering a password prompt"));
+    }
+
+    #[test]
+    fn test_rule_kills_task_stalled_twice() {
+        use crate::config::{RuleConfig, RuleThen, RuleWhen, SandboxConfig};
+        let rules = Rules::new(
+            &[RuleConfig {
+                name: "stuck".to_string(),
+                when: RuleWhen {
+                    reason: Some("process_stalled".to_string()),
+                    repeats: Some(2),
+                    ..RuleWhen::default()
+                },
+                then: RuleThen {
+                    escalate: true,
+                    kill: true,
+                    ..RuleThen::default()
+                },
+            }],
+            &SandboxConfig::default(),
+        )
+        .unwrap();
+        let process = FakeProcess::new()
+            .busy(10)
+            .idle(24)
+            .busy(2)
+            .idle(24)
+            .busy(100);
+        let (sent, task, events) = simulate_events(process.clone(), |builder| builder.rules(rules));
+
+        assert!(process.terminated());
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].urgency, Urgency::Normal);
+        assert_eq!(sent[1].urgency, Urgency::Critical);
+        let applied: Vec<&str> = events
+            .iter()
+            .filter(|e| e.kind == EventKind::Rule)
+            .map(|e| e.message.as_str())
+            .collect();
+        assert_eq!(applied, vec!["'stuck': escalated, killed the process"]);
+        assert_eq!(task.status, TaskStatus::Exited);
+    }
+
+    #[test]
+    fn test_unattended_stall_is_killed() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Database::open(db_file.path()).unwrap();
+        let clock = FakeClock::default();
+        let mut task = Task::new(
+            "sim".into(),
+            "tool".into(),
+            "Overnight".into(),
+            Some(1),
+            None,
+        );
+        task.created_at = clock.now_utc();
+        task.set_unattended(&crate::models::Unattended {
+            action: UnattendedAction::Kill,
+            after_secs: 120,
+        });
+        db.insert_task(&task).unwrap();
+
+        let channel = MockChannel::new();
+        let process = FakeProcess::new().busy(2).idle(200);
+        MonitorBuilder::new(db)
+            .stall_timeout(Duration::from_secs(60))
+            .probe(Box::new(process.clone()))
+            .clock(Box::new(clock))
+            .notifier(
+                Notifier::builder()
+                    .channel(Box::new(channel.clone()))
+                    .build(),
+            )
+            .build()
+            .monitor_task("sim".into(), 1, Some("/nonexistent/sim.log".into()))
+            .unwrap();
+
+        assert!(process.terminated());
+        assert!(process.polls() < 100);
+        let sent = channel.sent();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(
+            sent[1].body,
+            "No answer for 2m: killed the process\nProcess stalled (no activity)"
+        );
+        let events = Database::open(db_file.path())
+            .unwrap()
+            .task_events("sim")
+            .unwrap();
+        assert!(events.iter().any(|e| e.kind == EventKind::Unattended));
+    }
+
+    #[test]
+    fn test_unattended_answer_respects_deny_list() {
+        let db_file = NamedTempFile::new().unwrap();
+        let db = Database::open(db_file.path()).unwrap();
+        let clock = FakeClock::default();
+        let mut task = Task::new("sim".into(), "tool".into(), "Migrate".into(), Some(1), None);
+        task.created_at = clock.now_utc();
+        task.set_unattended(&crate::models::Unattended {
+            action: UnattendedAction::Respond("y".to_string()),
+            after_secs: 120,
+        });
+        db.insert_task(&task).unwrap();
+
+        let channel = MockChannel::new();
+        let input = MockInput::new();
+        let process = FakeProcess::new()
+            .print(&["Drop table users? [y/N]"])
+            .idle(60);
+        MonitorBuilder::new(db)
+            .stall_timeout(Duration::from_secs(60))
+            .probe(Box::new(process))
+            .clock(Box::new(clock))
+            .input(Box::new(input.clone()))
+            .notifier(
+                Notifier::builder()
+                    .channel(Box::new(channel.clone()))
+                    .build(),
+            )
+            .build()
+            .monitor_task("sim".into(), 1, Some("/nonexistent/sim.log".into()))
+            .unwrap();
+
+        assert!(input.lines().is_empty());
+        let sent = channel.sent();
+        let review = sent.last().unwrap();
+        assert_eq!(review.urgency, Urgency::Critical);
+        assert!(
+            review
+                .body
+                .starts_with("No answer for 2m: not answering, output matches \"Drop table\""),
+            "{}",
+            review.body
+        );
+    }
+
+    #[test]
+    fn test_simulated_output_feeds_progress() {
+        let process = FakeProcess::new()
+            .print(&["Step 1/4"])
+            .busy(5)
+            .print(&["Step 3/4"])
+            .idle(20);
+        let (sent, task) = simulate(process);
+
+        assert_eq!(task.progress().unwrap().percent(), 75);
+        assert!(sent[0]
+            .body
+            .starts_with("Process stalled (no activity) at 75% after"));
+    }
 
     #[test]
     fn test_is_process_alive() {
         // Current process should be alive
         let current_pid = std::process::id() as i32;
-        assert!(is_process_alive(current_pid));
+        assert!(ProcProbe.is_alive(current_pid));
 
         // PID 999999 very unlikely to exist
-        assert!(!is_process_alive(999999));
+        assert!(!ProcProbe.is_alive(999999));
+    }
+
+    #[test]
+    fn test_attention_message_includes_progress() {
+        let mut task = Task::new(
+            "test-id".to_string(),
+            "claude_code".to_string(),
+            "Test task".to_string(),
+            None,
+            None,
+        );
+        task.created_at = Utc::now() - chrono::Duration::minutes(40);
+        assert_eq!(
+            attention_message(
+                ReasonRegistry::builtin(),
+                &AttentionReason::ProcessStalled,
+                &task,
+                Utc::now()
+            ),
+            "Process stalled (no activity)"
+        );
+
+        task.set_progress(&Progress {
+            fraction: 0.8,
+            label: "8/10".to_string(),
+            eta_secs: Some(600),
+            updated_at: Utc::now(),
+        });
+        assert_eq!(
+            attention_message(
+                ReasonRegistry::builtin(),
+                &AttentionReason::ProcessStalled,
+                &task,
+                Utc::now()
+            ),
+            "Process stalled (no activity) at 80% after 40m (ETA was 10m)"
+        );
+    }
+
+    /// Source that replays scripted pids, then clears the task and saves
+    /// its activity log
+    struct ScriptedSource {
+        pids: Mutex<VecDeque<i32>>,
+        db_path: PathBuf,
+        events: Mutex<Vec<TaskEvent>>,
+    }
+
+    impl PidSource for ScriptedSource {
+        fn describe(&self) -> String {
+            "agent.pid".to_string()
+        }
+
+        fn resolve(&self) -> Option<i32> {
+            let pid = self.pids.lock().unwrap().pop_front();
+            if pid.is_none() {
+                let db = Database::open(&self.db_path).unwrap();
+                let mut events = self.events.lock().unwrap();
+                if events.is_empty() {
+                    *events = db.task_events("svc").unwrap();
+                    db.delete_task("svc").unwrap();
+                }
+            }
+            pid
+        }
+    }
+
+    #[test]
+    fn test_follow_starts_a_run_per_process() {
+        let db_file = NamedTempFile::new().unwrap();
+        let source = ScriptedSource {
+            pids: Mutex::new([7, 7, 7, 9, 9, 9].into()),
+            db_path: db_file.path().to_path_buf(),
+            events: Mutex::new(Vec::new()),
+        };
+        let task = Task::new("svc".into(), "service".into(), "Service".into(), None, None);
+
+        MonitorBuilder::new(Database::open(db_file.path()).unwrap())
+            .probe(Box::new(FakeProcess::new().busy(20)))
+            .clock(Box::new(FakeClock::default()))
+            .notifier(Notifier::builder().build())
+            .build()
+            .follow(task, &source, Some("/nonexistent/svc.log".into()))
+            .unwrap();
+
+        let events = source.events.into_inner().unwrap();
+        let messages: Vec<&str> = events.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "pid 7 from agent.pid",
+                "Replaced by pid 9",
+                "Run 2, pid 9 from agent.pid"
+            ]
+        );
     }
 }
@@ -0,0 +1,96 @@
+//! Notify once when a process exits: `agent-inbox oneshot --pid <pid>`
+//!
+//! For the process already running that you'd like to hear about without
+//! registering a task: wait in the foreground for it to exit, however the
+//! machine best notices that (see [`liveness`](super::liveness)), then send
+//! one notification through the configured channels and return. Nothing is
+//! stored and no daemon is needed.
+//!
+//! The process isn't this one's child, so how it exited can't be known:
+//! the notification says that it ended, what it was and how long it was
+//! watched.
+
+use super::liveness::ExitWatch;
+use super::probe::ProcessProbe;
+use crate::exit::{Code, Failure};
+use crate::models::progress::format_duration;
+use crate::notify::{Category, Notification, Urgency};
+use std::time::{Duration, Instant};
+
+/// How long each wait lasts before waiting again
+const WAIT: Duration = Duration::from_secs(60);
+
+/// A process being waited for
+pub struct Oneshot {
+    pid: i32,
+    command: Option<String>,
+    started: Instant,
+    watch: ExitWatch,
+}
+
+impl Oneshot {
+    /// Start watching `pid`; fails if there is no such process
+    pub fn watch(probe: &dyn ProcessProbe, pid: i32) -> anyhow::Result<Self> {
+        if !probe.is_alive(pid) {
+            return Err(
+                Failure::new(Code::NotFound, format!("No process with pid {}", pid)).into(),
+            );
+        }
+        Ok(Self {
+            pid,
+            command: probe.command_line(pid),
+            started: Instant::now(),
+            watch: ExitWatch::new(pid),
+        })
+    }
+
+    /// Wait for the process to exit, then describe that as a notification
+    /// titled `message`, or "Process <pid> exited" without one
+    pub fn wait(mut self, message: Option<String>) -> Notification {
+        while !self.watch.wait(WAIT) {}
+        let watched = format_duration(self.started.elapsed().as_secs() as i64);
+        let body = match self.command {
+            Some(command) => format!(
+                "{} (pid {}) ended after {} watched",
+                command, self.pid, watched
+            ),
+            None => format!("pid {} ended after {} watched", self.pid, watched),
+        };
+        Notification {
+            title: message.unwrap_or_else(|| format!("Process {} exited", self.pid)),
+            body,
+            urgency: Urgency::Normal,
+            category: Category::Completed,
+            task_id: None,
+            reason: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::probe::ProcProbe;
+    use std::process::Command;
+
+    #[test]
+    fn test_oneshot_waits_for_exit() {
+        let mut child = Command::new("sleep").arg("0.2").spawn().unwrap();
+        let pid = child.id() as i32;
+        // Polling sees a zombie as alive, so reap it
+        let reaper = std::thread::spawn(move || child.wait());
+        let notification = Oneshot::watch(&ProcProbe, pid)
+            .unwrap()
+            .wait(Some("backup done".into()));
+        reaper.join().unwrap().unwrap();
+        assert_eq!(notification.title, "backup done");
+        assert!(notification.body.contains(&format!("pid {}", pid)));
+        assert_eq!(
+            (notification.category, notification.task_id),
+            (Category::Completed, None)
+        );
+
+        let gone = Oneshot::watch(&ProcProbe, pid).err().unwrap();
+        assert_eq!(crate::exit::code_of(&gone), Code::NotFound);
+    }
+}
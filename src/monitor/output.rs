@@ -0,0 +1,167 @@
+//! Incremental reader for a task's captured output log
+//!
+//! The log holds the raw terminal stream; lines handed out here have escape
+//! sequences removed (see [`super::ansi`]) and anything after a password
+//! prompt masked (see [`super::redact`]).
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Default location of a task's captured output:
+/// ~/.local/state/agent-inbox/logs/<task_id>.log
+pub fn default_log_path(task_id: &str) -> PathBuf {
+    crate::storage::state_dir()
+        .join("logs")
+        .join(format!("{}.log", task_id))
+}
+
+/// Read at most `max_bytes` from the end of a log, starting at a line boundary
+pub fn read_tail<P: AsRef<Path>>(path: P, max_bytes: u64) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    let start = len.saturating_sub(max_bytes);
+    file.seek(SeekFrom::Start(start)).ok()?;
+
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).ok()?;
+    let text = String::from_utf8_lossy(&buf).into_owned();
+
+    if start > 0 {
+        // Drop the partial first line
+        Some(
+            text.split_once('\n')
+                .map(|(_, rest)| rest.to_string())
+                .unwrap_or_default(),
+        )
+    } else {
+        Some(text)
+    }
+}
+
+fn clean(line: &str) -> String {
+    super::redact::mask_line(&super::ansi::strip(line))
+}
+
+/// Follows a log file like `tail -f`, returning only complete new lines
+pub struct OutputTail {
+    path: PathBuf,
+    offset: u64,
+    /// Raw incomplete line, so escapes split across reads reassemble
+    raw_partial: String,
+    partial: String,
+}
+
+impl OutputTail {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            offset: 0,
+            raw_partial: String::new(),
+            partial: String::new(),
+        }
+    }
+
+    /// Read lines appended since the last call, without escape sequences.
+    /// Carriage returns count as line breaks so progress bars redrawn in
+    /// place still produce observations.
+    pub fn read_new_lines(&mut self) -> Vec<String> {
+        let Ok(mut file) = File::open(&self.path) else {
+            return Vec::new();
+        };
+        let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+        if len < self.offset {
+            // Truncated or rotated: start over
+            self.offset = 0;
+            self.raw_partial.clear();
+            self.partial.clear();
+        }
+        if file.seek(SeekFrom::Start(self.offset)).is_err() {
+            return Vec::new();
+        }
+
+        let mut buf = Vec::new();
+        if file.read_to_end(&mut buf).is_err() {
+            return Vec::new();
+        }
+        self.offset += buf.len() as u64;
+        self.raw_partial.push_str(&String::from_utf8_lossy(&buf));
+
+        let mut lines: Vec<String> = self
+            .raw_partial
+            .split(['\n', '\r'])
+            .map(str::to_string)
+            .collect();
+        // The last element is an incomplete line (or empty); keep it for next time
+        self.raw_partial = lines.pop().unwrap_or_default();
+        self.partial = clean(&self.raw_partial);
+        lines
+            .iter()
+            .map(|l| clean(l))
+            .filter(|l| !l.trim().is_empty())
+            .collect()
+    }
+
+    /// The incomplete last line, e.g. a prompt waiting for input
+    pub fn partial(&self) -> &str {
+        &self.partial
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_read_new_lines() {
+        let mut file = NamedTempFile::new().unwrap();
+        let mut tail = OutputTail::new(file.path());
+
+        write!(file, "first\nsecond\r40%").unwrap();
+        assert_eq!(tail.read_new_lines(), vec!["first", "second"]);
+        assert_eq!(tail.partial(), "40%");
+
+        writeln!(file, " done").unwrap();
+        assert_eq!(tail.read_new_lines(), vec!["40% done"]);
+        assert!(tail.read_new_lines().is_empty());
+    }
+
+    #[test]
+    fn test_lines_are_stripped_of_escapes() {
+        let mut file = NamedTempFile::new().unwrap();
+        let mut tail = OutputTail::new(file.path());
+
+        write!(
+            file,
+            "\x1b[32m[2/4]\x1b[0m ok\n\x1b[2K\n\x1b[1mProceed?\x1b["
+        )
+        .unwrap();
+        assert_eq!(tail.read_new_lines(), vec!["[2/4] ok"]);
+        assert_eq!(tail.partial(), "Proceed?");
+
+        write!(file, "0m [y/n] ").unwrap();
+        assert!(tail.read_new_lines().is_empty());
+        assert_eq!(tail.partial(), "Proceed? [y/n] ");
+    }
+
+    #[test]
+    fn test_read_tail() {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "line one\nline two\nline three\n").unwrap();
+
+        assert_eq!(
+            read_tail(file.path(), 1024).unwrap(),
+            "line one\nline two\nline three\n"
+        );
+        assert_eq!(read_tail(file.path(), 13).unwrap(), "line three\n");
+        assert!(read_tail("/nonexistent/agent-inbox.log", 10).is_none());
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let mut tail = OutputTail::new("/nonexistent/agent-inbox.log");
+        assert!(tail.read_new_lines().is_empty());
+    }
+}
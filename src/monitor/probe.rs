@@ -0,0 +1,148 @@
+//! Seams between the monitor loop and the outside world
+//!
+//! The monitor only talks to the operating system and the wall clock through
+//! these traits, so tests can drive it with scripted fakes (see the
+//! `test-util` feature) instead of real processes and real sleeps.
+
+use super::access::Access;
+use anyhow::Result;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Observes a monitored process
+pub trait ProcessProbe: Send + Sync {
+    fn is_alive(&self, pid: i32) -> bool;
+
+    /// Cumulative CPU time of the process tree, in clock ticks
+    fn cpu_time(&self, pid: i32) -> Option<u64>;
+
+    /// Output lines the probe itself produced since the last call. The real
+    /// probe has none; captured output is read from the task's log instead.
+    fn take_output(&self, _pid: i32) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The process is blocked reading a password from its terminal
+    fn reading_password(&self, _pid: i32) -> bool {
+        false
+    }
+
+    /// A mark that changes whenever someone types into the process's
+    /// terminal: the terminal's access time, which only reads of input
+    /// move. None without a terminal.
+    fn input_mark(&self, _pid: i32) -> Option<SystemTime> {
+        None
+    }
+
+    /// The process's terminal, while it is still open. None without one,
+    /// or once the session behind it (ssh, a tmux pane) has closed it.
+    fn terminal(&self, _pid: i32) -> Option<PathBuf> {
+        None
+    }
+
+    /// Ask the process to exit (SIGTERM)
+    fn terminate(&self, _pid: i32) -> Result<()> {
+        anyhow::bail!("can't terminate processes here")
+    }
+
+    /// The process's command line, redacted like the one captured at
+    /// registration. None when it can't be read.
+    fn command_line(&self, _pid: i32) -> Option<String> {
+        None
+    }
+
+    /// What the probe can read of the process; see [`access`](super::access)
+    fn access(&self, _pid: i32) -> Access {
+        Access::FULL
+    }
+}
+
+/// Probe backed by /proc, or by `sysctl(kern.proc)` on FreeBSD and OpenBSD
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProcProbe;
+
+impl ProcessProbe for ProcProbe {
+    #[cfg(not(any(target_os = "freebsd", target_os = "openbsd")))]
+    fn is_alive(&self, pid: i32) -> bool {
+        // With hidepid, or without /proc, a live process has no entry
+        std::path::Path::new(&format!("/proc/{}", pid)).exists() || signalable(pid)
+    }
+
+    #[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+    fn is_alive(&self, pid: i32) -> bool {
+        super::kinfo::process(pid).is_some()
+    }
+
+    fn cpu_time(&self, pid: i32) -> Option<u64> {
+        super::detectors::process_tree_cpu_time(pid)
+    }
+
+    fn reading_password(&self, pid: i32) -> bool {
+        super::detectors::reading_with_echo_off(pid)
+    }
+
+    fn input_mark(&self, pid: i32) -> Option<SystemTime> {
+        let tty = crate::presence::terminal_of(pid)?;
+        std::fs::metadata(tty).ok()?.accessed().ok()
+    }
+
+    fn terminal(&self, pid: i32) -> Option<PathBuf> {
+        // A closed pty's node is removed: its link reads "/dev/pts/3 (deleted)"
+        crate::presence::terminal_of(pid).filter(|tty| tty.exists())
+    }
+
+    fn command_line(&self, pid: i32) -> Option<String> {
+        super::environ::capture(Some(pid), &[]).command_line
+    }
+
+    fn access(&self, pid: i32) -> Access {
+        Access::of(pid)
+    }
+
+    #[cfg(unix)]
+    fn terminate(&self, pid: i32) -> Result<()> {
+        // SAFETY: kill(2) has no memory effects
+        if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+}
+
+/// The process exists as far as kill(2) can tell: signal 0 reaches it, or
+/// it belongs to someone else
+#[cfg(all(unix, not(any(target_os = "freebsd", target_os = "openbsd"))))]
+fn signalable(pid: i32) -> bool {
+    if pid <= 0 {
+        return false;
+    }
+    // SAFETY: signal 0 only checks that the process exists
+    let sent = unsafe { libc::kill(pid, 0) } == 0;
+    sent || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn signalable(_pid: i32) -> bool {
+    false
+}
+
+/// Source of time for the monitor loop
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real wall clock
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
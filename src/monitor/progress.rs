@@ -0,0 +1,180 @@
+//! Progress parsing for captured task output
+//!
+//! Recognises common progress markers ("Step 3/10", "[3/10]", "42%",
+//! cargo's "running N tests") and turns them into a completion fraction
+//! with a linear ETA estimate.
+
+use crate::models::Progress;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+
+pub struct ProgressTracker {
+    step_re: Regex,
+    bracket_re: Regex,
+    percent_re: Regex,
+    tests_total_re: Regex,
+    test_line_re: Regex,
+    /// First observation used as the baseline for rate estimation
+    baseline: Option<(DateTime<Utc>, f64)>,
+    current: Option<Progress>,
+    tests_total: Option<u64>,
+    tests_done: u64,
+}
+
+impl ProgressTracker {
+    pub fn new() -> Self {
+        Self {
+            step_re: Regex::new(r"(?i)\b(?:step|stage|phase|task)\s+(\d+)\s*(?:/|of)\s*(\d+)")
+                .unwrap(),
+            bracket_re: Regex::new(r"[\[(]\s*(\d+)\s*/\s*(\d+)\s*[\])]").unwrap(),
+            percent_re: Regex::new(r"(\d{1,3}(?:\.\d+)?)\s?%").unwrap(),
+            tests_total_re: Regex::new(r"^running (\d+) tests?$").unwrap(),
+            test_line_re: Regex::new(r"^test .+ \.\.\. (ok|FAILED|ignored)").unwrap(),
+            baseline: None,
+            current: None,
+            tests_total: None,
+            tests_done: 0,
+        }
+    }
+
+    /// Feed one line of output. Returns true when the progress changed.
+    pub fn observe(&mut self, line: &str, now: DateTime<Utc>) -> bool {
+        let Some((fraction, label)) = self.parse_line(line.trim()) else {
+            return false;
+        };
+
+        if let Some(current) = &self.current {
+            if current.fraction == fraction && current.label == label {
+                return false;
+            }
+        }
+
+        // A fraction going backwards means a new phase started; reset the rate baseline
+        let moved_back = self.current.as_ref().is_some_and(|c| fraction < c.fraction);
+        let baseline = match self.baseline {
+            Some(baseline) if !moved_back => baseline,
+            _ => (now, fraction),
+        };
+        self.baseline = Some(baseline);
+
+        self.current = Some(Progress {
+            fraction,
+            label,
+            eta_secs: estimate_eta(baseline, (now, fraction)),
+            updated_at: now,
+        });
+        true
+    }
+
+    pub fn current(&self) -> Option<&Progress> {
+        self.current.as_ref()
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<(f64, String)> {
+        // cargo test: "running N tests" followed by one line per test
+        if let Some(caps) = self.tests_total_re.captures(line) {
+            self.tests_total = caps[1].parse().ok().filter(|n| *n > 0);
+            self.tests_done = 0;
+            return None;
+        }
+        if let Some(total) = self.tests_total {
+            if self.test_line_re.is_match(line) {
+                self.tests_done = (self.tests_done + 1).min(total);
+                return Some((
+                    self.tests_done as f64 / total as f64,
+                    format!("{}/{} tests", self.tests_done, total),
+                ));
+            }
+        }
+
+        for re in [&self.step_re, &self.bracket_re] {
+            if let Some(caps) = re.captures(line) {
+                let done: u64 = caps[1].parse().ok()?;
+                let total: u64 = caps[2].parse().ok()?;
+                if total > 0 && done <= total {
+                    return Some((done as f64 / total as f64, format!("{}/{}", done, total)));
+                }
+            }
+        }
+
+        // Progress bars usually repeat the percentage; the last one on the line wins
+        let percent = self
+            .percent_re
+            .captures_iter(line)
+            .filter_map(|caps| caps[1].parse::<f64>().ok())
+            .filter(|p| *p <= 100.0)
+            .last()?;
+        Some((percent / 100.0, format!("{}%", percent)))
+    }
+}
+
+impl Default for ProgressTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Linear extrapolation from the baseline observation to the latest one
+fn estimate_eta(baseline: (DateTime<Utc>, f64), latest: (DateTime<Utc>, f64)) -> Option<i64> {
+    let elapsed = (latest.0 - baseline.0).num_seconds();
+    let gained = latest.1 - baseline.1;
+    if elapsed <= 0 || gained <= 0.0 {
+        return None;
+    }
+    let rate = gained / elapsed as f64;
+    Some(((1.0 - latest.1) / rate).round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_parse_markers() {
+        let mut tracker = ProgressTracker::new();
+        let now = Utc::now();
+
+        assert!(tracker.observe("Step 3/10: compiling", now));
+        assert_eq!(tracker.current().unwrap().label, "3/10");
+
+        assert!(tracker.observe("[4/10] Linking", now));
+        assert_eq!(tracker.current().unwrap().percent(), 40);
+
+        assert!(tracker.observe("Downloading ... 55%", now));
+        assert_eq!(tracker.current().unwrap().percent(), 55);
+
+        assert!(!tracker.observe("Nothing to see here", now));
+        assert!(!tracker.observe("Downloading ... 55%", now));
+    }
+
+    #[test]
+    fn test_cargo_test_counts() {
+        let mut tracker = ProgressTracker::new();
+        let now = Utc::now();
+
+        assert!(!tracker.observe("running 4 tests", now));
+        tracker.observe("test a ... ok", now);
+        tracker.observe("test b ... FAILED", now);
+        let progress = tracker.current().unwrap();
+        assert_eq!(progress.label, "2/4 tests");
+        assert_eq!(progress.percent(), 50);
+    }
+
+    #[test]
+    fn test_eta_estimation() {
+        let mut tracker = ProgressTracker::new();
+        let start = Utc::now();
+
+        tracker.observe("Step 1/10", start);
+        assert_eq!(tracker.current().unwrap().eta_secs, None);
+
+        // 10% per minute -> 60% remaining takes six more minutes
+        tracker.observe("Step 4/10", start + Duration::seconds(180));
+        assert_eq!(tracker.current().unwrap().eta_secs, Some(360));
+
+        // Going backwards starts a new baseline
+        tracker.observe("Step 1/5", start + Duration::seconds(200));
+        assert_eq!(tracker.current().unwrap().eta_secs, None);
+    }
+}
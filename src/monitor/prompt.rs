@@ -0,0 +1,102 @@
+//! The question a task is waiting on, cut out of its recent output
+//!
+//! Quoting the last few lines of output often shows half a question, or
+//! spinner frames and a progress bar instead of it. [`extract`] walks back
+//! from the end to where the question starts: a blank line, or the top of
+//! the box a TUI draws around it.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+/// The trailing block of `lines` that holds the prompt, at most
+/// `max_lines` long (the end wins), with spinner and progress-bar lines
+/// left out
+pub fn extract(lines: &[&str], max_lines: usize) -> String {
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|line| line.trim_end())
+        .filter(|line| !is_spinner(line))
+        .collect();
+    let end = lines
+        .iter()
+        .rposition(|line| !line.is_empty())
+        .map_or(0, |i| i + 1);
+
+    let mut start = end;
+    let mut in_box = false;
+    while start > 0 {
+        let line = lines[start - 1].trim_start();
+        if line.is_empty() && !in_box {
+            break;
+        }
+        start -= 1;
+        if line.starts_with(BOX_BOTTOM) {
+            in_box = true;
+        } else if line.starts_with(BOX_TOP) || line.starts_with("+-") {
+            break;
+        }
+    }
+    let start = start.max(end.saturating_sub(max_lines));
+    lines[start..end].join("\n")
+}
+
+/// Corners a box around a prompt starts and ends with
+const BOX_TOP: [char; 4] = ['╭', '┌', '╔', '┏'];
+const BOX_BOTTOM: [char; 4] = ['╰', '└', '╚', '┗'];
+
+/// Spinner frames ("⠹ Thinking…", "✻ Working", "|") and bare progress
+/// bars ("[=====>    ] 45%", "████░░░░")
+fn is_spinner(line: &str) -> bool {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(
+            r"(?x)^\s*(
+                [⠀-⣿◐◓◑◒✻✽✶✳✢] (\s.*)?
+                | [|/\\-]
+                | \[[=\#>.\ -]+\] .*
+                | [█▉▊▋▌▍▎▏░▒▓\ ]+ (\s+\d+%)?
+            )\s*$",
+        )
+        .unwrap()
+    });
+    re.is_match(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_question_after_blank_line() {
+        let output = [
+            "Compiling 12 crates",
+            "⠹ Thinking…",
+            "",
+            "The migration will rewrite 40 files.",
+            "Apply it now?",
+            "[=======>      ] 52%",
+            "  (y/N) ",
+        ];
+        assert_eq!(
+            extract(&output, 10),
+            "The migration will rewrite 40 files.\nApply it now?\n  (y/N)"
+        );
+        assert_eq!(extract(&output, 2), "Apply it now?\n  (y/N)");
+    }
+
+    #[test]
+    fn test_question_in_a_box() {
+        let output = [
+            "Edited src/lib.rs",
+            "╭──────────────────────────╮",
+            "│ Do you want to proceed?  │",
+            "│                          │",
+            "│ ❯ 1. Yes                 │",
+            "╰──────────────────────────╯",
+            "",
+        ];
+        assert_eq!(extract(&output, 10), output[1..6].join("\n"));
+        assert_eq!(extract(&["", "   "], 10), "");
+    }
+}
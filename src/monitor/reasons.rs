@@ -0,0 +1,288 @@
+//! Attention reason taxonomy
+//!
+//! The reasons the monitor itself detects are typed variants of
+//! [`AttentionReason`]; everything else (plugin detectors, wrappers running
+//! `agent-inbox report attention`) uses string-keyed kinds. Each kind has
+//! registered metadata: the severity its notifications go out with, an
+//! icon for the title, a message template where `{detail}` stands for
+//! the specifics ("Merge conflict in {detail}"), and how long an
+//! acknowledged need of the kind stays quiet.
+//!
+//! A few common kinds are registered out of the box; `[reasons.<kind>]` in
+//! the config adds new ones or overrides any field of an existing one,
+//! built-ins included.
+
+use super::detectors::AttentionReason;
+use crate::config::ReasonConfig;
+use crate::notify::{template, Urgency};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// How a kind of attention reason is presented
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReasonInfo {
+    pub severity: Urgency,
+    /// Prefixed to notification titles; empty for none
+    pub icon: String,
+    /// Message text; `{detail}` is replaced by the reason's detail, and
+    /// takes filters such as `{detail|truncate:80}`
+    pub template: String,
+    /// Once acknowledged, the same need isn't notified again for this long
+    /// unless the task shows activity in between; 0 for never held back
+    pub ack_cooldown_minutes: u64,
+}
+
+/// Cooldown of kinds that don't set their own
+const ACK_COOLDOWN_MINUTES: u64 = 30;
+
+impl ReasonInfo {
+    pub fn new(severity: Urgency, icon: &str, template: &str) -> Self {
+        Self {
+            severity,
+            icon: icon.to_string(),
+            template: template.to_string(),
+            ack_cooldown_minutes: ACK_COOLDOWN_MINUTES,
+        }
+    }
+}
+
+/// Metadata for every known kind of reason
+#[derive(Debug, Clone)]
+pub struct ReasonRegistry {
+    kinds: HashMap<String, ReasonInfo>,
+}
+
+impl Default for ReasonRegistry {
+    fn default() -> Self {
+        let kinds = [
+            (
+                "waiting_for_input",
+                Urgency::Normal,
+                "",
+                "Waiting for input",
+            ),
+            (
+                "process_stalled",
+                Urgency::Normal,
+                "",
+                "Process stalled (no activity)",
+            ),
+            (
+                "password_prompt",
+                Urgency::Critical,
+                "",
+                "Waiting for a password",
+            ),
+            (
+                "waiting_approval",
+                Urgency::Normal,
+                "✋",
+                "Waiting for approval: {detail}",
+            ),
+            ("rate_limited", Urgency::Low, "⏱", "Rate limited: {detail}"),
+            (
+                "quota_exceeded",
+                Urgency::Normal,
+                "💸",
+                "Quota exceeded: {detail}",
+            ),
+            (
+                "merge_conflict",
+                Urgency::Normal,
+                "⚔",
+                "Merge conflict in {detail}",
+            ),
+            (
+                "error_burst",
+                Urgency::Normal,
+                "🔥",
+                "Errors piling up: {detail}",
+            ),
+            ("looping", Urgency::Normal, "🔁", "Output looping: {detail}"),
+            (
+                "terminal_lost",
+                Urgency::Critical,
+                "🔌",
+                "Terminal {detail} closed; the process runs on without it",
+            ),
+        ];
+        Self {
+            kinds: kinds
+                .into_iter()
+                .map(|(key, severity, icon, template)| {
+                    (key.to_string(), ReasonInfo::new(severity, icon, template))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ReasonRegistry {
+    /// The built-in kinds plus `[reasons.<kind>]` from the config
+    pub fn from_config(config: &HashMap<String, ReasonConfig>) -> Self {
+        let mut registry = Self::default();
+        for (key, custom) in config {
+            let info = registry
+                .kinds
+                .entry(key.clone())
+                .or_insert_with(|| ReasonInfo::new(Urgency::Normal, "", &humanize(key)));
+            if let Some(severity) = custom.severity {
+                info.severity = severity;
+            }
+            if let Some(icon) = &custom.icon {
+                info.icon = icon.clone();
+            }
+            if let Some(template) = &custom.template {
+                info.template = template.clone();
+            }
+            if let Some(minutes) = custom.ack_cooldown_minutes {
+                info.ack_cooldown_minutes = minutes;
+            }
+        }
+        registry
+    }
+
+    /// The registry with only the built-in kinds, shared
+    pub fn builtin() -> &'static Self {
+        static REGISTRY: OnceLock<ReasonRegistry> = OnceLock::new();
+        REGISTRY.get_or_init(Self::default)
+    }
+
+    /// Add or replace a kind
+    pub fn register(&mut self, key: &str, info: ReasonInfo) {
+        self.kinds.insert(key.to_string(), info);
+    }
+
+    pub fn info(&self, key: &str) -> Option<&ReasonInfo> {
+        self.kinds.get(key)
+    }
+
+    /// Registered kinds, alphabetically
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = self.kinds.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    /// Message for `reason`. Unregistered kinds read as their name, with the
+    /// detail after a colon.
+    pub fn describe(&self, reason: &AttentionReason) -> String {
+        let detail = reason.detail();
+        let detail = detail.as_deref();
+        let Some(info) = self.info(reason.key()) else {
+            return match reason {
+                AttentionReason::Custom(text) => text.clone(),
+                _ => with_detail(&humanize(reason.key()), detail),
+            };
+        };
+        if info.template.contains("{detail") {
+            // Reasons are shown as plain text, in `list` too
+            let text = template::plain(&template::render(&info.template, |name| {
+                (name == "detail").then(|| detail.unwrap_or_default().to_string())
+            }));
+            // "Rate limited: " with nothing to follow
+            text.trim_end_matches([' ', ':']).to_string()
+        } else {
+            with_detail(&info.template, detail)
+        }
+    }
+
+    pub fn severity(&self, reason: &AttentionReason) -> Urgency {
+        self.info(reason.key())
+            .map_or(Urgency::Normal, |info| info.severity)
+    }
+
+    pub fn icon(&self, reason: &AttentionReason) -> &str {
+        self.info(reason.key())
+            .map_or("", |info| info.icon.as_str())
+    }
+
+    /// How long an acknowledged need of kind `key` stays quiet
+    pub fn ack_cooldown(&self, key: &str) -> chrono::Duration {
+        let minutes = self
+            .info(key)
+            .map_or(ACK_COOLDOWN_MINUTES, |info| info.ack_cooldown_minutes);
+        chrono::Duration::minutes(minutes as i64)
+    }
+}
+
+fn with_detail(text: &str, detail: Option<&str>) -> String {
+    match detail {
+        Some(detail) if !detail.is_empty() => format!("{}: {}", text, detail),
+        _ => text.to_string(),
+    }
+}
+
+/// "merge_conflict" -> "Merge conflict"
+fn humanize(key: &str) -> String {
+    let text = key.replace(['_', '-'], " ");
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let mut config = HashMap::new();
+        config.insert(
+            "deploy_gate".to_string(),
+            ReasonConfig {
+                severity: Some(Urgency::Critical),
+                icon: Some("🚦".to_string()),
+                template: None,
+                ack_cooldown_minutes: Some(0),
+            },
+        );
+        config.insert(
+            "process_stalled".to_string(),
+            ReasonConfig {
+                severity: None,
+                icon: Some("💤".to_string()),
+                ..Default::default()
+            },
+        );
+        let registry = ReasonRegistry::from_config(&config);
+
+        let gate = AttentionReason::kind("deploy_gate", Some("prod"));
+        assert_eq!(registry.describe(&gate), "Deploy gate: prod");
+        assert_eq!(registry.severity(&gate), Urgency::Critical);
+        assert_eq!(registry.icon(&gate), "🚦");
+        assert_eq!(
+            registry.ack_cooldown("deploy_gate"),
+            chrono::Duration::zero()
+        );
+        assert_eq!(
+            registry.ack_cooldown("waiting_for_input"),
+            chrono::Duration::minutes(30)
+        );
+
+        let stalled = AttentionReason::ProcessStalled;
+        assert_eq!(registry.describe(&stalled), "Process stalled (no activity)");
+        assert_eq!(registry.icon(&stalled), "💤");
+        assert_eq!(
+            registry.severity(&AttentionReason::PasswordPrompt),
+            Urgency::Critical
+        );
+
+        let conflict = AttentionReason::kind("merge_conflict", Some("src/lib.rs"));
+        assert_eq!(registry.describe(&conflict), "Merge conflict in src/lib.rs");
+        assert_eq!(
+            registry.describe(&AttentionReason::kind("rate_limited", None)),
+            "Rate limited"
+        );
+        assert_eq!(
+            registry.describe(&AttentionReason::kind("unknown_thing", None)),
+            "Unknown thing"
+        );
+        assert_eq!(
+            registry.describe(&AttentionReason::Custom("Look here".into())),
+            "Look here"
+        );
+    }
+}
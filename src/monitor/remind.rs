@@ -0,0 +1,149 @@
+//! Reminders while a task keeps waiting: `[reminders]`
+//!
+//! One missed notification shouldn't leave an agent waiting all afternoon.
+//! While an attention need of one of `reasons` (waiting for input by
+//! default) goes unanswered and unacknowledged, it is sent again after
+//! growing waits: `first_secs`, then each wait `factor` times the one
+//! before (5m, 15m, 45m, ...), `max` times at most. Each reminder says how
+//! long the task has been waiting.
+//!
+//! A channel can keep its own schedule under `[reminders.channels.<name>]`,
+//! to keep pinging a phone but not the desktop, say. Reminders go to the
+//! channels routing selects at the time, each on its own schedule.
+
+use crate::config::RemindersConfig;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// When reminders go to a channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    pub first: Duration,
+    pub factor: f64,
+    pub max: u32,
+}
+
+impl Schedule {
+    /// How long after the first notification reminder `n` (from 1) is due
+    pub fn at(&self, n: u32) -> Duration {
+        (0..n)
+            .map(|k| self.first.mul_f64(self.factor.max(1.0).powi(k as i32)))
+            .sum()
+    }
+}
+
+/// The configured schedules; none are sent by default
+#[derive(Debug, Clone)]
+pub struct Reminders {
+    schedule: Schedule,
+    channels: HashMap<String, Schedule>,
+    reasons: Vec<String>,
+}
+
+impl Default for Reminders {
+    fn default() -> Self {
+        Self::from_config(&RemindersConfig::default())
+    }
+}
+
+impl Reminders {
+    pub fn from_config(config: &RemindersConfig) -> Self {
+        let schedule = Schedule {
+            first: Duration::from_secs(config.first_secs),
+            factor: config.factor,
+            max: config.max,
+        };
+        let channels = config
+            .channels
+            .iter()
+            .map(|(name, channel)| {
+                let own = Schedule {
+                    first: channel
+                        .first_secs
+                        .map_or(schedule.first, Duration::from_secs),
+                    factor: channel.factor.unwrap_or(schedule.factor),
+                    max: channel.max.unwrap_or(schedule.max),
+                };
+                (name.clone(), own)
+            })
+            .collect();
+        Self {
+            schedule,
+            channels,
+            reasons: config.reasons.clone(),
+        }
+    }
+
+    fn schedule(&self, channel: &str) -> &Schedule {
+        self.channels.get(channel).unwrap_or(&self.schedule)
+    }
+
+    /// Whether needs of the reason `key` are reminded about on any channel
+    pub fn covers(&self, key: &str) -> bool {
+        self.reasons.iter().any(|reason| reason == key)
+            && (self.schedule.max > 0 || self.channels.values().any(|s| s.max > 0))
+    }
+
+    /// The channels out of `channels` due their next reminder after
+    /// `waited`, counted in `sent`, which holds how many each has had
+    pub fn due(
+        &self,
+        channels: &[&str],
+        sent: &mut HashMap<String, u32>,
+        waited: Duration,
+    ) -> Vec<String> {
+        let mut due = Vec::new();
+        for &channel in channels {
+            let schedule = self.schedule(channel);
+            let count = sent.entry(channel.to_string()).or_default();
+            if *count < schedule.max && waited >= schedule.at(*count + 1) {
+                *count += 1;
+                due.push(channel.to_string());
+            }
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ReminderChannelConfig;
+
+    #[test]
+    fn test_reminder_schedule() {
+        let mut config = RemindersConfig {
+            max: 3,
+            ..Default::default()
+        };
+        config.channels.insert(
+            "desktop".into(),
+            ReminderChannelConfig {
+                max: Some(1),
+                ..Default::default()
+            },
+        );
+        let reminders = Reminders::from_config(&config);
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        // 5m, then 15m and 45m more
+        let schedule = reminders.schedule("slack");
+        assert_eq!(
+            (schedule.at(1), schedule.at(2), schedule.at(3)),
+            (minutes(5), minutes(20), minutes(65))
+        );
+        assert!(reminders.covers("waiting_for_input") && !reminders.covers("process_stalled"));
+        assert!(!Reminders::default().covers("waiting_for_input"));
+
+        let mut sent = HashMap::new();
+        let channels = ["desktop", "slack"];
+        assert!(reminders.due(&channels, &mut sent, minutes(4)).is_empty());
+        assert_eq!(
+            reminders.due(&channels, &mut sent, minutes(6)),
+            ["desktop", "slack"]
+        );
+        assert!(reminders.due(&channels, &mut sent, minutes(6)).is_empty());
+        assert_eq!(reminders.due(&channels, &mut sent, minutes(25)), ["slack"]);
+        assert_eq!(reminders.due(&channels, &mut sent, minutes(70)), ["slack"]);
+        assert!(reminders.due(&channels, &mut sent, minutes(600)).is_empty());
+    }
+}
@@ -0,0 +1,385 @@
+//! Auto-respond rules for known prompts
+//!
+//! A rule pairs a prompt pattern with the reply to type, and only applies to
+//! the tasks it is explicitly allowed for. When the last line a task printed
+//! matches, the monitor writes the reply to the task's input and sends an
+//! informational notification instead of an attention one.
+//!
+//! A deny-list guards every reply: if the prompt or the output leading up to
+//! it looks destructive ("rm -rf", "force push", "drop table", payments...),
+//! the reply is never sent and the task is escalated with a critical
+//! notification quoting the output, whatever the rules say. Replies typed by
+//! `[[rules]]` and `--on-unattended` go through the same check with
+//! [`AutoResponder::denied`].
+
+use crate::config::AutoRespondRule;
+use crate::models::Task;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::Command;
+use std::sync::Mutex;
+
+/// Responses kept in a task's metadata; older ones are dropped
+const MAX_LOGGED_RESPONSES: usize = 20;
+
+/// Output that must never be answered automatically. Extra patterns can be
+/// added with `auto_respond_deny` in the config; these cannot be removed.
+const BUILTIN_DENY: &[&str] = &[
+    r"\brm\s+-[a-z]*(r[a-z]*f|f[a-z]*r)",
+    r"\bdelet(e|ed|es|ing|ion)\b",
+    r"\b(remove|destroy|wipe|erase|purge)\b",
+    r"\bdrop\s+(table|database|schema|index)\b",
+    r"\btruncate\s+table\b",
+    r"force[- ]push|\bpush\b.*(\s-f\b|--force)",
+    r"\breset\s+--hard\b",
+    r"\bmkfs\b|\bdd\s+if=",
+    r"\b(payment|purchase|pay now|billing|credit card|subscribe|subscription)\b",
+    r"cannot be undone|irreversibl|permanently",
+];
+
+/// Where replies to a task's prompts are written
+pub trait InputSink: Send {
+    /// Type `text` followed by Enter
+    fn send_line(&self, text: &str) -> Result<()>;
+}
+
+/// Writes to a pipe connected to the task's stdin (tasks the daemon started)
+pub struct PipeInput<W: Write + Send> {
+    pipe: Mutex<W>,
+}
+
+impl<W: Write + Send> PipeInput<W> {
+    pub fn new(pipe: W) -> Self {
+        Self {
+            pipe: Mutex::new(pipe),
+        }
+    }
+}
+
+impl<W: Write + Send> InputSink for PipeInput<W> {
+    fn send_line(&self, text: &str) -> Result<()> {
+        let mut pipe = self.pipe.lock().unwrap();
+        writeln!(pipe, "{}", text)?;
+        pipe.flush()?;
+        Ok(())
+    }
+}
+
+/// Types into a tmux pane with `tmux send-keys`
+pub struct TmuxInput {
+    pane: String,
+}
+
+impl TmuxInput {
+    pub fn new(pane: &str) -> Self {
+        Self {
+            pane: pane.to_string(),
+        }
+    }
+}
+
+impl InputSink for TmuxInput {
+    fn send_line(&self, text: &str) -> Result<()> {
+        let status = Command::new("tmux")
+            .args(["send-keys", "-t", &self.pane, "-l", text])
+            .status()
+            .context("Failed to run tmux")?;
+        if !status.success() {
+            anyhow::bail!("tmux send-keys failed for pane {}", self.pane);
+        }
+        Command::new("tmux")
+            .args(["send-keys", "-t", &self.pane, "Enter"])
+            .status()
+            .context("Failed to run tmux")?;
+        Ok(())
+    }
+}
+
+/// A reply chosen for a prompt
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply {
+    pub rule: String,
+    pub response: String,
+}
+
+/// What to do about a prompt a rule matched
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    Reply(Reply),
+    /// The deny-list matched; `matched` is the offending text
+    Blocked {
+        rule: String,
+        matched: String,
+    },
+}
+
+/// An auto-response recorded in the task metadata
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AutoResponse {
+    pub at: DateTime<Utc>,
+    pub rule: String,
+    pub prompt: String,
+    pub response: String,
+}
+
+struct Rule {
+    name: String,
+    pattern: Regex,
+    response: String,
+    tasks: Vec<String>,
+}
+
+impl Rule {
+    fn allowed_for(&self, task: &Task) -> bool {
+        self.tasks
+            .iter()
+            .any(|p| glob_match(p, &task.task_id) || glob_match(p, &task.agent_type))
+    }
+}
+
+pub struct AutoResponder {
+    rules: Vec<Rule>,
+    deny: Vec<Regex>,
+}
+
+impl Default for AutoResponder {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            deny: builtin_deny(),
+        }
+    }
+}
+
+fn builtin_deny() -> Vec<Regex> {
+    BUILTIN_DENY
+        .iter()
+        .map(|p| Regex::new(&format!("(?i){}", p)).unwrap())
+        .collect()
+}
+
+impl AutoResponder {
+    pub fn new(rules: &[AutoRespondRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                Ok(Rule {
+                    name: rule.name.clone(),
+                    pattern: Regex::new(&rule.pattern).with_context(|| {
+                        format!("Invalid pattern in auto_respond '{}'", rule.name)
+                    })?,
+                    response: rule.response.clone(),
+                    tasks: rule.tasks.clone(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            rules,
+            deny: builtin_deny(),
+        })
+    }
+
+    /// Add deny patterns on top of the built-in ones (case-insensitive)
+    pub fn deny(mut self, patterns: &[String]) -> Result<Self> {
+        for pattern in patterns {
+            self.deny.push(
+                Regex::new(&format!("(?i){}", pattern))
+                    .with_context(|| format!("Invalid auto_respond_deny pattern '{}'", pattern))?,
+            );
+        }
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether any rule is allowed for `task`
+    pub fn applies_to(&self, task: &Task) -> bool {
+        self.rules.iter().any(|rule| rule.allowed_for(task))
+    }
+
+    /// Decide how to answer `prompt`, the last line of output. `excerpt`
+    /// is the output leading up to it, which is checked against the
+    /// deny-list too. None when no allowed rule matches.
+    pub fn decide(&self, task: &Task, prompt: &str, excerpt: &str) -> Option<Decision> {
+        let rule = self
+            .rules
+            .iter()
+            .find(|rule| rule.allowed_for(task) && rule.pattern.is_match(prompt))?;
+
+        Some(match self.denied(prompt, excerpt) {
+            Some(matched) => Decision::Blocked {
+                rule: rule.name.clone(),
+                matched,
+            },
+            None => Decision::Reply(Reply {
+                rule: rule.name.clone(),
+                response: rule.response.clone(),
+            }),
+        })
+    }
+
+    /// The text in `prompt` or the `excerpt` before it that the deny-list
+    /// forbids answering, if any
+    pub fn denied(&self, prompt: &str, excerpt: &str) -> Option<String> {
+        self.deny
+            .iter()
+            .find_map(|deny| deny.find(prompt).or_else(|| deny.find(excerpt)))
+            .map(|found| found.as_str().to_string())
+    }
+}
+
+/// Append an auto-response to the task's log of them
+pub fn record(task: &mut Task, response: AutoResponse) {
+    let mut log = responses(task);
+    log.push(response);
+    let excess = log.len().saturating_sub(MAX_LOGGED_RESPONSES);
+    log.drain(..excess);
+
+    if let Ok(value) = serde_json::to_value(&log) {
+        task.metadata
+            .get_or_insert_with(Default::default)
+            .insert("auto_responses".to_string(), value);
+    }
+}
+
+/// Auto-responses sent to a task, oldest first
+pub fn responses(task: &Task) -> Vec<AutoResponse> {
+    task.metadata
+        .as_ref()
+        .and_then(|m| m.get("auto_responses"))
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+/// Match `text` against a pattern where `*` stands for any run of characters
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == text;
+    };
+    let Some(mut remaining) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts: Vec<&str> = rest.split('*').collect();
+    let last = parts.pop().unwrap_or("");
+    for part in parts {
+        match remaining.find(part) {
+            Some(i) => remaining = &remaining[i + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.len() >= last.len() && remaining.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, tasks: &[&str]) -> AutoRespondRule {
+        AutoRespondRule {
+            name: "proceed".to_string(),
+            pattern: pattern.to_string(),
+            response: "y".to_string(),
+            tasks: tasks.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    fn task(id: &str) -> Task {
+        Task::new(
+            id.to_string(),
+            "claude_code".to_string(),
+            "t".to_string(),
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_rules_only_apply_to_allowed_tasks() {
+        let responder = AutoResponder::new(&[rule(r"Proceed\? \[y/N\]", &["schedule-*"])]).unwrap();
+
+        let nightly = task("schedule-nightly");
+        assert!(responder.applies_to(&nightly));
+        assert_eq!(
+            responder.decide(&nightly, "Proceed? [y/N] ", ""),
+            Some(Decision::Reply(Reply {
+                rule: "proceed".to_string(),
+                response: "y".to_string()
+            }))
+        );
+        assert!(responder.decide(&nightly, "Continue?", "").is_none());
+
+        assert!(responder
+            .decide(&task("adhoc"), "Proceed? [y/N]", "")
+            .is_none());
+
+        let by_agent = AutoResponder::new(&[rule("Proceed", &["claude_code"])]).unwrap();
+        assert!(by_agent.decide(&task("adhoc"), "Proceed?", "").is_some());
+
+        assert!(AutoResponder::new(&[rule("(", &["*"])]).is_err());
+    }
+
+    #[test]
+    fn test_deny_list_blocks_destructive_prompts() {
+        let responder = AutoResponder::new(&[rule(r"\[y/N\]", &["*"])])
+            .unwrap()
+            .deny(&["terraform apply".to_string()])
+            .unwrap();
+        let t = task("t");
+        let blocked = |prompt: &str, excerpt: &str| {
+            matches!(
+                responder.decide(&t, prompt, excerpt),
+                Some(Decision::Blocked { .. })
+            )
+        };
+
+        assert!(blocked("Delete 3 files? [y/N]", ""));
+        assert!(blocked("Run it? [y/N]", "About to run: rm -rf build/"));
+        assert!(blocked("Continue? [y/N]", "git push --force origin main"));
+        assert!(blocked("Confirm payment of $20? [y/N]", ""));
+        assert!(blocked("OK? [y/N]", "DROP TABLE users;"));
+        assert!(blocked("OK? [y/N]", "Running terraform apply"));
+        assert!(!blocked("Run the tests? [y/N]", "Compiling foo"));
+
+        match responder.decide(&t, "Proceed? [y/N]", "will rm -fr /tmp/x") {
+            Some(Decision::Blocked { matched, .. }) => assert_eq!(matched, "rm -fr"),
+            other => panic!("expected block, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("schedule-*", "schedule-nightly"));
+        assert!(glob_match("*-agent", "deploy-agent"));
+        assert!(glob_match("a*b*c", "a-x-b-y-c"));
+        assert!(!glob_match("a*b", "ba"));
+        assert!(!glob_match("exact", "exactly"));
+    }
+
+    #[test]
+    fn test_pipe_input_and_record() {
+        let sink = PipeInput::new(Vec::new());
+        sink.send_line("y").unwrap();
+        assert_eq!(*sink.pipe.lock().unwrap(), b"y\n");
+
+        let mut task = task("t");
+        for _ in 0..MAX_LOGGED_RESPONSES + 3 {
+            record(
+                &mut task,
+                AutoResponse {
+                    at: Utc::now(),
+                    rule: "proceed".to_string(),
+                    prompt: "Proceed? [y/N]".to_string(),
+                    response: "y".to_string(),
+                },
+            );
+        }
+        assert_eq!(responses(&task).len(), MAX_LOGGED_RESPONSES);
+    }
+}
@@ -0,0 +1,426 @@
+//! Compound conditions on attention: `[[rules]]`
+//!
+//! Reasons map to one severity each; rules add what that mapping can't
+//! express, like "stalled twice in ten minutes on a `ci` task: kill it and
+//! page me". A rule combines conditions on the reason, the task's tags, its
+//! runtime, the time of day, recent output and how often the reason came
+//! up, and acts by routing, escalating, replying, killing or running a
+//! command. The monitor carries out the actions; this module decides which
+//! rules hold.
+//!
+//! What the built-in conditions can't say, a `script` can: a WebAssembly
+//! module (see [`crate::sandbox::wasm`]) exporting
+//! `matches(ptr: i32, len: i32) -> i32`, called last with
+//! `{"task", "reason", "output"}` and holding when it returns non-zero. A
+//! script that fails, or runs out of fuel, doesn't hold.
+
+use crate::config::{RuleConfig, RuleThen, SandboxConfig};
+use crate::models::Task;
+use crate::monitor::respond::glob_match;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveTime, Utc};
+use regex::Regex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// The configured rules and how often each task's reasons came up
+#[derive(Default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+    /// When each (task, reason) came up, within the longest window
+    seen: Mutex<HashMap<(String, String), VecDeque<SystemTime>>>,
+}
+
+struct Rule {
+    config: RuleConfig,
+    hours: Option<(NaiveTime, NaiveTime)>,
+    output: Option<Regex>,
+    #[cfg(feature = "wasm")]
+    script: Option<crate::sandbox::wasm::Script>,
+}
+
+/// A task needing attention, as the rules see it
+pub struct Occurrence<'a> {
+    pub task: &'a Task,
+    /// Reason key, as in `[reasons.<key>]`
+    pub reason: &'a str,
+    /// The last lines of output
+    pub output: &'a str,
+    pub now: SystemTime,
+}
+
+impl Rules {
+    /// The rules in `configs`, with scripts limited per `sandbox`
+    pub fn new(configs: &[RuleConfig], sandbox: &SandboxConfig) -> Result<Self> {
+        let rules = configs
+            .iter()
+            .map(|config| {
+                let hours = config
+                    .when
+                    .hours
+                    .as_deref()
+                    .map(parse_hours)
+                    .transpose()
+                    .with_context(|| format!("Invalid hours in rule '{}'", config.name))?;
+                let output = config
+                    .when
+                    .output
+                    .as_deref()
+                    .map(Regex::new)
+                    .transpose()
+                    .with_context(|| format!("Invalid output pattern in rule '{}'", config.name))?;
+                #[cfg(feature = "wasm")]
+                let script = config
+                    .when
+                    .script
+                    .as_deref()
+                    .map(|path| {
+                        let path = crate::storage::config_dir().join(path);
+                        let script = crate::sandbox::wasm::Script::load(&path, sandbox)?;
+                        anyhow::ensure!(script.exports("matches"), "it exports no matches");
+                        Ok(script)
+                    })
+                    .transpose()
+                    .with_context(|| format!("Invalid script in rule '{}'", config.name))?;
+                #[cfg(not(feature = "wasm"))]
+                {
+                    let _ = sandbox;
+                    anyhow::ensure!(
+                        config.when.script.is_none(),
+                        "Rule '{}' has a script, which needs the wasm feature",
+                        config.name
+                    );
+                }
+                Ok(Rule {
+                    config: config.clone(),
+                    hours,
+                    output,
+                    #[cfg(feature = "wasm")]
+                    script,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            rules,
+            seen: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Count `occurrence` towards repeats and return the rules it matches,
+    /// by name, in config order
+    pub fn matching(&self, occurrence: &Occurrence) -> Vec<(&str, &RuleThen)> {
+        if self.rules.is_empty() {
+            return Vec::new();
+        }
+        let times = self.record(occurrence);
+        self.rules
+            .iter()
+            .filter(|rule| rule.holds(occurrence, &times))
+            .map(|rule| (rule.config.name.as_str(), &rule.config.then))
+            .collect()
+    }
+
+    /// Remember this occurrence; returns every recent one of the same
+    /// reason for the task, this one included
+    fn record(&self, occurrence: &Occurrence) -> Vec<SystemTime> {
+        let window = self
+            .rules
+            .iter()
+            .map(|rule| rule.config.when.within_secs)
+            .max()
+            .unwrap_or_default();
+        let cutoff = occurrence.now - Duration::from_secs(window);
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, times| {
+            times.retain(|&at| at >= cutoff);
+            !times.is_empty()
+        });
+        let key = (
+            occurrence.task.task_id.clone(),
+            occurrence.reason.to_string(),
+        );
+        let times = seen.entry(key).or_default();
+        times.push_back(occurrence.now);
+        times.iter().copied().collect()
+    }
+}
+
+impl Rule {
+    fn holds(&self, occurrence: &Occurrence, times: &[SystemTime]) -> bool {
+        let when = &self.config.when;
+        let task = occurrence.task;
+        if when
+            .reason
+            .as_ref()
+            .is_some_and(|p| !glob_match(p, occurrence.reason))
+        {
+            return false;
+        }
+        if when
+            .tag
+            .as_ref()
+            .is_some_and(|tag| !task.tags().contains(tag))
+        {
+            return false;
+        }
+        if let Some(min) = when.min_runtime_secs {
+            let now: DateTime<Utc> = occurrence.now.into();
+            if (now - task.created_at).num_seconds() < min as i64 {
+                return false;
+            }
+        }
+        if let Some((from, to)) = self.hours {
+            let time = DateTime::<Local>::from(occurrence.now).time();
+            let inside = if from < to {
+                from <= time && time < to
+            } else {
+                time >= from || time < to
+            };
+            if !inside {
+                return false;
+            }
+        }
+        if self
+            .output
+            .as_ref()
+            .is_some_and(|re| !re.is_match(occurrence.output))
+        {
+            return false;
+        }
+        if let Some(repeats) = when.repeats {
+            let cutoff = occurrence.now - Duration::from_secs(when.within_secs);
+            if times.iter().filter(|&&at| at >= cutoff).count() < repeats {
+                return false;
+            }
+        }
+        #[cfg(feature = "wasm")]
+        if let Some(script) = &self.script {
+            let input = serde_json::json!({
+                "task": task,
+                "reason": occurrence.reason,
+                "output": occurrence.output,
+            });
+            return match script.number("matches", &input) {
+                Ok(matches) => matches != 0,
+                Err(e) => {
+                    eprintln!("Rule '{}': {:#}", self.config.name, e);
+                    false
+                }
+            };
+        }
+        true
+    }
+}
+
+/// "22:00-07:00"
+fn parse_hours(hours: &str) -> Result<(NaiveTime, NaiveTime)> {
+    let (from, to) = hours
+        .split_once('-')
+        .context("expected a range like \"22:00-07:00\"")?;
+    let parse = |time: &str| {
+        NaiveTime::parse_from_str(time.trim(), "%H:%M")
+            .with_context(|| format!("'{}' is not a time like 07:00", time.trim()))
+    };
+    Ok((parse(from)?, parse(to)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RuleWhen;
+
+    fn rule(name: &str, when: RuleWhen) -> RuleConfig {
+        RuleConfig {
+            name: name.to_string(),
+            when,
+            then: RuleThen {
+                kill: true,
+                ..RuleThen::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_repeats_on_tagged_tasks() {
+        let rules = Rules::new(
+            &[
+                rule(
+                    "flaky-ci",
+                    RuleWhen {
+                        reason: Some("process_stalled".to_string()),
+                        tag: Some("ci".to_string()),
+                        repeats: Some(2),
+                        ..RuleWhen::default()
+                    },
+                ),
+                rule(
+                    "panics",
+                    RuleWhen {
+                        output: Some("panicked at".to_string()),
+                        ..RuleWhen::default()
+                    },
+                ),
+            ],
+            &SandboxConfig::default(),
+        )
+        .unwrap();
+        let mut ci = Task::new("t1".into(), "shell".into(), "CI".into(), None, None);
+        ci.set_tags(&["ci".to_string()]);
+        let other = Task::new("t2".into(), "shell".into(), "Other".into(), None, None);
+        let start = SystemTime::now();
+        let at = |task, reason, output, mins: u64| {
+            let occurrence = Occurrence {
+                task,
+                reason,
+                output,
+                now: start + Duration::from_secs(mins * 60),
+            };
+            rules
+                .matching(&occurrence)
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(at(&ci, "process_stalled", "", 0).is_empty());
+        // Another reason and another task don't count
+        assert!(at(&ci, "error_burst", "", 1).is_empty());
+        assert!(at(&other, "process_stalled", "", 2).is_empty());
+        assert!(at(&other, "process_stalled", "", 3).is_empty());
+        assert_eq!(at(&ci, "process_stalled", "", 5), vec!["flaky-ci"]);
+        // The first two are out of the window by now
+        assert!(at(&ci, "process_stalled", "", 20).is_empty());
+        assert_eq!(
+            at(
+                &ci,
+                "process_stalled",
+                "thread 'main' panicked at src/main.rs",
+                22
+            ),
+            vec!["flaky-ci", "panics"]
+        );
+    }
+
+    #[test]
+    fn test_hours() {
+        assert!(parse_hours("7am-9am").is_err());
+        let (from, to) = parse_hours("22:00 - 07:00").unwrap();
+        assert_eq!(from, NaiveTime::from_hms_opt(22, 0, 0).unwrap());
+        assert_eq!(to, NaiveTime::from_hms_opt(7, 0, 0).unwrap());
+
+        let task = Task::new("t1".into(), "shell".into(), "Night".into(), None, None);
+        let all_day = Rules::new(
+            &[rule(
+                "always",
+                RuleWhen {
+                    hours: Some("00:00-00:00".to_string()),
+                    ..RuleWhen::default()
+                },
+            )],
+            &SandboxConfig::default(),
+        )
+        .unwrap();
+        let occurrence = Occurrence {
+            task: &task,
+            reason: "process_stalled",
+            output: "",
+            now: SystemTime::now(),
+        };
+        // An empty range wraps around the whole day
+        assert_eq!(all_day.matching(&occurrence).len(), 1);
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_script() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, body: &str| {
+            let wat = format!(
+                r#"(module
+                    (memory (export "memory") 1)
+                    (data (i32.const 0) "panicked")
+                    (func (export "alloc") (param i32) (result i32) i32.const 1024)
+                    {}
+                )"#,
+                body
+            );
+            let path = dir.path().join(name);
+            std::fs::write(&path, wat::parse_str(wat).unwrap()).unwrap();
+            rule(
+                name,
+                RuleWhen {
+                    script: Some(path),
+                    ..RuleWhen::default()
+                },
+            )
+        };
+        // Holds when the input has "panicked" in it
+        let panics = write(
+            "panics.wasm",
+            r#"(func (export "matches") (param $ptr i32) (param $len i32) (result i32)
+                (local $i i32) (local $j i32)
+                (block $none
+                    (loop $next
+                        (br_if $none (i32.gt_s (i32.add (local.get $i) (i32.const 8))
+                                               (local.get $len)))
+                        (local.set $j (i32.const 0))
+                        (block $differ
+                            (loop $compare
+                                (br_if $differ (i32.ne
+                                    (i32.load8_u (i32.add (local.get $ptr)
+                                        (i32.add (local.get $i) (local.get $j))))
+                                    (i32.load8_u (local.get $j))))
+                                (local.set $j (i32.add (local.get $j) (i32.const 1)))
+                                (br_if $compare (i32.lt_u (local.get $j) (i32.const 8)))
+                                (return (i32.const 1))))
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br $next)))
+                i32.const 0)"#,
+        );
+        let spins = write(
+            "spins.wasm",
+            r#"(func (export "matches") (param i32 i32) (result i32)
+                (loop $forever (br $forever))
+                i32.const 1)"#,
+        );
+        let sandbox = SandboxConfig {
+            fuel: 1_000_000,
+            ..SandboxConfig::default()
+        };
+        let rules = Rules::new(&[panics, spins], &sandbox).unwrap();
+
+        let task = Task::new("t1".into(), "shell".into(), "Build".into(), None, None);
+        let matching = |output: &str| -> Vec<String> {
+            let occurrence = Occurrence {
+                task: &task,
+                reason: "process_stalled",
+                output,
+                now: SystemTime::now(),
+            };
+            rules
+                .matching(&occurrence)
+                .into_iter()
+                .map(|(name, _)| name.to_string())
+                .collect()
+        };
+        // The script that runs out of fuel never holds
+        assert_eq!(
+            matching("thread 'main' panicked at src/main.rs"),
+            vec!["panics.wasm"]
+        );
+        assert!(matching("all good").is_empty());
+
+        let nothing = write("nothing.wasm", "");
+        let error = Rules::new(&[nothing], &sandbox).err().unwrap();
+        assert!(
+            format!("{:#}", error).contains("exports no matches"),
+            "{:#}",
+            error
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Processes managed outside agent-inbox, found through a pid file or a
+//! systemd unit
+//!
+//! [`TaskMonitor::follow`](super::TaskMonitor::follow) resolves the source
+//! again on every poll. When it points at a different process (the service
+//! restarted, or re-executed and rewrote its pid file) the current run ends
+//! and a new run of the same task starts.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Where to find the process behind a task
+pub trait PidSource: Send + Sync {
+    /// Shown in events and messages, e.g. "/run/agent.pid"
+    fn describe(&self) -> String;
+
+    /// The current process, None while there is none
+    fn resolve(&self) -> Option<i32>;
+}
+
+/// A pid file written by the process or its supervisor
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl PidSource for PidFile {
+    fn describe(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn resolve(&self) -> Option<i32> {
+        // Missing, empty or half-written files all mean "no process yet"
+        let content = std::fs::read_to_string(&self.path).ok()?;
+        content.trim().parse().ok().filter(|&pid| pid > 0)
+    }
+}
+
+/// The main process of a systemd service, read from the service manager
+/// over D-Bus with `busctl`
+pub struct SystemdUnit {
+    unit: String,
+    /// The user's service manager rather than the system one
+    user: bool,
+}
+
+impl SystemdUnit {
+    /// `unit` without a suffix is taken to be a service
+    pub fn new(unit: &str, user: bool) -> Self {
+        let unit = if unit.contains('.') {
+            unit.to_string()
+        } else {
+            format!("{}.service", unit)
+        };
+        Self { unit, user }
+    }
+
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+}
+
+impl PidSource for SystemdUnit {
+    fn describe(&self) -> String {
+        self.unit.clone()
+    }
+
+    fn resolve(&self) -> Option<i32> {
+        let mut command = Command::new("busctl");
+        if self.user {
+            command.arg("--user");
+        }
+        let output = command
+            .args([
+                "get-property",
+                "org.freedesktop.systemd1",
+                &unit_object_path(&self.unit),
+                "org.freedesktop.systemd1.Service",
+                "MainPID",
+            ])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_main_pid(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// D-Bus object path of a unit: every byte that isn't alphanumeric (and a
+/// leading digit) becomes `_xx`, so "my-agent.service" is
+/// `/org/freedesktop/systemd1/unit/my_2dagent_2eservice`
+fn unit_object_path(unit: &str) -> String {
+    let mut path = String::from("/org/freedesktop/systemd1/unit/");
+    for (i, byte) in unit.bytes().enumerate() {
+        if byte.is_ascii_alphabetic() || (byte.is_ascii_digit() && i > 0) {
+            path.push(byte as char);
+        } else {
+            path.push_str(&format!("_{:02x}", byte));
+        }
+    }
+    path
+}
+
+/// `busctl` prints the property as "u 1234"; 0 means the unit isn't running
+fn parse_main_pid(output: &str) -> Option<i32> {
+    let pid = output.trim().strip_prefix("u ")?.parse().ok()?;
+    (pid > 0).then_some(pid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = PidFile::new(dir.path().join("agent.pid"));
+        assert_eq!(source.resolve(), None);
+
+        std::fs::write(dir.path().join("agent.pid"), "4242\n").unwrap();
+        assert_eq!(source.resolve(), Some(4242));
+        std::fs::write(dir.path().join("agent.pid"), "").unwrap();
+        assert_eq!(source.resolve(), None);
+    }
+
+    #[test]
+    fn test_systemd_unit() {
+        let unit = SystemdUnit::new("my-agent", true);
+        assert_eq!(unit.unit(), "my-agent.service");
+        assert_eq!(
+            unit_object_path(unit.unit()),
+            "/org/freedesktop/systemd1/unit/my_2dagent_2eservice"
+        );
+        assert_eq!(
+            unit_object_path("2fa@x.service"),
+            "/org/freedesktop/systemd1/unit/_32fa_40x_2eservice"
+        );
+        assert_eq!(parse_main_pid("u 1234\n"), Some(1234));
+        assert_eq!(parse_main_pid("u 0\n"), None);
+    }
+}
@@ -0,0 +1,133 @@
+//! Why a notification did or didn't fire: `agent-inbox debug trace <task>`
+//!
+//! At every check the monitor writes down what it decided and from what:
+//! the idle times and startup grace it went by, each detector's verdict in
+//! order (the first that finds something wins, so later ones aren't asked),
+//! how far false positives relaxed them, and what became of a detection.
+//! That last one covers a need still within its acknowledgement cooldown,
+//! a classifier verdict, one already raised, a rule keeping it quiet, a
+//! user attached to the task, and the channels it went to.
+//!
+//! ```text
+//! 2026-10-16T09:30:05Z check    idle 11m (output 11m, cpu 40s); startup grace over
+//! 2026-10-16T09:30:05Z detect   TerminalLost -, PasswordPrompt -, Approval -, RateLimit -, Stall process_stalled
+//! 2026-10-16T09:30:05Z route    Process stalled (no activity) (via desktop, slack)
+//! ```
+//!
+//! Each task's trace goes to `~/.local/state/agent-inbox/trace/<task>.log`,
+//! which is kept under [`MAX_BYTES`]: the older half moves to `.log.1`.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Size at which a trace starts over, keeping the previous one as `.1`
+pub const MAX_BYTES: u64 = 256 * 1024;
+
+/// Where traces are written
+pub fn dir() -> PathBuf {
+    crate::storage::state_dir().join("trace")
+}
+
+/// `task_id`'s trace in `dir`
+pub fn path(dir: &Path, task_id: &str) -> PathBuf {
+    dir.join(format!("{}.log", task_id))
+}
+
+/// The file before the last rotation
+pub fn previous(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// Appends a task's trace; failing to write never disturbs monitoring
+pub struct Trace {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl Trace {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, file: None }
+    }
+
+    /// Write `message` as one line under `stage` ("check", "detect",
+    /// "route", ...)
+    pub fn record(&mut self, at: DateTime<Utc>, stage: &str, message: &str) {
+        let line = format!(
+            "{} {:<8} {}\n",
+            at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            stage,
+            message.replace('\n', " ")
+        );
+        if self
+            .file
+            .as_ref()
+            .is_some_and(|f| f.metadata().is_ok_and(|m| m.len() >= MAX_BYTES))
+        {
+            self.file = None;
+            let _ = fs::rename(&self.path, previous(&self.path));
+        }
+        if self.file.is_none() {
+            if let Some(dir) = self.path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            self.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .ok();
+        }
+        if let Some(file) = &mut self.file {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+}
+
+/// The last `lines` lines traced at `path`, reaching into the rotated file
+/// when the current one is short
+pub fn last_lines(path: &Path, lines: usize) -> Vec<String> {
+    let read = |path: &Path| fs::read_to_string(path).unwrap_or_default();
+    let current = read(path);
+    let mut found: Vec<String> = current.lines().map(str::to_string).collect();
+    if found.len() < lines {
+        let older = read(&previous(path));
+        let mut earlier: Vec<String> = older.lines().map(str::to_string).collect();
+        earlier.append(&mut found);
+        found = earlier;
+    }
+    found.split_off(found.len().saturating_sub(lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_rotates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = path(dir.path(), "t1");
+        let mut trace = Trace::new(path.clone());
+        let at = DateTime::parse_from_rfc3339("2026-10-16T09:30:05Z")
+            .unwrap()
+            .to_utc();
+        trace.record(at, "detect", "Stall process_stalled");
+        assert_eq!(
+            last_lines(&path, 5),
+            ["2026-10-16T09:30:05Z detect   Stall process_stalled"]
+        );
+
+        let long = "x".repeat(1024);
+        for _ in 0..(MAX_BYTES / 1024 + 1) {
+            trace.record(at, "check", &long);
+        }
+        trace.record(at, "route", "multi\nline");
+        assert!(previous(&path).exists());
+        assert!(fs::metadata(&path).unwrap().len() < MAX_BYTES);
+        let last = last_lines(&path, 3);
+        assert_eq!(last.len(), 3);
+        assert!(last[2].ends_with("route    multi line"));
+    }
+}
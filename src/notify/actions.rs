@@ -0,0 +1,308 @@
+//! Actions on macOS notifications
+//!
+//! osascript banners can't carry buttons, so when [alerter] is installed the
+//! desktop channel hands notifications about a task to `agent-inbox alert`,
+//! which shows them with "Reply…" and "Open Terminal" actions and waits for
+//! the click. A reply is typed into the task's tmux pane; "Open Terminal"
+//! (or clicking the notification) brings the task's terminal to the front,
+//! on its tmux pane or Zellij tab, or runs the task's focus command. The action labels are translated with
+//! the desktop channel's [`locale`](super::locale).
+//!
+//! Alerts about a tool-approval prompt also get "Approve" and "Deny", which
+//! type the answer the prompt expects, as `agent-inbox respond` does.
+//! "Copy Prompt" puts what the task waits on on the clipboard, as
+//! `agent-inbox copy-prompt` does.
+//!
+//! [alerter]: https://github.com/vjeantet/alerter
+
+use super::locale::Catalog;
+use super::{Notification, Urgency};
+use crate::db::Database;
+use crate::models::{EventKind, Task};
+use crate::monitor::approval::Approval;
+use crate::monitor::respond::{InputSink, TmuxInput};
+use anyhow::{Context, Result};
+use std::process::{Command, Stdio};
+
+pub const OPEN_TERMINAL: &str = "Open Terminal";
+pub const REPLY: &str = "Reply…";
+pub const APPROVE: &str = "Approve";
+pub const DENY: &str = "Deny";
+pub const COPY_PROMPT: &str = "Copy Prompt";
+
+/// What the user did with an alert
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Reply(String),
+    OpenTerminal,
+    Approve,
+    Deny,
+    CopyPrompt,
+    /// Closed, timed out or never answered
+    Dismissed,
+}
+
+/// `alerter` is on PATH
+pub fn alerter_available() -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join("alerter").is_file()))
+}
+
+/// Show `notification` with actions from a detached `agent-inbox alert`, so
+/// the sender doesn't wait for the click
+pub fn spawn_alert(notification: &Notification, task_id: &str) -> Result<()> {
+    let mut command = Command::new("agent-inbox");
+    command
+        .args([
+            "alert",
+            task_id,
+            "--title",
+            &notification.title,
+            "--body",
+            &notification.body,
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if notification.urgency == Urgency::Critical {
+        command.arg("--critical");
+    }
+    if notification.reason.as_deref() == Some("waiting_approval") {
+        command.arg("--approval");
+    }
+    command.spawn().context("Failed to run agent-inbox alert")?;
+    Ok(())
+}
+
+/// Action labels in the alert's language
+struct Labels {
+    open: String,
+    reply: String,
+    approve: String,
+    deny: String,
+    copy: String,
+}
+
+impl Labels {
+    fn new(catalog: &Catalog) -> Self {
+        Self {
+            open: catalog.translate(OPEN_TERMINAL),
+            reply: catalog.translate(REPLY),
+            approve: catalog.translate(APPROVE),
+            deny: catalog.translate(DENY),
+            copy: catalog.translate(COPY_PROMPT),
+        }
+    }
+}
+
+fn alerter_args(
+    task_id: &str,
+    title: &str,
+    body: &str,
+    critical: bool,
+    approval: bool,
+    labels: &Labels,
+) -> Vec<String> {
+    let actions = if approval {
+        format!(
+            "{},{},{},{}",
+            labels.approve, labels.deny, labels.open, labels.copy
+        )
+    } else {
+        format!("{},{}", labels.open, labels.copy)
+    };
+    let mut args: Vec<String> = [
+        "-title",
+        title,
+        "-message",
+        body,
+        "-group",
+        task_id,
+        "-actions",
+        &actions,
+        "-reply",
+        &labels.reply,
+        "-json",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    if critical {
+        args.extend(["-sound".to_string(), "default".to_string()]);
+    }
+    args
+}
+
+/// alerter's `-json` output, with the actions shown under `labels`
+fn parse_response(output: &str, labels: &Labels) -> Action {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(output.trim()) else {
+        return Action::Dismissed;
+    };
+    let activation = value["activationValue"].as_str().unwrap_or_default();
+    match value["activationType"].as_str() {
+        Some("replied") if !activation.trim().is_empty() => Action::Reply(activation.to_string()),
+        Some("actionClicked") if activation == labels.open => Action::OpenTerminal,
+        Some("actionClicked") if activation == labels.approve => Action::Approve,
+        Some("actionClicked") if activation == labels.deny => Action::Deny,
+        Some("actionClicked") if activation == labels.copy => Action::CopyPrompt,
+        Some("contentsClicked") => Action::OpenTerminal,
+        _ => Action::Dismissed,
+    }
+}
+
+/// Show the alert, wait for the user and carry out what they chose
+pub fn run_alert(
+    db: &Database,
+    task_id: &str,
+    title: &str,
+    body: &str,
+    critical: bool,
+    approval: bool,
+    catalog: &Catalog,
+) -> Result<Action> {
+    let labels = Labels::new(catalog);
+    let output = Command::new("alerter")
+        .args(alerter_args(
+            task_id, title, body, critical, approval, &labels,
+        ))
+        .output()
+        .context("Failed to run alerter")?;
+    let action = parse_response(&String::from_utf8_lossy(&output.stdout), &labels);
+    let task = db.get_task_by_id(task_id)?;
+    match (&action, task.as_ref()) {
+        (Action::Reply(text), Some(task)) => reply(db, task, text, "a notification")?,
+        (Action::Approve | Action::Deny, Some(task)) => {
+            answer_approval(db, task, action == Action::Approve, "a notification")?;
+        }
+        (Action::CopyPrompt, Some(task)) => {
+            super::clipboard::copy_prompt(task)?;
+        }
+        (Action::OpenTerminal, task) => open_terminal(task)?,
+        (Action::Dismissed, _) => {}
+        (_, None) => anyhow::bail!("Task {} not found", task_id),
+    }
+    Ok(action)
+}
+
+/// Type `text` into `task`'s tmux pane and log it, as sent `from` a
+/// notification or the command line
+pub fn reply(db: &Database, task: &Task, text: &str, from: &str) -> Result<()> {
+    type_into(task, text)?;
+    db.log_event(
+        &task.task_id,
+        EventKind::Replied,
+        format!("\"{}\" from {}", text, from),
+    );
+    Ok(())
+}
+
+/// Approve or deny the tool-approval prompt `task` waits on, with the key
+/// the prompt expects. Returns the prompt answered.
+pub fn answer_approval(db: &Database, task: &Task, approve: bool, from: &str) -> Result<Approval> {
+    let approval = Approval::pending(task)
+        .with_context(|| format!("{} is not waiting for an approval", task.task_id))?;
+    let (key, verdict) = if approve {
+        (&approval.approve, "Approved")
+    } else {
+        (&approval.deny, "Denied")
+    };
+    type_into(task, key)?;
+    db.log_event(
+        &task.task_id,
+        EventKind::Replied,
+        format!("{} from {}: {}", verdict, from, approval.action),
+    );
+    Ok(approval)
+}
+
+fn type_into(task: &Task, text: &str) -> Result<()> {
+    let pane = task.tmux_pane().with_context(|| {
+        format!(
+            "No tmux pane known for {}, can't type the reply",
+            task.task_id
+        )
+    })?;
+    TmuxInput::new(pane).send_line(text)
+}
+
+/// Bring the terminal app forward, and the task's tmux pane or Zellij tab
+/// within it; a task's own focus command does all of that instead
+fn open_terminal(task: Option<&Task>) -> Result<()> {
+    if let Some(task) = task.filter(|t| t.focus_command().is_some()) {
+        return crate::presence::focus(task);
+    }
+    if let Some(task) = task.filter(|t| t.tmux_pane().is_some() || t.zellij_pane().is_some()) {
+        crate::presence::focus(task)?;
+    }
+    let app = terminal_app(std::env::var("TERM_PROGRAM").ok().as_deref());
+    let status = Command::new("open")
+        .args(["-a", app])
+        .status()
+        .context("Failed to run open")?;
+    anyhow::ensure!(status.success(), "open -a {} exited with {}", app, status);
+    Ok(())
+}
+
+/// The app behind `$TERM_PROGRAM`, inherited from the terminal the task was
+/// started in
+fn terminal_app(term_program: Option<&str>) -> &'static str {
+    match term_program {
+        Some("iTerm.app") => "iTerm",
+        Some("WezTerm") => "WezTerm",
+        Some("ghostty") => "Ghostty",
+        Some("vscode") => "Visual Studio Code",
+        Some("alacritty") => "Alacritty",
+        _ => "Terminal",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        let labels = Labels::new(&Catalog::default());
+        let replied = r#"{"activationType":"replied","activationValue":"yes, go ahead","deliveredAt":"2026-10-16 10:00:00 +0000"}"#;
+        assert_eq!(
+            parse_response(replied, &labels),
+            Action::Reply("yes, go ahead".to_string())
+        );
+        let clicked = r#"{"activationType":"actionClicked","activationValue":"Open Terminal"}"#;
+        assert_eq!(parse_response(clicked, &labels), Action::OpenTerminal);
+        assert_eq!(
+            parse_response(r#"{"activationType":"contentsClicked"}"#, &labels),
+            Action::OpenTerminal
+        );
+        assert_eq!(
+            parse_response(r#"{"activationType":"timeout"}"#, &labels),
+            Action::Dismissed
+        );
+        assert_eq!(
+            parse_response(
+                r#"{"activationType":"replied","activationValue":" "}"#,
+                &labels
+            ),
+            Action::Dismissed
+        );
+        assert_eq!(parse_response("", &labels), Action::Dismissed);
+        let approved = r#"{"activationType":"actionClicked","activationValue":"Approve"}"#;
+        assert_eq!(parse_response(approved, &labels), Action::Approve);
+        assert_eq!(
+            parse_response(
+                r#"{"activationType":"actionClicked","activationValue":"Deny"}"#,
+                &labels
+            ),
+            Action::Deny
+        );
+        let copy = r#"{"activationType":"actionClicked","activationValue":"Copy Prompt"}"#;
+        assert_eq!(parse_response(copy, &labels), Action::CopyPrompt);
+        let localized = r#"{"activationType":"actionClicked","activationValue":"Terminal öffnen"}"#;
+        let german = Labels {
+            open: "Terminal öffnen".to_string(),
+            ..Labels::new(&Catalog::default())
+        };
+        assert_eq!(parse_response(localized, &german), Action::OpenTerminal);
+        assert_eq!(parse_response(clicked, &german), Action::Dismissed);
+    }
+}
@@ -0,0 +1,203 @@
+//! What the on-call channels (PagerDuty, Opsgenie) share: one alert per
+//! task and reason, resolved when the task resumes or finishes
+//!
+//! The dedup key of an alert is `agent-inbox/<task_id>/<reason>`, so a
+//! task stuck on the same prompt updates one alert instead of paging again.
+//! Keys of open alerts are kept under `~/.local/state/agent-inbox/alerts/<channel>/`,
+//! one file per task, because the process that resolves an alert (e.g.
+//! `report running` from a hook) is rarely the one that raised it.
+
+use super::{Category, Notification, Urgency};
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// Open alerts raised through one channel
+pub struct OpenAlerts {
+    dir: PathBuf,
+}
+
+impl OpenAlerts {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// ~/.local/state/agent-inbox/alerts/<channel>
+    pub fn for_channel(channel: &str) -> Self {
+        Self::new(crate::storage::state_dir().join("alerts").join(channel))
+    }
+
+    fn path(&self, task_id: &str) -> PathBuf {
+        let name: String = task_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(name)
+    }
+
+    fn keys(&self, task_id: &str) -> Vec<String> {
+        std::fs::read_to_string(self.path(task_id))
+            .map(|keys| keys.lines().map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    fn open(&self, task_id: &str, key: &str) -> Result<()> {
+        let mut keys = self.keys(task_id);
+        if !keys.iter().any(|k| k == key) {
+            keys.push(key.to_string());
+            std::fs::create_dir_all(&self.dir)?;
+            std::fs::write(self.path(task_id), keys.join("\n"))?;
+        }
+        Ok(())
+    }
+
+    /// Resolve every open alert of `task_id` with `resolve`; keys that fail
+    /// to resolve stay open for the next attempt
+    pub fn resolve(&self, task_id: &str, resolve: impl Fn(&str) -> Result<()>) -> Result<()> {
+        let keys = self.keys(task_id);
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let mut failed = Vec::new();
+        let mut error = None;
+        for key in keys {
+            if let Err(e) = resolve(&key) {
+                failed.push(key);
+                error = Some(e);
+            }
+        }
+        if failed.is_empty() {
+            let _ = std::fs::remove_file(self.path(task_id));
+        } else {
+            std::fs::write(self.path(task_id), failed.join("\n"))?;
+        }
+        error.map_or(Ok(()), Err)
+    }
+
+    /// Raise, update or resolve alerts for `notification`. Attention opens
+    /// an alert until the task resumes; completion resolves the task's
+    /// alerts, and a failure then raises one that stays until acknowledged.
+    /// Everything else is not worth paging for.
+    pub fn deliver(
+        &self,
+        notification: &Notification,
+        trigger: impl Fn(Option<&str>) -> Result<()>,
+        resolve: impl Fn(&str) -> Result<()>,
+    ) -> Result<()> {
+        let key = dedup_key(notification);
+        match (notification.category, &notification.task_id, &key) {
+            (Category::Attention, Some(task_id), Some(key)) => {
+                trigger(Some(key))?;
+                self.open(task_id, key)
+            }
+            (Category::Completed, Some(task_id), _) => self.resolve(task_id, resolve),
+            (Category::Failed, Some(task_id), _) => {
+                self.resolve(task_id, &resolve)?;
+                trigger(key.as_deref())
+            }
+            (Category::Attention | Category::Failed, None, _) => trigger(None),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// `agent-inbox/<task_id>/<reason>`; the category stands in for the reason
+/// of failures and manual reports
+pub fn dedup_key(notification: &Notification) -> Option<String> {
+    let task_id = notification.task_id.as_deref()?;
+    let reason = match (notification.category, &notification.reason) {
+        (Category::Attention, Some(reason)) => reason.as_str(),
+        (Category::Failed, _) => "failed",
+        _ => "attention",
+    };
+    Some(format!("agent-inbox/{}/{}", task_id, reason))
+}
+
+/// PagerDuty's event severity
+pub fn pagerduty_severity(urgency: Urgency) -> &'static str {
+    match urgency {
+        Urgency::Critical => "critical",
+        Urgency::Normal => "warning",
+        Urgency::Low => "info",
+    }
+}
+
+/// Opsgenie's alert priority
+pub fn opsgenie_priority(urgency: Urgency) -> &'static str {
+    match urgency {
+        Urgency::Critical => "P1",
+        Urgency::Normal => "P3",
+        Urgency::Low => "P5",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn notification(category: Category, reason: Option<&str>) -> Notification {
+        Notification {
+            title: "[claude_code] Deploy".to_string(),
+            body: String::new(),
+            urgency: Urgency::Normal,
+            category,
+            task_id: Some("task-1".to_string()),
+            reason: reason.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_alert_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let alerts = OpenAlerts::new(dir.path().join("pagerduty"));
+        let calls = RefCell::new(Vec::new());
+        let trigger = |key: Option<&str>| {
+            calls
+                .borrow_mut()
+                .push(format!("trigger {}", key.unwrap_or("-")));
+            Ok(())
+        };
+        let resolve = |key: &str| {
+            calls.borrow_mut().push(format!("resolve {}", key));
+            Ok(())
+        };
+
+        let stalled = notification(Category::Attention, Some("stalled"));
+        alerts.deliver(&stalled, trigger, resolve).unwrap();
+        alerts.deliver(&stalled, trigger, resolve).unwrap();
+        alerts
+            .deliver(
+                &notification(Category::Attention, Some("prompt")),
+                trigger,
+                resolve,
+            )
+            .unwrap();
+        alerts
+            .deliver(&notification(Category::Info, None), trigger, resolve)
+            .unwrap();
+        alerts
+            .deliver(&notification(Category::Failed, None), trigger, resolve)
+            .unwrap();
+        // Nothing left to resolve on resume
+        alerts.resolve("task-1", resolve).unwrap();
+        assert_eq!(
+            *calls.borrow(),
+            vec![
+                "trigger agent-inbox/task-1/stalled",
+                "trigger agent-inbox/task-1/stalled",
+                "trigger agent-inbox/task-1/prompt",
+                "resolve agent-inbox/task-1/stalled",
+                "resolve agent-inbox/task-1/prompt",
+                "trigger agent-inbox/task-1/failed",
+            ]
+        );
+        assert_eq!(pagerduty_severity(Urgency::Critical), "critical");
+        assert_eq!(opsgenie_priority(Urgency::Low), "P5");
+    }
+}
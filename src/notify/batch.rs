@@ -0,0 +1,186 @@
+//! Coalescing notifications that arrive together
+//!
+//! Monitors, wrappers and the daemon each notify from their own process, so
+//! batches are collected in the task database. The first notification of a
+//! batch holds it open for the window, then sends everything queued in the
+//! meantime as one message; notifications arriving while a batch is open
+//! just join it. A batch whose holder died is picked up by the next
+//! notification after it has been stale for another window.
+
+use super::{Category, Notification, Urgency};
+use crate::db::Database;
+use anyhow::Result;
+use chrono::Utc;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// Lines of detail per notification in a batch body
+const DETAIL_LINES: usize = 1;
+
+/// Holds notifications for `window` and sends them as one
+pub struct Batcher {
+    db_path: PathBuf,
+    window: Duration,
+}
+
+impl Batcher {
+    pub fn new(db_path: PathBuf, window: Duration) -> Self {
+        Self { db_path, window }
+    }
+
+    /// Queue `notification`. Returns the batch to send when this caller
+    /// opened it (after blocking for the window), or None when another
+    /// caller will send it.
+    pub fn submit(&self, notification: &Notification) -> Result<Option<Notification>> {
+        let db = Database::open(&self.db_path)?;
+        let now = Utc::now();
+        let id = db.queue_notification(notification, now)?;
+        let stale = chrono::Duration::from_std(self.window * 2)?;
+        match db.first_queued_notification()? {
+            Some((first, at)) if first != id && now - at < stale => return Ok(None),
+            _ => {}
+        }
+
+        thread::sleep(self.window);
+        Ok(combine(db.take_queued_notifications()?))
+    }
+}
+
+/// One notification standing for all of `batch`; a batch of one is sent
+/// unchanged
+pub fn combine(mut batch: Vec<Notification>) -> Option<Notification> {
+    if batch.len() <= 1 {
+        return batch.pop();
+    }
+
+    let count = |category: Category| batch.iter().filter(|n| n.category == category).count();
+    let counts = [
+        (count(Category::Completed), "completed", "completed"),
+        (count(Category::Failed), "failed", "failed"),
+        (count(Category::Attention), "needs input", "need input"),
+        (count(Category::Info), "update", "updates"),
+    ];
+    let title = counts
+        .iter()
+        .filter(|(n, _, _)| *n > 0)
+        .map(|(n, one, many)| format!("{} {}", n, if *n == 1 { one } else { many }))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let body = batch
+        .iter()
+        .map(|n| {
+            let icon = match n.category {
+                Category::Completed => "✓",
+                Category::Failed => "✗",
+                Category::Attention => "⚠",
+                Category::Info => "•",
+            };
+            let detail: Vec<&str> = n
+                .body
+                .lines()
+                .filter(|l| !l.trim().is_empty())
+                .take(DETAIL_LINES)
+                .collect();
+            if detail.is_empty() {
+                format!("{} {}", icon, n.title)
+            } else {
+                format!("{} {}: {}", icon, n.title, detail.join(" "))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Some(Notification {
+        title,
+        body,
+        urgency: batch
+            .iter()
+            .map(|n| n.urgency)
+            .max()
+            .unwrap_or(Urgency::Normal),
+        category: Category::Info,
+        task_id: None,
+        reason: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn notification(title: &str, body: &str, category: Category) -> Notification {
+        Notification {
+            title: title.to_string(),
+            body: body.to_string(),
+            urgency: Urgency::Normal,
+            category,
+            task_id: None,
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn test_combine() {
+        let batch = combine(vec![
+            notification(
+                "[claude_code] Fix bug",
+                "3 tests passed\nmore",
+                Category::Completed,
+            ),
+            notification("[opencode] Docs", "", Category::Completed),
+            notification(
+                "[claude_code] Refactor",
+                "Process stalled",
+                Category::Attention,
+            ),
+        ])
+        .unwrap();
+        assert_eq!(batch.title, "2 completed, 1 needs input");
+        assert_eq!(
+            batch.body,
+            "✓ [claude_code] Fix bug: 3 tests passed\n✓ [opencode] Docs\n⚠ [claude_code] Refactor: Process stalled"
+        );
+
+        let single = combine(vec![notification("t", "b", Category::Failed)]).unwrap();
+        assert_eq!(single.title, "t");
+        assert!(combine(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_first_caller_sends_the_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let batcher = Arc::new(Batcher::new(
+            dir.path().join("tasks.db"),
+            Duration::from_millis(500),
+        ));
+        // Create the schema before the callers race to open it
+        Database::open(dir.path().join("tasks.db")).unwrap();
+
+        let first = {
+            let batcher = batcher.clone();
+            thread::spawn(move || {
+                batcher
+                    .submit(&notification("a", "", Category::Completed))
+                    .unwrap()
+            })
+        };
+        thread::sleep(Duration::from_millis(100));
+        let second = batcher
+            .submit(&notification("b", "", Category::Attention))
+            .unwrap();
+        assert!(second.is_none());
+
+        let batch = first.join().unwrap().unwrap();
+        assert_eq!(batch.title, "1 completed, 1 needs input");
+
+        // The next notification opens a new batch
+        let batcher = Batcher::new(dir.path().join("tasks.db"), Duration::from_millis(10));
+        let next = batcher
+            .submit(&notification("c", "", Category::Completed))
+            .unwrap();
+        assert_eq!(next.unwrap().title, "c");
+    }
+}
@@ -0,0 +1,134 @@
+//! Copying what a task waits on: `agent-inbox copy-prompt <task>`
+//!
+//! "Paste the error into the other tool" shouldn't take a trip to the
+//! terminal. The trailing block of the task's captured output, cut at the
+//! question it asks as [`prompt::extract`] does (or else the last block of
+//! output), goes to the system clipboard with escape sequences stripped.
+//! Without captured output, the attention message is copied instead.
+//!
+//! The clipboard is reached through the first tool found on PATH that fits
+//! the session: `pbcopy` (macOS), `wl-copy` (Wayland), `xclip` or `xsel`
+//! (X11), or `clip.exe` (Windows and WSL).
+
+use crate::models::Task;
+use crate::monitor::{ansi, output, prompt};
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Most lines copied
+const COPY_LINES: usize = 40;
+
+/// Clipboard tools in order of preference, with their arguments and the
+/// display server they need, if any
+const COPIERS: [(&str, &[&str], Option<&str>); 5] = [
+    ("pbcopy", &[], None),
+    ("wl-copy", &[], Some("WAYLAND_DISPLAY")),
+    ("xclip", &["-selection", "clipboard"], Some("DISPLAY")),
+    ("xsel", &["--clipboard", "--input"], Some("DISPLAY")),
+    ("clip.exe", &[], None),
+];
+
+/// The clipboard tool to use, given which programs are installed and which
+/// environment variables are set
+fn copier(
+    installed: impl Fn(&str) -> bool,
+    set: impl Fn(&str) -> bool,
+) -> Option<(&'static str, &'static [&'static str])> {
+    COPIERS
+        .iter()
+        .find(|(program, _, display)| display.is_none_or(&set) && installed(program))
+        .map(|&(program, args, _)| (program, args))
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+/// Put `text` on the system clipboard
+pub fn copy(text: &str) -> Result<()> {
+    let (program, args) = copier(on_path, |var| std::env::var_os(var).is_some())
+        .context("No clipboard tool found (install wl-clipboard, xclip or xsel)")?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", program))?;
+    child
+        .stdin
+        .take()
+        .context("No stdin for the clipboard tool")?
+        .write_all(text.as_bytes())?;
+    let status = child.wait()?;
+    anyhow::ensure!(status.success(), "{} exited with {}", program, status);
+    Ok(())
+}
+
+/// The prompt or last block in `output`, escape sequences stripped
+fn last_block(output: &str) -> String {
+    let output = ansi::strip(output);
+    let lines: Vec<&str> = output.lines().collect();
+    prompt::extract(&lines, COPY_LINES)
+}
+
+/// What `task` waits on: the end of its captured output, or its attention
+/// message
+pub fn prompt_text(task: &Task) -> Option<String> {
+    output::read_tail(output::default_log_path(&task.task_id), 64 * 1024)
+        .map(|output| last_block(&output))
+        .filter(|text| !text.is_empty())
+        .or_else(|| task.attention_reason.clone())
+}
+
+/// Copy what `task` waits on to the clipboard and return it
+pub fn copy_prompt(task: &Task) -> Result<String> {
+    let text = prompt_text(task)
+        .with_context(|| format!("Nothing to copy: no output captured for {}", task.task_id))?;
+    copy(&text)?;
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copier_and_block() {
+        let installed = |programs: &'static [&'static str]| move |p: &str| programs.contains(&p);
+        let wayland = |var: &str| var == "WAYLAND_DISPLAY";
+        let x11 = |var: &str| var == "DISPLAY";
+
+        assert_eq!(
+            copier(installed(&["pbcopy"]), x11),
+            Some(("pbcopy", &[][..]))
+        );
+        assert_eq!(
+            copier(installed(&["wl-copy", "xclip"]), wayland).unwrap().0,
+            "wl-copy"
+        );
+        // XWayland sets both; without wl-copy, xclip still works
+        assert_eq!(
+            copier(installed(&["xclip"]), |_: &str| true).unwrap().1,
+            ["-selection", "clipboard"]
+        );
+        assert_eq!(
+            copier(installed(&["wl-copy", "xsel"]), x11).unwrap().0,
+            "xsel"
+        );
+        assert_eq!(copier(installed(&["xclip"]), |_: &str| false), None);
+        assert_eq!(
+            copier(installed(&["clip.exe"]), |_: &str| false).unwrap().0,
+            "clip.exe"
+        );
+
+        let output =
+            "Compiling api\n\nerror[E0308]: \x1b[1mmismatched types\x1b[0m\n  --> src/lib.rs:4:5\n";
+        assert_eq!(
+            last_block(output),
+            "error[E0308]: mismatched types\n  --> src/lib.rs:4:5"
+        );
+    }
+}
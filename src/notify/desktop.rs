@@ -0,0 +1,163 @@
+use super::{actions, Channel, Notification, Urgency};
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// PowerShell's own AppUserModelID, so toasts work without registering one
+const POWERSHELL_APP_ID: &str =
+    r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe";
+
+/// Where WSL mounts the Windows PowerShell, for when interop left it off PATH
+const WSL_POWERSHELL: &str = "/mnt/c/Windows/System32/WindowsPowerShell/v1.0/powershell.exe";
+
+/// Desktop notifications via notify-send (Linux), osascript (macOS) or
+/// Windows toasts (Windows, and Linux under WSL). On macOS, notifications
+/// about a task get reply and terminal actions when `alerter` is installed.
+pub struct DesktopChannel;
+
+impl Channel for DesktopChannel {
+    fn name(&self) -> &str {
+        "desktop"
+    }
+
+    fn max_body_chars(&self) -> Option<usize> {
+        // Bubbles show a few lines; the rest is in `agent-inbox show`
+        Some(500)
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let status = if cfg!(target_os = "macos") {
+            if let Some(task_id) = notification
+                .task_id
+                .as_deref()
+                .filter(|_| actions::alerter_available())
+            {
+                return actions::spawn_alert(notification, task_id);
+            }
+            let script = format!(
+                "display notification {:?} with title {:?}",
+                notification.body, notification.title
+            );
+            Command::new("osascript").args(["-e", &script]).status()
+        } else if cfg!(windows) || is_wsl() {
+            return toast(notification);
+        } else {
+            Command::new("notify-send")
+                .args([
+                    "-a",
+                    "agent-inbox",
+                    "-u",
+                    notification.urgency.as_str(),
+                    &notification.title,
+                    &notification.body,
+                ])
+                .status()
+        }
+        .context("Failed to run desktop notifier")?;
+
+        if !status.success() {
+            anyhow::bail!("Desktop notifier exited with {}", status);
+        }
+        Ok(())
+    }
+}
+
+/// Running under the Windows Subsystem for Linux, where notify-send has no
+/// desktop to talk to
+fn is_wsl() -> bool {
+    if cfg!(not(target_os = "linux")) {
+        return false;
+    }
+    std::env::var_os("WSL_DISTRO_NAME").is_some()
+        || std::env::var_os("WSL_INTEROP").is_some()
+        || std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .is_ok_and(|release| release.to_lowercase().contains("microsoft"))
+}
+
+/// Show a Windows toast through PowerShell; the script goes over stdin so
+/// nothing has to survive command-line quoting
+fn toast(notification: &Notification) -> Result<()> {
+    let powershell = if cfg!(windows) || !Path::new(WSL_POWERSHELL).exists() {
+        "powershell.exe"
+    } else {
+        WSL_POWERSHELL
+    };
+    let mut child = Command::new(powershell)
+        .args(["-NoProfile", "-NonInteractive", "-Command", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .context("Failed to run powershell.exe for a Windows toast")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(toast_script(notification).as_bytes())?;
+    }
+    let status = child.wait()?;
+    anyhow::ensure!(status.success(), "powershell.exe exited with {}", status);
+    Ok(())
+}
+
+fn toast_script(notification: &Notification) -> String {
+    let duration = match notification.urgency {
+        Urgency::Critical => "long",
+        _ => "short",
+    };
+    let xml = format!(
+        "<toast duration=\"{}\"><visual><binding template=\"ToastGeneric\"><text>{}</text><text>{}</text></binding></visual></toast>",
+        duration,
+        xml_escape(&notification.title),
+        xml_escape(&notification.body)
+    );
+    format!(
+        "$ErrorActionPreference = 'Stop'\n\
+         [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] | Out-Null\n\
+         [Windows.Data.Xml.Dom.XmlDocument, Windows.Data.Xml.Dom.XmlDocument, ContentType = WindowsRuntime] | Out-Null\n\
+         $xml = New-Object Windows.Data.Xml.Dom.XmlDocument\n\
+         $xml.LoadXml('{}')\n\
+         [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('{}').Show([Windows.UI.Notifications.ToastNotification]::new($xml))\n",
+        xml, POWERSHELL_APP_ID
+    )
+}
+
+/// Escape for the toast XML. Every kind of quote becomes a character
+/// reference too, since PowerShell ends a '...' string at curly quotes as well.
+fn xml_escape(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' | '\u{2018}'..='\u{201B}' => format!("&#x{:X};", c as u32),
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::Category;
+
+    #[test]
+    fn test_toast_script_quoting() {
+        let notification = Notification {
+            title: "[claude_code] Fix <bug>".to_string(),
+            body: "It's done & ‘tested’".to_string(),
+            urgency: Urgency::Critical,
+            category: Category::Completed,
+            task_id: None,
+            reason: None,
+        };
+        let script = toast_script(&notification);
+        assert!(script.contains("<toast duration=\"long\">"));
+        assert!(script.contains("<text>[claude_code] Fix &lt;bug&gt;</text>"));
+        assert!(script.contains("<text>It&#x27;s done &amp; &#x2018;tested&#x2019;</text>"));
+        // Only the script's own quotes delimit PowerShell strings
+        let load = script
+            .lines()
+            .find(|line| line.starts_with("$xml.LoadXml"))
+            .unwrap();
+        assert_eq!(load.matches('\'').count(), 2);
+    }
+}
@@ -0,0 +1,49 @@
+use super::{Channel, Notification};
+use crate::config::EmailConfig;
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Email through the local sendmail binary (msmtp, postfix, ...)
+pub struct EmailChannel {
+    config: EmailConfig,
+}
+
+impl EmailChannel {
+    pub fn new(config: EmailConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Channel for EmailChannel {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let mut child = Command::new(&self.config.sendmail)
+            .arg("-t")
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", self.config.sendmail))?;
+
+        let message = format!(
+            "To: {}\nFrom: {}\nSubject: {}\n\n{}\n",
+            self.config.to,
+            self.config.from,
+            notification.title.replace('\n', " "),
+            notification.body
+        );
+        child
+            .stdin
+            .take()
+            .context("sendmail stdin unavailable")?
+            .write_all(message.as_bytes())?;
+
+        let status = child.wait()?;
+        if !status.success() {
+            anyhow::bail!("{} exited with {}", self.config.sendmail, status);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use super::{http, Channel, Notification, Urgency};
+use crate::config::GotifyConfig;
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+
+/// Gotify `POST /message`. The server replies with the stored message, so
+/// its ID goes into the delivery history as the receipt.
+pub struct GotifyChannel {
+    config: GotifyConfig,
+}
+
+impl GotifyChannel {
+    pub fn new(config: GotifyConfig) -> Self {
+        Self { config }
+    }
+
+    fn message(&self, notification: &Notification) -> Value {
+        let priority = &self.config.priority;
+        json!({
+            "title": notification.title,
+            "message": notification.body,
+            "priority": match notification.by_kind(&self.config.reasons) {
+                Some(&priority) => priority,
+                None => match notification.urgency {
+                    Urgency::Low => priority.low,
+                    Urgency::Normal => priority.normal,
+                    Urgency::Critical => priority.critical,
+                },
+            },
+            "extras": {
+                "client::display": { "contentType": "text/plain" },
+            },
+        })
+    }
+}
+
+impl Channel for GotifyChannel {
+    fn name(&self) -> &str {
+        "gotify"
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        self.send_with_receipt(notification).map(|_| ())
+    }
+
+    fn send_with_receipt(&self, notification: &Notification) -> Result<Option<String>> {
+        let token = std::env::var(&self.config.app_token_env)
+            .with_context(|| format!("{} is not set", self.config.app_token_env))?;
+        let url = format!("{}/message", self.config.url.trim_end_matches('/'));
+        let reply = http::post_json_reply(
+            &url,
+            &[("X-Gotify-Key", &token)],
+            &self.message(notification),
+        )?;
+        let id = reply["id"]
+            .as_u64()
+            .with_context(|| format!("Gotify did not confirm the message: {}", reply))?;
+        Ok(Some(id.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GotifyPriority;
+    use crate::notify::Category;
+
+    #[test]
+    fn test_message() {
+        let channel = GotifyChannel::new(GotifyConfig {
+            url: "https://push.example.com/".to_string(),
+            app_token_env: "GOTIFY_APP_TOKEN".to_string(),
+            priority: GotifyPriority {
+                critical: 10,
+                ..GotifyPriority::default()
+            },
+            reasons: [
+                ("waiting-for-input".to_string(), 9),
+                ("completed".to_string(), 4),
+            ]
+            .into(),
+        });
+        let mut notification = Notification {
+            title: "[claude_code] Deploy".to_string(),
+            body: "Waiting for input".to_string(),
+            urgency: Urgency::Critical,
+            category: Category::Attention,
+            task_id: None,
+            reason: None,
+        };
+        let message = channel.message(&notification);
+        assert_eq!(message["title"], "[claude_code] Deploy");
+        assert_eq!(message["priority"], 10);
+        notification.urgency = Urgency::Low;
+        assert_eq!(channel.message(&notification)["priority"], 2);
+        notification.reason = Some("waiting_for_input".to_string());
+        assert_eq!(channel.message(&notification)["priority"], 9);
+        notification.reason = None;
+        notification.category = Category::Completed;
+        assert_eq!(channel.message(&notification)["priority"], 4);
+    }
+}
@@ -0,0 +1,169 @@
+//! Delivery audit log: every notification sent, with the channels that
+//! took it and those that failed
+//!
+//! Kept in the task database so `agent-inbox notifications history` can
+//! show what was missed and `--resend` can fire it again. Entries older
+//! than [`KEEP_DAYS`] are dropped as new ones come in.
+
+use super::{template, Notification};
+use crate::db::Database;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// How long deliveries stay in the log
+pub const KEEP_DAYS: i64 = 30;
+
+/// One logged notification
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    pub id: i64,
+    pub at: DateTime<Utc>,
+    pub notification: Notification,
+    /// Channels that accepted it
+    pub delivered: Vec<String>,
+    /// Channels that failed, timed out or were switched off
+    pub failed: Vec<String>,
+    /// The ID each channel that confirms deliveries got for the message
+    pub receipts: BTreeMap<String, String>,
+}
+
+/// Which deliveries a history listing shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// At least one channel took it
+    Delivered,
+    /// At least one channel failed
+    Failed,
+}
+
+/// Filters for [`Database::deliveries`]; unset fields match everything
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    pub channel: Option<String>,
+    pub task: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub outcome: Option<Outcome>,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, delivery: &Delivery) -> bool {
+        let channel = |names: &[String]| {
+            self.channel
+                .as_ref()
+                .is_none_or(|channel| names.iter().any(|name| name == channel))
+        };
+        let outcome = match self.outcome {
+            None => channel(&delivery.delivered) || channel(&delivery.failed),
+            Some(Outcome::Delivered) => {
+                !delivery.delivered.is_empty() && channel(&delivery.delivered)
+            }
+            Some(Outcome::Failed) => !delivery.failed.is_empty() && channel(&delivery.failed),
+        };
+        outcome
+            && self
+                .task
+                .as_ref()
+                .is_none_or(|task| delivery.notification.task_id.as_ref() == Some(task))
+            && self.since.is_none_or(|since| delivery.at >= since)
+            && self.until.is_none_or(|until| delivery.at < until)
+    }
+}
+
+/// Where a [`Notifier`](super::Notifier) logs its deliveries
+pub struct History {
+    db_path: PathBuf,
+}
+
+impl History {
+    pub fn new(db_path: PathBuf) -> Self {
+        Self { db_path }
+    }
+
+    pub fn record(
+        &self,
+        notification: &Notification,
+        delivered: &[String],
+        failed: &[String],
+        receipts: &BTreeMap<String, String>,
+    ) -> Result<i64> {
+        let db = Database::open(&self.db_path)?;
+        let now = Utc::now();
+        db.prune_deliveries(now - chrono::Duration::days(KEEP_DAYS))?;
+        // Logged as the plain-text channels sent it
+        let notification = Notification {
+            title: template::plain(&notification.title),
+            body: template::plain(&notification.body),
+            ..notification.clone()
+        };
+        db.record_delivery(now, &notification, delivered, failed, receipts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::{Category, Urgency};
+
+    fn notification(task_id: &str) -> Notification {
+        Notification {
+            title: "[claude_code] Deploy".to_string(),
+            body: "Waiting for input".to_string(),
+            urgency: Urgency::Normal,
+            category: Category::Attention,
+            task_id: Some(task_id.to_string()),
+            reason: Some("waiting_for_input".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_record_and_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("tasks.db");
+        let history = History::new(db_path.clone());
+        let strings = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let receipts = BTreeMap::from([("gotify".to_string(), "42".to_string())]);
+        history
+            .record(
+                &notification("t1"),
+                &strings(&["desktop"]),
+                &strings(&["slack"]),
+                &BTreeMap::new(),
+            )
+            .unwrap();
+        let second = history
+            .record(
+                &notification("t2"),
+                &strings(&["desktop", "gotify"]),
+                &[],
+                &receipts,
+            )
+            .unwrap();
+
+        let db = Database::open(&db_path).unwrap();
+        let all = db.deliveries(&HistoryFilter::default()).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].id, second);
+        let logged = db.delivery(second).unwrap().unwrap();
+        assert_eq!(logged.notification.task_id.as_deref(), Some("t2"));
+        assert_eq!(logged.receipts, receipts);
+        assert!(all[1].receipts.is_empty());
+
+        let failed_on_slack = HistoryFilter {
+            channel: Some("slack".to_string()),
+            outcome: Some(Outcome::Failed),
+            ..HistoryFilter::default()
+        };
+        let failed = db.deliveries(&failed_on_slack).unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].notification.task_id.as_deref(), Some("t1"));
+
+        let later = HistoryFilter {
+            since: Some(Utc::now() + chrono::Duration::minutes(1)),
+            ..HistoryFilter::default()
+        };
+        assert!(db.deliveries(&later).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,111 @@
+//! Shared HTTP helpers for webhook-style channels
+
+use anyhow::{Context, Result};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn post_json(url: &str, headers: &[(&str, &str)], body: &serde_json::Value) -> Result<()> {
+    send_json(ureq::request("POST", url), url, headers, body)
+}
+
+/// POST and return the JSON reply
+#[cfg(any(feature = "slack", feature = "gotify"))]
+pub fn post_json_reply(
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &serde_json::Value,
+) -> Result<serde_json::Value> {
+    read_reply(
+        request_json(ureq::request("POST", url), url, headers, body)?,
+        url,
+    )
+}
+
+/// PUT and return the JSON reply
+#[cfg(feature = "matrix")]
+pub fn put_json(
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &serde_json::Value,
+) -> Result<serde_json::Value> {
+    read_reply(
+        request_json(ureq::request("PUT", url), url, headers, body)?,
+        url,
+    )
+}
+
+fn send_json(
+    request: ureq::Request,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &serde_json::Value,
+) -> Result<()> {
+    request_json(request, url, headers, body)?;
+    Ok(())
+}
+
+fn request_json(
+    request: ureq::Request,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &serde_json::Value,
+) -> Result<ureq::Response> {
+    let method = request.method().to_string();
+    let mut request = request.timeout(TIMEOUT);
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    request
+        .send_json(body)
+        .with_context(|| format!("{} {} failed", method, redact_url(url)))
+}
+
+#[cfg(any(feature = "slack", feature = "matrix", feature = "gotify"))]
+fn read_reply(response: ureq::Response, url: &str) -> Result<serde_json::Value> {
+    response
+        .into_json()
+        .with_context(|| format!("Unreadable reply from {}", redact_url(url)))
+}
+
+/// Strip the path so tokens embedded in URLs never reach logs
+fn redact_url(url: &str) -> &str {
+    let after_scheme = url.find("://").map(|i| i + 3).unwrap_or(0);
+    match url[after_scheme..].find('/') {
+        Some(i) => &url[..after_scheme + i],
+        None => url,
+    }
+}
+
+/// Percent-encode everything outside the RFC 3986 unreserved set
+#[cfg(any(feature = "matrix", feature = "opsgenie"))]
+pub fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(feature = "matrix", feature = "opsgenie"))]
+    #[test]
+    fn test_percent_encode() {
+        assert_eq!(percent_encode("!room:matrix.org"), "%21room%3Amatrix.org");
+    }
+
+    #[test]
+    fn test_redact_url() {
+        assert_eq!(
+            redact_url("https://api.telegram.org/bot123:secret/sendMessage"),
+            "https://api.telegram.org"
+        );
+        assert_eq!(redact_url("https://example.com"), "https://example.com");
+    }
+}
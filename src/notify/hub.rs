@@ -0,0 +1,227 @@
+//! Forwarding to a hub machine, which notifies on this one's behalf
+
+use super::{Channel, Notification};
+use crate::config::HubConfig;
+#[cfg(feature = "http")]
+use crate::federation::protocol::{self, Versions};
+use crate::federation::{self, Envelope};
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+#[cfg(feature = "http")]
+use std::time::Duration;
+
+/// How long forwarding over HTTP may take
+#[cfg(feature = "http")]
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How an edge reaches its hub
+enum Transport {
+    #[cfg(feature = "http")]
+    Http {
+        url: String,
+        agent: ureq::Agent,
+        token_env: Option<String>,
+    },
+    Ssh {
+        destination: String,
+        command: String,
+    },
+}
+
+pub struct HubChannel {
+    transport: Transport,
+    node: String,
+    /// Used while the hub is unreachable
+    fallback: Vec<Box<dyn Channel>>,
+}
+
+impl HubChannel {
+    pub fn new(config: HubConfig, fallback: Vec<Box<dyn Channel>>) -> Result<Self> {
+        let transport = match (config.url.clone(), config.ssh.clone()) {
+            (Some(_), Some(_)) => anyhow::bail!("[notify.hub] sets both url and ssh; pick one"),
+            #[cfg(feature = "http")]
+            (Some(url), None) => {
+                let agent = ureq::AgentBuilder::new();
+                #[cfg(feature = "tls")]
+                let agent = match crate::federation::tls::client_config(&config)? {
+                    Some(tls) => agent.tls_config(tls),
+                    None => agent,
+                };
+                #[cfg(not(feature = "tls"))]
+                anyhow::ensure!(
+                    config.ca.is_none() && config.client_cert.is_none(),
+                    "[notify.hub] ca and client_cert need the `tls` feature"
+                );
+                Transport::Http {
+                    url: url.trim_end_matches('/').to_string(),
+                    agent: agent.build(),
+                    token_env: config.token_env.clone(),
+                }
+            }
+            #[cfg(not(feature = "http"))]
+            (Some(_), None) => anyhow::bail!(
+                "[notify.hub] url needs HTTP support; agent-inbox was built without the `http` feature"
+            ),
+            (None, Some(destination)) => Transport::Ssh {
+                destination,
+                command: config.remote_command.clone().unwrap_or_else(|| "agent-inbox".to_string()),
+            },
+            (None, None) => anyhow::bail!("[notify.hub] needs either url or ssh"),
+        };
+        Ok(Self {
+            transport,
+            node: config.node.unwrap_or_else(federation::node_name),
+            fallback,
+        })
+    }
+
+    fn forward(&self, envelope: &Envelope) -> Result<()> {
+        match &self.transport {
+            #[cfg(feature = "http")]
+            Transport::Http {
+                url,
+                agent,
+                token_env,
+            } => {
+                let authorization = match token_env {
+                    Some(var) => {
+                        let token =
+                            std::env::var(var).with_context(|| format!("{} is not set", var))?;
+                        Some(format!("Bearer {}", token))
+                    }
+                    None => None,
+                };
+                let mut request = agent
+                    .post(&format!("{}/v1/notify", url))
+                    .timeout(TIMEOUT)
+                    .set(protocol::HEADER, &Versions::OURS.to_string());
+                if let Some(authorization) = &authorization {
+                    request = request.set("Authorization", authorization);
+                }
+                match request.send_json(serde_json::to_value(envelope)?) {
+                    Ok(_) => Ok(()),
+                    Err(ureq::Error::Status(status, response)) => Err(rejected(status, response)),
+                    Err(e) => Err(e).context("Failed to reach the hub"),
+                }
+            }
+            Transport::Ssh {
+                destination,
+                command,
+            } => {
+                let mut child = Command::new("ssh")
+                    .args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"])
+                    .arg(destination)
+                    .arg(format!("{} hub receive", command))
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .spawn()
+                    .context("Failed to run ssh")?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(serde_json::to_string(envelope)?.as_bytes())?;
+                }
+                let status = child.wait()?;
+                anyhow::ensure!(
+                    status.success(),
+                    "ssh {} exited with {}",
+                    destination,
+                    status
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Why the hub refused a notification. A hub that shares no protocol
+/// version with this machine says so in its versions header, or, from
+/// before versioning, by not sending one.
+#[cfg(feature = "http")]
+fn rejected(status: u16, response: ureq::Response) -> anyhow::Error {
+    let theirs = match response.header(protocol::HEADER) {
+        Some(header) => Versions::from_str(header).ok(),
+        None => Some(Versions::LEGACY),
+    };
+    let message = response
+        .into_json::<serde_json::Value>()
+        .ok()
+        .and_then(|body| body["error"].as_str().map(str::to_string))
+        .unwrap_or_default();
+    match theirs.map(|theirs| Versions::OURS.negotiate(theirs)) {
+        Some(Err(mismatch)) => {
+            anyhow::anyhow!(
+                "Hub refused the notification: {}",
+                mismatch.explain("this machine", "the hub")
+            )
+        }
+        _ => anyhow::anyhow!("Hub refused the notification ({}): {}", status, message),
+    }
+}
+
+impl Channel for HubChannel {
+    fn name(&self) -> &str {
+        "hub"
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let Err(e) = self.forward(&Envelope::new(&self.node, notification)) else {
+            return Ok(());
+        };
+        if self.fallback.is_empty() {
+            return Err(e);
+        }
+        eprintln!("Hub unreachable, notifying locally: {:#}", e);
+        let delivered = self
+            .fallback
+            .iter()
+            .filter(|channel| match channel.send(notification) {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!(
+                        "Failed to deliver notification via {}: {}",
+                        channel.name(),
+                        e
+                    );
+                    false
+                }
+            })
+            .count();
+        anyhow::ensure!(
+            delivered > 0,
+            "Hub unreachable and no fallback channel delivered: {:#}",
+            e
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::{Category, Urgency};
+    use crate::testing::MockChannel;
+
+    #[test]
+    fn test_falls_back_when_the_hub_is_unreachable() {
+        let local = MockChannel::new();
+        let config = HubConfig {
+            ssh: Some("nobody@hub.invalid".to_string()),
+            remote_command: Some("/nonexistent/agent-inbox".to_string()),
+            node: Some("laptop".to_string()),
+            ..Default::default()
+        };
+        let hub = HubChannel::new(config, vec![Box::new(local.clone())]).unwrap();
+        let notification = Notification {
+            title: "t".to_string(),
+            body: "b".to_string(),
+            urgency: Urgency::Normal,
+            category: Category::Info,
+            task_id: None,
+            reason: None,
+        };
+        hub.send(&notification).unwrap();
+        assert_eq!(local.sent().len(), 1);
+
+        assert!(HubChannel::new(HubConfig::default(), Vec::new()).is_err());
+    }
+}
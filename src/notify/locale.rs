@@ -0,0 +1,243 @@
+//! Localized notification text, per channel
+//!
+//! A locale is a catalog file, `~/.config/agent-inbox/locales/<locale>.toml`:
+//!
+//! - `[messages]` maps built-in English text to its translation. Keys may
+//!   hold `{name}` placeholders for the parts that vary, which the
+//!   translation can use again, as in
+//!   `"Pipeline failed at {task}" = "Pipeline fehlgeschlagen bei {task}"`.
+//! - `[strings]` holds text that `[reasons]` templates pull in with
+//!   `{t:name}`, so one template reads right in every channel's language.
+//!
+//! `[notify.locale]` picks the locale per channel. Each channel's worker
+//! translates the title and body just before sending, so one notification
+//! can go out in German to a shared Slack channel and in English to the
+//! desktop. Text the catalog doesn't cover is sent as is.
+
+use crate::config::LocaleConfig;
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+/// Locale of channels that don't pick one; its catalog is optional
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// `~/.config/agent-inbox/locales`
+pub fn default_locale_dir() -> PathBuf {
+    crate::storage::config_dir().join("locales")
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CatalogFile {
+    messages: HashMap<String, String>,
+    strings: HashMap<String, String>,
+}
+
+/// One `[messages]` entry, as a pattern over the text
+#[derive(Debug)]
+struct Message {
+    pattern: Regex,
+    replacement: String,
+}
+
+/// Translations for one locale
+#[derive(Debug, Default)]
+pub struct Catalog {
+    /// Longest keys first, so a whole phrase wins over a word in it
+    messages: Vec<Message>,
+    strings: HashMap<String, String>,
+}
+
+impl Catalog {
+    pub fn parse(text: &str) -> Result<Self> {
+        let file: CatalogFile = toml::from_str(text)?;
+        let mut keys: Vec<(String, String)> = file.messages.into_iter().collect();
+        keys.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+        let messages = keys
+            .into_iter()
+            .filter(|(key, _)| !key.is_empty())
+            .map(|(key, translation)| message(&key, &translation))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            messages,
+            strings: file.strings,
+        })
+    }
+
+    /// The catalog of `locale` in `dir`; a missing file is only an error
+    /// for a locale other than the default
+    pub fn load(dir: &Path, locale: &str) -> Result<Self> {
+        let path = dir.join(format!("{}.toml", locale));
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text)
+                .with_context(|| format!("Invalid locale file {}", path.display())),
+            Err(_) if locale == DEFAULT_LOCALE => Ok(Self::default()),
+            Err(e) => {
+                Err(e).with_context(|| format!("Failed to read locale file {}", path.display()))
+            }
+        }
+    }
+
+    /// `text` with `{t:name}` references filled in and the known messages
+    /// translated
+    pub fn translate(&self, text: &str) -> String {
+        static REFERENCE: OnceLock<Regex> = OnceLock::new();
+        let reference = REFERENCE.get_or_init(|| Regex::new(r"\{t:([A-Za-z0-9_.-]+)\}").unwrap());
+        let mut text = reference
+            .replace_all(text, |caps: &Captures| match self.strings.get(&caps[1]) {
+                Some(value) => value.clone(),
+                None => caps[0].to_string(),
+            })
+            .into_owned();
+        for message in &self.messages {
+            if let std::borrow::Cow::Owned(translated) = message
+                .pattern
+                .replace_all(&text, message.replacement.as_str())
+            {
+                text = translated;
+            }
+        }
+        text
+    }
+}
+
+/// Pattern and replacement for `key`: literal text, with each `{name}`
+/// matching the rest of a line (when last) or as little as possible
+fn message(key: &str, translation: &str) -> Result<Message> {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    let placeholder =
+        PLACEHOLDER.get_or_init(|| Regex::new(r"\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap());
+
+    let mut pattern = String::new();
+    let mut last = 0;
+    for caps in placeholder.captures_iter(key) {
+        let whole = caps.get(0).unwrap();
+        pattern.push_str(&regex::escape(&key[last..whole.start()]));
+        let rest = if whole.end() == key.len() { "+" } else { "+?" };
+        pattern.push_str(&format!("(?P<{}>[^\\n]{})", &caps[1], rest));
+        last = whole.end();
+    }
+    pattern.push_str(&regex::escape(&key[last..]));
+
+    let mut replacement = String::new();
+    let mut last = 0;
+    for caps in placeholder.captures_iter(translation) {
+        let whole = caps.get(0).unwrap();
+        replacement.push_str(&translation[last..whole.start()].replace('$', "$$"));
+        replacement.push_str(&format!("${{{}}}", &caps[1]));
+        last = whole.end();
+    }
+    replacement.push_str(&translation[last..].replace('$', "$$"));
+
+    Ok(Message {
+        pattern: Regex::new(&pattern).with_context(|| format!("Invalid message key: {}", key))?,
+        replacement,
+    })
+}
+
+/// The catalog for each channel, from `[notify.locale]`
+#[derive(Default)]
+pub struct Locales {
+    default: Option<Arc<Catalog>>,
+    channels: HashMap<String, Arc<Catalog>>,
+}
+
+impl Locales {
+    pub fn from_config(config: &LocaleConfig, dir: &Path) -> Result<Self> {
+        let mut loaded: HashMap<String, Arc<Catalog>> = HashMap::new();
+        let mut load = |locale: &str| -> Result<Arc<Catalog>> {
+            if let Some(catalog) = loaded.get(locale) {
+                return Ok(catalog.clone());
+            }
+            let catalog = Arc::new(Catalog::load(dir, locale)?);
+            loaded.insert(locale.to_string(), catalog.clone());
+            Ok(catalog)
+        };
+
+        let default = load(config.default.as_deref().unwrap_or(DEFAULT_LOCALE))?;
+        let mut channels = HashMap::new();
+        for (channel, locale) in &config.channels {
+            channels.insert(channel.clone(), load(locale)?);
+        }
+        Ok(Self {
+            default: Some(default),
+            channels,
+        })
+    }
+
+    /// The catalog `channel` translates with, if any
+    pub fn for_channel(&self, channel: &str) -> Option<Arc<Catalog>> {
+        self.channels
+            .get(channel)
+            .or(self.default.as_ref())
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GERMAN: &str = r#"
+[messages]
+"Process stalled (no activity)" = "Prozess hängt (keine Aktivität)"
+"Pipeline failed at {task}" = "Pipeline fehlgeschlagen bei {task}"
+"{count} completed" = "{count} abgeschlossen"
+"stalled" = "hängt"
+
+[strings]
+oncall = "Bereitschaft anrufen"
+"#;
+
+    #[test]
+    fn test_translate_messages_and_strings() {
+        let catalog = Catalog::parse(GERMAN).unwrap();
+        assert_eq!(
+            catalog.translate("Process stalled (no activity) at 80% after 40m"),
+            "Prozess hängt (keine Aktivität) at 80% after 40m"
+        );
+        assert_eq!(
+            catalog.translate("Pipeline failed at deploy-agent"),
+            "Pipeline fehlgeschlagen bei deploy-agent"
+        );
+        assert_eq!(
+            catalog.translate("3 completed, 2 need input"),
+            "3 abgeschlossen, 2 need input"
+        );
+        assert_eq!(
+            catalog.translate("Merge conflict: {t:oncall} ({t:missing})"),
+            "Merge conflict: Bereitschaft anrufen ({t:missing})"
+        );
+    }
+
+    #[test]
+    fn test_locale_per_channel() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("de.toml"), GERMAN).unwrap();
+        let config = LocaleConfig {
+            default: None,
+            channels: HashMap::from([("slack".to_string(), "de".to_string())]),
+        };
+        let locales = Locales::from_config(&config, dir.path()).unwrap();
+
+        let text = "Pipeline failed at tests";
+        assert_eq!(
+            locales.for_channel("slack").unwrap().translate(text),
+            "Pipeline fehlgeschlagen bei tests"
+        );
+        assert_eq!(
+            locales.for_channel("desktop").unwrap().translate(text),
+            text
+        );
+
+        let missing = LocaleConfig {
+            default: Some("fr".to_string()),
+            channels: HashMap::new(),
+        };
+        assert!(Locales::from_config(&missing, dir.path()).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use super::threads::Threads;
+use super::{http, Channel, Notification};
+use crate::config::MatrixConfig;
+use anyhow::{Context, Result};
+use serde_json::json;
+
+/// Matrix client-server API: posts an m.text message to a room, each
+/// task's messages in one thread
+pub struct MatrixChannel {
+    config: MatrixConfig,
+    threads: Threads,
+}
+
+impl MatrixChannel {
+    pub fn new(config: MatrixConfig, threads: Threads) -> Self {
+        Self { config, threads }
+    }
+
+    /// Send `text`, in the thread rooted at `thread` if set; returns the
+    /// new event's ID
+    fn post(&self, text: &str, thread: Option<&str>) -> Result<Option<String>> {
+        let token = std::env::var(&self.config.access_token_env)
+            .with_context(|| format!("{} is not set", self.config.access_token_env))?;
+        let url = format!(
+            "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+            self.config.homeserver.trim_end_matches('/'),
+            http::percent_encode(&self.config.room_id),
+            uuid::Uuid::new_v4()
+        );
+        let mut content = json!({ "msgtype": "m.text", "body": text });
+        if let Some(root) = thread {
+            // Clients without thread support show a plain reply to the root
+            content["m.relates_to"] = json!({
+                "rel_type": "m.thread",
+                "event_id": root,
+                "is_falling_back": true,
+                "m.in_reply_to": { "event_id": root },
+            });
+        }
+
+        let reply = http::put_json(
+            &url,
+            &[("Authorization", &format!("Bearer {}", token))],
+            &content,
+        )?;
+        Ok(reply["event_id"].as_str().map(str::to_string))
+    }
+}
+
+impl Channel for MatrixChannel {
+    fn name(&self) -> &str {
+        "matrix"
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let text = format!("{}\n{}", notification.title, notification.body);
+        self.threads
+            .deliver(notification, |thread| self.post(&text, thread))
+    }
+
+    fn resolved(&self, task_id: &str) -> Result<()> {
+        self.threads.reply(task_id, |thread| {
+            self.post("▶ Running again", Some(thread)).map(drop)
+        })
+    }
+}
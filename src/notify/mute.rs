@@ -0,0 +1,223 @@
+//! Silencing notifications without turning off detection
+//!
+//! A mute names any of an attention reason, a task and a channel; a
+//! notification matching all the fields a mute sets is not sent through
+//! the muted channels (all of them when the mute names none). Detectors
+//! keep running, so the task still shows as needing attention and the
+//! timeline records what was held back.
+//!
+//! Mutes come from `[[notify.mute]]` in the config and from `agent-inbox
+//! mute`, which stores them in the task database so running monitors pick
+//! them up without a restart.
+
+use super::Notification;
+use crate::config::MuteConfig;
+use crate::db::Database;
+use crate::monitor::respond::glob_match;
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+
+/// One mute rule; unset fields match everything
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mute {
+    /// Database ID, for mutes added with `agent-inbox mute`
+    pub id: Option<i64>,
+    /// Attention reason kind, e.g. "process_stalled"
+    pub reason: Option<String>,
+    /// Task ID, `*` wildcards allowed
+    pub task: Option<String>,
+    pub channel: Option<String>,
+    /// When the mute lifts by itself
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Mute {
+    pub fn new(reason: Option<&str>, task: Option<&str>, channel: Option<&str>) -> Self {
+        Self {
+            id: None,
+            reason: reason.map(normalize_reason),
+            task: task.map(str::to_string),
+            channel: channel.map(str::to_string),
+            until: None,
+        }
+    }
+
+    pub fn from_config(config: &MuteConfig) -> Self {
+        Self::new(
+            config.reason.as_deref(),
+            config.task.as_deref(),
+            config.channel.as_deref(),
+        )
+    }
+
+    /// Lift the mute at `until`
+    pub fn until(mut self, until: DateTime<Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_none_or(|until| now < until)
+    }
+
+    /// Whether `notification` is held back from `channel`
+    pub fn matches(&self, notification: &Notification, channel: &str) -> bool {
+        let field =
+            |rule: &Option<String>, value: Option<&str>, matches: fn(&str, &str) -> bool| {
+                rule.as_deref()
+                    .is_none_or(|rule| value.is_some_and(|value| matches(rule, value)))
+            };
+        field(
+            &self.reason,
+            notification.reason.as_deref(),
+            |rule, reason| rule == reason,
+        ) && field(&self.task, notification.task_id.as_deref(), glob_match)
+            && field(&self.channel, Some(channel), |rule, channel| {
+                rule == channel
+            })
+    }
+
+    /// "reason process_stalled, task build-*, every channel"
+    pub fn describe(&self) -> String {
+        let mut parts = vec![
+            format!("reason {}", self.reason.as_deref().unwrap_or("any")),
+            format!("task {}", self.task.as_deref().unwrap_or("any")),
+            match &self.channel {
+                Some(channel) => format!("via {}", channel),
+                None => "every channel".to_string(),
+            },
+        ];
+        if let Some(until) = self.until {
+            parts.push(format!(
+                "until {}",
+                crate::datetime::times().absolute(until)
+            ));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Reason kinds are snake_case; accept "process-stalled" too
+pub fn normalize_reason(reason: &str) -> String {
+    reason.trim().replace('-', "_")
+}
+
+/// The mutes a [`Notifier`](super::Notifier) applies
+#[derive(Default)]
+pub struct Mutes {
+    rules: Vec<Mute>,
+    /// Task database holding the mutes added from the CLI
+    db_path: Option<PathBuf>,
+}
+
+impl Mutes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The `[[notify.mute]]` rules plus those stored in the default database
+    pub fn from_config(config: &[MuteConfig]) -> Self {
+        config
+            .iter()
+            .fold(Self::new(), |mutes, rule| {
+                mutes.rule(Mute::from_config(rule))
+            })
+            .stored(crate::db::default_db_path())
+    }
+
+    pub fn rule(mut self, mute: Mute) -> Self {
+        self.rules.push(mute);
+        self
+    }
+
+    /// Also apply the mutes stored in this task database, re-read for every
+    /// notification
+    pub fn stored(mut self, db_path: PathBuf) -> Self {
+        self.db_path = Some(db_path);
+        self
+    }
+
+    /// Every mute in effect right now
+    pub fn active(&self) -> Vec<Mute> {
+        let now = Utc::now();
+        let mut active: Vec<Mute> = self
+            .rules
+            .iter()
+            .filter(|m| m.is_active(now))
+            .cloned()
+            .collect();
+        if let Some(path) = self.db_path.as_ref().filter(|path| path.exists()) {
+            match Database::open(path).and_then(|db| db.list_mutes(now)) {
+                Ok(stored) => active.extend(stored),
+                Err(e) => eprintln!("Failed to read mutes, ignoring them: {:#}", e),
+            }
+        }
+        active
+    }
+
+    /// Whether `notification` is held back from `channel` by any of `active`
+    pub fn muted(active: &[Mute], notification: &Notification, channel: &str) -> bool {
+        active
+            .iter()
+            .any(|mute| mute.matches(notification, channel))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::{Category, Urgency};
+
+    fn stalled(task_id: &str) -> Notification {
+        Notification {
+            title: "[claude_code] Deploy".to_string(),
+            body: "Process stalled (no activity)".to_string(),
+            urgency: Urgency::Normal,
+            category: Category::Attention,
+            task_id: Some(task_id.to_string()),
+            reason: Some("process_stalled".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_matching() {
+        let mute = Mute::new(Some("process-stalled"), Some("build-*"), None);
+        assert_eq!(mute.reason.as_deref(), Some("process_stalled"));
+        assert!(mute.matches(&stalled("build-1"), "slack"));
+        assert!(!mute.matches(&stalled("deploy"), "slack"));
+        assert!(!mute.matches(
+            &Notification {
+                reason: None,
+                ..stalled("build-1")
+            },
+            "slack"
+        ));
+
+        let slack_only = Mute::new(None, None, Some("slack"));
+        assert!(slack_only.matches(&stalled("deploy"), "slack"));
+        assert!(!slack_only.matches(&stalled("deploy"), "desktop"));
+    }
+
+    #[test]
+    fn test_stored_mutes_expire() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("tasks.db");
+        let db = Database::open(&db_path).unwrap();
+        let now = Utc::now();
+        db.insert_mute(&Mute::new(Some("rate_limited"), None, None))
+            .unwrap();
+        db.insert_mute(
+            &Mute::new(None, Some("t1"), None).until(now - chrono::Duration::minutes(1)),
+        )
+        .unwrap();
+
+        let mutes = Mutes::new()
+            .rule(Mute::new(None, None, Some("email")))
+            .stored(db_path);
+        let active = mutes.active();
+        assert_eq!(active.len(), 2);
+        assert!(active[1].id.is_some());
+        assert!(!Mutes::muted(&active, &stalled("t1"), "desktop"));
+        assert!(Mutes::muted(&active, &stalled("t1"), "email"));
+    }
+}
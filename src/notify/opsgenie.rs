@@ -0,0 +1,111 @@
+use super::alerts::{opsgenie_priority, OpenAlerts};
+use super::{http, Channel, Notification};
+use crate::config::OpsgenieConfig;
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+
+/// Opsgenie Alert API: attention and failures create alerts, closed when
+/// the task resumes or completes
+pub struct OpsgenieChannel {
+    config: OpsgenieConfig,
+    alerts: OpenAlerts,
+}
+
+impl OpsgenieChannel {
+    pub fn new(config: OpsgenieConfig, alerts: OpenAlerts) -> Self {
+        Self { config, alerts }
+    }
+
+    fn authorization(&self) -> Result<String> {
+        let key = std::env::var(&self.config.api_key_env)
+            .with_context(|| format!("{} is not set", self.config.api_key_env))?;
+        Ok(format!("GenieKey {}", key))
+    }
+
+    fn close(&self, authorization: &str, alias: &str) -> Result<()> {
+        let url = format!(
+            "{}/v2/alerts/{}/close?identifierType=alias",
+            self.config.api_url.trim_end_matches('/'),
+            http::percent_encode(alias)
+        );
+        http::post_json(
+            &url,
+            &[("Authorization", authorization)],
+            &json!({ "source": "agent-inbox", "note": "The task is running again" }),
+        )
+    }
+}
+
+fn alert(notification: &Notification, alias: Option<&str>) -> Value {
+    let mut alert = json!({
+        // Opsgenie rejects messages over 130 characters
+        "message": notification.title.chars().take(130).collect::<String>(),
+        "description": notification.body,
+        "priority": opsgenie_priority(notification.urgency),
+        "source": crate::federation::node_name(),
+        "tags": ["agent-inbox"],
+        "details": {
+            "task_id": notification.task_id.clone().unwrap_or_default(),
+            "reason": notification.reason.clone().unwrap_or_default(),
+        },
+    });
+    if let Some(alias) = alias {
+        alert["alias"] = json!(alias);
+    }
+    alert
+}
+
+impl Channel for OpsgenieChannel {
+    fn name(&self) -> &str {
+        "opsgenie"
+    }
+
+    fn max_body_chars(&self) -> Option<usize> {
+        // The longest description the API accepts
+        Some(15000)
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let authorization = self.authorization()?;
+        let url = format!("{}/v2/alerts", self.config.api_url.trim_end_matches('/'));
+        self.alerts.deliver(
+            notification,
+            |alias| {
+                http::post_json(
+                    &url,
+                    &[("Authorization", &authorization)],
+                    &alert(notification, alias),
+                )
+            },
+            |alias| self.close(&authorization, alias),
+        )
+    }
+
+    fn resolved(&self, task_id: &str) -> Result<()> {
+        let authorization = self.authorization()?;
+        self.alerts
+            .resolve(task_id, |alias| self.close(&authorization, alias))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::{Category, Urgency};
+
+    #[test]
+    fn test_alert() {
+        let notification = Notification {
+            title: "x".repeat(200),
+            body: "Stalled for 10m".to_string(),
+            urgency: Urgency::Normal,
+            category: Category::Attention,
+            task_id: Some("task-1".to_string()),
+            reason: Some("stalled".to_string()),
+        };
+        let alert = alert(&notification, Some("agent-inbox/task-1/stalled"));
+        assert_eq!(alert["alias"], "agent-inbox/task-1/stalled");
+        assert_eq!(alert["priority"], "P3");
+        assert_eq!(alert["message"].as_str().unwrap().len(), 130);
+    }
+}
@@ -0,0 +1,133 @@
+//! Per-task delivery order
+//!
+//! Channels deliver concurrently, and several processes notify about the
+//! same task: its monitor, `report` from the wrappers, the daemon. Left
+//! alone, a "running again" from `report resumed` can reach Slack while
+//! the monitor's "needs input" is still on its way, and the chat reads
+//! backwards.
+//!
+//! So every notification and resolution about a task takes the next
+//! number of the task's sequence, kept in the task database. Each channel
+//! records the last number it finished for the task, delivered or not,
+//! and a number is only handed to the channel once the one before it is
+//! done. Channels a number isn't meant for (routed away, muted, switched
+//! off) are marked done right away, and a notification that joins a batch
+//! another one holds open is done for every channel.
+//!
+//! A number whose sender died never gets done, so waiting is bounded:
+//! after `wait` the channel goes ahead anyway.
+
+use crate::db::Database;
+use anyhow::Result;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a channel's progress on a task is checked while waiting
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A place in a task's sequence
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ticket {
+    pub task_id: String,
+    pub seq: i64,
+}
+
+/// Sequences and per-channel progress in the task database
+pub struct Order {
+    db_path: PathBuf,
+    wait: Duration,
+}
+
+impl Order {
+    /// Wait at most `wait` for an earlier number; it should cover the
+    /// delivery timeout plus the batch window
+    pub fn new(db_path: PathBuf, wait: Duration) -> Self {
+        Self { db_path, wait }
+    }
+
+    /// The next place in `task_id`'s sequence
+    pub fn take(&self, task_id: &str) -> Result<Ticket> {
+        let seq = Database::open(&self.db_path)?.next_sequence(task_id)?;
+        Ok(Ticket {
+            task_id: task_id.to_string(),
+            seq,
+        })
+    }
+
+    /// Call `start` for each of `channels` once its turn for `ticket` has
+    /// come, or the wait is over; channels whose turn has come start first
+    pub fn in_turn<T>(&self, ticket: &Ticket, channels: Vec<(&str, T)>, mut start: impl FnMut(T)) {
+        let db = match Database::open(&self.db_path) {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("Failed to read delivery order, sending unordered: {:#}", e);
+                channels.into_iter().for_each(|(_, channel)| start(channel));
+                return;
+            }
+        };
+        let give_up = Instant::now() + self.wait;
+        let mut waiting = channels;
+        loop {
+            let timed_out = Instant::now() >= give_up;
+            let (ready, behind): (Vec<_>, Vec<_>) = waiting.into_iter().partition(|(name, _)| {
+                timed_out
+                    || db
+                        .finished_sequence(&ticket.task_id, name)
+                        .map_or(true, |done| done >= ticket.seq - 1)
+            });
+            for (name, channel) in ready {
+                if timed_out {
+                    eprintln!(
+                        "Sending #{} of {} via {} without waiting any longer",
+                        ticket.seq, ticket.task_id, name
+                    );
+                }
+                start(channel);
+            }
+            if behind.is_empty() {
+                return;
+            }
+            waiting = behind;
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// `channel` is done with `ticket`, whether it delivered or not
+    pub fn done(&self, ticket: &Ticket, channel: &str) {
+        let finished = Database::open(&self.db_path)
+            .and_then(|db| db.finish_sequence(&ticket.task_id, channel, ticket.seq));
+        if let Err(e) = finished {
+            eprintln!(
+                "Failed to record delivery order for {}: {:#}",
+                ticket.task_id, e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_turn() {
+        let dir = tempfile::tempdir().unwrap();
+        let order = Order::new(dir.path().join("tasks.db"), Duration::from_millis(300));
+        let first = order.take("build").unwrap();
+        let second = order.take("build").unwrap();
+        assert_eq!((first.seq, second.seq), (1, 2));
+        assert_eq!(order.take("deploy").unwrap().seq, 1);
+
+        // slack is done with the first number, telegram isn't
+        order.done(&first, "slack");
+        let mut started = Vec::new();
+        let at = Instant::now();
+        order.in_turn(
+            &second,
+            vec![("telegram", "telegram"), ("slack", "slack")],
+            |name| started.push((name, at.elapsed() >= Duration::from_millis(300))),
+        );
+        assert_eq!(started, vec![("slack", false), ("telegram", true)]);
+    }
+}
@@ -0,0 +1,108 @@
+use super::alerts::{pagerduty_severity, OpenAlerts};
+use super::{http, Channel, Notification};
+use crate::config::PagerDutyConfig;
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+
+const EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// PagerDuty Events API v2: attention and failures trigger incidents,
+/// resolved when the task resumes or completes
+pub struct PagerDutyChannel {
+    config: PagerDutyConfig,
+    alerts: OpenAlerts,
+}
+
+impl PagerDutyChannel {
+    pub fn new(config: PagerDutyConfig, alerts: OpenAlerts) -> Self {
+        Self { config, alerts }
+    }
+
+    fn routing_key(&self) -> Result<String> {
+        std::env::var(&self.config.routing_key_env)
+            .with_context(|| format!("{} is not set", self.config.routing_key_env))
+    }
+}
+
+fn trigger_event(routing_key: &str, notification: &Notification, dedup_key: Option<&str>) -> Value {
+    let mut event = json!({
+        "routing_key": routing_key,
+        "event_action": "trigger",
+        "payload": {
+            // PagerDuty truncates at 1024 characters
+            "summary": notification.title.chars().take(1024).collect::<String>(),
+            "source": crate::federation::node_name(),
+            "severity": pagerduty_severity(notification.urgency),
+            "component": "agent-inbox",
+            "custom_details": {
+                "details": notification.body,
+                "task_id": notification.task_id,
+                "reason": notification.reason,
+            },
+        },
+    });
+    if let Some(key) = dedup_key {
+        event["dedup_key"] = json!(key);
+    }
+    event
+}
+
+impl Channel for PagerDutyChannel {
+    fn name(&self) -> &str {
+        "pagerduty"
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let routing_key = self.routing_key()?;
+        self.alerts.deliver(
+            notification,
+            |key| {
+                http::post_json(
+                    EVENTS_URL,
+                    &[],
+                    &trigger_event(&routing_key, notification, key),
+                )
+            },
+            |key| resolve(&routing_key, key),
+        )
+    }
+
+    fn resolved(&self, task_id: &str) -> Result<()> {
+        let routing_key = self.routing_key()?;
+        self.alerts
+            .resolve(task_id, |key| resolve(&routing_key, key))
+    }
+}
+
+fn resolve(routing_key: &str, dedup_key: &str) -> Result<()> {
+    http::post_json(
+        EVENTS_URL,
+        &[],
+        &json!({ "routing_key": routing_key, "event_action": "resolve", "dedup_key": dedup_key }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::{Category, Urgency};
+
+    #[test]
+    fn test_trigger_event() {
+        let notification = Notification {
+            title: "⏳ [claude_code] Deploy".to_string(),
+            body: "Waiting for input".to_string(),
+            urgency: Urgency::Critical,
+            category: Category::Attention,
+            task_id: Some("task-1".to_string()),
+            reason: Some("prompt".to_string()),
+        };
+        let event = trigger_event("key", &notification, Some("agent-inbox/task-1/prompt"));
+        assert_eq!(event["dedup_key"], "agent-inbox/task-1/prompt");
+        assert_eq!(event["payload"]["severity"], "critical");
+        assert_eq!(event["payload"]["custom_details"]["reason"], "prompt");
+        assert!(trigger_event("key", &notification, None)
+            .get("dedup_key")
+            .is_none());
+    }
+}
@@ -0,0 +1,93 @@
+use super::template::Markup;
+use super::threads::Threads;
+use super::{http, Channel, Notification};
+use crate::config::SlackConfig;
+use anyhow::{Context, Result};
+use serde_json::json;
+
+const POST_MESSAGE_URL: &str = "https://slack.com/api/chat.postMessage";
+
+/// Slack through an incoming webhook, or through the Web API as a bot,
+/// which keeps each task's messages in one thread
+pub struct SlackChannel {
+    config: SlackConfig,
+    threads: Threads,
+}
+
+impl SlackChannel {
+    pub fn new(config: SlackConfig, threads: Threads) -> Result<Self> {
+        anyhow::ensure!(
+            !config.webhook_url.is_empty()
+                || (config.bot_token_env.is_some() && config.channel.is_some()),
+            "[notify.slack] needs webhook_url, or bot_token_env and channel"
+        );
+        Ok(Self { config, threads })
+    }
+
+    /// Bot token variable and channel, when posting through the Web API
+    fn bot(&self) -> Option<(&str, &str)> {
+        Some((
+            self.config.bot_token_env.as_deref()?,
+            self.config.channel.as_deref()?,
+        ))
+    }
+
+    /// chat.postMessage, as a reply when `thread` is set; returns the
+    /// message's timestamp, which is how Slack names threads
+    fn post(&self, text: &str, thread: Option<&str>) -> Result<Option<String>> {
+        let Some((token_env, channel)) = self.bot() else {
+            return Ok(None);
+        };
+        let token =
+            std::env::var(token_env).with_context(|| format!("{} is not set", token_env))?;
+        let mut body = json!({ "channel": channel, "text": text });
+        if let Some(ts) = thread {
+            body["thread_ts"] = json!(ts);
+        }
+        let reply = http::post_json_reply(
+            POST_MESSAGE_URL,
+            &[("Authorization", &format!("Bearer {}", token))],
+            &body,
+        )?;
+        // The Web API answers 200 even when it refuses the message
+        anyhow::ensure!(
+            reply["ok"] == true,
+            "Slack refused the message: {}",
+            reply["error"].as_str().unwrap_or("unknown error")
+        );
+        Ok(reply["ts"].as_str().map(str::to_string))
+    }
+}
+
+impl Channel for SlackChannel {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    fn max_body_chars(&self) -> Option<usize> {
+        // Slack splits longer messages, or truncates them past 40000
+        Some(4000)
+    }
+
+    fn markup(&self) -> Markup {
+        Markup::Slack
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let text = format!("*{}*\n{}", notification.title, notification.body);
+        if self.bot().is_none() {
+            return http::post_json(&self.config.webhook_url, &[], &json!({ "text": text }));
+        }
+        self.threads
+            .deliver(notification, |thread| self.post(&text, thread))
+    }
+
+    fn resolved(&self, task_id: &str) -> Result<()> {
+        if self.bot().is_none() {
+            return Ok(());
+        }
+        self.threads.reply(task_id, |thread| {
+            self.post("▶ Running again", Some(thread)).map(drop)
+        })
+    }
+}
@@ -0,0 +1,94 @@
+use super::{http, Channel, Notification, Urgency};
+use crate::config::{TelegramConfig, TelegramSound};
+use anyhow::{Context, Result};
+use serde_json::json;
+
+/// Telegram bot API `sendMessage`
+pub struct TelegramChannel {
+    config: TelegramConfig,
+}
+
+impl TelegramChannel {
+    pub fn new(config: TelegramConfig) -> Self {
+        Self { config }
+    }
+
+    /// Arrive without a sound: as `[notify.telegram.reasons]` says, else
+    /// for low-urgency events
+    fn silent(&self, notification: &Notification) -> bool {
+        match notification.by_kind(&self.config.reasons) {
+            Some(sound) => *sound == TelegramSound::Silent,
+            None => notification.urgency == Urgency::Low,
+        }
+    }
+
+    /// The `sendMessage` request body
+    fn message(&self, notification: &Notification) -> serde_json::Value {
+        json!({
+            "chat_id": self.config.chat_id,
+            "text": format!("{}\n{}", notification.title, notification.body),
+            "disable_notification": self.silent(notification),
+        })
+    }
+}
+
+impl Channel for TelegramChannel {
+    fn name(&self) -> &str {
+        "telegram"
+    }
+
+    fn max_body_chars(&self) -> Option<usize> {
+        // The API rejects messages over 4096 characters, title included
+        Some(4000)
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let token = std::env::var(&self.config.bot_token_env)
+            .with_context(|| format!("{} is not set", self.config.bot_token_env))?;
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", token);
+
+        http::post_json(&url, &[], &self.message(notification))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::Category;
+
+    #[test]
+    fn test_sound_by_reason() {
+        let channel = TelegramChannel::new(TelegramConfig {
+            chat_id: "42".to_string(),
+            bot_token_env: "TELEGRAM_BOT_TOKEN".to_string(),
+            reasons: [
+                ("rate-limited".to_string(), TelegramSound::Loud),
+                ("completed".to_string(), TelegramSound::Silent),
+            ]
+            .into(),
+        });
+        let mut notification = Notification {
+            title: "[claude_code] Deploy".to_string(),
+            body: "Rate limited".to_string(),
+            urgency: Urgency::Low,
+            category: Category::Attention,
+            task_id: None,
+            reason: Some("rate_limited".to_string()),
+        };
+        let silent = |n: &Notification| channel.message(n)["disable_notification"].clone();
+        assert_eq!(
+            channel.message(&notification)["text"],
+            "[claude_code] Deploy\nRate limited"
+        );
+        // Mapped: loud despite the low urgency
+        assert_eq!(silent(&notification), false);
+        // Unmapped: silent when low urgency
+        notification.reason = Some("looping".to_string());
+        assert_eq!(silent(&notification), true);
+        notification.urgency = Urgency::Normal;
+        assert_eq!(silent(&notification), false);
+        notification.reason = None;
+        notification.category = Category::Completed;
+        assert_eq!(silent(&notification), true);
+    }
+}
@@ -0,0 +1,339 @@
+//! Placeholders and helper filters for notification templates
+//!
+//! A placeholder is `{name}`, optionally followed by filters:
+//! `{summary|truncate:200|code}`. Filters apply left to right:
+//!
+//! - `duration`: a number of seconds as "1h42m"; `duration:long` as
+//!   "1 hour 42 minutes"
+//! - `truncate:N`: at most N characters, cut at a word boundary with "…"
+//! - `emoji`: a severity ("low", "normal", "critical") as 🟢, 🟡 or 🔴
+//! - `escape`: escaped for each channel's markup, so output with `<`, `>`
+//!   or `&` in it shows as typed
+//! - `code`: a code block in each channel's markup
+//!
+//! `escape` and `code` depend on where the text goes, so they only mark the
+//! text; each channel's worker resolves the marks with [`finish`] just
+//! before sending. Slack gets mrkdwn; the other channels send plain text.
+//!
+//! Filters of your own go in `[helpers]`, by name (lowercase letters): a
+//! WebAssembly module exporting `filter(ptr: i32, len: i32) -> i64`, given
+//! `{"value", "arg"}` and returning the new value. It can't reach anything
+//! but its input; see [`crate::sandbox::wasm`]. Built-in filters take
+//! precedence.
+//!
+//! Names the template's caller doesn't know, and placeholders with unknown
+//! filters or ones that fail, are left as written.
+
+use crate::models::progress::format_duration;
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+#[cfg(feature = "wasm")]
+use {
+    crate::config::{Config, SandboxConfig},
+    crate::sandbox::wasm::Script,
+    std::collections::HashMap,
+    std::path::PathBuf,
+};
+
+/// How a channel formats text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Markup {
+    #[default]
+    Plain,
+    /// Slack's mrkdwn
+    Slack,
+}
+
+// Private-use characters around text marked by `escape` and `code`
+const ESCAPE_OPEN: char = '\u{E000}';
+const ESCAPE_CLOSE: char = '\u{E001}';
+const CODE_OPEN: char = '\u{E002}';
+const CODE_CLOSE: char = '\u{E003}';
+const MARKS: [char; 4] = [ESCAPE_OPEN, ESCAPE_CLOSE, CODE_OPEN, CODE_CLOSE];
+
+/// Fill `template`, looking up each placeholder's value with `value`
+pub fn render(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    let placeholder = PLACEHOLDER
+        .get_or_init(|| Regex::new(r"\{([a-z_]+)((?:\|[a-z]+(?::[^|{}]*)?)*)\}").unwrap());
+    placeholder
+        .replace_all(template, |caps: &Captures| {
+            let mut filters = caps[2].split('|').skip(1);
+            value(&caps[1])
+                .and_then(|value| filters.try_fold(value, |value, filter| apply(&value, filter)))
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+/// `value` through `filter`; None for an unknown filter or a value it
+/// doesn't take
+fn apply(value: &str, filter: &str) -> Option<String> {
+    let (name, arg) = filter.split_once(':').unwrap_or((filter, ""));
+    match (name, arg) {
+        ("duration", "") => Some(format_duration(value.trim().parse().ok()?)),
+        ("duration", "long") => Some(long_duration(value.trim().parse().ok()?)),
+        ("truncate", max) => Some(truncate(value, max.parse().ok()?)),
+        ("emoji", "") => Some(severity_emoji(value).to_string()),
+        ("escape", "") => Some(format!("{}{}{}", ESCAPE_OPEN, value, ESCAPE_CLOSE)),
+        ("code", "") => Some(format!("{}{}{}", CODE_OPEN, value, CODE_CLOSE)),
+        #[cfg(feature = "wasm")]
+        _ => helpers().apply(name, value, arg),
+        #[cfg(not(feature = "wasm"))]
+        _ => None,
+    }
+}
+
+/// Filters from `[helpers]`, by name
+#[cfg(feature = "wasm")]
+#[derive(Default)]
+pub struct Helpers(HashMap<String, Script>);
+
+#[cfg(feature = "wasm")]
+impl Helpers {
+    /// Compile `helpers`, relative to the config directory; broken ones are
+    /// reported and left out
+    pub fn new(helpers: &HashMap<String, PathBuf>, sandbox: &SandboxConfig) -> Self {
+        let load = |name: &str, path: &PathBuf| -> anyhow::Result<Script> {
+            anyhow::ensure!(
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase()),
+                "filter names are lowercase letters"
+            );
+            let script = Script::load(&crate::storage::config_dir().join(path), sandbox)?;
+            anyhow::ensure!(script.exports("filter"), "it exports no filter");
+            Ok(script)
+        };
+        let helpers = helpers
+            .iter()
+            .filter_map(|(name, path)| match load(name, path) {
+                Ok(script) => Some((name.clone(), script)),
+                Err(e) => {
+                    eprintln!("Skipping helper '{}': {:#}", name, e);
+                    None
+                }
+            })
+            .collect();
+        Self(helpers)
+    }
+
+    /// `value` through the helper `name`; None when there is no such
+    /// helper or it fails
+    pub fn apply(&self, name: &str, value: &str, arg: &str) -> Option<String> {
+        let script = self.0.get(name)?;
+        let input = serde_json::json!({ "value": value, "arg": arg });
+        script
+            .text("filter", &input)
+            .map_err(|e| eprintln!("Helper '{}': {:#}", name, e))
+            .ok()?
+    }
+}
+
+/// This process's helpers, from `[helpers]`
+#[cfg(feature = "wasm")]
+fn helpers() -> &'static Helpers {
+    static HELPERS: OnceLock<Helpers> = OnceLock::new();
+    HELPERS.get_or_init(|| {
+        let config = Config::load().unwrap_or_default();
+        Helpers::new(&config.helpers, &config.sandbox)
+    })
+}
+
+/// "1 hour 42 minutes", "2 days 3 hours", "45 seconds": like
+/// [`format_duration`], the largest unit and the next one
+pub fn long_duration(secs: i64) -> String {
+    let secs = secs.max(0);
+    let units = [
+        (secs / 86400, "day"),
+        (secs % 86400 / 3600, "hour"),
+        (secs % 3600 / 60, "minute"),
+        (secs % 60, "second"),
+    ];
+    let word = |(count, unit): (i64, &str)| {
+        format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+    };
+    let Some(largest) = units.iter().position(|&(count, _)| count > 0) else {
+        return "0 seconds".to_string();
+    };
+    match units.get(largest + 1).filter(|&&(count, _)| count > 0) {
+        Some(&next) => format!("{} {}", word(units[largest]), word(next)),
+        None => word(units[largest]),
+    }
+}
+
+/// `text` cut to at most `max` characters, at the last word boundary when
+/// there is one near the end, with "…" added
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max.saturating_sub(1)).collect();
+    // Back off to a word boundary unless that loses too much
+    let kept = match cut.rfind(char::is_whitespace) {
+        Some(at) if cut[..at].chars().count() >= max * 4 / 5 => &cut[..at],
+        _ => &cut,
+    };
+    format!("{}…", kept.trim_end())
+}
+
+fn severity_emoji(severity: &str) -> &'static str {
+    match severity.trim() {
+        "low" => "🟢",
+        "critical" => "🔴",
+        _ => "🟡",
+    }
+}
+
+/// Resolve the `escape` and `code` marks in `text` for `markup`
+pub fn finish(text: &str, markup: Markup) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find([ESCAPE_OPEN, CODE_OPEN]) {
+        out.push_str(&strip(&rest[..open]));
+        let mark = rest[open..].chars().next().unwrap_or_default();
+        let inner = &rest[open + mark.len_utf8()..];
+        let close = if mark == CODE_OPEN {
+            CODE_CLOSE
+        } else {
+            ESCAPE_CLOSE
+        };
+        // A mark cut off by truncation runs to the end
+        let (marked, after) = match inner.find(close) {
+            Some(end) => (&inner[..end], &inner[end + close.len_utf8()..]),
+            None => (inner, ""),
+        };
+        let marked = strip(marked);
+        if mark == CODE_OPEN {
+            code_block(&mut out, &marked, markup);
+        } else {
+            out.push_str(&escape(&marked, markup));
+        }
+        rest = after;
+    }
+    out.push_str(&strip(rest));
+    out
+}
+
+/// `text` without marks, as plain text
+pub fn plain(text: &str) -> String {
+    finish(text, Markup::Plain)
+}
+
+fn strip(text: &str) -> String {
+    text.replace(MARKS, "")
+}
+
+fn escape(text: &str, markup: Markup) -> String {
+    match markup {
+        Markup::Plain => text.to_string(),
+        Markup::Slack => text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;"),
+    }
+}
+
+/// Append `code` on lines of its own
+fn code_block(out: &mut String, code: &str, markup: Markup) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    let code = code.trim_matches('\n');
+    match markup {
+        Markup::Plain => out.push_str(code),
+        // A fence inside the block would end it early
+        Markup::Slack => {
+            out.push_str("```\n");
+            out.push_str(&escape(code, markup).replace("```", "`\u{200B}``"));
+            out.push_str("\n```");
+        }
+    }
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let value = |name: &str| match name {
+            "title" => Some("Fix <Foo> & *bar*".to_string()),
+            "secs" => Some("6150".to_string()),
+            "severity" => Some("critical".to_string()),
+            "summary" => Some("error[E0308]: mismatched types\n --> src/lib.rs".to_string()),
+            _ => None,
+        };
+        let template =
+            "{severity|emoji} {title|escape} after {secs|duration} ({secs|duration:long})\n\
+                        Summary: {summary|truncate:30|code}{unknown} {title|shout}";
+        let rendered = render(template, value);
+
+        assert_eq!(
+            plain(&rendered),
+            "🔴 Fix <Foo> & *bar* after 1h42m (1 hour 42 minutes)\n\
+             Summary: \nerror[E0308]: mismatched…\n{unknown} {title|shout}"
+        );
+        assert_eq!(
+            finish(&rendered, Markup::Slack),
+            "🔴 Fix &lt;Foo&gt; &amp; *bar* after 1h42m (1 hour 42 minutes)\n\
+             Summary: \n```\nerror[E0308]: mismatched…\n```\n{unknown} {title|shout}"
+        );
+        // A mark cut in half by truncation still resolves
+        let cut: String = rendered.chars().take(70).collect();
+        assert!(!finish(&cut, Markup::Slack).contains(MARKS));
+
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("abcdefghijklmnop", 6), "abcde…");
+        assert_eq!(long_duration(45), "45 seconds");
+        assert_eq!(long_duration(183600), "2 days 3 hours");
+        assert_eq!(long_duration(3600), "1 hour");
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_helpers() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let module = |name: &str, body: &str| {
+            let wat = format!(
+                r#"(module
+                    (memory (export "memory") 1)
+                    (func (export "alloc") (param i32) (result i32) i32.const 1024)
+                    {}
+                )"#,
+                body
+            );
+            let path = dir.path().join(name);
+            std::fs::write(&path, wat::parse_str(wat).unwrap()).unwrap();
+            path
+        };
+        // Returns its input as is
+        let echo = module(
+            "echo.wasm",
+            r#"(func (export "filter") (param i32 i32) (result i64)
+                (i64.or (i64.shl (i64.extend_i32_u (local.get 0)) (i64.const 32))
+                        (i64.extend_i32_u (local.get 1))))"#,
+        );
+        let trap = module(
+            "trap.wasm",
+            r#"(func (export "filter") (param i32 i32) (result i64) unreachable)"#,
+        );
+        let helpers = Helpers::new(
+            &HashMap::from([
+                ("echo".to_string(), echo.clone()),
+                ("trap".to_string(), trap),
+                ("Echo".to_string(), echo),
+                ("missing".to_string(), dir.path().join("missing.wasm")),
+            ]),
+            &SandboxConfig::default(),
+        );
+        assert_eq!(
+            helpers.apply("echo", "Fix bug", "2").as_deref(),
+            Some(r#"{"arg":"2","value":"Fix bug"}"#)
+        );
+        assert_eq!(helpers.apply("trap", "Fix bug", ""), None);
+        // Names a placeholder can't hold, and modules that can't be read,
+        // are left out
+        assert_eq!(helpers.0.len(), 2);
+        assert_eq!(helpers.apply("missing", "Fix bug", ""), None);
+    }
+}
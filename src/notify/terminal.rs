@@ -0,0 +1,186 @@
+//! Marks a task's own terminal, so a glance at the tmux window list (or the
+//! terminal's tab bar) shows which agents need you
+//!
+//! Inside tmux the task's window is renamed to e.g. "⏳ api"; the original
+//! name is kept in the `@agent_inbox_name` window option and restored when
+//! the task runs again. Outside tmux the terminal title is set instead.
+
+use super::{Category, Channel, Notification};
+use crate::db::Database;
+use crate::models::Task;
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Window options holding the name and the window's own
+/// `automatic-rename` setting from before the mark
+const SAVED_NAME: &str = "@agent_inbox_name";
+const SAVED_AUTO_RENAME: &str = "@agent_inbox_auto_rename";
+
+pub struct TerminalChannel {
+    db_path: PathBuf,
+}
+
+impl TerminalChannel {
+    pub fn new(db_path: PathBuf) -> Self {
+        Self { db_path }
+    }
+
+    fn task(&self, task_id: &str) -> Result<Option<Task>> {
+        Database::open(&self.db_path)?.get_task_by_id(task_id)
+    }
+}
+
+/// Where a task's terminal can be marked
+enum Target {
+    Tmux(String),
+    Tty(PathBuf),
+}
+
+fn target(task: &Task) -> Option<Target> {
+    if let Some(pane) = task.tmux_pane() {
+        return Some(Target::Tmux(pane.to_string()));
+    }
+    let tty = crate::presence::terminal_of(task.pid?)?;
+    match tmux(&["list-panes", "-a", "-F", "#{pane_tty} #{pane_id}"]) {
+        Ok(listing) => match pane_on_tty(&listing, &tty) {
+            Some(pane) => Some(Target::Tmux(pane)),
+            None => Some(Target::Tty(tty)),
+        },
+        Err(_) => Some(Target::Tty(tty)),
+    }
+}
+
+fn marker(category: Category) -> Option<&'static str> {
+    match category {
+        Category::Attention => Some("⏳"),
+        Category::Completed => Some("✅"),
+        Category::Failed => Some("❌"),
+        Category::Info => None,
+    }
+}
+
+impl Channel for TerminalChannel {
+    fn name(&self) -> &str {
+        "terminal"
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        // Digests, budget alerts and the like have no terminal to mark
+        let (Some(task_id), Some(marker)) = (&notification.task_id, marker(notification.category))
+        else {
+            return Ok(());
+        };
+        let task = self
+            .task(task_id)?
+            .with_context(|| format!("Task not found: {}", task_id))?;
+        match target(&task).with_context(|| format!("No terminal found for {}", task_id))? {
+            Target::Tmux(pane) => mark_window(&pane, marker),
+            Target::Tty(tty) => set_title(&tty, &format!("{} {}", marker, task.title)),
+        }
+    }
+
+    fn resolved(&self, task_id: &str) -> Result<()> {
+        let Some(task) = self.task(task_id)? else {
+            return Ok(());
+        };
+        match target(&task) {
+            Some(Target::Tmux(pane)) => restore_window(&pane),
+            Some(Target::Tty(tty)) => set_title(&tty, &task.title),
+            None => Ok(()),
+        }
+    }
+}
+
+fn tmux(args: &[&str]) -> Result<String> {
+    let output = Command::new("tmux")
+        .args(args)
+        .output()
+        .context("Failed to run tmux")?;
+    anyhow::ensure!(output.status.success(), "tmux {} failed", args[0]);
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string())
+}
+
+fn mark_window(pane: &str, marker: &str) -> Result<()> {
+    let mut name = tmux(&["show-options", "-wqv", "-t", pane, SAVED_NAME])?;
+    if name.is_empty() {
+        name = tmux(&["display-message", "-p", "-t", pane, "#{window_name}"])?;
+        // Empty when the window inherits the global setting
+        let auto_rename = tmux(&["show-options", "-wqv", "-t", pane, "automatic-rename"])?;
+        tmux(&["set-option", "-w", "-t", pane, SAVED_NAME, &name])?;
+        tmux(&[
+            "set-option",
+            "-w",
+            "-t",
+            pane,
+            SAVED_AUTO_RENAME,
+            &auto_rename,
+        ])?;
+    }
+    tmux(&["rename-window", "-t", pane, &format!("{} {}", marker, name)])?;
+    Ok(())
+}
+
+fn restore_window(pane: &str) -> Result<()> {
+    let name = tmux(&["show-options", "-wqv", "-t", pane, SAVED_NAME])?;
+    if name.is_empty() {
+        return Ok(());
+    }
+    let auto_rename = tmux(&["show-options", "-wqv", "-t", pane, SAVED_AUTO_RENAME])?;
+    tmux(&["rename-window", "-t", pane, &name])?;
+    // rename-window turned automatic renaming off for the window
+    if auto_rename.is_empty() {
+        tmux(&["set-option", "-wu", "-t", pane, "automatic-rename"])?;
+    } else {
+        tmux(&[
+            "set-option",
+            "-w",
+            "-t",
+            pane,
+            "automatic-rename",
+            &auto_rename,
+        ])?;
+    }
+    tmux(&["set-option", "-wu", "-t", pane, SAVED_NAME])?;
+    tmux(&["set-option", "-wu", "-t", pane, SAVED_AUTO_RENAME])?;
+    Ok(())
+}
+
+/// Set the title with the xterm OSC 2 sequence
+fn set_title(tty: &Path, title: &str) -> Result<()> {
+    let mut terminal = std::fs::OpenOptions::new()
+        .write(true)
+        .open(tty)
+        .with_context(|| format!("Failed to open {}", tty.display()))?;
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    write!(terminal, "\x1b]2;{}\x07", title)?;
+    Ok(())
+}
+
+/// The pane whose tty is `tty`, from `list-panes -F "#{pane_tty} #{pane_id}"`
+fn pane_on_tty(listing: &str, tty: &Path) -> Option<String> {
+    let tty = tty.to_string_lossy();
+    listing.lines().find_map(|line| {
+        let (pane_tty, pane) = line.split_once(' ')?;
+        (pane_tty == tty).then(|| pane.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pane_on_tty() {
+        let listing = "/dev/pts/3 %0\n/dev/pts/5 %4\n";
+        assert_eq!(
+            pane_on_tty(listing, Path::new("/dev/pts/5")),
+            Some("%4".to_string())
+        );
+        assert_eq!(pane_on_tty(listing, Path::new("/dev/pts/9")), None);
+        assert_eq!(marker(Category::Info), None);
+    }
+}
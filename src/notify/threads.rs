@@ -0,0 +1,128 @@
+//! One chat thread per task for the chat channels (Slack, Matrix)
+//!
+//! The first message about a task starts a thread; everything after it
+//! (needs input, running again, finished) is posted as a reply, so a busy
+//! task is one conversation instead of a dozen scattered messages. The ID
+//! of each thread is kept in the task database, because the process that
+//! posts the next message (a monitor, `report complete`, a hub) is rarely
+//! the one that started the thread.
+
+use super::Notification;
+use crate::db::Database;
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// Threads started through one channel
+pub struct Threads {
+    channel: String,
+    db_path: PathBuf,
+}
+
+impl Threads {
+    pub fn new(channel: &str, db_path: PathBuf) -> Self {
+        Self {
+            channel: channel.to_string(),
+            db_path,
+        }
+    }
+
+    /// Threads kept in the default task database
+    pub fn for_channel(channel: &str) -> Self {
+        Self::new(channel, crate::db::default_db_path())
+    }
+
+    fn root(&self, task_id: &str) -> Result<Option<String>> {
+        Database::open(&self.db_path)?.thread(task_id, &self.channel)
+    }
+
+    /// Post `notification` with `post`, in its task's thread once there is
+    /// one. `post` returns the ID of the message it sent; the first message
+    /// about a task becomes the root of its thread.
+    pub fn deliver(
+        &self,
+        notification: &Notification,
+        post: impl Fn(Option<&str>) -> Result<Option<String>>,
+    ) -> Result<()> {
+        let Some(task_id) = &notification.task_id else {
+            return post(None).map(drop);
+        };
+        let root = self.root(task_id)?;
+        let id = post(root.as_deref())?;
+        match (root, id) {
+            (None, Some(id)) => {
+                Database::open(&self.db_path)?.set_thread(task_id, &self.channel, &id)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Reply in the task's thread with `post`; tasks without a thread in
+    /// this channel are left alone
+    pub fn reply(&self, task_id: &str, post: impl Fn(&str) -> Result<()>) -> Result<()> {
+        match self.root(task_id)? {
+            Some(root) => post(&root),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::{Category, Urgency};
+    use std::cell::RefCell;
+
+    fn notification(task_id: Option<&str>, body: &str) -> Notification {
+        Notification {
+            title: "[claude_code] Deploy".to_string(),
+            body: body.to_string(),
+            urgency: Urgency::Normal,
+            category: Category::Attention,
+            task_id: task_id.map(str::to_string),
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn test_one_thread_per_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let threads = Threads::new("slack", dir.path().join("tasks.db"));
+        let posted = RefCell::new(Vec::new());
+        let post = |thread: Option<&str>| {
+            let mut posted = posted.borrow_mut();
+            posted.push(thread.map(str::to_string));
+            Ok(Some(format!("msg-{}", posted.len())))
+        };
+
+        threads
+            .deliver(&notification(Some("t1"), "Waiting for input"), post)
+            .unwrap();
+        threads
+            .deliver(&notification(Some("t1"), "Done"), post)
+            .unwrap();
+        threads
+            .deliver(&notification(Some("t2"), "Stalled"), post)
+            .unwrap();
+        threads
+            .deliver(&notification(None, "Digest"), post)
+            .unwrap();
+        assert_eq!(
+            *posted.borrow(),
+            vec![None, Some("msg-1".to_string()), None, None]
+        );
+
+        let replied = RefCell::new(Vec::new());
+        let reply = |root: &str| {
+            replied.borrow_mut().push(root.to_string());
+            Ok(())
+        };
+        threads.reply("t2", reply).unwrap();
+        threads.reply("unknown", reply).unwrap();
+        assert_eq!(*replied.borrow(), vec!["msg-3"]);
+
+        // Each channel has threads of its own
+        let matrix = Threads::new("matrix", dir.path().join("tasks.db"));
+        matrix.reply("t1", reply).unwrap();
+        assert_eq!(replied.borrow().len(), 1);
+    }
+}
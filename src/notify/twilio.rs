@@ -0,0 +1,215 @@
+//! Twilio SMS and WhatsApp, for when nothing else gets through
+//!
+//! Texts cost money, so the channel is strict about what it sends:
+//!
+//! - only notifications at `min_urgency` or above (default critical); the
+//!   rest are dropped quietly, like an on-call channel drops chatter
+//! - at most `max_per_hour` and `max_per_day` texts, counted across
+//!   processes in `~/.local/state/agent-inbox/twilio.sent`; past either
+//!   limit sending fails, so the refusal shows in the history
+//! - at most `max_segments` SMS segments per text. Icons are dropped and
+//!   characters outside the GSM alphabet replaced, so that a text stays in
+//!   the 160-character encoding instead of the 70-character one.
+
+use super::{Channel, Notification};
+use crate::config::TwilioConfig;
+use anyhow::{Context, Result};
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use std::path::PathBuf;
+
+const API_URL: &str = "https://api.twilio.com/2010-04-01";
+
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Times texts were sent, one Unix timestamp per line
+pub struct SentLog {
+    path: PathBuf,
+}
+
+impl SentLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Count a text sent at `now`, unless that would go over a limit
+    pub fn reserve(&self, now: DateTime<Utc>, per_hour: usize, per_day: usize) -> Result<()> {
+        let mut sent: Vec<i64> = std::fs::read_to_string(&self.path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.trim().parse().ok())
+            .filter(|&at| at > (now - Duration::days(1)).timestamp())
+            .collect();
+        let last_hour = sent
+            .iter()
+            .filter(|&&at| at > (now - Duration::hours(1)).timestamp())
+            .count();
+        anyhow::ensure!(
+            last_hour < per_hour,
+            "Texting limit reached: {} in the last hour",
+            last_hour
+        );
+        anyhow::ensure!(
+            sent.len() < per_day,
+            "Texting limit reached: {} in the last day",
+            sent.len()
+        );
+        sent.push(now.timestamp());
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let lines: Vec<String> = sent.iter().map(i64::to_string).collect();
+        std::fs::write(&self.path, lines.join("\n"))
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+pub struct TwilioChannel {
+    config: TwilioConfig,
+    sent: SentLog,
+}
+
+impl TwilioChannel {
+    pub fn new(config: TwilioConfig, sent: SentLog) -> Self {
+        Self { config, sent }
+    }
+
+    fn address(&self, number: &str) -> String {
+        if self.config.whatsapp {
+            format!("whatsapp:{}", number)
+        } else {
+            number.to_string()
+        }
+    }
+}
+
+impl Channel for TwilioChannel {
+    fn name(&self) -> &str {
+        "twilio"
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        if notification.urgency < self.config.min_urgency {
+            return Ok(());
+        }
+        let token = std::env::var(&self.config.auth_token_env)
+            .with_context(|| format!("{} is not set", self.config.auth_token_env))?;
+        self.sent.reserve(
+            Utc::now(),
+            self.config.max_per_hour,
+            self.config.max_per_day,
+        )?;
+
+        let url = format!(
+            "{}/Accounts/{}/Messages.json",
+            API_URL, self.config.account_sid
+        );
+        let credentials = format!("{}:{}", self.config.account_sid, token);
+        let response = ureq::post(&url)
+            .timeout(TIMEOUT)
+            .set(
+                "Authorization",
+                &format!(
+                    "Basic {}",
+                    base64::engine::general_purpose::STANDARD.encode(credentials)
+                ),
+            )
+            .send_form(&[
+                ("From", &self.address(&self.config.from)),
+                ("To", &self.address(&self.config.to)),
+                ("Body", &text(notification, self.config.max_segments)),
+            ]);
+        match response {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(status, response)) => {
+                let message = response
+                    .into_json::<serde_json::Value>()
+                    .ok()
+                    .and_then(|reply| reply["message"].as_str().map(str::to_string))
+                    .unwrap_or_default();
+                anyhow::bail!("Twilio refused the message ({}): {}", status, message)
+            }
+            Err(e) => Err(e).context("Failed to reach Twilio"),
+        }
+    }
+}
+
+/// Title and body as one text of at most `segments` SMS segments
+fn text(notification: &Notification, segments: usize) -> String {
+    let text = Notification {
+        body: gsm(&format!("{}\n{}", notification.title, notification.body)),
+        ..notification.clone()
+    };
+    // Room for the ellipsis `fit` puts in, spelled out below
+    let limit = segment_chars(&text.body, segments.max(1)) - 2;
+    text.fit(limit).body.replace('…', "...")
+}
+
+/// `text` with icons dropped and characters outside the GSM alphabet (or
+/// taking two places in it) replaced by close ones
+fn gsm(text: &str) -> String {
+    let text: String = text
+        .chars()
+        .filter_map(|c| match c {
+            '[' | '{' => Some('('),
+            ']' | '}' => Some(')'),
+            '\\' | '|' => Some('/'),
+            '~' => Some('-'),
+            '`' => Some('\''),
+            '^' => None,
+            c if c.is_ascii() || c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect();
+    text.lines().map(str::trim).collect::<Vec<_>>().join("\n")
+}
+
+/// Characters that fit in `segments` segments; plain ASCII texts go as
+/// GSM-7, anything else as UCS-2
+fn segment_chars(text: &str, segments: usize) -> usize {
+    let (single, per_segment) = if text.is_ascii() {
+        (160, 153)
+    } else {
+        (70, 67)
+    };
+    if segments == 1 {
+        single
+    } else {
+        per_segment * segments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::{Category, Urgency};
+
+    #[test]
+    fn test_text_and_limits() {
+        let notification = Notification {
+            title: "⏳ [claude_code] Deploy".to_string(),
+            body: format!("Waiting for input\n{}\nProceed? [y/N]", "x".repeat(300)),
+            urgency: Urgency::Critical,
+            category: Category::Attention,
+            task_id: None,
+            reason: None,
+        };
+        let text = text(&notification, 1);
+        assert!(text.starts_with("(claude_code) Deploy\n...\n"));
+        assert!(text.ends_with("Proceed? (y/N)"));
+        assert_eq!(text.chars().count(), 160);
+        assert!(super::text(&notification, 3).chars().count() <= 459);
+        assert_eq!(segment_chars("Über", 1), 70);
+
+        let dir = tempfile::tempdir().unwrap();
+        let sent = SentLog::new(dir.path().join("twilio.sent"));
+        let now = Utc::now();
+        sent.reserve(now - Duration::hours(2), 2, 3).unwrap();
+        sent.reserve(now, 2, 3).unwrap();
+        sent.reserve(now, 2, 3).unwrap();
+        let err = sent.reserve(now, 2, 3).unwrap_err();
+        assert_eq!(err.to_string(), "Texting limit reached: 2 in the last hour");
+        let err = sent.reserve(now + Duration::hours(2), 5, 3).unwrap_err();
+        assert_eq!(err.to_string(), "Texting limit reached: 3 in the last day");
+    }
+}
@@ -0,0 +1,206 @@
+//! Delivery workers: every channel sends from its own thread, so a hanging
+//! API call only holds up its own channel
+//!
+//! The [`Notifier`](super::Notifier) waits for each worker up to the
+//! delivery timeout. A channel that fails (or times out) `failure_threshold`
+//! times in a row trips its circuit breaker: it is skipped for `cooldown`,
+//! then gets one trial delivery, which either closes the breaker again or
+//! re-opens it for another cooldown.
+//!
+//! Before sending, a worker translates the notification into its channel's
+//! locale and resolves template marks in the channel's markup.
+//!
+//! A worker's queue keeps the order the notifier handed it jobs in; with an
+//! [`Order`], the worker also records each task's progress when a job is
+//! done, so that other processes can wait their turn.
+
+use super::locale::Catalog;
+use super::order::{Order, Ticket};
+use super::template;
+use super::{Channel, Notification};
+use crate::config::DeliveryConfig;
+use anyhow::Result;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Timeout and circuit breaker settings shared by every worker
+#[derive(Debug, Clone, Copy)]
+pub struct DeliveryPolicy {
+    pub timeout: Duration,
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+impl Default for DeliveryPolicy {
+    fn default() -> Self {
+        Self::from_config(&DeliveryConfig::default())
+    }
+}
+
+impl DeliveryPolicy {
+    pub fn from_config(config: &DeliveryConfig) -> Self {
+        Self {
+            timeout: Duration::from_secs(config.timeout_secs),
+            failure_threshold: config.failure_threshold.max(1),
+            cooldown: Duration::from_secs(config.cooldown_secs),
+        }
+    }
+}
+
+enum Job {
+    Send(Notification, Option<Ticket>, Sender<Result<Option<String>>>),
+    Resolved(String, Option<Ticket>),
+}
+
+#[derive(Default)]
+struct Breaker {
+    /// Failed deliveries in a row
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// A channel running on its own thread
+pub(crate) struct Worker {
+    name: String,
+    jobs: Sender<Job>,
+    breaker: Mutex<Breaker>,
+}
+
+/// The outcome of a delivery that is still in flight
+pub(crate) enum Pending {
+    Waiting(Receiver<Result<Option<String>>>),
+    /// The breaker is open; nothing was sent
+    Skipped,
+}
+
+impl Worker {
+    /// Run `channel` on its own thread, translating with `catalog` if set
+    /// and recording finished tickets in `order` if set
+    pub fn spawn(
+        channel: Box<dyn Channel>,
+        catalog: Option<Arc<Catalog>>,
+        order: Option<Arc<Order>>,
+    ) -> Self {
+        let name = channel.name().to_string();
+        let (jobs, queue) = mpsc::channel::<Job>();
+        thread::Builder::new()
+            .name(format!("notify-{}", name))
+            .spawn(move || {
+                for job in queue {
+                    let ticket = match job {
+                        Job::Send(mut notification, ticket, done) => {
+                            if let Some(catalog) = &catalog {
+                                notification.title = catalog.translate(&notification.title);
+                                notification.body = catalog.translate(&notification.body);
+                            }
+                            notification.title =
+                                template::finish(&notification.title, channel.markup());
+                            notification.body =
+                                template::finish(&notification.body, channel.markup());
+                            let notification = match channel.max_body_chars() {
+                                Some(max) => notification.fit(max),
+                                None => notification,
+                            };
+                            let _ = done.send(channel.send_with_receipt(&notification));
+                            ticket
+                        }
+                        Job::Resolved(task_id, ticket) => {
+                            if let Err(e) = channel.resolved(&task_id) {
+                                eprintln!(
+                                    "Failed to clear {} for {}: {:#}",
+                                    channel.name(),
+                                    task_id,
+                                    e
+                                );
+                            }
+                            ticket
+                        }
+                    };
+                    if let (Some(order), Some(ticket)) = (&order, ticket) {
+                        order.done(&ticket, channel.name());
+                    }
+                }
+            })
+            .expect("failed to spawn notification worker");
+        Self {
+            name,
+            jobs,
+            breaker: Mutex::new(Breaker::default()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The breaker is open and the cooldown has not run out
+    pub fn is_disabled(&self) -> bool {
+        self.breaker
+            .lock()
+            .unwrap()
+            .open_until
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    /// Hand a notification to the worker thread without waiting for it
+    pub fn start(&self, notification: &Notification, ticket: Option<&Ticket>) -> Pending {
+        if self.is_disabled() {
+            return Pending::Skipped;
+        }
+        let (done, result) = mpsc::channel();
+        match self
+            .jobs
+            .send(Job::Send(notification.clone(), ticket.cloned(), done))
+        {
+            Ok(()) => Pending::Waiting(result),
+            Err(_) => Pending::Skipped,
+        }
+    }
+
+    pub fn resolved(&self, task_id: &str, ticket: Option<&Ticket>) {
+        let _ = self
+            .jobs
+            .send(Job::Resolved(task_id.to_string(), ticket.cloned()));
+    }
+
+    /// Wait until `deadline` for a delivery started with [`Worker::start`]
+    /// and update the breaker. Returns the channel's receipt, or the error
+    /// and whether this failure just tripped the breaker.
+    pub fn finish(
+        &self,
+        result: Receiver<Result<Option<String>>>,
+        deadline: Instant,
+        policy: &DeliveryPolicy,
+    ) -> Result<Option<String>, (anyhow::Error, bool)> {
+        let outcome = match result.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            Ok(outcome) => outcome,
+            Err(RecvTimeoutError::Timeout) => Err(anyhow::anyhow!(
+                "timed out after {}s",
+                policy.timeout.as_secs_f32()
+            )),
+            Err(RecvTimeoutError::Disconnected) => Err(anyhow::anyhow!("delivery worker stopped")),
+        };
+        let mut breaker = self.breaker.lock().unwrap();
+        match outcome {
+            Ok(receipt) => {
+                if breaker.open_until.take().is_some() {
+                    eprintln!("Channel {} is delivering again", self.name);
+                }
+                breaker.failures = 0;
+                Ok(receipt)
+            }
+            Err(e) => {
+                breaker.failures += 1;
+                let trips = breaker.failures >= policy.failure_threshold;
+                let tripped_now = trips && breaker.open_until.is_none();
+                if trips {
+                    breaker.open_until = Some(Instant::now() + policy.cooldown);
+                }
+                Err((e, tripped_now))
+            }
+        }
+    }
+}
@@ -0,0 +1,901 @@
+//! Runtime-loaded detector and channel plugins
+//!
+//! Plugins live in `~/.config/agent-inbox/plugins/<name>/` with a `plugin.toml`
+//! manifest next to a shared library exporting the C ABI below. Every value
+//! crossing the boundary is a NUL-terminated UTF-8 JSON string, so the ABI
+//! stays stable as the task model grows.
+//!
+//! ```c
+//! uint32_t agent_inbox_plugin_abi_version(void);           // must return 1
+//! void    *agent_inbox_plugin_init(const char *config);     // config JSON, may return NULL
+//! void     agent_inbox_plugin_destroy(void *state);
+//! void     agent_inbox_plugin_free_string(char *s);
+//! // "detector" capability: NULL = no attention needed, otherwise a reason:
+//! // plain text, or {"kind": "merge_conflict", "detail": "src/lib.rs"}
+//! char    *agent_inbox_detector_check(void *state, const char *task, const char *context);
+//! // "channel" capability: 0 on success
+//! int32_t  agent_inbox_channel_send(void *state, const char *notification);
+//! ```
+//!
+//! Each plugin is loaded in a process of its own, `agent-inbox plugin-host`,
+//! confined by `[sandbox]`, and the daemon talks to it over a pipe. Calls
+//! into a plugin never overlap and come from one thread; what it prints goes
+//! to stderr. A plugin that crashes, or runs over its CPU or memory limit,
+//! is started again on the next call.
+//!
+//! A detector may be a WebAssembly module instead, `library = "*.wasm"`,
+//! run in the daemon with no access to anything (see
+//! [`crate::sandbox::wasm`]). It exports `detector_check(ptr, len) -> i64`,
+//! given `{"config", "task", "context"}` and returning a reason as above,
+//! or none. Having no way to deliver anything, it can't be a channel.
+
+use crate::config::SandboxConfig;
+use crate::models::Task;
+use crate::monitor::detectors::{AttentionDetector, AttentionReason, TaskContext};
+use crate::notify::{Channel, Notification};
+#[cfg(feature = "wasm")]
+use crate::sandbox::wasm::Script;
+use crate::sandbox::Sandbox;
+use anyhow::{Context, Result};
+use libloading::{Library, Symbol};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// The hidden command each plugin runs in: `agent-inbox plugin-host <dir>`
+pub const HOST_COMMAND: &str = "plugin-host";
+
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+type InitFn = unsafe extern "C" fn(*const c_char) -> *mut c_void;
+type DestroyFn = unsafe extern "C" fn(*mut c_void);
+type FreeStringFn = unsafe extern "C" fn(*mut c_char);
+type DetectorCheckFn =
+    unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char) -> *mut c_char;
+type ChannelSendFn = unsafe extern "C" fn(*mut c_void, *const c_char) -> i32;
+
+/// Contents of a plugin's `plugin.toml`
+#[derive(Debug, Clone, Deserialize)]
+pub struct PluginManifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    /// Shared library file, relative to the manifest
+    pub library: PathBuf,
+    /// "detector" and/or "channel"
+    pub capabilities: Vec<String>,
+    /// Config keys the plugin accepts: key -> { type, default, description }
+    #[serde(default)]
+    pub config_schema: HashMap<String, ConfigField>,
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConfigField {
+    #[serde(rename = "type")]
+    pub field_type: String,
+    pub default: Option<serde_json::Value>,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl PluginManifest {
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
+    /// Merge user config over schema defaults and check value types
+    pub fn resolve_config(&self, user: Option<&serde_json::Value>) -> Result<serde_json::Value> {
+        let mut resolved = serde_json::Map::new();
+        for (key, field) in &self.config_schema {
+            if let Some(default) = &field.default {
+                resolved.insert(key.clone(), default.clone());
+            }
+        }
+
+        if let Some(user) = user {
+            let user = user
+                .as_object()
+                .with_context(|| format!("Config for plugin {} must be a table", self.name))?;
+            for (key, value) in user {
+                let field = self.config_schema.get(key).with_context(|| {
+                    format!("Plugin {} has no config option '{}'", self.name, key)
+                })?;
+                let matches = match field.field_type.as_str() {
+                    "string" => value.is_string(),
+                    "integer" => value.is_i64() || value.is_u64(),
+                    "number" => value.is_number(),
+                    "boolean" => value.is_boolean(),
+                    _ => true,
+                };
+                if !matches {
+                    anyhow::bail!(
+                        "Plugin {} option '{}' must be of type {}",
+                        self.name,
+                        key,
+                        field.field_type
+                    );
+                }
+                resolved.insert(key.clone(), value.clone());
+            }
+        }
+
+        Ok(serde_json::Value::Object(resolved))
+    }
+}
+
+pub fn default_plugin_dir() -> PathBuf {
+    crate::storage::config_dir().join("plugins")
+}
+
+/// Find every `*/plugin.toml` under the plugin directory
+pub fn discover<P: AsRef<Path>>(dir: P) -> Result<Vec<PluginManifest>> {
+    let dir = dir.as_ref();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut manifests = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let plugin_dir = entry?.path();
+        let manifest_path = plugin_dir.join("plugin.toml");
+        if !manifest_path.is_file() {
+            continue;
+        }
+
+        match read_manifest(&plugin_dir) {
+            Ok(manifest) => manifests.push(manifest),
+            // One broken plugin mustn't take the others down with it
+            Err(e) => eprintln!(
+                "Skipping invalid manifest {}: {:#}",
+                manifest_path.display(),
+                e
+            ),
+        }
+    }
+
+    manifests.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(manifests)
+}
+
+/// The manifest of the plugin in `dir`
+fn read_manifest(dir: &Path) -> Result<PluginManifest> {
+    let content = std::fs::read_to_string(dir.join("plugin.toml"))?;
+    let mut manifest: PluginManifest = toml::from_str(&content)?;
+    manifest.dir = dir.to_path_buf();
+    Ok(manifest)
+}
+
+/// A loaded shared library plus the state returned by its init function.
+/// The state is locked for every call, so a detector and a channel of the
+/// same plugin, or channel workers, never use it at once.
+struct PluginInstance {
+    name: String,
+    state: Mutex<State>,
+    free_string: FreeStringFn,
+    destroy: DestroyFn,
+    // Keep the library mapped for as long as any function pointer is alive
+    library: Library,
+}
+
+/// The plugin's own state, opaque to us
+struct State(*mut c_void);
+
+// SAFETY: the pointer is only passed to the plugin with the mutex held, so
+// it moves between threads but is never used from two at once
+unsafe impl Send for State {}
+
+impl Drop for PluginInstance {
+    fn drop(&mut self) {
+        let state = self.state.get_mut().unwrap_or_else(|e| e.into_inner());
+        unsafe { (self.destroy)(state.0) }
+    }
+}
+
+impl PluginInstance {
+    fn load(manifest: &PluginManifest, config: &serde_json::Value) -> Result<Self> {
+        let path = manifest.dir.join(&manifest.library);
+        // The library runs with your rights, less what the sandbox takes
+        for file in [&path, &manifest.dir] {
+            if let Some(problem) = untrusted(file) {
+                anyhow::bail!(
+                    "{} {}, so anyone could run code as you",
+                    file.display(),
+                    problem
+                );
+            }
+        }
+        unsafe {
+            let library = Library::new(&path)
+                .with_context(|| format!("Failed to load plugin library {}", path.display()))?;
+
+            let abi_version: Symbol<AbiVersionFn> = library
+                .get(b"agent_inbox_plugin_abi_version\0")
+                .context("Plugin does not export agent_inbox_plugin_abi_version")?;
+            let version = abi_version();
+            if version != PLUGIN_ABI_VERSION {
+                anyhow::bail!(
+                    "Plugin {} uses ABI version {}, expected {}",
+                    manifest.name,
+                    version,
+                    PLUGIN_ABI_VERSION
+                );
+            }
+
+            let init: Symbol<InitFn> = library.get(b"agent_inbox_plugin_init\0")?;
+            let destroy: DestroyFn = *library.get::<DestroyFn>(b"agent_inbox_plugin_destroy\0")?;
+            let free_string: FreeStringFn =
+                *library.get::<FreeStringFn>(b"agent_inbox_plugin_free_string\0")?;
+
+            let config_json = CString::new(config.to_string())?;
+            let state = init(config_json.as_ptr());
+
+            Ok(Self {
+                name: manifest.name.clone(),
+                state: Mutex::new(State(state)),
+                free_string,
+                destroy,
+                library,
+            })
+        }
+    }
+
+    /// The function for `capability`, when the manifest declares it and
+    /// the library exports it
+    ///
+    /// # Safety
+    /// `T` must be the function type the ABI gives `symbol`
+    unsafe fn function<T: Copy>(
+        &self,
+        manifest: &PluginManifest,
+        capability: &str,
+        symbol: &[u8],
+    ) -> Option<T> {
+        if !manifest.has_capability(capability) {
+            return None;
+        }
+        match self.library.get::<T>(symbol) {
+            Ok(function) => Some(*function),
+            Err(e) => {
+                eprintln!("Plugin {} declares {} but: {}", self.name, capability, e);
+                None
+            }
+        }
+    }
+
+    /// The reason `check` gives, if any
+    fn check(
+        &self,
+        check: DetectorCheckFn,
+        task: &serde_json::Value,
+        context: &serde_json::Value,
+    ) -> Result<Option<String>> {
+        let task = CString::new(task.to_string())?;
+        let context = CString::new(context.to_string())?;
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        unsafe {
+            let result = check(state.0, task.as_ptr(), context.as_ptr());
+            if result.is_null() {
+                return Ok(None);
+            }
+            let reason = CStr::from_ptr(result).to_string_lossy().into_owned();
+            (self.free_string)(result);
+            Ok(Some(reason))
+        }
+    }
+
+    /// The status `send` returns
+    fn send(&self, send: ChannelSendFn, notification: &serde_json::Value) -> Result<i32> {
+        let payload = CString::new(notification.to_string())?;
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(unsafe { send(state.0, payload.as_ptr()) })
+    }
+}
+
+/// Why other users could change `path`: it is writable by group or
+/// others, or owned by someone other than you or root. None when only you
+/// can, or it can't be checked.
+#[cfg(unix)]
+fn untrusted(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = std::fs::metadata(path).ok()?;
+    // SAFETY: geteuid(2) has no memory effects
+    let me = unsafe { libc::geteuid() };
+    if metadata.uid() != me && metadata.uid() != 0 {
+        return Some(format!("is owned by uid {}", metadata.uid()));
+    }
+    (metadata.mode() & 0o022 != 0)
+        .then(|| format!("is writable by others (mode {:o})", metadata.mode() & 0o777))
+}
+
+#[cfg(not(unix))]
+fn untrusted(_path: &Path) -> Option<String> {
+    None
+}
+
+/// A plugin running in a process of its own, `agent-inbox plugin-host`, in
+/// the sandbox. Calls go one at a time; if the process dies (a crash, or over
+/// its CPU or memory limit), the next call starts it again.
+struct PluginProcess {
+    manifest: PluginManifest,
+    config: serde_json::Value,
+    sandbox: Sandbox,
+    host: Mutex<Option<Host>>,
+}
+
+/// The running process and its pipes
+struct Host {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+/// What the daemon asks of a plugin's process, one JSON line each
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Request {
+    /// The first request: load the library and init it with `config`
+    Init {
+        config: serde_json::Value,
+    },
+    Check {
+        task: serde_json::Value,
+        context: serde_json::Value,
+    },
+    Send {
+        notification: serde_json::Value,
+    },
+}
+
+/// A plugin process's answer, one JSON line each
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Reply {
+    /// Loaded, with the capabilities the library has
+    Ready {
+        detector: bool,
+        channel: bool,
+    },
+    Reason(Option<String>),
+    Status(i32),
+    Error(String),
+}
+
+impl Host {
+    fn call(&mut self, request: &Request) -> Result<Reply> {
+        writeln!(self.stdin, "{}", serde_json::to_string(request)?)?;
+        self.stdin.flush()?;
+        let mut line = String::new();
+        anyhow::ensure!(
+            self.stdout.read_line(&mut line)? > 0,
+            "the plugin's process exited"
+        );
+        match serde_json::from_str(&line)? {
+            Reply::Error(e) => anyhow::bail!(e),
+            reply => Ok(reply),
+        }
+    }
+}
+
+impl Drop for Host {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl PluginProcess {
+    /// Start the process unless it runs; returns the capabilities found
+    fn start(&self) -> Result<(bool, bool)> {
+        let mut host = self.host.lock().unwrap_or_else(|e| e.into_inner());
+        self.started(&mut host)
+            .map(|(_, capabilities)| capabilities)
+    }
+
+    fn started<'a>(&self, host: &'a mut Option<Host>) -> Result<(&'a mut Host, (bool, bool))> {
+        let mut capabilities = (false, false);
+        if host.is_none() {
+            let exe = host_exe()?;
+            let mut command = Command::new(&exe);
+            command
+                .arg(HOST_COMMAND)
+                .arg(&self.manifest.dir)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped());
+            self.sandbox
+                .clone()
+                .read(&exe)
+                .read(&self.manifest.dir)
+                .apply(&mut command)?;
+            let mut child = command
+                .spawn()
+                .with_context(|| format!("Failed to run {}", exe.display()))?;
+            let mut started = Host {
+                stdin: child.stdin.take().context("no stdin")?,
+                stdout: BufReader::new(child.stdout.take().context("no stdout")?),
+                child,
+            };
+            match started.call(&Request::Init {
+                config: self.config.clone(),
+            })? {
+                Reply::Ready { detector, channel } => capabilities = (detector, channel),
+                reply => anyhow::bail!("Unexpected reply {:?}", reply),
+            }
+            *host = Some(started);
+        }
+        Ok((host.as_mut().expect("started above"), capabilities))
+    }
+
+    fn call(&self, request: &Request) -> Result<Reply> {
+        let mut host = self.host.lock().unwrap_or_else(|e| e.into_inner());
+        let result = self
+            .started(&mut host)
+            .and_then(|(running, _)| running.call(request));
+        if result.is_err() {
+            // Whatever state it is in, start afresh next time
+            *host = None;
+        }
+        result
+    }
+}
+
+/// The `agent-inbox` executable, which hosts plugins; next to this one
+/// when that is another tool
+fn host_exe() -> Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    Ok(exe.with_file_name(format!("agent-inbox{}", std::env::consts::EXE_SUFFIX)))
+}
+
+/// Detector backed by a plugin's `agent_inbox_detector_check`
+pub struct PluginDetector {
+    process: Arc<PluginProcess>,
+}
+
+impl AttentionDetector for PluginDetector {
+    fn check(&self, task: &Task, context: &TaskContext) -> Option<AttentionReason> {
+        let request = Request::Check {
+            task: serde_json::to_value(task).ok()?,
+            context: context_json(context),
+        };
+        match self.process.call(&request) {
+            Ok(Reply::Reason(reason)) => reason.map(parse_reason),
+            Ok(reply) => {
+                eprintln!("{}: unexpected reply {:?}", self.name(), reply);
+                None
+            }
+            Err(e) => {
+                eprintln!("{} failed: {:#}", self.name(), e);
+                None
+            }
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("plugin:{}", self.process.manifest.name)
+    }
+}
+
+/// What a detector is told of the task's process
+fn context_json(context: &TaskContext) -> serde_json::Value {
+    serde_json::json!({
+        "pid": context.pid,
+        "idle_secs": context.idle_duration.as_secs(),
+        "cpu_idle_secs": context.cpu_idle.as_secs(),
+        "output_idle_secs": context.output_idle.map(|d| d.as_secs()),
+        "last_cpu_time": context.last_cpu_time,
+    })
+}
+
+/// Detector compiled to WebAssembly, run in the daemon
+#[cfg(feature = "wasm")]
+pub struct WasmDetector {
+    name: String,
+    config: serde_json::Value,
+    script: Script,
+}
+
+#[cfg(feature = "wasm")]
+impl AttentionDetector for WasmDetector {
+    fn check(&self, task: &Task, context: &TaskContext) -> Option<AttentionReason> {
+        let input = serde_json::json!({
+            "config": self.config,
+            "task": task,
+            "context": context_json(context),
+        });
+        match self.script.text("detector_check", &input) {
+            Ok(reason) => reason.map(parse_reason),
+            Err(e) => {
+                eprintln!("{} failed: {:#}", self.name(), e);
+                None
+            }
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("plugin:{}", self.name)
+    }
+}
+
+/// Compile a WebAssembly plugin: its detector, unless it declares none
+#[cfg(feature = "wasm")]
+fn load_wasm(
+    manifest: PluginManifest,
+    config: serde_json::Value,
+    sandbox: &SandboxConfig,
+) -> Result<Option<Box<dyn AttentionDetector>>> {
+    anyhow::ensure!(
+        !manifest.has_capability("channel"),
+        "a WebAssembly plugin can't reach anything, so it can't be a channel"
+    );
+    let script = Script::load(&manifest.dir.join(&manifest.library), sandbox)?;
+    if !manifest.has_capability("detector") {
+        return Ok(None);
+    }
+    anyhow::ensure!(
+        script.exports("detector_check"),
+        "it declares detector but exports no detector_check"
+    );
+    Ok(Some(Box::new(WasmDetector {
+        name: manifest.name,
+        config,
+        script,
+    })))
+}
+
+#[cfg(not(feature = "wasm"))]
+fn load_wasm(
+    _manifest: PluginManifest,
+    _config: serde_json::Value,
+    _sandbox: &SandboxConfig,
+) -> Result<Option<Box<dyn AttentionDetector>>> {
+    anyhow::bail!("WebAssembly plugins need the wasm feature")
+}
+
+/// A detector's answer: a `{"kind", "detail"}` object names a registered
+/// kind, anything else is shown as is
+fn parse_reason(reason: String) -> AttentionReason {
+    #[derive(serde::Deserialize)]
+    struct Kind {
+        kind: String,
+        detail: Option<String>,
+    }
+    match serde_json::from_str::<Kind>(&reason) {
+        Ok(Kind { kind, detail }) => AttentionReason::Kind { kind, detail },
+        Err(_) => AttentionReason::Custom(reason),
+    }
+}
+
+/// Channel backed by a plugin's `agent_inbox_channel_send`
+pub struct PluginChannel {
+    process: Arc<PluginProcess>,
+}
+
+impl Channel for PluginChannel {
+    fn name(&self) -> &str {
+        &self.process.manifest.name
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let request = Request::Send {
+            notification: serde_json::json!({
+                "title": notification.title,
+                "body": notification.body,
+                "urgency": notification.urgency.as_str(),
+            }),
+        };
+        match self.process.call(&request)? {
+            Reply::Status(0) => Ok(()),
+            Reply::Status(status) => anyhow::bail!("Plugin channel returned status {}", status),
+            reply => anyhow::bail!("Unexpected reply {:?}", reply),
+        }
+    }
+}
+
+/// Detectors and channels contributed by all loadable plugins
+#[derive(Default)]
+pub struct LoadedPlugins {
+    pub detectors: Vec<Box<dyn AttentionDetector>>,
+    pub channels: Vec<Box<dyn Channel>>,
+}
+
+/// Start every plugin in `dir`, configured from `[plugins.<name>]` tables
+/// and confined or limited per `[sandbox]`. Broken plugins are reported
+/// and skipped rather than failing the caller.
+pub fn load_all<P: AsRef<Path>>(
+    dir: P,
+    configs: &HashMap<String, serde_json::Value>,
+    sandbox: &SandboxConfig,
+) -> Result<LoadedPlugins> {
+    let mut loaded = LoadedPlugins::default();
+
+    for manifest in discover(dir)? {
+        let config = match manifest.resolve_config(configs.get(&manifest.name)) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Skipping plugin {}: {:#}", manifest.name, e);
+                continue;
+            }
+        };
+        if manifest
+            .library
+            .extension()
+            .is_some_and(|ext| ext == "wasm")
+        {
+            let name = manifest.name.clone();
+            match load_wasm(manifest, config, sandbox) {
+                Ok(detector) => loaded.detectors.extend(detector),
+                Err(e) => eprintln!("Skipping plugin {}: {:#}", name, e),
+            }
+            continue;
+        }
+        let process = PluginProcess {
+            manifest,
+            config,
+            sandbox: Sandbox::new(sandbox.clone()),
+            host: Mutex::new(None),
+        };
+        let (detector, channel) = match process.start() {
+            Ok(capabilities) => capabilities,
+            Err(e) => {
+                eprintln!("Skipping plugin {}: {:#}", process.manifest.name, e);
+                continue;
+            }
+        };
+        let process = Arc::new(process);
+        if detector {
+            loaded.detectors.push(Box::new(PluginDetector {
+                process: process.clone(),
+            }));
+        }
+        if channel {
+            loaded.channels.push(Box::new(PluginChannel { process }));
+        }
+    }
+
+    Ok(loaded)
+}
+
+/// Serve the plugin in `dir` over stdin and stdout, one request per line;
+/// the body of `agent-inbox plugin-host`
+pub fn host(dir: &Path) -> Result<()> {
+    let manifest = read_manifest(dir)
+        .with_context(|| format!("Invalid plugin manifest in {}", dir.display()))?;
+    let mut replies = replies()?;
+    let mut reply = |reply: Reply| -> Result<()> {
+        writeln!(replies, "{}", serde_json::to_string(&reply)?)?;
+        Ok(replies.flush()?)
+    };
+    let mut requests = std::io::stdin().lock().lines();
+    let mut next = || -> Result<Option<Request>> {
+        requests
+            .next()
+            .transpose()?
+            .map(|line| Ok(serde_json::from_str(&line)?))
+            .transpose()
+    };
+
+    let Some(Request::Init { config }) = next()? else {
+        anyhow::bail!("Expected init first");
+    };
+    let instance = match PluginInstance::load(&manifest, &config) {
+        Ok(instance) => instance,
+        Err(e) => return reply(Reply::Error(format!("{:#}", e))),
+    };
+    // SAFETY: the symbols have the types the ABI above gives them
+    let (check, send) = unsafe {
+        (
+            instance.function::<DetectorCheckFn>(
+                &manifest,
+                "detector",
+                b"agent_inbox_detector_check\0",
+            ),
+            instance.function::<ChannelSendFn>(&manifest, "channel", b"agent_inbox_channel_send\0"),
+        )
+    };
+    reply(Reply::Ready {
+        detector: check.is_some(),
+        channel: send.is_some(),
+    })?;
+    while let Some(request) = next()? {
+        reply(match (request, check, send) {
+            (Request::Check { task, context }, Some(check), _) => {
+                Reply::Reason(instance.check(check, &task, &context)?)
+            }
+            (Request::Send { notification }, _, Some(send)) => {
+                Reply::Status(instance.send(send, &notification)?)
+            }
+            (request, _, _) => Reply::Error(format!("Can't serve {:?}", request)),
+        })?;
+    }
+    Ok(())
+}
+
+/// Where replies go: stdout, which from here on only they use. What the
+/// plugin prints goes to stderr instead.
+fn replies() -> Result<Box<dyn Write>> {
+    #[cfg(unix)]
+    {
+        use std::os::fd::FromRawFd;
+        // SAFETY: the new descriptor is owned by the File alone
+        unsafe {
+            let out = libc::dup(1);
+            if out < 0 || libc::dup2(2, 1) < 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+            Ok(Box::new(std::fs::File::from_raw_fd(out)))
+        }
+    }
+    #[cfg(not(unix))]
+    Ok(Box::new(std::io::stdout()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_manifest(root: &Path, name: &str, body: &str) {
+        let dir = root.join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("plugin.toml"), body).unwrap();
+    }
+
+    const MANIFEST: &str = r#"
+name = "slow-tests"
+version = "0.1.0"
+library = "libslow_tests.so"
+capabilities = ["detector"]
+
+[config_schema.threshold]
+type = "integer"
+default = 30
+"#;
+
+    #[test]
+    fn test_discover_manifests() {
+        let root = TempDir::new().unwrap();
+        write_manifest(root.path(), "slow-tests", MANIFEST);
+        std::fs::create_dir_all(root.path().join("not-a-plugin")).unwrap();
+        write_manifest(root.path(), "broken", "name = ");
+
+        let manifests = discover(root.path()).unwrap();
+        assert_eq!(manifests.len(), 1);
+        assert_eq!(manifests[0].name, "slow-tests");
+        assert!(manifests[0].has_capability("detector"));
+        assert!(!manifests[0].has_capability("channel"));
+        assert_eq!(manifests[0].dir, root.path().join("slow-tests"));
+
+        assert!(discover(root.path().join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_config_against_schema() {
+        let manifest: PluginManifest = toml::from_str(MANIFEST).unwrap();
+
+        let defaults = manifest.resolve_config(None).unwrap();
+        assert_eq!(defaults["threshold"], 30);
+
+        let user = serde_json::json!({"threshold": 90});
+        assert_eq!(
+            manifest.resolve_config(Some(&user)).unwrap()["threshold"],
+            90
+        );
+
+        let wrong_type = serde_json::json!({"threshold": "high"});
+        assert!(manifest.resolve_config(Some(&wrong_type)).is_err());
+
+        let unknown = serde_json::json!({"colour": "red"});
+        assert!(manifest.resolve_config(Some(&unknown)).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_library_others_can_write_is_refused() {
+        use std::os::unix::fs::PermissionsExt;
+        let root = TempDir::new().unwrap();
+        write_manifest(root.path(), "slow-tests", MANIFEST);
+        let library = root.path().join("slow-tests/libslow_tests.so");
+        std::fs::write(&library, b"not a library").unwrap();
+        std::fs::set_permissions(&library, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+        let manifest = discover(root.path()).unwrap().remove(0);
+        let refused = PluginInstance::load(&manifest, &serde_json::json!({}))
+            .err()
+            .unwrap();
+        assert!(
+            refused
+                .to_string()
+                .contains("is writable by others (mode 666)"),
+            "{}",
+            refused
+        );
+    }
+
+    #[test]
+    fn test_missing_library_is_skipped() {
+        let root = TempDir::new().unwrap();
+        write_manifest(root.path(), "slow-tests", MANIFEST);
+
+        let loaded = load_all(root.path(), &HashMap::new(), &SandboxConfig::default()).unwrap();
+        assert!(loaded.detectors.is_empty());
+        assert!(loaded.channels.is_empty());
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_wasm_detector() {
+        use crate::monitor::detectors::TaskContext;
+        use std::time::SystemTime;
+
+        let root = TempDir::new().unwrap();
+        let manifest = |name: &str, capabilities: &str| {
+            write_manifest(
+                root.path(),
+                name,
+                &format!(
+                    r#"
+name = "{name}"
+version = "0.1.0"
+library = "detector.wasm"
+capabilities = {capabilities}
+
+[config_schema.threshold]
+type = "integer"
+default = 30
+"#
+                ),
+            );
+            // Gives its input back as the reason
+            let wasm = wat::parse_str(
+                r#"(module
+                    (memory (export "memory") 1)
+                    (func (export "alloc") (param i32) (result i32) i32.const 1024)
+                    (func (export "detector_check") (param i32 i32) (result i64)
+                        (i64.or (i64.shl (i64.extend_i32_u (local.get 0)) (i64.const 32))
+                                (i64.extend_i32_u (local.get 1))))
+                )"#,
+            )
+            .unwrap();
+            std::fs::write(root.path().join(name).join("detector.wasm"), wasm).unwrap();
+        };
+        manifest("echo", r#"["detector"]"#);
+        manifest("courier", r#"["detector", "channel"]"#);
+
+        let loaded = load_all(root.path(), &HashMap::new(), &SandboxConfig::default()).unwrap();
+        // A channel couldn't deliver anything, so that plugin is skipped
+        assert_eq!(loaded.detectors.len(), 1);
+        assert!(loaded.channels.is_empty());
+
+        let detector = &loaded.detectors[0];
+        assert_eq!(detector.name(), "plugin:echo");
+        let task = Task::new("t1".into(), "shell".into(), "Build".into(), None, None);
+        let Some(AttentionReason::Custom(input)) =
+            detector.check(&task, &TaskContext::new(4242, SystemTime::now()))
+        else {
+            panic!("no reason");
+        };
+        let input: serde_json::Value = serde_json::from_str(&input).unwrap();
+        assert_eq!(input["config"]["threshold"], 30);
+        assert_eq!(input["task"]["title"], "Build");
+        assert_eq!(input["context"]["pid"], 4242);
+    }
+
+    #[test]
+    fn test_detector_reasons() {
+        assert_eq!(
+            parse_reason(r#"{"kind": "merge_conflict", "detail": "src/lib.rs"}"#.to_string()),
+            AttentionReason::kind("merge_conflict", Some("src/lib.rs"))
+        );
+        assert_eq!(
+            parse_reason("Tests slower than 30m".to_string()),
+            AttentionReason::Custom("Tests slower than 30m".to_string())
+        );
+    }
+}
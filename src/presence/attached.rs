@@ -0,0 +1,77 @@
+//! Tasks the user follows through `agent-inbox attach` or `tail -f`
+//!
+//! Unlike the heuristics in the parent module, this is certain: while a
+//! viewer runs, the task's prompts are on the user's screen. Each viewer
+//! records its pid in the task database for as long as it runs; viewers
+//! killed before they could remove themselves are recognized by their dead
+//! pid and dropped.
+
+use crate::db::Database;
+use crate::monitor::probe::{ProcProbe, ProcessProbe};
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// This process following a task; removed again when dropped
+pub struct Attachment {
+    db_path: PathBuf,
+    task_id: String,
+    pid: i32,
+}
+
+impl Attachment {
+    /// `kind` is "attach" or "tail"
+    pub fn start(db_path: PathBuf, task_id: &str, kind: &str) -> Result<Self> {
+        let pid = std::process::id() as i32;
+        Database::open(&db_path)?.add_viewer(task_id, pid, kind)?;
+        Ok(Self {
+            db_path,
+            task_id: task_id.to_string(),
+            pid,
+        })
+    }
+}
+
+impl Drop for Attachment {
+    fn drop(&mut self) {
+        if let Err(e) =
+            Database::open(&self.db_path).and_then(|db| db.remove_viewer(&self.task_id, self.pid))
+        {
+            eprintln!("Failed to detach from {}: {:#}", self.task_id, e);
+        }
+    }
+}
+
+/// Whether a live viewer follows `task_id`
+pub fn is_attached(db: &Database, task_id: &str) -> Result<bool> {
+    let mut attached = false;
+    for (pid, _) in db.viewers(task_id)? {
+        if ProcProbe.is_alive(pid) {
+            attached = true;
+        } else {
+            db.remove_viewer(task_id, pid)?;
+        }
+    }
+    Ok(attached)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attach_and_detach() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("tasks.db");
+        let db = Database::open(&db_path).unwrap();
+        // A viewer that was killed
+        db.add_viewer("t1", 999999, "tail").unwrap();
+        assert!(!is_attached(&db, "t1").unwrap());
+        assert!(db.viewers("t1").unwrap().is_empty());
+
+        let attachment = Attachment::start(db_path.clone(), "t1", "attach").unwrap();
+        assert!(is_attached(&db, "t1").unwrap());
+        assert!(!is_attached(&db, "t2").unwrap());
+        drop(attachment);
+        assert!(!is_attached(&db, "t1").unwrap());
+    }
+}
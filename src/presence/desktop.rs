@@ -0,0 +1,337 @@
+//! Is the user at the machine at all?
+//!
+//! Where [`Presence`](super::Presence) asks about one task's terminal, a
+//! [`PresenceProvider`] reports on the whole desktop session: locked or not,
+//! and how long since the last input. Notification routing uses it to pick
+//! between local and phone channels. On headless machines, attached tmux
+//! and Zellij clients stand in for the session; see [`multiplexer`].
+
+use super::multiplexer::{self, MultiplexerProvider};
+use crate::config::RoutingConfig;
+use anyhow::Result;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Snapshot of the desktop session
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DesktopState {
+    pub locked: bool,
+    /// Time since the last keyboard or mouse input
+    pub idle: Duration,
+    /// A Focus / Do Not Disturb mode is on
+    pub focus: bool,
+}
+
+impl DesktopState {
+    pub fn active() -> Self {
+        Self {
+            locked: false,
+            idle: Duration::ZERO,
+            focus: false,
+        }
+    }
+
+    /// Locked, or idle for at least `after`
+    pub fn is_away(&self, after: Duration) -> bool {
+        self.locked || self.idle >= after
+    }
+}
+
+/// A source of desktop session state. Returns None when it cannot tell,
+/// e.g. the tool it relies on is missing.
+pub trait PresenceProvider: Send + Sync {
+    fn name(&self) -> &str;
+    fn state(&self) -> Option<DesktopState>;
+}
+
+/// Build the provider named by `[notify.routing] provider`
+pub fn provider_from_config(config: &RoutingConfig) -> Result<Box<dyn PresenceProvider>> {
+    Ok(match config.provider.as_str() {
+        "auto" => Box::new(AutoProvider::new()),
+        "hook" => Box::new(HookProvider::new(default_hook_path())),
+        "logind" => Box::new(LogindProvider),
+        "macos" => Box::new(MacProvider),
+        "multiplexer" => Box::new(MultiplexerProvider),
+        "xprintidle" => Box::new(XprintidleProvider),
+        other => anyhow::bail!("Unknown presence provider: {}", other),
+    })
+}
+
+/// Tries the hook file, logind, macOS and xprintidle in that order. Without
+/// a graphical session, tmux and Zellij clients come right after the hook
+/// file: an SSH login's logind session never locks or goes idle.
+pub struct AutoProvider {
+    providers: Vec<Box<dyn PresenceProvider>>,
+}
+
+impl AutoProvider {
+    pub fn new() -> Self {
+        let mut providers: Vec<Box<dyn PresenceProvider>> =
+            vec![Box::new(HookProvider::new(default_hook_path()))];
+        if multiplexer::headless() {
+            providers.push(Box::new(MultiplexerProvider));
+        }
+        providers.push(Box::new(LogindProvider));
+        providers.push(Box::new(MacProvider));
+        providers.push(Box::new(XprintidleProvider));
+        Self { providers }
+    }
+}
+
+impl Default for AutoProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PresenceProvider for AutoProvider {
+    fn name(&self) -> &str {
+        "auto"
+    }
+
+    fn state(&self) -> Option<DesktopState> {
+        self.providers.iter().find_map(|p| p.state())
+    }
+}
+
+/// State written by `agent-inbox presence away|active`, typically from
+/// swayidle or another idle daemon's hooks
+pub struct HookProvider {
+    path: PathBuf,
+}
+
+impl HookProvider {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl PresenceProvider for HookProvider {
+    fn name(&self) -> &str {
+        "hook"
+    }
+
+    fn state(&self) -> Option<DesktopState> {
+        let content = std::fs::read_to_string(&self.path).ok()?;
+        match content.trim() {
+            // The idle daemon already applied its own timeout
+            "away" => Some(DesktopState {
+                locked: true,
+                ..DesktopState::active()
+            }),
+            "active" => Some(DesktopState::active()),
+            _ => None,
+        }
+    }
+}
+
+/// Where `agent-inbox presence` records the hook state
+pub fn default_hook_path() -> PathBuf {
+    crate::storage::state_dir().join("presence")
+}
+
+/// Record the hook state read by [`HookProvider`]
+pub fn write_hook_state(away: bool) -> Result<()> {
+    let path = default_hook_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, if away { "away\n" } else { "active\n" })?;
+    Ok(())
+}
+
+/// systemd-logind's LockedHint and IdleHint for the current session
+pub struct LogindProvider;
+
+impl PresenceProvider for LogindProvider {
+    fn name(&self) -> &str {
+        "logind"
+    }
+
+    fn state(&self) -> Option<DesktopState> {
+        let session = match std::env::var("XDG_SESSION_ID") {
+            Ok(id) => id,
+            // Services and cron jobs run outside the graphical session
+            Err(_) => {
+                let output = Command::new("loginctl")
+                    .args(["show-user", "--property=Display", "--value"])
+                    .output()
+                    .ok()?;
+                String::from_utf8(output.stdout).ok()?.trim().to_string()
+            }
+        };
+        if session.is_empty() {
+            return None;
+        }
+
+        let output = Command::new("loginctl")
+            .args([
+                "show-session",
+                &session,
+                "--property=LockedHint",
+                "--property=IdleHint",
+                "--property=IdleSinceHint",
+            ])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_loginctl(&String::from_utf8_lossy(&output.stdout), SystemTime::now())
+    }
+}
+
+fn parse_loginctl(output: &str, now: SystemTime) -> Option<DesktopState> {
+    let mut locked = None;
+    let mut idle_hint = false;
+    let mut idle_since = 0u64;
+    for line in output.lines() {
+        match line.split_once('=') {
+            Some(("LockedHint", value)) => locked = Some(value == "yes"),
+            Some(("IdleHint", value)) => idle_hint = value == "yes",
+            Some(("IdleSinceHint", value)) => idle_since = value.parse().unwrap_or(0),
+            _ => {}
+        }
+    }
+
+    let idle = if !idle_hint {
+        Duration::ZERO
+    } else if idle_since == 0 {
+        // Idle, but the session does not say since when
+        Duration::MAX
+    } else {
+        let now_us = now.duration_since(UNIX_EPOCH).ok()?.as_micros() as u64;
+        Duration::from_micros(now_us.saturating_sub(idle_since))
+    };
+    Some(DesktopState {
+        locked: locked?,
+        idle,
+        focus: false,
+    })
+}
+
+/// macOS HID idle time, the value CGEventSourceSecondsSinceLastEventType
+/// reports, read through `ioreg`
+pub struct MacProvider;
+
+impl PresenceProvider for MacProvider {
+    fn name(&self) -> &str {
+        "macos"
+    }
+
+    fn state(&self) -> Option<DesktopState> {
+        if !cfg!(target_os = "macos") {
+            return None;
+        }
+        let output = Command::new("ioreg")
+            .args(["-c", "IOHIDSystem", "-d", "4"])
+            .output()
+            .ok()?;
+        let idle = parse_hid_idle(&String::from_utf8_lossy(&output.stdout))?;
+        let focus = std::env::var("HOME")
+            .ok()
+            .and_then(|home| {
+                std::fs::read_to_string(PathBuf::from(home).join(FOCUS_ASSERTIONS)).ok()
+            })
+            .is_some_and(|json| focus_active(&json));
+        // A locked screen receives no input, so the idle timer covers it
+        Some(DesktopState {
+            locked: false,
+            idle,
+            focus,
+        })
+    }
+}
+
+/// Where macOS records the Focus modes turned on by hand, relative to the
+/// home directory. Reading it needs Full Disk Access.
+const FOCUS_ASSERTIONS: &str = "Library/DoNotDisturb/DB/Assertions.json";
+
+/// Any Focus mode asserted in Assertions.json
+fn focus_active(json: &str) -> bool {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(json) else {
+        return false;
+    };
+    value["data"].as_array().is_some_and(|data| {
+        data.iter().any(|entry| {
+            entry["storeAssertionRecords"]
+                .as_array()
+                .is_some_and(|records| !records.is_empty())
+        })
+    })
+}
+
+fn parse_hid_idle(output: &str) -> Option<Duration> {
+    output.lines().find_map(|line| {
+        let (_, value) = line.split_once("\"HIDIdleTime\" = ")?;
+        value.trim().parse().ok().map(Duration::from_nanos)
+    })
+}
+
+/// X11 idle time from `xprintidle`
+pub struct XprintidleProvider;
+
+impl PresenceProvider for XprintidleProvider {
+    fn name(&self) -> &str {
+        "xprintidle"
+    }
+
+    fn state(&self) -> Option<DesktopState> {
+        super::desktop_idle().map(|idle| DesktopState {
+            idle,
+            ..DesktopState::active()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_loginctl() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let active = "LockedHint=no\nIdleHint=no\nIdleSinceHint=0\n";
+        assert_eq!(parse_loginctl(active, now), Some(DesktopState::active()));
+
+        let idle = "IdleHint=yes\nIdleSinceHint=400000000\nLockedHint=no\n";
+        let state = parse_loginctl(idle, now).unwrap();
+        assert_eq!(state.idle, Duration::from_secs(600));
+        assert!(state.is_away(Duration::from_secs(300)));
+
+        let locked = parse_loginctl("LockedHint=yes\nIdleHint=no\n", now).unwrap();
+        assert!(locked.is_away(Duration::from_secs(300)));
+        assert!(parse_loginctl("", now).is_none());
+    }
+
+    #[test]
+    fn test_parse_hid_idle() {
+        let output = "  | |   \"HIDIdleTime\" = 2500000000\n  | |   \"HIDKind\" = 4\n";
+        assert_eq!(parse_hid_idle(output), Some(Duration::from_millis(2500)));
+        assert!(parse_hid_idle("nothing here").is_none());
+    }
+
+    #[test]
+    fn test_focus_active() {
+        let on = r#"{"data":[{"storeAssertionRecords":[{"assertionDetails":
+            {"assertionDetailsModeIdentifier":"com.apple.donotdisturb.mode.default"}}]}]}"#;
+        assert!(focus_active(on));
+        assert!(!focus_active(r#"{"data":[{"storeAssertionRecords":[]}]}"#));
+        assert!(!focus_active(r#"{"data":[{}]}"#));
+        assert!(!focus_active("not json"));
+    }
+
+    #[test]
+    fn test_hook_provider() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("presence");
+        let provider = HookProvider::new(path.clone());
+        assert!(provider.state().is_none());
+
+        std::fs::write(&path, "away\n").unwrap();
+        assert!(provider.state().unwrap().is_away(Duration::from_secs(300)));
+        std::fs::write(&path, "active\n").unwrap();
+        assert_eq!(provider.state(), Some(DesktopState::active()));
+    }
+}
@@ -0,0 +1,263 @@
+//! Is the user already looking at a task?
+//!
+//! Notifications about a task are pointless when the user is typing in the
+//! terminal that runs it. This module finds the task's terminal and checks:
+//!
+//! - when the terminal last received input (the tty's access time, as `w`
+//!   reports it)
+//! - inside tmux, whether the task's pane is the focused pane of an attached
+//!   session
+//! - on X11 with `xprintidle` installed, how long the desktop has been idle
+//!
+//! The [`desktop`] submodule answers the coarser question of whether the user
+//! is at the machine at all, for routing notifications to the phone, with
+//! [`multiplexer`] answering it from tmux and Zellij clients on servers,
+//! [`attached`] tracks tasks followed with `agent-inbox attach` or `tail -f`,
+//! and [`zellij`] finds and focuses tasks running in Zellij. [`focus`]
+//! brings a task's terminal to the front, with the task's own focus command
+//! when it has one.
+
+use crate::config::PresenceConfig;
+use crate::models::Task;
+use anyhow::{Context, Result};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub mod attached;
+pub mod desktop;
+pub mod multiplexer;
+pub mod zellij;
+
+pub use desktop::{DesktopState, PresenceProvider};
+
+/// Decides whether the user is watching the terminal of a process
+pub trait Presence: Send + Sync {
+    fn watching(&self, pid: i32) -> bool;
+}
+
+/// Presence detection from the tty, tmux and the X11 idle timer
+pub struct SystemPresence {
+    /// Input within this window counts as "at the terminal"
+    threshold: Duration,
+}
+
+impl SystemPresence {
+    pub fn new(threshold: Duration) -> Self {
+        Self { threshold }
+    }
+
+    pub fn from_config(config: &PresenceConfig) -> Option<Self> {
+        config
+            .suppress_when_watching
+            .then(|| Self::new(Duration::from_secs(config.watching_secs)))
+    }
+}
+
+impl Presence for SystemPresence {
+    fn watching(&self, pid: i32) -> bool {
+        let Some(tty) = terminal_of(pid) else {
+            return false;
+        };
+        let Some(idle) = tty_idle(&tty) else {
+            return false;
+        };
+
+        idle < self.threshold
+            && tmux_pane_focused(&tty) != Some(false)
+            && desktop_idle().is_none_or(|d| d < self.threshold)
+    }
+}
+
+/// The terminal device a process reads from or writes to, if any
+#[cfg(not(any(target_os = "freebsd", target_os = "openbsd")))]
+pub fn terminal_of(pid: i32) -> Option<PathBuf> {
+    (0..3)
+        .filter_map(|fd| fs::read_link(format!("/proc/{}/fd/{}", pid, fd)).ok())
+        .find(|path| is_terminal_device(path))
+}
+
+/// The process's controlling terminal
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub fn terminal_of(pid: i32) -> Option<PathBuf> {
+    crate::monitor::kinfo::process(pid)?.tty
+}
+
+#[cfg(not(any(target_os = "freebsd", target_os = "openbsd")))]
+fn is_terminal_device(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    path.starts_with("/dev/pts/") || path.starts_with("/dev/tty")
+}
+
+/// Time since the terminal last received input
+pub fn tty_idle(tty: &Path) -> Option<Duration> {
+    let atime = fs::metadata(tty).ok()?.atime();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    Some(Duration::from_secs(now.saturating_sub(atime).max(0) as u64))
+}
+
+/// Whether the tmux pane on `tty` is the active pane of the active window of
+/// an attached session. None when the tty is not a tmux pane.
+pub fn tmux_pane_focused(tty: &Path) -> Option<bool> {
+    let output = Command::new("tmux")
+        .args([
+            "list-panes",
+            "-a",
+            "-F",
+            "#{pane_tty} #{pane_active} #{window_active} #{session_attached}",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    parse_tmux_panes(&String::from_utf8_lossy(&output.stdout), tty)
+}
+
+fn parse_tmux_panes(listing: &str, tty: &Path) -> Option<bool> {
+    let tty = tty.to_string_lossy();
+    listing.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        if fields.next()? != tty {
+            return None;
+        }
+        let flags: Vec<&str> = fields.collect();
+        Some(flags.len() == 3 && flags[0] == "1" && flags[1] == "1" && flags[2] != "0")
+    })
+}
+
+/// Bring the task's terminal to the front: with its focus command when it
+/// has one, otherwise by selecting its tmux pane or Zellij tab
+pub fn focus(task: &Task) -> Result<()> {
+    if let Some(command) = task.focus_command() {
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("AGENT_TASK_ID", &task.task_id)
+            .env(
+                "AGENT_TASK_PID",
+                task.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+            )
+            .status()
+            .with_context(|| format!("Failed to run '{}'", command))?;
+        anyhow::ensure!(status.success(), "'{}' exited with {}", command, status);
+        return Ok(());
+    }
+    if let Some(pane) = task.tmux_pane() {
+        let tmux = |args: &[&str]| -> Result<()> {
+            let status = Command::new("tmux")
+                .args(args)
+                .status()
+                .context("Failed to run tmux")?;
+            anyhow::ensure!(status.success(), "tmux could not {} {}", args[0], pane);
+            Ok(())
+        };
+        tmux(&["select-window", "-t", pane, ";", "select-pane", "-t", pane])?;
+        if std::env::var_os("TMUX").is_some() {
+            tmux(&["switch-client", "-t", pane])?;
+        }
+        return Ok(());
+    }
+    match task.zellij_pane() {
+        Some(pane) => zellij::focus(&pane),
+        None => anyhow::bail!(
+            "{} has no focus command, tmux pane or Zellij tab to focus",
+            task.task_id
+        ),
+    }
+}
+
+/// A focus command for the terminal pane a task is registered from, found
+/// in its environment: WezTerm's pane, or kitty's window when kitty allows
+/// remote control. Looked up through `env`.
+pub fn focus_command_from_env(env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let numeric =
+        |var: &str| env(var).filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()));
+    if let Some(pane) = numeric("WEZTERM_PANE") {
+        return Some(format!("wezterm cli activate-pane --pane-id {}", pane));
+    }
+    let window = numeric("KITTY_WINDOW_ID")?;
+    let socket =
+        env("KITTY_LISTEN_ON").filter(|socket| !socket.is_empty() && !socket.contains('\''))?;
+    Some(format!(
+        "kitty @ --to '{}' focus-window --match id:{}",
+        socket, window
+    ))
+}
+
+/// Desktop idle time on X11 (needs `xprintidle`); None elsewhere
+pub fn desktop_idle() -> Option<Duration> {
+    std::env::var_os("DISPLAY")?;
+    let output = Command::new("xprintidle").output().ok()?;
+    let millis: u64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_millis(millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tmux_panes() {
+        let listing = "/dev/pts/3 1 1 1\n/dev/pts/4 0 1 1\n/dev/pts/5 1 1 0\n";
+        assert_eq!(
+            parse_tmux_panes(listing, Path::new("/dev/pts/3")),
+            Some(true)
+        );
+        assert_eq!(
+            parse_tmux_panes(listing, Path::new("/dev/pts/4")),
+            Some(false)
+        );
+        // Detached session
+        assert_eq!(
+            parse_tmux_panes(listing, Path::new("/dev/pts/5")),
+            Some(false)
+        );
+        assert_eq!(parse_tmux_panes(listing, Path::new("/dev/pts/9")), None);
+    }
+
+    #[test]
+    fn test_process_without_terminal() {
+        assert!(terminal_of(999999).is_none());
+        assert!(!SystemPresence::new(Duration::from_secs(60)).watching(999999));
+    }
+
+    #[test]
+    fn test_focus_command_from_env() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |var: &str| {
+                vars.iter()
+                    .find(|(name, _)| *name == var)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert_eq!(
+            focus_command_from_env(env(&[("WEZTERM_PANE", "7"), ("KITTY_WINDOW_ID", "2")]))
+                .as_deref(),
+            Some("wezterm cli activate-pane --pane-id 7")
+        );
+        assert_eq!(
+            focus_command_from_env(env(&[
+                ("KITTY_WINDOW_ID", "2"),
+                ("KITTY_LISTEN_ON", "unix:/tmp/kitty")
+            ]))
+            .as_deref(),
+            Some("kitty @ --to 'unix:/tmp/kitty' focus-window --match id:2")
+        );
+        // Without remote control, kitty can't be asked from outside
+        assert_eq!(
+            focus_command_from_env(env(&[("KITTY_WINDOW_ID", "2")])),
+            None
+        );
+        assert_eq!(
+            focus_command_from_env(env(&[("WEZTERM_PANE", "7; rm -rf ~")])),
+            None
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Presence from terminal multiplexers
+//!
+//! On a server reached over SSH there is no desktop to lock or go idle, but
+//! the user's tmux or Zellij client detaches when they walk away (or their
+//! laptop sleeps and the connection drops). [`MultiplexerProvider`] counts
+//! the user as present while any client is attached to a tmux server or a
+//! Zellij session, and as away once the last one detaches. tmux also
+//! reports each client's last keypress, which stands in for the idle timer.
+
+use super::desktop::{DesktopState, PresenceProvider};
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Attached tmux clients and Zellij sessions. Says nothing when neither
+/// multiplexer is running.
+pub struct MultiplexerProvider;
+
+impl PresenceProvider for MultiplexerProvider {
+    fn name(&self) -> &str {
+        "multiplexer"
+    }
+
+    fn state(&self) -> Option<DesktopState> {
+        combine(tmux_idle(), zellij_attached())
+    }
+}
+
+/// Whether `auto` should ask the multiplexers before the desktop providers:
+/// on Linux without a graphical session, the only presence there is
+pub fn headless() -> bool {
+    cfg!(target_os = "linux")
+        && std::env::var_os("DISPLAY").is_none()
+        && std::env::var_os("WAYLAND_DISPLAY").is_none()
+}
+
+/// `tmux`: None without a server, Some(None) with no client attached, else
+/// the idle time of the most recently used client
+fn combine(tmux: Option<Option<Duration>>, zellij: Option<bool>) -> Option<DesktopState> {
+    let idle = match (tmux, zellij) {
+        (None, None) => return None,
+        // Zellij doesn't report client activity
+        (_, Some(true)) => Duration::ZERO,
+        (Some(Some(idle)), _) => idle,
+        _ => {
+            return Some(DesktopState {
+                locked: true,
+                ..DesktopState::active()
+            })
+        }
+    };
+    Some(DesktopState {
+        idle,
+        ..DesktopState::active()
+    })
+}
+
+fn tmux_idle() -> Option<Option<Duration>> {
+    let output = Command::new("tmux")
+        .args(["list-clients", "-F", "#{client_activity}"])
+        .output()
+        .ok()?;
+    // Fails when no server runs
+    if !output.status.success() {
+        return None;
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(parse_tmux_clients(
+        &String::from_utf8_lossy(&output.stdout),
+        now,
+    ))
+}
+
+/// Idle time of the most recently active client in `list-clients` output
+/// (one activity timestamp per line); None without clients
+fn parse_tmux_clients(listing: &str, now: u64) -> Option<Duration> {
+    listing
+        .lines()
+        .filter_map(|line| line.trim().parse::<u64>().ok())
+        .max()
+        .map(|activity| Duration::from_secs(now.saturating_sub(activity)))
+}
+
+/// None when no Zellij session runs, else whether any has a client
+fn zellij_attached() -> Option<bool> {
+    let output = Command::new("zellij")
+        .args(["list-sessions", "--short", "--no-formatting"])
+        .output()
+        .ok()?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let sessions: Vec<&str> = listing
+        .lines()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    if !output.status.success() || sessions.is_empty() {
+        return None;
+    }
+    Some(sessions.iter().any(|session| {
+        Command::new("zellij")
+            .args(["--session", session, "action", "list-clients"])
+            .output()
+            .is_ok_and(|output| {
+                output.status.success()
+                    && client_count(&String::from_utf8_lossy(&output.stdout)) > 0
+            })
+    }))
+}
+
+/// Clients in `zellij action list-clients` output, below its header
+fn client_count(listing: &str) -> usize {
+    listing
+        .lines()
+        .skip_while(|line| !line.starts_with("CLIENT_ID"))
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multiplexer_state() {
+        assert_eq!(
+            parse_tmux_clients("1000\n1090\n", 1100),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(parse_tmux_clients("", 1100), None);
+        let listing = "CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND\n1         terminal_0     claude\n";
+        assert_eq!(client_count(listing), 1);
+        assert_eq!(
+            client_count("CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND\n"),
+            0
+        );
+
+        let away = Duration::from_secs(300);
+        assert_eq!(combine(None, None), None);
+        assert!(combine(Some(None), None).unwrap().is_away(away));
+        assert!(combine(Some(None), Some(false)).unwrap().is_away(away));
+        assert_eq!(
+            combine(Some(None), Some(true)),
+            Some(DesktopState::active())
+        );
+        let state = combine(Some(Some(Duration::from_secs(600))), None).unwrap();
+        assert!(state.is_away(away) && !state.locked);
+        assert!(!combine(Some(Some(Duration::from_secs(5))), None)
+            .unwrap()
+            .is_away(away));
+    }
+}
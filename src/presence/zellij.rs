@@ -0,0 +1,106 @@
+//! Zellij, alongside tmux
+//!
+//! A task started inside Zellij inherits `$ZELLIJ_SESSION_NAME` and
+//! `$ZELLIJ_PANE_ID`; [`from_env`] records them, together with the tab that
+//! was focused at the time (the one the command was typed in), so `focus`,
+//! `attach` and notification actions can bring the task back on screen
+//! with `zellij action go-to-tab-name` or `zellij attach`.
+
+use crate::models::ZellijPane;
+use anyhow::{Context, Result};
+use std::process::{Command, ExitStatus};
+
+/// The pane this process runs in, if it runs inside Zellij
+pub fn from_env() -> Option<ZellijPane> {
+    let session = std::env::var("ZELLIJ_SESSION_NAME")
+        .ok()
+        .filter(|s| !s.is_empty())?;
+    let pane_id = std::env::var("ZELLIJ_PANE_ID")
+        .ok()
+        .filter(|p| !p.is_empty())?;
+    let tab = zellij(&session, &["action", "dump-layout"])
+        .ok()
+        .and_then(|layout| focused_tab(&layout));
+    Some(ZellijPane {
+        session,
+        pane_id,
+        tab,
+    })
+}
+
+/// This process runs inside a Zellij session
+pub fn inside() -> bool {
+    std::env::var_os("ZELLIJ").is_some()
+}
+
+/// Switch the task's session to its tab, for every client attached to it
+pub fn focus(pane: &ZellijPane) -> Result<()> {
+    let tab = pane
+        .tab
+        .as_deref()
+        .with_context(|| format!("No tab known for Zellij pane {}", pane.pane_id))?;
+    zellij(&pane.session, &["action", "go-to-tab-name", tab])?;
+    Ok(())
+}
+
+/// Attach this terminal to the task's session, on its tab, until the user
+/// detaches
+pub fn attach(pane: &ZellijPane) -> Result<ExitStatus> {
+    // Best effort: the tab may have been renamed since
+    if let Err(e) = focus(pane) {
+        eprintln!("{:#}", e);
+    }
+    Command::new("zellij")
+        .args(["attach", &pane.session])
+        .status()
+        .context("Failed to run zellij")
+}
+
+fn zellij(session: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new("zellij")
+        .arg("--session")
+        .arg(session)
+        .args(args)
+        .output()
+        .context("Failed to run zellij")?;
+    anyhow::ensure!(output.status.success(), "zellij {} failed", args.join(" "));
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The name of the tab marked `focus=true` in `zellij action dump-layout`
+fn focused_tab(layout: &str) -> Option<String> {
+    layout
+        .lines()
+        .map(str::trim_start)
+        .filter(|line| line.starts_with("tab ") && line.contains("focus=true"))
+        .find_map(|line| {
+            let name = line.split_once("name=\"")?.1;
+            Some(name[..name.find('"')?].to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_focused_tab() {
+        let layout = r#"layout {
+    cwd "/home/me"
+    tab name="build" hide_floating_panes=true {
+        pane command="cargo"
+    }
+    tab name="agents" focus=true hide_floating_panes=true {
+        pane size=1 borderless=true {
+            plugin location="zellij:tab-bar"
+        }
+        pane focus=true
+    }
+}"#;
+        assert_eq!(focused_tab(layout).as_deref(), Some("agents"));
+        assert_eq!(
+            focused_tab("layout {\n    tab name=\"x\" {\n    }\n}"),
+            None
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! Landlock, the Linux security module through which a process gives up
+//! filesystem rights (5.13 and later) and TCP (6.7 and later)
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+const CREATE_RULESET_VERSION: u32 = 1 << 0;
+const RULE_PATH_BENEATH: libc::c_int = 1;
+
+const EXECUTE: u64 = 1 << 0;
+const WRITE_FILE: u64 = 1 << 1;
+const READ_FILE: u64 = 1 << 2;
+const READ_DIR: u64 = 1 << 3;
+/// Every right of ABI 1: those above, and making and removing entries
+const ABI_1: u64 = (1 << 13) - 1;
+/// Linking and renaming across directories (ABI 2)
+const REFER: u64 = 1 << 13;
+/// Truncating (ABI 3)
+const TRUNCATE: u64 = 1 << 14;
+const NET_BIND_TCP: u64 = 1 << 0;
+const NET_CONNECT_TCP: u64 = 1 << 1;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+    /// Read by the kernel from ABI 4 on
+    handled_access_net: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+/// The kernel's Landlock ABI version; None without Landlock
+pub fn abi() -> Option<i64> {
+    // SAFETY: asks for the version only; the null attribute isn't read
+    let version = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0usize,
+            CREATE_RULESET_VERSION,
+        )
+    };
+    (version > 0).then_some(version)
+}
+
+/// Whether the kernel can also take TCP away
+pub fn handles_network(abi: i64) -> bool {
+    abi >= 4
+}
+
+/// Rules prepared in the parent, so that the child only makes syscalls
+/// between fork and exec
+pub struct Ruleset {
+    attr: RulesetAttr,
+    size: usize,
+    rules: Vec<(u64, OwnedFd)>,
+}
+
+impl Ruleset {
+    /// Reading and running under `read`, writing `write`, nothing else;
+    /// and without `network`, no TCP where the kernel can take it away.
+    /// Paths that don't exist are left out.
+    pub fn new(abi: i64, read: &[&Path], write: &[&Path], network: bool) -> Self {
+        let mut handled = ABI_1;
+        if abi >= 2 {
+            handled |= REFER;
+        }
+        if abi >= 3 {
+            handled |= TRUNCATE;
+        }
+        let net = handles_network(abi) && !network;
+        let reading = read
+            .iter()
+            .map(|path| (*path, READ_FILE | READ_DIR | EXECUTE));
+        let writing = write
+            .iter()
+            .map(|path| (*path, READ_FILE | WRITE_FILE | TRUNCATE));
+        let rules = reading
+            .chain(writing)
+            .filter_map(|(path, access)| {
+                let file = open_path(path).ok()?;
+                let is_dir = file.metadata().is_ok_and(|m| m.is_dir());
+                // Directory rights only go on directories
+                let access = if is_dir { access } else { access & !READ_DIR };
+                Some((access & handled, file.into()))
+            })
+            .collect();
+        Self {
+            attr: RulesetAttr {
+                handled_access_fs: handled,
+                handled_access_net: if net {
+                    NET_BIND_TCP | NET_CONNECT_TCP
+                } else {
+                    0
+                },
+            },
+            size: if handles_network(abi) {
+                std::mem::size_of::<RulesetAttr>()
+            } else {
+                std::mem::size_of::<u64>()
+            },
+            rules,
+        }
+    }
+
+    /// Whether TCP is taken away too
+    pub fn denies_network(&self) -> bool {
+        self.attr.handled_access_net != 0
+    }
+
+    /// Restrict the calling process, for good
+    pub fn restrict_self(&self) -> io::Result<()> {
+        // SAFETY: the attributes outlive the calls that read them, and the
+        // ruleset descriptor is closed on every path
+        unsafe {
+            let fd = libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &self.attr as *const RulesetAttr,
+                self.size,
+                0u32,
+            );
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let fd = fd as libc::c_int;
+            let result = self.enforce(fd);
+            libc::close(fd);
+            result
+        }
+    }
+
+    unsafe fn enforce(&self, ruleset: libc::c_int) -> io::Result<()> {
+        for (access, path) in &self.rules {
+            let rule = PathBeneathAttr {
+                allowed_access: *access,
+                parent_fd: path.as_raw_fd(),
+            };
+            let added = libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset,
+                RULE_PATH_BENEATH,
+                &rule as *const PathBeneathAttr,
+                0u32,
+            );
+            if added != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0
+            || libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0u32) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// A handle on `path` for naming it in rules, not for reading it
+fn open_path(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_PATH)
+        .open(path)
+}
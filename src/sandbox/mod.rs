@@ -0,0 +1,377 @@
+//! Confinement for code the user installs: `[sandbox]`
+//!
+//! `[[rules]]` `run` commands and plugins come from the config and plugin
+//! directories, so a mistake or a planted file there runs with the user's
+//! rights. They run as processes of their own instead, each with:
+//!
+//! - resource limits: `cpu_secs` of CPU, `memory_mb` of address space,
+//!   256 open files, no core dumps and no file growing past empty
+//! - no network: a user and network namespace of its own, with nothing
+//!   but a loopback interface that is down, or where namespaces are
+//!   switched off, no TCP through Landlock (Linux 6.7)
+//! - no filesystem beyond the system: through Landlock (Linux 5.13) it
+//!   may read and run what is under the system directories and the
+//!   `read` paths, and write nothing but `/dev/null`
+//!
+//! `[[hooks]]` commands are the user's own and left out unless `hooks` is
+//! set. What the system lacks is skipped, or with `strict`, nothing runs.
+//! `agent-inbox doctor` says which apply. A rule command that has to reach
+//! the network needs `network = true`; `enabled = false` lifts it all.
+//!
+//! Scripts compiled to WebAssembly need none of this: they run inside the
+//! daemon with no access to anything, within `fuel` and `memory_mb`. See
+//! [`wasm`].
+
+#[cfg(target_os = "linux")]
+mod landlock;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use crate::config::SandboxConfig;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// What confined processes may always read and run: the system's
+/// programs, libraries and settings
+const SYSTEM_READ: &[&str] = &[
+    "/usr",
+    "/bin",
+    "/sbin",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/etc",
+    "/opt",
+    "/nix",
+    "/dev/random",
+    "/dev/urandom",
+    "/dev/zero",
+];
+
+/// What they may write, which keeps nothing
+const SYSTEM_WRITE: &[&str] = &["/dev/null"];
+
+/// Open files a confined process may hold
+const MAX_FILES: u64 = 256;
+
+const MB: u64 = 1024 * 1024;
+
+/// How processes are confined, from `[sandbox]`
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    config: SandboxConfig,
+    /// Readable for this use only, such as a plugin's directory
+    read: Vec<PathBuf>,
+}
+
+impl Sandbox {
+    pub fn new(config: SandboxConfig) -> Self {
+        Self {
+            config,
+            read: Vec::new(),
+        }
+    }
+
+    /// Whether `[[hooks]]` commands are confined too
+    pub fn hooks(&self) -> bool {
+        self.config.enabled && self.config.hooks
+    }
+
+    /// Let the process read and run what is under `path` too
+    pub fn read(mut self, path: impl Into<PathBuf>) -> Self {
+        self.read.push(path.into());
+        self
+    }
+
+    /// Confine `command` when it is spawned. With `strict`, fails here
+    /// where the system has nothing to confine it with, and makes the spawn
+    /// fail where it lacks a part.
+    pub fn apply(&self, command: &mut Command) -> Result<()> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            let confine = self.prepare();
+            // SAFETY: the closure only makes syscalls on what was prepared
+            // before the fork; it allocates nothing
+            unsafe {
+                command.pre_exec(move || confine.enter());
+            }
+        }
+        #[cfg(not(unix))]
+        anyhow::ensure!(
+            !self.config.strict,
+            "[sandbox] strict is set, and this system has no sandbox"
+        );
+        Ok(())
+    }
+
+    /// What applies on this system, for `doctor`: whether all of it does,
+    /// and a line saying what
+    pub fn describe(&self) -> (bool, String) {
+        if !self.config.enabled {
+            return (
+                false,
+                "off; rule commands and plugins run with your rights".to_string(),
+            );
+        }
+        let mut applied = vec!["resource limits".to_string()];
+        let mut missing = Vec::new();
+        #[cfg(target_os = "linux")]
+        {
+            let abi = landlock::abi();
+            if !self.config.network {
+                if namespaces_work() {
+                    applied.push("no network (namespace)".to_string());
+                } else if abi.is_some_and(landlock::handles_network) {
+                    applied.push("no TCP (Landlock)".to_string());
+                } else {
+                    missing.push("network");
+                }
+            }
+            match abi {
+                Some(abi) => applied.push(format!("system files only (Landlock ABI {})", abi)),
+                None => missing.push("filesystem"),
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        missing.extend(["network", "filesystem"]);
+        let mut line = applied.join(", ");
+        if !missing.is_empty() {
+            line += &format!(
+                "; {} not confined{}",
+                missing.join(" and "),
+                if self.config.strict {
+                    ", so strict runs nothing"
+                } else {
+                    ""
+                }
+            );
+        }
+        (missing.is_empty(), line)
+    }
+
+    #[cfg(unix)]
+    fn prepare(&self) -> Confinement {
+        let mut limits = vec![
+            (libc::RLIMIT_NOFILE, MAX_FILES),
+            (libc::RLIMIT_CORE, 0),
+            (libc::RLIMIT_FSIZE, 0),
+        ];
+        if self.config.cpu_secs > 0 {
+            limits.push((libc::RLIMIT_CPU, self.config.cpu_secs));
+        }
+        if self.config.memory_mb > 0 {
+            limits.push((libc::RLIMIT_AS, self.config.memory_mb * MB));
+        }
+        let limits = limits
+            .into_iter()
+            .map(|(resource, limit)| {
+                let mut current = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                // SAFETY: `current` is a valid rlimit to fill in
+                let max = match unsafe { libc::getrlimit(resource, &mut current) } {
+                    0 => current.rlim_max,
+                    _ => libc::RLIM_INFINITY,
+                };
+                // Can't be raised past the hard limit there is
+                let limit = (limit as libc::rlim_t).min(max);
+                (
+                    resource as libc::c_int,
+                    libc::rlimit {
+                        rlim_cur: limit,
+                        rlim_max: limit,
+                    },
+                )
+            })
+            .collect();
+        #[cfg(target_os = "linux")]
+        let (network, landlock) = {
+            let network = (!self.config.network).then(|| {
+                // SAFETY: getuid(2) and getgid(2) have no memory effects
+                let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+                Identity {
+                    uid_map: format!("{} {} 1", uid, uid).into_bytes(),
+                    gid_map: format!("{} {} 1", gid, gid).into_bytes(),
+                }
+            });
+            let read: Vec<&Path> = SYSTEM_READ
+                .iter()
+                .map(Path::new)
+                .chain(self.config.read.iter().map(PathBuf::as_path))
+                .chain(self.read.iter().map(PathBuf::as_path))
+                .collect();
+            let write: Vec<&Path> = SYSTEM_WRITE.iter().map(Path::new).collect();
+            let landlock = landlock::abi()
+                .map(|abi| landlock::Ruleset::new(abi, &read, &write, self.config.network));
+            (network, landlock)
+        };
+        Confinement {
+            limits,
+            strict: self.config.strict,
+            #[cfg(target_os = "linux")]
+            network,
+            #[cfg(target_os = "linux")]
+            landlock,
+        }
+    }
+}
+
+/// Everything a process is confined with, ready before it forks
+#[cfg(unix)]
+struct Confinement {
+    limits: Vec<(libc::c_int, libc::rlimit)>,
+    strict: bool,
+    /// Who the process stays in its own namespaces; None when it may
+    /// reach the network
+    #[cfg(target_os = "linux")]
+    network: Option<Identity>,
+    #[cfg(target_os = "linux")]
+    landlock: Option<landlock::Ruleset>,
+}
+
+#[cfg(target_os = "linux")]
+struct Identity {
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+}
+
+#[cfg(unix)]
+impl Confinement {
+    /// Confine the calling process; run between fork and exec, so it only
+    /// makes syscalls and its errors are bare OS errors, which don't
+    /// allocate: `ENOSYS` where `strict` finds a part missing
+    fn enter(&self) -> std::io::Result<()> {
+        use std::io::Error;
+
+        for (resource, limit) in &self.limits {
+            // SAFETY: `limit` is a valid rlimit
+            if unsafe { libc::setrlimit(*resource as _, limit) } != 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(identity) = &self.network {
+                // SAFETY: unshare(2) affects only this, single-threaded,
+                // child
+                if unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) } == 0 {
+                    // Keep its own ids rather than show up as nobody
+                    write(c"/proc/self/setgroups", b"deny");
+                    write(c"/proc/self/gid_map", &identity.gid_map);
+                    write(c"/proc/self/uid_map", &identity.uid_map);
+                } else if self.strict && !self.landlock.as_ref().is_some_and(|r| r.denies_network())
+                {
+                    return Err(Error::last_os_error());
+                }
+            }
+            match &self.landlock {
+                Some(ruleset) => {
+                    if let Err(e) = ruleset.restrict_self() {
+                        if self.strict {
+                            return Err(e);
+                        }
+                    }
+                }
+                None if self.strict => return Err(Error::from_raw_os_error(libc::ENOSYS)),
+                None => {}
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        if self.strict {
+            return Err(Error::from_raw_os_error(libc::ENOSYS));
+        }
+        Ok(())
+    }
+}
+
+/// Write `data` to `path` with bare syscalls; whether it worked
+#[cfg(target_os = "linux")]
+fn write(path: &std::ffi::CStr, data: &[u8]) -> bool {
+    // SAFETY: path is NUL-terminated and data valid for its length
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return false;
+        }
+        let written = libc::write(fd, data.as_ptr().cast(), data.len());
+        libc::close(fd);
+        written == data.len() as isize
+    }
+}
+
+/// Whether a child can get user and network namespaces of its own
+#[cfg(target_os = "linux")]
+fn namespaces_work() -> bool {
+    use std::os::unix::process::CommandExt;
+    let mut probe = Command::new("true");
+    // SAFETY: unshare(2) affects only the child
+    unsafe {
+        probe.pre_exec(
+            || match libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) {
+                0 => Ok(()),
+                _ => Err(std::io::Error::last_os_error()),
+            },
+        );
+    }
+    probe.status().is_ok_and(|status| status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(sandbox: &Sandbox, script: &str) -> Option<i32> {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(script)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null());
+        sandbox.apply(&mut command).unwrap();
+        command.status().unwrap().code()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sandbox() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("out");
+        let sandbox = Sandbox::new(SandboxConfig {
+            cpu_secs: 1,
+            ..SandboxConfig::default()
+        });
+
+        assert_eq!(run(&sandbox, "echo hi > /dev/null"), Some(0));
+        assert_eq!(run(&sandbox, "exit 3"), Some(3));
+        // Files don't grow, with or without Landlock
+        run(&sandbox, &format!("echo hi > {}", file.display()));
+        assert!(std::fs::read(&file).unwrap_or_default().is_empty());
+        // A busy loop is stopped
+        assert_eq!(run(&sandbox, "while :; do :; done"), None);
+        if landlock::abi().is_some() {
+            let secret = dir.path().join("secret");
+            std::fs::write(&secret, "x").unwrap();
+            let read = format!("cat {}", secret.display());
+            assert_ne!(run(&sandbox, &read), Some(0));
+            assert_eq!(run(&sandbox.clone().read(dir.path()), &read), Some(0));
+        }
+        if namespaces_work() {
+            // Only a loopback interface
+            let interfaces = "exit $(grep -c : /proc/self/net/dev)";
+            assert_eq!(run(&sandbox.clone().read("/proc"), interfaces), Some(1));
+        }
+
+        let off = Sandbox::new(SandboxConfig {
+            enabled: false,
+            ..SandboxConfig::default()
+        });
+        run(&off, &format!("echo hi > {}", file.display()));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "hi\n");
+    }
+}
@@ -0,0 +1,259 @@
+//! WebAssembly scripts: code that can only compute
+//!
+//! Rule conditions, template helpers and plugins may be WebAssembly modules
+//! instead of commands and libraries. A module that imports anything is
+//! refused, so a script has no way to reach files, the network or the
+//! daemon: JSON goes in, and text comes out. Every call runs in a fresh
+//! instance with `[sandbox] fuel` to burn, about one unit per instruction,
+//! and `memory_mb` of memory. Running out of fuel ends the call with an
+//! error, and memory doesn't grow past the cap. These limits hold whatever
+//! `enabled` says.
+//!
+//! A module exports its `memory`, an allocator for the input and the
+//! functions it is called through:
+//!
+//! ```text
+//! alloc(len: i32) -> i32                ;; room for `len` bytes
+//! <function>(ptr: i32, len: i32) -> ..  ;; UTF-8 JSON input at `ptr`
+//! ```
+//!
+//! A function returning text returns an i64, `(ptr << 32) | len` of UTF-8
+//! in memory, or 0 for none.
+
+use crate::config::SandboxConfig;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use wasmi::{
+    Config, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, WasmResults,
+};
+
+/// Longest text a call may return
+const MAX_OUTPUT: usize = 64 * 1024;
+
+const MB: u64 = 1024 * 1024;
+
+/// A compiled module, ready to be called
+pub struct Script {
+    path: PathBuf,
+    engine: Engine,
+    module: Module,
+    fuel: u64,
+    memory: usize,
+}
+
+impl Script {
+    /// Compile the module in `path`, limited per `config`
+    pub fn load(path: &Path, config: &SandboxConfig) -> Result<Self> {
+        let wasm =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::new(path, &wasm, config)
+    }
+
+    /// Compile `wasm`, which came from `path`
+    pub fn new(path: &Path, wasm: &[u8], config: &SandboxConfig) -> Result<Self> {
+        let mut engine_config = Config::default();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config);
+        let module = Module::new(&engine, wasm)
+            .with_context(|| format!("{} is not a WebAssembly module", path.display()))?;
+        if let Some(import) = module.imports().next() {
+            anyhow::bail!(
+                "{} imports {}::{}, and scripts may import nothing",
+                path.display(),
+                import.module(),
+                import.name()
+            );
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            engine,
+            module,
+            fuel: match config.fuel {
+                0 => u64::MAX,
+                fuel => fuel,
+            },
+            memory: match config.memory_mb {
+                0 => usize::MAX,
+                mb => usize::try_from(mb * MB).unwrap_or(usize::MAX),
+            },
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the module exports `function`
+    pub fn exports(&self, function: &str) -> bool {
+        self.module.get_export(function).is_some()
+    }
+
+    /// Call `function` with `input`; the text it returns, if any
+    pub fn text(&self, function: &str, input: &serde_json::Value) -> Result<Option<String>> {
+        self.call::<i64, _>(function, input, |store, memory, result| {
+            if result == 0 {
+                return Ok(None);
+            }
+            let (ptr, len) = ((result >> 32) as u32 as usize, result as u32 as usize);
+            anyhow::ensure!(
+                len <= MAX_OUTPUT,
+                "returned {} bytes, over {}",
+                len,
+                MAX_OUTPUT
+            );
+            let mut text = vec![0; len];
+            memory
+                .read(store, ptr, &mut text)
+                .map_err(wasmi::Error::from)?;
+            Ok(Some(
+                String::from_utf8(text).context("returned text that isn't UTF-8")?,
+            ))
+        })
+    }
+
+    /// Call `function` with `input`; the number it returns
+    pub fn number(&self, function: &str, input: &serde_json::Value) -> Result<i32> {
+        self.call::<i32, _>(function, input, |_, _, result| Ok(result))
+    }
+
+    /// Run `function` in a fresh instance and read its result with `read`
+    fn call<R: WasmResults, T>(
+        &self,
+        function: &str,
+        input: &serde_json::Value,
+        read: impl FnOnce(&Store<StoreLimits>, Memory, R) -> Result<T>,
+    ) -> Result<T> {
+        let run = || -> Result<T> {
+            let limits = StoreLimitsBuilder::new().memory_size(self.memory).build();
+            let mut store = Store::new(&self.engine, limits);
+            store.limiter(|limits| limits);
+            store.set_fuel(self.fuel).map_err(wasmi::Error::from)?;
+            let instance = Linker::new(&self.engine)
+                .instantiate(&mut store, &self.module)?
+                .start(&mut store)?;
+            let memory = instance
+                .get_memory(&store, "memory")
+                .context("exports no memory")?;
+            let input = input.to_string();
+            let len = i32::try_from(input.len())?;
+            let ptr = instance
+                .get_typed_func::<i32, i32>(&store, "alloc")?
+                .call(&mut store, len)?;
+            memory
+                .write(&mut store, ptr as u32 as usize, input.as_bytes())
+                .map_err(wasmi::Error::from)?;
+            let result = instance
+                .get_typed_func::<(i32, i32), R>(&store, function)?
+                .call(&mut store, (ptr, len))?;
+            read(&store, memory, result)
+        };
+        run().with_context(|| format!("{} in {} failed", function, self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A module with the allocator every script needs: input goes at 1024
+    fn script(body: &str, config: &SandboxConfig) -> Result<Script> {
+        let wat = format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) i32.const 1024)
+                {}
+            )"#,
+            body
+        );
+        Script::new(Path::new("test.wasm"), &wat::parse_str(wat)?, config)
+    }
+
+    #[test]
+    fn test_text_in_and_out() {
+        // Echoes its input
+        let echo = script(
+            r#"(func (export "echo") (param i32 i32) (result i64)
+                local.get 0
+                i64.extend_i32_u
+                i64.const 32
+                i64.shl
+                local.get 1
+                i64.extend_i32_u
+                i64.or)
+            (func (export "nothing") (param i32 i32) (result i64) i64.const 0)
+            (func (export "length") (param i32 i32) (result i32) local.get 1)"#,
+            &SandboxConfig::default(),
+        )
+        .unwrap();
+        assert!(echo.exports("echo"));
+        assert!(!echo.exports("matches"));
+        let input = json!({"value": "hi"});
+        assert_eq!(
+            echo.text("echo", &input).unwrap().as_deref(),
+            Some(r#"{"value":"hi"}"#)
+        );
+        assert_eq!(echo.text("nothing", &input).unwrap(), None);
+        assert_eq!(echo.number("length", &input).unwrap(), 14);
+        // The wrong signature is an error, not a crash
+        assert!(echo.number("echo", &input).is_err());
+    }
+
+    #[test]
+    fn test_fuel_runs_out() {
+        let spin = script(
+            r#"(func (export "spin") (param i32 i32) (result i32)
+                (loop $forever (br $forever))
+                i32.const 0)"#,
+            &SandboxConfig {
+                fuel: 100_000,
+                ..SandboxConfig::default()
+            },
+        )
+        .unwrap();
+        let error = format!("{:#}", spin.number("spin", &json!({})).unwrap_err());
+        assert!(error.contains("spin in test.wasm failed"), "{}", error);
+        assert!(error.contains("fuel"), "{}", error);
+    }
+
+    #[test]
+    fn test_memory_is_capped() {
+        // Grows by 64 pages (4 MiB); returns the old size, or -1 when refused
+        let body = r#"(func (export "grow") (param i32 i32) (result i32)
+            i32.const 64
+            memory.grow)"#;
+        let capped = script(
+            body,
+            &SandboxConfig {
+                memory_mb: 2,
+                ..SandboxConfig::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(capped.number("grow", &json!({})).unwrap(), -1);
+        let roomy = script(body, &SandboxConfig::default()).unwrap();
+        assert_eq!(roomy.number("grow", &json!({})).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_imports_are_refused() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (import "wasi_snapshot_preview1" "fd_write"
+                    (func (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+            )"#,
+        )
+        .unwrap();
+        let error = Script::new(Path::new("test.wasm"), &wasm, &SandboxConfig::default())
+            .err()
+            .unwrap();
+        assert!(
+            error
+                .to_string()
+                .contains("imports wasi_snapshot_preview1::fd_write"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! The JSON agent-inbox sends to the outside world
+//!
+//! Hook commands and webhooks, `[[rules]]` commands, the HTTP API, its
+//! observer view and the observer's server-sent events all carry the types
+//! below, so a consumer parses one shape wherever it reads from:
+//!
+//! ```json
+//! {"schema_version": 1, "type": "completed", "task_id": "t1",
+//!  "time": "2026-10-16T09:30:00Z", "message": null, "task": {...}}
+//! ```
+//!
+//! `type` names what happened with the activity log's kinds (`started`,
+//! `attention`, `completed`, `exited`, ...). Listings wrap their tasks as
+//! `{"schema_version": 1, "tasks": [...]}`.
+//!
+//! Compatibility: within a schema version, fields and event types are only
+//! ever added, so consumers should ignore ones they don't know. Removing or
+//! renaming a field, or changing what one means, bumps [`SCHEMA_VERSION`].
+//! Times are RFC 3339 in UTC. The hub's own protocol between machines is
+//! versioned separately (see [`federation::protocol`](crate::federation::protocol)).
+
+use crate::models::{Note, Priority, Progress, Task, TaskEvent, TaskStatus};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Version of the shapes in this module
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A task as external payloads describe it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskInfo {
+    pub task_id: String,
+    pub agent_type: String,
+    pub title: String,
+    /// running, queued, completed or exited
+    pub status: String,
+    /// The attention message, while the task needs attention
+    pub attention_reason: Option<String>,
+    /// The attention reason's kind ("waiting_for_input", ...)
+    pub attention_kind: Option<String>,
+    pub priority: Priority,
+    pub tags: Vec<String>,
+    /// Left out of the observer view
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<i32>,
+    /// Working directory; left out of the observer view
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Machine the task runs on; left out of the observer view
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub exit_code: Option<i32>,
+    pub progress: Option<Progress>,
+    pub notes: Vec<Note>,
+}
+
+impl TaskInfo {
+    pub fn of(task: &Task) -> Self {
+        Self {
+            task_id: task.task_id.clone(),
+            agent_type: task.agent_type.clone(),
+            title: task.title.clone(),
+            status: task.status.as_str().to_string(),
+            attention_reason: task.attention_reason.clone(),
+            attention_kind: task
+                .attention_reason
+                .as_ref()
+                .and(task.attention_key())
+                .map(str::to_string),
+            priority: task.priority(),
+            tags: task.tags(),
+            pid: task.pid,
+            cwd: task.context.as_ref().and_then(|c| c.project_path.clone()),
+            host: task.host().map(|host| host.hostname),
+            created_at: task.created_at,
+            updated_at: task.updated_at,
+            completed_at: task.completed_at,
+            exit_code: task.exit_code,
+            progress: task.progress(),
+            notes: task.notes(),
+        }
+    }
+
+    /// `task` trimmed for observers: no pid, working directory or host
+    pub fn observed(task: &Task) -> Self {
+        Self {
+            pid: None,
+            cwd: None,
+            host: None,
+            ..Self::of(task)
+        }
+    }
+}
+
+/// Something that happened to a task
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Event {
+    pub schema_version: u32,
+    /// An activity log kind: "started", "attention", "completed", ...
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub task_id: String,
+    pub time: DateTime<Utc>,
+    /// What the activity log says about it
+    pub message: Option<String>,
+    /// The task as it stands; left out of timelines and streams
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<TaskInfo>,
+}
+
+impl Event {
+    /// `task`'s current state as an event: "attention" while it needs
+    /// attention, else its status ("completed", "exited", ...)
+    pub fn task(task: &Task, now: DateTime<Utc>) -> Self {
+        let kind = match (&task.status, &task.attention_reason) {
+            (TaskStatus::Running, Some(_)) => "attention",
+            (status, _) => status.as_str(),
+        };
+        Self {
+            schema_version: SCHEMA_VERSION,
+            kind: kind.to_string(),
+            task_id: task.task_id.clone(),
+            time: now,
+            message: task.attention_reason.clone(),
+            task: Some(TaskInfo::of(task)),
+        }
+    }
+}
+
+impl Event {
+    /// `event` as observers see it: what happened and when, without the
+    /// activity log's message, which may name pids, prompts and paths
+    pub fn observed(event: &TaskEvent) -> Self {
+        Self {
+            message: None,
+            ..Self::from(event)
+        }
+    }
+}
+
+impl From<&TaskEvent> for Event {
+    fn from(event: &TaskEvent) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            kind: event.kind.as_str().to_string(),
+            task_id: event.task_id.clone(),
+            time: event.at,
+            message: Some(event.message.clone()),
+            task: None,
+        }
+    }
+}
+
+/// A list of tasks: `GET /v1/tasks` and the observer's
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskList {
+    pub schema_version: u32,
+    pub tasks: Vec<TaskInfo>,
+}
+
+impl TaskList {
+    pub fn new(tasks: Vec<TaskInfo>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            tasks,
+        }
+    }
+}
+
+/// One task with its activity log: `GET /v1/tasks/<id>` and the observer's
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Timeline {
+    pub schema_version: u32,
+    pub task: TaskInfo,
+    pub events: Vec<Event>,
+}
+
+impl Timeline {
+    pub fn new(task: TaskInfo, events: &[TaskEvent]) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            task,
+            events: events.iter().map(Event::from).collect(),
+        }
+    }
+
+    /// The timeline for observers: events without their messages
+    pub fn observed(task: TaskInfo, events: &[TaskEvent]) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            task,
+            events: events.iter().map(Event::observed).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EventKind;
+
+    #[test]
+    fn test_event_shapes() {
+        let mut task = Task::new(
+            "t1".into(),
+            "claude_code".into(),
+            "Fix".into(),
+            Some(4242),
+            None,
+        );
+        task.set_tags(&["ci".to_string()]);
+        task.set_attention("waiting_for_input", "Waiting for input".to_string());
+        let now = Utc::now();
+
+        let event = serde_json::to_value(Event::task(&task, now)).unwrap();
+        assert_eq!(event["schema_version"], SCHEMA_VERSION);
+        assert_eq!(event["type"], "attention");
+        assert_eq!(event["task"]["attention_kind"], "waiting_for_input");
+        assert_eq!(event["task"]["tags"][0], "ci");
+        assert_eq!(event["task"]["pid"], 4242);
+
+        task.attention_reason = None;
+        task.complete();
+        let event = Event::task(&task, now);
+        assert_eq!((event.kind.as_str(), event.message), ("completed", None));
+        let observed = serde_json::to_value(TaskInfo::observed(&task)).unwrap();
+        assert!(observed.get("pid").is_none() && observed.get("cwd").is_none());
+
+        let logged = TaskEvent::new("t1", EventKind::Started, "pid 4242");
+        let timeline = Timeline::new(TaskInfo::of(&task), &[logged]);
+        assert_eq!(timeline.events[0].kind, "started");
+        // Consumers parse what was sent, with unknown fields ignored
+        let mut sent = serde_json::to_value(&timeline).unwrap();
+        sent["added_later"] = serde_json::json!(true);
+        assert_eq!(serde_json::from_value::<Timeline>(sent).unwrap(), timeline);
+        let logged = TaskEvent::new("t1", EventKind::Started, "pid 4242");
+        let observed = Timeline::observed(TaskInfo::observed(&task), &[logged]);
+        assert_eq!(observed.events[0].kind, "started");
+        assert_eq!(observed.events[0].message, None);
+    }
+}
@@ -0,0 +1,278 @@
+//! How long tasks wait on the user
+//!
+//! A task starts waiting when an attention or completion notification is
+//! delivered, and stops when it resumes: the monitor sees activity again, or
+//! a wrapper reports the agent generating. The wait is recorded in the
+//! task's metadata when that happens. Auto-responses, new runs and exits end
+//! a wait without recording it, since nobody answered; so do rate limits,
+//! which the agent waits out by itself.
+//!
+//! `agent-inbox stats` aggregates the recorded waits, which is a fair
+//! measure of how much time the notifications actually save.
+
+use crate::db::Database;
+use crate::models::progress::format_duration;
+use crate::models::{EventKind, Task, TaskEvent};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Waits kept in a task's metadata; older ones are dropped
+const MAX_RECORDED: usize = 50;
+
+/// One answered notification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResponseTime {
+    /// When the user responded
+    pub at: DateTime<Utc>,
+    pub secs: i64,
+}
+
+/// When the unanswered notification in `events` (oldest first) was
+/// delivered, if the task is still waiting on one
+pub fn waiting_since(events: &[TaskEvent]) -> Option<DateTime<Utc>> {
+    let mut since = None;
+    // Rate limits resolve themselves; nobody is expected to answer them
+    let mut backing_off = false;
+    for event in events {
+        match event.kind {
+            EventKind::RateLimited => backing_off = true,
+            EventKind::Attention => backing_off = false,
+            EventKind::Notified if !backing_off => {
+                since.get_or_insert(event.at);
+            }
+            EventKind::AttentionCleared
+            | EventKind::Resumed
+            | EventKind::AutoResponded
+            | EventKind::Started
+            | EventKind::Exited => {
+                since = None;
+                backing_off = false;
+            }
+            _ => {}
+        }
+    }
+    since
+}
+
+/// The task resumed at `now`: record how long it waited since its last
+/// notification. Call before logging the event that ends the wait.
+pub fn record_response(db: &Database, task: &mut Task, now: DateTime<Utc>) -> Option<i64> {
+    let events = db.task_events(&task.task_id).ok()?;
+    let secs = (now - waiting_since(&events)?).num_seconds().max(0);
+
+    let mut log = response_times(task);
+    log.push(ResponseTime { at: now, secs });
+    let excess = log.len().saturating_sub(MAX_RECORDED);
+    log.drain(..excess);
+    if let Ok(value) = serde_json::to_value(&log) {
+        task.metadata
+            .get_or_insert_with(Default::default)
+            .insert("response_times".to_string(), value);
+    }
+    Some(secs)
+}
+
+/// Recorded waits for a task, oldest first
+pub fn response_times(task: &Task) -> Vec<ResponseTime> {
+    task.metadata
+        .as_ref()
+        .and_then(|m| m.get("response_times"))
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+/// Summary of a set of waits, in seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Latency {
+    pub count: usize,
+    pub median: i64,
+    pub p90: i64,
+    pub longest: i64,
+}
+
+impl Latency {
+    /// None when there is nothing to summarize
+    pub fn of(mut secs: Vec<i64>) -> Option<Self> {
+        if secs.is_empty() {
+            return None;
+        }
+        secs.sort_unstable();
+        let n = secs.len();
+        let median = if n.is_multiple_of(2) {
+            (secs[n / 2 - 1] + secs[n / 2]) / 2
+        } else {
+            secs[n / 2]
+        };
+        Some(Self {
+            count: n,
+            median,
+            p90: secs[(n * 9).div_ceil(10) - 1],
+            longest: secs[n - 1],
+        })
+    }
+}
+
+/// Response times across tasks
+#[derive(Debug, Clone)]
+pub struct Stats {
+    pub overall: Option<Latency>,
+    /// Per agent type, alphabetically
+    pub by_agent: Vec<(String, Latency)>,
+    /// Task that waited longest, and for how long
+    pub slowest: Option<(String, i64)>,
+}
+
+impl Stats {
+    /// Waits answered at or after `since` (all of them when None)
+    pub fn from_tasks(tasks: &[Task], since: Option<DateTime<Utc>>) -> Self {
+        let mut all = Vec::new();
+        let mut agents: BTreeMap<&str, Vec<i64>> = BTreeMap::new();
+        let mut slowest: Option<(String, i64)> = None;
+        for task in tasks {
+            for response in response_times(task) {
+                if since.is_some_and(|since| response.at < since) {
+                    continue;
+                }
+                all.push(response.secs);
+                agents
+                    .entry(&task.agent_type)
+                    .or_default()
+                    .push(response.secs);
+                if slowest
+                    .as_ref()
+                    .is_none_or(|(_, secs)| response.secs > *secs)
+                {
+                    slowest = Some((task.title.clone(), response.secs));
+                }
+            }
+        }
+        Self {
+            overall: Latency::of(all),
+            by_agent: agents
+                .into_iter()
+                .filter_map(|(agent, secs)| Some((agent.to_string(), Latency::of(secs)?)))
+                .collect(),
+            slowest,
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let Some(overall) = &self.overall else {
+            return vec!["No answered notifications recorded yet".to_string()];
+        };
+        let mut lines = vec![
+            format!(
+                "Median time you leave agents hanging: {} ({} response(s))",
+                format_duration(overall.median),
+                overall.count
+            ),
+            format!("90th percentile: {}", format_duration(overall.p90)),
+        ];
+        if let Some((title, secs)) = &self.slowest {
+            lines.push(format!("Longest: {} ({})", format_duration(*secs), title));
+        }
+        if self.by_agent.len() > 1 {
+            for (agent, latency) in &self.by_agent {
+                lines.push(format!(
+                    "  {}: median {} over {}",
+                    agent,
+                    format_duration(latency.median),
+                    latency.count
+                ));
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_wait_is_recorded_when_the_task_resumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(dir.path().join("tasks.db")).unwrap();
+        let mut task = Task::new(
+            "t1".into(),
+            "claude_code".into(),
+            "Fix bug".into(),
+            None,
+            None,
+        );
+        db.insert_task(&task).unwrap();
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let log = |minute: i64, kind: EventKind| {
+            db.record_event(&TaskEvent::new("t1", kind, "").at(start + Duration::minutes(minute)))
+                .unwrap()
+        };
+
+        log(0, EventKind::Started);
+        log(5, EventKind::Attention);
+        log(5, EventKind::Notified);
+        // Repeated notifications don't restart the clock
+        log(7, EventKind::Notified);
+        assert_eq!(
+            record_response(&db, &mut task, start + Duration::minutes(16)),
+            Some(660)
+        );
+        log(16, EventKind::AttentionCleared);
+        // Nothing pending any more
+        assert_eq!(
+            record_response(&db, &mut task, start + Duration::minutes(17)),
+            None
+        );
+
+        log(20, EventKind::Notified);
+        log(21, EventKind::AutoResponded);
+        assert_eq!(
+            record_response(&db, &mut task, start + Duration::minutes(22)),
+            None
+        );
+
+        log(30, EventKind::RateLimited);
+        log(30, EventKind::Notified);
+        assert_eq!(
+            record_response(&db, &mut task, start + Duration::minutes(32)),
+            None
+        );
+        assert_eq!(response_times(&task).len(), 1);
+    }
+
+    #[test]
+    fn test_stats() {
+        let at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let task = |agent: &str, title: &str, waits: &[i64]| {
+            let mut task = Task::new(title.into(), agent.into(), title.into(), None, None);
+            let log: Vec<ResponseTime> = waits
+                .iter()
+                .map(|&secs| ResponseTime { at, secs })
+                .collect();
+            task.metadata.get_or_insert_with(Default::default).insert(
+                "response_times".to_string(),
+                serde_json::to_value(log).unwrap(),
+            );
+            task
+        };
+        let tasks = vec![
+            task("claude_code", "refactor", &[60, 600, 660]),
+            task("opencode", "docs", &[7200]),
+        ];
+
+        let stats = Stats::from_tasks(&tasks, None);
+        let overall = stats.overall.clone().unwrap();
+        assert_eq!((overall.count, overall.median, overall.p90), (4, 630, 7200));
+        assert_eq!(stats.slowest, Some(("docs".to_string(), 7200)));
+        assert_eq!(
+            stats.lines()[0],
+            "Median time you leave agents hanging: 10m (4 response(s))"
+        );
+        assert_eq!(stats.by_agent[0].1.median, 600);
+
+        assert!(Stats::from_tasks(&tasks, Some(at + Duration::seconds(1)))
+            .overall
+            .is_none());
+    }
+}
@@ -0,0 +1,322 @@
+//! Where agent-inbox keeps its files
+//!
+//! Three directories, after the XDG base directory spec:
+//!
+//! - config, `$XDG_CONFIG_HOME/agent-inbox` (~/.config/agent-inbox):
+//!   `config.toml`, plugins, locale catalogs and the agent wrappers
+//! - data, `$XDG_DATA_HOME/agent-inbox` (~/.local/share/agent-inbox): the
+//!   task database and run artifacts
+//! - state, `$XDG_STATE_HOME/agent-inbox` (~/.local/state/agent-inbox):
+//!   captured output and recordings, presence, open alerts
+//!
+//! `[storage] data_dir` and `state_dir` in the config override the last
+//! two.
+//!
+//! Everything used to live in ~/.agent-tasks. While that directory holds a
+//! config, plugins, locales or wrappers (which shell aliases point at), it
+//! stays the config directory; its data and state are moved to their new
+//! places by [`migrate`] the first time a newer agent-inbox runs.
+
+use crate::config::StorageConfig;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Name of the directories under the XDG base directories
+const APP: &str = "agent-inbox";
+
+/// The directory everything lived in before, under $HOME
+const LEGACY_DIR: &str = ".agent-tasks";
+
+/// What keeps the legacy directory in use as the config directory
+const LEGACY_CONFIG: &[&str] = &["config.toml", "plugins", "locales", "wrappers"];
+
+/// What moves out of the legacy directory into the data directory
+const LEGACY_DATA: &[&str] = &["tasks.db", "tasks.db-wal", "tasks.db-shm", "artifacts"];
+
+/// What moves out of the legacy directory into the state directory
+const LEGACY_STATE: &[&str] = &["logs", "alerts", "presence", "daemon.panic"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dirs {
+    pub config: PathBuf,
+    pub data: PathBuf,
+    pub state: PathBuf,
+}
+
+/// Just the `[storage]` table of a config file
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ConfigFile {
+    storage: StorageConfig,
+}
+
+impl Dirs {
+    /// The directories for `home`, with XDG variables looked up through
+    /// `env` and `[storage]` read from the config found
+    pub fn resolve(home: &Path, env: impl Fn(&str) -> Option<String>) -> Self {
+        let base = |var: &str, default: &str| {
+            env(var)
+                .filter(|dir| Path::new(dir).is_absolute())
+                .map(PathBuf::from)
+                .unwrap_or_else(|| home.join(default))
+                .join(APP)
+        };
+        let legacy = home.join(LEGACY_DIR);
+        let xdg_config = base("XDG_CONFIG_HOME", ".config");
+        let config = if !xdg_config.join("config.toml").exists()
+            && LEGACY_CONFIG.iter().any(|name| legacy.join(name).exists())
+        {
+            legacy
+        } else {
+            xdg_config
+        };
+        let storage = fs::read_to_string(config.join("config.toml"))
+            .ok()
+            .and_then(|text| toml::from_str::<ConfigFile>(&text).ok())
+            .unwrap_or_default()
+            .storage;
+        Self {
+            data: storage
+                .data_dir
+                .unwrap_or_else(|| base("XDG_DATA_HOME", ".local/share")),
+            state: storage
+                .state_dir
+                .unwrap_or_else(|| base("XDG_STATE_HOME", ".local/state")),
+            config,
+        }
+    }
+}
+
+fn home() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").expect("HOME environment variable not set"))
+}
+
+/// This process's directories, resolved once
+pub fn dirs() -> &'static Dirs {
+    static DIRS: OnceLock<Dirs> = OnceLock::new();
+    DIRS.get_or_init(|| Dirs::resolve(&home(), |var| std::env::var(var).ok()))
+}
+
+pub fn config_dir() -> PathBuf {
+    dirs().config.clone()
+}
+
+pub fn data_dir() -> PathBuf {
+    dirs().data.clone()
+}
+
+pub fn state_dir() -> PathBuf {
+    dirs().state.clone()
+}
+
+/// ~/.agent-tasks
+pub fn legacy_dir() -> PathBuf {
+    home().join(LEGACY_DIR)
+}
+
+/// Create the data and state directories, moving over what an older
+/// agent-inbox left in the legacy directory
+pub fn prepare() -> Result<()> {
+    let dirs = dirs();
+    for dir in [&dirs.data, &dirs.state] {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    for (from, to) in migrate(&legacy_dir(), dirs)? {
+        eprintln!("Moved {} to {}", from.display(), to.display());
+    }
+    Ok(())
+}
+
+/// Move the data and state in `legacy` to `dirs`; returns what moved.
+/// Anything already at its destination stays where it is.
+pub fn migrate(legacy: &Path, dirs: &Dirs) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut moved = Vec::new();
+    let items = LEGACY_DATA
+        .iter()
+        .map(|name| (name, &dirs.data))
+        .chain(LEGACY_STATE.iter().map(|name| (name, &dirs.state)));
+    for (name, dir) in items {
+        let (from, to) = (legacy.join(name), dir.join(name));
+        if from == to || !from.exists() || to.exists() {
+            continue;
+        }
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        move_item(&from, &to)
+            .with_context(|| format!("Failed to move {} to {}", from.display(), to.display()))?;
+        moved.push((from, to));
+    }
+    Ok(moved)
+}
+
+/// Rename, or copy and delete across filesystems
+fn move_item(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_item(from, to)?;
+    if from.is_dir() {
+        fs::remove_dir_all(from)?;
+    } else {
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+fn copy_item(from: &Path, to: &Path) -> Result<()> {
+    if !from.is_dir() {
+        fs::copy(from, to)?;
+        return Ok(());
+    }
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_item(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+/// Disk space one task takes, in bytes
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TaskStorage {
+    /// The captured output log and its compressed chunks
+    pub output: u64,
+    pub recording: u64,
+    pub artifacts: u64,
+}
+
+impl TaskStorage {
+    pub fn total(&self) -> u64 {
+        self.output + self.recording + self.artifacts
+    }
+}
+
+/// Space taken per task by output in `logs` and runs under `artifacts`,
+/// largest first
+pub fn usage_by_task(logs: &Path, artifacts: &Path) -> Vec<(String, TaskStorage)> {
+    let mut usage: std::collections::HashMap<String, TaskStorage> = Default::default();
+    for entry in fs::read_dir(logs).into_iter().flatten().flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let size = entry.metadata().map(|m| m.len()).unwrap_or_default();
+        if let Some(task_id) = name.strip_suffix(".cast") {
+            usage.entry(task_id.to_string()).or_default().recording += size;
+        } else if let Some(at) = name.rfind(".log") {
+            usage.entry(name[..at].to_string()).or_default().output += size;
+        }
+    }
+    for entry in fs::read_dir(artifacts).into_iter().flatten().flatten() {
+        let size = dir_size(&entry.path());
+        usage
+            .entry(entry.file_name().to_string_lossy().into_owned())
+            .or_default()
+            .artifacts += size;
+    }
+    let mut usage: Vec<(String, TaskStorage)> = usage.into_iter().collect();
+    usage.sort_by(|a, b| b.1.total().cmp(&a.1.total()).then_with(|| a.0.cmp(&b.0)));
+    usage
+}
+
+/// Bytes in the files under `path`
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| dir_size(&entry.path()))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_and_migrate() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path();
+        let no_env = |_: &str| None;
+
+        let fresh = Dirs::resolve(home, no_env);
+        assert_eq!(fresh.config, home.join(".config/agent-inbox"));
+        assert_eq!(fresh.data, home.join(".local/share/agent-inbox"));
+        let xdg = Dirs::resolve(home, |var| {
+            (var == "XDG_STATE_HOME").then(|| "/var/lib/me".to_string())
+        });
+        assert_eq!(xdg.state, PathBuf::from("/var/lib/me/agent-inbox"));
+
+        // An older install keeps its config where it is, and may move data
+        let legacy = home.join(".agent-tasks");
+        fs::create_dir_all(legacy.join("logs")).unwrap();
+        fs::write(legacy.join("logs/build.log"), "output").unwrap();
+        fs::write(legacy.join("tasks.db"), "db").unwrap();
+        fs::write(
+            legacy.join("config.toml"),
+            "[storage]\nstate_dir = \"/srv/state\"\n",
+        )
+        .unwrap();
+        let dirs = Dirs::resolve(home, no_env);
+        assert_eq!(dirs.config, legacy);
+        assert_eq!(dirs.state, PathBuf::from("/srv/state"));
+
+        let dirs = Dirs {
+            state: home.join("state"),
+            ..dirs
+        };
+        let moved = migrate(&legacy, &dirs).unwrap();
+        assert_eq!(moved.len(), 2);
+        assert_eq!(
+            fs::read_to_string(dirs.data.join("tasks.db")).unwrap(),
+            "db"
+        );
+        assert_eq!(
+            fs::read_to_string(dirs.state.join("logs/build.log")).unwrap(),
+            "output"
+        );
+        assert!(!legacy.join("tasks.db").exists());
+        assert!(legacy.join("config.toml").exists());
+        assert!(migrate(&legacy, &dirs).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_usage_by_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let (logs, artifacts) = (dir.path().join("logs"), dir.path().join("artifacts"));
+        fs::create_dir_all(&logs).unwrap();
+        fs::create_dir_all(artifacts.join("build/20250301T041500Z")).unwrap();
+        fs::write(logs.join("build.log"), [0; 100]).unwrap();
+        fs::write(logs.join("build.log.1.gz"), [0; 20]).unwrap();
+        fs::write(logs.join("build.cast"), [0; 300]).unwrap();
+        fs::write(logs.join("schedule-v1.2.log"), [0; 10]).unwrap();
+        fs::write(artifacts.join("build/20250301T041500Z/output.log"), [0; 5]).unwrap();
+
+        let usage = usage_by_task(&logs, &artifacts);
+        assert_eq!(
+            usage,
+            vec![
+                (
+                    "build".to_string(),
+                    TaskStorage {
+                        output: 120,
+                        recording: 300,
+                        artifacts: 5
+                    }
+                ),
+                (
+                    "schedule-v1.2".to_string(),
+                    TaskStorage {
+                        output: 10,
+                        ..Default::default()
+                    }
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Completion notification text: how long the run took, how much of that
+//! it waited on the user, the exit status, test counts and the output
+//! summary, filled into the `[summary.completion]` templates
+
+use crate::config::CompletionConfig;
+use crate::models::progress::format_duration;
+use crate::models::{EventKind, Task, TaskEvent, TestResults};
+use crate::notify::template;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Success,
+    Failure,
+    /// The agent finished without an exit status, e.g. a finished turn
+    Finished,
+    /// The run ended normally but its tests failed
+    Warning,
+}
+
+impl Outcome {
+    pub fn of(exit_code: Option<i32>) -> Self {
+        match exit_code {
+            Some(0) => Outcome::Success,
+            Some(_) => Outcome::Failure,
+            None => Outcome::Finished,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::Failure => "failure",
+            Outcome::Finished => "finished",
+            Outcome::Warning => "warning",
+        }
+    }
+
+    fn default_icon(&self) -> &'static str {
+        match self {
+            Outcome::Success => "✅",
+            Outcome::Failure => "❌",
+            Outcome::Finished => "💬",
+            Outcome::Warning => "⚠️",
+        }
+    }
+}
+
+/// What a completion notification reports about a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub outcome: Outcome,
+    pub exit_code: Option<i32>,
+    pub runtime_secs: i64,
+    pub finished_at: DateTime<Utc>,
+    /// Time flagged as needing attention during the run
+    pub waited_secs: i64,
+    /// The last test run in the output
+    pub tests: Option<TestResults>,
+}
+
+impl Completion {
+    /// The current run of `task`, ending at `now`. `events` are the task's
+    /// events, oldest first. Failing tests recorded on the task turn a
+    /// success into a warning.
+    pub fn of(
+        task: &Task,
+        events: &[TaskEvent],
+        exit_code: Option<i32>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let tests = task.test_results();
+        let outcome = match Outcome::of(exit_code) {
+            Outcome::Failure => Outcome::Failure,
+            _ if tests.as_ref().is_some_and(TestResults::has_failures) => Outcome::Warning,
+            outcome => outcome,
+        };
+        Self {
+            outcome,
+            exit_code,
+            runtime_secs: (now - task.created_at).num_seconds().max(0),
+            finished_at: now,
+            waited_secs: attention_secs(events, task.created_at, now),
+            tests,
+        }
+    }
+
+    /// "Done in 1h42m (waited on you for 23m), exit 0, tests: 43 passed, 2 failed"
+    pub fn status(&self) -> String {
+        let duration = format_duration(self.runtime_secs);
+        let mut status = match self.outcome {
+            Outcome::Failure => format!("Failed after {}", duration),
+            _ => format!("Done in {}", duration),
+        };
+        if self.waited_secs > 0 {
+            status += &format!(" (waited on you for {})", format_duration(self.waited_secs));
+        }
+        if let Some(code) = self.exit_code {
+            status += &format!(", exit {}", code);
+        }
+        if let Some(tests) = &self.tests {
+            status += &format!(", tests: {}", tests.describe());
+        }
+        status
+    }
+
+    /// Severity the notification goes out with: "critical" for failures
+    pub fn severity(&self) -> &'static str {
+        match self.outcome {
+            Outcome::Failure => "critical",
+            _ => "normal",
+        }
+    }
+
+    /// Title and body from the templates
+    pub fn render(
+        &self,
+        config: &CompletionConfig,
+        task: &Task,
+        summary: Option<&str>,
+    ) -> (String, String) {
+        let icon = config
+            .icons
+            .get(self.outcome.as_str())
+            .map_or(self.outcome.default_icon(), String::as_str);
+        // Notes left during this run
+        let notes: Vec<String> = task
+            .notes()
+            .into_iter()
+            .filter(|note| note.at >= task.created_at)
+            .map(|note| format!("✎ {}", note.text))
+            .collect();
+        let notes = notes.join("\n");
+        // Tasks registered before hosts were recorded ran here
+        let host = task
+            .host()
+            .unwrap_or_else(|| crate::host::current(&Default::default()));
+        let value = |name: &str| {
+            Some(match name {
+                "icon" => icon.to_string(),
+                "agent" => task.agent_type.clone(),
+                "title" => task.title.clone(),
+                "status" => self.status(),
+                "outcome" => self.outcome.as_str().to_string(),
+                "severity" => self.severity().to_string(),
+                "duration" => format_duration(self.runtime_secs),
+                "duration_secs" => self.runtime_secs.to_string(),
+                "waited" => format_duration(self.waited_secs),
+                "waited_secs" => self.waited_secs.to_string(),
+                "started" => crate::datetime::times().absolute(task.created_at),
+                "finished" => crate::datetime::times().absolute(self.finished_at),
+                "exit" => self.exit_code.map(|c| c.to_string()).unwrap_or_default(),
+                "tests" => self
+                    .tests
+                    .as_ref()
+                    .map(TestResults::describe)
+                    .unwrap_or_default(),
+                "summary" => summary.unwrap_or_default().to_string(),
+                "notes" => notes.clone(),
+                "host" => host.name().to_string(),
+                "hostname" => host.hostname.clone(),
+                "user" => host.user.clone(),
+                _ => return None,
+            })
+        };
+        let fill = |text: &str| template::render(text, value);
+        let title = fill(&config.title).trim().to_string();
+        let mut body = fill(&config.body).trim_end().to_string();
+        if config.notes && !notes.is_empty() && !config.body.contains("{notes}") {
+            body = format!("{}\n{}", body, notes);
+        }
+        (title, body)
+    }
+}
+
+/// Seconds between `since` and `now` during which the task was flagged as
+/// needing attention
+pub fn attention_secs(events: &[TaskEvent], since: DateTime<Utc>, now: DateTime<Utc>) -> i64 {
+    let mut total = 0;
+    let mut flagged: Option<DateTime<Utc>> = None;
+    for event in events.iter().filter(|e| e.at >= since) {
+        match event.kind {
+            EventKind::Attention => {
+                flagged.get_or_insert(event.at);
+            }
+            EventKind::AttentionCleared
+            | EventKind::Resumed
+            | EventKind::AutoResponded
+            | EventKind::Replied
+            | EventKind::Completed
+            | EventKind::Exited => {
+                if let Some(start) = flagged.take() {
+                    total += (event.at - start).num_seconds().max(0);
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = flagged {
+        total += (now - start).num_seconds().max(0);
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn event(at: DateTime<Utc>, kind: EventKind) -> TaskEvent {
+        TaskEvent {
+            task_id: "t".to_string(),
+            at,
+            kind,
+            message: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_completion_text() {
+        let start = Utc::now() - Duration::minutes(102);
+        let mut task = Task::new(
+            "t".into(),
+            "claude_code".into(),
+            "Refactor api".into(),
+            None,
+            None,
+        );
+        task.created_at = start;
+        let events = vec![
+            event(start - Duration::minutes(30), EventKind::Attention),
+            event(start - Duration::minutes(20), EventKind::Resumed),
+            event(start + Duration::minutes(10), EventKind::Attention),
+            event(start + Duration::minutes(10), EventKind::Notified),
+            event(start + Duration::minutes(33), EventKind::Resumed),
+        ];
+        let completion = Completion::of(&task, &events, Some(0), start + Duration::minutes(102));
+        assert_eq!(
+            completion.status(),
+            "Done in 1h42m (waited on you for 23m), exit 0"
+        );
+
+        let config = CompletionConfig::default();
+        let (title, body) = completion.render(&config, &task, None);
+        assert_eq!(title, "✅ [claude_code] Refactor api");
+        assert_eq!(body, "Done in 1h42m (waited on you for 23m), exit 0");
+
+        task.add_note("waiting on infra team");
+        let config = CompletionConfig {
+            notes: true,
+            ..config
+        };
+        let (_, body) = completion.render(&config, &task, None);
+        assert_eq!(
+            body,
+            "Done in 1h42m (waited on you for 23m), exit 0\n✎ waiting on infra team"
+        );
+
+        let failed = Completion::of(&task, &[], Some(2), start + Duration::seconds(40));
+        let config = CompletionConfig {
+            title: "{icon} {title} ({exit})".to_string(),
+            notes: false,
+            icons: [("failure".to_string(), "🔥".to_string())].into(),
+            ..config
+        };
+        let (title, body) = failed.render(&config, &task, Some("2 errors"));
+        assert_eq!(title, "🔥 Refactor api (2)");
+        assert_eq!(body, "Failed after 40s, exit 2\n2 errors");
+
+        task.set_test_results(Some(&TestResults {
+            framework: "cargo".to_string(),
+            passed: 43,
+            failed: 2,
+            skipped: 0,
+        }));
+        let warning = Completion::of(&task, &[], Some(0), start + Duration::seconds(40));
+        assert_eq!(warning.outcome, Outcome::Warning);
+        let (title, _) = warning.render(&CompletionConfig::default(), &task, None);
+        assert!(title.starts_with("⚠️"));
+        assert_eq!(
+            warning.status(),
+            "Done in 40s, exit 0, tests: 43 passed, 2 failed"
+        );
+        assert_eq!(
+            Completion::of(&task, &[], Some(1), start).outcome,
+            Outcome::Failure
+        );
+
+        let config = CompletionConfig {
+            title: "{severity|emoji} {title|escape}".to_string(),
+            body: "Took {duration_secs|duration:long}\n{summary|truncate:12|code}".to_string(),
+            ..CompletionConfig::default()
+        };
+        let (title, body) = failed.render(&config, &task, Some("2 errors in src/lib.rs"));
+        assert_eq!(template::plain(&title), "🔴 Refactor api");
+        assert_eq!(template::plain(&body), "Took 40 seconds\n2 errors in…\n");
+    }
+}
@@ -0,0 +1,136 @@
+//! Test-run summaries in captured output: cargo test, pytest and jest
+//!
+//! Only the last run counts. cargo prints one `test result:` line per test
+//! binary, so those are added up until the next build starts; a pytest or
+//! jest summary simply replaces whatever came before it.
+
+use crate::models::TestResults;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// The last test run summarized in `output`, if any
+pub fn parse(output: &str) -> Option<TestResults> {
+    static CARGO: OnceLock<Regex> = OnceLock::new();
+    static CARGO_BUILD: OnceLock<Regex> = OnceLock::new();
+    static PYTEST: OnceLock<Regex> = OnceLock::new();
+    static JEST: OnceLock<Regex> = OnceLock::new();
+    let cargo = CARGO.get_or_init(|| {
+        Regex::new(r"test result: (?:ok|FAILED)\. (\d+) passed; (\d+) failed; (\d+) ignored")
+            .unwrap()
+    });
+    // Printed once per `cargo test` invocation, before its test binaries run
+    let cargo_build =
+        CARGO_BUILD.get_or_init(|| Regex::new(r"^\s*Finished .*target\(s\) in").unwrap());
+    let pytest = PYTEST.get_or_init(|| {
+        Regex::new(r"^=+ (.*\d+ (?:passed|failed|errors?).*) in [\d.]+s\b.*=+$").unwrap()
+    });
+    let jest = JEST.get_or_init(|| Regex::new(r"^Tests:\s+(.*\d+ total)$").unwrap());
+
+    let mut last: Option<TestResults> = None;
+    let mut cargo_run_over = true;
+    for line in output.lines().map(str::trim_end) {
+        if cargo_build.is_match(line) {
+            cargo_run_over = true;
+        } else if let Some(caps) = cargo.captures(line) {
+            let count = |i: usize| caps[i].parse::<u64>().unwrap_or(0);
+            let same_run =
+                !cargo_run_over && last.as_ref().is_some_and(|run| run.framework == "cargo");
+            let run = match last.as_mut() {
+                Some(run) if same_run => run,
+                _ => last.insert(TestResults {
+                    framework: "cargo".to_string(),
+                    ..TestResults::default()
+                }),
+            };
+            run.passed += count(1);
+            run.failed += count(2);
+            run.skipped += count(3);
+            cargo_run_over = false;
+        } else if let Some(caps) = pytest.captures(line) {
+            last = Some(counts("pytest", &caps[1]));
+            cargo_run_over = true;
+        } else if let Some(caps) = jest.captures(line) {
+            last = Some(counts("jest", &caps[1]));
+            cargo_run_over = true;
+        }
+    }
+    last
+}
+
+/// Counts from "3 failed, 10 passed, 2 skipped, 1 error"
+fn counts(framework: &str, summary: &str) -> TestResults {
+    let mut results = TestResults {
+        framework: framework.to_string(),
+        ..TestResults::default()
+    };
+    for part in summary.split(',') {
+        let mut words = part.split_whitespace();
+        let (Some(count), Some(label)) = (words.next(), words.next()) else {
+            continue;
+        };
+        let Ok(count) = count.parse::<u64>() else {
+            continue;
+        };
+        match label {
+            "passed" => results.passed += count,
+            "failed" | "error" | "errors" => results.failed += count,
+            "skipped" | "todo" | "xfailed" => results.skipped += count,
+            _ => {}
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cargo_runs() {
+        let output = "\
+    Finished `test` profile [unoptimized] target(s) in 0.50s
+test result: ok. 7 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.01s
+    Finished `test` profile [unoptimized] target(s) in 3.20s
+     Running unittests src/lib.rs
+test result: FAILED. 40 passed; 2 failed; 1 ignored; 0 measured; 0 filtered out; finished in 1.02s
+     Running tests/cli.rs
+test result: ok. 3 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.20s
+";
+        let results = parse(output).unwrap();
+        assert_eq!(results.framework, "cargo");
+        assert_eq!(
+            (results.passed, results.failed, results.skipped),
+            (43, 2, 1)
+        );
+        assert!(results.has_failures());
+        assert_eq!(results.describe(), "43 passed, 2 failed, 1 skipped");
+    }
+
+    #[test]
+    fn test_pytest_and_jest() {
+        let pytest = "tests/test_api.py ..F.\n===== 1 failed, 12 passed, 2 skipped, 1 error in 3.41s =====\n";
+        let results = parse(pytest).unwrap();
+        assert_eq!(
+            (results.passed, results.failed, results.skipped),
+            (12, 2, 2)
+        );
+
+        let jest = "Test Suites: 1 failed, 4 passed, 5 total\nTests:       1 failed, 3 todo, 30 passed, 34 total\nTime: 2.1 s";
+        let results = parse(jest).unwrap();
+        assert_eq!(results.framework, "jest");
+        assert_eq!(
+            (results.passed, results.failed, results.skipped),
+            (30, 1, 3)
+        );
+
+        // The run printed last wins
+        let results = parse(&format!(
+            "{}\n========== 5 passed in 0.12s ==========",
+            jest
+        ))
+        .unwrap();
+        assert_eq!(results.framework, "pytest");
+        assert!(!results.has_failures());
+        assert!(parse("Fixed 3 failed tests in the parser").is_none());
+    }
+}
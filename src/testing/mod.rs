@@ -0,0 +1,394 @@
+//! Test doubles for exercising the monitor and notification routing without
+//! real processes, real channels, real users or real sleeps
+//!
+//! Enabled in this crate's own tests and, for downstream crates, with the
+//! `test-util` feature:
+//!
+//! ```
+//! use agent_inbox::monitor::MonitorBuilder;
+//! use agent_inbox::notify::Notifier;
+//! use agent_inbox::testing::{FakeClock, FakeProcess, MockChannel};
+//! use agent_inbox::{Database, Task};
+//! use std::time::Duration;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let dir = tempfile::tempdir()?;
+//! let db = Database::open(dir.path().join("tasks.db"))?;
+//! let clock = FakeClock::default();
+//! let mut task = Task::new("t1".into(), "tool".into(), "Build".into(), Some(1), None);
+//! task.created_at = clock.now_utc();
+//! db.insert_task(&task)?;
+//!
+//! // Busy for a minute, silent for 15 minutes, then exits
+//! let process = FakeProcess::new().busy(12).idle(180);
+//! let channel = MockChannel::new();
+//! MonitorBuilder::new(db)
+//!     .probe(Box::new(process))
+//!     .clock(Box::new(clock.clone()))
+//!     .notifier(Notifier::builder().channel(Box::new(channel.clone())).build())
+//!     .build()
+//!     .monitor_task("t1".into(), 1, Some(dir.path().join("none.log")))?;
+//!
+//! assert_eq!(channel.sent().len(), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::monitor::access::Access;
+use crate::monitor::probe::{Clock, ProcessProbe};
+use crate::monitor::respond::InputSink;
+use crate::notify::{Channel, Notification};
+use crate::presence::{DesktopState, Presence, PresenceProvider};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Channel that records every notification it is asked to send.
+///
+/// Clones share the same record, so keep one clone and hand the other to the
+/// [`Notifier`](crate::notify::Notifier).
+#[derive(Clone)]
+pub struct MockChannel {
+    name: String,
+    sent: Arc<Mutex<Vec<Notification>>>,
+    fail: bool,
+    delay: Duration,
+}
+
+impl MockChannel {
+    pub fn new() -> Self {
+        Self::named("mock")
+    }
+
+    pub fn named(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            sent: Arc::new(Mutex::new(Vec::new())),
+            fail: false,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Record notifications but report every send as failed
+    pub fn failing(mut self) -> Self {
+        self.fail = true;
+        self
+    }
+
+    /// Take this long over every send, like a hanging API call. The
+    /// notification is recorded once the delay is over.
+    pub fn slow(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Notifications dispatched so far, oldest first
+    pub fn sent(&self) -> Vec<Notification> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.sent.lock().unwrap().clear();
+    }
+}
+
+impl Default for MockChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Channel for MockChannel {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&self, notification: &Notification) -> Result<()> {
+        if !self.delay.is_zero() {
+            std::thread::sleep(self.delay);
+        }
+        self.sent.lock().unwrap().push(notification.clone());
+        if self.fail {
+            anyhow::bail!("{} is configured to fail", self.name);
+        }
+        Ok(())
+    }
+}
+
+/// Input that records the lines auto-responses would have typed.
+///
+/// Clones share the same record.
+#[derive(Clone, Default)]
+pub struct MockInput {
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().clone()
+    }
+}
+
+impl InputSink for MockInput {
+    fn send_line(&self, text: &str) -> Result<()> {
+        self.lines.lock().unwrap().push(text.to_string());
+        Ok(())
+    }
+}
+
+/// Presence that reports whatever the test sets, both for a task's terminal
+/// ([`Presence`]) and for the desktop session ([`PresenceProvider`]).
+///
+/// Clones share the same state.
+#[derive(Clone, Default)]
+pub struct FakePresence {
+    watching: Arc<AtomicBool>,
+    desktop: Arc<Mutex<Option<DesktopState>>>,
+}
+
+impl FakePresence {
+    pub fn new(watching: bool) -> Self {
+        Self {
+            watching: Arc::new(AtomicBool::new(watching)),
+            ..Self::default()
+        }
+    }
+
+    pub fn set_watching(&self, watching: bool) {
+        self.watching.store(watching, Ordering::SeqCst);
+    }
+
+    /// Desktop state to report; None means "cannot tell"
+    pub fn set_desktop(&self, state: Option<DesktopState>) {
+        *self.desktop.lock().unwrap() = state;
+    }
+}
+
+impl Presence for FakePresence {
+    fn watching(&self, _pid: i32) -> bool {
+        self.watching.load(Ordering::SeqCst)
+    }
+}
+
+impl PresenceProvider for FakePresence {
+    fn name(&self) -> &str {
+        "fake"
+    }
+
+    fn state(&self) -> Option<DesktopState> {
+        *self.desktop.lock().unwrap()
+    }
+}
+
+/// Clock that only moves when told to; `sleep` advances it instantly.
+///
+/// Clones share the same time.
+#[derive(Clone)]
+pub struct FakeClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl FakeClock {
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+
+    pub fn now_utc(&self) -> DateTime<Utc> {
+        self.now().into()
+    }
+}
+
+impl Default for FakeClock {
+    /// Starts at the real current time so timestamps look plausible
+    fn default() -> Self {
+        Self::new(SystemTime::now())
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// One poll of a [`FakeProcess`] script
+#[derive(Debug, Clone, Default)]
+struct Step {
+    cpu_ticks: u64,
+    output: Vec<String>,
+    /// The user types into the process's terminal
+    typed: bool,
+    /// The terminal closes
+    hang_up: bool,
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    script: VecDeque<Step>,
+    current: Option<Step>,
+    cpu_time: u64,
+    polls: usize,
+    terminated: bool,
+    keystrokes: u64,
+    hung_up: bool,
+    /// What /proc shows of the process; everything when None
+    access: Option<Access>,
+}
+
+/// Scripted process for the monitor loop.
+///
+/// Every liveness check advances the script by one step; each step burns
+/// some CPU and may print lines. The process exits once the script runs out.
+/// Clones share the same script, so a test can keep a handle for assertions.
+#[derive(Clone, Default)]
+pub struct FakeProcess {
+    state: Arc<Mutex<FakeState>>,
+}
+
+impl FakeProcess {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a step that burns `cpu_ticks` and prints `output`
+    pub fn step(self, cpu_ticks: u64, output: &[&str]) -> Self {
+        self.state.lock().unwrap().script.push_back(Step {
+            cpu_ticks,
+            output: output.iter().map(|l| l.to_string()).collect(),
+            ..Default::default()
+        });
+        self
+    }
+
+    /// A step in which the user types into the process's terminal, without
+    /// CPU usage or output
+    pub fn typing(self) -> Self {
+        self.state.lock().unwrap().script.push_back(Step {
+            typed: true,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// A step in which the process's terminal closes, as when the ssh
+    /// session drops, while the process lives on
+    pub fn hang_up(self) -> Self {
+        self.state.lock().unwrap().script.push_back(Step {
+            hang_up: true,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// A process /proc only partly shows, as in a container: without `stat`
+    /// its CPU time is unknown, without `fds` its terminal and keystrokes
+    pub fn restricted(self, access: Access) -> Self {
+        self.state.lock().unwrap().access = Some(access);
+        self
+    }
+
+    /// `polls` steps that each burn CPU without printing
+    pub fn busy(self, polls: usize) -> Self {
+        (0..polls).fold(self, |p, _| p.step(1, &[]))
+    }
+
+    /// `polls` steps without CPU usage or output
+    pub fn idle(self, polls: usize) -> Self {
+        (0..polls).fold(self, |p, _| p.step(0, &[]))
+    }
+
+    /// A single busy step that prints `lines`
+    pub fn print(self, lines: &[&str]) -> Self {
+        self.step(1, lines)
+    }
+
+    /// Number of liveness checks made so far
+    pub fn polls(&self) -> usize {
+        self.state.lock().unwrap().polls
+    }
+
+    /// Whether the monitor terminated the process
+    pub fn terminated(&self) -> bool {
+        self.state.lock().unwrap().terminated
+    }
+}
+
+impl ProcessProbe for FakeProcess {
+    fn is_alive(&self, _pid: i32) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.polls += 1;
+        state.current = state.script.pop_front();
+        let Some((cpu_ticks, typed, hang_up)) = state
+            .current
+            .as_ref()
+            .map(|step| (step.cpu_ticks, step.typed, step.hang_up))
+        else {
+            return false;
+        };
+        state.cpu_time += cpu_ticks;
+        state.keystrokes += typed as u64;
+        state.hung_up |= hang_up;
+        true
+    }
+
+    fn cpu_time(&self, _pid: i32) -> Option<u64> {
+        let state = self.state.lock().unwrap();
+        state
+            .current
+            .as_ref()
+            .filter(|_| state.access.is_none_or(|a| a.stat))
+            .map(|_| state.cpu_time)
+    }
+
+    fn take_output(&self, _pid: i32) -> Vec<String> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .current
+            .as_mut()
+            .map(|step| std::mem::take(&mut step.output))
+            .unwrap_or_default()
+    }
+
+    fn input_mark(&self, _pid: i32) -> Option<SystemTime> {
+        let state = self.state.lock().unwrap();
+        state
+            .access
+            .is_none_or(|a| a.fds)
+            .then(|| SystemTime::UNIX_EPOCH + Duration::from_secs(state.keystrokes))
+    }
+
+    /// `/dev/pts/0` until the script hangs up
+    fn terminal(&self, _pid: i32) -> Option<std::path::PathBuf> {
+        let state = self.state.lock().unwrap();
+        (!state.hung_up && state.access.is_none_or(|a| a.fds)).then(|| "/dev/pts/0".into())
+    }
+
+    fn access(&self, _pid: i32) -> Access {
+        self.state.lock().unwrap().access.unwrap_or(Access::FULL)
+    }
+
+    /// Ends the script: the process is gone at the next check
+    fn terminate(&self, _pid: i32) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.script.clear();
+        state.terminated = true;
+        Ok(())
+    }
+}
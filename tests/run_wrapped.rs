@@ -0,0 +1,28 @@
+//! `agent-inbox -- <command>` end to end, in a home of its own
+
+use std::process::Command;
+
+#[cfg(unix)]
+#[test]
+fn test_wrapped_command_exit_code() {
+    let home = tempfile::tempdir().unwrap();
+    let config = home.path().join(".config/agent-inbox");
+    std::fs::create_dir_all(&config).unwrap();
+    std::fs::write(config.join("config.toml"), "[notify]\nchannels = []\n").unwrap();
+    let wrapped = |script: &str| {
+        Command::new(env!("CARGO_BIN_EXE_agent-inbox"))
+            .env("HOME", home.path())
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_DATA_HOME")
+            .env_remove("XDG_STATE_HOME")
+            .args(["--", "sh", "-c", script])
+            .output()
+            .unwrap()
+            .status
+            .code()
+    };
+
+    assert_eq!(wrapped("exit 0"), Some(0));
+    assert_eq!(wrapped("exit 7"), Some(7));
+    assert_eq!(wrapped("kill -TERM $$"), Some(128 + 15));
+}